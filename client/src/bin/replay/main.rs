@@ -0,0 +1,256 @@
+//! Plays back a replay recorded by the server with `/record`, printing what
+//! happened from the viewpoint of the recorded player.
+use common::{
+    comp::{self, ChatType, Health, Player, Pos, Stats},
+    resources::PlayerEntity,
+    uid::Uid,
+};
+use common_net::{
+    msg::{PlayerListUpdate, ServerGeneral},
+    replay::ReplayReader,
+    sync::WorldSyncExt,
+};
+use common_state::State;
+use hashbrown::HashMap;
+use specs::{Entity as EcsEntity, Join, WorldExt};
+use std::{fs::File, io::BufReader, path::PathBuf};
+use structopt::StructOpt;
+use tracing::{error, info, warn};
+
+#[derive(StructOpt)]
+struct Opt {
+    /// Replay file written by the server
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+    /// Seconds into the replay to start printing at
+    #[structopt(long, default_value = "0")]
+    from: f64,
+    /// Seconds into the replay to stop at
+    #[structopt(long)]
+    to: Option<f64>,
+    /// Seconds between the summaries of the players in view
+    #[structopt(long, default_value = "10")]
+    interval: f64,
+    /// Print every changed block, instead of a summary of each update
+    #[structopt(long)]
+    blocks: bool,
+}
+
+struct Replay {
+    opt: Opt,
+    state: State,
+    player_uid: Option<Uid>,
+    aliases: HashMap<Uid, String>,
+    time: f64,
+    next_summary: f64,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    common_frontend::init_stdout(None);
+
+    let mut reader = match File::open(&opt.file)
+        .map_err(Into::into)
+        .and_then(|file| ReplayReader::new(BufReader::new(file)))
+    {
+        Ok(reader) => reader,
+        Err(err) => {
+            error!("Failed to open {}: {}", opt.file.display(), err);
+            return;
+        },
+    };
+    let header = reader.header();
+    info!(
+        "Replay of {} recorded at unix time {} by server version {}",
+        header.player_alias, header.start_time, header.server_version
+    );
+    if header.server_version != *common::util::GIT_HASH {
+        warn!(
+            "This client is version {}, the replay may not be readable",
+            *common::util::GIT_HASH
+        );
+    }
+
+    let mut replay = Replay {
+        next_summary: opt.from,
+        opt,
+        state: State::client(),
+        player_uid: None,
+        aliases: HashMap::new(),
+        time: 0.0,
+    };
+    loop {
+        match reader.next_frame() {
+            Ok(Some((time, msg))) => {
+                if replay.opt.to.map_or(false, |to| time > to) {
+                    break;
+                }
+                replay.time = time;
+                replay.handle(msg);
+                if time >= replay.next_summary {
+                    replay.summary();
+                    replay.next_summary = time + replay.opt.interval;
+                }
+            },
+            Ok(None) => break,
+            // E.g. when the server stopped in the middle of a frame
+            Err(err) => {
+                error!("Failed to read the replay: {}", err);
+                break;
+            },
+        }
+    }
+    replay.summary();
+    info!("End of the replay at {:.1}s", replay.time);
+}
+
+impl Replay {
+    fn print(&self, event: String) {
+        if self.time >= self.opt.from {
+            println!("[{:>8.1}s] {}", self.time, event);
+        }
+    }
+
+    fn name(&self, uid: Uid) -> String {
+        let ecs = self.state.ecs();
+        self.aliases.get(&uid).cloned().unwrap_or_else(|| {
+            ecs.entity_from_uid(uid.0)
+                .and_then(|entity| {
+                    ecs.read_storage::<Stats>()
+                        .get(entity)
+                        .map(|stats| stats.name.clone())
+                })
+                .unwrap_or_else(|| format!("<entity {}>", uid))
+        })
+    }
+
+    fn describe(&self, entity: EcsEntity) -> Option<String> {
+        let ecs = self.state.ecs();
+        let player = ecs.read_storage::<Player>().get(entity)?.alias.clone();
+        let pos = ecs.read_storage::<Pos>().get(entity).map_or_else(
+            || "unknown".to_owned(),
+            |pos| format!("({:.1}, {:.1}, {:.1})", pos.0.x, pos.0.y, pos.0.z),
+        );
+        let health = ecs.read_storage::<Health>().get(entity).map_or_else(
+            || "unknown".to_owned(),
+            |health| format!("{:.0}/{:.0}", health.current(), health.maximum()),
+        );
+        Some(format!("{} at {} with health {}", player, pos, health))
+    }
+
+    /// Prints the players in view
+    fn summary(&self) {
+        let ecs = self.state.ecs();
+        for (entity, _) in (&ecs.entities(), &ecs.read_storage::<Player>()).join() {
+            if let Some(description) = self.describe(entity) {
+                self.print(format!("status: {}", description));
+            }
+        }
+    }
+
+    fn handle(&mut self, msg: ServerGeneral) {
+        match msg {
+            ServerGeneral::SetPlayerEntity(uid) => {
+                self.player_uid = Some(uid);
+                let entity = self.state.ecs().entity_from_uid(uid.0);
+                *self.state.ecs_mut().write_resource() = PlayerEntity(entity);
+            },
+            ServerGeneral::PlayerListUpdate(update) => match update {
+                PlayerListUpdate::Init(players) => {
+                    self.aliases = players
+                        .into_iter()
+                        .map(|(uid, info)| (uid, info.player_alias))
+                        .collect();
+                },
+                PlayerListUpdate::Add(uid, info) => {
+                    self.print(format!("{} joined the server", info.player_alias));
+                    self.aliases.insert(uid, info.player_alias);
+                },
+                PlayerListUpdate::Remove(uid) => {
+                    self.print(format!("{} left the server", self.name(uid)));
+                },
+                PlayerListUpdate::Alias(uid, alias) => {
+                    self.aliases.insert(uid, alias);
+                },
+                _ => {},
+            },
+            ServerGeneral::ChatMsg(msg) => self.print(self.chat(&msg)),
+            ServerGeneral::EntitySync(package) => {
+                self.state.ecs_mut().apply_entity_sync_package(package);
+            },
+            ServerGeneral::CompSync(package) => {
+                self.state.ecs_mut().apply_comp_sync_package(package);
+            },
+            ServerGeneral::CreateEntity(package) => {
+                let known = self.state.ecs().entity_from_uid(package.uid).is_some();
+                let entity = self.state.ecs_mut().apply_entity_package(package);
+                if !known {
+                    if let Some(description) = self.describe(entity) {
+                        self.print(format!("in view: {}", description));
+                    }
+                }
+            },
+            ServerGeneral::DeleteEntity(uid) => {
+                if self.player_uid != Some(uid) {
+                    if let Some(description) = self
+                        .state
+                        .ecs()
+                        .entity_from_uid(uid.0)
+                        .and_then(|entity| self.describe(entity))
+                    {
+                        self.print(format!("out of view: {}", description));
+                    }
+                    self.state
+                        .ecs_mut()
+                        .delete_entity_and_clear_from_uid_allocator(uid.0);
+                }
+            },
+            ServerGeneral::TerrainBlockUpdates(blocks) => {
+                if let Some(blocks) = blocks.decompress() {
+                    if self.opt.blocks {
+                        for (pos, block) in blocks.iter() {
+                            self.print(format!("block at {:?} set to {:?}", pos, block));
+                        }
+                    } else if let Some(pos) = blocks.keys().next() {
+                        self.print(format!("{} blocks changed near {:?}", blocks.len(), pos));
+                    }
+                }
+            },
+            ServerGeneral::InventoryUpdate(_, event) => {
+                self.print(format!("inventory: {:?}", event));
+            },
+            ServerGeneral::Notification(notification) => {
+                self.print(format!("notification: {:?}", notification));
+            },
+            ServerGeneral::Disconnect(reason) => {
+                self.print(format!("disconnected: {:?}", reason));
+            },
+            _ => {},
+        }
+    }
+
+    fn chat(&self, msg: &comp::ChatMsg) -> String {
+        let name = |uid: &Uid| self.name(*uid);
+        match &msg.chat_type {
+            ChatType::Online(uid) => format!("{} is online", name(uid)),
+            ChatType::Offline(uid) => format!("{} is offline", name(uid)),
+            ChatType::Kill(source, victim) => {
+                format!("{} was killed by {:?}", name(victim), source)
+            },
+            ChatType::Tell(from, to) => {
+                format!("{} tells {}: {}", name(from), name(to), msg.message)
+            },
+            ChatType::Say(uid) => format!("{} says: {}", name(uid), msg.message),
+            ChatType::Group(uid, _) => format!("[group] {}: {}", name(uid), msg.message),
+            ChatType::Faction(uid, faction) => {
+                format!("[{}] {}: {}", faction, name(uid), msg.message)
+            },
+            ChatType::Region(uid) => format!("[region] {}: {}", name(uid), msg.message),
+            ChatType::World(uid) => format!("[world] {}: {}", name(uid), msg.message),
+            ChatType::Npc(uid, _) | ChatType::NpcSay(uid, _) | ChatType::NpcTell(uid, _, _) => {
+                format!("{} says: {}", name(uid), msg.message)
+            },
+            _ => format!("chat: {}", msg.message),
+        }
+    }
+}
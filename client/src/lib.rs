@@ -32,6 +32,7 @@ use common::{
         GroupManip, InputKind, InventoryAction, InventoryEvent, InventoryUpdateEvent,
         UtteranceKind,
     },
+    consts::MAX_PICKUP_RANGE,
     event::{EventBus, LocalEvent},
     grid::Grid,
     outcome::Outcome,
@@ -46,6 +47,8 @@ use common::{
     vol::RectVolSize,
 };
 use common_base::{prof_span, span};
+#[cfg(feature = "plugins")]
+use common_net::msg::PluginMsg;
 use common_net::{
     msg::{
         self, validate_chat_msg,
@@ -53,7 +56,7 @@ use common_net::{
         ChatMsgValidationError, ClientGeneral, ClientMsg, ClientRegister, ClientType,
         DisconnectReason, InviteAnswer, Notification, PingMsg, PlayerInfo, PlayerListUpdate,
         PresenceKind, RegisterError, ServerGeneral, ServerInit, ServerRegisterAnswer,
-        MAX_BYTES_CHAT_MSG,
+        MAX_BYTES_CHAT_MSG, SESSION_RESUME_GRACE_PERIOD,
     },
     sync::WorldSyncExt,
 };
@@ -179,6 +182,8 @@ pub struct Client {
     pending_invites: HashSet<Uid>,
     // The pending trade the client is involved in, and it's id
     pending_trade: Option<(TradeId, PendingTrade, Option<SitePrices>)>,
+    // The storage container the client has open
+    storage: Option<comp::StorageView>,
 
     network: Option<Network>,
     participant: Option<Participant>,
@@ -188,6 +193,7 @@ pub struct Client {
     character_screen_stream: Stream,
     in_game_stream: Stream,
     terrain_stream: Stream,
+    plugin_stream: Stream,
 
     client_timeout: Duration,
     last_server_ping: f64,
@@ -222,6 +228,7 @@ impl Client {
         mismatched_server_info: &mut Option<ServerInfo>,
     ) -> Result<Self, Error> {
         let network = Network::new(Pid::new(), &runtime);
+        network.set_resume_grace_period(SESSION_RESUME_GRACE_PERIOD);
 
         let participant = match addr {
             ConnectionArgs::Tcp {
@@ -251,6 +258,8 @@ impl Client {
         let character_screen_stream = participant.opened().await?;
         let in_game_stream = participant.opened().await?;
         let terrain_stream = participant.opened().await?;
+        #[cfg_attr(not(feature = "plugins"), allow(unused_mut))]
+        let mut plugin_stream = participant.opened().await?;
 
         register_stream.send(ClientType::Game)?;
         let server_info: ServerInfo = register_stream.recv().await?;
@@ -299,6 +308,7 @@ impl Client {
                 recipe_book,
                 material_stats,
                 ability_map,
+                plugins,
             } => {
                 // Initialize `State`
                 let mut state = State::client();
@@ -311,6 +321,45 @@ impl Client {
                 state.ecs_mut().insert(material_stats);
                 state.ecs_mut().insert(ability_map);
 
+                #[cfg(feature = "plugins")]
+                {
+                    let missing = state
+                        .ecs()
+                        .read_resource::<common_state::plugin::PluginMgr>()
+                        .missing_plugins(&plugins);
+                    if !missing.is_empty() {
+                        debug!("Downloading {} plugin(s) from the server", missing.len());
+                        plugin_stream.send(PluginMsg::RequestPlugins(missing.clone()))?;
+                        let msg = loop {
+                            tokio::select! {
+                                res = plugin_stream.recv() => break res?,
+                                _ = ping_interval.tick() => ping_stream.send(PingMsg::Ping)?,
+                            }
+                        };
+                        match msg {
+                            PluginMsg::Plugins(archives) => {
+                                state.load_server_plugins(&missing, archives).map_err(|e| {
+                                    Error::Other(format!(
+                                        "Failed to load the server plugins: {:?}",
+                                        e
+                                    ))
+                                })?
+                            },
+                            PluginMsg::RequestPlugins(_) => {
+                                return Err(Error::Other(
+                                    "Server sent an unexpected plugin message".into(),
+                                ));
+                            },
+                        }
+                    }
+                }
+                #[cfg(not(feature = "plugins"))]
+                if !plugins.is_empty() {
+                    warn!(
+                        "The server uses plugins, but this client was built without plugin support"
+                    );
+                }
+
                 let map_size_lg = common::terrain::MapSizeLg::new(world_map.dimensions_lg)
                     .map_err(|_| {
                         Error::Other(format!(
@@ -627,6 +676,7 @@ impl Client {
             group_members: HashMap::new(),
             pending_invites: HashSet::new(),
             pending_trade: None,
+            storage: None,
 
             network: Some(network),
             participant: Some(participant),
@@ -636,6 +686,7 @@ impl Client {
             character_screen_stream,
             in_game_stream,
             terrain_stream,
+            plugin_stream,
 
             client_timeout,
 
@@ -785,6 +836,7 @@ impl Client {
                 stream.send(msg)
             },
             ClientMsg::Ping(msg) => self.ping_stream.send(msg),
+            ClientMsg::Plugin(msg) => self.plugin_stream.send(msg),
         }
     }
 
@@ -1109,6 +1161,43 @@ impl Client {
         &self.pending_trade
     }
 
+    /// The storage container the client has open
+    pub fn storage(&self) -> Option<&comp::StorageView> { self.storage.as_ref() }
+
+    pub fn storage_swap_slots(&mut self, a: comp::StorageSlot, b: comp::StorageSlot) {
+        self.send_msg(ClientGeneral::ControlEvent(ControlEvent::InventoryEvent(
+            InventoryEvent::StorageSwap(a, b),
+        )));
+    }
+
+    pub fn storage_split_swap_slots(&mut self, a: comp::StorageSlot, b: comp::StorageSlot) {
+        self.send_msg(ClientGeneral::ControlEvent(ControlEvent::InventoryEvent(
+            InventoryEvent::StorageSplitSwap(a, b),
+        )));
+    }
+
+    pub fn set_storage_access(&mut self, access: comp::StorageAccess) {
+        self.send_msg(ClientGeneral::ControlEvent(ControlEvent::InventoryEvent(
+            InventoryEvent::SetStorageAccess(access),
+        )));
+    }
+
+    /// Picks up the open storage container, which must be empty and owned by
+    /// the client
+    pub fn pick_up_storage(&mut self) {
+        self.send_msg(ClientGeneral::ControlEvent(ControlEvent::InventoryEvent(
+            InventoryEvent::PickUpStorage,
+        )));
+    }
+
+    pub fn close_storage(&mut self) {
+        if self.storage.take().is_some() {
+            self.send_msg(ClientGeneral::ControlEvent(ControlEvent::InventoryEvent(
+                InventoryEvent::CloseStorage,
+            )));
+        }
+    }
+
     pub fn send_invite(&mut self, invitee: Uid, kind: InviteKind) {
         self.send_msg(ClientGeneral::ControlEvent(ControlEvent::InitiateInvite(
             invitee, kind,
@@ -1403,6 +1492,10 @@ impl Client {
         )));
     }
 
+    /// Opens the storage container at `pos`, which, like collecting, first
+    /// goes through the sprite interaction
+    pub fn open_storage(&mut self, pos: Vec3<i32>) { self.collect_block(pos); }
+
     pub fn change_ability(&mut self, slot: usize, new_ability: comp::ability::AuxiliaryAbility) {
         self.send_msg(ClientGeneral::ControlEvent(ControlEvent::ChangeAbility {
             slot,
@@ -1508,6 +1601,17 @@ impl Client {
             self.invite = None;
         }
 
+        // Close the open storage container once out of reach of it
+        if let Some(storage_pos) = self.storage.as_ref().map(|storage| storage.pos) {
+            let in_reach = self.position().map_or(false, |pos| {
+                pos.distance_squared(storage_pos.map(|e| e as f32 + 0.5))
+                    < (MAX_PICKUP_RANGE + 1.0).powi(2)
+            });
+            if !in_reach {
+                self.close_storage();
+            }
+        }
+
         // Lerp towards the target time of day - this ensures a smooth transition for
         // large jumps in TimeOfDay such as when using /time
         if let Some(target_tod) = self.target_time_of_day {
@@ -1532,6 +1636,8 @@ impl Client {
             .ecs()
             .fetch::<EventBus<common::event::ServerEvent>>()
             .recv_all();
+        #[cfg(feature = "plugins")]
+        reject_plugin_actions(self.state.ecs());
 
         // 5) Terrain
         self.tick_terrain()?;
@@ -1943,6 +2049,7 @@ impl Client {
             // Cleanup for when the client goes back to the `presence = None`
             ServerGeneral::ExitInGameSuccess => {
                 self.presence = None;
+                self.storage = None;
                 self.clean_state();
             },
             ServerGeneral::InventoryUpdate(inventory, event) => {
@@ -2005,6 +2112,12 @@ impl Client {
                     rich.economy = Some(economy);
                 }
             },
+            ServerGeneral::StorageUpdate(storage) => {
+                self.storage = Some(storage);
+            },
+            ServerGeneral::StorageClosed => {
+                self.storage = None;
+            },
             _ => unreachable!("Not a in_game message"),
         }
         Ok(())
@@ -2570,6 +2683,20 @@ impl Drop for Client {
     }
 }
 
+/// Plugins can only change the world on the server, so the actions queued by
+/// client plugins are dropped. Returns how many were dropped.
+#[cfg(feature = "plugins")]
+fn reject_plugin_actions(ecs: &World) -> usize {
+    let actions = ecs.fetch::<EventBus<plugin_api::Action>>().recv_all();
+    if !actions.is_empty() {
+        warn!(
+            ?actions,
+            "Ignoring actions of client plugins, only server plugins can apply them"
+        );
+    }
+    actions.len()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2641,4 +2768,15 @@ mod tests {
             clock.tick();
         });
     }
+
+    #[test]
+    #[cfg(feature = "plugins")]
+    fn plugin_actions_are_rejected() {
+        let mut ecs = World::new();
+        ecs.insert(EventBus::<plugin_api::Action>::default());
+        ecs.fetch::<EventBus<plugin_api::Action>>()
+            .emit_now(plugin_api::Action::KillEntity(Uid(1)));
+        assert_eq!(reject_plugin_actions(&ecs), 1);
+        assert_eq!(reject_plugin_actions(&ecs), 0);
+    }
 }
@@ -1,4 +1,5 @@
 #![allow(incomplete_features)]
 #![feature(generic_const_exprs, const_fn_floating_point_arithmetic)]
 pub mod msg;
+pub mod replay;
 pub mod sync;
@@ -21,6 +21,7 @@ pub use self::{
 };
 use common::character::CharacterId;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum PresenceKind {
@@ -34,8 +35,27 @@ pub enum PingMsg {
     Pong,
 }
 
+/// SHA-256 hash of a plugin archive, used to identify the plugins a server
+/// distributes to its clients
+pub type PluginHash = [u8; 32];
+
+/// Messages of the plugin stream, used by clients to download the plugins
+/// advertised in `ServerInit::GameSync`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PluginMsg {
+    /// Sent by the client to request the archives with the given hashes
+    RequestPlugins(Vec<PluginHash>),
+    /// Sent by the server in response to `RequestPlugins`, archives of plugins
+    /// that are no longer loaded are left out
+    Plugins(Vec<Vec<u8>>),
+}
+
 pub const MAX_BYTES_CHAT_MSG: usize = 256;
 
+/// How long a client that lost its connection has to resume its session. The
+/// character stays in the world meanwhile.
+pub const SESSION_RESUME_GRACE_PERIOD: Duration = Duration::from_secs(20);
+
 pub enum ChatMsgValidationError {
     TooLong,
 }
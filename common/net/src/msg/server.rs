@@ -1,6 +1,6 @@
 use super::{
-    world_msg::EconomyInfo, ClientType, CompressedData, EcsCompPacket, PingMsg, QuadPngEncoding,
-    TriPngEncoding, WidePacking, WireChonk,
+    world_msg::EconomyInfo, ClientType, CompressedData, EcsCompPacket, PingMsg, PluginHash,
+    PluginMsg, QuadPngEncoding, TriPngEncoding, WidePacking, WireChonk,
 };
 use crate::sync;
 use common::{
@@ -33,6 +33,8 @@ pub enum ServerMsg {
     ///Msg that can be send ALWAYS as soon as client is registered, e.g. `Chat`
     General(ServerGeneral),
     Ping(PingMsg),
+    /// Plugin archives requested by the client
+    Plugin(PluginMsg),
 }
 
 /*
@@ -62,6 +64,9 @@ pub enum ServerInit {
         recipe_book: RecipeBook,
         material_stats: MaterialStatManifest,
         ability_map: comp::item::tool::AbilityMap,
+        /// Hashes of the plugins clients have to download from the plugin
+        /// stream and run
+        plugins: Vec<PluginHash>,
     },
 }
 
@@ -189,6 +194,12 @@ pub enum ServerGeneral {
     FinishedTrade(TradeResult),
     /// Economic information about sites
     SiteEconomy(EconomyInfo),
+    /// The contents of the storage container the client has open, sent when it
+    /// is opened and whenever it changes
+    StorageUpdate(comp::StorageView),
+    /// The storage container the client had open was closed, e.g. because
+    /// they lost access to it
+    StorageClosed,
 }
 
 impl ServerGeneral {
@@ -239,6 +250,8 @@ pub enum InviteAnswer {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Notification {
     WaypointSaved,
+    /// An equipped item wore down completely, contains the name of the item
+    ItemBroken(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -298,7 +311,9 @@ impl ServerMsg {
                         | ServerGeneral::Knockback(_)
                         | ServerGeneral::UpdatePendingTrade(_, _, _)
                         | ServerGeneral::FinishedTrade(_)
-                        | ServerGeneral::SiteEconomy(_) => {
+                        | ServerGeneral::SiteEconomy(_)
+                        | ServerGeneral::StorageUpdate(_)
+                        | ServerGeneral::StorageClosed => {
                             c_type == ClientType::Game && presence.is_some()
                         },
                         // Always possible
@@ -315,7 +330,7 @@ impl ServerMsg {
                         | ServerGeneral::Notification(_) => true,
                     }
             },
-            ServerMsg::Ping(_) => true,
+            ServerMsg::Ping(_) | ServerMsg::Plugin(_) => true,
         }
     }
 }
@@ -343,3 +358,7 @@ impl From<ServerGeneral> for ServerMsg {
 impl From<PingMsg> for ServerMsg {
     fn from(o: PingMsg) -> ServerMsg { ServerMsg::Ping(o) }
 }
+
+impl From<PluginMsg> for ServerMsg {
+    fn from(o: PluginMsg) -> ServerMsg { ServerMsg::Plugin(o) }
+}
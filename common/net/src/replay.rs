@@ -0,0 +1,254 @@
+//! Recordings of the messages sent by the server to a client, which can be
+//! played back to see a session from the viewpoint of that client.
+//!
+//! A replay starts with [`MAGIC`], the format version and a
+//! [`ReplayHeader`], followed by frames made of the time since the start of
+//! the recording in seconds (`f64`), the length of the message (`u32`) and
+//! the [`ServerGeneral`] message serialized with bincode, all little endian.
+
+use crate::msg::ServerGeneral;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+pub const MAGIC: &[u8; 8] = b"VELOREPL";
+pub const VERSION: u32 = 1;
+/// The largest message a replay may contain, so that reading a corrupt replay
+/// can't allocate arbitrary amounts of memory
+pub const MAX_MESSAGE_LEN: u32 = 64 * 1024 * 1024;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    /// Git hash of the server which recorded the replay, as messages may not
+    /// deserialize with another version
+    pub server_version: String,
+    /// Alias of the player the replay was recorded from
+    pub player_alias: String,
+    /// Unix timestamp of the start of the recording
+    pub start_time: i64,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Bincode(bincode::Error),
+    NotAReplay,
+    UnsupportedVersion(u32),
+    /// A message is longer than [`MAX_MESSAGE_LEN`]
+    MessageTooLong(u64),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Bincode(err) => write!(f, "Invalid message: {}", err),
+            Self::NotAReplay => write!(f, "Not a replay file"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Replay format version {} is not supported (expected {})",
+                version, VERSION
+            ),
+            Self::MessageTooLong(len) => write!(
+                f,
+                "Message of {} bytes is longer than the maximum of {} bytes",
+                len, MAX_MESSAGE_LEN
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self { Self::Io(err) }
+}
+
+impl From<bincode::Error> for ReplayError {
+    fn from(err: bincode::Error) -> Self { Self::Bincode(err) }
+}
+
+/// Serializes a message for [`ReplayWriter::write_encoded`], so that it can
+/// be encoded once for several recordings.
+pub fn encode(msg: &ServerGeneral) -> Result<Vec<u8>, ReplayError> { Ok(bincode::serialize(msg)?) }
+
+pub struct ReplayWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> ReplayWriter<W> {
+    pub fn new(mut writer: W, header: &ReplayHeader) -> Result<Self, ReplayError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, header)?;
+        Ok(Self { writer })
+    }
+
+    /// Appends a message serialized with [`encode`], received `time` seconds
+    /// after the start of the recording.
+    pub fn write_encoded(&mut self, time: f64, msg: &[u8]) -> Result<(), ReplayError> {
+        let len = msg.len() as u64;
+        if len > MAX_MESSAGE_LEN as u64 {
+            return Err(ReplayError::MessageTooLong(len));
+        }
+        self.writer.write_all(&time.to_le_bytes())?;
+        self.writer.write_all(&(len as u32).to_le_bytes())?;
+        self.writer.write_all(msg)?;
+        Ok(())
+    }
+
+    pub fn write(&mut self, time: f64, msg: &ServerGeneral) -> Result<(), ReplayError> {
+        self.write_encoded(time, &encode(msg)?)
+    }
+
+    pub fn flush(&mut self) -> Result<(), ReplayError> { Ok(self.writer.flush()?) }
+}
+
+pub struct ReplayReader<R: Read> {
+    reader: R,
+    header: ReplayHeader,
+}
+
+impl<R: Read> ReplayReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, ReplayError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(ReplayError::NotAReplay);
+        }
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let header = bincode::deserialize_from(&mut reader)?;
+        Ok(Self { reader, header })
+    }
+
+    pub fn header(&self) -> &ReplayHeader { &self.header }
+
+    /// The next message and the time it was received at, or `None` at the end
+    /// of the replay.
+    pub fn next_frame(&mut self) -> Result<Option<(f64, ServerGeneral)>, ReplayError> {
+        // The replay may only end between frames, not within the time of one
+        let mut time = [0; 8];
+        let mut read = 0;
+        while read < time.len() {
+            match self.reader.read(&mut time[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => read += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {},
+                Err(err) => return Err(err.into()),
+            }
+        }
+        let mut len = [0; 4];
+        self.reader.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len);
+        if len > MAX_MESSAGE_LEN {
+            return Err(ReplayError::MessageTooLong(len as u64));
+        }
+        // The buffer only grows as the message is read, in case the replay is
+        // truncated
+        let mut msg = Vec::new();
+        (&mut self.reader).take(len as u64).read_to_end(&mut msg)?;
+        if msg.len() != len as usize {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(Some((
+            f64::from_le_bytes(time),
+            bincode::deserialize(&msg)?,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> ReplayHeader {
+        ReplayHeader {
+            server_version: "abc".to_owned(),
+            player_alias: "player".to_owned(),
+            start_time: 1_600_000_000,
+        }
+    }
+
+    fn replay() -> Vec<u8> {
+        let mut writer = ReplayWriter::new(Vec::new(), &header()).unwrap();
+        writer
+            .write(0.5, &ServerGeneral::SetViewDistance(12))
+            .unwrap();
+        writer
+            .write_encoded(
+                1.25,
+                &encode(&ServerGeneral::CharacterDataLoadError("error".to_owned())).unwrap(),
+            )
+            .unwrap();
+        writer.flush().unwrap();
+        writer.writer
+    }
+
+    #[test]
+    fn roundtrip() {
+        let bytes = replay();
+        let mut reader = ReplayReader::new(&bytes[..]).unwrap();
+        assert_eq!(reader.header().player_alias, "player");
+        assert_eq!(reader.header().start_time, 1_600_000_000);
+        assert!(matches!(
+            reader.next_frame(),
+            Ok(Some((time, ServerGeneral::SetViewDistance(12)))) if time == 0.5
+        ));
+        assert!(matches!(
+            reader.next_frame(),
+            Ok(Some((time, ServerGeneral::CharacterDataLoadError(msg))))
+                if time == 1.25 && msg == "error"
+        ));
+        assert!(matches!(reader.next_frame(), Ok(None)));
+    }
+
+    #[test]
+    fn truncated_replays_are_rejected() {
+        let bytes = replay();
+        assert!(matches!(
+            ReplayReader::new(&bytes[..10]),
+            Err(ReplayError::Io(_))
+        ));
+        assert!(matches!(
+            ReplayReader::new(&b"NOTREPLAY0000"[..]),
+            Err(ReplayError::NotAReplay)
+        ));
+        // Cutting the replay anywhere within the last frame must be an error
+        let last_frame = bytes.len()
+            - 8
+            - 4
+            - encode(&ServerGeneral::CharacterDataLoadError("error".to_owned()))
+                .unwrap()
+                .len();
+        for len in last_frame + 1..bytes.len() {
+            let mut reader = ReplayReader::new(&bytes[..len]).unwrap();
+            assert!(matches!(reader.next_frame(), Ok(Some(_))));
+            assert!(reader.next_frame().is_err(), "cut at {}", len);
+        }
+    }
+
+    #[test]
+    fn oversized_messages_are_rejected() {
+        let mut writer = ReplayWriter::new(Vec::new(), &header()).unwrap();
+        assert!(matches!(
+            writer.write_encoded(0.0, &vec![0; MAX_MESSAGE_LEN as usize + 1]),
+            Err(ReplayError::MessageTooLong(len)) if len == MAX_MESSAGE_LEN as u64 + 1
+        ));
+
+        // A corrupt length is rejected before anything is allocated for it
+        let mut bytes = writer.writer;
+        bytes.extend_from_slice(&0.0f64.to_le_bytes());
+        bytes.extend_from_slice(&(MAX_MESSAGE_LEN + 1).to_le_bytes());
+        let mut reader = ReplayReader::new(&bytes[..]).unwrap();
+        assert!(matches!(
+            reader.next_frame(),
+            Err(ReplayError::MessageTooLong(len)) if len == MAX_MESSAGE_LEN as u64 + 1
+        ));
+    }
+}
@@ -54,10 +54,12 @@ pub enum ChatCommand {
     BuildAreaList,
     BuildAreaRemove,
     Campfire,
+    Claim,
     DebugColumn,
     DisconnectAllPlayers,
     DropAll,
     Dummy,
+    EditHistory,
     Explosion,
     Faction,
     GiveItem,
@@ -82,11 +84,16 @@ pub enum ChatCommand {
     MakeNpc,
     MakeSprite,
     Motd,
+    Mute,
     Object,
+    PermissionGroup,
     PermitBuild,
     Players,
+    Record,
     Region,
     RemoveLights,
+    RestoreTerrain,
+    RevertEdits,
     RevokeBuild,
     RevokeBuildAll,
     Safezone,
@@ -102,6 +109,7 @@ pub enum ChatCommand {
     Time,
     Tp,
     Unban,
+    Unmute,
     Version,
     Waypoint,
     Whitelist,
@@ -236,6 +244,12 @@ lazy_static! {
 
     static ref ROLES: Vec<String> = ["admin", "moderator"].iter().copied().map(Into::into).collect();
 
+    static ref MUTE_CHANNELS: Vec<String> = ["all", "tell", "say", "group", "faction", "region", "world"]
+        .iter()
+        .copied()
+        .map(Into::into)
+        .collect();
+
     /// List of item specifiers. Useful for tab completing
     pub static ref ITEM_SPECS: Vec<String> = {
         let mut items = try_all_item_defs()
@@ -369,6 +383,16 @@ impl ChatCommand {
                 Some(Admin),
             ),
             ChatCommand::Campfire => cmd(vec![], "Spawns a campfire", Some(Admin)),
+            ChatCommand::Claim => cmd(
+                vec![
+                    Any("list/abandon/transfer/trust/untrust", Optional),
+                    Any("username", Optional),
+                ],
+                "Claims the chunk you are standing in so only you and the players you trust can \
+                 build there, or lists, abandons or transfers your claims and manages who you \
+                 trust",
+                None,
+            ),
             ChatCommand::DebugColumn => cmd(
                 vec![Integer("x", 15000, Required), Integer("y", 15000, Required)],
                 "Prints some debug information about a column",
@@ -385,6 +409,17 @@ impl ChatCommand {
                 Some(Moderator),
             ),
             ChatCommand::Dummy => cmd(vec![], "Spawns a training dummy", Some(Admin)),
+            ChatCommand::EditHistory => cmd(
+                vec![
+                    Integer("x", 0, Required),
+                    Integer("y", 0, Required),
+                    Integer("z", 0, Required),
+                    Integer("radius", 0, Optional),
+                    Integer("limit", 10, Optional),
+                ],
+                "Shows who edited a block, or the blocks within a radius of it",
+                Some(Moderator),
+            ),
             ChatCommand::Explosion => cmd(
                 vec![Float("radius", 5.0, Required)],
                 "Explodes the ground around you",
@@ -513,22 +548,73 @@ impl ChatCommand {
                 Some(Admin),
             ),
             ChatCommand::Motd => cmd(vec![Message(Optional)], "View the server description", None),
+            ChatCommand::Mute => cmd(
+                vec![
+                    Any("username", Required),
+                    Enum("channel", MUTE_CHANNELS.clone(), Required),
+                    Boolean("overwrite", "true".to_string(), Optional),
+                    Any("mute duration", Optional),
+                    Message(Optional),
+                ],
+                "Mute a player in a chat channel or in all of them, for a given duration (if \
+                 provided).  Pass true for overwrite to alter an existing mute.",
+                Some(Moderator),
+            ),
             ChatCommand::Object => cmd(
                 vec![Enum("object", OBJECTS.clone(), Required)],
                 "Spawn an object",
                 Some(Admin),
             ),
+            ChatCommand::PermissionGroup => cmd(
+                vec![
+                    Any("add/remove/list", Required),
+                    Any("username", Required),
+                    Any("group", Optional),
+                    Any("duration", Optional),
+                ],
+                "Adds/removes a player to/from a permission group, for a given duration (if \
+                 provided), or lists their groups",
+                Some(Admin),
+            ),
             ChatCommand::PermitBuild => cmd(
                 vec![Any("area_name", Required)],
                 "Grants player a bounded box they can build in",
                 Some(Admin),
             ),
             ChatCommand::Players => cmd(vec![], "Lists players currently online", None),
+            ChatCommand::Record => cmd(
+                vec![Any("start/stop", Required), PlayerName(Optional)],
+                "Starts/stops recording a replay of what a player (or yourself) sees",
+                Some(Moderator),
+            ),
             ChatCommand::RemoveLights => cmd(
                 vec![Float("radius", 20.0, Optional)],
                 "Removes all lights spawned by players",
                 Some(Admin),
             ),
+            ChatCommand::RestoreTerrain => cmd(
+                vec![
+                    Any("snapshot", Required),
+                    Integer("xlo", 0, Required),
+                    Integer("xhi", 10, Required),
+                    Integer("ylo", 0, Required),
+                    Integer("yhi", 10, Required),
+                    Integer("zlo", 0, Required),
+                    Integer("zhi", 10, Required),
+                ],
+                "Restores the terrain within an area to a snapshot of terrain persistence",
+                Some(Admin),
+            ),
+            ChatCommand::RevertEdits => cmd(
+                vec![
+                    Any("player", Required),
+                    Float("radius", 32.0, Optional),
+                    Any("duration", Optional),
+                ],
+                "Reverts the block edits of a player within a radius around you and/or within a \
+                 duration (e.g. 2h) before now",
+                Some(Moderator),
+            ),
             ChatCommand::RevokeBuild => cmd(
                 vec![Any("area_name", Required)],
                 "Revokes build area permission for player",
@@ -622,6 +708,11 @@ impl ChatCommand {
                 "Remove the ban for the given username",
                 Some(Moderator),
             ),
+            ChatCommand::Unmute => cmd(
+                vec![Any("username", Required)],
+                "Remove the mute for the given username",
+                Some(Moderator),
+            ),
             ChatCommand::Version => cmd(vec![], "Prints server version", None),
             ChatCommand::Waypoint => cmd(
                 vec![],
@@ -658,10 +749,12 @@ impl ChatCommand {
             ChatCommand::BuildAreaList => "build_area_list",
             ChatCommand::BuildAreaRemove => "build_area_remove",
             ChatCommand::Campfire => "campfire",
+            ChatCommand::Claim => "claim",
             ChatCommand::DebugColumn => "debug_column",
             ChatCommand::DisconnectAllPlayers => "disconnect_all_players",
             ChatCommand::DropAll => "dropall",
             ChatCommand::Dummy => "dummy",
+            ChatCommand::EditHistory => "edit_history",
             ChatCommand::Explosion => "explosion",
             ChatCommand::Faction => "faction",
             ChatCommand::GiveItem => "give_item",
@@ -686,11 +779,16 @@ impl ChatCommand {
             ChatCommand::MakeNpc => "make_npc",
             ChatCommand::MakeSprite => "make_sprite",
             ChatCommand::Motd => "motd",
+            ChatCommand::Mute => "mute",
             ChatCommand::Object => "object",
+            ChatCommand::PermissionGroup => "permission_group",
             ChatCommand::PermitBuild => "permit_build",
             ChatCommand::Players => "players",
+            ChatCommand::Record => "record",
             ChatCommand::Region => "region",
             ChatCommand::RemoveLights => "remove_lights",
+            ChatCommand::RestoreTerrain => "restore_terrain",
+            ChatCommand::RevertEdits => "revert_edits",
             ChatCommand::RevokeBuild => "revoke_build",
             ChatCommand::RevokeBuildAll => "revoke_build_all",
             ChatCommand::Safezone => "safezone",
@@ -706,6 +804,7 @@ impl ChatCommand {
             ChatCommand::Time => "time",
             ChatCommand::Tp => "tp",
             ChatCommand::Unban => "unban",
+            ChatCommand::Unmute => "unmute",
             ChatCommand::Version => "version",
             ChatCommand::Waypoint => "waypoint",
             ChatCommand::Wiring => "wiring",
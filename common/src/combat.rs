@@ -58,6 +58,7 @@ pub struct AttackerInfo<'a> {
     pub energy: Option<&'a Energy>,
     pub combo: Option<&'a Combo>,
     pub inventory: Option<&'a Inventory>,
+    pub player: Option<&'a Player>,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -70,6 +71,7 @@ pub struct TargetInfo<'a> {
     pub pos: Vec3<f32>,
     pub ori: Option<&'a Ori>,
     pub char_state: Option<&'a CharacterState>,
+    pub player: Option<&'a Player>,
 }
 
 #[derive(Clone, Copy)]
@@ -79,6 +81,36 @@ pub struct AttackOptions {
     pub target_group: GroupTarget,
 }
 
+/// Durability lost by the held weapons of an attacker each time it hits
+pub const WEAPON_WEAR_ON_HIT: u32 = 1;
+/// Durability lost by each piece of armor of a target each time it is damaged
+pub const ARMOR_WEAR_ON_HIT: u32 = 1;
+/// Durability lost by the held weapons of a target each time it blocks an
+/// attack
+pub const WEAPON_WEAR_ON_BLOCK: u32 = 2;
+
+/// Which equipped items wear down
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WearKind {
+    /// The held weapons
+    Weapons,
+    Armor,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl WearKind {
+    pub fn wears(self, equip_slot: EquipSlot) -> bool {
+        match self {
+            WearKind::Weapons => matches!(
+                equip_slot,
+                EquipSlot::ActiveMainhand | EquipSlot::ActiveOffhand
+            ),
+            WearKind::Armor => matches!(equip_slot, EquipSlot::Armor(_)),
+        }
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 #[derive(Clone, Debug, Serialize, Deserialize)] // TODO: Yeet clone derive
 pub struct Attack {
@@ -158,6 +190,13 @@ impl Attack {
                             entity: target.entity,
                             energy_cost: data.static_data.energy_cost,
                         });
+                        if target.player.is_some() && target.inventory.is_some() {
+                            emit(ServerEvent::ItemWear {
+                                entity: target.entity,
+                                kind: WearKind::Weapons,
+                                amount: WEAPON_WEAR_ON_BLOCK,
+                            });
+                        }
                         if parry {
                             1.0
                         } else {
@@ -467,6 +506,26 @@ impl Attack {
                 }
             }
         }
+        // Hitting wears down the weapons of the attacker and the armor of the target,
+        // only the gear of players wears down
+        if accumulated_damage > 0.0 {
+            if let Some(attacker) = attacker.filter(|a| a.player.is_some() && a.inventory.is_some())
+            {
+                emit(ServerEvent::ItemWear {
+                    entity: attacker.entity,
+                    kind: WearKind::Weapons,
+                    amount: WEAPON_WEAR_ON_HIT,
+                });
+            }
+            if target.player.is_some() && target.inventory.is_some() {
+                emit(ServerEvent::ItemWear {
+                    entity: target.entity,
+                    kind: WearKind::Armor,
+                    amount: ARMOR_WEAR_ON_HIT,
+                });
+            }
+        }
+
         // Emits event to handle things that should happen for any successful attack,
         // regardless of if the attack had any damages or effects in it
         if is_applied {
@@ -682,6 +741,8 @@ impl Damage {
         let inventory_dr = if let Some(inventory) = inventory {
             let protection = inventory
                 .equipped_items()
+                // Broken armor doesn't provide any stats
+                .filter(|item| !item.is_broken())
                 .filter_map(|item| {
                     if let ItemKind::Armor(armor) = &item.kind() {
                         Some(armor.protection())
@@ -1018,6 +1079,7 @@ pub fn compute_crit_mult(inventory: Option<&Inventory>) -> f32 {
     // defaults to a value of 1.25 if no inventory is equipped
     inventory.map_or(1.25, |inv| {
         inv.equipped_items()
+            .filter(|item| !item.is_broken())
             .filter_map(|item| {
                 if let ItemKind::Armor(armor) = &item.kind() {
                     armor.crit_power()
@@ -1036,6 +1098,7 @@ pub fn compute_energy_reward_mod(inventory: Option<&Inventory>) -> f32 {
     // defaults to a value of 1.0 if no inventory is present
     inventory.map_or(1.0, |inv| {
         inv.equipped_items()
+            .filter(|item| !item.is_broken())
             .filter_map(|item| {
                 if let ItemKind::Armor(armor) = &item.kind() {
                     armor.energy_reward()
@@ -1054,6 +1117,7 @@ pub fn compute_max_energy_mod(inventory: Option<&Inventory>) -> f32 {
     // Defaults to a value of 0 if no inventory is present
     inventory.map_or(0.0, |inv| {
         inv.equipped_items()
+            .filter(|item| !item.is_broken())
             .filter_map(|item| {
                 if let ItemKind::Armor(armor) = &item.kind() {
                     armor.energy_max()
@@ -1073,6 +1137,7 @@ pub fn compute_stealth_coefficient(inventory: Option<&Inventory>) -> f32 {
     // defaults to a value of 2.0 if no inventory is equipped
     inventory.map_or(2.0, |inv| {
         inv.equipped_items()
+            .filter(|item| !item.is_broken())
             .filter_map(|item| {
                 if let ItemKind::Armor(armor) = &item.kind() {
                     armor.stealth()
@@ -369,6 +369,9 @@ pub enum TimerAction {
 pub struct Timer {
     action_starts: Vec<Option<f64>>,
     last_action: Option<TimerAction>,
+    /// Start times of the timers named by the combat tactic of the agent,
+    /// which are independent of the actions
+    named_starts: Vec<(String, f64)>,
 }
 
 impl Default for Timer {
@@ -376,6 +379,7 @@ impl Default for Timer {
         Self {
             action_starts: TimerAction::iter().map(|_| None).collect(),
             last_action: None,
+            named_starts: Vec::new(),
         }
     }
 }
@@ -421,6 +425,22 @@ impl Timer {
             .map_or(true, |last_time| (time - last_time).max(0.0) > timeout)
     }
 
+    /// Start the timer of the given name, even if it was already started.
+    pub fn start_named(&mut self, time: f64, name: &str) {
+        match self.named_starts.iter_mut().find(|(n, _)| n == name) {
+            Some((_, start)) => *start = time,
+            None => self.named_starts.push((name.to_owned(), time)),
+        }
+    }
+
+    /// Return the time that the timer of the given name was last started at.
+    pub fn time_of_last_named(&self, name: &str) -> Option<f64> {
+        self.named_starts
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, start)| *start)
+    }
+
     /// Return `true` while the time since the action was last started is less
     /// than the given period. Once the time has elapsed, reset the timer.
     pub fn timeout_elapsed(
@@ -454,6 +474,11 @@ pub struct Agent {
     pub sounds_heard: Vec<Sound>,
     pub awareness: f32,
     pub position_pid_controller: Option<PidController<fn(Vec3<f32>, Vec3<f32>) -> f32, 16>>,
+    /// Asset specifier of the combat tactic, instead of the one of the main
+    /// weapon
+    pub tactic: Option<String>,
+    /// Asset specifiers of the quests offered by the agent when talked to
+    pub quests: Vec<String>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -480,6 +505,8 @@ impl Agent {
             sounds_heard: Vec::new(),
             awareness: 0.0,
             position_pid_controller: None,
+            tactic: None,
+            quests: Vec::new(),
         }
     }
 
@@ -522,6 +549,18 @@ impl Agent {
         self
     }
 
+    #[must_use]
+    pub fn with_tactic(mut self, tactic: Option<String>) -> Self {
+        self.tactic = tactic;
+        self
+    }
+
+    #[must_use]
+    pub fn with_quests(mut self, quests: Vec<String>) -> Self {
+        self.quests = quests;
+        self
+    }
+
     #[must_use]
     pub fn with_aggro_no_warn(mut self) -> Self {
         self.psyche.aggro_dist = None;
@@ -15,8 +15,8 @@ use crate::{
 use serde::{Deserialize, Serialize};
 use specs::{Component, DerefFlaggedStorage, VecStorage};
 use specs_idvs::IdvStorage;
-use std::collections::BTreeMap;
-use strum_macros::Display;
+use std::{collections::BTreeMap, time::Duration};
+use strum_macros::{Display, IntoStaticStr};
 use vek::*;
 
 /// Data returned from character behavior fn's to Character Behavior System.
@@ -64,7 +64,7 @@ impl From<&JoinData<'_>> for StateUpdate {
         }
     }
 }
-#[derive(Clone, Debug, Display, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Display, IntoStaticStr, PartialEq, Serialize, Deserialize)]
 pub enum CharacterState {
     Idle(idle::Data),
     Climb(climb::Data),
@@ -188,6 +188,29 @@ impl CharacterState {
         )
     }
 
+    /// The stage of the attack in progress and the time spent in it
+    pub fn attack_stage(&self) -> Option<(StageSection, Duration)> {
+        match self {
+            CharacterState::BasicMelee(data) => Some((data.stage_section, data.timer)),
+            CharacterState::BasicRanged(data) => Some((data.stage_section, data.timer)),
+            CharacterState::DashMelee(data) => Some((data.stage_section, data.timer)),
+            CharacterState::ComboMelee(data) => Some((data.stage_section, data.timer)),
+            CharacterState::LeapMelee(data) => Some((data.stage_section, data.timer)),
+            CharacterState::SpinMelee(data) => Some((data.stage_section, data.timer)),
+            CharacterState::ChargedMelee(data) => Some((data.stage_section, data.timer)),
+            CharacterState::ChargedRanged(data) => Some((data.stage_section, data.timer)),
+            CharacterState::RepeaterRanged(data) => Some((data.stage_section, data.timer)),
+            CharacterState::Shockwave(data) => Some((data.stage_section, data.timer)),
+            CharacterState::BasicBeam(data) => Some((data.stage_section, data.timer)),
+            CharacterState::BasicAura(data) => Some((data.stage_section, data.timer)),
+            CharacterState::SelfBuff(data) => Some((data.stage_section, data.timer)),
+            CharacterState::Blink(data) => Some((data.stage_section, data.timer)),
+            CharacterState::BasicSummon(data) => Some((data.stage_section, data.timer)),
+            CharacterState::SpriteSummon(data) => Some((data.stage_section, data.timer)),
+            _ => None,
+        }
+    }
+
     pub fn is_aimed(&self) -> bool {
         matches!(
             self,
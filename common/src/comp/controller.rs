@@ -3,6 +3,7 @@ use crate::{
         ability,
         inventory::slot::{EquipSlot, InvSlotId, Slot},
         invite::{InviteKind, InviteResponse},
+        storage::{StorageAccess, StorageSlot},
         BuffKind,
     },
     trade::{TradeAction, TradeId},
@@ -27,6 +28,11 @@ pub enum InventoryEvent {
         craft_event: CraftEvent,
         craft_sprite: Option<Vec3<i32>>,
     },
+    StorageSwap(StorageSlot, StorageSlot),
+    StorageSplitSwap(StorageSlot, StorageSlot),
+    CloseStorage,
+    SetStorageAccess(StorageAccess),
+    PickUpStorage,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
@@ -53,6 +59,14 @@ pub enum InventoryManip {
         craft_sprite: Option<Vec3<i32>>,
     },
     SwapEquippedWeapons,
+    OpenStorage(Vec3<i32>),
+    StorageSwap(StorageSlot, StorageSlot),
+    StorageSplitSwap(StorageSlot, StorageSlot),
+    CloseStorage,
+    SetStorageAccess(StorageAccess),
+    /// Removes the open storage container, if empty, and gives it back to its
+    /// owner
+    PickUpStorage,
 }
 
 impl From<InventoryAction> for InventoryManip {
@@ -87,6 +101,11 @@ impl From<InventoryEvent> for InventoryManip {
                 craft_event,
                 craft_sprite,
             },
+            InventoryEvent::StorageSwap(a, b) => Self::StorageSwap(a, b),
+            InventoryEvent::StorageSplitSwap(a, b) => Self::StorageSplitSwap(a, b),
+            InventoryEvent::CloseStorage => Self::CloseStorage,
+            InventoryEvent::SetStorageAccess(access) => Self::SetStorageAccess(access),
+            InventoryEvent::PickUpStorage => Self::PickUpStorage,
         }
     }
 }
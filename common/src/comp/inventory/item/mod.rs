@@ -49,6 +49,7 @@ pub enum Reagent {
 pub enum Utility {
     Coins,
     Collar,
+    StorageChest,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -369,6 +370,11 @@ pub struct Item {
     /// The slots for items that this item has
     slots: Vec<InvSlot>,
     item_config: Option<Box<ItemConfig>>,
+    /// Durability lost to wear, the item is broken once it reaches
+    /// `ItemDef::max_durability`. Hidden because the stats of the item depend
+    /// on whether it is broken.
+    #[serde(default)]
+    durability_lost: u32,
 }
 
 // Custom serialization for ItemDef, we only want to send the item_definition_id
@@ -450,7 +456,7 @@ impl TryFrom<(&Item, &AbilityMap, &MaterialStatManifest)> for ItemConfig {
                 let key = &AbilitySpec::Tool(tool_kind);
                 ability_map.get_ability_set(key)
             };
-            let abilities = if let Some(set_key) = item.ability_spec() {
+            let mut abilities = if let Some(set_key) = item.ability_spec() {
                 if let Some(set) = ability_map.get_ability_set(set_key) {
                     set.clone().modified_by_tool(tool, msm, &item.components)
                 } else {
@@ -470,6 +476,13 @@ impl TryFrom<(&Item, &AbilityMap, &MaterialStatManifest)> for ItemConfig {
                 );
                 Default::default()
             };
+            // Broken tools lose their stats until repaired
+            if item.is_broken() {
+                abilities = abilities.map(|a| tool::AbilityItem {
+                    id: a.id,
+                    ability: a.ability.adjusted_by_stats(tool::Stats::broken()),
+                });
+            }
 
             Ok(ItemConfig { abilities })
         } else {
@@ -510,6 +523,36 @@ impl ItemDef {
     // currently needed by trade_pricing
     pub fn id(&self) -> &str { &self.item_definition_id }
 
+    /// Durability of the item when new, or `None` if it doesn't wear down.
+    /// Only armor and tools have durability, which is higher the better the
+    /// quality.
+    pub fn max_durability(&self) -> Option<u32> {
+        let has_durability = match &self.kind {
+            ItemKind::Armor(a) => !matches!(
+                a.kind,
+                armor::ArmorKind::Tabard(_) | armor::ArmorKind::Bag(_)
+            ),
+            ItemKind::Tool(tool) => !matches!(
+                tool.kind,
+                ToolKind::Debug | ToolKind::Natural | ToolKind::Empty
+            ),
+            _ => false,
+        };
+        if !has_durability {
+            return None;
+        }
+        match self.quality {
+            Quality::Low => Some(100),
+            Quality::Common => Some(150),
+            Quality::Moderate => Some(200),
+            Quality::High => Some(300),
+            Quality::Epic => Some(400),
+            Quality::Legendary => Some(600),
+            Quality::Artifact => Some(800),
+            Quality::Debug => None,
+        }
+    }
+
     #[cfg(test)]
     pub fn new_test(
         item_definition_id: String,
@@ -641,6 +684,7 @@ impl Item {
             slots: vec![None; inner_item.slots as usize],
             item_def: inner_item,
             item_config: None,
+            durability_lost: 0,
         };
         item.update_item_config(ability_map, msm);
         item
@@ -687,6 +731,7 @@ impl Item {
             "`new_item` has the same `item_def` and as an invariant, \
              self.set_amount(self.amount()) should always succeed.",
         );
+        new_item.set_durability_lost(self.durability_lost, ability_map, msm);
         new_item.slots_mut().iter_mut().zip(self.slots()).for_each(
             |(new_item_slot, old_item_slot)| {
                 *new_item_slot = old_item_slot
@@ -778,6 +823,57 @@ impl Item {
         }
     }
 
+    /// Remaining durability of the item, or `None` if it doesn't wear down
+    pub fn durability(&self) -> Option<u32> {
+        self.max_durability()
+            .map(|max| max.saturating_sub(self.durability_lost))
+    }
+
+    pub fn durability_lost(&self) -> u32 { self.durability_lost }
+
+    pub fn is_broken(&self) -> bool { self.durability() == Some(0) }
+
+    /// Sets the durability lost by the item, e.g. when loading it from the
+    /// database. Clamped to the max durability of the item.
+    pub fn set_durability_lost(
+        &mut self,
+        durability_lost: u32,
+        ability_map: &AbilityMap,
+        msm: &MaterialStatManifest,
+    ) {
+        let was_broken = self.is_broken();
+        self.durability_lost = self
+            .max_durability()
+            .map_or(0, |max| durability_lost.min(max));
+        // breaking or repairing the item changes the stats, so recalculate the
+        // ItemConfig
+        if was_broken != self.is_broken() {
+            self.update_item_config(ability_map, msm);
+        }
+    }
+
+    /// Wears the item down by the given amount. Returns true if this broke
+    /// the item.
+    pub fn apply_wear(
+        &mut self,
+        amount: u32,
+        ability_map: &AbilityMap,
+        msm: &MaterialStatManifest,
+    ) -> bool {
+        let was_broken = self.is_broken();
+        self.set_durability_lost(
+            self.durability_lost.saturating_add(amount),
+            ability_map,
+            msm,
+        );
+        !was_broken && self.is_broken()
+    }
+
+    /// Restores the durability of the item, along with its stats if broken
+    pub fn repair(&mut self, ability_map: &AbilityMap, msm: &MaterialStatManifest) {
+        self.set_durability_lost(0, ability_map, msm);
+    }
+
     /// Returns an iterator that drains items contained within the item's slots
     pub fn drain(&mut self) -> impl Iterator<Item = Item> + '_ {
         self.slots.iter_mut().filter_map(mem::take)
@@ -869,6 +965,9 @@ pub trait ItemDesc {
     fn item_definition_id(&self) -> &str;
     fn components(&self) -> &[Item];
     fn tags(&self) -> &[ItemTag];
+    /// Remaining durability, or `None` if the item doesn't wear down
+    fn durability(&self) -> Option<u32>;
+    fn max_durability(&self) -> Option<u32>;
 
     fn tool(&self) -> Option<&Tool> {
         if let ItemKind::Tool(tool) = self.kind() {
@@ -895,6 +994,10 @@ impl ItemDesc for Item {
     fn components(&self) -> &[Item] { &self.components }
 
     fn tags(&self) -> &[ItemTag] { &self.item_def.tags }
+
+    fn durability(&self) -> Option<u32> { Item::durability(self) }
+
+    fn max_durability(&self) -> Option<u32> { self.item_def.max_durability() }
 }
 
 impl ItemDesc for ItemDef {
@@ -913,6 +1016,10 @@ impl ItemDesc for ItemDef {
     fn components(&self) -> &[Item] { &[] }
 
     fn tags(&self) -> &[ItemTag] { &self.tags }
+
+    fn durability(&self) -> Option<u32> { ItemDef::max_durability(self) }
+
+    fn max_durability(&self) -> Option<u32> { ItemDef::max_durability(self) }
 }
 
 impl Component for Item {
@@ -942,6 +1049,10 @@ impl<'a, T: ItemDesc + ?Sized> ItemDesc for &'a T {
     fn components(&self) -> &[Item] { (*self).components() }
 
     fn tags(&self) -> &[ItemTag] { (*self).tags() }
+
+    fn durability(&self) -> Option<u32> { (*self).durability() }
+
+    fn max_durability(&self) -> Option<u32> { (*self).max_durability() }
 }
 
 /// Returns all item asset specifiers
@@ -968,4 +1079,30 @@ mod tests {
             std::mem::drop(item)
         }
     }
+
+    #[test]
+    fn test_wear_breaks_and_repair_restores() {
+        let ability_map = AbilityMap::default();
+        let msm = MaterialStatManifest::default();
+        let mut sword = Item::new_from_asset_expect("common.items.weapons.sword.starter");
+        let max = sword
+            .max_durability()
+            .expect("Tools should have durability");
+        assert_eq!(sword.durability(), Some(max));
+
+        assert!(!sword.apply_wear(max - 1, &ability_map, &msm));
+        assert_eq!(sword.durability(), Some(1));
+        assert!(sword.apply_wear(2, &ability_map, &msm));
+        assert!(sword.is_broken());
+        // Wear doesn't go beyond the max durability
+        assert_eq!(sword.durability_lost(), max);
+        assert!(!sword.apply_wear(1, &ability_map, &msm));
+
+        sword.repair(&ability_map, &msm);
+        assert_eq!(sword.durability(), Some(max));
+
+        let coins = Item::new_from_asset_expect("common.items.utility.coins");
+        assert_eq!(coins.durability(), None);
+        assert!(!coins.is_broken());
+    }
 }
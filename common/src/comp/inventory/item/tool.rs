@@ -105,6 +105,21 @@ impl Stats {
         }
     }
 
+    /// Stats that abilities are adjusted by when their tool is broken, leaving
+    /// them without any damage or effect
+    pub fn broken() -> Stats {
+        Stats {
+            equip_time_secs: 1.0,
+            power: 0.0,
+            effect_power: 0.0,
+            speed: 1.0,
+            crit_chance: 1.0,
+            range: 1.0,
+            energy_efficiency: 1.0,
+            buff_strength: 0.0,
+        }
+    }
+
     #[must_use]
     pub fn clamp_speed(mut self) -> Self {
         // if a tool has 0.0 speed, that panics due to being infinite duration, so
@@ -311,6 +311,14 @@ impl Loadout {
         self.slots.iter().filter_map(|x| x.slot.as_ref())
     }
 
+    pub(super) fn items_with_equip_slot_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (EquipSlot, &mut Item)> {
+        self.slots
+            .iter_mut()
+            .filter_map(|x| x.slot.as_mut().map(|item| (x.equip_slot, item)))
+    }
+
     /// Checks that a slot can hold a given item
     pub(super) fn slot_can_hold(
         &self,
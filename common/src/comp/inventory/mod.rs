@@ -90,6 +90,16 @@ impl Inventory {
         }
     }
 
+    /// Creates an inventory without a loadout that has the given number of
+    /// built-in slots, such as the inventory of a storage container
+    pub fn new_container(slots: usize) -> Inventory {
+        Inventory {
+            next_sort_order: InventorySortOrder::Name,
+            loadout: LoadoutBuilder::empty().build(),
+            slots: vec![None; slots],
+        }
+    }
+
     /// Total number of slots in in the inventory.
     pub fn capacity(&self) -> usize { self.slots().count() }
 
@@ -459,6 +469,32 @@ impl Inventory {
             .sum()
     }
 
+    /// Removes the given amount of a particular item from the inventory, if
+    /// there is enough of it. Returns whether the items were removed.
+    pub fn remove_item_amount(&mut self, item_def: &ItemDef, amount: u64) -> bool {
+        if self.item_count(item_def) < amount {
+            return false;
+        }
+        let mut remaining = amount;
+        for slot in self.slots_mut() {
+            if remaining == 0 {
+                break;
+            }
+            if let Some(item) = slot.as_mut().filter(|it| it.is_same_item_def(item_def)) {
+                let held = u64::from(item.amount());
+                if held <= remaining {
+                    remaining -= held;
+                    *slot = None;
+                } else {
+                    // `remaining` is less than the amount of the item, so fits in a u32
+                    let _ = item.decrease_amount(remaining as u32);
+                    remaining = 0;
+                }
+            }
+        }
+        true
+    }
+
     /// Adds a new item to the first empty slot of the inventory. Returns the
     /// item again in an Err if no free slot was found, otherwise returns a
     /// reference to the item.
@@ -766,6 +802,28 @@ impl Inventory {
     }
 
     pub fn swap_equipped_weapons(&mut self) { self.loadout.swap_equipped_weapons() }
+
+    /// Wears down the equipped items in the slots accepted by `wears`,
+    /// returning the names of the items that broke
+    pub fn wear_equipped(
+        &mut self,
+        wears: impl Fn(EquipSlot) -> bool,
+        amount: u32,
+        ability_map: &AbilityMap,
+        msm: &MaterialStatManifest,
+    ) -> Vec<String> {
+        self.loadout
+            .items_with_equip_slot_mut()
+            .filter(|(equip_slot, _)| wears(*equip_slot))
+            .filter_map(|(_, item)| {
+                if item.apply_wear(amount, ability_map, msm) {
+                    Some(item.name().to_owned())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
 }
 
 impl Component for Inventory {
@@ -372,7 +372,8 @@ impl TradePricing {
         // Apply recipe book
         let book = default_recipe_book().read();
         let mut ordered_recipes: Vec<RememberedRecipe> = Vec::new();
-        for (_, recipe) in book.iter() {
+        // Repair recipes don't produce new items, so they don't affect prices
+        for (_, recipe) in book.iter().filter(|(_, recipe)| !recipe.repair) {
             let (ref asset_path, amount) = recipe.output;
             ordered_recipes.push(RememberedRecipe {
                 output: asset_path.id().into(),
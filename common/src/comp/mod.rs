@@ -37,12 +37,15 @@ pub mod invite;
 #[cfg(not(target_arch = "wasm32"))] pub mod poise;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod projectile;
+#[cfg(not(target_arch = "wasm32"))] pub mod quest;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod shockwave;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod skills;
 #[cfg(not(target_arch = "wasm32"))] mod stats;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod storage;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod visual;
 
 // Reexports
@@ -98,9 +101,11 @@ pub use self::{
     player::{AliasError, Player, MAX_ALIAS_LEN},
     poise::{Poise, PoiseState},
     projectile::{Projectile, ProjectileConstructor},
+    quest::QuestLog,
     shockwave::{Shockwave, ShockwaveHitEntities},
     skills::{Skill, SkillGroup, SkillGroupKind, SkillSet},
     stats::{Stats, StatsModifier},
+    storage::{OpenStorage, StorageAccess, StorageContainers, StorageSlot, StorageView},
     visual::{LightAnimation, LightEmitter},
 };
 
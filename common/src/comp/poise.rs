@@ -178,6 +178,7 @@ impl Poise {
     pub fn compute_poise_damage_reduction(inventory: &Inventory) -> f32 {
         let protection = inventory
             .equipped_items()
+            .filter(|item| !item.is_broken())
             .filter_map(|item| {
                 if let ItemKind::Armor(armor) = &item.kind() {
                     Some(armor.poise_resilience())
@@ -0,0 +1,265 @@
+use crate::{
+    assets::AssetExt,
+    comp::{Body, Inventory, Item},
+    quest::{body_matches, Objective, QuestSpec},
+};
+use serde::{Deserialize, Serialize};
+use specs::Component;
+use specs_idvs::IdvStorage;
+use vek::*;
+
+/// Distance from the centre of a site to reach it
+const SITE_RADIUS: f32 = 64.0;
+
+/// The site a `Reach` objective leads to, chosen when starting the quest
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuestSite {
+    pub name: String,
+    pub wpos: Vec2<i32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quest {
+    /// Asset specifier of the [`QuestSpec`]
+    pub spec: String,
+    /// Progress toward each objective of the spec, in order
+    pub progress: Vec<u32>,
+    /// Site of each objective, for `Reach` objectives
+    pub sites: Vec<Option<QuestSite>>,
+    pub completed: bool,
+}
+
+impl Quest {
+    pub fn new(spec: String, sites: Vec<Option<QuestSite>>) -> Self {
+        Self {
+            progress: vec![0; sites.len()],
+            spec,
+            sites,
+            completed: false,
+        }
+    }
+
+    /// Whether all the objectives are met
+    pub fn is_met(&self) -> bool {
+        let spec = QuestSpec::load_expect(&self.spec).read();
+        spec.objectives
+            .iter()
+            .zip(&self.progress)
+            .all(|(objective, progress)| *progress >= objective.amount())
+    }
+
+    /// Describes the objectives and the progress toward them, one per line
+    pub fn describe(&self) -> String {
+        let spec = QuestSpec::load_expect(&self.spec).read();
+        spec.objectives
+            .iter()
+            .enumerate()
+            .map(|(i, objective)| self.describe_objective(i, objective))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Describes the progress made toward the quest, to notify the player
+    pub fn progress_message(&self) -> String {
+        let name = QuestSpec::load_expect(&self.spec).read().name.clone();
+        if self.is_met() {
+            format!("{}: objectives met, return to the quest giver", name)
+        } else {
+            format!("{}\n{}", name, self.describe())
+        }
+    }
+
+    fn describe_objective(&self, i: usize, objective: &Objective) -> String {
+        let progress = self.progress.get(i).copied().unwrap_or(0);
+        match objective {
+            Objective::Kill(keyword, amount) => {
+                format!("Kill {} {}: {}/{}", amount, keyword, progress, amount)
+            },
+            Objective::Collect(item, amount) => format!(
+                "Collect {} {}: {}/{}",
+                amount,
+                Item::new_from_asset(item)
+                    .map_or_else(|_| item.clone(), |item| item.name().to_owned()),
+                progress,
+                amount
+            ),
+            Objective::Reach(kind) => match self.sites.get(i).and_then(Option::as_ref) {
+                Some(site) => format!("Reach {}: {}/1", site.name, progress),
+                None => format!("Reach a {:?}: {}/1", kind, progress),
+            },
+            Objective::Talk(name) => format!("Talk to a {}: {}/1", name, progress),
+        }
+    }
+}
+
+/// The quests a character started, to track their progress
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QuestLog {
+    pub quests: Vec<Quest>,
+}
+
+impl QuestLog {
+    pub fn active(&self) -> impl Iterator<Item = &Quest> {
+        self.quests.iter().filter(|quest| !quest.completed)
+    }
+
+    pub fn get(&self, spec: &str) -> Option<&Quest> {
+        self.quests.iter().find(|quest| quest.spec == spec)
+    }
+
+    /// Whether the quest can be started, i.e. it isn't in progress and wasn't
+    /// completed unless repeatable
+    pub fn can_start(&self, spec: &str) -> bool {
+        self.get(spec).map_or(true, |quest| {
+            quest.completed && QuestSpec::load_expect(spec).read().repeatable
+        })
+    }
+
+    /// Starts the quest, replacing an earlier completion of it
+    pub fn start(&mut self, quest: Quest) {
+        self.quests.retain(|old| old.spec != quest.spec);
+        self.quests.push(quest);
+    }
+
+    /// Updates the progress toward the objectives of the active quests with
+    /// `f`, returning the quests whose progress changed.
+    fn advance(
+        &mut self,
+        mut f: impl FnMut(&Objective, Option<&QuestSite>, u32) -> u32,
+    ) -> Vec<&Quest> {
+        self.quests
+            .iter_mut()
+            .filter(|quest| !quest.completed)
+            .filter_map(|quest| {
+                let spec = QuestSpec::load_expect(&quest.spec).read();
+                let mut changed = false;
+                for (i, objective) in spec.objectives.iter().enumerate() {
+                    let site = quest.sites.get(i).and_then(Option::as_ref);
+                    if let Some(progress) = quest.progress.get_mut(i) {
+                        let new = f(objective, site, *progress).min(objective.amount());
+                        changed |= new != *progress;
+                        *progress = new;
+                    }
+                }
+                if changed { Some(&*quest) } else { None }
+            })
+            .collect()
+    }
+
+    /// Records the kill of a creature
+    pub fn record_kill(&mut self, body: &Body) -> Vec<&Quest> {
+        self.advance(|objective, _, progress| match objective {
+            Objective::Kill(keyword, _) if body_matches(keyword, body) => progress + 1,
+            _ => progress,
+        })
+    }
+
+    /// Records talking to an NPC of the given name
+    pub fn record_talk(&mut self, name: &str) -> Vec<&Quest> {
+        self.advance(|objective, _, progress| match objective {
+            Objective::Talk(npc) if npc.eq_ignore_ascii_case(name) => 1,
+            _ => progress,
+        })
+    }
+
+    /// Records the position of the character, for `Reach` objectives
+    pub fn record_pos(&mut self, pos: Vec3<f32>) -> Vec<&Quest> {
+        self.advance(|objective, site, progress| match (objective, site) {
+            (Objective::Reach(_), Some(site))
+                if site.wpos.as_::<f32>().distance_squared(pos.xy()) < SITE_RADIUS.powi(2) =>
+            {
+                1
+            },
+            _ => progress,
+        })
+    }
+
+    /// Records the items held by the character, for `Collect` objectives
+    pub fn record_inventory(&mut self, inventory: &Inventory) -> Vec<&Quest> {
+        self.advance(|objective, _, progress| match objective {
+            Objective::Collect(item, _) => Item::new_from_asset(item).map_or(progress, |item| {
+                inventory.item_count(&item).min(u64::from(u32::MAX)) as u32
+            }),
+            _ => progress,
+        })
+    }
+
+    /// Completes the quest if its objectives are met, taking the items to
+    /// collect from the inventory. Returns whether the quest was completed.
+    pub fn turn_in(&mut self, spec: &str, inventory: &mut Inventory) -> bool {
+        let quest = match self
+            .quests
+            .iter_mut()
+            .find(|quest| quest.spec == spec && !quest.completed)
+        {
+            Some(quest) => quest,
+            None => return false,
+        };
+        // The items could have been dropped since the progress was recorded
+        quest.progress = quest
+            .progress
+            .iter()
+            .copied()
+            .zip(&QuestSpec::load_expect(spec).read().objectives)
+            .map(|(progress, objective)| match objective {
+                Objective::Collect(item, _) => Item::new_from_asset(item).map_or(0, |item| {
+                    inventory.item_count(&item).min(u64::from(u32::MAX)) as u32
+                }),
+                _ => progress,
+            })
+            .collect();
+        if !quest.is_met() {
+            return false;
+        }
+        for objective in &QuestSpec::load_expect(spec).read().objectives {
+            if let Objective::Collect(item, amount) = objective {
+                if let Ok(item) = Item::new_from_asset(item) {
+                    inventory.remove_item_amount(&item, u64::from(*amount));
+                }
+            }
+        }
+        quest.completed = true;
+        true
+    }
+}
+
+impl Component for QuestLog {
+    type Storage = IdvStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comp::quadruped_medium;
+
+    #[test]
+    fn kills_advance_matching_objectives() {
+        let mut log = QuestLog::default();
+        let spec = "common.quest.wolf_hunt";
+        let objectives = QuestSpec::load_expect(spec).read().objectives.len();
+        log.start(Quest::new(spec.to_owned(), vec![None; objectives]));
+        assert!(!log.can_start(spec));
+
+        let wolf = Body::QuadrupedMedium(quadruped_medium::Body::random_with(
+            &mut rand::thread_rng(),
+            &quadruped_medium::Species::Wolf,
+        ));
+        let frostfang = Body::QuadrupedMedium(quadruped_medium::Body::random_with(
+            &mut rand::thread_rng(),
+            &quadruped_medium::Species::Frostfang,
+        ));
+        assert_eq!(log.record_kill(&frostfang).len(), 0);
+        assert_eq!(log.record_kill(&wolf).len(), 1);
+        assert_eq!(log.get(spec).unwrap().progress[0], 1);
+
+        while !log.get(spec).unwrap().is_met() {
+            log.record_kill(&wolf);
+        }
+        // The progress doesn't go beyond the objective
+        assert_eq!(log.record_kill(&wolf).len(), 0);
+
+        let mut inventory = Inventory::new_empty();
+        assert!(log.turn_in(spec, &mut inventory));
+        assert!(log.active().next().is_none());
+    }
+}
@@ -0,0 +1,337 @@
+use crate::{
+    character::CharacterId,
+    comp::{
+        inventory::{
+            item::{tool::AbilityMap, MaterialStatManifest},
+            slot::InvSlotId,
+            Inventory,
+        },
+        Item,
+    },
+    terrain::TerrainGrid,
+};
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use specs::Component;
+use specs_idvs::IdvStorage;
+use vek::*;
+
+/// Number of slots of a storage container
+pub const STORAGE_SLOTS: usize = 27;
+
+/// Who, besides its owner, may open a storage container
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StorageAccess {
+    Owner,
+    /// Members of the group the owner is currently in
+    Group,
+    Public,
+}
+
+/// A slot of either the inventory of the player or of the storage container
+/// they have open
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StorageSlot {
+    Inventory(InvSlotId),
+    Storage(InvSlotId),
+}
+
+/// A container placed in the world, backed by an inventory
+#[derive(Clone, Debug)]
+pub struct StorageContainer {
+    pub owner: CharacterId,
+    pub access: StorageAccess,
+    pub inventory: Inventory,
+}
+
+impl StorageContainer {
+    pub fn new(owner: CharacterId) -> Self {
+        Self {
+            owner,
+            access: StorageAccess::Owner,
+            inventory: Inventory::new_container(STORAGE_SLOTS),
+        }
+    }
+
+    /// Whether a character may open the container, `same_group` being whether
+    /// they are in the same group as the owner
+    pub fn can_access(&self, character: CharacterId, same_group: bool) -> bool {
+        character == self.owner
+            || match self.access {
+                StorageAccess::Owner => false,
+                StorageAccess::Group => same_group,
+                StorageAccess::Public => true,
+            }
+    }
+
+    pub fn is_empty(&self) -> bool { self.inventory.populated_slots() == 0 }
+}
+
+/// All the storage containers of the world, keyed by the position of their
+/// sprite
+#[derive(Default)]
+pub struct StorageContainers {
+    containers: HashMap<Vec3<i32>, StorageContainer>,
+    /// Positions of the containers in each chunk
+    chunks: HashMap<Vec2<i32>, HashSet<Vec3<i32>>>,
+    /// Positions of the containers changed since the last call to
+    /// `take_changes`
+    changed: HashSet<Vec3<i32>>,
+}
+
+impl StorageContainers {
+    /// Adds containers loaded from the database, without marking them changed
+    pub fn load(&mut self, containers: impl IntoIterator<Item = (Vec3<i32>, StorageContainer)>) {
+        for (pos, container) in containers {
+            self.index(pos);
+            self.containers.insert(pos, container);
+        }
+    }
+
+    pub fn get(&self, pos: Vec3<i32>) -> Option<&StorageContainer> { self.containers.get(&pos) }
+
+    pub fn get_mut(&mut self, pos: Vec3<i32>) -> Option<&mut StorageContainer> {
+        let container = self.containers.get_mut(&pos)?;
+        self.changed.insert(pos);
+        Some(container)
+    }
+
+    pub fn insert(&mut self, pos: Vec3<i32>, container: StorageContainer) {
+        self.index(pos);
+        self.containers.insert(pos, container);
+        self.changed.insert(pos);
+    }
+
+    pub fn remove(&mut self, pos: Vec3<i32>) -> Option<StorageContainer> {
+        let container = self.containers.remove(&pos)?;
+        let key = TerrainGrid::chunk_key(pos);
+        if let Some(chunk) = self.chunks.get_mut(&key) {
+            chunk.remove(&pos);
+            if chunk.is_empty() {
+                self.chunks.remove(&key);
+            }
+        }
+        self.changed.insert(pos);
+        Some(container)
+    }
+
+    /// The positions of the containers in a chunk
+    pub fn in_chunk(&self, key: Vec2<i32>) -> impl Iterator<Item = Vec3<i32>> + '_ {
+        self.chunks.get(&key).into_iter().flatten().copied()
+    }
+
+    /// Returns the positions of the containers changed or removed since the
+    /// last call
+    pub fn take_changes(&mut self) -> Vec<Vec3<i32>> { self.changed.drain().collect() }
+
+    fn index(&mut self, pos: Vec3<i32>) {
+        self.chunks
+            .entry(TerrainGrid::chunk_key(pos))
+            .or_default()
+            .insert(pos);
+    }
+}
+
+/// The storage container an entity has open
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OpenStorage(pub Vec3<i32>);
+
+impl Component for OpenStorage {
+    type Storage = IdvStorage<Self>;
+}
+
+/// What a client is told about the storage container it has open
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StorageView {
+    pub pos: Vec3<i32>,
+    pub access: StorageAccess,
+    pub is_owner: bool,
+    pub inventory: Inventory,
+}
+
+/// Moves the item from slot `a` to slot `b`, either of them being in the
+/// inventory of the player or in the storage. Compatible stacks are merged and
+/// other items are swapped. With `split` only half of a stack is moved, and
+/// only onto an empty slot or a stack of the same item.
+pub fn swap_storage_slots(
+    inventory: &mut Inventory,
+    storage: &mut Inventory,
+    a: StorageSlot,
+    b: StorageSlot,
+    split: bool,
+    ability_map: &AbilityMap,
+    msm: &MaterialStatManifest,
+) {
+    if a == b {
+        return;
+    }
+    let (src, src_slot, dst, dst_slot) = match (a, b) {
+        (StorageSlot::Inventory(a), StorageSlot::Inventory(b)) => {
+            return swap_within(inventory, a, b, split, ability_map, msm);
+        },
+        (StorageSlot::Storage(a), StorageSlot::Storage(b)) => {
+            return swap_within(storage, a, b, split, ability_map, msm);
+        },
+        (StorageSlot::Inventory(a), StorageSlot::Storage(b)) => (inventory, a, storage, b),
+        (StorageSlot::Storage(a), StorageSlot::Inventory(b)) => (storage, a, inventory, b),
+    };
+
+    if src.slot(src_slot).is_none() || dst.slot(dst_slot).is_none() {
+        return;
+    }
+    if split && !can_split_onto(src.get(src_slot), dst.get(dst_slot)) {
+        return;
+    }
+
+    let item = if split {
+        src.take_half(src_slot, ability_map, msm)
+    } else {
+        src.remove(src_slot)
+    };
+    if let Some(item) = item {
+        // Either the stack didn't fit or another item was swapped out; the source
+        // slot was emptied or still holds the rest of the same stack, so it fits
+        if let Ok(Some(returned)) = dst.insert_or_stack_at(dst_slot, item) {
+            src.insert_or_stack_at(src_slot, returned)
+                .expect("slot was just vacated of item, so it definitely fits there.");
+        }
+    }
+}
+
+fn swap_within(
+    inventory: &mut Inventory,
+    a: InvSlotId,
+    b: InvSlotId,
+    split: bool,
+    ability_map: &AbilityMap,
+    msm: &MaterialStatManifest,
+) {
+    if split {
+        if can_split_onto(inventory.get(a), inventory.get(b)) {
+            if let Some(item) = inventory.take_half(a, ability_map, msm) {
+                inventory.insert_or_stack_at(b, item).ok();
+            }
+        }
+    } else if !inventory.merge_stack_into(a, b) {
+        inventory.swap_slots(a, b);
+    }
+}
+
+fn can_split_onto(source: Option<&Item>, target: Option<&Item>) -> bool {
+    match (source, target) {
+        (Some(source), Some(target)) => source == target,
+        (source, None) => source.is_some(),
+        (None, Some(_)) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(idx: u16) -> InvSlotId { InvSlotId::new(0, idx) }
+
+    fn stones(amount: u32) -> Item {
+        let mut item = Item::new_from_asset_expect("common.items.crafting_ing.stones");
+        item.set_amount(amount).unwrap();
+        item
+    }
+
+    #[test]
+    fn test_swap_between_inventory_and_storage() {
+        let (ability_map, msm) = (AbilityMap::default(), MaterialStatManifest::default());
+        let mut inventory = Inventory::new_empty();
+        let mut storage = Inventory::new_container(STORAGE_SLOTS);
+        let sword = Item::new_from_asset_expect("common.items.weapons.sword.starter");
+        inventory.insert_at(slot(0), sword).unwrap();
+        storage.insert_at(slot(3), stones(4)).unwrap();
+
+        // Moving onto an occupied slot swaps the items
+        swap_storage_slots(
+            &mut inventory,
+            &mut storage,
+            StorageSlot::Inventory(slot(0)),
+            StorageSlot::Storage(slot(3)),
+            false,
+            &ability_map,
+            &msm,
+        );
+        assert_eq!(inventory.get(slot(0)).map(Item::amount), Some(4));
+        assert_eq!(
+            storage.get(slot(3)).map(Item::item_definition_id),
+            Some("common.items.weapons.sword.starter")
+        );
+
+        // Splitting moves half of the stack, and merges it back
+        swap_storage_slots(
+            &mut inventory,
+            &mut storage,
+            StorageSlot::Inventory(slot(0)),
+            StorageSlot::Storage(slot(5)),
+            true,
+            &ability_map,
+            &msm,
+        );
+        assert_eq!(inventory.get(slot(0)).map(Item::amount), Some(2));
+        assert_eq!(storage.get(slot(5)).map(Item::amount), Some(2));
+        swap_storage_slots(
+            &mut inventory,
+            &mut storage,
+            StorageSlot::Storage(slot(5)),
+            StorageSlot::Inventory(slot(0)),
+            false,
+            &ability_map,
+            &msm,
+        );
+        assert_eq!(inventory.get(slot(0)).map(Item::amount), Some(4));
+        assert!(storage.get(slot(5)).is_none());
+
+        // Splitting onto a different item does nothing
+        swap_storage_slots(
+            &mut inventory,
+            &mut storage,
+            StorageSlot::Inventory(slot(0)),
+            StorageSlot::Storage(slot(3)),
+            true,
+            &ability_map,
+            &msm,
+        );
+        assert_eq!(inventory.get(slot(0)).map(Item::amount), Some(4));
+        assert_eq!(storage.populated_slots(), 1);
+    }
+
+    #[test]
+    fn test_storage_access() {
+        let mut container = StorageContainer::new(1);
+        assert!(container.can_access(1, false));
+        assert!(!container.can_access(2, true));
+        container.access = StorageAccess::Group;
+        assert!(container.can_access(2, true));
+        assert!(!container.can_access(2, false));
+        container.access = StorageAccess::Public;
+        assert!(container.can_access(2, false));
+    }
+
+    #[test]
+    fn test_containers_indexed_by_chunk() {
+        let mut containers = StorageContainers::default();
+        let (a, b) = (Vec3::new(1, 2, 3), Vec3::new(-1, 2, 3));
+        containers.load(vec![(a, StorageContainer::new(1))]);
+        containers.insert(b, StorageContainer::new(1));
+        let (key_a, key_b) = (TerrainGrid::chunk_key(a), TerrainGrid::chunk_key(b));
+        assert_ne!(key_a, key_b);
+        assert_eq!(containers.in_chunk(key_a).collect::<Vec<_>>(), vec![a]);
+        assert_eq!(containers.in_chunk(key_b).collect::<Vec<_>>(), vec![b]);
+
+        assert!(containers.remove(a).is_some());
+        assert!(containers.remove(a).is_none());
+        assert_eq!(containers.in_chunk(key_a).count(), 0);
+        assert_eq!(
+            containers
+                .take_changes()
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            vec![a, b].into_iter().collect()
+        );
+    }
+}
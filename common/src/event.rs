@@ -1,5 +1,6 @@
 use crate::{
     character::CharacterId,
+    combat,
     comp::{
         self,
         agent::Sound,
@@ -95,6 +96,11 @@ pub enum ServerEvent {
     EnableLantern(EcsEntity),
     DisableLantern(EcsEntity),
     NpcInteract(EcsEntity, EcsEntity),
+    /// A player asks a quest giver for work, to start or turn in its quests
+    QuestTalk {
+        player: EcsEntity,
+        giver: EcsEntity,
+    },
     InviteResponse(EcsEntity, InviteResponse),
     InitiateInvite(EcsEntity, Uid, InviteKind),
     ProcessTradeAction(EcsEntity, TradeId, TradeAction),
@@ -116,6 +122,7 @@ pub enum ServerEvent {
             comp::Inventory,
             Option<comp::Waypoint>,
             Vec<(comp::Pet, comp::Body, comp::Stats)>,
+            comp::QuestLog,
         ),
     },
     ExitIngame {
@@ -171,6 +178,12 @@ pub enum ServerEvent {
         entity: EcsEntity,
         energy_cost: f32,
     },
+    /// Wears down the equipped items of the given kind
+    ItemWear {
+        entity: EcsEntity,
+        kind: combat::WearKind,
+        amount: u32,
+    },
     RequestSiteInfo {
         entity: EcsEntity,
         id: SiteId,
@@ -56,6 +56,8 @@ impl Default for Hands {
 pub enum Meta {
     LoadoutAsset(String),
     SkillSetAsset(String),
+    TacticAsset(String),
+    Quest(String),
 }
 
 // FIXME: currently this is used for both base definition
@@ -124,6 +126,9 @@ pub struct EntityConfig {
     /// Possible fields:
     /// LoadoutAsset(String) with asset_specifier for loadout
     /// SkillSetAsset(String) with asset_specifier for skillset
+    /// TacticAsset(String) with asset_specifier for combat tactic
+    /// Quest(String) with asset_specifier for a quest offered by the entity,
+    /// can be given several times
     #[serde(default)]
     pub meta: Vec<Meta>,
 }
@@ -172,6 +177,8 @@ pub struct EntityInfo {
     pub loadout_asset: Option<String>,
     pub make_loadout: Option<fn(LoadoutBuilder, Option<&trade::SiteInformation>) -> LoadoutBuilder>,
     pub skillset_asset: Option<String>,
+    pub tactic_asset: Option<String>,
+    pub quests: Vec<String>,
     pub pet: Option<Box<EntityInfo>>,
     // we can't use DHashMap, do we want to move that into common?
     pub trading_information: Option<trade::SiteInformation>,
@@ -196,6 +203,8 @@ impl EntityInfo {
             loadout_asset: None,
             make_loadout: None,
             skillset_asset: None,
+            tactic_asset: None,
+            quests: Vec::new(),
             pet: None,
             trading_information: None,
         }
@@ -294,6 +303,12 @@ impl EntityInfo {
                 Meta::SkillSetAsset(asset) => {
                     self = self.with_skillset_asset(asset);
                 },
+                Meta::TacticAsset(asset) => {
+                    self = self.with_tactic_asset(asset);
+                },
+                Meta::Quest(asset) => {
+                    self = self.with_quest(asset);
+                },
             }
         }
 
@@ -395,6 +410,18 @@ impl EntityInfo {
         self
     }
 
+    #[must_use]
+    pub fn with_tactic_asset(mut self, asset: String) -> Self {
+        self.tactic_asset = Some(asset);
+        self
+    }
+
+    #[must_use]
+    pub fn with_quest(mut self, asset: String) -> Self {
+        self.quests.push(asset);
+        self
+    }
+
     #[must_use]
     pub fn with_automatic_name(mut self) -> Self {
         let npc_names = NPC_NAMES.read();
@@ -452,13 +479,17 @@ pub fn get_npc_name<
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{comp::inventory::slot::EquipSlot, SkillSetBuilder};
+    use crate::{
+        comp::inventory::slot::EquipSlot, quest::QuestSpec, tactic::TacticSpec, SkillSetBuilder,
+    };
     use hashbrown::HashMap;
 
     #[derive(Debug, Eq, Hash, PartialEq)]
     enum MetaId {
         LoadoutAsset,
         SkillSetAsset,
+        TacticAsset,
+        Quest,
     }
 
     impl Meta {
@@ -466,6 +497,8 @@ mod tests {
             match self {
                 Meta::LoadoutAsset(_) => MetaId::LoadoutAsset,
                 Meta::SkillSetAsset(_) => MetaId::SkillSetAsset,
+                Meta::TacticAsset(_) => MetaId::TacticAsset,
+                Meta::Quest(_) => MetaId::Quest,
             }
         }
     }
@@ -539,10 +572,17 @@ mod tests {
                 Meta::SkillSetAsset(asset) => {
                     std::mem::drop(SkillSetBuilder::from_asset_expect(&asset));
                 },
+                Meta::TacticAsset(asset) => {
+                    std::mem::drop(TacticSpec::load_expect(&asset));
+                },
+                Meta::Quest(asset) => {
+                    std::mem::drop(QuestSpec::load_expect(&asset));
+                },
             }
         }
         for (meta_id, counter) in meta_counter {
-            if counter > 1 {
+            // Entities can offer several quests
+            if counter > 1 && meta_id != MetaId::Quest {
                 panic!("Duplicate {:?} in {}", meta_id, config_asset);
             }
         }
@@ -52,6 +52,7 @@ pub mod lottery;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod outcome;
 #[cfg(not(target_arch = "wasm32"))] pub mod path;
+#[cfg(not(target_arch = "wasm32"))] pub mod quest;
 #[cfg(not(target_arch = "wasm32"))] pub mod ray;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod recipe;
@@ -69,6 +70,8 @@ pub mod spiral;
 pub mod states;
 #[cfg(not(target_arch = "wasm32"))] pub mod store;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod tactic;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod terrain;
 #[cfg(not(target_arch = "wasm32"))] pub mod time;
 #[cfg(not(target_arch = "wasm32"))] pub mod trade;
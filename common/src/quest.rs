@@ -0,0 +1,129 @@
+//! Quests that NPCs offer to players, defined in the assets of
+//! `common.quest`.
+//!
+//! NPCs offer the quests listed by `Quest` in the meta of their
+//! `EntityConfig` when players talk to them. The progress of players is kept
+//! by their [`crate::comp::QuestLog`].
+use crate::{
+    assets::{self, Error},
+    comp::Body,
+    lottery::LootSpec,
+    npc::NPC_NAMES,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct QuestSpec {
+    pub name: String,
+    /// Localization key of what the quest giver says when offering the
+    /// quest, e.g. `"npc.speech.quest.wolf_hunt"`
+    pub description: String,
+    pub objectives: Vec<Objective>,
+    /// Given to the player when completing the quest, each rolled separately
+    #[serde(default)]
+    pub rewards: Vec<LootSpec<String>>,
+    /// Whether the quest can be taken again once completed
+    #[serde(default)]
+    pub repeatable: bool,
+}
+
+impl assets::Asset for QuestSpec {
+    type Loader = assets::RonLoader;
+
+    const EXTENSION: &'static str = "ron";
+}
+
+/// Returns the specifiers of all the quest assets.
+pub fn try_all_quests() -> Result<Vec<String>, Error> {
+    let quests = assets::load_dir::<QuestSpec>("common.quest", true)?;
+    Ok(quests.ids().map(|id| id.to_owned()).collect())
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub enum Objective {
+    /// Kill the given number of creatures, by the keyword of their body or
+    /// species as used by `/spawn`, e.g. `"wolf"` or `"quadruped_low"`
+    Kill(String, u32),
+    /// Hold the given number of an item, by asset specifier. The items are
+    /// taken when completing the quest.
+    Collect(String, u32),
+    /// Reach the site of the given kind closest to the quest giver
+    Reach(SiteKind),
+    /// Talk to an NPC of the given name, e.g. `"Guard"`
+    Talk(String),
+}
+
+impl Objective {
+    /// Progress needed to meet the objective
+    pub fn amount(&self) -> u32 {
+        match self {
+            Objective::Kill(_, amount) | Objective::Collect(_, amount) => *amount,
+            Objective::Reach(_) | Objective::Talk(_) => 1,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SiteKind {
+    Town,
+    Dungeon,
+    Castle,
+    Cave,
+    Tree,
+}
+
+/// Whether the body, or its species, has the given keyword from
+/// `common.npc_names`
+pub fn body_matches(keyword: &str, body: &Body) -> bool {
+    let npc_names = NPC_NAMES.read();
+    let species = match body {
+        Body::Humanoid(body) => Some(&npc_names.humanoid.species[&body.species]),
+        Body::QuadrupedMedium(body) => Some(&npc_names.quadruped_medium.species[&body.species]),
+        Body::BirdMedium(body) => Some(&npc_names.bird_medium.species[&body.species]),
+        Body::BirdLarge(body) => Some(&npc_names.bird_large.species[&body.species]),
+        Body::FishSmall(body) => Some(&npc_names.fish_small.species[&body.species]),
+        Body::FishMedium(body) => Some(&npc_names.fish_medium.species[&body.species]),
+        Body::Theropod(body) => Some(&npc_names.theropod.species[&body.species]),
+        Body::QuadrupedSmall(body) => Some(&npc_names.quadruped_small.species[&body.species]),
+        Body::Dragon(body) => Some(&npc_names.dragon.species[&body.species]),
+        Body::QuadrupedLow(body) => Some(&npc_names.quadruped_low.species[&body.species]),
+        Body::Golem(body) => Some(&npc_names.golem.species[&body.species]),
+        Body::BipedLarge(body) => Some(&npc_names.biped_large.species[&body.species]),
+        Body::BipedSmall(body) => Some(&npc_names.biped_small.species[&body.species]),
+        _ => None,
+    };
+    npc_names[body].keyword == keyword
+        || species.map_or(false, |species| species.keyword == keyword)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assets::AssetExt, comp::Item, lottery, npc::NpcBody};
+
+    #[test]
+    fn test_all_quest_assets() {
+        for quest in try_all_quests().expect("Failed to access quests directory") {
+            println!("{}:", &quest);
+            let spec = QuestSpec::load_expect(&quest).read();
+            assert!(spec.description.starts_with("npc.speech."));
+            for objective in &spec.objectives {
+                match objective {
+                    Objective::Kill(keyword, _) => {
+                        let NpcBody(_, mut body) = keyword
+                            .parse::<NpcBody>()
+                            .unwrap_or_else(|_| panic!("Unknown body {:?}", keyword));
+                        assert!(body_matches(keyword, &body()));
+                    },
+                    Objective::Collect(item, _) => {
+                        std::mem::drop(Item::new_from_asset_expect(item));
+                    },
+                    Objective::Reach(_) | Objective::Talk(_) => {},
+                }
+            }
+            for reward in &spec.rewards {
+                lottery::tests::validate_loot_spec(reward);
+            }
+        }
+    }
+}
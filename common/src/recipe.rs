@@ -2,7 +2,7 @@ use crate::{
     assets::{self, AssetExt, AssetHandle},
     comp::{
         inventory::slot::InvSlotId,
-        item::{modular, tool::AbilityMap, ItemDef, ItemTag, MaterialStatManifest},
+        item::{modular, tool::AbilityMap, ItemDef, ItemKind, ItemTag, MaterialStatManifest},
         Inventory, Item,
     },
     terrain::SpriteKind,
@@ -22,6 +22,9 @@ pub struct Recipe {
     pub output: (Arc<ItemDef>, u32),
     pub inputs: Vec<(RecipeInput, u32)>,
     pub craft_sprite: Option<SpriteKind>,
+    /// Repair recipes restore the durability of a worn item of the output
+    /// kind, which must be one of the inputs, instead of crafting a new one
+    pub repair: bool,
 }
 
 #[allow(clippy::type_complexity)]
@@ -78,6 +81,26 @@ impl Recipe {
                 }
             });
 
+        // Repair recipes need a worn item to repair
+        let (output_def, _) = &self.output;
+        let repaired_slot = if self.repair {
+            let slot = slot_claims.keys().copied().find(|slot| {
+                inv.get(*slot).map_or(false, |item| {
+                    item.is_same_item_def(output_def) && item.durability_lost() > 0
+                })
+            });
+            if slot.is_none() {
+                if let Some((input, _)) = self.inputs.iter().find(|(input, _)| {
+                    matches!(input, RecipeInput::Item(def) if def.id() == output_def.id())
+                }) {
+                    unsatisfied_requirements.push((input, 1));
+                }
+            }
+            slot
+        } else {
+            None
+        };
+
         // If there are no unsatisfied requirements, create the items produced by the
         // recipe in the necessary quantity and remove the items that the recipe
         // consumes
@@ -89,6 +112,12 @@ impl Recipe {
                         .expect("Expected item to exist in the inventory");
                 }
             }
+            if let Some(slot) = repaired_slot {
+                if let Some(Some(item)) = inv.slot_mut(slot) {
+                    item.repair(ability_map, msm);
+                }
+                return Ok(Vec::new());
+            }
             let (item_def, quantity) = &self.output;
             let crafted_item = Item::new_from_item_def(Arc::clone(item_def), &[], ability_map, msm);
             let mut crafted_items = Vec::with_capacity(*quantity as usize);
@@ -101,6 +130,38 @@ impl Recipe {
         }
     }
 
+    /// The recipe repairing the output of this one, if it has durability. It
+    /// takes half of the materials needed to craft the item at the same
+    /// station, along with the worn item itself. Items not made of materials,
+    /// like food, can't be repaired.
+    fn repair_recipe(&self) -> Option<Recipe> {
+        let (output, amount) = &self.output;
+        if self.repair || *amount != 1 || output.max_durability().is_none() {
+            return None;
+        }
+        let materials = self
+            .inputs
+            .iter()
+            .filter_map(|(input, amount)| match input {
+                // Tools which aren't consumed are needed again
+                _ if *amount == 0 => Some((input.clone(), 0)),
+                RecipeInput::Item(def) if !matches!(def.kind, ItemKind::Ingredient { .. }) => None,
+                _ => Some((input.clone(), (amount + 1) / 2)),
+            })
+            .collect::<Vec<_>>();
+        if materials.iter().all(|(_, amount)| *amount == 0) {
+            return None;
+        }
+        let mut inputs = vec![(RecipeInput::Item(Arc::clone(output)), 0)];
+        inputs.extend(materials);
+        Some(Recipe {
+            output: (Arc::clone(output), 1),
+            inputs,
+            craft_sprite: self.craft_sprite,
+            repair: true,
+        })
+    }
+
     pub fn inputs(&self) -> impl ExactSizeIterator<Item = (&RecipeInput, u32)> {
         self.inputs
             .iter()
@@ -264,7 +325,7 @@ impl assets::Compound for RecipeBook {
             modular::append_modular_recipes(&mut raw);
         }
 
-        let recipes = raw
+        let mut recipes = raw
             .0
             .iter()
             .map(
@@ -285,10 +346,25 @@ impl assets::Compound for RecipeBook {
                         output,
                         inputs,
                         craft_sprite: *craft_sprite,
+                        repair: false,
                     }))
                 },
             )
-            .collect::<Result<_, assets::Error>>()?;
+            .collect::<Result<HashMap<_, _>, assets::Error>>()?;
+
+        // Every item with durability crafted by a recipe can be repaired. When several
+        // recipes craft the same item, the first one by name is used.
+        let mut crafting = recipes.iter().collect::<Vec<_>>();
+        crafting.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut repair_recipes = HashMap::new();
+        for (_, recipe) in crafting {
+            if let Some(repair) = recipe.repair_recipe() {
+                repair_recipes
+                    .entry(format!("repair {}", recipe.output.0.id()))
+                    .or_insert(repair);
+            }
+        }
+        recipes.extend(repair_recipes);
 
         Ok(RecipeBook { recipes })
     }
@@ -297,3 +373,37 @@ impl assets::Compound for RecipeBook {
 pub fn default_recipe_book() -> AssetHandle<RecipeBook> {
     RecipeBook::load_expect("common.recipe_book")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repair_recipes_need_worn_item_and_materials() {
+        let book = default_recipe_book().read();
+        let repairs = book
+            .iter()
+            .filter(|(_, recipe)| recipe.repair)
+            .collect::<Vec<_>>();
+        assert!(!repairs.is_empty());
+        for (name, recipe) in repairs {
+            let (output, _) = &recipe.output;
+            assert!(output.max_durability().is_some(), "{}", name);
+            assert!(
+                matches!(&recipe.inputs[0], (RecipeInput::Item(def), 0) if def.id() == output.id()),
+                "{} doesn't need the item to repair",
+                name
+            );
+            assert!(
+                recipe.inputs[1..].iter().any(|(_, amount)| *amount > 0),
+                "{} doesn't need any materials",
+                name
+            );
+        }
+        // Made of food, so it can't be repaired
+        assert!(
+            book.get("repair common.items.weapons.hammer.burnt_drumstick")
+                .is_none()
+        );
+    }
+}
@@ -95,7 +95,12 @@ impl CharacterBehavior for Data {
                     });
                 } else {
                     // Create inventory manipulation event
-                    let inv_manip = InventoryManip::Collect(self.static_data.sprite_pos);
+                    let inv_manip = match self.static_data.sprite_kind {
+                        SpriteInteractKind::Storage => {
+                            InventoryManip::OpenStorage(self.static_data.sprite_pos)
+                        },
+                        _ => InventoryManip::Collect(self.static_data.sprite_pos),
+                    };
                     output_events.emit_server(ServerEvent::InventoryManip(data.entity, inv_manip));
                     // Done
 
@@ -132,6 +137,7 @@ pub enum SpriteInteractKind {
     Chest,
     Harvestable,
     Collectible,
+    Storage,
     Fallback,
 }
 
@@ -158,6 +164,7 @@ impl From<SpriteKind> for Option<SpriteInteractKind> {
             | SpriteKind::PotionMinor
             | SpriteKind::Seashells
             | SpriteKind::Bomb => Some(SpriteInteractKind::Collectible),
+            SpriteKind::StorageChest => Some(SpriteInteractKind::Storage),
             // Collectible checked in addition to container for case that sprite requires a tool to
             // collect and cannot be collected by hand, yet still meets the container check
             _ if sprite_kind.is_container() && sprite_kind.is_collectible() => {
@@ -188,6 +195,11 @@ impl SpriteInteractKind {
                 Duration::from_secs_f32(0.5),
                 Duration::from_secs_f32(0.2),
             ),
+            Self::Storage => (
+                Duration::from_secs_f32(0.2),
+                Duration::from_secs_f32(0.3),
+                Duration::from_secs_f32(0.2),
+            ),
             Self::Fallback => (
                 Duration::from_secs_f32(5.0),
                 Duration::from_secs_f32(5.0),
@@ -0,0 +1,380 @@
+//! Combat tactics of NPCs, defined as behaviour trees in the assets of
+//! `common.tactic`.
+//!
+//! An agent fighting a target runs the [`Node`] at the root of its tactic
+//! every tick. Nodes either act (use abilities, move, update the state of the
+//! tactic) or combine other nodes, choosing between them with [`Condition`]s
+//! on the agent, its target and the state of the tactic. Each node succeeds
+//! or fails, which matters to the nodes combining it.
+//!
+//! The tactic of an NPC is set by `TacticAsset` in the meta of its
+//! `EntityConfig`, and otherwise depends on its main weapon.
+//!
+//! The nodes and conditions combining others are handled by [`Node::run`] and
+//! [`Condition::holds`], the others by the [`Tactician`] running the tactic.
+//!
+//! # Example
+//! ```ron
+//! TacticSpec(
+//!     root: Select([
+//!         // Bite when close, otherwise chase the target
+//!         ([InRange(1.0), Angle(90.0)], Sequence([Stand, Use(Primary)])),
+//!         ([], Chase),
+//!     ]),
+//! )
+//! ```
+use crate::{
+    assets::{self, Error},
+    comp::{buff::BuffKind, skills::Skill, InputKind},
+    states::utils::StageSection,
+};
+use serde::{Deserialize, Deserializer};
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TacticSpec {
+    /// Height to aim at, relative to the eyes of the target, e.g. 1.0 for
+    /// jumping attacks to land on the body or -1.0 for splash damage at the
+    /// feet
+    #[serde(default)]
+    pub aim_offset: f32,
+    /// Weight of the direction to the target when aiming leaps, from 0.0 to
+    /// leap straight down to 1.0 to leap at the target. Leaps are aimed like
+    /// other attacks if unset.
+    #[serde(default)]
+    pub leap_aim: Option<f32>,
+    pub root: Node,
+}
+
+impl assets::Asset for TacticSpec {
+    type Loader = assets::RonLoader;
+
+    const EXTENSION: &'static str = "ron";
+}
+
+/// Returns the specifiers of all the tactic assets.
+pub fn try_all_tactics() -> Result<Vec<String>, Error> {
+    let tactics = assets::load_dir::<TacticSpec>("common.tactic", true)?;
+    Ok(tactics.ids().map(|id| id.to_owned()).collect())
+}
+
+/// Checks the conditions and runs the nodes of a tactic for an agent
+pub trait Tactician {
+    /// Checks a condition, which [`Condition::holds`] only calls for the
+    /// conditions not combining others
+    fn check(&mut self, condition: &Condition) -> bool;
+
+    /// Runs a node, which [`Node::run`] only calls for the nodes not combining
+    /// others, returning whether it succeeded
+    fn act(&mut self, node: &Node) -> bool;
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub enum Node {
+    /// Runs the first branch whose conditions all hold, like an `if`/`else if`
+    /// chain. Fails if there is no such branch.
+    Select(Vec<(Vec<Condition>, Node)>),
+    /// Runs the node if the conditions all hold, and fails otherwise
+    When(Vec<Condition>, Box<Node>),
+    /// Runs the nodes in order until one fails
+    Sequence(Vec<Node>),
+    /// Runs all the nodes, succeeding if they all did
+    All(Vec<Node>),
+    /// Presses an input, e.g. `Use(Ability(0))`
+    Use(InputKind),
+    /// Presses an input, aiming the ability at the target, e.g. to blink to it
+    UseOnTarget(InputKind),
+    /// Releases an input
+    Cancel(InputKind),
+    /// Stops moving
+    Stand,
+    /// Paths toward the target, failing if there is no path
+    Chase,
+    /// Like [`Node::Chase`], keeping some distance from the allies also
+    /// fighting the target
+    ChaseSeparated,
+    /// Like [`Node::Chase`], at the given fraction of the speed
+    Approach(f32),
+    /// Moves at `speed` (from 0.0 to 1.0) in the direction of the target
+    /// rotated by `angle` degrees, e.g. 90.0 to circle it or 180.0 to kite it
+    Strafe {
+        angle: f32,
+        speed: f32,
+    },
+    /// Like [`Node::Strafe`], to the left or the right of the target as
+    /// picked at random whenever the count of the tactic is zero, which
+    /// stores the side
+    Circle {
+        angle: f32,
+        speed: f32,
+    },
+    /// Runs away from the target
+    Flee,
+    /// Flies upward
+    Fly,
+    /// Flies toward the target, descending to land next to it
+    FlyToward,
+    /// Looks straight ahead instead of at the target
+    LookForward,
+    /// Turns around at the given radians per second, instead of looking at the
+    /// target
+    Spin(f32),
+    /// Gives up on the target
+    Forget,
+    /// Increases the timer of the tactic by the duration of the tick
+    TickTimer,
+    ResetTimer,
+    /// Increases the counter of the tactic by the duration of the tick
+    TickCounter,
+    SetCounter(f32),
+    AddCounter(f32),
+    SetFlag(bool),
+    /// Sets the whole number count of the tactic
+    SetCount(u8),
+    /// Sets the count of the tactic to a random number below the given one
+    RandomCount(u8),
+    /// Decreases the count of the tactic, failing if it is already zero
+    DecCount,
+    /// Starts the timer of the given name, see [`Condition::Elapsed`]
+    StartTimer(String),
+}
+
+impl Node {
+    /// Runs the node, returning whether it succeeded
+    pub fn run(&self, tactician: &mut impl Tactician) -> bool {
+        match self {
+            Node::Select(branches) => branches
+                .iter()
+                .find(|(conditions, _)| Condition::all_hold(conditions, tactician))
+                .map_or(false, |(_, node)| node.run(tactician)),
+            Node::When(conditions, node) => {
+                Condition::all_hold(conditions, tactician) && node.run(tactician)
+            },
+            Node::Sequence(nodes) => nodes.iter().all(|node| node.run(tactician)),
+            Node::All(nodes) => nodes
+                .iter()
+                .fold(true, |success, node| node.run(tactician) && success),
+            _ => tactician.act(self),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub enum Condition {
+    /// The target is closer than the given multiple of the attack range of the
+    /// agent
+    InRange(f32),
+    /// The target is closer than the given number of blocks
+    Within(f32),
+    /// The target is within the given number of blocks of the edge of the
+    /// body of the agent, for large creatures
+    Reach(f32),
+    /// The target is within the given number of blocks beyond the attack range
+    /// of the agent
+    NearRange(f32),
+    /// The target is less than the given degrees away from where the agent
+    /// looks
+    Angle(f32),
+    /// Nothing blocks the view of the target
+    CanSee,
+    /// The target moves across the view of the agent slower than the given
+    /// blocks per second
+    TargetSlowerThan(f32),
+    /// The target moves faster than the given blocks per second
+    TargetFasterThan(f32),
+    /// The target comes closer to the agent faster than the given blocks per
+    /// second
+    TargetApproaching(f32),
+    /// The agent is more than the given number of blocks above the target
+    Above(f32),
+    /// There is no ground within the given number of blocks below the agent
+    Airborne(f32),
+    /// Something blocks the way within the given number of blocks in the
+    /// direction the agent moves
+    Blocked(f32),
+    /// The agent has more energy than given
+    Energy(f32),
+    /// The agent has less than the given fraction of its health
+    Health(f32),
+    /// The agent has a smaller fraction of its health than the counter of the
+    /// tactic, for thresholds that change during the fight
+    HealthBelowCounter,
+    HasSkill(Skill),
+    HasBuff(BuffKind),
+    /// The combo of the agent is at least the given value
+    Combo(u32),
+    Humanoid,
+    /// The agent isn't moving, e.g. when stuck
+    Still,
+    /// Holds with the given probability each tick, clamped from 0.0 to 1.0
+    Chance(#[serde(deserialize_with = "deserialize_probability")] f64),
+    /// Holds each tick with the fraction of its health the agent has as
+    /// probability
+    HealthChance,
+    /// The timer of the tactic is above the given seconds
+    Timer(f32),
+    /// The counter of the tactic is above the given value
+    Counter(f32),
+    /// The flag of the tactic is set
+    Flag,
+    /// The count of the tactic is above zero
+    Count,
+    /// The timer of the given name was never started, or at least the given
+    /// seconds ago, e.g. for cooldowns
+    Elapsed(String, f64),
+    /// The agent is in the middle of an attack
+    Attacking,
+    /// The agent is charging a ranged attack, to less than the given fraction
+    /// of the full charge yet
+    Charging(f32),
+    /// The agent is spinning, and spun fewer than the given times in a row
+    Spinning(u32),
+    /// The agent is in the character state of the given name, e.g.
+    /// `"BasicBeam"`, optionally only in or out of a stage of it and for
+    /// less than some seconds in that stage
+    State {
+        name: String,
+        #[serde(default)]
+        stage: Option<StageSection>,
+        #[serde(default)]
+        not_stage: Option<StageSection>,
+        #[serde(default)]
+        max_secs: Option<f32>,
+    },
+    Not(Box<Condition>),
+    Any(Vec<Condition>),
+    All(Vec<Condition>),
+}
+
+impl Condition {
+    /// Checks whether the condition holds
+    pub fn holds(&self, tactician: &mut impl Tactician) -> bool {
+        match self {
+            Condition::Not(condition) => !condition.holds(tactician),
+            Condition::Any(conditions) => conditions
+                .iter()
+                .any(|condition| condition.holds(tactician)),
+            Condition::All(conditions) => Condition::all_hold(conditions, tactician),
+            _ => tactician.check(self),
+        }
+    }
+
+    fn all_hold(conditions: &[Condition], tactician: &mut impl Tactician) -> bool {
+        conditions
+            .iter()
+            .all(|condition| condition.holds(tactician))
+    }
+}
+
+/// Deserializes a probability, as `gen_bool` panics outside of 0.0 to 1.0.
+/// NaN becomes 0.0 as `max` ignores it.
+fn deserialize_probability<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    f64::deserialize(deserializer).map(|probability| probability.max(0.0).min(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::{AssetExt, Loader, RonLoader};
+    use std::borrow::Cow;
+
+    /// Records the nodes run by a tactic, with the conditions holding as given
+    struct Recorder<F> {
+        holds: F,
+        actions: Vec<Node>,
+    }
+
+    impl<F: FnMut(&Condition) -> bool> Tactician for Recorder<F> {
+        fn check(&mut self, condition: &Condition) -> bool { (self.holds)(condition) }
+
+        fn act(&mut self, node: &Node) -> bool {
+            self.actions.push(node.clone());
+            true
+        }
+    }
+
+    /// Runs a tactic for a tick, returning the nodes run
+    fn run(specifier: &str, holds: impl FnMut(&Condition) -> bool) -> Vec<Node> {
+        let mut recorder = Recorder {
+            holds,
+            actions: Vec::new(),
+        };
+        TacticSpec::load_expect(specifier)
+            .read()
+            .root
+            .run(&mut recorder);
+        recorder.actions
+    }
+
+    /// Checks a condition for an agent charging its bow to the given fraction
+    fn charging_bow(charge: f32) -> impl FnMut(&Condition) -> bool {
+        move |condition| match condition {
+            Condition::State {
+                name, not_stage, ..
+            } => name == "ChargedRanged" && *not_stage != Some(StageSection::Charge),
+            Condition::Charging(fraction) => charge < *fraction,
+            Condition::Within(_) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_all_tactic_assets() {
+        for tactic in try_all_tactics().expect("Failed to access tactics directory") {
+            println!("{}:", &tactic);
+            std::mem::drop(TacticSpec::load_expect(&tactic));
+        }
+    }
+
+    #[test]
+    fn chance_is_clamped() {
+        let parse = |ron: &str| {
+            <RonLoader as Loader<Condition>>::load(Cow::Borrowed(ron.as_bytes()), "ron")
+                .expect("Failed to parse condition")
+        };
+        assert_eq!(parse("Chance(1.5)"), Condition::Chance(1.0));
+        assert_eq!(parse("Chance(-0.5)"), Condition::Chance(0.0));
+        assert_eq!(parse("Chance(0.25)"), Condition::Chance(0.25));
+    }
+
+    #[test]
+    fn bow_releases_charged_shots() {
+        let actions = run("common.tactic.bow", charging_bow(0.3));
+        assert_eq!(actions, vec![Node::Use(InputKind::Primary), Node::Chase]);
+        let actions = run("common.tactic.bow", charging_bow(0.7));
+        assert_eq!(actions, vec![Node::Chase]);
+    }
+
+    #[test]
+    fn circle_charge_stops_circling_at_obstacles() {
+        let circling = |blocked: bool| {
+            run(
+                "common.tactic.quad_med_quick",
+                move |condition| match condition {
+                    Condition::NearRange(_) => true,
+                    Condition::Blocked(_) => blocked,
+                    _ => false,
+                },
+            )
+        };
+        assert_eq!(circling(false), vec![
+            Node::Circle {
+                angle: 84.6,
+                speed: 1.0
+            },
+            Node::TickCounter,
+        ]);
+        assert_eq!(circling(true), vec![Node::SetCounter(3.0)]);
+    }
+
+    #[test]
+    fn mindflayer_summons_below_health_threshold() {
+        let actions = run("common.tactic.mindflayer", |condition| {
+            matches!(condition, Condition::HealthBelowCounter)
+        });
+        assert_eq!(actions, vec![
+            Node::SetCounter(0.8),
+            Node::SetFlag(true),
+            Node::Use(InputKind::Ability(2)),
+        ]);
+    }
+}
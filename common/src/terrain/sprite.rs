@@ -190,6 +190,7 @@ make_case_elim!(
         Bomb = 0xA3,
         ChristmasOrnament = 0xA4,
         ChristmasWreath = 0xA5,
+        StorageChest = 0xA6,
     }
 );
 
@@ -212,6 +213,7 @@ impl SpriteKind {
             SpriteKind::DungeonChest3 => 1.09,
             SpriteKind::DungeonChest4 => 1.09,
             SpriteKind::DungeonChest5 => 1.09,
+            SpriteKind::StorageChest => 1.09,
             SpriteKind::StreetLamp => 2.65,
             SpriteKind::Carrot => 0.18,
             SpriteKind::Radish => 0.18,
@@ -455,6 +457,7 @@ impl SpriteKind {
                 | SpriteKind::DismantlingBench
                 | SpriteKind::ChristmasOrnament
                 | SpriteKind::ChristmasWreath
+                | SpriteKind::StorageChest
         )
     }
 }
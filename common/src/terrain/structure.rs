@@ -6,13 +6,13 @@ use crate::{
     volumes::dyna::{Dyna, DynaError},
 };
 use hashbrown::HashMap;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{num::NonZeroU8, sync::Arc};
 use vek::*;
 
 make_case_elim!(
     structure_block,
-    #[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+    #[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
     #[repr(u8)]
     pub enum StructureBlock {
         None = 0,
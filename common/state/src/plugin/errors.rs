@@ -1,5 +1,5 @@
 use bincode::ErrorKind;
-use wasmer::{ExportError, InstantiationError, RuntimeError};
+use wasmer::{CompileError, ExportError, InstantiationError, RuntimeError};
 
 #[derive(Debug)]
 pub enum PluginError {
@@ -9,19 +9,34 @@ pub enum PluginError {
     NoSuchModule,
     Encoding(Box<ErrorKind>),
     PluginModuleError(String, String, PluginModuleError),
+    Watcher(notify::Error),
+    /// The plugins sent by the server don't match the advertised hashes
+    HashMismatch,
 }
 
 #[derive(Debug)]
 pub enum PluginModuleError {
+    Compile(CompileError),
     InstantiationError(InstantiationError),
     MemoryAllocation(MemoryAllocationError),
     MemoryUninit(ExportError),
     FindFunction(ExportError),
     RunFunction(RuntimeError),
+    /// The event used more than the plugin `fuel` limit
+    FuelExhausted,
+    /// The event tried to grow the memory past the plugin memory limit
+    MemoryLimitReached(RuntimeError),
     InvalidArgumentType(),
     Encoding(Box<ErrorKind>),
 }
 
+impl PluginModuleError {
+    /// Whether the module went over the limits set in its plugin manifest
+    pub fn is_limit_exceeded(&self) -> bool {
+        matches!(self, Self::FuelExhausted | Self::MemoryLimitReached(_))
+    }
+}
+
 #[derive(Debug)]
 pub enum MemoryAllocationError {
     InvalidReturnType,
@@ -7,18 +7,30 @@ use specs::{
 use wasmer::{Function, Memory, Value};
 
 use common::{
-    comp::{Health, Player},
+    comp::{Health, Inventory, Player, Pos, Stats},
+    event::EventBus,
     uid::{Uid, UidAllocator},
 };
+use plugin_api::Action;
 
-use super::errors::{MemoryAllocationError, PluginModuleError};
+use super::{
+    errors::{MemoryAllocationError, PluginModuleError},
+    storage::PluginStorage,
+};
 
 pub struct EcsWorld<'a, 'b> {
     pub entities: &'b Entities<'a>,
     pub health: EcsComponentAccess<'a, 'b, Health>,
     pub uid: EcsComponentAccess<'a, 'b, Uid>,
     pub player: EcsComponentAccess<'a, 'b, Player>,
+    pub pos: EcsComponentAccess<'a, 'b, Pos>,
+    pub inventory: EcsComponentAccess<'a, 'b, Inventory>,
+    pub stats: EcsComponentAccess<'a, 'b, Stats>,
     pub uid_allocator: &'b Read<'a, UidAllocator>,
+    /// Queue of the actions emitted by plugins, they are applied by the
+    /// server at the start of the next event handling
+    pub plugin_actions: &'b EventBus<Action>,
+    pub plugin_storage: &'b PluginStorage,
 }
 
 pub enum EcsComponentAccess<'a, 'b, T: Component> {
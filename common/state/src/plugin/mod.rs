@@ -1,33 +1,81 @@
 pub mod errors;
 pub mod memory_manager;
 pub mod module;
+pub mod storage;
+mod tunables;
 pub mod wasm_env;
 
-use common::assets::ASSETS_PATH;
+use common::{assets::ASSETS_PATH, event::EventBus, resources::GameMode, uid::UidAllocator};
+use common_net::msg::PluginHash;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use specs::WorldExt;
 use std::{
     collections::{HashMap, HashSet},
     fs,
     io::Read,
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use wasmer::Pages;
 
-use plugin_api::Event;
+use plugin_api::{event::PluginLoadEvent, Action, Event};
 
 use self::{
     errors::PluginError,
     memory_manager::EcsWorld,
     module::{PluginModule, PreparedEventQuery},
+    storage::PluginStorage,
 };
 
 use rayon::prelude::*;
 
+/// How long a plugin archive must stay untouched before being reloaded, to
+/// avoid reading a file that is still being written
+const PLUGIN_RELOAD_DELAY: Duration = Duration::from_millis(300);
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PluginData {
     name: String,
     modules: HashSet<PathBuf>,
     dependencies: HashSet<String>,
+    #[serde(default)]
+    limits: PluginLimits,
+    /// Whether clients download and run the plugin too. Other plugins only
+    /// run on the server and are never sent to clients.
+    #[serde(default)]
+    client: bool,
+}
+
+/// Resources every module of a plugin may use, set in the `[limits]` table of
+/// the `plugin.toml` manifest. A plugin going over them is disabled.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginLimits {
+    /// Maximum number of wasm operators a module can run for a single event
+    pub fuel: u64,
+    /// Maximum size of the memory of a module, in MiB
+    pub max_memory_mib: u32,
+}
+
+impl Default for PluginLimits {
+    fn default() -> Self {
+        Self {
+            fuel: 10_000_000,
+            max_memory_mib: 64,
+        }
+    }
+}
+
+impl PluginLimits {
+    /// The memory limit as a number of 64KiB wasm pages
+    pub fn memory_pages(&self) -> Pages { Pages(self.max_memory_mib.saturating_mul(16)) }
 }
 
 #[derive(Clone)]
@@ -36,9 +84,23 @@ pub struct Plugin {
     modules: Vec<PluginModule>,
     #[allow(dead_code)]
     files: HashMap<PathBuf, Vec<u8>>,
+    /// The raw archive, kept so the server can send it to clients
+    archive: Vec<u8>,
+    hash: PluginHash,
+    /// The archive this plugin was loaded from, if any
+    path: Option<PathBuf>,
+    /// Set when a module went over the plugin limits, no more events are sent
+    /// to the plugin until it's reloaded
+    disabled: Arc<AtomicBool>,
 }
 
 impl Plugin {
+    pub fn from_path(path: &Path) -> Result<Self, PluginError> {
+        let mut plugin = Self::from_reader(fs::File::open(path).map_err(PluginError::Io)?)?;
+        plugin.path = Some(path.to_owned());
+        Ok(plugin)
+    }
+
     pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, PluginError> {
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf).map_err(PluginError::Io)?;
@@ -69,7 +131,7 @@ impl Plugin {
             .iter()
             .map(|path| {
                 let wasm_data = files.remove(path).ok_or(PluginError::NoSuchModule)?;
-                PluginModule::new(data.name.to_owned(), &wasm_data).map_err(|e| {
+                PluginModule::new(data.name.to_owned(), &wasm_data, &data.limits).map_err(|e| {
                     PluginError::PluginModuleError(data.name.to_owned(), "<init>".to_owned(), e)
                 })
             })
@@ -79,9 +141,19 @@ impl Plugin {
             data,
             modules,
             files,
+            hash: hash_archive(&buf),
+            archive: buf,
+            path: None,
+            disabled: Arc::new(AtomicBool::new(false)),
         })
     }
 
+    pub fn is_disabled(&self) -> bool { self.disabled.load(Ordering::Relaxed) }
+
+    pub fn hash(&self) -> PluginHash { self.hash }
+
+    pub fn archive(&self) -> &[u8] { &self.archive }
+
     pub fn execute_prepared<T>(
         &self,
         ecs: &EcsWorld,
@@ -93,23 +165,124 @@ impl Plugin {
         self.modules
             .iter()
             .flat_map(|module| {
-                module.try_execute(ecs, event).map(|x| {
-                    x.map_err(|e| {
+                if self.is_disabled() {
+                    return None;
+                }
+                match module.try_execute(ecs, event)? {
+                    Err(e) if e.is_limit_exceeded() => {
+                        error!(
+                            ?e,
+                            "Plugin '{}' went over its limits while running '{}', disabling it",
+                            self.data.name,
+                            event.get_function_name()
+                        );
+                        self.disabled.store(true, Ordering::Relaxed);
+                        None
+                    },
+                    result => Some(result.map_err(|e| {
                         PluginError::PluginModuleError(
                             self.data.name.to_owned(),
                             event.get_function_name().to_owned(),
                             e,
                         )
-                    })
-                })
+                    })),
+                }
             })
             .collect::<Result<Vec<_>, _>>()
     }
 }
 
+/// This structure watches a plugin directory for changed archives
+struct PluginWatcher {
+    _watcher: RecommendedWatcher,
+    events: mpsc::Receiver<PathBuf>,
+    /// Changed archives with the time of their last change
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl PluginWatcher {
+    fn new(dir: &Path) -> Result<Self, PluginError> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+                Ok(event) => {
+                    if let EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) =
+                        event.kind
+                    {
+                        event
+                            .paths
+                            .into_iter()
+                            .filter(|path| is_plugin_archive(path))
+                            .for_each(|path| {
+                                let _ = sender.send(path);
+                            });
+                    }
+                },
+                Err(e) => error!(?e, "Plugin watcher error"),
+            })
+            .map_err(PluginError::Watcher)?;
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(PluginError::Watcher)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Returns the archives that changed and weren't touched for
+    /// [`PLUGIN_RELOAD_DELAY`]
+    fn changed_files(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        for path in self.events.try_iter() {
+            self.pending.insert(path, now);
+        }
+        let changed = self
+            .pending
+            .iter()
+            .filter(|(_, last_change)| now.duration_since(**last_change) >= PLUGIN_RELOAD_DELAY)
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        for path in &changed {
+            self.pending.remove(path);
+        }
+        changed
+    }
+}
+
+/// Hashes a plugin archive, this is how the server and its clients identify
+/// the plugins to download
+pub fn hash_archive(archive: &[u8]) -> PluginHash { Sha256::digest(archive).into() }
+
+fn is_plugin_archive(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(|s| s.ends_with(".plugin.tar"))
+        .unwrap_or(false)
+}
+
+/// Borrows everything plugins may retrieve from the given ECS
+fn with_ecs_world<R>(ecs: &specs::World, f: impl FnOnce(&EcsWorld) -> R) -> R {
+    f(&EcsWorld {
+        entities: &ecs.entities(),
+        health: ecs.read_component().into(),
+        uid: ecs.read_component().into(),
+        uid_allocator: &ecs.read_resource::<UidAllocator>().into(),
+        player: ecs.read_component().into(),
+        pos: ecs.read_component().into(),
+        inventory: ecs.read_component().into(),
+        stats: ecs.read_component().into(),
+        plugin_actions: &ecs.read_resource::<EventBus<Action>>(),
+        plugin_storage: &ecs.read_resource::<PluginStorage>(),
+    })
+}
+
 #[derive(Clone, Default)]
 pub struct PluginMgr {
     plugins: Vec<Plugin>,
+    watcher: Option<Arc<Mutex<PluginWatcher>>>,
 }
 
 impl PluginMgr {
@@ -149,22 +322,139 @@ impl PluginMgr {
         self.execute_prepared(ecs, &PreparedEventQuery::new(event)?)
     }
 
+    /// Same as [`PluginMgr::execute_event`] but borrows everything plugins
+    /// may retrieve from the given ECS. Must not be called while any of the
+    /// [`EcsWorld`] components are mutably borrowed.
+    pub fn execute_event_in_ecs<T>(
+        &self,
+        ecs: &specs::World,
+        event: &T,
+    ) -> Result<Vec<T::Response>, PluginError>
+    where
+        T: Event,
+    {
+        with_ecs_world(ecs, |ecs_world| self.execute_event(ecs_world, event))
+    }
+
+    /// Returns the plugin archives that changed on disk since the last call.
+    /// Always empty if the plugin directory isn't watched.
+    pub fn changed_files(&self) -> Vec<PathBuf> {
+        self.watcher
+            .as_ref()
+            .map_or_else(Vec::new, |watcher| watcher.lock().unwrap().changed_files())
+    }
+
+    /// Unloads the plugins loaded from the given archives and loads them
+    /// again, running their `on_load` event. Archives that were removed are
+    /// only unloaded.
+    pub fn reload(&mut self, ecs: &specs::World, paths: &[PathBuf], game_mode: GameMode) {
+        for path in paths {
+            self.plugins.retain(|plugin| {
+                let unchanged =
+                    plugin.path.as_ref().and_then(|p| p.file_name()) != path.file_name();
+                if !unchanged {
+                    info!("Unloading plugin '{}'", plugin.data.name);
+                }
+                unchanged
+            });
+
+            if !path.is_file() {
+                continue;
+            }
+            info!("Reloading plugin at {:?}", path);
+            match Plugin::from_path(path) {
+                Ok(plugin) => self.load(ecs, plugin, game_mode),
+                // The old version was unloaded already, so the plugin stays disabled until
+                // the archive is fixed
+                Err(e) => error!(?e, ?path, "Failed to reload plugin, it stays unloaded"),
+            }
+        }
+    }
+
+    /// Hashes of the loaded client plugins that aren't disabled, advertised by
+    /// the server to its clients
+    pub fn plugin_hashes(&self) -> Vec<PluginHash> {
+        self.plugins
+            .iter()
+            .filter(|plugin| plugin.data.client && !plugin.is_disabled())
+            .map(Plugin::hash)
+            .collect()
+    }
+
+    /// Returns the archive of the loaded client plugin with the given hash
+    pub fn find_archive(&self, hash: &PluginHash) -> Option<&[u8]> {
+        self.plugins
+            .iter()
+            .find(|plugin| plugin.data.client && &plugin.hash == hash)
+            .map(Plugin::archive)
+    }
+
+    /// Returns the hashes of plugins that aren't loaded yet
+    pub fn missing_plugins(&self, hashes: &[PluginHash]) -> Vec<PluginHash> {
+        hashes
+            .iter()
+            .filter(|hash| self.find_archive(hash).is_none())
+            .copied()
+            .collect()
+    }
+
+    /// Loads plugins downloaded from the server, running their `on_load`
+    /// event. Archives are checked against the expected hashes first, a
+    /// single mismatching archive rejects them all.
+    pub fn load_server_plugins(
+        &mut self,
+        ecs: &specs::World,
+        expected: &[PluginHash],
+        archives: Vec<Vec<u8>>,
+        game_mode: GameMode,
+    ) -> Result<(), PluginError> {
+        let mut hashes = archives
+            .iter()
+            .map(|archive| hash_archive(archive))
+            .collect::<Vec<_>>();
+        hashes.sort_unstable();
+        let mut expected = expected.to_vec();
+        expected.sort_unstable();
+        if hashes != expected {
+            return Err(PluginError::HashMismatch);
+        }
+
+        for archive in archives {
+            let plugin = Plugin::from_reader(&*archive)?;
+            info!("Loading plugin '{}' from the server", plugin.data.name);
+            self.load(ecs, plugin, game_mode);
+        }
+        Ok(())
+    }
+
+    /// Runs the `on_load` event of the plugin and adds it to the loaded ones
+    fn load(&mut self, ecs: &specs::World, plugin: Plugin, game_mode: GameMode) {
+        let loaded = PreparedEventQuery::new(&PluginLoadEvent { game_mode }).and_then(|event| {
+            with_ecs_world(ecs, |ecs_world| plugin.execute_prepared(ecs_world, &event))
+        });
+        match loaded {
+            Ok(_) => {
+                info!(
+                    "Loaded plugin '{}' with {} module(s)",
+                    plugin.data.name,
+                    plugin.modules.len()
+                );
+                self.plugins.push(plugin);
+            },
+            Err(e) => error!(?e, "Failed to run init of plugin '{}'", plugin.data.name),
+        }
+    }
+
     pub fn from_dir<P: AsRef<Path>>(path: P) -> Result<Self, PluginError> {
-        let plugins = fs::read_dir(path)
+        let plugins = fs::read_dir(&path)
             .map_err(PluginError::Io)?
             .filter_map(|e| e.ok())
             .map(|entry| {
                 if entry.file_type().map(|ft| ft.is_file()).unwrap_or(false)
-                    && entry
-                        .path()
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .map(|s| s.ends_with(".plugin.tar"))
-                        .unwrap_or(false)
+                    && is_plugin_archive(&entry.path())
                 {
                     info!("Loading plugin at {:?}", entry.path());
-                    Plugin::from_reader(fs::File::open(entry.path()).map_err(PluginError::Io)?)
-                        .map(Some)
+                    Plugin::from_path(&entry.path()).map(Some)
                 } else {
                     Ok(None)
                 }
@@ -183,6 +473,18 @@ impl PluginMgr {
             );
         }
 
-        Ok(Self { plugins })
+        // Plugins still work without hot-reloading
+        let watcher = match PluginWatcher::new(path.as_ref()) {
+            Ok(watcher) => Some(Arc::new(Mutex::new(watcher))),
+            Err(e) => {
+                warn!(
+                    ?e,
+                    "Failed to watch the plugin directory, hot-reloading is disabled"
+                );
+                None
+            },
+        };
+
+        Ok(Self { plugins, watcher })
     }
 }
@@ -5,16 +5,28 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use specs::saveload::MarkerAllocator;
-use wasmer::{imports, Cranelift, Function, Instance, Memory, Module, Store, Universal, Value};
+use common::uid::Uid;
+use specs::{saveload::MarkerAllocator, Component, Entity, Join};
+use wasmer::{
+    imports, wasmparser::Operator, BaseTunables, CompilerConfig, Cranelift, Function, Instance,
+    Memory, Module, Pages, Store, Target, Universal, Value,
+};
+use wasmer_middlewares::{
+    metering::{get_remaining_points, set_remaining_points, MeteringPoints},
+    Metering,
+};
 
 use super::{
     errors::{PluginError, PluginModuleError},
-    memory_manager::{self, EcsAccessManager, EcsWorld, MemoryManager},
+    memory_manager::{self, EcsAccessManager, EcsComponentAccess, EcsWorld, MemoryManager},
+    tunables::LimitingTunables,
     wasm_env::HostFunctionEnvironement,
+    PluginLimits,
 };
 
-use plugin_api::{Action, EcsAccessError, Event, Retrieve, RetrieveError, RetrieveResult};
+use plugin_api::{
+    Action, EcsAccessError, EntityStats, Event, ItemStack, Retrieve, RetrieveError, RetrieveResult,
+};
 
 #[derive(Clone)]
 /// This structure represent the WASM State of the plugin.
@@ -25,34 +37,51 @@ pub struct PluginModule {
     events: HashSet<String>,
     allocator: Function,
     memory: Memory,
+    fuel: u64,
+    memory_limit: Pages,
     #[allow(dead_code)]
     name: String,
 }
 
 impl PluginModule {
     /// This function takes bytes from a WASM File and compile them
-    pub fn new(name: String, wasm_data: &[u8]) -> Result<Self, PluginModuleError> {
+    pub fn new(
+        name: String,
+        wasm_data: &[u8],
+        limits: &PluginLimits,
+    ) -> Result<Self, PluginModuleError> {
+        // Every operator costs one point, the points are reset before each event
+        let metering = Arc::new(Metering::new(limits.fuel, |_: &Operator| 1));
+        let mut compiler = Cranelift::default();
+        compiler.push_middleware(metering);
         // This is creating the engine is this case a JIT based on Cranelift
-        let engine = Universal::new(Cranelift::default()).engine();
-        // We are creating an enironnement
-        let store = Store::new(&engine);
+        let engine = Universal::new(compiler).engine();
+        // We are creating an enironnement, with the memory capped to the plugin limit
+        let memory_limit = limits.memory_pages();
+        let tunables =
+            LimitingTunables::new(BaseTunables::for_target(&Target::default()), memory_limit);
+        let store = Store::new_with_tunables(&engine, tunables);
         // We are compiling the WASM file in the previously generated environement
-        let module = Module::new(&store, &wasm_data).expect("Can't compile");
+        let module = Module::new(&store, &wasm_data).map_err(PluginModuleError::Compile)?;
 
         // This is the function imported into the wasm environement
         fn raw_emit_actions(env: &HostFunctionEnvironement, ptr: i64, len: i64) {
-            handle_actions(match env.read_data(from_i64(ptr), from_i64(len)) {
-                Ok(e) => e,
-                Err(e) => {
-                    tracing::error!(?e, "Can't decode action");
-                    return;
+            handle_actions(
+                &env.name,
+                &env.ecs,
+                match env.read_data(from_i64(ptr), from_i64(len)) {
+                    Ok(e) => e,
+                    Err(e) => {
+                        tracing::error!(?e, "Can't decode action");
+                        return;
+                    },
                 },
-            });
+            );
         }
 
         fn raw_retrieve_action(env: &HostFunctionEnvironement, ptr: i64, len: i64) -> i64 {
             let out = match env.read_data(from_i64(ptr), from_i64(len)) {
-                Ok(data) => retrieve_action(&env.ecs, data),
+                Ok(data) => retrieve_action(&env.name, &env.ecs, data),
                 Err(e) => Err(RetrieveError::BincodeError(e.to_string())),
             };
 
@@ -99,6 +128,8 @@ impl PluginModule {
                 .map(|(name, _)| name.to_string())
                 .collect(),
             wasm_state: Arc::new(Mutex::new(instance)),
+            fuel: limits.fuel,
+            memory_limit,
             name,
         })
     }
@@ -184,6 +215,9 @@ fn execute_raw(
     event_name: &str,
     bytes: &[u8],
 ) -> Result<Vec<u8>, PluginModuleError> {
+    // The metering points are shared by the allocation and the event itself
+    set_remaining_points(instance, module.fuel);
+
     // This write into memory `bytes` using allocation if necessary returning a
     // pointer and a length
 
@@ -203,7 +237,15 @@ fn execute_raw(
 
     let function_result = func
         .call(&[Value::I64(to_i64(mem_position)), Value::I64(to_i64(len))])
-        .map_err(PluginModuleError::RunFunction)?;
+        .map_err(|e| {
+            if let MeteringPoints::Exhausted = get_remaining_points(instance) {
+                PluginModuleError::FuelExhausted
+            } else if module.memory.size() >= module.memory_limit {
+                PluginModuleError::MemoryLimitReached(e)
+            } else {
+                PluginModuleError::RunFunction(e)
+            }
+        })?;
 
     // Waiting for `multi-value` to be added to LLVM. So we encode a pointer to a
     // u128 that represent [u64; 2]
@@ -228,62 +270,101 @@ fn execute_raw(
 }
 
 fn retrieve_action(
+    plugin_name: &str,
     ecs: &EcsAccessManager,
     action: Retrieve,
 ) -> Result<RetrieveResult, RetrieveError> {
+    // Safety: No reference is leaked out the function so it is safe.
+    let world = unsafe {
+        ecs.get().ok_or(RetrieveError::EcsAccessError(
+            EcsAccessError::EcsPointerNotAvailable,
+        ))?
+    };
     match action {
         Retrieve::GetPlayerName(e) => {
-            // Safety: No reference is leaked out the function so it is safe.
-            let world = unsafe {
-                ecs.get().ok_or(RetrieveError::EcsAccessError(
-                    EcsAccessError::EcsPointerNotAvailable,
-                ))?
-            };
-            let player = world.uid_allocator.retrieve_entity_internal(e.0).ok_or(
-                RetrieveError::EcsAccessError(EcsAccessError::EcsEntityNotFound(e)),
-            )?;
-
+            let player = retrieve_entity(world, e)?;
             Ok(RetrieveResult::GetPlayerName(
-                world
-                    .player
-                    .get(player)
-                    .ok_or_else(|| {
-                        RetrieveError::EcsAccessError(EcsAccessError::EcsComponentNotFound(
-                            e,
-                            "Player".to_owned(),
-                        ))
-                    })?
+                retrieve_component(&world.player, player, e, "Player")?
                     .alias
                     .to_owned(),
             ))
         },
         Retrieve::GetEntityHealth(e) => {
-            // Safety: No reference is leaked out the function so it is safe.
-            let world = unsafe {
-                ecs.get().ok_or(RetrieveError::EcsAccessError(
-                    EcsAccessError::EcsPointerNotAvailable,
-                ))?
-            };
-            let player = world.uid_allocator.retrieve_entity_internal(e.0).ok_or(
-                RetrieveError::EcsAccessError(EcsAccessError::EcsEntityNotFound(e)),
-            )?;
+            let player = retrieve_entity(world, e)?;
             Ok(RetrieveResult::GetEntityHealth(
-                world
-                    .health
-                    .get(player)
-                    .ok_or_else(|| {
-                        RetrieveError::EcsAccessError(EcsAccessError::EcsComponentNotFound(
-                            e,
-                            "Health".to_owned(),
-                        ))
-                    })?
-                    .clone(),
+                retrieve_component(&world.health, player, e, "Health")?.clone(),
+            ))
+        },
+        Retrieve::GetEntityPosition(e) => {
+            let entity = retrieve_entity(world, e)?;
+            Ok(RetrieveResult::GetEntityPosition(
+                retrieve_component(&world.pos, entity, e, "Pos")?.0,
+            ))
+        },
+        Retrieve::GetEntityInventory(e) => {
+            let entity = retrieve_entity(world, e)?;
+            Ok(RetrieveResult::GetEntityInventory(
+                retrieve_component(&world.inventory, entity, e, "Inventory")?
+                    .slots()
+                    .flatten()
+                    .map(|item| ItemStack {
+                        item: item.item_definition_id().to_owned(),
+                        amount: item.amount(),
+                    })
+                    .collect(),
             ))
         },
+        Retrieve::GetEntityStats(e) => {
+            let entity = retrieve_entity(world, e)?;
+            let stats = retrieve_component(&world.stats, entity, e, "Stats")?;
+            Ok(RetrieveResult::GetEntityStats(EntityStats {
+                name: stats.name.clone(),
+                damage_reduction: stats.damage_reduction,
+                move_speed_modifier: stats.move_speed_modifier,
+                attack_speed_modifier: stats.attack_speed_modifier,
+                friction_modifier: stats.friction_modifier,
+            }))
+        },
+        Retrieve::GetNearbyEntities { pos, radius } => Ok(RetrieveResult::GetNearbyEntities(
+            world
+                .entities
+                .join()
+                .filter(|entity| {
+                    world
+                        .pos
+                        .get(*entity)
+                        .map_or(false, |p| p.0.distance_squared(pos) <= radius.powi(2))
+                })
+                .filter_map(|entity| world.uid.get(entity).copied())
+                .collect(),
+        )),
+        Retrieve::GetStorageValue(key) => Ok(RetrieveResult::GetStorageValue(
+            world.plugin_storage.get(plugin_name, &key),
+        )),
     }
 }
 
-fn handle_actions(actions: Vec<Action>) {
+fn retrieve_entity(world: &EcsWorld, uid: Uid) -> Result<Entity, RetrieveError> {
+    world
+        .uid_allocator
+        .retrieve_entity_internal(uid.0)
+        .ok_or(RetrieveError::EcsAccessError(
+            EcsAccessError::EcsEntityNotFound(uid),
+        ))
+}
+
+fn retrieve_component<'c, T: Component>(
+    storage: &'c EcsComponentAccess<T>,
+    entity: Entity,
+    uid: Uid,
+    name: &str,
+) -> Result<&'c T, RetrieveError> {
+    storage.get(entity).ok_or_else(|| {
+        RetrieveError::EcsAccessError(EcsAccessError::EcsComponentNotFound(uid, name.to_owned()))
+    })
+}
+
+fn handle_actions(plugin_name: &str, ecs: &EcsAccessManager, actions: Vec<Action>) {
     for action in actions {
         match action {
             Action::ServerClose => {
@@ -293,12 +374,32 @@ fn handle_actions(actions: Vec<Action>) {
             Action::Print(e) => {
                 tracing::info!("{}", e);
             },
-            Action::PlayerSendMessage(a, b) => {
-                tracing::info!("SendMessage {} -> {}", a, b);
+            // The storage is namespaced by plugin name, which is only known here
+            Action::SetStorageValue { key, value } => {
+                set_storage_value(plugin_name, ecs, key, Some(value))
             },
-            Action::KillEntity(e) => {
-                tracing::info!("Kill Entity {}", e);
+            Action::RemoveStorageValue(key) => set_storage_value(plugin_name, ecs, key, None),
+            // Everything else modifies the ECS, so it is queued to be applied by the server
+            action => {
+                // Safety: No reference is leaked out the function so it is safe.
+                match unsafe { ecs.get() } {
+                    Some(world) => world.plugin_actions.emit_now(action),
+                    None => tracing::error!(?action, "Can't queue action, ECS isn't available"),
+                }
             },
         }
     }
 }
+
+fn set_storage_value(
+    plugin_name: &str,
+    ecs: &EcsAccessManager,
+    key: String,
+    value: Option<Vec<u8>>,
+) {
+    // Safety: No reference is leaked out the function so it is safe.
+    match unsafe { ecs.get() } {
+        Some(world) => world.plugin_storage.set(plugin_name, key, value),
+        None => tracing::error!(?key, "Can't edit plugin storage, ECS isn't available"),
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+};
+
+/// A change made by a plugin to its storage, `None` meaning that the key was
+/// removed
+#[derive(Debug, Clone)]
+pub struct PluginStorageChange {
+    pub plugin: String,
+    pub key: String,
+    pub value: Option<Vec<u8>>,
+}
+
+/// This structure holds the key/value store of every plugin, namespaced by
+/// plugin name. Values are read and written in memory, the changes are
+/// collected so the server can persist them.
+#[derive(Default)]
+pub struct PluginStorage {
+    values: RwLock<HashMap<String, HashMap<String, Vec<u8>>>>,
+    changes: Mutex<Vec<PluginStorageChange>>,
+}
+
+impl PluginStorage {
+    /// Replaces the whole storage content with `(plugin, key, value)` entries,
+    /// without recording any change
+    pub fn load(&self, entries: impl IntoIterator<Item = (String, String, Vec<u8>)>) {
+        let mut values = self.values.write().unwrap();
+        values.clear();
+        for (plugin, key, value) in entries {
+            values.entry(plugin).or_default().insert(key, value);
+        }
+    }
+
+    pub fn get(&self, plugin: &str, key: &str) -> Option<Vec<u8>> {
+        self.values
+            .read()
+            .unwrap()
+            .get(plugin)
+            .and_then(|values| values.get(key))
+            .cloned()
+    }
+
+    /// Sets or removes (if `value` is `None`) a value of a plugin
+    pub fn set(&self, plugin: &str, key: String, value: Option<Vec<u8>>) {
+        {
+            let mut values = self.values.write().unwrap();
+            match &value {
+                Some(value) => {
+                    values
+                        .entry(plugin.to_owned())
+                        .or_default()
+                        .insert(key.clone(), value.clone());
+                },
+                None => {
+                    if let Some(values) = values.get_mut(plugin) {
+                        values.remove(&key);
+                    }
+                },
+            }
+        }
+        self.changes.lock().unwrap().push(PluginStorageChange {
+            plugin: plugin.to_owned(),
+            key,
+            value,
+        });
+    }
+
+    /// Returns the changes made since the last call, in order
+    pub fn take_changes(&self) -> Vec<PluginStorageChange> {
+        std::mem::take(&mut *self.changes.lock().unwrap())
+    }
+}
@@ -1,9 +1,5 @@
 #[cfg(feature = "plugins")]
-use crate::plugin::memory_manager::EcsWorld;
-#[cfg(feature = "plugins")]
 use crate::plugin::PluginMgr;
-#[cfg(feature = "plugins")]
-use common::uid::UidAllocator;
 use common::{
     calendar::Calendar,
     comp,
@@ -236,19 +232,14 @@ impl State {
 
         // Load plugins from asset directory
         #[cfg(feature = "plugins")]
+        ecs.insert(EventBus::<plugin_api::Action>::default());
+        #[cfg(feature = "plugins")]
+        ecs.insert(crate::plugin::storage::PluginStorage::default());
+        #[cfg(feature = "plugins")]
         ecs.insert(match PluginMgr::from_assets() {
             Ok(plugin_mgr) => {
-                let ecs_world = EcsWorld {
-                    entities: &ecs.entities(),
-                    health: ecs.read_component().into(),
-                    uid: ecs.read_component().into(),
-                    uid_allocator: &ecs.read_resource::<UidAllocator>().into(),
-                    player: ecs.read_component().into(),
-                };
                 if let Err(e) = plugin_mgr
-                    .execute_event(&ecs_world, &plugin_api::event::PluginLoadEvent {
-                        game_mode,
-                    })
+                    .execute_event_in_ecs(&ecs, &plugin_api::event::PluginLoadEvent { game_mode })
                 {
                     tracing::debug!(?e, "Failed to run plugin init");
                     tracing::info!("Plugins disabled, enable debug logging for more information.");
@@ -479,6 +470,36 @@ impl State {
         self.ecs.write_resource::<TerrainChanges>().modified_blocks = modified_blocks;
     }
 
+    /// Reload the plugins whose archive changed on disk.
+    #[cfg(feature = "plugins")]
+    fn reload_changed_plugins(&mut self) {
+        let changed = self.ecs.read_resource::<PluginMgr>().changed_files();
+        if changed.is_empty() {
+            return;
+        }
+        let game_mode = *self.ecs.read_resource::<GameMode>();
+        // The manager is taken out of the ECS so plugins can be loaded with access to
+        // it
+        let mut plugin_mgr = std::mem::take(&mut *self.ecs.write_resource::<PluginMgr>());
+        plugin_mgr.reload(&self.ecs, &changed, game_mode);
+        *self.ecs.write_resource::<PluginMgr>() = plugin_mgr;
+    }
+
+    /// Load the plugins downloaded from the server, checking them against the
+    /// hashes it advertised.
+    #[cfg(feature = "plugins")]
+    pub fn load_server_plugins(
+        &mut self,
+        expected: &[common_net::msg::PluginHash],
+        archives: Vec<Vec<u8>>,
+    ) -> Result<(), crate::plugin::errors::PluginError> {
+        let game_mode = *self.ecs.read_resource::<GameMode>();
+        let mut plugin_mgr = std::mem::take(&mut *self.ecs.write_resource::<PluginMgr>());
+        let result = plugin_mgr.load_server_plugins(&self.ecs, expected, archives, game_mode);
+        *self.ecs.write_resource::<PluginMgr>() = plugin_mgr;
+        result
+    }
+
     /// Execute a single tick, simulating the game state by the given duration.
     pub fn tick(
         &mut self,
@@ -496,6 +517,9 @@ impl State {
         // important physics events.
         self.ecs.write_resource::<DeltaTime>().0 = dt.as_secs_f32().min(MAX_DELTA_TIME);
 
+        #[cfg(feature = "plugins")]
+        self.reload_changed_plugins();
+
         if update_terrain_and_regions {
             self.update_region_map();
         }
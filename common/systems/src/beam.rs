@@ -214,6 +214,7 @@ impl<'a> System<'a> for Sys {
                                         energy: read_data.energies.get(entity),
                                         combo: read_data.combos.get(entity),
                                         inventory: read_data.inventories.get(entity),
+                                        player: read_data.players.get(entity),
                                     }
                                 });
 
@@ -226,6 +227,7 @@ impl<'a> System<'a> for Sys {
                                 pos: pos_b.0,
                                 ori: read_data.orientations.get(target),
                                 char_state: read_data.character_states.get(target),
+                                player: read_data.players.get(target),
                             };
 
                             // PvP check
@@ -153,6 +153,7 @@ impl<'a> System<'a> for Sys {
                         energy: read_data.energies.get(attacker),
                         combo: read_data.combos.get(attacker),
                         inventory: read_data.inventories.get(attacker),
+                        player: read_data.players.get(attacker),
                     });
 
                     let target_info = TargetInfo {
@@ -164,6 +165,7 @@ impl<'a> System<'a> for Sys {
                         pos: pos_b.0,
                         ori: read_data.orientations.get(target),
                         char_state: read_data.char_states.get(target),
+                        player: read_data.players.get(target),
                     };
 
                     // PvP check
@@ -268,6 +268,7 @@ fn dispatch_hit(
                         energy: read_data.energies.get(entity),
                         combo: read_data.combos.get(entity),
                         inventory: read_data.inventories.get(entity),
+                        player: read_data.players.get(entity),
                     });
 
             let target_info = TargetInfo {
@@ -279,6 +280,7 @@ fn dispatch_hit(
                 pos: target_pos,
                 ori: projectile_target_info.ori,
                 char_state: read_data.character_states.get(target),
+                player: read_data.players.get(target),
             };
 
             // TODO: Is it possible to have projectile without body??
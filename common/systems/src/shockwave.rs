@@ -196,6 +196,7 @@ impl<'a> System<'a> for Sys {
                                 energy: read_data.energies.get(entity),
                                 combo: read_data.combos.get(entity),
                                 inventory: read_data.inventories.get(entity),
+                                player: read_data.players.get(entity),
                             });
 
                     let target_info = TargetInfo {
@@ -207,6 +208,7 @@ impl<'a> System<'a> for Sys {
                         pos: pos_b.0,
                         ori: read_data.orientations.get(target),
                         char_state: read_data.character_states.get(target),
+                        player: read_data.players.get(target),
                     };
 
                     // PvP check
@@ -0,0 +1,256 @@
+//! Encryption for [`Promises::ENCRYPTED`] streams of protocols which don't
+//! encrypt the connection themselves, e.g. TCP.
+//!
+//! Both sides send an ephemeral X25519 public key during the handshake, like
+//! the `NN` pattern of the Noise protocol framework. A key per direction is
+//! derived with HKDF-SHA256 from the shared secret and both public keys.
+//! Messages are sealed with ChaCha20-Poly1305, using their [`Mid`] as nonce and
+//! their [`Sid`] as additional data, so they can't be moved to another stream.
+//!
+//! The session secret of the `Init` frame is masked with a key derived the same
+//! way, so it can't be read and used to take over the session on resume.
+//!
+//! As the keys are ephemeral, this protects against eavesdropping, but not
+//! against an active man in the middle.
+//!
+//! [`Promises::ENCRYPTED`]: crate::Promises::ENCRYPTED
+use crate::types::{Mid, Promises, Sid};
+use bytes::{Bytes, BytesMut};
+use hashbrown::HashSet;
+use ring::{aead, agreement, hkdf, rand::SystemRandom};
+use std::sync::{Arc, Mutex};
+
+pub(crate) const PUBLIC_KEY_SIZE: usize = 32;
+/// Appended to every encrypted message
+pub(crate) const TAG_SIZE: usize = 16;
+const INITIATOR_INFO: &[u8] = b"veloren initiator";
+const RESPONDER_INFO: &[u8] = b"veloren responder";
+const INITIATOR_SECRET_INFO: &[u8] = b"veloren initiator secret";
+const RESPONDER_SECRET_INFO: &[u8] = b"veloren responder secret";
+
+/// Keys agreed on by both sides of a handshake
+pub(crate) struct Agreement {
+    pub send_cipher: Cipher,
+    pub recv_cipher: Cipher,
+    /// XORed onto the secret sent in the `Init` frame
+    pub send_secret_mask: u128,
+    /// XORed onto the secret received in the `Init` frame
+    pub recv_secret_mask: u128,
+}
+
+/// Output length of the secret masks for HKDF
+struct SecretMask;
+
+impl hkdf::KeyType for SecretMask {
+    fn len(&self) -> usize { std::mem::size_of::<u128>() }
+}
+
+fn derive_secret_mask(prk: &hkdf::Prk, info: &[u8]) -> Result<u128, ()> {
+    let info = [info];
+    let mut mask = [0u8; 16];
+    prk.expand(&info, SecretMask)
+        .and_then(|okm| okm.fill(&mut mask))
+        .map_err(|_| ())?;
+    Ok(u128::from_le_bytes(mask))
+}
+
+/// Ephemeral key pair of one side of the handshake
+pub(crate) struct KeyExchange {
+    private_key: agreement::EphemeralPrivateKey,
+    public_key: [u8; PUBLIC_KEY_SIZE],
+}
+
+impl KeyExchange {
+    pub(crate) fn new() -> Result<Self, ()> {
+        let rng = SystemRandom::new();
+        let private_key =
+            agreement::EphemeralPrivateKey::generate(&agreement::X25519, &rng).map_err(|_| ())?;
+        let mut public_key = [0u8; PUBLIC_KEY_SIZE];
+        public_key.copy_from_slice(private_key.compute_public_key().map_err(|_| ())?.as_ref());
+        Ok(Self {
+            private_key,
+            public_key,
+        })
+    }
+
+    pub(crate) fn public_key(&self) -> [u8; PUBLIC_KEY_SIZE] { self.public_key }
+
+    pub(crate) fn agree(
+        self,
+        remote_public_key: [u8; PUBLIC_KEY_SIZE],
+        initializer: bool,
+    ) -> Result<Agreement, ()> {
+        let (initiator_key, responder_key) = if initializer {
+            (self.public_key, remote_public_key)
+        } else {
+            (remote_public_key, self.public_key)
+        };
+        let mut salt = [0u8; 2 * PUBLIC_KEY_SIZE];
+        salt[..PUBLIC_KEY_SIZE].copy_from_slice(&initiator_key);
+        salt[PUBLIC_KEY_SIZE..].copy_from_slice(&responder_key);
+
+        let remote_public_key =
+            agreement::UnparsedPublicKey::new(&agreement::X25519, remote_public_key);
+        let streams = Arc::new(Mutex::new(HashSet::new()));
+        agreement::agree_ephemeral(self.private_key, &remote_public_key, (), |shared_secret| {
+            let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, &salt).extract(shared_secret);
+            let initiator = Cipher::derive(&prk, INITIATOR_INFO, Arc::clone(&streams))?;
+            let responder = Cipher::derive(&prk, RESPONDER_INFO, streams)?;
+            let initiator_mask = derive_secret_mask(&prk, INITIATOR_SECRET_INFO)?;
+            let responder_mask = derive_secret_mask(&prk, RESPONDER_SECRET_INFO)?;
+            Ok(if initializer {
+                Agreement {
+                    send_cipher: initiator,
+                    recv_cipher: responder,
+                    send_secret_mask: initiator_mask,
+                    recv_secret_mask: responder_mask,
+                }
+            } else {
+                Agreement {
+                    send_cipher: responder,
+                    recv_cipher: initiator,
+                    send_secret_mask: responder_mask,
+                    recv_secret_mask: initiator_mask,
+                }
+            })
+        })
+    }
+}
+
+/// Seals or opens the messages of one direction of a channel
+#[derive(Debug)]
+pub struct Cipher {
+    key: aead::LessSafeKey,
+    /// Encrypted streams, shared by both directions as only the send side
+    /// learns about streams opened locally
+    streams: Arc<Mutex<HashSet<Sid>>>,
+}
+
+impl Cipher {
+    fn derive(prk: &hkdf::Prk, info: &[u8], streams: Arc<Mutex<HashSet<Sid>>>) -> Result<Self, ()> {
+        let info = [info];
+        let okm = prk
+            .expand(&info, &aead::CHACHA20_POLY1305)
+            .map_err(|_| ())?;
+        Ok(Self {
+            key: aead::LessSafeKey::new(aead::UnboundKey::from(okm)),
+            streams,
+        })
+    }
+
+    pub(crate) fn open_stream(&self, sid: Sid, promises: Promises) {
+        if promises.contains(Promises::ENCRYPTED) {
+            self.streams.lock().unwrap().insert(sid);
+        }
+    }
+
+    pub(crate) fn close_stream(&self, sid: Sid) { self.streams.lock().unwrap().remove(&sid); }
+
+    pub(crate) fn is_encrypted(&self, sid: Sid) -> bool {
+        self.streams.lock().unwrap().contains(&sid)
+    }
+
+    /// A `Mid` is only used once per direction
+    fn nonce(mid: Mid) -> aead::Nonce {
+        let mut nonce = [0u8; aead::NONCE_LEN];
+        nonce[..8].copy_from_slice(&mid.to_le_bytes());
+        aead::Nonce::assume_unique_for_key(nonce)
+    }
+
+    pub(crate) fn seal(&self, sid: Sid, mid: Mid, data: &[u8]) -> Result<Bytes, ()> {
+        let mut in_out = Vec::with_capacity(data.len() + TAG_SIZE);
+        in_out.extend_from_slice(data);
+        let aad = aead::Aad::from(sid.get_u64().to_le_bytes());
+        self.key
+            .seal_in_place_append_tag(Self::nonce(mid), aad, &mut in_out)
+            .map_err(|_| ())?;
+        Ok(Bytes::from(in_out))
+    }
+
+    /// Err => the message was not sealed by the remote side for this stream
+    pub(crate) fn open(&self, sid: Sid, mid: Mid, mut data: BytesMut) -> Result<Bytes, ()> {
+        let aad = aead::Aad::from(sid.get_u64().to_le_bytes());
+        let len = self
+            .key
+            .open_in_place(Self::nonce(mid), aad, &mut data)
+            .map_err(|_| ())?
+            .len();
+        data.truncate(len);
+        Ok(data.freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agreements() -> (Agreement, Agreement) {
+        let k1 = KeyExchange::new().unwrap();
+        let k2 = KeyExchange::new().unwrap();
+        let (p1, p2) = (k1.public_key(), k2.public_key());
+        (k1.agree(p2, true).unwrap(), k2.agree(p1, false).unwrap())
+    }
+
+    fn ciphers() -> ((Cipher, Cipher), (Cipher, Cipher)) {
+        let (a1, a2) = agreements();
+        (
+            (a1.send_cipher, a1.recv_cipher),
+            (a2.send_cipher, a2.recv_cipher),
+        )
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let ((s1, r1), (s2, r2)) = ciphers();
+        let sid = Sid::new(3);
+        let sealed = s1.seal(sid, 7, b"Hello World").unwrap();
+        assert_eq!(sealed.len(), 11 + TAG_SIZE);
+        assert_ne!(&sealed[..11], b"Hello World");
+        let opened = r2.open(sid, 7, BytesMut::from(&sealed[..])).unwrap();
+        assert_eq!(&opened[..], b"Hello World");
+
+        let sealed = s2.seal(sid, 0, &[42u8; 1000]).unwrap();
+        let opened = r1.open(sid, 0, BytesMut::from(&sealed[..])).unwrap();
+        assert_eq!(&opened[..], &[42u8; 1000][..]);
+    }
+
+    #[test]
+    fn open_fails_when_tampered() {
+        let ((s1, r1), (_, r2)) = ciphers();
+        let sid = Sid::new(3);
+        let sealed = s1.seal(sid, 7, b"Hello World").unwrap();
+        // other stream or message
+        assert!(
+            r2.open(Sid::new(4), 7, BytesMut::from(&sealed[..]))
+                .is_err()
+        );
+        assert!(r2.open(sid, 8, BytesMut::from(&sealed[..])).is_err());
+        let mut tampered = BytesMut::from(&sealed[..]);
+        tampered[0] ^= 1;
+        assert!(r2.open(sid, 7, tampered).is_err());
+        // keys differ per direction
+        assert!(r1.open(sid, 7, BytesMut::from(&sealed[..])).is_err());
+    }
+
+    #[test]
+    fn secret_masks_match() {
+        let (a1, a2) = agreements();
+        assert_eq!(a1.send_secret_mask, a2.recv_secret_mask);
+        assert_eq!(a1.recv_secret_mask, a2.send_secret_mask);
+        assert_ne!(a1.send_secret_mask, a1.recv_secret_mask);
+        // a new handshake uses new masks
+        let (a3, _) = agreements();
+        assert_ne!(a1.send_secret_mask, a3.send_secret_mask);
+    }
+
+    #[test]
+    fn streams_shared_by_directions() {
+        let ((s1, r1), _) = ciphers();
+        s1.open_stream(Sid::new(1), Promises::ENCRYPTED | Promises::ORDERED);
+        s1.open_stream(Sid::new(2), Promises::ORDERED);
+        assert!(r1.is_encrypted(Sid::new(1)));
+        assert!(!r1.is_encrypted(Sid::new(2)));
+        r1.close_stream(Sid::new(1));
+        assert!(!s1.is_encrypted(Sid::new(1)));
+    }
+}
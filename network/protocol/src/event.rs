@@ -21,6 +21,12 @@ pub enum ProtocolEvent {
     CloseStream {
         sid: Sid,
     },
+    /// The sender received `count` messages of stream `sid` so far. Used to
+    /// acknowledge messages, so they no longer need to be kept for a resume
+    Received {
+        sid: Sid,
+        count: u64,
+    },
     Message {
         data: Bytes,
         sid: Sid,
@@ -43,6 +49,10 @@ impl ProtocolEvent {
                 guaranteed_bandwidth: *guaranteed_bandwidth,
             },
             ProtocolEvent::CloseStream { sid } => OTFrame::CloseStream { sid: *sid },
+            ProtocolEvent::Received { sid, count } => OTFrame::Received {
+                sid: *sid,
+                count: *count,
+            },
             ProtocolEvent::Message { .. } => {
                 unimplemented!("Event::Message to OTFrame IS NOT supported")
             },
@@ -61,6 +71,17 @@ mod tests {
             ProtocolEvent::CloseStream { sid: Sid::new(42) }.to_frame(),
             OTFrame::CloseStream { sid: Sid::new(42) }
         );
+        assert_eq!(
+            ProtocolEvent::Received {
+                sid: Sid::new(42),
+                count: 7
+            }
+            .to_frame(),
+            OTFrame::Received {
+                sid: Sid::new(42),
+                count: 7
+            }
+        );
     }
 
     #[test]
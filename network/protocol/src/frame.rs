@@ -10,7 +10,8 @@ const FRAME_CLOSE_STREAM: u8 = 5;
 const FRAME_DATA_HEADER: u8 = 6;
 const FRAME_DATA: u8 = 7;
 const FRAME_RAW: u8 = 8;
-//const FRAME_RESERVED_2: u8 = 10;
+const FRAME_KEY_EXCHANGE: u8 = 9;
+const FRAME_RECEIVED: u8 = 10;
 //const FRAME_RESERVED_3: u8 = 13;
 
 /// Used for Communication between Channel <----(TCP/UDP)----> Channel
@@ -24,6 +25,10 @@ pub enum InitFrame {
         pid: Pid,
         secret: u128,
     },
+    /// Ephemeral X25519 public key, to encrypt `ENCRYPTED` streams
+    KeyExchange {
+        public_key: [u8; 32],
+    },
     /// WARNING: sending RAW is only for debug purposes and will drop the
     /// connection
     Raw(Vec<u8>),
@@ -43,6 +48,14 @@ pub enum OTFrame {
     CloseStream {
         sid: Sid,
     },
+    /// Number of messages of a stream received so far, see
+    /// [`ProtocolEvent::Received`]
+    ///
+    /// [`ProtocolEvent::Received`]: crate::ProtocolEvent::Received
+    Received {
+        sid: Sid,
+        count: u64,
+    },
     DataHeader {
         mid: Mid,
         sid: Sid,
@@ -68,6 +81,14 @@ pub enum ITFrame {
     CloseStream {
         sid: Sid,
     },
+    /// Number of messages of a stream received so far, see
+    /// [`ProtocolEvent::Received`]
+    ///
+    /// [`ProtocolEvent::Received`]: crate::ProtocolEvent::Received
+    Received {
+        sid: Sid,
+        count: u64,
+    },
     DataHeader {
         mid: Mid,
         sid: Sid,
@@ -83,6 +104,7 @@ impl InitFrame {
     // Size WITHOUT the 1rst indicating byte
     pub(crate) const HANDSHAKE_CNS: usize = 19;
     pub(crate) const INIT_CNS: usize = 32;
+    pub(crate) const KEY_EXCHANGE_CNS: usize = 32;
     /// const part of the RAW frame, actual size is variable
     pub(crate) const RAW_CNS: usize = 2;
 
@@ -104,6 +126,10 @@ impl InitFrame {
                 pid.to_bytes(bytes);
                 bytes.put_u128_le(secret);
             },
+            InitFrame::KeyExchange { public_key } => {
+                bytes.put_u8(FRAME_KEY_EXCHANGE);
+                bytes.put_slice(&public_key);
+            },
             InitFrame::Raw(data) => {
                 bytes.put_u8(FRAME_RAW);
                 bytes.put_u16_le(data.len() as u16);
@@ -141,6 +167,15 @@ impl InitFrame {
                     secret: bytes.get_u128_le(),
                 }
             },
+            FRAME_KEY_EXCHANGE => {
+                if bytes.len() < Self::KEY_EXCHANGE_CNS + 1 {
+                    return None;
+                }
+                bytes.advance(1);
+                let mut public_key = [0u8; 32];
+                bytes.copy_to_slice(&mut public_key);
+                InitFrame::KeyExchange { public_key }
+            },
             FRAME_RAW => {
                 if bytes.len() < Self::RAW_CNS + 1 {
                     return None;
@@ -164,6 +199,7 @@ pub(crate) const TCP_CLOSE_STREAM_CNS: usize = 8;
 pub(crate) const TCP_DATA_CNS: usize = 10;
 pub(crate) const TCP_DATA_HEADER_CNS: usize = 24;
 pub(crate) const TCP_OPEN_STREAM_CNS: usize = 18;
+pub(crate) const TCP_RECEIVED_CNS: usize = 16;
 // Size WITHOUT the 1rst indicating byte
 pub(crate) const TCP_SHUTDOWN_CNS: usize = 0;
 
@@ -189,6 +225,11 @@ impl OTFrame {
                 bytes.put_u8(FRAME_CLOSE_STREAM);
                 sid.to_bytes(bytes);
             },
+            Self::Received { sid, count } => {
+                bytes.put_u8(FRAME_RECEIVED);
+                sid.to_bytes(bytes);
+                bytes.put_u64_le(count);
+            },
             Self::DataHeader { mid, sid, length } => {
                 bytes.put_u8(FRAME_DATA_HEADER);
                 bytes.put_u64_le(mid);
@@ -217,6 +258,7 @@ impl ITFrame {
             FRAME_SHUTDOWN => TCP_SHUTDOWN_CNS,
             FRAME_OPEN_STREAM => TCP_OPEN_STREAM_CNS,
             FRAME_CLOSE_STREAM => TCP_CLOSE_STREAM_CNS,
+            FRAME_RECEIVED => TCP_RECEIVED_CNS,
             FRAME_DATA_HEADER => TCP_DATA_HEADER_CNS,
             FRAME_DATA => {
                 if bytes.len() < 9 + 1 + 1 {
@@ -253,6 +295,14 @@ impl ITFrame {
                     sid: Sid::from_bytes(&mut bytes),
                 }
             },
+            FRAME_RECEIVED => {
+                let mut bytes = bytes.split_to(size + 1);
+                bytes.advance(1);
+                Self::Received {
+                    sid: Sid::from_bytes(&mut bytes),
+                    count: bytes.get_u64_le(),
+                }
+            },
             FRAME_DATA_HEADER => {
                 let mut bytes = bytes.split_to(size + 1);
                 bytes.advance(1);
@@ -293,6 +343,7 @@ impl PartialEq<ITFrame> for OTFrame {
                 guaranteed_bandwidth,
             }),
             Self::CloseStream { sid } => matches!(other, ITFrame::CloseStream { sid }),
+            Self::Received { sid, count } => matches!(other, ITFrame::Received { sid, count }),
             Self::DataHeader { mid, sid, length } => {
                 matches!(other, ITFrame::DataHeader { mid, sid, length })
             },
@@ -316,6 +367,9 @@ mod tests {
                 pid: Pid::fake(0),
                 secret: 0u128,
             },
+            InitFrame::KeyExchange {
+                public_key: [7u8; 32],
+            },
             InitFrame::Raw(vec![1, 2, 3]),
         ]
     }
@@ -341,6 +395,10 @@ mod tests {
                 mid: 0,
                 data: Bytes::from(&[42u8; 16][..]),
             },
+            OTFrame::Received {
+                sid: Sid::new(1337),
+                count: 1,
+            },
             OTFrame::CloseStream {
                 sid: Sid::new(1337),
             },
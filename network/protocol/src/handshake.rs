@@ -1,4 +1,5 @@
 use crate::{
+    crypto::{Cipher, KeyExchange},
     error::{InitProtocolError, ProtocolError},
     frame::InitFrame,
     types::{
@@ -22,6 +23,12 @@ use tracing::{debug, error, info, trace};
 #[async_trait]
 pub trait ReliableDrain {
     async fn send(&mut self, frame: InitFrame) -> Result<(), ProtocolError>;
+    /// Called with the key for sent messages once the handshake agreed on it.
+    /// Protocols which don't encrypt the connection themselves use it for
+    /// [`Promises::ENCRYPTED`] streams.
+    ///
+    /// [`Promises::ENCRYPTED`]: crate::Promises::ENCRYPTED
+    fn set_cipher(&mut self, _cipher: Cipher) {}
 }
 
 /// Implement this for auto Handshake with [`ReliableDrain`]. See
@@ -31,6 +38,10 @@ pub trait ReliableDrain {
 #[async_trait]
 pub trait ReliableSink {
     async fn recv(&mut self) -> Result<InitFrame, ProtocolError>;
+    /// Counterpart of [`ReliableDrain::set_cipher`], for received messages
+    ///
+    /// [`ReliableDrain::set_cipher`]: crate::ReliableDrain::set_cipher
+    fn set_cipher(&mut self, _cipher: Cipher) {}
 }
 
 #[async_trait]
@@ -58,6 +69,10 @@ where
 
         let drain = &mut self.0;
         let sink = &mut self.1;
+        let key_exchange = KeyExchange::new().map_err(|()| {
+            error!("Failed to generate a key for the handshake");
+            InitProtocolError::Closed
+        })?;
 
         if initializer {
             drain
@@ -99,13 +114,11 @@ where
                     Err(InitProtocolError::WrongVersion(version))
                 } else {
                     trace!("Handshake Frame completed");
+                    let key_exchange_frame = InitFrame::KeyExchange {
+                        public_key: key_exchange.public_key(),
+                    };
                     if initializer {
-                        drain
-                            .send(InitFrame::Init {
-                                pid: local_pid,
-                                secret: local_secret,
-                            })
-                            .await?;
+                        drain.send(key_exchange_frame).await?;
                     } else {
                         drain
                             .send(InitFrame::Handshake {
@@ -113,6 +126,7 @@ where
                                 version: VELOREN_NETWORK_VERSION,
                             })
                             .await?;
+                        drain.send(key_exchange_frame).await?;
                     }
                     Ok(())
                 }
@@ -130,6 +144,40 @@ where
             },
         }?;
 
+        // The secret is only sent masked with the agreed keys, as it authenticates
+        // a resumed session
+        let (send_secret_mask, recv_secret_mask) = match sink.recv().await? {
+            InitFrame::KeyExchange { public_key } => {
+                trace!("Recv public key");
+                let agreement = key_exchange.agree(public_key, initializer).map_err(|()| {
+                    info!("Key exchange failed");
+                    InitProtocolError::Closed
+                })?;
+                drain.set_cipher(agreement.send_cipher);
+                sink.set_cipher(agreement.recv_cipher);
+                if initializer {
+                    drain
+                        .send(InitFrame::Init {
+                            pid: local_pid,
+                            secret: local_secret ^ agreement.send_secret_mask,
+                        })
+                        .await?;
+                }
+                Ok((agreement.send_secret_mask, agreement.recv_secret_mask))
+            },
+            InitFrame::Raw(bytes) => {
+                match std::str::from_utf8(bytes.as_slice()) {
+                    Ok(string) => error!(?string, ERR_S),
+                    _ => error!(?bytes, ERR_S),
+                }
+                Err(InitProtocolError::Closed)
+            },
+            _ => {
+                info!("Handshake failed");
+                Err(InitProtocolError::Closed)
+            },
+        }?;
+
         match sink.recv().await? {
             InitFrame::Init { pid, secret } => {
                 debug!(?pid, "Participant send their ID");
@@ -139,13 +187,13 @@ where
                     drain
                         .send(InitFrame::Init {
                             pid: local_pid,
-                            secret: local_secret,
+                            secret: local_secret ^ send_secret_mask,
                         })
                         .await?;
                     STREAM_ID_OFFSET2
                 };
                 info!(?pid, "This Handshake is now configured!");
-                Ok((pid, stream_id_offset, secret))
+                Ok((pid, stream_id_offset, secret ^ recv_secret_mask))
             },
             InitFrame::Raw(bytes) => {
                 match std::str::from_utf8(bytes.as_slice()) {
@@ -220,6 +268,36 @@ mod tests {
         assert_eq!(r2.unwrap(), Err(InitProtocolError::Closed));
     }
 
+    #[tokio::test]
+    async fn handshake_masks_secret() {
+        let [mut p1, mut p2] = ac_bound(10, None);
+        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
+        let r2 = tokio::spawn(async move {
+            let _ = p2.1.recv().await?;
+            p2.0.send(InitFrame::Handshake {
+                magic_number: VELOREN_MAGIC_NUMBER,
+                version: VELOREN_NETWORK_VERSION,
+            })
+            .await?;
+            let key_exchange = KeyExchange::new().unwrap();
+            p2.0.send(InitFrame::KeyExchange {
+                public_key: key_exchange.public_key(),
+            })
+            .await?;
+            let _ = p2.1.recv().await?;
+            p2.1.recv().await
+        });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(r1.unwrap(), Err(InitProtocolError::Closed));
+        match r2.unwrap() {
+            Ok(InitFrame::Init { pid, secret }) => {
+                assert_eq!(pid, Pid::fake(2));
+                assert_ne!(secret, 1337);
+            },
+            frame => panic!("expected Init frame, got {:?}", frame),
+        }
+    }
+
     #[tokio::test]
     async fn handshake_unexpected_raw() {
         let [mut p1, mut p2] = ac_bound(10, None);
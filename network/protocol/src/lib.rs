@@ -15,8 +15,7 @@
 //!  - TCP
 //!  - MPSC
 //!  - QUIC
-//!
-//! eventually a pure UDP implementation will follow
+//!  - UDP
 //!
 //! warning: don't mix protocol, using the TCP variant for actual UDP socket
 //! will result in dropped data  using UDP with a TCP socket will be a waste of
@@ -36,7 +35,8 @@
 //!
 //! Implement the Handshake: [`InitProtocol`], alternatively you can also
 //! implement `ReliableDrain` and `ReliableSink`, by this, you use the default
-//! Handshake.
+//! Handshake. It also exchanges keys, implement their `set_cipher` to encrypt
+//! `ENCRYPTED` streams if the connection isn't encrypted already.
 //!
 //! This crate also contains consts and definitions for the network protocol.
 //!
@@ -50,6 +50,7 @@
 //! [`RecvProtocol`]: crate::RecvProtocol
 //! [`InitProtocol`]: crate::InitProtocol
 
+mod crypto;
 mod error;
 mod event;
 mod frame;
@@ -61,6 +62,7 @@ mod prio;
 mod quic;
 mod tcp;
 mod types;
+mod udp;
 mod util;
 
 pub use error::{InitProtocolError, ProtocolError};
@@ -72,6 +74,7 @@ pub use mpsc::{MpscMsg, MpscRecvProtocol, MpscSendProtocol};
 pub use quic::{QuicDataFormat, QuicDataFormatStream, QuicRecvProtocol, QuicSendProtocol};
 pub use tcp::{TcpRecvProtocol, TcpSendProtocol};
 pub use types::{Bandwidth, Cid, Pid, Prio, Promises, Sid, HIGHEST_PRIO, VELOREN_NETWORK_VERSION};
+pub use udp::{UdpChannelState, UdpCookies, UdpRecvProtocol, UdpSendProtocol};
 
 ///use at own risk, might change any time, for internal benchmarks
 pub mod _internal {
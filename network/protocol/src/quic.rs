@@ -243,6 +243,12 @@ where
                     self.closing_streams.push(sid);
                 }
             },
+            ProtocolEvent::Received { .. } => {
+                event.to_frame().write_bytes(&mut self.main_buffer);
+                self.drain
+                    .send(QuicDataFormat::with_main(&mut self.main_buffer))
+                    .await?;
+            },
             ProtocolEvent::Shutdown => {
                 if self.store.is_empty() {
                     event.to_frame().write_bytes(&mut self.main_buffer);
@@ -372,6 +378,9 @@ where
                             //let _ = self.reliable_buffers.delete(sid); // if it was reliable
                             break 'outer Ok(ProtocolEvent::CloseStream { sid });
                         },
+                        ITFrame::Received { sid, count } => {
+                            break 'outer Ok(ProtocolEvent::Received { sid, count });
+                        },
                         _ => break 'outer Err(ProtocolError::Violated),
                     };
                 },
@@ -499,12 +508,12 @@ where
     S: UnreliableSink<DataFormat = QuicDataFormat>,
 {
     async fn recv(&mut self) -> Result<InitFrame, ProtocolError> {
+        // the remote side might have sent multiple frames at once
         while self.main_buffer.len() < 100 {
-            if self.recv_into_stream().await? == QuicDataFormatStream::Main {
-                if let Some(frame) = InitFrame::read_frame(&mut self.main_buffer) {
-                    return Ok(frame);
-                }
+            if let Some(frame) = InitFrame::read_frame(&mut self.main_buffer) {
+                return Ok(frame);
             }
+            self.recv_into_stream().await?;
         }
         Err(ProtocolError::Violated)
     }
@@ -1,4 +1,5 @@
 use crate::{
+    crypto::Cipher,
     error::ProtocolError,
     event::ProtocolEvent,
     frame::{ITFrame, InitFrame, OTFrame},
@@ -31,6 +32,8 @@ where
     closing_streams: Vec<Sid>,
     notify_closing_streams: Vec<Sid>,
     pending_shutdown: bool,
+    /// Set by the handshake, encrypts messages of `ENCRYPTED` streams
+    cipher: Option<Cipher>,
     drain: D,
     #[allow(dead_code)]
     last: Instant,
@@ -48,6 +51,8 @@ where
     buffer: BytesMut,
     itmsg_allocator: BytesMut,
     incoming: HashMap<Mid, ITMessage>,
+    /// Set by the handshake, decrypts messages of `ENCRYPTED` streams
+    cipher: Option<Cipher>,
     sink: S,
     metrics: ProtocolMetricCache,
 }
@@ -64,6 +69,7 @@ where
             closing_streams: vec![],
             notify_closing_streams: vec![],
             pending_shutdown: false,
+            cipher: None,
             drain,
             last: Instant::now(),
             metrics,
@@ -77,6 +83,15 @@ where
             | Promises::CONSISTENCY
             | Promises::GUARANTEED_DELIVERY
             | Promises::COMPRESSED
+            | Promises::ENCRYPTED
+    }
+
+    fn open_stream(&mut self, sid: Sid, prio: u8, promises: Promises, guaranteed_bandwidth: u64) {
+        self.store
+            .open_stream(sid, prio, promises, guaranteed_bandwidth);
+        if let Some(cipher) = &self.cipher {
+            cipher.open_stream(sid, promises);
+        }
     }
 }
 
@@ -89,6 +104,7 @@ where
             buffer: BytesMut::new(),
             itmsg_allocator: BytesMut::with_capacity(ALLOC_BLOCK),
             incoming: HashMap::new(),
+            cipher: None,
             sink,
             metrics,
         }
@@ -108,8 +124,7 @@ where
                 promises,
                 guaranteed_bandwidth,
             } => {
-                self.store
-                    .open_stream(sid, prio, promises, guaranteed_bandwidth);
+                self.open_stream(sid, prio, promises, guaranteed_bandwidth);
             },
             ProtocolEvent::CloseStream { sid } => {
                 if !self.store.try_close_stream(sid) {
@@ -132,8 +147,7 @@ where
                 promises,
                 guaranteed_bandwidth,
             } => {
-                self.store
-                    .open_stream(sid, prio, promises, guaranteed_bandwidth);
+                self.open_stream(sid, prio, promises, guaranteed_bandwidth);
                 event.to_frame().write_bytes(&mut self.buffer);
                 self.drain.send(self.buffer.split()).await?;
             },
@@ -147,6 +161,10 @@ where
                     self.closing_streams.push(sid);
                 }
             },
+            ProtocolEvent::Received { .. } => {
+                event.to_frame().write_bytes(&mut self.buffer);
+                self.drain.send(self.buffer.split()).await?;
+            },
             ProtocolEvent::Shutdown => {
                 if self.store.is_empty() {
                     event.to_frame().write_bytes(&mut self.buffer);
@@ -158,6 +176,12 @@ where
                 }
             },
             ProtocolEvent::Message { data, sid } => {
+                let data = match &self.cipher {
+                    Some(cipher) if cipher.is_encrypted(sid) => cipher
+                        .seal(sid, self.next_mid, &data)
+                        .map_err(|()| ProtocolError::Violated)?,
+                    _ => data,
+                };
                 self.metrics.smsg_ib(sid, data.len() as u64);
                 self.store.add(data, self.next_mid, sid);
                 self.next_mid += 1;
@@ -243,6 +267,9 @@ where
                                 promises,
                                 guaranteed_bandwidth,
                             } => {
+                                if let Some(cipher) = &self.cipher {
+                                    cipher.open_stream(sid, promises);
+                                }
                                 break 'outer Ok(ProtocolEvent::OpenStream {
                                     sid,
                                     prio: prio.min(crate::types::HIGHEST_PRIO),
@@ -251,8 +278,14 @@ where
                                 });
                             },
                             ITFrame::CloseStream { sid } => {
+                                if let Some(cipher) = &self.cipher {
+                                    cipher.close_stream(sid);
+                                }
                                 break 'outer Ok(ProtocolEvent::CloseStream { sid });
                             },
+                            ITFrame::Received { sid, count } => {
+                                break 'outer Ok(ProtocolEvent::Received { sid, count });
+                            },
                             ITFrame::DataHeader { sid, mid, length } => {
                                 let m = ITMessage::new(sid, length, &mut self.itmsg_allocator);
                                 self.metrics.rmsg_ib(sid, length);
@@ -280,10 +313,23 @@ where
                                         RemoveReason::Finished,
                                         m.data.len() as u64,
                                     );
-                                    break 'outer Ok(ProtocolEvent::Message {
-                                        sid: m.sid,
-                                        data: m.data.freeze(),
-                                    });
+                                    let data = match &self.cipher {
+                                        Some(cipher) if cipher.is_encrypted(m.sid) => {
+                                            match cipher.open(m.sid, mid, m.data) {
+                                                Ok(data) => data,
+                                                Err(()) => {
+                                                    info!(
+                                                        ?mid,
+                                                        "protocol violation by remote side: \
+                                                         message can't be decrypted"
+                                                    );
+                                                    break 'outer Err(ProtocolError::Violated);
+                                                },
+                                            }
+                                        },
+                                        _ => m.data.freeze(),
+                                    };
+                                    break 'outer Ok(ProtocolEvent::Message { sid: m.sid, data });
                                 }
                             },
                         };
@@ -312,6 +358,8 @@ where
         frame.write_bytes(&mut buffer);
         self.drain.send(buffer).await
     }
+
+    fn set_cipher(&mut self, cipher: Cipher) { self.cipher = Some(cipher); }
 }
 
 #[async_trait]
@@ -320,15 +368,18 @@ where
     S: UnreliableSink<DataFormat = BytesMut>,
 {
     async fn recv(&mut self) -> Result<InitFrame, ProtocolError> {
+        // the remote side might have sent multiple frames at once
         while self.buffer.len() < 100 {
-            let chunk = self.sink.recv().await?;
-            self.buffer.extend_from_slice(&chunk);
             if let Some(frame) = InitFrame::read_frame(&mut self.buffer) {
                 return Ok(frame);
             }
+            let chunk = self.sink.recv().await?;
+            self.buffer.extend_from_slice(&chunk);
         }
         Err(ProtocolError::Violated)
     }
+
+    fn set_cipher(&mut self, cipher: Cipher) { self.cipher = Some(cipher); }
 }
 
 #[cfg(test)]
@@ -432,6 +483,34 @@ mod tests {
         assert_eq!(event, e);
     }
 
+    #[tokio::test]
+    async fn send_encrypted_msg() {
+        let [mut p1, mut p2] = tcp_bound(10, None);
+        let r1 =
+            tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await.map(|_| p1) });
+        let r2 =
+            tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42).await.map(|_| p2) });
+        let (r1, r2) = tokio::join!(r1, r2);
+        let (mut s, _) = r1.unwrap().unwrap();
+        let (_, mut r) = r2.unwrap().unwrap();
+        let event = ProtocolEvent::OpenStream {
+            sid: Sid::new(10),
+            prio: 3u8,
+            promises: Promises::ORDERED | Promises::ENCRYPTED,
+            guaranteed_bandwidth: 1_000_000,
+        };
+        s.send(event).await.unwrap();
+        let _ = r.recv().await.unwrap();
+        let event = ProtocolEvent::Message {
+            sid: Sid::new(10),
+            data: Bytes::from(&[188u8; 600][..]),
+        };
+        s.send(event.clone()).await.unwrap();
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        let e = r.recv().await.unwrap();
+        assert_eq!(event, e);
+    }
+
     #[tokio::test]
     async fn send_short_msg() {
         let [p1, p2] = tcp_bound(10, None);
@@ -1,37 +1,1803 @@
-// TODO: quick and dirty which activly waits for an ack!
 /*
 UDP protocol
 
+Every datagram is a packet, starting with its type. Handshake packets carry a
+single `InitFrame`. As they aren't retransmitted they are sent multiple times,
+duplicates are ignored by the receiver.
+
+A listener only allocates a channel for a remote side which proved it receives
+datagrams sent to its address, so spoofed addresses can't make it keep state or
+send handshakes to someone else. The remote side sends a HELLO first and gets a
+COOKIE back, which it repeats in its handshake packets. The HELLO is padded to
+the size of the COOKIE, so the answer never amplifies traffic, and the cookie
+is derived from the address with a secret key, so the listener doesn't need
+any state to check it.
+
+Data packets carry a packet number, increasing with every packet sent, followed
+by frames:
+ - ACK: selective acknowledgement of the received packet numbers, as ranges
+ - CONTROL: open/close a stream or shutdown, delivered in order
+ - HEADER: start of a message, with its stream and its sequence number there
+ - DATA: part of a message at a given offset
+ - PING: only makes the packet acknowledged
+
 All Good Case:
-S --HEADER--> R
-S --DATA--> R
-S --DATA--> R
-S <--FINISHED-- R
-
-
-Delayed HEADER:
-S --HEADER-->
-S --DATA--> R // STORE IT
-  --HEADER--> R // apply left data and continue
-S --DATA--> R
-S <--FINISHED-- R
-
-
-NO HEADER:
-S --HEADER--> !
-S --DATA--> R // STORE IT
-S --DATA--> R // STORE IT
-S <--MISSING_HEADER-- R // SEND AFTER 10 ms after DATA1
-S --HEADER--> R
-S <--FINISHED-- R
-
-
-NO DATA:
-S --HEADER--> R
-S --DATA--> R
-S --DATA--> !
-S --STATUS--> R
-S <--MISSING_DATA -- R
-S --DATA--> R
-S <--FINISHED-- R
+S --[HEADER, DATA]--> R
+S --[DATA]--> R
+S <--[ACK]-- R
+
+Lost packet:
+S --[HEADER, DATA]--> !
+S --[DATA]--> R
+S --[DATA]--> R
+S --[DATA]--> R
+S <--[ACK]-- R // 3 later packets acked, first one is lost
+S --[HEADER, DATA]--> R // only for GUARANTEED_DELIVERY streams
+S <--[ACK]-- R
+
+Packets that aren't acked after the retransmission timeout are lost too.
+Received packet numbers are acked until a packet carrying their ACK got acked
+itself, a side only sending ACKs adds a PING once they pile up.
+Messages of ORDERED streams are delivered in sequence order, without
+GUARANTEED_DELIVERY late messages are dropped instead of waiting for lost ones.
+
+Congestion control is NewReno: the window grows by the acked bytes in slow
+start and by one packet per window afterwards, it's halved once per window
+when packets get lost.
 */
+use crate::{
+    error::ProtocolError,
+    event::ProtocolEvent,
+    frame::{ITFrame, InitFrame, OTFrame},
+    handshake::{ReliableDrain, ReliableSink},
+    metrics::{ProtocolMetricCache, RemoveReason},
+    prio::PrioManager,
+    types::{Bandwidth, Mid, Promises, Sid},
+    RecvProtocol, SendProtocol, UnreliableDrain, UnreliableSink,
+};
+use async_trait::async_trait;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use hashbrown::HashMap;
+use ring::hmac;
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::info;
+#[cfg(feature = "trace_pedantic")]
+use tracing::trace;
+
+const PACKET_INIT: u8 = 0;
+const PACKET_DATA: u8 = 1;
+const PACKET_HELLO: u8 = 2;
+const PACKET_COOKIE: u8 = 3;
+/// Handshake packet carrying the cookie of the listener
+const PACKET_INIT_COOKIE: u8 = 4;
+
+const UDP_FRAME_ACK: u8 = 1;
+const UDP_FRAME_CONTROL: u8 = 2;
+const UDP_FRAME_HEADER: u8 = 3;
+const UDP_FRAME_DATA: u8 = 4;
+const UDP_FRAME_PING: u8 = 5;
+
+/// Biggest datagram sent, small enough to not get fragmented on most links
+const MAX_DATAGRAM_SIZE: usize = 1400;
+/// packet type + packet number
+const PACKET_HEADER_SIZE: usize = 9;
+/// Number of ranges in an ACK frame, the newest ones are sent
+const MAX_ACK_RANGES: usize = 16;
+/// Received ranges kept while the remote side doesn't ack our ACKs, older
+/// ones are forgotten
+const MAX_RECEIVED_RANGES: usize = 256;
+const MAX_ACK_FRAME_SIZE: usize = 2 + 16 * MAX_ACK_RANGES;
+/// Room left for frames, every packet may carry an ACK frame
+const MAX_FRAMES_SIZE: usize = MAX_DATAGRAM_SIZE - PACKET_HEADER_SIZE - MAX_ACK_FRAME_SIZE;
+const UDP_DATA_CNS: usize = 18;
+/// Biggest payload of a DATA frame, so it always fits in a packet
+const MAX_FRAGMENT_SIZE: usize = MAX_FRAMES_SIZE - 1 - UDP_DATA_CNS;
+const UDP_HEADER_CNS: usize = 32;
+/// A packet is lost once a packet sent this much later got acked
+const PACKET_THRESHOLD: u64 = 3;
+/// How often handshake packets are sent
+const INIT_REPEAT: usize = 3;
+const INITIAL_RTT: Duration = Duration::from_millis(100);
+const MIN_RTO: Duration = Duration::from_millis(50);
+const MAX_RTO: Duration = Duration::from_secs(2);
+const MAX_RTO_BACKOFF: u32 = 5;
+const INITIAL_WINDOW: usize = 10 * MAX_DATAGRAM_SIZE;
+const MIN_WINDOW: usize = 2 * MAX_DATAGRAM_SIZE;
+/// Incomplete messages are dropped once nothing was received for them for
+/// this long
+const INCOMPLETE_TIMEOUT: Duration = Duration::from_secs(30);
+/// CONTROL frames received ahead of the next one to handle
+const MAX_PENDING_CONTROL: u64 = 4096;
+/// Bytes of messages completed before the CONTROL frame opening their stream
+const MAX_UNOPENED_SIZE: usize = 16 * 1024 * 1024;
+/// Epoch of the cookie and the start of its tag
+const COOKIE_SIZE: usize = 8 + 16;
+/// Padded to the size of the COOKIE answering it
+const HELLO_SIZE: usize = 1 + COOKIE_SIZE;
+/// Cookies are accepted in the epoch they were handed out in and the next one
+const COOKIE_EPOCH: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq)]
+enum UdpFrame {
+    /// Received packet numbers, as sorted inclusive ranges
+    Ack {
+        ranges: Vec<(u64, u64)>,
+    },
+    Control {
+        cseq: u64,
+        frame: OTFrame,
+    },
+    Header {
+        mid: Mid,
+        sid: Sid,
+        seq: u64,
+        length: u64,
+    },
+    Data {
+        mid: Mid,
+        offset: u64,
+        data: Bytes,
+    },
+    Ping,
+}
+
+impl UdpFrame {
+    /// Size WITH the 1rst indicating byte
+    fn len(&self) -> usize {
+        1 + match self {
+            UdpFrame::Ack { ranges } => 1 + 16 * ranges.len(),
+            UdpFrame::Control { frame, .. } => {
+                8 + 1
+                    + match frame {
+                        OTFrame::OpenStream { .. } => crate::frame::TCP_OPEN_STREAM_CNS,
+                        OTFrame::CloseStream { .. } => crate::frame::TCP_CLOSE_STREAM_CNS,
+                        OTFrame::Received { .. } => crate::frame::TCP_RECEIVED_CNS,
+                        _ => crate::frame::TCP_SHUTDOWN_CNS,
+                    }
+            },
+            UdpFrame::Header { .. } => UDP_HEADER_CNS,
+            UdpFrame::Data { data, .. } => UDP_DATA_CNS + data.len(),
+            UdpFrame::Ping => 0,
+        }
+    }
+
+    fn write_bytes(&self, bytes: &mut BytesMut) {
+        match self {
+            UdpFrame::Ack { ranges } => {
+                bytes.put_u8(UDP_FRAME_ACK);
+                bytes.put_u8(ranges.len() as u8);
+                for &(start, end) in ranges {
+                    bytes.put_u64_le(start);
+                    bytes.put_u64_le(end);
+                }
+            },
+            UdpFrame::Control { cseq, frame } => {
+                bytes.put_u8(UDP_FRAME_CONTROL);
+                bytes.put_u64_le(*cseq);
+                frame.clone().write_bytes(bytes);
+            },
+            UdpFrame::Header {
+                mid,
+                sid,
+                seq,
+                length,
+            } => {
+                bytes.put_u8(UDP_FRAME_HEADER);
+                bytes.put_u64_le(*mid);
+                sid.to_bytes(bytes);
+                bytes.put_u64_le(*seq);
+                bytes.put_u64_le(*length);
+            },
+            UdpFrame::Data { mid, offset, data } => {
+                bytes.put_u8(UDP_FRAME_DATA);
+                bytes.put_u64_le(*mid);
+                bytes.put_u64_le(*offset);
+                bytes.put_u16_le(data.len() as u16);
+                bytes.put_slice(data);
+            },
+            UdpFrame::Ping => bytes.put_u8(UDP_FRAME_PING),
+        }
+    }
+
+    /// Err => the packet is malformed
+    fn read_frame(bytes: &mut BytesMut) -> Result<Self, ()> {
+        if bytes.is_empty() {
+            return Err(());
+        }
+        let frame = match bytes.get_u8() {
+            UDP_FRAME_ACK => {
+                if bytes.is_empty() {
+                    return Err(());
+                }
+                let count = bytes.get_u8() as usize;
+                if bytes.remaining() < 16 * count {
+                    return Err(());
+                }
+                let ranges = (0..count)
+                    .map(|_| (bytes.get_u64_le(), bytes.get_u64_le()))
+                    .collect::<Vec<_>>();
+                if ranges.iter().any(|(start, end)| start > end) {
+                    return Err(());
+                }
+                UdpFrame::Ack { ranges }
+            },
+            UDP_FRAME_CONTROL => {
+                if bytes.remaining() < 8 {
+                    return Err(());
+                }
+                let cseq = bytes.get_u64_le();
+                let frame = match ITFrame::read_frame(bytes)? {
+                    Some(ITFrame::Shutdown) => OTFrame::Shutdown,
+                    Some(ITFrame::OpenStream {
+                        sid,
+                        prio,
+                        promises,
+                        guaranteed_bandwidth,
+                    }) => OTFrame::OpenStream {
+                        sid,
+                        prio,
+                        promises,
+                        guaranteed_bandwidth,
+                    },
+                    Some(ITFrame::CloseStream { sid }) => OTFrame::CloseStream { sid },
+                    Some(ITFrame::Received { sid, count }) => OTFrame::Received { sid, count },
+                    _ => return Err(()),
+                };
+                UdpFrame::Control { cseq, frame }
+            },
+            UDP_FRAME_HEADER => {
+                if bytes.remaining() < UDP_HEADER_CNS {
+                    return Err(());
+                }
+                UdpFrame::Header {
+                    mid: bytes.get_u64_le(),
+                    sid: Sid::from_bytes(bytes),
+                    seq: bytes.get_u64_le(),
+                    length: bytes.get_u64_le(),
+                }
+            },
+            UDP_FRAME_DATA => {
+                if bytes.remaining() < UDP_DATA_CNS {
+                    return Err(());
+                }
+                let mid = bytes.get_u64_le();
+                let offset = bytes.get_u64_le();
+                let length = bytes.get_u16_le() as usize;
+                if bytes.remaining() < length {
+                    return Err(());
+                }
+                UdpFrame::Data {
+                    mid,
+                    offset,
+                    data: bytes.split_to(length).freeze(),
+                }
+            },
+            UDP_FRAME_PING => UdpFrame::Ping,
+            _ => return Err(()),
+        };
+        Ok(frame)
+    }
+}
+
+/// Inserts a packet number into sorted, inclusive and disjoint ranges.
+/// Returns false if it was already contained.
+fn insert_into_ranges(ranges: &mut Vec<(u64, u64)>, pn: u64) -> bool {
+    let i = ranges.partition_point(|&(start, _)| start <= pn);
+    if i > 0 && ranges[i - 1].1 >= pn {
+        return false;
+    }
+    let merge_prev = i > 0 && ranges[i - 1].1.checked_add(1) == Some(pn);
+    let merge_next = i < ranges.len() && pn.checked_add(1) == Some(ranges[i].0);
+    match (merge_prev, merge_next) {
+        (true, true) => {
+            ranges[i - 1].1 = ranges[i].1;
+            ranges.remove(i);
+        },
+        (true, false) => ranges[i - 1].1 = pn,
+        (false, true) => ranges[i].0 = pn,
+        (false, false) => ranges.insert(i, (pn, pn)),
+    }
+    true
+}
+
+#[derive(Debug, Default)]
+struct AckState {
+    /// Packet numbers received from the remote side, above `floor`
+    received: Vec<(u64, u64)>,
+    /// Packets up to this one are duplicates, the remote side already knows
+    /// whether they were received
+    floor: Option<u64>,
+    /// A packet that has to be acknowledged was received
+    ack_pending: bool,
+    /// Ranges acknowledged by the remote side, not yet handled by the sender
+    acked: Vec<(u64, u64)>,
+}
+
+/// State shared by the send and recv half of a UDP channel, so the sender
+/// acknowledges received packets and learns about acknowledged ones.
+#[derive(Debug, Clone, Default)]
+pub struct UdpChannelState {
+    inner: Arc<Mutex<AckState>>,
+}
+
+impl UdpChannelState {
+    /// returns false if the packet was already received
+    fn on_packet_received(&self, pn: u64, ack_eliciting: bool) -> bool {
+        let mut state = self.inner.lock().unwrap();
+        // Duplicates are acked again, the previous ACK might have been lost
+        state.ack_pending |= ack_eliciting;
+        if state.floor.map_or(false, |floor| pn <= floor) {
+            return false;
+        }
+        let new = insert_into_ranges(&mut state.received, pn);
+        let len = state.received.len();
+        if len > MAX_RECEIVED_RANGES {
+            let forgotten = len - MAX_RECEIVED_RANGES;
+            state.floor = Some(state.received[forgotten - 1].1);
+            state.received.drain(..forgotten);
+        }
+        new
+    }
+
+    /// A packet carrying an ACK frame from `lowest` to `largest` was acked,
+    /// the remote side doesn't need to hear about these ranges again
+    fn on_ack_acked(&self, lowest: u64, largest: u64) {
+        let mut state = self.inner.lock().unwrap();
+        state
+            .received
+            .retain(|&(start, end)| start < lowest || end > largest);
+        if state
+            .received
+            .first()
+            .map_or(true, |&(start, _)| start > largest)
+        {
+            state.floor = Some(state.floor.map_or(largest, |floor| floor.max(largest)));
+        }
+    }
+
+    fn on_ack_received(&self, ranges: Vec<(u64, u64)>) {
+        self.inner.lock().unwrap().acked.extend(ranges);
+    }
+
+    /// The newest ranges to ack, if a packet has to be acknowledged
+    fn take_ack(&self) -> Option<Vec<(u64, u64)>> {
+        let mut state = self.inner.lock().unwrap();
+        if state.ack_pending {
+            state.ack_pending = false;
+            let len = state.received.len();
+            Some(state.received[len.saturating_sub(MAX_ACK_RANGES)..].to_vec())
+        } else {
+            None
+        }
+    }
+
+    fn ack_pending(&self) -> bool { self.inner.lock().unwrap().ack_pending }
+
+    fn take_acked(&self) -> Vec<(u64, u64)> {
+        std::mem::take(&mut self.inner.lock().unwrap().acked)
+    }
+}
+
+/// Hands out the cookies a UDP listener requires in the handshake packets of
+/// new remote sides, see the protocol description. The remote side gets them
+/// with [`hello`] and [`read_cookie`].
+///
+/// [`hello`]: UdpCookies::hello
+/// [`read_cookie`]: UdpCookies::read_cookie
+#[derive(Debug)]
+pub struct UdpCookies {
+    key: hmac::Key,
+    start: Instant,
+}
+
+impl UdpCookies {
+    pub fn new() -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, &rand::random::<[u8; 32]>()),
+            start: Instant::now(),
+        }
+    }
+
+    fn epoch(&self) -> u64 { self.start.elapsed().as_secs() / COOKIE_EPOCH.as_secs() }
+
+    fn tag(&self, remote_addr: &SocketAddr, epoch: u64) -> hmac::Tag {
+        let mut context = hmac::Context::with_key(&self.key);
+        context.update(remote_addr.to_string().as_bytes());
+        context.update(&epoch.to_le_bytes());
+        context.sign()
+    }
+
+    /// Answer to a datagram of a remote side without a channel, if it's a
+    /// HELLO
+    pub fn answer_hello(&self, remote_addr: &SocketAddr, datagram: &[u8]) -> Option<BytesMut> {
+        if datagram.len() < HELLO_SIZE || datagram[0] != PACKET_HELLO {
+            return None;
+        }
+        let epoch = self.epoch();
+        let mut answer = BytesMut::with_capacity(HELLO_SIZE);
+        answer.put_u8(PACKET_COOKIE);
+        answer.put_u64_le(epoch);
+        answer.put_slice(&self.tag(remote_addr, epoch).as_ref()[..COOKIE_SIZE - 8]);
+        Some(answer)
+    }
+
+    /// Whether the datagram is a handshake packet carrying a valid cookie for
+    /// `remote_addr`, only then a channel may be allocated for it
+    pub fn accepts(&self, remote_addr: &SocketAddr, datagram: &[u8]) -> bool {
+        if datagram.len() < HELLO_SIZE || datagram[0] != PACKET_INIT_COOKIE {
+            return false;
+        }
+        let mut cookie = &datagram[1..HELLO_SIZE];
+        let epoch = cookie.get_u64_le();
+        let current = self.epoch();
+        if epoch != current && epoch.checked_add(1) != Some(current) {
+            return false;
+        }
+        let tag = self.tag(remote_addr, epoch);
+        ring::constant_time::verify_slices_are_equal(&tag.as_ref()[..COOKIE_SIZE - 8], cookie)
+            .is_ok()
+    }
+
+    /// HELLO to send to a listener, which answers with a cookie
+    pub fn hello() -> BytesMut {
+        let mut hello = BytesMut::with_capacity(HELLO_SIZE);
+        hello.put_u8(PACKET_HELLO);
+        hello.put_bytes(0, COOKIE_SIZE);
+        hello
+    }
+
+    /// Cookie of the listener, if the datagram is its answer to a HELLO
+    pub fn read_cookie(datagram: &[u8]) -> Option<Bytes> {
+        if datagram.len() < HELLO_SIZE || datagram[0] != PACKET_COOKIE {
+            return None;
+        }
+        Some(Bytes::copy_from_slice(&datagram[1..HELLO_SIZE]))
+    }
+}
+
+impl Default for UdpCookies {
+    fn default() -> Self { Self::new() }
+}
+
+/// Round trip time estimation, as in RFC 6298
+#[derive(Debug)]
+struct RttEstimator {
+    srtt: Duration,
+    rttvar: Duration,
+    has_sample: bool,
+    /// Number of retransmission timeouts in a row
+    backoff: u32,
+}
+
+impl RttEstimator {
+    fn new() -> Self {
+        Self {
+            srtt: INITIAL_RTT,
+            rttvar: INITIAL_RTT / 2,
+            has_sample: false,
+            backoff: 0,
+        }
+    }
+
+    fn update(&mut self, sample: Duration) {
+        if self.has_sample {
+            let diff = if self.srtt > sample {
+                self.srtt - sample
+            } else {
+                sample - self.srtt
+            };
+            self.rttvar = (self.rttvar * 3 + diff) / 4;
+            self.srtt = (self.srtt * 7 + sample) / 8;
+        } else {
+            self.srtt = sample;
+            self.rttvar = sample / 2;
+            self.has_sample = true;
+        }
+    }
+
+    fn rto(&self) -> Duration {
+        ((self.srtt + self.rttvar * 4).max(MIN_RTO) * (1 << self.backoff)).min(MAX_RTO)
+    }
+
+    /// A packet acked after a packet sent this much later is lost
+    fn loss_delay(&self) -> Duration { self.srtt * 9 / 8 }
+}
+
+/// NewReno congestion control, sizes are in bytes
+#[derive(Debug)]
+struct CongestionController {
+    window: usize,
+    ssthresh: usize,
+    bytes_in_flight: usize,
+    /// Packets sent before this one belong to the window that was already
+    /// reduced
+    recovery_start: Option<u64>,
+}
+
+impl CongestionController {
+    fn new() -> Self {
+        Self {
+            window: INITIAL_WINDOW,
+            ssthresh: usize::MAX,
+            bytes_in_flight: 0,
+            recovery_start: None,
+        }
+    }
+
+    fn can_send(&self, size: usize) -> bool {
+        // A single packet always fits, otherwise nothing could ever be sent
+        self.bytes_in_flight == 0 || self.bytes_in_flight + size <= self.window
+    }
+
+    fn in_recovery(&self, pn: u64) -> bool { self.recovery_start.map_or(false, |start| pn < start) }
+
+    fn on_sent(&mut self, size: usize) { self.bytes_in_flight += size; }
+
+    fn on_acked(&mut self, pn: u64, size: usize) {
+        self.bytes_in_flight = self.bytes_in_flight.saturating_sub(size);
+        if self.in_recovery(pn) {
+            return;
+        }
+        if self.window < self.ssthresh {
+            self.window += size;
+        } else {
+            self.window += MAX_DATAGRAM_SIZE * size / self.window;
+        }
+    }
+
+    fn on_lost(&mut self, pn: u64, size: usize, next_pn: u64) {
+        self.bytes_in_flight = self.bytes_in_flight.saturating_sub(size);
+        if self.in_recovery(pn) {
+            return;
+        }
+        self.recovery_start = Some(next_pn);
+        self.window = (self.window / 2).max(MIN_WINDOW);
+        self.ssthresh = self.window;
+    }
+
+    fn on_timeout(&mut self) { self.window = MIN_WINDOW; }
+}
+
+#[derive(Debug)]
+struct SentPacket {
+    time: Instant,
+    size: usize,
+    /// Frames that are sent again if the packet is lost
+    reliable_frames: Vec<UdpFrame>,
+    /// Lowest and largest packet number of the ACK frame it carried
+    ack_ranges: Option<(u64, u64)>,
+}
+
+#[derive(Debug)]
+struct SendStreamInfo {
+    promises: Promises,
+    next_seq: u64,
+}
+
+/// UDP implementation of [`SendProtocol`]
+///
+/// [`SendProtocol`]: crate::SendProtocol
+#[derive(Debug)]
+pub struct UdpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    buffer: BytesMut,
+    store: PrioManager,
+    next_mid: Mid,
+    next_pn: u64,
+    next_cseq: u64,
+    streams: HashMap<Sid, SendStreamInfo>,
+    /// Offset of the next DATA frame and length of messages being sent
+    offsets: HashMap<Mid, (u64, u64)>,
+    /// Frames taken from the `store` which didn't fit in the congestion window
+    /// yet
+    pending: VecDeque<(Sid, UdpFrame)>,
+    /// Reliable frames of lost packets
+    retransmit: VecDeque<UdpFrame>,
+    in_flight: BTreeMap<u64, SentPacket>,
+    largest_acked: Option<u64>,
+    rtt: RttEstimator,
+    congestion: CongestionController,
+    closing_streams: Vec<Sid>,
+    notify_closing_streams: Vec<Sid>,
+    pending_shutdown: bool,
+    /// Repeated in handshake packets, if the remote side is a listener
+    cookie: Option<Bytes>,
+    state: UdpChannelState,
+    drain: D,
+    metrics: ProtocolMetricCache,
+}
+
+#[derive(Debug)]
+struct UdpITMessage {
+    /// sid, sequence number in the stream and length, once the HEADER arrived
+    header: Option<(Sid, u64, u64)>,
+    fragments: BTreeMap<u64, Bytes>,
+    received: u64,
+    last_update: Instant,
+}
+
+#[derive(Debug)]
+struct RecvStreamInfo {
+    promises: Promises,
+    /// Every message before was delivered, or dropped for streams without
+    /// `GUARANTEED_DELIVERY`
+    next_seq: u64,
+    /// Delivered after `next_seq`, unordered streams only
+    delivered: BTreeSet<u64>,
+    /// Complete but waiting for `next_seq`, ordered streams only
+    ready: BTreeMap<u64, Bytes>,
+}
+
+/// UDP implementation of [`RecvProtocol`]
+///
+/// [`RecvProtocol`]: crate::RecvProtocol
+#[derive(Debug)]
+pub struct UdpRecvProtocol<S>
+where
+    S: UnreliableSink<DataFormat = BytesMut>,
+{
+    events: VecDeque<ProtocolEvent>,
+    next_cseq: u64,
+    pending_control: BTreeMap<u64, OTFrame>,
+    streams: HashMap<Sid, RecvStreamInfo>,
+    /// Messages completed before the CONTROL frame opening their stream
+    unopened: Vec<(Sid, u64, Bytes)>,
+    incoming: HashMap<Mid, UdpITMessage>,
+    last_cleanup: Instant,
+    /// Data packets received during the handshake
+    early_packets: Vec<BytesMut>,
+    last_init: Option<InitFrame>,
+    state: UdpChannelState,
+    sink: S,
+    metrics: ProtocolMetricCache,
+}
+
+impl<D> UdpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    /// `state` must be shared with the [`UdpRecvProtocol`] of the same
+    /// channel
+    pub fn new(drain: D, state: UdpChannelState, metrics: ProtocolMetricCache) -> Self {
+        Self {
+            buffer: BytesMut::new(),
+            store: PrioManager::new(metrics.clone()),
+            next_mid: 0u64,
+            next_pn: 0u64,
+            next_cseq: 0u64,
+            streams: HashMap::new(),
+            offsets: HashMap::new(),
+            pending: VecDeque::new(),
+            retransmit: VecDeque::new(),
+            in_flight: BTreeMap::new(),
+            largest_acked: None,
+            rtt: RttEstimator::new(),
+            congestion: CongestionController::new(),
+            closing_streams: vec![],
+            notify_closing_streams: vec![],
+            pending_shutdown: false,
+            cookie: None,
+            state,
+            drain,
+            metrics,
+        }
+    }
+
+    /// Cookie the listener handed out, see [`UdpCookies`]
+    pub fn set_cookie(&mut self, cookie: Bytes) { self.cookie = Some(cookie); }
+
+    /// returns all promises that this Protocol can take care of
+    /// If you open a Stream anyway, unsupported promises are ignored.
+    pub fn supported_promises() -> Promises {
+        Promises::ORDERED
+            | Promises::CONSISTENCY
+            | Promises::GUARANTEED_DELIVERY
+            | Promises::COMPRESSED
+    }
+
+    fn open_stream(&mut self, sid: Sid, prio: u8, promises: Promises, guaranteed_bandwidth: u64) {
+        self.store
+            .open_stream(sid, prio, promises, guaranteed_bandwidth);
+        self.streams.insert(sid, SendStreamInfo {
+            promises,
+            next_seq: 0,
+        });
+    }
+
+    fn is_reliable(&self, sid: Sid) -> bool {
+        self.streams.get(&sid).map_or(false, |s| {
+            s.promises.contains(Promises::GUARANTEED_DELIVERY)
+        })
+    }
+
+    /// No reliable frame is waiting to be sent or acknowledged
+    fn all_acked(&self) -> bool {
+        self.retransmit.is_empty()
+            && self
+                .in_flight
+                .values()
+                .all(|packet| packet.reliable_frames.is_empty())
+    }
+
+    fn try_close_stream(&mut self, sid: Sid) -> bool {
+        if self.pending.iter().any(|(s, _)| *s == sid) || !self.all_acked() {
+            return false;
+        }
+        if self.store.try_close_stream(sid) {
+            self.streams.remove(&sid);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.store.is_empty() && self.pending.is_empty() && self.all_acked()
+    }
+
+    /// Converts the frames of the `store` to UDP frames, splitting DATA frames
+    /// so they fit in a packet
+    fn queue_frame(&mut self, sid: Sid, frame: OTFrame) {
+        match frame {
+            OTFrame::DataHeader { mid, sid, length } => {
+                let stream = self.streams.entry(sid).or_insert(SendStreamInfo {
+                    promises: Promises::empty(),
+                    next_seq: 0,
+                });
+                let seq = stream.next_seq;
+                stream.next_seq += 1;
+                if length > 0 {
+                    self.offsets.insert(mid, (0, length));
+                }
+                self.pending.push_back((sid, UdpFrame::Header {
+                    mid,
+                    sid,
+                    seq,
+                    length,
+                }));
+            },
+            OTFrame::Data { mid, mut data } => {
+                let (offset, length) = match self.offsets.get_mut(&mid) {
+                    Some(offset) => offset,
+                    None => return,
+                };
+                while !data.is_empty() {
+                    let fragment = data.split_to(data.len().min(MAX_FRAGMENT_SIZE));
+                    let fragment_len = fragment.len() as u64;
+                    self.pending.push_back((sid, UdpFrame::Data {
+                        mid,
+                        offset: *offset,
+                        data: fragment,
+                    }));
+                    *offset += fragment_len;
+                }
+                if *offset >= *length {
+                    self.offsets.remove(&mid);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    async fn send_packet(&mut self, frames: Vec<(UdpFrame, bool)>) -> Result<(), ProtocolError> {
+        let pn = self.next_pn;
+        self.next_pn += 1;
+        self.buffer.reserve(MAX_DATAGRAM_SIZE);
+        self.buffer.put_u8(PACKET_DATA);
+        self.buffer.put_u64_le(pn);
+        let mut ack_eliciting = !frames.is_empty();
+        let mut ack_ranges = None;
+        if let Some(ranges) = self.state.take_ack() {
+            // The ranges are only forgotten once this packet got acked, which
+            // packets only carrying an ACK aren't
+            if !ack_eliciting && ranges.len() > MAX_ACK_RANGES / 2 {
+                UdpFrame::Ping.write_bytes(&mut self.buffer);
+                ack_eliciting = true;
+            }
+            ack_ranges = ranges
+                .first()
+                .zip(ranges.last())
+                .map(|(first, last)| (first.0, last.1));
+            UdpFrame::Ack { ranges }.write_bytes(&mut self.buffer);
+        }
+        let mut reliable_frames = vec![];
+        for (frame, reliable) in frames {
+            frame.write_bytes(&mut self.buffer);
+            if reliable {
+                reliable_frames.push(frame);
+            }
+        }
+        let size = self.buffer.len();
+        self.drain.send(self.buffer.split()).await?;
+        // Packets only carrying an ACK aren't acknowledged
+        if ack_eliciting {
+            self.congestion.on_sent(size);
+            self.in_flight.insert(pn, SentPacket {
+                time: Instant::now(),
+                size,
+                reliable_frames,
+                ack_ranges,
+            });
+        }
+        Ok(())
+    }
+
+    async fn send_control(&mut self, frame: OTFrame) -> Result<(), ProtocolError> {
+        let frame = UdpFrame::Control {
+            cseq: self.next_cseq,
+            frame,
+        };
+        self.next_cseq += 1;
+        self.send_packet(vec![(frame, true)]).await
+    }
+
+    fn handle_acks(&mut self, now: Instant) {
+        for (start, end) in self.state.take_acked() {
+            let acked = self
+                .in_flight
+                .range(start..=end)
+                .map(|(pn, _)| *pn)
+                .collect::<Vec<_>>();
+            for pn in acked {
+                if let Some(packet) = self.in_flight.remove(&pn) {
+                    if self.largest_acked.map_or(true, |largest| pn > largest) {
+                        self.largest_acked = Some(pn);
+                        self.rtt.update(now.duration_since(packet.time));
+                    }
+                    self.rtt.backoff = 0;
+                    self.congestion.on_acked(pn, packet.size);
+                    if let Some((lowest, largest)) = packet.ack_ranges {
+                        self.state.on_ack_acked(lowest, largest);
+                    }
+                }
+            }
+        }
+    }
+
+    fn detect_losses(&mut self, now: Instant) {
+        let rto = self.rtt.rto();
+        let loss_delay = self.rtt.loss_delay();
+        let largest_acked = self.largest_acked;
+        let mut timed_out = false;
+        let lost = self
+            .in_flight
+            .iter()
+            .filter(|(&pn, packet)| {
+                let age = now.duration_since(packet.time);
+                let acked_later = largest_acked.map_or(false, |largest| largest > pn);
+                if acked_later
+                    && (largest_acked.unwrap_or(0) >= pn + PACKET_THRESHOLD || age >= loss_delay)
+                {
+                    true
+                } else if age >= rto {
+                    timed_out = true;
+                    true
+                } else {
+                    false
+                }
+            })
+            .map(|(pn, _)| *pn)
+            .collect::<Vec<_>>();
+
+        for pn in lost {
+            if let Some(packet) = self.in_flight.remove(&pn) {
+                #[cfg(feature = "trace_pedantic")]
+                trace!(?pn, "packet lost");
+                self.congestion.on_lost(pn, packet.size, self.next_pn);
+                self.retransmit.extend(packet.reliable_frames);
+            }
+        }
+        if timed_out {
+            self.congestion.on_timeout();
+            self.rtt.backoff = (self.rtt.backoff + 1).min(MAX_RTO_BACKOFF);
+        }
+    }
+}
+
+impl<S> UdpRecvProtocol<S>
+where
+    S: UnreliableSink<DataFormat = BytesMut>,
+{
+    /// `state` must be shared with the [`UdpSendProtocol`] of the same
+    /// channel
+    pub fn new(sink: S, state: UdpChannelState, metrics: ProtocolMetricCache) -> Self {
+        Self {
+            events: VecDeque::new(),
+            next_cseq: 0,
+            pending_control: BTreeMap::new(),
+            streams: HashMap::new(),
+            unopened: vec![],
+            incoming: HashMap::new(),
+            last_cleanup: Instant::now(),
+            early_packets: vec![],
+            last_init: None,
+            state,
+            sink,
+            metrics,
+        }
+    }
+
+    fn handle_packet(&mut self, mut bytes: BytesMut) -> Result<(), ProtocolError> {
+        if bytes.is_empty() {
+            return Err(ProtocolError::Violated);
+        }
+        match bytes.get_u8() {
+            // late duplicates of the handshake
+            PACKET_INIT | PACKET_INIT_COOKIE | PACKET_HELLO | PACKET_COOKIE => return Ok(()),
+            PACKET_DATA => {},
+            _ => return Err(ProtocolError::Violated),
+        }
+        if bytes.remaining() < 8 {
+            return Err(ProtocolError::Violated);
+        }
+        let pn = bytes.get_u64_le();
+        // Packet numbers count up from 0, the remote side can't have sent this many
+        if pn == u64::MAX {
+            return Err(ProtocolError::Violated);
+        }
+        let mut frames = vec![];
+        while !bytes.is_empty() {
+            frames.push(UdpFrame::read_frame(&mut bytes).map_err(|()| ProtocolError::Violated)?);
+        }
+        let ack_eliciting = frames
+            .iter()
+            .any(|frame| !matches!(frame, UdpFrame::Ack { .. }));
+        if !self.state.on_packet_received(pn, ack_eliciting) {
+            #[cfg(feature = "trace_pedantic")]
+            trace!(?pn, "duplicate packet");
+            return Ok(());
+        }
+        for frame in frames {
+            self.handle_frame(frame)?;
+        }
+        self.cleanup();
+        Ok(())
+    }
+
+    fn handle_frame(&mut self, frame: UdpFrame) -> Result<(), ProtocolError> {
+        #[cfg(feature = "trace_pedantic")]
+        trace!(?frame, "recv");
+        match frame {
+            UdpFrame::Ack { ranges } => self.state.on_ack_received(ranges),
+            UdpFrame::Control { cseq, frame } => {
+                if cseq >= self.next_cseq.saturating_add(MAX_PENDING_CONTROL) {
+                    info!(
+                        ?cseq,
+                        "protocol violation by remote side: Control too far ahead"
+                    );
+                    return Err(ProtocolError::Violated);
+                }
+                if cseq >= self.next_cseq {
+                    self.pending_control.insert(cseq, frame);
+                }
+                while let Some(frame) = self.pending_control.remove(&self.next_cseq) {
+                    self.next_cseq += 1;
+                    self.handle_control(frame);
+                }
+            },
+            UdpFrame::Header {
+                mid,
+                sid,
+                seq,
+                length,
+            } => {
+                if !self.streams.get(&sid).map_or(true, |s| s.is_new(seq)) {
+                    return Ok(());
+                }
+                let m = self.incoming.entry(mid).or_insert_with(UdpITMessage::new);
+                if m.header.is_none() {
+                    m.header = Some((sid, seq, length));
+                    m.last_update = Instant::now();
+                    self.metrics.rmsg_ib(sid, length);
+                    self.try_complete(mid)?;
+                }
+            },
+            UdpFrame::Data { mid, offset, data } => {
+                self.metrics.rdata_frames_b(data.len() as u64);
+                let m = self.incoming.entry(mid).or_insert_with(UdpITMessage::new);
+                m.last_update = Instant::now();
+                if !m.fragments.contains_key(&offset) {
+                    m.received += data.len() as u64;
+                    m.fragments.insert(offset, data);
+                    self.try_complete(mid)?;
+                }
+            },
+            UdpFrame::Ping => {},
+        }
+        Ok(())
+    }
+
+    fn handle_control(&mut self, frame: OTFrame) {
+        match frame {
+            OTFrame::Shutdown => self.events.push_back(ProtocolEvent::Shutdown),
+            OTFrame::OpenStream {
+                sid,
+                prio,
+                promises,
+                guaranteed_bandwidth,
+            } => {
+                self.events.push_back(ProtocolEvent::OpenStream {
+                    sid,
+                    prio: prio.min(crate::types::HIGHEST_PRIO),
+                    promises,
+                    guaranteed_bandwidth,
+                });
+                let mut stream = RecvStreamInfo::new(promises);
+                for (_, seq, data) in self.unopened.drain_filter(|(s, _, _)| *s == sid) {
+                    stream.deliver(sid, seq, data, &mut self.events);
+                }
+                self.streams.insert(sid, stream);
+            },
+            OTFrame::CloseStream { sid } => {
+                // The remote side only closes streams once all their messages were acked
+                self.streams.remove(&sid);
+                self.events.push_back(ProtocolEvent::CloseStream { sid });
+            },
+            OTFrame::Received { sid, count } => {
+                self.events
+                    .push_back(ProtocolEvent::Received { sid, count });
+            },
+            _ => {},
+        }
+    }
+
+    fn try_complete(&mut self, mid: Mid) -> Result<(), ProtocolError> {
+        let complete = match self.incoming.get(&mid) {
+            Some(UdpITMessage {
+                header: Some((_, _, length)),
+                received,
+                ..
+            }) => received >= length,
+            _ => false,
+        };
+        if !complete {
+            return Ok(());
+        }
+        let m = self.incoming.remove(&mid).ok_or(ProtocolError::Violated)?;
+        let (sid, seq, length) = m.header.ok_or(ProtocolError::Violated)?;
+        let mut data = BytesMut::with_capacity(length as usize);
+        for (offset, fragment) in m.fragments {
+            if offset != data.len() as u64 {
+                info!(?mid, "protocol violation by remote side: overlapping Data");
+                return Err(ProtocolError::Violated);
+            }
+            data.extend_from_slice(&fragment);
+        }
+        if data.len() as u64 != length {
+            info!(
+                ?mid,
+                "protocol violation by remote side: Data longer than Header"
+            );
+            return Err(ProtocolError::Violated);
+        }
+        self.metrics.rmsg_ob(sid, RemoveReason::Finished, length);
+        match self.streams.get_mut(&sid) {
+            Some(stream) => stream.deliver(sid, seq, data.freeze(), &mut self.events),
+            None => {
+                let size = self
+                    .unopened
+                    .iter()
+                    .map(|(_, _, data)| data.len())
+                    .sum::<usize>();
+                if size + data.len() > MAX_UNOPENED_SIZE {
+                    info!(
+                        ?sid,
+                        "protocol violation by remote side: too much Data for unopened streams"
+                    );
+                    return Err(ProtocolError::Violated);
+                }
+                self.unopened.push((sid, seq, data.freeze()))
+            },
+        }
+        Ok(())
+    }
+
+    /// Drops messages which will never complete, e.g. when packets of
+    /// streams without `GUARANTEED_DELIVERY` were lost
+    fn cleanup(&mut self) {
+        let now = Instant::now();
+        if now.duration_since(self.last_cleanup) < Duration::from_secs(1) {
+            return;
+        }
+        self.last_cleanup = now;
+        let metrics = &mut self.metrics;
+        self.incoming.retain(|_, m| {
+            let keep = now.duration_since(m.last_update) < INCOMPLETE_TIMEOUT;
+            if !keep {
+                if let Some((sid, _, length)) = m.header {
+                    metrics.rmsg_ob(sid, RemoveReason::Dropped, length);
+                }
+            }
+            keep
+        });
+    }
+}
+
+impl UdpITMessage {
+    fn new() -> Self {
+        Self {
+            header: None,
+            fragments: BTreeMap::new(),
+            received: 0,
+            last_update: Instant::now(),
+        }
+    }
+}
+
+impl RecvStreamInfo {
+    fn new(promises: Promises) -> Self {
+        Self {
+            promises,
+            next_seq: 0,
+            delivered: BTreeSet::new(),
+            ready: BTreeMap::new(),
+        }
+    }
+
+    fn is_new(&self, seq: u64) -> bool {
+        seq >= self.next_seq && !self.delivered.contains(&seq) && !self.ready.contains_key(&seq)
+    }
+
+    fn deliver(&mut self, sid: Sid, seq: u64, data: Bytes, events: &mut VecDeque<ProtocolEvent>) {
+        if !self.is_new(seq) {
+            return;
+        }
+        let ordered = self.promises.contains(Promises::ORDERED);
+        let guaranteed = self.promises.contains(Promises::GUARANTEED_DELIVERY);
+        match (ordered, guaranteed) {
+            (true, true) => {
+                self.ready.insert(seq, data);
+                while let Some(data) = self.ready.remove(&self.next_seq) {
+                    events.push_back(ProtocolEvent::Message { sid, data });
+                    self.next_seq += 1;
+                }
+            },
+            // Messages sent before this one are dropped when they arrive
+            (true, false) => {
+                events.push_back(ProtocolEvent::Message { sid, data });
+                self.next_seq = seq + 1;
+            },
+            (false, true) => {
+                events.push_back(ProtocolEvent::Message { sid, data });
+                self.delivered.insert(seq);
+                while self.delivered.remove(&self.next_seq) {
+                    self.next_seq += 1;
+                }
+            },
+            (false, false) => events.push_back(ProtocolEvent::Message { sid, data }),
+        }
+    }
+}
+
+#[async_trait]
+impl<D> SendProtocol for UdpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    fn notify_from_recv(&mut self, event: ProtocolEvent) {
+        match event {
+            ProtocolEvent::OpenStream {
+                sid,
+                prio,
+                promises,
+                guaranteed_bandwidth,
+            } => {
+                self.open_stream(sid, prio, promises, guaranteed_bandwidth);
+            },
+            ProtocolEvent::CloseStream { sid } => {
+                if !self.try_close_stream(sid) {
+                    #[cfg(feature = "trace_pedantic")]
+                    trace!(?sid, "hold back notify close stream");
+                    self.notify_closing_streams.push(sid);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    async fn send(&mut self, event: ProtocolEvent) -> Result<(), ProtocolError> {
+        #[cfg(feature = "trace_pedantic")]
+        trace!(?event, "send");
+        match event {
+            ProtocolEvent::OpenStream {
+                sid,
+                prio,
+                promises,
+                guaranteed_bandwidth,
+            } => {
+                self.open_stream(sid, prio, promises, guaranteed_bandwidth);
+                self.send_control(event.to_frame()).await?;
+            },
+            ProtocolEvent::CloseStream { sid } => {
+                if self.try_close_stream(sid) {
+                    self.send_control(event.to_frame()).await?;
+                } else {
+                    #[cfg(feature = "trace_pedantic")]
+                    trace!(?sid, "hold back close stream");
+                    self.closing_streams.push(sid);
+                }
+            },
+            ProtocolEvent::Received { .. } => {
+                self.send_control(event.to_frame()).await?;
+            },
+            ProtocolEvent::Shutdown => {
+                if self.is_idle() {
+                    self.send_control(event.to_frame()).await?;
+                } else {
+                    #[cfg(feature = "trace_pedantic")]
+                    trace!("hold back shutdown");
+                    self.pending_shutdown = true;
+                }
+            },
+            ProtocolEvent::Message { data, sid } => {
+                self.metrics.smsg_ib(sid, data.len() as u64);
+                self.store.add(data, self.next_mid, sid);
+                self.next_mid += 1;
+            },
+        }
+        Ok(())
+    }
+
+    async fn flush(
+        &mut self,
+        bandwidth: Bandwidth,
+        dt: Duration,
+    ) -> Result</* actual */ Bandwidth, ProtocolError> {
+        let now = Instant::now();
+        self.handle_acks(now);
+        self.detect_losses(now);
+
+        // Only grab new frames once everything grabbed before was sent, the
+        // congestion window limits what the store may hand out
+        if self.pending.is_empty() {
+            let window = self
+                .congestion
+                .window
+                .saturating_sub(self.congestion.bytes_in_flight) as f64;
+            let secs = dt.as_secs_f64();
+            let bandwidth = if secs > 0.0 {
+                bandwidth.min((window / secs) as u64)
+            } else {
+                bandwidth
+            };
+            let (frames, _) = self.store.grab(bandwidth, dt);
+            for (sid, frame) in frames {
+                self.queue_frame(sid, frame);
+            }
+        }
+
+        let mut data_frames = 0;
+        let mut data_bandwidth = 0;
+        let mut frames = vec![];
+        let mut size = 0;
+        loop {
+            let (frame, reliable) = if let Some(frame) = self.retransmit.front() {
+                (frame, true)
+            } else if let Some((sid, frame)) = self.pending.front() {
+                (frame, self.is_reliable(*sid))
+            } else {
+                break;
+            };
+            let frame_len = frame.len();
+            if size + frame_len > MAX_FRAMES_SIZE {
+                self.send_packet(std::mem::take(&mut frames)).await?;
+                size = 0;
+            }
+            if !self
+                .congestion
+                .can_send(PACKET_HEADER_SIZE + size + frame_len)
+            {
+                break;
+            }
+            let frame = if self.retransmit.is_empty() {
+                self.pending.pop_front().map(|(_, frame)| frame)
+            } else {
+                self.retransmit.pop_front()
+            }
+            .ok_or(ProtocolError::Violated)?;
+            if let UdpFrame::Data { data, .. } = &frame {
+                data_bandwidth += data.len();
+                data_frames += 1;
+            }
+            size += frame_len;
+            frames.push((frame, reliable));
+        }
+        if !frames.is_empty() || self.state.ack_pending() {
+            self.send_packet(frames).await?;
+        }
+        self.metrics
+            .sdata_frames_b(data_frames, data_bandwidth as u64);
+
+        let mut finished_streams = vec![];
+        for i in 0..self.closing_streams.len() {
+            let sid = self.closing_streams[i];
+            if self.try_close_stream(sid) {
+                #[cfg(feature = "trace_pedantic")]
+                trace!(?sid, "close stream, as it's now empty");
+                self.send_control(OTFrame::CloseStream { sid }).await?;
+                finished_streams.push(i);
+            }
+        }
+        for i in finished_streams.iter().rev() {
+            self.closing_streams.remove(*i);
+        }
+
+        let mut finished_streams = vec![];
+        for i in 0..self.notify_closing_streams.len() {
+            let sid = self.notify_closing_streams[i];
+            if self.try_close_stream(sid) {
+                #[cfg(feature = "trace_pedantic")]
+                trace!(?sid, "close stream, as it's now empty");
+                finished_streams.push(i);
+            }
+        }
+        for i in finished_streams.iter().rev() {
+            self.notify_closing_streams.remove(*i);
+        }
+
+        if self.pending_shutdown && self.is_idle() {
+            #[cfg(feature = "trace_pedantic")]
+            trace!("shutdown, as it's now empty");
+            self.send_control(OTFrame::Shutdown).await?;
+            self.pending_shutdown = false;
+        }
+        Ok(data_bandwidth as u64)
+    }
+}
+
+#[async_trait]
+impl<S> RecvProtocol for UdpRecvProtocol<S>
+where
+    S: UnreliableSink<DataFormat = BytesMut>,
+{
+    async fn recv(&mut self) -> Result<ProtocolEvent, ProtocolError> {
+        for packet in std::mem::take(&mut self.early_packets) {
+            self.handle_packet(packet)?;
+        }
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            let packet = self.sink.recv().await?;
+            self.handle_packet(packet)?;
+        }
+    }
+}
+
+#[async_trait]
+impl<D> ReliableDrain for UdpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    async fn send(&mut self, frame: InitFrame) -> Result<(), ProtocolError> {
+        let mut buffer = BytesMut::with_capacity(500);
+        match &self.cookie {
+            Some(cookie) => {
+                buffer.put_u8(PACKET_INIT_COOKIE);
+                buffer.put_slice(cookie);
+            },
+            None => buffer.put_u8(PACKET_INIT),
+        }
+        frame.write_bytes(&mut buffer);
+        // handshake packets aren't acked, sending them multiple times makes
+        // losing all of them unlikely
+        for _ in 0..INIT_REPEAT {
+            self.drain.send(buffer.clone()).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S> ReliableSink for UdpRecvProtocol<S>
+where
+    S: UnreliableSink<DataFormat = BytesMut>,
+{
+    async fn recv(&mut self) -> Result<InitFrame, ProtocolError> {
+        loop {
+            let mut packet = self.sink.recv().await?;
+            match packet.first() {
+                Some(&PACKET_INIT) => packet.advance(1),
+                Some(&PACKET_INIT_COOKIE) if packet.len() >= HELLO_SIZE => {
+                    packet.advance(HELLO_SIZE)
+                },
+                // The remote side finished its handshake before us
+                Some(&PACKET_DATA) => {
+                    self.early_packets.push(packet);
+                    continue;
+                },
+                // repeated while getting the cookie
+                Some(&PACKET_HELLO) | Some(&PACKET_COOKIE) => continue,
+                _ => return Err(ProtocolError::Violated),
+            }
+            let frame = InitFrame::read_frame(&mut packet).ok_or(ProtocolError::Violated)?;
+            if self.last_init.as_ref() == Some(&frame) {
+                continue;
+            }
+            self.last_init = Some(frame.clone());
+            return Ok(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_utils {
+    //UDP protocol based on Channel
+    use super::*;
+    use crate::metrics::{ProtocolMetricCache, ProtocolMetrics};
+    use async_channel::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::sync::Arc;
+
+    pub struct UdpDrain {
+        pub sender: Sender<BytesMut>,
+        pub drop_ratio: f32,
+        /// seeded, so lost packets are the same in every run
+        pub rng: StdRng,
+    }
+
+    pub struct UdpSink {
+        pub receiver: Receiver<BytesMut>,
+    }
+
+    /// emulate Udp protocol on Channels, dropping data packets
+    pub fn udp_bound(
+        cap: usize,
+        drop_ratio: f32,
+        metrics: Option<ProtocolMetricCache>,
+    ) -> [(UdpSendProtocol<UdpDrain>, UdpRecvProtocol<UdpSink>); 2] {
+        let (s1, r1) = async_channel::bounded(cap);
+        let (s2, r2) = async_channel::bounded(cap);
+        let m = metrics.unwrap_or_else(|| {
+            ProtocolMetricCache::new("udp", Arc::new(ProtocolMetrics::new().unwrap()))
+        });
+        let state1 = UdpChannelState::default();
+        let state2 = UdpChannelState::default();
+        [
+            (
+                UdpSendProtocol::new(
+                    UdpDrain {
+                        sender: s1,
+                        drop_ratio,
+                        rng: StdRng::seed_from_u64(1),
+                    },
+                    state1.clone(),
+                    m.clone(),
+                ),
+                UdpRecvProtocol::new(UdpSink { receiver: r2 }, state1, m.clone()),
+            ),
+            (
+                UdpSendProtocol::new(
+                    UdpDrain {
+                        sender: s2,
+                        drop_ratio,
+                        rng: StdRng::seed_from_u64(2),
+                    },
+                    state2.clone(),
+                    m.clone(),
+                ),
+                UdpRecvProtocol::new(UdpSink { receiver: r1 }, state2, m),
+            ),
+        ]
+    }
+
+    #[async_trait]
+    impl UnreliableDrain for UdpDrain {
+        type DataFormat = BytesMut;
+
+        async fn send(&mut self, data: Self::DataFormat) -> Result<(), ProtocolError> {
+            use rand::Rng;
+            if data.first() == Some(&PACKET_DATA) && self.rng.gen::<f32>() < self.drop_ratio {
+                return Ok(());
+            }
+            self.sender
+                .send(data)
+                .await
+                .map_err(|_| ProtocolError::Closed)
+        }
+    }
+
+    #[async_trait]
+    impl UnreliableSink for UdpSink {
+        type DataFormat = BytesMut;
+
+        async fn recv(&mut self) -> Result<Self::DataFormat, ProtocolError> {
+            self.receiver
+                .recv()
+                .await
+                .map_err(|_| ProtocolError::Closed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        insert_into_ranges, CongestionController, RecvStreamInfo, UdpChannelState, UdpCookies,
+        UdpFrame, COOKIE_SIZE, MAX_ACK_RANGES, MAX_PENDING_CONTROL, MIN_WINDOW, PACKET_INIT_COOKIE,
+    };
+    use crate::{
+        frame::OTFrame,
+        types::{Pid, Promises, Sid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2},
+        udp::test_utils::*,
+        InitProtocol, ProtocolError, ProtocolEvent, RecvProtocol, SendProtocol,
+    };
+    use bytes::{BufMut, Bytes, BytesMut};
+    use std::{collections::VecDeque, net::SocketAddr, time::Duration};
+
+    #[tokio::test]
+    async fn handshake_all_good() {
+        let [mut p1, mut p2] = udp_bound(10, 0.5, None);
+        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
+        let r2 = tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42).await });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(r1.unwrap(), Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42)));
+        assert_eq!(r2.unwrap(), Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337)));
+    }
+
+    #[tokio::test]
+    async fn handshake_with_cookie() {
+        let [mut p1, mut p2] = udp_bound(10, 0.0, None);
+        p1.0.set_cookie(Bytes::from(vec![7u8; COOKIE_SIZE]));
+        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
+        let r2 = tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42).await });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(r1.unwrap(), Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42)));
+        assert_eq!(r2.unwrap(), Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337)));
+    }
+
+    #[test]
+    fn cookies() {
+        let cookies = UdpCookies::new();
+        let addr = SocketAddr::from(([127, 0, 0, 1], 1234));
+        let other = SocketAddr::from(([127, 0, 0, 1], 1235));
+        // a HELLO that isn't padded would be amplified by the answer
+        assert!(
+            cookies
+                .answer_hello(&addr, &UdpCookies::hello()[..1])
+                .is_none()
+        );
+        let answer = cookies.answer_hello(&addr, &UdpCookies::hello()).unwrap();
+        assert!(answer.len() <= UdpCookies::hello().len());
+        let cookie = UdpCookies::read_cookie(&answer).unwrap();
+
+        let mut init = BytesMut::new();
+        init.put_u8(PACKET_INIT_COOKIE);
+        init.put_slice(&cookie);
+        init.put_slice(&[0u8; 10]);
+        assert!(cookies.accepts(&addr, &init));
+        assert!(!cookies.accepts(&other, &init));
+        assert!(!UdpCookies::new().accepts(&addr, &init));
+        init[COOKIE_SIZE] ^= 1;
+        assert!(!cookies.accepts(&addr, &init));
+    }
+
+    #[tokio::test]
+    async fn open_stream() {
+        let [p1, p2] = udp_bound(10, 0.0, None);
+        let (mut s, mut r) = (p1.0, p2.1);
+        let event = ProtocolEvent::OpenStream {
+            sid: Sid::new(10),
+            prio: 0u8,
+            promises: Promises::ORDERED,
+            guaranteed_bandwidth: 1_000_000,
+        };
+        s.send(event.clone()).await.unwrap();
+        let e = r.recv().await.unwrap();
+        assert_eq!(event, e);
+    }
+
+    #[tokio::test]
+    async fn send_short_msg() {
+        let [p1, p2] = udp_bound(10, 0.0, None);
+        let (mut s, mut r) = (p1.0, p2.1);
+        let event = ProtocolEvent::OpenStream {
+            sid: Sid::new(10),
+            prio: 3u8,
+            promises: Promises::ORDERED,
+            guaranteed_bandwidth: 1_000_000,
+        };
+        s.send(event).await.unwrap();
+        let _ = r.recv().await.unwrap();
+        let event = ProtocolEvent::Message {
+            sid: Sid::new(10),
+            data: Bytes::from(&[188u8; 600][..]),
+        };
+        s.send(event.clone()).await.unwrap();
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        let e = r.recv().await.unwrap();
+        assert_eq!(event, e);
+        // 2nd short message
+        let event = ProtocolEvent::Message {
+            sid: Sid::new(10),
+            data: Bytes::from(&[7u8; 30][..]),
+        };
+        s.send(event.clone()).await.unwrap();
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        let e = r.recv().await.unwrap();
+        assert_eq!(event, e)
+    }
+
+    #[tokio::test]
+    async fn reliable_msgs_survive_loss() {
+        let sid = Sid::new(1);
+        let [p1, p2] = udp_bound(10000, 0.3, None);
+        let (mut s1, mut r1) = p1;
+        let (mut s2, mut r2) = p2;
+        s1.send(ProtocolEvent::OpenStream {
+            sid,
+            prio: 3u8,
+            promises: Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
+            guaranteed_bandwidth: 1_000_000,
+        })
+        .await
+        .unwrap();
+        let messages = (0..100u8)
+            .map(|i| Bytes::from(vec![i; 50 + 40 * i as usize]))
+            .collect::<Vec<_>>();
+        for data in &messages {
+            s1.send(ProtocolEvent::Message {
+                sid,
+                data: data.clone(),
+            })
+            .await
+            .unwrap();
+        }
+        // flush both sides so packets and acks keep flowing, r1 handles the acks
+        tokio::spawn(async move {
+            loop {
+                s1.flush(1_000_000, Duration::from_millis(5)).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        });
+        tokio::spawn(async move { r1.recv().await });
+        let (event_s, event_r) = async_channel::unbounded();
+        tokio::spawn(async move {
+            while let Ok(event) = r2.recv().await {
+                event_s.send(event).await.unwrap();
+            }
+        });
+        tokio::spawn(async move {
+            loop {
+                s2.flush(1_000_000, Duration::from_millis(5)).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        });
+
+        assert!(matches!(
+            event_r.recv().await.unwrap(),
+            ProtocolEvent::OpenStream { .. }
+        ));
+        for data in messages {
+            assert_eq!(event_r.recv().await.unwrap(), ProtocolEvent::Message {
+                sid,
+                data
+            });
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        let mut ranges = vec![];
+        assert!(insert_into_ranges(&mut ranges, 5));
+        assert!(insert_into_ranges(&mut ranges, 7));
+        assert_eq!(ranges, vec![(5, 5), (7, 7)]);
+        assert!(insert_into_ranges(&mut ranges, 6));
+        assert_eq!(ranges, vec![(5, 7)]);
+        assert!(!insert_into_ranges(&mut ranges, 6));
+        assert!(insert_into_ranges(&mut ranges, 4));
+        assert!(insert_into_ranges(&mut ranges, 10));
+        assert_eq!(ranges, vec![(4, 7), (10, 10)]);
+        assert!(insert_into_ranges(&mut ranges, 0));
+        assert_eq!(ranges, vec![(0, 0), (4, 7), (10, 10)]);
+        assert!(insert_into_ranges(&mut ranges, u64::MAX));
+        assert!(insert_into_ranges(&mut ranges, u64::MAX - 1));
+        assert_eq!(ranges, vec![
+            (0, 0),
+            (4, 7),
+            (10, 10),
+            (u64::MAX - 1, u64::MAX)
+        ]);
+    }
+
+    #[test]
+    fn ack_newest_ranges() {
+        let state = UdpChannelState::default();
+        for pn in 0..40 {
+            assert!(state.on_packet_received(pn * 2, true));
+        }
+        let ranges = state.take_ack().unwrap();
+        assert_eq!(ranges.len(), MAX_ACK_RANGES);
+        assert_eq!(ranges.last(), Some(&(78, 78)));
+        assert_eq!(state.take_ack(), None);
+    }
+
+    #[test]
+    fn acked_ranges_are_forgotten() {
+        let state = UdpChannelState::default();
+        for pn in [0, 2, 4, 6] {
+            assert!(state.on_packet_received(pn, true));
+        }
+        assert_eq!(state.take_ack().unwrap(), vec![
+            (0, 0),
+            (2, 2),
+            (4, 4),
+            (6, 6)
+        ]);
+        assert!(state.on_packet_received(8, true));
+        // the packet carrying the ACK above was acked
+        state.on_ack_acked(0, 6);
+        assert_eq!(state.take_ack().unwrap(), vec![(8, 8)]);
+        // late packets the remote side knows about aren't handled again
+        assert!(!state.on_packet_received(5, true));
+        assert!(state.on_packet_received(7, true));
+        assert_eq!(state.take_ack().unwrap(), vec![(7, 8)]);
+    }
+
+    #[tokio::test]
+    async fn pending_control_is_bounded() {
+        let [_, (_, mut r)] = udp_bound(10, 0.0, None);
+        let control = |cseq| UdpFrame::Control {
+            cseq,
+            frame: OTFrame::Shutdown,
+        };
+        assert_eq!(r.handle_frame(control(MAX_PENDING_CONTROL - 1)), Ok(()));
+        assert_eq!(
+            r.handle_frame(control(MAX_PENDING_CONTROL)),
+            Err(ProtocolError::Violated)
+        );
+    }
+
+    fn deliver_all(promises: Promises, seqs: &[u64]) -> Vec<u8> {
+        let sid = Sid::new(1);
+        let mut stream = RecvStreamInfo::new(promises);
+        let mut events = VecDeque::new();
+        for &seq in seqs {
+            stream.deliver(sid, seq, Bytes::from(vec![seq as u8]), &mut events);
+        }
+        events
+            .into_iter()
+            .map(|e| match e {
+                ProtocolEvent::Message { data, .. } => data[0],
+                e => panic!("unexpected event {:?}", e),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ordered_delivery() {
+        let reliable = Promises::ORDERED | Promises::GUARANTEED_DELIVERY;
+        assert_eq!(deliver_all(reliable, &[0, 2, 1, 1, 3]), vec![0, 1, 2, 3]);
+        // late messages are dropped
+        assert_eq!(deliver_all(Promises::ORDERED, &[0, 2, 1, 3]), vec![0, 2, 3]);
+        // duplicates are dropped
+        let unordered = Promises::GUARANTEED_DELIVERY;
+        assert_eq!(deliver_all(unordered, &[0, 2, 2, 1, 0]), vec![0, 2, 1]);
+        assert_eq!(deliver_all(Promises::empty(), &[1, 0]), vec![1, 0]);
+    }
+
+    #[test]
+    fn congestion_window() {
+        let mut cc = CongestionController::new();
+        let initial = cc.window;
+        cc.on_sent(1000);
+        cc.on_acked(0, 1000);
+        assert_eq!(cc.window, initial + 1000);
+        cc.on_sent(2000);
+        cc.on_lost(1, 1000, 3);
+        assert_eq!(cc.window, (initial + 1000) / 2);
+        // packets sent before the reduction don't reduce the window again
+        cc.on_lost(2, 1000, 3);
+        assert_eq!(cc.window, (initial + 1000) / 2);
+        assert_eq!(cc.bytes_in_flight, 0);
+        cc.on_timeout();
+        assert_eq!(cc.window, MIN_WINDOW);
+    }
+}
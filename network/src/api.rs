@@ -14,7 +14,7 @@ use serde::{de::DeserializeOwned, Serialize};
 use std::{
     net::SocketAddr,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
     time::Duration,
@@ -120,6 +120,11 @@ pub enum ParticipantError {
     /// that failed. This is also returned when local side tries to do
     /// something while remote site gracefully disconnects
     ProtocolFailedUnrecoverable,
+    ///None of the channels can keep the [`Promises`] of the stream, e.g.
+    /// [`ENCRYPTED`] on a protocol which can't encrypt
+    ///
+    /// [`ENCRYPTED`]: network_protocol::Promises::ENCRYPTED
+    PromisesUnsupported,
 }
 
 /// Error type thrown by [`Streams`](Stream) methods
@@ -189,6 +194,7 @@ pub struct Network {
     connect_sender: Mutex<mpsc::UnboundedSender<A2sConnect>>,
     connected_receiver: Mutex<mpsc::UnboundedReceiver<Participant>>,
     shutdown_network_s: Option<oneshot::Sender<oneshot::Sender<()>>>,
+    resume_grace_period: Arc<AtomicU64>,
 }
 
 impl Network {
@@ -264,9 +270,11 @@ impl Network {
         let p = participant_id;
         let span = tracing::info_span!("network", ?p);
         span.in_scope(|| trace!("Starting Network"));
+        let resume_grace_period = Arc::new(AtomicU64::new(0));
         let (scheduler, listen_sender, connect_sender, connected_receiver, shutdown_sender) =
             Scheduler::new(
                 participant_id,
+                Arc::clone(&resume_grace_period),
                 #[cfg(feature = "metrics")]
                 registry,
             );
@@ -294,9 +302,38 @@ impl Network {
             connect_sender: Mutex::new(connect_sender),
             connected_receiver: Mutex::new(connected_receiver),
             shutdown_network_s: Some(shutdown_network_s),
+            resume_grace_period,
         }
     }
 
+    /// Lets [`Participants`] survive a connection loss of up to `grace`,
+    /// instead of closing all their [`Streams`]. It applies to `Participants`
+    /// connected afterwards, and both sides need to set it.
+    ///
+    /// When the last channel of a `Participant` fails, the side which called
+    /// [`connect`] reconnects to the same [`ConnectAddr`]. The remote side
+    /// recognizes it by the secret exchanged in the first handshake and the
+    /// new channel replaces the old one. The secret is never sent in
+    /// plaintext, and only protocols which support [`ENCRYPTED`] streams,
+    /// i.e. not UDP, can resume a session. Messages of streams with
+    /// [`ORDERED`] and [`GUARANTEED_DELIVERY`] that didn't reach the remote
+    /// side are sent again, messages of other streams might get lost. Streams
+    /// which had too much data in flight to keep it for a resume are closed
+    /// instead.
+    ///
+    /// Defaults to zero, i.e. `Participants` are closed on a connection loss.
+    ///
+    /// [`Participants`]: crate::api::Participant
+    /// [`Streams`]: crate::api::Stream
+    /// [`connect`]: Network::connect
+    /// [`ENCRYPTED`]: network_protocol::Promises::ENCRYPTED
+    /// [`ORDERED`]: network_protocol::Promises::ORDERED
+    /// [`GUARANTEED_DELIVERY`]: network_protocol::Promises::GUARANTEED_DELIVERY
+    pub fn set_resume_grace_period(&self, grace: Duration) {
+        self.resume_grace_period
+            .store(grace.as_millis() as u64, Ordering::Relaxed);
+    }
+
     /// starts listening on an [`ListenAddr`].
     /// When the method returns the `Network` is ready to listen for incoming
     /// connections OR has returned a [`NetworkError`] (e.g. port already used).
@@ -305,9 +342,9 @@ impl Network {
     /// support multiple Protocols or NICs.
     ///
     /// # Examples
-    /// ```ignore
+    /// ```no_run
     /// use tokio::runtime::Runtime;
-    /// use veloren_network::{Network, Pid, ListenAddr};
+    /// use veloren_network::{ListenAddr, Network, Pid};
     ///
     /// # fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     /// // Create a Network, listen on port `2000` TCP on all NICs and `2001` UDP locally
@@ -348,9 +385,9 @@ impl Network {
     /// When the method returns the Network either returns a [`Participant`]
     /// ready to open [`Streams`] on OR has returned a [`NetworkError`] (e.g.
     /// can't connect, or invalid Handshake) # Examples
-    /// ```ignore
+    /// ```no_run
     /// use tokio::runtime::Runtime;
-    /// use veloren_network::{Network, Pid, ListenAddr, ConnectAddr};
+    /// use veloren_network::{ConnectAddr, ListenAddr, Network, Pid};
     ///
     /// # fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     /// // Create a Network, connect on port `2010` TCP and `2011` UDP like listening above
@@ -363,9 +400,6 @@ impl Network {
     ///     let p1 = network
     ///         .connect(ConnectAddr::Tcp("127.0.0.1:2010".parse().unwrap()))
     ///         .await?;
-    ///     # //this doesn't work yet, so skip the test
-    ///     # //TODO fixme!
-    ///     # return Ok(());
     ///     let p2 = network
     ///         .connect(ConnectAddr::Udp("127.0.0.1:2011".parse().unwrap()))
     ///         .await?;
@@ -547,8 +581,10 @@ impl Participant {
     ///   [`Bandwidth`] for details.
     ///
     /// A [`ParticipantError`] might be thrown if the `Participant` is already
-    /// closed. [`Streams`] can be created without a answer from the remote
-    /// side, resulting in very fast creation and closing latency.
+    /// closed, or if none of its channels can keep the `promises`, e.g.
+    /// [`ENCRYPTED`] on a UDP channel. [`Streams`] can be created without a
+    /// answer from the remote side, resulting in very fast creation and
+    /// closing latency.
     ///
     /// # Examples
     /// ```rust
@@ -579,6 +615,7 @@ impl Participant {
     /// [`Bandwidth`]: network_protocol::Bandwidth
     /// [`Promises`]: network_protocol::Promises
     /// [`Streams`]: crate::api::Stream
+    /// [`ENCRYPTED`]: network_protocol::Promises::ENCRYPTED
     #[instrument(name="network", skip(self, prio, promises, bandwidth), fields(p = %self.local_pid))]
     pub async fn open(
         &self,
@@ -587,7 +624,8 @@ impl Participant {
         bandwidth: Bandwidth,
     ) -> Result<Stream, ParticipantError> {
         debug_assert!(prio <= network_protocol::HIGHEST_PRIO, "invalid prio");
-        let (p2a_return_stream_s, p2a_return_stream_r) = oneshot::channel::<Stream>();
+        let (p2a_return_stream_s, p2a_return_stream_r) =
+            oneshot::channel::<Result<Stream, ParticipantError>>();
         if let Err(e) = self.a2b_open_stream_s.lock().await.send((
             prio,
             promises,
@@ -598,11 +636,12 @@ impl Participant {
             return Err(ParticipantError::ParticipantDisconnected);
         }
         match p2a_return_stream_r.await {
-            Ok(stream) => {
+            Ok(Ok(stream)) => {
                 let sid = stream.sid;
                 trace!(?sid, "opened stream");
                 Ok(stream)
             },
+            Ok(Err(e)) => Err(e),
             Err(_) => {
                 debug!("p2a_return_stream_r failed, closing participant");
                 Err(ParticipantError::ParticipantDisconnected)
@@ -1238,6 +1277,9 @@ impl core::fmt::Display for ParticipantError {
             ParticipantError::ProtocolFailedUnrecoverable => {
                 write!(f, "underlying protocol failed unrecoverable")
             },
+            ParticipantError::PromisesUnsupported => {
+                write!(f, "no channel supports the promises of the stream")
+            },
         }
     }
 }
@@ -1,14 +1,15 @@
 use crate::api::NetworkConnectError;
 use async_trait::async_trait;
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use futures_util::FutureExt;
 #[cfg(feature = "quic")]
 use futures_util::StreamExt;
 use hashbrown::HashMap;
 use network_protocol::{
-    Bandwidth, Cid, InitProtocolError, MpscMsg, MpscRecvProtocol, MpscSendProtocol, Pid,
+    Bandwidth, Cid, InitProtocolError, MpscMsg, MpscRecvProtocol, MpscSendProtocol, Pid, Promises,
     ProtocolError, ProtocolEvent, ProtocolMetricCache, ProtocolMetrics, Sid, TcpRecvProtocol,
-    TcpSendProtocol, UnreliableDrain, UnreliableSink,
+    TcpSendProtocol, UdpChannelState, UdpCookies, UdpRecvProtocol, UdpSendProtocol,
+    UnreliableDrain, UnreliableSink,
 };
 #[cfg(feature = "quic")]
 use network_protocol::{QuicDataFormat, QuicDataFormatStream, QuicRecvProtocol, QuicSendProtocol};
@@ -35,6 +36,7 @@ use tracing::{error, info, trace, warn};
 pub(crate) enum Protocols {
     Tcp((TcpSendProtocol<TcpDrain>, TcpRecvProtocol<TcpSink>)),
     Mpsc((MpscSendProtocol<MpscDrain>, MpscRecvProtocol<MpscSink>)),
+    Udp((UdpSendProtocol<UdpDrain>, UdpRecvProtocol<UdpSink>)),
     #[cfg(feature = "quic")]
     Quic((QuicSendProtocol<QuicDrain>, QuicRecvProtocol<QuicSink>)),
 }
@@ -43,6 +45,7 @@ pub(crate) enum Protocols {
 pub(crate) enum SendProtocols {
     Tcp(TcpSendProtocol<TcpDrain>),
     Mpsc(MpscSendProtocol<MpscDrain>),
+    Udp(UdpSendProtocol<UdpDrain>),
     #[cfg(feature = "quic")]
     Quic(QuicSendProtocol<QuicDrain>),
 }
@@ -51,6 +54,7 @@ pub(crate) enum SendProtocols {
 pub(crate) enum RecvProtocols {
     Tcp(TcpRecvProtocol<TcpSink>),
     Mpsc(MpscRecvProtocol<MpscSink>),
+    Udp(UdpRecvProtocol<UdpSink>),
     #[cfg(feature = "quic")]
     Quic(QuicRecvProtocol<QuicSink>),
 }
@@ -67,7 +71,14 @@ pub(crate) type C2cMpscConnect = (
 );
 
 impl Protocols {
+    /// Remote sides a UDP listener keeps channels for
+    const MAX_UDP_REMOTES: usize = 1024;
     const MPSC_CHANNEL_BOUND: usize = 1000;
+    const UDP_BUFFER_SIZE: usize = 1500;
+    const UDP_HELLO_ATTEMPTS: usize = 10;
+    const UDP_HELLO_TIMEOUT: Duration = Duration::from_millis(500);
+    /// Datagrams queued per channel, more are dropped like on a congested link
+    const UDP_QUEUE_SIZE: usize = 1024;
 
     pub(crate) async fn with_tcp_connect(
         addr: SocketAddr,
@@ -218,6 +229,185 @@ impl Protocols {
         Protocols::Mpsc((sp, rp))
     }
 
+    pub(crate) async fn with_udp_connect(
+        addr: SocketAddr,
+        metrics: ProtocolMetricCache,
+    ) -> Result<Self, NetworkConnectError> {
+        let bind_addr = match addr {
+            SocketAddr::V4(_) => SocketAddr::from(([0u8; 4], 0)),
+            SocketAddr::V6(_) => SocketAddr::from(([0u16; 8], 0)),
+        };
+        let socket = net::UdpSocket::bind(bind_addr)
+            .await
+            .map_err(NetworkConnectError::Io)?;
+        let socket = Arc::new(socket);
+        info!("Connecting Udp to: {}", &addr);
+        let (datagram_s, mut datagram_r) = mpsc::channel(Self::UDP_QUEUE_SIZE);
+        let socket_clone = Arc::clone(&socket);
+        tokio::spawn(async move {
+            let mut buffer = [0u8; Self::UDP_BUFFER_SIZE];
+            loop {
+                match socket_clone.recv_from(&mut buffer).await {
+                    Ok((n, remote_addr)) if remote_addr == addr => {
+                        if let Err(mpsc::error::TrySendError::Closed(_)) =
+                            datagram_s.try_send(BytesMut::from(&buffer[..n]))
+                        {
+                            break;
+                        }
+                    },
+                    Ok((_, remote_addr)) => {
+                        trace!(?remote_addr, "ignoring datagram from unknown address")
+                    },
+                    Err(e) => {
+                        trace!(?e, "UdpSocket Error, stopping");
+                        break;
+                    },
+                }
+            }
+        });
+        let cookie = Self::udp_cookie(&socket, addr, &mut datagram_r).await?;
+        Ok(Self::new_udp(
+            socket,
+            addr,
+            datagram_r,
+            Some(cookie),
+            metrics,
+        ))
+    }
+
+    /// Asks the listener for the cookie it requires in our handshake packets
+    async fn udp_cookie(
+        socket: &net::UdpSocket,
+        addr: SocketAddr,
+        datagram_r: &mut mpsc::Receiver<BytesMut>,
+    ) -> Result<Bytes, NetworkConnectError> {
+        for _ in 0..Self::UDP_HELLO_ATTEMPTS {
+            socket
+                .send_to(&UdpCookies::hello(), addr)
+                .await
+                .map_err(NetworkConnectError::Io)?;
+            let deadline = tokio::time::Instant::now() + Self::UDP_HELLO_TIMEOUT;
+            while let Ok(Some(datagram)) =
+                tokio::time::timeout_at(deadline, datagram_r.recv()).await
+            {
+                if let Some(cookie) = UdpCookies::read_cookie(&datagram) {
+                    return Ok(cookie);
+                }
+            }
+        }
+        Err(NetworkConnectError::Io(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "Udp listener didn't answer",
+        )))
+    }
+
+    pub(crate) async fn with_udp_listen(
+        addr: SocketAddr,
+        cids: Arc<AtomicU64>,
+        metrics: Arc<ProtocolMetrics>,
+        s2s_stop_listening_r: oneshot::Receiver<()>,
+        c2s_protocol_s: mpsc::UnboundedSender<(Self, Cid)>,
+    ) -> std::io::Result<()> {
+        let socket = net::UdpSocket::bind(addr).await?;
+        trace!(?addr, "Udp Listener bound");
+        Self::udp_listen(socket, cids, metrics, s2s_stop_listening_r, c2s_protocol_s);
+        Ok(())
+    }
+
+    /// All remote sides share the one socket, datagrams are dispatched by
+    /// their address. A channel is only allocated for a remote side that
+    /// returned a cookie, see [`UdpCookies`]. Existing connections keep
+    /// working after listening stopped.
+    fn udp_listen(
+        socket: net::UdpSocket,
+        cids: Arc<AtomicU64>,
+        metrics: Arc<ProtocolMetrics>,
+        s2s_stop_listening_r: oneshot::Receiver<()>,
+        c2s_protocol_s: mpsc::UnboundedSender<(Self, Cid)>,
+    ) {
+        let socket = Arc::new(socket);
+        let addr = socket.local_addr().ok();
+        let mut end_receiver = s2s_stop_listening_r.fuse();
+        tokio::spawn(async move {
+            let cookies = UdpCookies::new();
+            let mut remotes: HashMap<SocketAddr, mpsc::Sender<BytesMut>> = HashMap::new();
+            let mut buffer = [0u8; Self::UDP_BUFFER_SIZE];
+            let mut listening = true;
+            loop {
+                let data = if listening {
+                    select! {
+                        next = socket.recv_from(&mut buffer).fuse() => next,
+                        _ = &mut end_receiver => {
+                            trace!(?addr, "Udp Listener stopped");
+                            listening = false;
+                            continue;
+                        },
+                    }
+                } else {
+                    socket.recv_from(&mut buffer).await
+                };
+                remotes.retain(|_, datagram_s| !datagram_s.is_closed());
+                if !listening && remotes.is_empty() {
+                    break;
+                }
+                let (n, remote_addr) = match data {
+                    Ok(data) => data,
+                    Err(e) => {
+                        trace!(?e, "UdpSocket Error, ignoring datagram");
+                        continue;
+                    },
+                };
+                let datagram = &buffer[..n];
+                if let Some(datagram_s) = remotes.get(&remote_addr) {
+                    // the protocol recovers from datagrams dropped on a full queue
+                    let _ = datagram_s.try_send(BytesMut::from(datagram));
+                } else if !listening {
+                    trace!(?remote_addr, "ignoring datagram, not listening anymore");
+                } else if let Some(answer) = cookies.answer_hello(&remote_addr, datagram) {
+                    let _ = socket.send_to(&answer, remote_addr).await;
+                } else if !cookies.accepts(&remote_addr, datagram) {
+                    trace!(?remote_addr, "ignoring datagram without a valid cookie");
+                } else if remotes.len() >= Self::MAX_UDP_REMOTES {
+                    warn!(?remote_addr, "Too many Udp remote sides, ignoring");
+                } else {
+                    let cid = cids.fetch_add(1, Ordering::Relaxed);
+                    info!(?remote_addr, ?cid, "Accepting Udp from");
+                    let (datagram_s, datagram_r) = mpsc::channel(Self::UDP_QUEUE_SIZE);
+                    let _ = datagram_s.try_send(BytesMut::from(datagram));
+                    remotes.insert(remote_addr, datagram_s);
+                    let metrics = ProtocolMetricCache::new(&cid.to_string(), Arc::clone(&metrics));
+                    let udp =
+                        Self::new_udp(Arc::clone(&socket), remote_addr, datagram_r, None, metrics);
+                    let _ = c2s_protocol_s.send((udp, cid));
+                }
+            }
+        });
+    }
+
+    /// `cookie` is the one handed out by the listener, if we connect to one
+    pub(crate) fn new_udp(
+        socket: Arc<net::UdpSocket>,
+        remote_addr: SocketAddr,
+        receiver: mpsc::Receiver<BytesMut>,
+        cookie: Option<Bytes>,
+        metrics: ProtocolMetricCache,
+    ) -> Self {
+        let state = UdpChannelState::default();
+        let mut sp = UdpSendProtocol::new(
+            UdpDrain {
+                socket,
+                remote_addr,
+            },
+            state.clone(),
+            metrics.clone(),
+        );
+        if let Some(cookie) = cookie {
+            sp.set_cookie(cookie);
+        }
+        let rp = UdpRecvProtocol::new(UdpSink { receiver }, state, metrics);
+        Protocols::Udp((sp, rp))
+    }
+
     #[cfg(feature = "quic")]
     pub(crate) async fn with_quic_connect(
         addr: SocketAddr,
@@ -352,10 +542,22 @@ impl Protocols {
         Ok(Protocols::Quic((sp, rp)))
     }
 
+    /// returns all promises the protocol of this channel can take care of
+    pub(crate) fn supported_promises(&self) -> Promises {
+        match self {
+            Protocols::Tcp(_) => TcpSendProtocol::<TcpDrain>::supported_promises(),
+            Protocols::Mpsc(_) => MpscSendProtocol::<MpscDrain>::supported_promises(),
+            Protocols::Udp(_) => UdpSendProtocol::<UdpDrain>::supported_promises(),
+            #[cfg(feature = "quic")]
+            Protocols::Quic(_) => QuicSendProtocol::<QuicDrain>::supported_promises(),
+        }
+    }
+
     pub(crate) fn split(self) -> (SendProtocols, RecvProtocols) {
         match self {
             Protocols::Tcp((s, r)) => (SendProtocols::Tcp(s), RecvProtocols::Tcp(r)),
             Protocols::Mpsc((s, r)) => (SendProtocols::Mpsc(s), RecvProtocols::Mpsc(r)),
+            Protocols::Udp((s, r)) => (SendProtocols::Udp(s), RecvProtocols::Udp(r)),
             #[cfg(feature = "quic")]
             Protocols::Quic((s, r)) => (SendProtocols::Quic(s), RecvProtocols::Quic(r)),
         }
@@ -373,18 +575,33 @@ impl network_protocol::InitProtocol for Protocols {
         match self {
             Protocols::Tcp(p) => p.initialize(initializer, local_pid, secret).await,
             Protocols::Mpsc(p) => p.initialize(initializer, local_pid, secret).await,
+            Protocols::Udp(p) => p.initialize(initializer, local_pid, secret).await,
             #[cfg(feature = "quic")]
             Protocols::Quic(p) => p.initialize(initializer, local_pid, secret).await,
         }
     }
 }
 
+impl SendProtocols {
+    /// returns all promises the protocol of this channel can take care of
+    pub(crate) fn supported_promises(&self) -> Promises {
+        match self {
+            SendProtocols::Tcp(_) => TcpSendProtocol::<TcpDrain>::supported_promises(),
+            SendProtocols::Mpsc(_) => MpscSendProtocol::<MpscDrain>::supported_promises(),
+            SendProtocols::Udp(_) => UdpSendProtocol::<UdpDrain>::supported_promises(),
+            #[cfg(feature = "quic")]
+            SendProtocols::Quic(_) => QuicSendProtocol::<QuicDrain>::supported_promises(),
+        }
+    }
+}
+
 #[async_trait]
 impl network_protocol::SendProtocol for SendProtocols {
     fn notify_from_recv(&mut self, event: ProtocolEvent) {
         match self {
             SendProtocols::Tcp(s) => s.notify_from_recv(event),
             SendProtocols::Mpsc(s) => s.notify_from_recv(event),
+            SendProtocols::Udp(s) => s.notify_from_recv(event),
             #[cfg(feature = "quic")]
             SendProtocols::Quic(s) => s.notify_from_recv(event),
         }
@@ -394,6 +611,7 @@ impl network_protocol::SendProtocol for SendProtocols {
         match self {
             SendProtocols::Tcp(s) => s.send(event).await,
             SendProtocols::Mpsc(s) => s.send(event).await,
+            SendProtocols::Udp(s) => s.send(event).await,
             #[cfg(feature = "quic")]
             SendProtocols::Quic(s) => s.send(event).await,
         }
@@ -407,6 +625,7 @@ impl network_protocol::SendProtocol for SendProtocols {
         match self {
             SendProtocols::Tcp(s) => s.flush(bandwidth, dt).await,
             SendProtocols::Mpsc(s) => s.flush(bandwidth, dt).await,
+            SendProtocols::Udp(s) => s.flush(bandwidth, dt).await,
             #[cfg(feature = "quic")]
             SendProtocols::Quic(s) => s.flush(bandwidth, dt).await,
         }
@@ -419,6 +638,7 @@ impl network_protocol::RecvProtocol for RecvProtocols {
         match self {
             RecvProtocols::Tcp(r) => r.recv().await,
             RecvProtocols::Mpsc(r) => r.recv().await,
+            RecvProtocols::Udp(r) => r.recv().await,
             #[cfg(feature = "quic")]
             RecvProtocols::Quic(r) => r.recv().await,
         }
@@ -497,6 +717,40 @@ impl UnreliableSink for MpscSink {
     }
 }
 
+///////////////////////////////////////
+//// UDP
+#[derive(Debug)]
+pub struct UdpDrain {
+    socket: Arc<net::UdpSocket>,
+    remote_addr: SocketAddr,
+}
+
+#[derive(Debug)]
+pub struct UdpSink {
+    receiver: mpsc::Receiver<BytesMut>,
+}
+
+#[async_trait]
+impl UnreliableDrain for UdpDrain {
+    type DataFormat = BytesMut;
+
+    async fn send(&mut self, data: Self::DataFormat) -> Result<(), ProtocolError> {
+        match self.socket.send_to(&data, self.remote_addr).await {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ProtocolError::Closed),
+        }
+    }
+}
+
+#[async_trait]
+impl UnreliableSink for UdpSink {
+    type DataFormat = BytesMut;
+
+    async fn recv(&mut self) -> Result<Self::DataFormat, ProtocolError> {
+        self.receiver.recv().await.ok_or(ProtocolError::Closed)
+    }
+}
+
 ///////////////////////////////////////
 //// QUIC
 #[cfg(feature = "quic")]
@@ -727,4 +981,32 @@ mod tests {
         assert!(e.is_err());
         assert_eq!(e.unwrap_err(), ProtocolError::Closed);
     }
+
+    #[tokio::test]
+    async fn udp_sinks() {
+        let metrics = Arc::new(ProtocolMetrics::new().unwrap());
+        let socket = net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let (_stop_s, stop_r) = oneshot::channel();
+        let (protocol_s, mut protocol_r) = mpsc::unbounded_channel();
+        let cids = Arc::new(AtomicU64::new(0));
+        Protocols::udp_listen(socket, cids, Arc::clone(&metrics), stop_r, protocol_s);
+        // handshake packets without a cookie don't get a channel
+        let spoofed = net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        spoofed.send_to(&[0u8; 64], addr).await.unwrap();
+        let metrics = ProtocolMetricCache::new("0", metrics);
+        let client = Protocols::with_udp_connect(addr, metrics).await.unwrap();
+        let (mut s, _) = client.split();
+        let event = ProtocolEvent::OpenStream {
+            sid: Sid::new(1),
+            prio: 4u8,
+            promises: Promises::GUARANTEED_DELIVERY,
+            guaranteed_bandwidth: 1_000,
+        };
+        s.send(event.clone()).await.unwrap();
+        let (server, cid) = protocol_r.recv().await.unwrap();
+        assert_eq!(cid, 0);
+        let (_, mut r) = server.split();
+        assert_eq!(r.recv().await.unwrap(), event);
+    }
 }
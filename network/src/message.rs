@@ -38,8 +38,12 @@ impl Message {
     /// [`Streams`]: crate::api::Stream
     pub fn serialize<M: Serialize + ?Sized>(message: &M, stream_params: StreamParams) -> Self {
         //this will never fail: https://docs.rs/bincode/0.8.0/bincode/fn.serialize.html
-        let serialized_data = bincode::serialize(message).unwrap();
+        Self::from_serialized(bincode::serialize(message).unwrap(), stream_params)
+    }
 
+    /// Like [`Message::serialize`], for a message which was already serialized
+    /// with bincode, e.g. to also use the serialized data for something else.
+    pub fn from_serialized(serialized_data: Vec<u8>, stream_params: StreamParams) -> Self {
         #[cfg(feature = "compression")]
         let compressed = stream_params.promises.contains(Promises::COMPRESSED);
         #[cfg(feature = "compression")]
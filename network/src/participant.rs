@@ -12,8 +12,9 @@ use network_protocol::{
     _internal::SortedVec,
 };
 use std::{
+    collections::VecDeque,
     sync::{
-        atomic::{AtomicBool, AtomicI32, Ordering},
+        atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering},
         Arc,
     },
     time::{Duration, Instant},
@@ -26,10 +27,18 @@ use tokio::{
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tracing::*;
 
-pub(crate) type A2bStreamOpen = (Prio, Promises, Bandwidth, oneshot::Sender<Stream>);
+pub(crate) type A2bStreamOpen = (
+    Prio,
+    Promises,
+    Bandwidth,
+    oneshot::Sender<Result<Stream, ParticipantError>>,
+);
 pub(crate) type S2bCreateChannel = (Cid, Sid, Protocols, oneshot::Sender<()>);
 pub(crate) type S2bShutdownBparticipant = (Duration, oneshot::Sender<Result<(), ParticipantError>>);
 pub(crate) type B2sPrioStatistic = (Pid, u64, u64);
+/// The participant lost its last channel and waits till the deadline for the
+/// session to be resumed
+pub(crate) type B2sResume = (Pid, Instant);
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -40,14 +49,105 @@ struct ChannelInfo {
 
 #[derive(Debug)]
 struct StreamInfo {
-    #[allow(dead_code)]
     prio: Prio,
-    #[allow(dead_code)]
     promises: Promises,
+    guaranteed_bandwidth: Bandwidth,
+    /// messages received so far, told the remote side to resume the stream
+    received: AtomicU64,
     send_closed: Arc<AtomicBool>,
     b2a_msg_recv_s: Mutex<async_channel::Sender<Bytes>>,
 }
 
+/// Messages of a stream which might not have reached the remote side yet.
+/// They are replayed when the stream is moved to a new channel.
+#[derive(Debug)]
+struct ReplayBuffer {
+    /// Only `ORDERED` and `GUARANTEED_DELIVERY` streams can be replayed, as
+    /// the remote side counts their messages
+    replayable: bool,
+    /// More than `MAX_BYTES` were unacknowledged, the stream can't be replayed
+    /// anymore and gets closed instead of resumed
+    overflowed: bool,
+    /// number of the first message in `msgs`
+    first: u64,
+    msgs: VecDeque<Bytes>,
+    bytes: usize,
+    /// After moving to a new channel, messages are held back till the remote
+    /// side told us on that channel how many it received
+    held: Option<Cid>,
+    /// the api closed the stream while it was held back
+    close_when_released: bool,
+}
+
+impl ReplayBuffer {
+    /// Messages get acknowledged every second, so this is only reached by
+    /// very busy streams
+    const MAX_BYTES: usize = 16 * 1024 * 1024;
+
+    fn new(promises: Promises, resumable: bool) -> Self {
+        Self {
+            replayable: resumable
+                && promises.contains(Promises::ORDERED | Promises::GUARANTEED_DELIVERY),
+            overflowed: false,
+            first: 0,
+            msgs: VecDeque::new(),
+            bytes: 0,
+            held: None,
+            close_when_released: false,
+        }
+    }
+
+    /// returns whether the message can be sent right away
+    fn push(&mut self, data: &Bytes) -> bool {
+        if self.held.is_none() && (!self.replayable || self.overflowed) {
+            return true;
+        }
+        self.bytes += data.len();
+        self.msgs.push_back(data.clone());
+        if self.held.is_none() && self.bytes > Self::MAX_BYTES {
+            // Rather than dropping single messages and replaying a stream with gaps,
+            // give up on replaying it at all
+            self.overflowed = true;
+            self.first += self.msgs.len() as u64;
+            self.msgs.clear();
+            self.bytes = 0;
+        }
+        self.held.is_none()
+    }
+
+    fn pop(&mut self) {
+        if let Some(data) = self.msgs.pop_front() {
+            self.bytes -= data.len();
+            self.first += 1;
+        }
+    }
+
+    /// The remote side received `count` messages
+    fn acknowledge(&mut self, count: u64) {
+        if self.overflowed {
+            return;
+        }
+        while self.first < count && !self.msgs.is_empty() {
+            self.pop();
+        }
+    }
+
+    /// returns the messages to send on the new channel, Err if messages the
+    /// remote side is missing were already dropped
+    fn release(&mut self, count: u64) -> Result<Vec<Bytes>, ()> {
+        self.held = None;
+        if !self.replayable {
+            self.bytes = 0;
+            return Ok(self.msgs.drain(..).collect());
+        }
+        if self.overflowed || count < self.first {
+            return Err(());
+        }
+        self.acknowledge(count);
+        Ok(self.msgs.iter().cloned().collect())
+    }
+}
+
 #[derive(Debug)]
 struct ControlChannels {
     a2b_open_stream_r: mpsc::UnboundedReceiver<A2bStreamOpen>,
@@ -75,9 +175,14 @@ pub struct BParticipant {
     shutdown_barrier: AtomicI32,
     metrics: Arc<NetworkMetrics>,
     open_stream_channels: Arc<Mutex<Option<OpenStreamInfo>>>,
+    /// How long to wait for a new channel after the last one failed
+    resume_grace_period: Duration,
+    /// A graceful shutdown was requested, locally or by the remote side
+    shutting_down: AtomicBool,
 }
 
 impl BParticipant {
+    const ACK_INTERVAL: Duration = Duration::from_secs(1);
     // We use integer instead of Barrier to not block mgr from freeing at the end
     const BARR_CHANNEL: i32 = 1;
     const BARR_RECV: i32 = 4;
@@ -90,6 +195,7 @@ impl BParticipant {
         local_pid: Pid,
         remote_pid: Pid,
         offset_sid: Sid,
+        resume_grace_period: Duration,
         metrics: Arc<NetworkMetrics>,
     ) -> (
         Self,
@@ -127,6 +233,8 @@ impl BParticipant {
                 run_channels,
                 metrics,
                 open_stream_channels: Arc::new(Mutex::new(None)),
+                resume_grace_period,
+                shutting_down: AtomicBool::new(false),
             },
             a2b_open_stream_s,
             b2a_stream_opened_r,
@@ -136,11 +244,15 @@ impl BParticipant {
         )
     }
 
-    pub async fn run(mut self, b2s_prio_statistic_s: mpsc::UnboundedSender<B2sPrioStatistic>) {
+    pub async fn run(
+        mut self,
+        b2s_prio_statistic_s: mpsc::UnboundedSender<B2sPrioStatistic>,
+        b2s_resume_s: mpsc::UnboundedSender<B2sResume>,
+    ) {
         let (b2b_add_send_protocol_s, b2b_add_send_protocol_r) =
             mpsc::unbounded_channel::<(Cid, SendProtocols)>();
         let (b2b_add_recv_protocol_s, b2b_add_recv_protocol_r) =
-            mpsc::unbounded_channel::<(Cid, RecvProtocols)>();
+            mpsc::unbounded_channel::<(Cid, RecvProtocols, oneshot::Sender<()>)>();
         let (b2b_close_send_protocol_s, b2b_close_send_protocol_r) =
             async_channel::unbounded::<Cid>();
         let (b2b_force_close_recv_protocol_s, b2b_force_close_recv_protocol_r) =
@@ -149,6 +261,9 @@ impl BParticipant {
             crossbeam_channel::unbounded::<(Cid, Sid, Prio, Promises, u64)>();
         let (b2b_notify_send_of_recv_close_s, b2b_notify_send_of_recv_close_r) =
             crossbeam_channel::unbounded::<(Cid, Sid)>();
+        let (b2b_notify_send_of_recv_received_s, b2b_notify_send_of_recv_received_r) =
+            crossbeam_channel::unbounded::<(Cid, Sid, u64)>();
+        let (b2b_ack_stream_s, b2b_ack_stream_r) = crossbeam_channel::unbounded::<(Cid, Sid)>();
 
         let (a2b_close_stream_s, a2b_close_stream_r) = mpsc::unbounded_channel::<Sid>();
         let (a2b_msg_s, a2b_msg_r) = crossbeam_channel::unbounded::<(Sid, Bytes)>();
@@ -168,7 +283,11 @@ impl BParticipant {
                 b2b_close_send_protocol_r,
                 b2b_notify_send_of_recv_open_r,
                 b2b_notify_send_of_recv_close_r,
+                b2b_notify_send_of_recv_received_r,
+                b2b_ack_stream_r,
+                b2b_force_close_recv_protocol_s.clone(),
                 b2s_prio_statistic_s,
+                b2s_resume_s,
                 run_channels.b2a_bandwidth_stats_s,
             )
             .instrument(tracing::info_span!("send")),
@@ -179,6 +298,8 @@ impl BParticipant {
                 b2b_close_send_protocol_s.clone(),
                 b2b_notify_send_of_recv_open_s,
                 b2b_notify_send_of_recv_close_s,
+                b2b_notify_send_of_recv_received_s,
+                b2b_ack_stream_s,
             )
             .instrument(tracing::info_span!("recv")),
             self.create_channel_mgr(
@@ -217,7 +338,11 @@ impl BParticipant {
         ).or_else(
             || {
                 warn!("couldn't satisfy promises");
-                all.data.first().map(|(c, _)| *c)
+                // never fall back to a channel which would send an encrypted stream in plaintext
+                all.data.iter().find(|(_, p)| {
+                    !promises.contains(Promises::ENCRYPTED)
+                        || p.supported_promises().contains(Promises::ENCRYPTED)
+                }).map(|(c, _)| *c)
             }
         )
     }
@@ -239,20 +364,58 @@ impl BParticipant {
             Bandwidth,
         )>,
         b2b_notify_send_of_recv_close_r: crossbeam_channel::Receiver<(Cid, Sid)>,
+        b2b_notify_send_of_recv_received_r: crossbeam_channel::Receiver<(Cid, Sid, u64)>,
+        b2b_ack_stream_r: crossbeam_channel::Receiver<(Cid, Sid)>,
+        b2b_force_close_recv_protocol_s: async_channel::Sender<Cid>,
         _b2s_prio_statistic_s: mpsc::UnboundedSender<B2sPrioStatistic>,
+        b2s_resume_s: mpsc::UnboundedSender<B2sResume>,
         b2a_bandwidth_stats_s: watch::Sender<f32>,
     ) {
         let mut sorted_send_protocols = SortedVec::<Cid, SendProtocols>::default();
         let mut sorted_stream_protocols = SortedVec::<Sid, Cid>::default();
+        let mut replays = HashMap::<Sid, ReplayBuffer>::new();
+        let mut acked = HashMap::<Sid, u64>::new();
+        let mut pending_acks = Vec::<(Cid, Sid)>::new();
+        let resumable = !self.resume_grace_period.is_zero();
         let mut interval = tokio::time::interval(Self::TICK_TIME);
         let mut last_instant = Instant::now();
+        let mut last_ack = Instant::now();
         let mut stream_ids = self.offset_sid;
         let mut part_bandwidth = 0.0f32;
+        // set when the streams need to be moved to a new channel
+        let mut migrate = None;
         trace!("workaround, actively wait for first protocol");
         if let Some((c, p)) = b2b_add_protocol_r.recv().await {
             sorted_send_protocols.insert(c, p)
         }
         loop {
+            if sorted_send_protocols.data.is_empty() {
+                // without resuming, recv_mgr stops on its own
+                if self.resume_grace_period.is_zero() || self.shutting_down.load(Ordering::SeqCst) {
+                    break;
+                }
+                match self
+                    .wait_for_resume(
+                        &mut b2b_add_protocol_r,
+                        &b2b_close_send_protocol_r,
+                        &b2s_resume_s,
+                    )
+                    .await
+                {
+                    Some((cid, p)) => {
+                        sorted_send_protocols.insert(cid, p);
+                        migrate = Some(cid);
+                    },
+                    None => {
+                        trace!("stop receiving, as sending is no longer possible");
+                        for cid in self.channels.read().await.keys() {
+                            let _ = b2b_force_close_recv_protocol_s.send(*cid).await;
+                        }
+                        break;
+                    },
+                }
+            }
+
             let (open, close, _, addp, remp) = select!(
                 Some(n) = a2b_open_stream_r.recv().fuse() => (Some(n), None, None, None, None),
                 Some(n) = a2b_close_stream_r.recv().fuse() => (None, Some(n), None, None, None),
@@ -262,46 +425,85 @@ impl BParticipant {
             );
 
             if let Some((cid, p)) = addp {
-                debug!(?cid, "add protocol");
+                // A new channel of a known participant is a reconnect, e.g. after the remote
+                // side noticed a connection loss earlier than we did, it replaces the old ones
+                debug!(?cid, "add protocol, replacing the existing ones");
+                for (c, _) in sorted_send_protocols.data.drain(..) {
+                    trace!(?c, "drop replaced protocol");
+                    self.metrics.channels_disconnected(&self.remote_pid_string);
+                }
                 sorted_send_protocols.insert(cid, p);
+                migrate = Some(cid);
             }
 
-            //verify that we have at LEAST 1 channel before continuing
-            if sorted_send_protocols.data.is_empty() {
-                warn!("no channel");
-                tokio::time::sleep(Self::TICK_TIME * 1000).await; //TODO: failover
-                continue;
-            }
-
-            //let (cid, active) = sorted_send_protocols.data.iter_mut().next().unwrap();
             //used for error handling
             let mut cid = u64::MAX;
 
             let active_err = async {
-                if let Some((prio, promises, guaranteed_bandwidth, return_s)) = open {
-                    let sid = stream_ids;
-                    stream_ids += Sid::from(1);
-                    cid = Self::best_protocol(&sorted_send_protocols, promises).unwrap();
-                    trace!(?sid, ?cid, "open stream");
-
-                    let stream = self
-                        .create_stream(sid, prio, promises, guaranteed_bandwidth)
-                        .await;
+                if let Some(c) = migrate.take() {
+                    cid = c;
+                    let p = sorted_send_protocols.get_mut(&c).unwrap();
+                    for (_, stream_cid) in sorted_stream_protocols.data.iter_mut() {
+                        *stream_cid = c;
+                    }
+                    let streams = self
+                        .streams
+                        .read()
+                        .await
+                        .iter()
+                        .map(|(&sid, si)| ProtocolEvent::OpenStream {
+                            sid,
+                            prio: si.prio,
+                            promises: si.promises,
+                            guaranteed_bandwidth: si.guaranteed_bandwidth,
+                        })
+                        .collect::<Vec<_>>();
+                    // The remote side answers with the number of messages it received, till
+                    // then messages are held back
+                    for event in streams {
+                        if let ProtocolEvent::OpenStream { sid, .. } = event {
+                            trace!(?sid, "move stream to new channel");
+                            if let Some(replay) = replays.get_mut(&sid) {
+                                replay.held = Some(c);
+                            }
+                        }
+                        p.send(event).await?;
+                    }
+                }
 
-                    let event = ProtocolEvent::OpenStream {
-                        sid,
-                        prio,
-                        promises,
-                        guaranteed_bandwidth,
-                    };
+                if let Some((prio, promises, guaranteed_bandwidth, return_s)) = open {
+                    match Self::best_protocol(&sorted_send_protocols, promises) {
+                        Some(best_cid) => {
+                            let sid = stream_ids;
+                            stream_ids += Sid::from(1);
+                            cid = best_cid;
+                            trace!(?sid, ?cid, "open stream");
+
+                            let stream = self
+                                .create_stream(sid, prio, promises, guaranteed_bandwidth)
+                                .await;
+
+                            let event = ProtocolEvent::OpenStream {
+                                sid,
+                                prio,
+                                promises,
+                                guaranteed_bandwidth,
+                            };
 
-                    sorted_stream_protocols.insert(sid, cid);
-                    return_s.send(stream).unwrap();
-                    sorted_send_protocols
-                        .get_mut(&cid)
-                        .unwrap()
-                        .send(event)
-                        .await?;
+                            sorted_stream_protocols.insert(sid, cid);
+                            replays.insert(sid, ReplayBuffer::new(promises, resumable));
+                            return_s.send(Ok(stream)).unwrap();
+                            sorted_send_protocols
+                                .get_mut(&cid)
+                                .unwrap()
+                                .send(event)
+                                .await?;
+                        },
+                        None => {
+                            warn!(?promises, "no channel supports the promises of the stream");
+                            let _ = return_s.send(Err(ParticipantError::PromisesUnsupported));
+                        },
+                    }
                 }
 
                 // process recv content first
@@ -311,6 +513,7 @@ impl BParticipant {
                     match sorted_send_protocols.get_mut(&cid) {
                         Some(p) => {
                             sorted_stream_protocols.insert(sid, cid);
+                            replays.insert(sid, ReplayBuffer::new(promises, resumable));
                             p.notify_from_recv(ProtocolEvent::OpenStream {
                                 sid,
                                 prio,
@@ -322,8 +525,74 @@ impl BParticipant {
                     };
                 }
 
+                // tell the remote side how many messages we got of streams it (re)opened
+                pending_acks.extend(b2b_ack_stream_r.try_iter());
+                let newest = sorted_send_protocols.data.last().map(|(c, _)| *c);
+                let mut acks = vec![];
+                // keep requests of a channel which isn't added yet
+                pending_acks.retain(|&(c, sid)| {
+                    if Some(c) == newest {
+                        acks.push((c, sid));
+                    }
+                    newest.map_or(false, |n| c > n)
+                });
+                for (c, sid) in acks {
+                    cid = c;
+                    let received = {
+                        let lock = self.streams.read().await;
+                        lock.get(&sid).map(|si| si.received.load(Ordering::Relaxed))
+                    };
+                    if let Some(count) = received {
+                        acked.insert(sid, count);
+                        let event = ProtocolEvent::Received { sid, count };
+                        sorted_send_protocols
+                            .get_mut(&c)
+                            .unwrap()
+                            .send(event)
+                            .await?;
+                    }
+                }
+
+                // the remote side tells us how many messages it got
+                let mut closes = vec![];
+                for (c, sid, count) in b2b_notify_send_of_recv_received_r.try_iter() {
+                    let replay = match replays.get_mut(&sid) {
+                        Some(replay) => replay,
+                        None => continue,
+                    };
+                    if replay.held != Some(c) {
+                        replay.acknowledge(count);
+                        continue;
+                    }
+                    match replay.release(count) {
+                        Ok(msgs) => {
+                            trace!(?sid, ?count, "replay messages on new channel");
+                            cid = c;
+                            let p = sorted_send_protocols.get_mut(&c).unwrap();
+                            for data in msgs {
+                                p.send(ProtocolEvent::Message { data, sid }).await?;
+                            }
+                            if replay.close_when_released {
+                                closes.push(sid);
+                            }
+                        },
+                        Err(()) => {
+                            warn!(
+                                ?sid,
+                                "too many unacknowledged messages to replay the stream, closing it"
+                            );
+                            closes.push(sid);
+                        },
+                    }
+                }
+
                 // get all messages and assign it to a channel
                 for (sid, buffer) in a2b_msg_r.try_iter() {
+                    if let Some(replay) = replays.get_mut(&sid) {
+                        if !replay.push(&buffer) {
+                            continue;
+                        }
+                    }
                     cid = *sorted_stream_protocols.get(&sid).unwrap();
                     let event = ProtocolEvent::Message { data: buffer, sid };
                     sorted_send_protocols
@@ -335,6 +604,8 @@ impl BParticipant {
 
                 // process recv content afterwards
                 for (cid, sid) in b2b_notify_send_of_recv_close_r.try_iter() {
+                    replays.remove(&sid);
+                    acked.remove(&sid);
                     match sorted_send_protocols.get_mut(&cid) {
                         Some(p) => {
                             let _ = sorted_stream_protocols.delete(&sid);
@@ -345,7 +616,15 @@ impl BParticipant {
                 }
 
                 if let Some(sid) = close {
+                    match replays.get_mut(&sid) {
+                        Some(replay) if replay.held.is_some() => replay.close_when_released = true,
+                        _ => closes.push(sid),
+                    }
+                }
+                for sid in closes {
                     trace!(?stream_ids, "delete stream");
+                    replays.remove(&sid);
+                    acked.remove(&sid);
                     self.delete_stream(sid).await;
                     // Fire&Forget the protocol will take care to verify that this Frame is delayed
                     // till the last msg was received!
@@ -360,6 +639,37 @@ impl BParticipant {
                     }
                 }
 
+                // acknowledge messages, so the remote side can stop keeping them for a replay
+                if last_ack.elapsed() >= Self::ACK_INTERVAL {
+                    last_ack = Instant::now();
+                    let received = self
+                        .streams
+                        .read()
+                        .await
+                        .iter()
+                        .filter(|(_, si)| {
+                            si.promises
+                                .contains(Promises::ORDERED | Promises::GUARANTEED_DELIVERY)
+                        })
+                        .map(|(&sid, si)| (sid, si.received.load(Ordering::Relaxed)))
+                        .collect::<Vec<_>>();
+                    for (sid, count) in received {
+                        if acked.get(&sid).map_or(count == 0, |&a| a == count) {
+                            continue;
+                        }
+                        if let Some(&c) = sorted_stream_protocols.get(&sid) {
+                            cid = c;
+                            acked.insert(sid, count);
+                            let event = ProtocolEvent::Received { sid, count };
+                            sorted_send_protocols
+                                .get_mut(&c)
+                                .unwrap()
+                                .send(event)
+                                .await?;
+                        }
+                    }
+                }
+
                 let send_time = Instant::now();
                 let diff = send_time.duration_since(last_instant);
                 last_instant = send_time;
@@ -381,12 +691,8 @@ impl BParticipant {
                 info!(?cid, ?e, "protocol failed, shutting down channel");
                 // remote recv will now fail, which will trigger remote send which will trigger
                 // recv
-                trace!("TODO: for now decide to FAIL this participant and not wait for a failover");
                 sorted_send_protocols.delete(&cid).unwrap();
                 self.metrics.channels_disconnected(&self.remote_pid_string);
-                if sorted_send_protocols.data.is_empty() {
-                    break;
-                }
             }
 
             if let Some(cid) = remp {
@@ -394,16 +700,16 @@ impl BParticipant {
                 match sorted_send_protocols.delete(&cid) {
                     Some(mut prot) => {
                         self.metrics.channels_disconnected(&self.remote_pid_string);
-                        trace!("blocking flush");
-                        let _ = prot.flush(u64::MAX, Duration::from_secs(1)).await;
-                        trace!("shutdown prot");
-                        let _ = prot.send(ProtocolEvent::Shutdown).await;
+                        // a failed channel is just dropped, so the remote side can resume
+                        if self.shutting_down.load(Ordering::SeqCst) {
+                            trace!("blocking flush");
+                            let _ = prot.flush(u64::MAX, Duration::from_secs(1)).await;
+                            trace!("shutdown prot");
+                            let _ = prot.send(ProtocolEvent::Shutdown).await;
+                        }
                     },
                     None => trace!("tried to remove protocol twice"),
                 };
-                if sorted_send_protocols.data.is_empty() {
-                    break;
-                }
             }
         }
         trace!("stop sending in api!");
@@ -413,10 +719,44 @@ impl BParticipant {
             .fetch_sub(Self::BARR_SEND, Ordering::SeqCst);
     }
 
+    /// After the last channel failed, waits up to the grace period for a new
+    /// channel to resume the session with. The side which connected
+    /// reconnects, the other one just waits.
+    async fn wait_for_resume(
+        &self,
+        b2b_add_protocol_r: &mut mpsc::UnboundedReceiver<(Cid, SendProtocols)>,
+        b2b_close_send_protocol_r: &async_channel::Receiver<Cid>,
+        b2s_resume_s: &mpsc::UnboundedSender<B2sResume>,
+    ) -> Option<(Cid, SendProtocols)> {
+        info!("lost connection, waiting for the session to be resumed");
+        let _ = b2s_resume_s.send((self.remote_pid, Instant::now() + self.resume_grace_period));
+        let timeout = tokio::time::sleep(self.resume_grace_period);
+        tokio::pin!(timeout);
+        loop {
+            select!(
+                Some(n) = b2b_add_protocol_r.recv().fuse() => {
+                    info!(cid = ?n.0, "session resumed");
+                    break Some(n);
+                },
+                // closing failed channels is requested too, only stop on a shutdown
+                Ok(_) = b2b_close_send_protocol_r.recv().fuse() => {
+                    if self.shutting_down.load(Ordering::SeqCst) {
+                        break None;
+                    }
+                },
+                _ = &mut timeout => {
+                    info!("session wasn't resumed in time");
+                    break None;
+                },
+            );
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn recv_mgr(
         &self,
         b2a_stream_opened_s: mpsc::UnboundedSender<Stream>,
-        mut b2b_add_protocol_r: mpsc::UnboundedReceiver<(Cid, RecvProtocols)>,
+        mut b2b_add_protocol_r: mpsc::UnboundedReceiver<(Cid, RecvProtocols, oneshot::Sender<()>)>,
         b2b_force_close_recv_protocol_r: async_channel::Receiver<Cid>,
         b2b_close_send_protocol_s: async_channel::Sender<Cid>,
         b2b_notify_send_of_recv_open_r: crossbeam_channel::Sender<(
@@ -427,6 +767,8 @@ impl BParticipant {
             Bandwidth,
         )>,
         b2b_notify_send_of_recv_close_s: crossbeam_channel::Sender<(Cid, Sid)>,
+        b2b_notify_send_of_recv_received_s: crossbeam_channel::Sender<(Cid, Sid, u64)>,
+        b2b_ack_stream_s: crossbeam_channel::Sender<(Cid, Sid)>,
     ) {
         let mut recv_protocols: HashMap<Cid, JoinHandle<()>> = HashMap::new();
         // we should be able to directly await futures imo
@@ -466,9 +808,16 @@ impl BParticipant {
                 }
             );
 
-            if let Some((cid, p)) = addp {
+            if let Some((cid, p, b2b_added_s)) = addp {
+                // The new channel replaces the old ones. Their messages are dropped from now
+                // on, so the number of received messages we tell the remote side is final.
+                for (c, h) in recv_protocols.drain() {
+                    h.abort();
+                    debug!(?c, "remove replaced protocol");
+                }
                 debug!(?cid, "add protocol");
                 retrigger(cid, p, &mut recv_protocols);
+                let _ = b2b_added_s.send(());
             };
             if let Some(cid) = remp {
                 // no need to stop the send_mgr here as it has been canceled before
@@ -478,6 +827,10 @@ impl BParticipant {
             };
 
             if let Some((cid, r, p)) = event {
+                if !recv_protocols.contains_key(&cid) {
+                    trace!(?cid, "drop event of replaced protocol");
+                    continue;
+                }
                 match r {
                     Ok(ProtocolEvent::OpenStream {
                         sid,
@@ -485,20 +838,25 @@ impl BParticipant {
                         promises,
                         guaranteed_bandwidth,
                     }) => {
-                        trace!(?sid, "open stream");
-                        let _ = b2b_notify_send_of_recv_open_r.send((
-                            cid,
-                            sid,
-                            prio,
-                            promises,
-                            guaranteed_bandwidth,
-                        ));
-                        // waiting for receiving is not necessary, because the send_mgr will first
-                        // process this before process messages!
-                        let stream = self
-                            .create_stream(sid, prio, promises, guaranteed_bandwidth)
-                            .await;
-                        b2a_stream_opened_s.send(stream).unwrap();
+                        if self.streams.read().await.contains_key(&sid) {
+                            trace!(?sid, "remote side moved stream to this channel");
+                        } else {
+                            trace!(?sid, "open stream");
+                            let _ = b2b_notify_send_of_recv_open_r.send((
+                                cid,
+                                sid,
+                                prio,
+                                promises,
+                                guaranteed_bandwidth,
+                            ));
+                            // waiting for receiving is not necessary, because the send_mgr will
+                            // first process this before process messages!
+                            let stream = self
+                                .create_stream(sid, prio, promises, guaranteed_bandwidth)
+                                .await;
+                            b2a_stream_opened_s.send(stream).unwrap();
+                        }
+                        let _ = b2b_ack_stream_s.send((cid, sid));
                         retrigger(cid, p, &mut recv_protocols);
                     },
                     Ok(ProtocolEvent::CloseStream { sid }) => {
@@ -507,10 +865,15 @@ impl BParticipant {
                         self.delete_stream(sid).await;
                         retrigger(cid, p, &mut recv_protocols);
                     },
+                    Ok(ProtocolEvent::Received { sid, count }) => {
+                        let _ = b2b_notify_send_of_recv_received_s.send((cid, sid, count));
+                        retrigger(cid, p, &mut recv_protocols);
+                    },
                     Ok(ProtocolEvent::Message { data, sid }) => {
                         let lock = self.streams.read().await;
                         match lock.get(&sid) {
                             Some(stream) => {
+                                stream.received.fetch_add(1, Ordering::Relaxed);
                                 let _ = stream.b2a_msg_recv_s.lock().await.send(data).await;
                             },
                             None => defered_orphan.log(sid),
@@ -519,6 +882,7 @@ impl BParticipant {
                     },
                     Ok(ProtocolEvent::Shutdown) => {
                         info!(?cid, "shutdown protocol");
+                        self.shutting_down.store(true, Ordering::SeqCst);
                         if let Err(e) = b2b_close_send_protocol_s.send(cid).await {
                             debug!(?e, ?cid, "send_mgr was already closed simultaneously");
                         }
@@ -531,7 +895,11 @@ impl BParticipant {
                         if let Err(e) = b2b_close_send_protocol_s.send(cid).await {
                             debug!(?e, ?cid, "send_mgr was already closed simultaneously");
                         }
-                        if remove_c(&mut recv_protocols, &cid) {
+                        // when resuming, send_mgr stops us if no new channel comes in time
+                        if remove_c(&mut recv_protocols, &cid)
+                            && (self.resume_grace_period.is_zero()
+                                || self.shutting_down.load(Ordering::SeqCst))
+                        {
                             break;
                         }
                     },
@@ -558,7 +926,7 @@ impl BParticipant {
         &self,
         s2b_create_channel_r: mpsc::UnboundedReceiver<S2bCreateChannel>,
         b2b_add_send_protocol_s: mpsc::UnboundedSender<(Cid, SendProtocols)>,
-        b2b_add_recv_protocol_s: mpsc::UnboundedSender<(Cid, RecvProtocols)>,
+        b2b_add_recv_protocol_s: mpsc::UnboundedSender<(Cid, RecvProtocols, oneshot::Sender<()>)>,
     ) {
         let s2b_create_channel_r = UnboundedReceiverStream::new(s2b_create_channel_r);
         s2b_create_channel_r
@@ -569,6 +937,19 @@ impl BParticipant {
                 let b2b_add_send_protocol_s = b2b_add_send_protocol_s.clone();
                 let b2b_add_recv_protocol_s = b2b_add_recv_protocol_s.clone();
                 async move {
+                    let (send, recv) = protocol.split();
+                    // recv has to drop the replaced channels before send moves the streams
+                    let (b2b_added_s, b2b_added_r) = oneshot::channel();
+                    if b2b_add_recv_protocol_s
+                        .send((cid, recv, b2b_added_s))
+                        .is_err()
+                        || b2b_added_r.await.is_err()
+                        || b2b_add_send_protocol_s.send((cid, send)).is_err()
+                    {
+                        // dropping `b2s_create_channel_done_s` tells the scheduler
+                        debug!(?cid, "participant is already closed, dropping channel");
+                        return;
+                    }
                     let mut lock = channels.write().await;
                     let mut channel_no = lock.len();
                     lock.insert(
@@ -579,9 +960,6 @@ impl BParticipant {
                         }),
                     );
                     drop(lock);
-                    let (send, recv) = protocol.split();
-                    b2b_add_send_protocol_s.send((cid, send)).unwrap();
-                    b2b_add_recv_protocol_s.send((cid, recv)).unwrap();
                     b2s_create_channel_done_s.send(()).unwrap();
                     if channel_no > 5 {
                         debug!(?channel_no, "metrics will overwrite channel #5");
@@ -642,6 +1020,7 @@ impl BParticipant {
 
         let awaited = s2b_shutdown_bparticipant_r.await.ok();
         debug!("participant_shutdown_mgr triggered. Closing all streams for send");
+        self.shutting_down.store(true, Ordering::SeqCst);
         {
             let lock = self.streams.read().await;
             for si in lock.values() {
@@ -734,6 +1113,8 @@ impl BParticipant {
         self.streams.write().await.insert(sid, StreamInfo {
             prio,
             promises,
+            guaranteed_bandwidth,
+            received: AtomicU64::new(0),
             send_closed: Arc::clone(&send_closed),
             b2a_msg_recv_s: Mutex::new(b2a_msg_recv_s),
         });
@@ -791,12 +1172,30 @@ mod tests {
         mpsc::UnboundedReceiver<B2sPrioStatistic>,
         watch::Receiver<f32>,
         JoinHandle<()>,
+    ) {
+        mock_resumable_bparticipant(Duration::ZERO)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn mock_resumable_bparticipant(
+        resume_grace_period: Duration,
+    ) -> (
+        Arc<Runtime>,
+        mpsc::UnboundedSender<A2bStreamOpen>,
+        mpsc::UnboundedReceiver<Stream>,
+        mpsc::UnboundedSender<S2bCreateChannel>,
+        oneshot::Sender<S2bShutdownBparticipant>,
+        mpsc::UnboundedReceiver<B2sPrioStatistic>,
+        watch::Receiver<f32>,
+        JoinHandle<()>,
     ) {
         let runtime = Arc::new(tokio::runtime::Runtime::new().unwrap());
         let runtime_clone = Arc::clone(&runtime);
 
         let (b2s_prio_statistic_s, b2s_prio_statistic_r) =
             mpsc::unbounded_channel::<B2sPrioStatistic>();
+        // there is no scheduler to reconnect
+        let (b2s_resume_s, _) = mpsc::unbounded_channel::<B2sResume>();
 
         let (
             bparticipant,
@@ -811,10 +1210,16 @@ mod tests {
             let sid = Sid::new(1000);
             let metrics = Arc::new(NetworkMetrics::new(&local_pid).unwrap());
 
-            BParticipant::new(local_pid, remote_pid, sid, Arc::clone(&metrics))
+            BParticipant::new(
+                local_pid,
+                remote_pid,
+                sid,
+                resume_grace_period,
+                Arc::clone(&metrics),
+            )
         });
 
-        let handle = runtime_clone.spawn(bparticipant.run(b2s_prio_statistic_s));
+        let handle = runtime_clone.spawn(bparticipant.run(b2s_prio_statistic_s, b2s_resume_s));
         (
             runtime_clone,
             a2b_open_stream_s,
@@ -1019,4 +1424,95 @@ mod tests {
         drop((a2b_open_stream_s, b2a_stream_opened_r, b2s_prio_statistic_r));
         drop(runtime);
     }
+
+    #[test]
+    fn resume_after_channel_failure() {
+        let (
+            runtime,
+            a2b_open_stream_s,
+            mut b2a_stream_opened_r,
+            mut s2b_create_channel_s,
+            s2b_shutdown_bparticipant_s,
+            b2s_prio_statistic_r,
+            _b2a_bandwidth_stats_r,
+            handle,
+        ) = mock_resumable_bparticipant(Duration::from_secs(10));
+
+        let remote = runtime.block_on(mock_mpsc(0, &runtime, &mut s2b_create_channel_s));
+        std::thread::sleep(Duration::from_millis(50));
+
+        let sid = Sid::new(1000);
+        let (mut rs, mut rr) = remote.split();
+        runtime
+            .block_on(rs.send(ProtocolEvent::OpenStream {
+                sid,
+                prio: 9u8,
+                promises: Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
+                guaranteed_bandwidth: 1_000_000,
+            }))
+            .unwrap();
+        let mut stream = runtime.block_on(b2a_stream_opened_r.recv()).unwrap();
+        let event = runtime.block_on(rr.recv()).unwrap();
+        assert!(matches!(event, ProtocolEvent::Received { sid: s, count: 0 } if s == sid));
+        stream.send("first").unwrap();
+        let event = runtime.block_on(rr.recv()).unwrap();
+        assert!(matches!(event, ProtocolEvent::Message { .. }));
+
+        // connection loss, the participant waits for a new channel
+        drop((rs, rr));
+        std::thread::sleep(Duration::from_millis(50));
+        stream.send("second").unwrap();
+
+        let remote = runtime.block_on(mock_mpsc(1, &runtime, &mut s2b_create_channel_s));
+        let (mut rs, mut rr) = remote.split();
+        let event = runtime.block_on(rr.recv()).unwrap();
+        assert!(matches!(event, ProtocolEvent::OpenStream { sid: s, .. } if s == sid));
+        // messages are held back till the remote side tells how many it got
+        runtime
+            .block_on(rs.send(ProtocolEvent::Received { sid, count: 1 }))
+            .unwrap();
+        let event = runtime.block_on(rr.recv()).unwrap();
+        match event {
+            ProtocolEvent::Message { data, sid: s } => {
+                assert_eq!(s, sid);
+                assert_eq!(&data[..], &bincode::serialize("second").unwrap()[..]);
+            },
+            _ => panic!("wrong event"),
+        };
+
+        let (s, r) = oneshot::channel();
+        runtime.block_on(async {
+            drop(s2b_create_channel_s);
+            s2b_shutdown_bparticipant_s
+                .send((Duration::from_secs(1), s))
+                .unwrap();
+            drop((rs, rr));
+            r.await.unwrap().unwrap();
+        });
+
+        runtime.block_on(handle).unwrap();
+
+        drop((a2b_open_stream_s, b2a_stream_opened_r, b2s_prio_statistic_r));
+        drop(runtime);
+    }
+
+    #[test]
+    fn replay_buffer_overflow_fails_replay() {
+        let promises = Promises::ORDERED | Promises::GUARANTEED_DELIVERY;
+        let mut replay = ReplayBuffer::new(promises, true);
+        let msg = Bytes::from(vec![0u8; 1024 * 1024]);
+        assert!(replay.push(&msg));
+        replay.acknowledge(1);
+        assert!(replay.msgs.is_empty());
+        for _ in 0..ReplayBuffer::MAX_BYTES / msg.len() {
+            assert!(replay.push(&msg));
+        }
+        assert_eq!(replay.msgs.len(), ReplayBuffer::MAX_BYTES / msg.len());
+        // the next message doesn't fit anymore, no message is dropped silently
+        assert!(replay.push(&msg));
+        assert!(replay.overflowed);
+        assert!(replay.msgs.is_empty());
+        replay.held = Some(1);
+        assert_eq!(replay.release(1), Err(()));
+    }
 }
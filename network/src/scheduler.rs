@@ -2,11 +2,13 @@ use crate::{
     api::{ConnectAddr, ListenAddr, NetworkConnectError, Participant},
     channel::Protocols,
     metrics::{NetworkMetrics, ProtocolInfo},
-    participant::{B2sPrioStatistic, BParticipant, S2bCreateChannel, S2bShutdownBparticipant},
+    participant::{
+        B2sPrioStatistic, B2sResume, BParticipant, S2bCreateChannel, S2bShutdownBparticipant,
+    },
 };
 use futures_util::StreamExt;
 use hashbrown::HashMap;
-use network_protocol::{Cid, Pid, ProtocolMetricCache, ProtocolMetrics};
+use network_protocol::{Cid, Pid, Promises, ProtocolMetricCache, ProtocolMetrics};
 #[cfg(feature = "metrics")]
 use prometheus::Registry;
 use rand::Rng;
@@ -15,7 +17,7 @@ use std::{
         atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::{
     io,
@@ -35,10 +37,26 @@ use tracing::*;
 
 #[derive(Debug)]
 struct ParticipantInfo {
+    /// The remote side proves with it to be the same participant, when
+    /// opening additional channels or resuming the session
     secret: u128,
-    #[allow(dead_code)]
     s2b_create_channel_s: mpsc::UnboundedSender<S2bCreateChannel>,
     s2b_shutdown_bparticipant_s: Option<oneshot::Sender<S2bShutdownBparticipant>>,
+    /// Set if we connected, then we have to reconnect to resume the session
+    resume_addr: Option<ConnectAddr>,
+}
+
+/// Who is interested in the handshake of a new channel
+enum ChannelRequest {
+    /// New participants are returned to `Network::connected`
+    Listen,
+    Connect(
+        ConnectAddr,
+        oneshot::Sender<Result<Participant, NetworkConnectError>>,
+    ),
+    /// Reconnect to resume the session of an existing participant, returns
+    /// whether it was resumed
+    Resume(Pid, oneshot::Sender<bool>),
 }
 
 type A2sListen = (ListenAddr, oneshot::Sender<io::Result<()>>);
@@ -55,6 +73,7 @@ struct ControlChannels {
     a2s_scheduler_shutdown_r: oneshot::Receiver<()>,
     a2s_disconnect_r: mpsc::UnboundedReceiver<A2sDisconnect>,
     b2s_prio_statistic_r: mpsc::UnboundedReceiver<B2sPrioStatistic>,
+    b2s_resume_r: mpsc::UnboundedReceiver<B2sResume>,
 }
 
 #[derive(Debug, Clone)]
@@ -62,6 +81,7 @@ struct ParticipantChannels {
     s2a_connected_s: mpsc::UnboundedSender<Participant>,
     a2s_disconnect_s: mpsc::UnboundedSender<A2sDisconnect>,
     b2s_prio_statistic_s: mpsc::UnboundedSender<B2sPrioStatistic>,
+    b2s_resume_s: mpsc::UnboundedSender<B2sResume>,
 }
 
 #[derive(Debug)]
@@ -76,11 +96,16 @@ pub struct Scheduler {
     channel_listener: Mutex<HashMap<ProtocolInfo, oneshot::Sender<()>>>,
     metrics: Arc<NetworkMetrics>,
     protocol_metrics: Arc<ProtocolMetrics>,
+    /// in milliseconds, see `Network::set_resume_grace_period`
+    resume_grace_period: Arc<AtomicU64>,
 }
 
 impl Scheduler {
+    const RESUME_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
     pub fn new(
         local_pid: Pid,
+        resume_grace_period: Arc<AtomicU64>,
         #[cfg(feature = "metrics")] registry: Option<&Registry>,
     ) -> (
         Self,
@@ -96,6 +121,7 @@ impl Scheduler {
         let (a2s_disconnect_s, a2s_disconnect_r) = mpsc::unbounded_channel::<A2sDisconnect>();
         let (b2s_prio_statistic_s, b2s_prio_statistic_r) =
             mpsc::unbounded_channel::<B2sPrioStatistic>();
+        let (b2s_resume_s, b2s_resume_r) = mpsc::unbounded_channel::<B2sResume>();
 
         let run_channels = Some(ControlChannels {
             a2s_listen_r,
@@ -103,12 +129,14 @@ impl Scheduler {
             a2s_scheduler_shutdown_r,
             a2s_disconnect_r,
             b2s_prio_statistic_r,
+            b2s_resume_r,
         });
 
         let participant_channels = ParticipantChannels {
             s2a_connected_s,
             a2s_disconnect_s,
             b2s_prio_statistic_s,
+            b2s_resume_s,
         };
 
         let metrics = Arc::new(NetworkMetrics::new(&local_pid).unwrap());
@@ -137,6 +165,7 @@ impl Scheduler {
                 channel_listener: Mutex::new(HashMap::new()),
                 metrics,
                 protocol_metrics,
+                resume_grace_period,
             },
             a2s_listen_s,
             a2s_connect_s,
@@ -156,6 +185,7 @@ impl Scheduler {
             self.connect_mgr(run_channels.a2s_connect_r),
             self.disconnect_mgr(run_channels.a2s_disconnect_r),
             self.prio_adj_mgr(run_channels.b2s_prio_statistic_r),
+            self.resume_mgr(run_channels.b2s_resume_r),
             self.scheduler_shutdown_mgr(run_channels.a2s_scheduler_shutdown_r),
         );
     }
@@ -209,6 +239,16 @@ impl Scheduler {
                             )
                             .await
                         },
+                        ListenAddr::Udp(addr) => {
+                            Protocols::with_udp_listen(
+                                addr,
+                                cids,
+                                metrics,
+                                s2s_stop_listening_r,
+                                c2s_protocol_s,
+                            )
+                            .await
+                        },
                         ListenAddr::Mpsc(addr) => {
                             Protocols::with_mpsc_listen(
                                 addr,
@@ -219,12 +259,12 @@ impl Scheduler {
                             )
                             .await
                         },
-                        _ => unimplemented!(),
                     };
                     let _ = s2a_listen_result_s.send(res);
 
                     while let Some((prot, cid)) = c2s_protocol_r.recv().await {
-                        self.init_protocol(prot, cid, None, true).await;
+                        self.init_protocol(prot, cid, ChannelRequest::Listen, true)
+                            .await;
                     }
                 }
             })
@@ -236,31 +276,90 @@ impl Scheduler {
         trace!("Start connect_mgr");
         while let Some((addr, pid_sender)) = a2s_connect_r.recv().await {
             let cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
-            let metrics =
-                ProtocolMetricCache::new(&cid.to_string(), Arc::clone(&self.protocol_metrics));
-            self.metrics.connect_request(&addr);
-            let protocol = match addr {
-                ConnectAddr::Tcp(addr) => Protocols::with_tcp_connect(addr, metrics).await,
-                #[cfg(feature = "quic")]
-                ConnectAddr::Quic(addr, ref config, name) => {
-                    Protocols::with_quic_connect(addr, config.clone(), name, metrics).await
-                },
-                ConnectAddr::Mpsc(addr) => Protocols::with_mpsc_connect(addr, metrics).await,
-                _ => unimplemented!(),
-            };
-            let protocol = match protocol {
+            let protocol = match self.connect_protocol(cid, &addr).await {
                 Ok(p) => p,
                 Err(e) => {
                     pid_sender.send(Err(e)).unwrap();
                     continue;
                 },
             };
-            self.init_protocol(protocol, cid, Some(pid_sender), false)
-                .await;
+            self.init_protocol(
+                protocol,
+                cid,
+                ChannelRequest::Connect(addr, pid_sender),
+                false,
+            )
+            .await;
         }
         trace!("Stop connect_mgr");
     }
 
+    async fn connect_protocol(
+        &self,
+        cid: Cid,
+        addr: &ConnectAddr,
+    ) -> Result<Protocols, NetworkConnectError> {
+        let metrics =
+            ProtocolMetricCache::new(&cid.to_string(), Arc::clone(&self.protocol_metrics));
+        self.metrics.connect_request(addr);
+        match addr.clone() {
+            ConnectAddr::Tcp(addr) => Protocols::with_tcp_connect(addr, metrics).await,
+            #[cfg(feature = "quic")]
+            ConnectAddr::Quic(addr, config, name) => {
+                Protocols::with_quic_connect(addr, config, name, metrics).await
+            },
+            ConnectAddr::Udp(addr) => Protocols::with_udp_connect(addr, metrics).await,
+            ConnectAddr::Mpsc(addr) => Protocols::with_mpsc_connect(addr, metrics).await,
+        }
+    }
+
+    /// A participant we connected to lost its last channel, reconnect till
+    /// the session is resumed or the grace period is over
+    async fn resume_mgr(&self, b2s_resume_r: mpsc::UnboundedReceiver<B2sResume>) {
+        trace!("Start resume_mgr");
+        let b2s_resume_r = UnboundedReceiverStream::new(b2s_resume_r);
+        b2s_resume_r
+            .for_each_concurrent(None, |(pid, deadline)| async move {
+                let addr = self
+                    .participants
+                    .lock()
+                    .await
+                    .get(&pid)
+                    .and_then(|pi| pi.resume_addr.clone());
+                let addr = match addr {
+                    Some(addr) => addr,
+                    None => {
+                        trace!(?pid, "Waiting for remote side to resume the session");
+                        return;
+                    },
+                };
+                while Instant::now() < deadline {
+                    debug!(?pid, ?addr, "Try to resume session");
+                    let cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
+                    match self.connect_protocol(cid, &addr).await {
+                        Ok(protocol) => {
+                            let (resumed_s, resumed_r) = oneshot::channel();
+                            self.init_protocol(
+                                protocol,
+                                cid,
+                                ChannelRequest::Resume(pid, resumed_s),
+                                false,
+                            )
+                            .await;
+                            if resumed_r.await.unwrap_or(false) {
+                                return;
+                            }
+                        },
+                        Err(e) => debug!(?pid, ?e, "Failed to reconnect"),
+                    }
+                    tokio::time::sleep(Self::RESUME_RETRY_INTERVAL).await;
+                }
+                info!(?pid, "Giving up to resume session");
+            })
+            .await;
+        trace!("Stop resume_mgr");
+    }
+
     async fn disconnect_mgr(&self, a2s_disconnect_r: mpsc::UnboundedReceiver<A2sDisconnect>) {
         trace!("Start disconnect_mgr");
 
@@ -376,7 +475,7 @@ impl Scheduler {
         &self,
         mut protocol: Protocols,
         cid: Cid,
-        s2a_return_pid_s: Option<oneshot::Sender<Result<Participant, NetworkConnectError>>>,
+        request: ChannelRequest,
         send_handshake: bool,
     ) {
         //channels are unknown till PID is known!
@@ -394,6 +493,8 @@ impl Scheduler {
         let metrics = Arc::clone(&self.metrics);
         let local_pid = self.local_pid;
         let local_secret = self.local_secret;
+        let resume_grace_period =
+            Duration::from_millis(self.resume_grace_period.load(Ordering::Relaxed));
         // this is necessary for UDP to work at all and to remove code duplication
         tokio::spawn(
             async move {
@@ -412,6 +513,23 @@ impl Scheduler {
                         );
                         let mut participants = participants.lock().await;
                         if !participants.contains_key(&pid) {
+                            let (s2a_return_pid_s, resume_addr) = match request {
+                                ChannelRequest::Listen => (None, None),
+                                ChannelRequest::Connect(addr, pid_oneshot) => {
+                                    (Some(pid_oneshot), Some(addr))
+                                },
+                                ChannelRequest::Resume(expected_pid, resumed_s) => {
+                                    // e.g. the remote side restarted in the meantime
+                                    warn!(
+                                        ?cid,
+                                        ?pid,
+                                        ?expected_pid,
+                                        "Reconnected to another participant, can't resume"
+                                    );
+                                    let _ = resumed_s.send(false);
+                                    return;
+                                },
+                            };
                             debug!(?cid, "New participant connected via a channel");
                             let (
                                 bparticipant,
@@ -420,7 +538,13 @@ impl Scheduler {
                                 s2b_create_channel_s,
                                 s2b_shutdown_bparticipant_s,
                                 b2a_bandwidth_stats_r,
-                            ) = BParticipant::new(local_pid, pid, sid, Arc::clone(&metrics));
+                            ) = BParticipant::new(
+                                local_pid,
+                                pid,
+                                sid,
+                                resume_grace_period,
+                                Arc::clone(&metrics),
+                            );
 
                             let participant = Participant::new(
                                 local_pid,
@@ -437,13 +561,17 @@ impl Scheduler {
                                 secret,
                                 s2b_create_channel_s: s2b_create_channel_s.clone(),
                                 s2b_shutdown_bparticipant_s: Some(s2b_shutdown_bparticipant_s),
+                                resume_addr,
                             });
                             drop(participants);
                             trace!("dropped participants lock");
                             let p = pid;
                             tokio::spawn(
                                 bparticipant
-                                    .run(participant_channels.b2s_prio_statistic_s)
+                                    .run(
+                                        participant_channels.b2s_prio_statistic_s,
+                                        participant_channels.b2s_resume_s,
+                                    )
                                     .instrument(tracing::info_span!("remote", ?p)),
                             );
                             //create a new channel within BParticipant and wait for it to run
@@ -482,19 +610,60 @@ impl Scheduler {
                                 );
                                 error!(?cid, "Just dropping here, TODO handle this correctly!");
                                 //TODO
-                                if let Some(pid_oneshot) = s2a_return_pid_s {
-                                    // someone is waiting with `connect`, so give them their Error
-                                    pid_oneshot
-                                        .send(Err(NetworkConnectError::InvalidSecret))
-                                        .unwrap();
+                                match request {
+                                    ChannelRequest::Connect(_, pid_oneshot) => {
+                                        // someone is waiting with `connect`, so give them their
+                                        // Error
+                                        pid_oneshot
+                                            .send(Err(NetworkConnectError::InvalidSecret))
+                                            .unwrap();
+                                    },
+                                    ChannelRequest::Resume(_, resumed_s) => {
+                                        let _ = resumed_s.send(false);
+                                    },
+                                    ChannelRequest::Listen => {},
                                 }
                                 return;
                             }
-                            error!(
-                                ?cid,
-                                "Ufff i cant answer the pid_oneshot. as i need to create the SAME \
-                                 participant. maybe switch to ARC"
-                            );
+                            if let ChannelRequest::Connect(..) = request {
+                                error!(
+                                    ?cid,
+                                    "Ufff i cant answer the pid_oneshot. as i need to create the \
+                                     SAME participant. maybe switch to ARC"
+                                );
+                                return;
+                            }
+                            // The secret exchanged in the handshake is the session token: the
+                            // channel replaces the existing ones, e.g. after a connection loss.
+                            // It is only sent masked with the keys of the handshake, but streams
+                            // moved to a channel which can't encrypt them would leak their data
+                            if !protocol.supported_promises().contains(Promises::ENCRYPTED) {
+                                warn!(
+                                    ?cid,
+                                    ?pid,
+                                    "Refusing to resume session on an unencrypted channel"
+                                );
+                                if let ChannelRequest::Resume(_, resumed_s) = request {
+                                    let _ = resumed_s.send(false);
+                                }
+                                return;
+                            }
+                            let s2b_create_channel_s = pi.s2b_create_channel_s.clone();
+                            drop(participants);
+                            let (b2s_create_channel_done_s, b2s_create_channel_done_r) =
+                                oneshot::channel();
+                            let resumed = s2b_create_channel_s
+                                .send((cid, sid, protocol, b2s_create_channel_done_s))
+                                .is_ok()
+                                && b2s_create_channel_done_r.await.is_ok();
+                            if resumed {
+                                info!(?cid, ?pid, "Resumed session on new channel");
+                            } else {
+                                debug!(?cid, ?pid, "Participant is already closed, can't resume");
+                            }
+                            if let ChannelRequest::Resume(_, resumed_s) = request {
+                                let _ = resumed_s.send(resumed);
+                            }
                         }
                         //From now on this CHANNEL can receiver other frames!
                         // move directly to participant!
@@ -503,12 +672,18 @@ impl Scheduler {
                         debug!(?cid, ?e, "Handshake from a new connection failed");
                         #[cfg(feature = "metrics")]
                         metrics.failed_handshakes_total.inc();
-                        if let Some(pid_oneshot) = s2a_return_pid_s {
-                            // someone is waiting with `connect`, so give them their Error
-                            trace!(?cid, "returning the Err to api who requested the connect");
-                            pid_oneshot
-                                .send(Err(NetworkConnectError::Handshake(e)))
-                                .unwrap();
+                        match request {
+                            ChannelRequest::Connect(_, pid_oneshot) => {
+                                // someone is waiting with `connect`, so give them their Error
+                                trace!(?cid, "returning the Err to api who requested the connect");
+                                pid_oneshot
+                                    .send(Err(NetworkConnectError::Handshake(e)))
+                                    .unwrap();
+                            },
+                            ChannelRequest::Resume(_, resumed_s) => {
+                                let _ = resumed_s.send(false);
+                            },
+                            ChannelRequest::Listen => {},
                         }
                     },
                 }
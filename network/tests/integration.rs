@@ -1,6 +1,6 @@
 use std::sync::Arc;
 use tokio::runtime::Runtime;
-use veloren_network::{NetworkError, StreamError};
+use veloren_network::{NetworkError, ParticipantError, StreamError};
 mod helper;
 use helper::{mpsc, network_participant_stream, quic, tcp, udp, SLEEP_EXTERNAL, SLEEP_INTERNAL};
 use std::io::ErrorKind;
@@ -90,7 +90,6 @@ fn stream_simple_quic_3msg() {
 }
 
 #[test]
-#[ignore]
 fn stream_simple_udp() {
     let (_, _) = helper::setup(false, 0);
     let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) = network_participant_stream(udp());
@@ -101,7 +100,19 @@ fn stream_simple_udp() {
 }
 
 #[test]
-#[ignore]
+fn stream_encrypted_udp_unsupported() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, _n_a, p_a, _s1_a, _n_b, _p_b, _s1_b) = network_participant_stream(udp());
+
+    assert_eq!(
+        r.block_on(p_a.open(4, Promises::ORDERED | Promises::ENCRYPTED, 0))
+            .map(|_| ()),
+        Err(ParticipantError::PromisesUnsupported)
+    );
+    drop((_n_a, _n_b, p_a, _p_b)); //clean teardown
+}
+
+#[test]
 fn stream_simple_udp_3msg() {
     let (_, _) = helper::setup(false, 0);
     let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) = network_participant_stream(udp());
@@ -116,7 +127,6 @@ fn stream_simple_udp_3msg() {
 }
 
 #[test]
-#[ignore]
 fn tcp_and_udp_2_connections() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let (_, _) = helper::setup(false, 0);
     let r = Arc::new(Runtime::new().unwrap());
@@ -143,7 +153,6 @@ fn tcp_and_udp_2_connections() -> std::result::Result<(), Box<dyn std::error::Er
 }
 
 #[test]
-#[ignore]
 fn failed_listen_on_used_ports() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let (_, _) = helper::setup(false, 0);
     let r = Arc::new(Runtime::new().unwrap());
@@ -2,8 +2,10 @@ pub extern crate common;
 
 pub use common::comp::Health;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::Duration;
 
-pub use common::{resources::GameMode, uid::Uid};
+pub use common::{combat::DamageSource, comp::buff::BuffKind, resources::GameMode, uid::Uid};
+pub use vek::{Rgb, Vec3};
 
 mod errors;
 
@@ -35,6 +37,55 @@ pub enum Action {
     Print(String),
     PlayerSendMessage(Uid, String),
     KillEntity(Uid),
+    /// Spawn an NPC at the given position from an `EntityConfig` asset
+    /// specifier (e.g. `common.entity.wild.aggressive.wolf`)
+    SpawnEntity {
+        config: String,
+        pos: Vec3<f32>,
+    },
+    /// Put `amount` items of the given item asset specifier in the entity
+    /// inventory, items that don't fit are dropped. Items that can't stack
+    /// are given at most 100 at a time.
+    GiveItem {
+        target: Uid,
+        item: String,
+        amount: u32,
+    },
+    /// Remove up to `amount` items of the given item asset specifier from the
+    /// entity inventory
+    TakeItem {
+        target: Uid,
+        item: String,
+        amount: u32,
+    },
+    TeleportEntity(Uid, Vec3<f32>),
+    /// Replace the block at the given position, `block_kind` is the name of a
+    /// `BlockKind` (e.g. `Rock`)
+    SetBlock {
+        pos: Vec3<i32>,
+        block_kind: String,
+        color: Rgb<u8>,
+    },
+    /// Place a sprite at the given position, `sprite` is the name of a
+    /// `SpriteKind` (e.g. `Apple`). Has no effect if the block there is filled
+    SetSprite {
+        pos: Vec3<i32>,
+        sprite: String,
+    },
+    ApplyBuff {
+        target: Uid,
+        kind: BuffKind,
+        strength: f32,
+        duration: Option<Duration>,
+    },
+    /// Store a value in the plugin storage, it is kept across server
+    /// restarts. Keys are private to each plugin
+    SetStorageValue {
+        key: String,
+        value: Vec<u8>,
+    },
+    /// Remove a value from the plugin storage
+    RemoveStorageValue(String),
 }
 
 /// The [`Retrieve`] enum represents read of the ECS is sync and blocking.
@@ -68,6 +119,16 @@ pub enum Action {
 pub enum Retrieve {
     GetPlayerName(Uid),
     GetEntityHealth(Uid),
+    GetEntityPosition(Uid),
+    GetEntityInventory(Uid),
+    GetEntityStats(Uid),
+    /// Get all entities with a position in a sphere of `radius` around `pos`
+    GetNearbyEntities {
+        pos: Vec3<f32>,
+        radius: f32,
+    },
+    /// Get a value from the plugin storage, see [`Action::SetStorageValue`]
+    GetStorageValue(String),
 }
 
 /// The [`RetrieveResult`] struct is generated while using the `retrieve_action`
@@ -95,6 +156,30 @@ pub enum Retrieve {
 pub enum RetrieveResult {
     GetPlayerName(String),
     GetEntityHealth(Health),
+    GetEntityPosition(Vec3<f32>),
+    GetEntityInventory(Vec<ItemStack>),
+    GetEntityStats(EntityStats),
+    GetNearbyEntities(Vec<Uid>),
+    GetStorageValue(Option<Vec<u8>>),
+}
+
+/// This struct represent the stats of an entity as seen by plugins
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EntityStats {
+    pub name: String,
+    pub damage_reduction: f32,
+    pub move_speed_modifier: f32,
+    pub attack_speed_modifier: f32,
+    pub friction_modifier: f32,
+}
+
+/// This struct represent an inventory slot content as seen by plugins.
+/// Items are identified by their asset specifier (e.g.
+/// `common.items.food.apple`)
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ItemStack {
+    pub item: String,
+    pub amount: u32,
 }
 
 /// This trait is implement by all events and ensure type safety of FFI.
@@ -220,6 +305,209 @@ pub mod event {
         fn get_event_name(&self) -> String { "on_load".to_owned() }
     }
 
+    /// This event is called when a player leaves the server.
+    /// Your event should be named `on_leave`
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_leave(leave: PlayerLeaveEvent) {
+    ///     emit_action(Action::Print(format!("{} left", leave.player_name)));
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct PlayerLeaveEvent {
+        pub player_name: String,
+        pub player_id: [u8; 16],
+    }
+
+    impl Event for PlayerLeaveEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_leave".to_owned() }
+    }
+
+    /// This event is called when a player enters the world with a character.
+    /// Your event should be named `on_character_select`
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct CharacterSelectEvent {
+        pub player: Player,
+        pub character_id: i64,
+    }
+
+    impl Event for CharacterSelectEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_character_select".to_owned() }
+    }
+
+    /// This event is called before an entity takes damage.
+    /// Your event should be named `on_entity_damage`
+    ///
+    /// If any plugin returns `Cancel` the damage is not applied
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_entity_damage(damage: EntityDamageEvent) -> EntityDamageResult {
+    ///     if damage.attacker.is_none() {
+    ///         EntityDamageResult::Cancel
+    ///     } else {
+    ///         EntityDamageResult::None
+    ///     }
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct EntityDamageEvent {
+        pub entity: Uid,
+        pub attacker: Option<Uid>,
+        pub source: Option<DamageSource>,
+        pub amount: f32,
+    }
+
+    impl Event for EntityDamageEvent {
+        type Response = EntityDamageResult;
+
+        fn get_event_name(&self) -> String { "on_entity_damage".to_owned() }
+    }
+
+    /// This is the return type of an `on_entity_damage` event. See
+    /// [`EntityDamageEvent`]
+    ///
+    /// Variants:
+    ///  - `Cancel` will prevent the damage from being applied.
+    ///  - `None` will let the damage through.
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    #[repr(u8)]
+    pub enum EntityDamageResult {
+        Cancel,
+        None,
+    }
+
+    impl Default for EntityDamageResult {
+        fn default() -> Self { Self::None }
+    }
+
+    /// This event is called when an entity dies.
+    /// Your event should be named `on_entity_death`
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct EntityDeathEvent {
+        pub entity: Uid,
+        pub killer: Option<Uid>,
+        pub cause: Option<DamageSource>,
+    }
+
+    impl Event for EntityDeathEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_entity_death".to_owned() }
+    }
+
+    /// This event is called before a chat message sent by a player is
+    /// broadcast. Your event should be named `on_chat_message`
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_chat_message(chat: ChatMessageEvent) -> ChatMessageResult {
+    ///     ChatMessageResult::Rewrite(chat.message.to_uppercase())
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct ChatMessageEvent {
+        pub player: Player,
+        pub message: String,
+    }
+
+    impl Event for ChatMessageEvent {
+        type Response = ChatMessageResult;
+
+        fn get_event_name(&self) -> String { "on_chat_message".to_owned() }
+    }
+
+    /// This is the return type of an `on_chat_message` event. See
+    /// [`ChatMessageEvent`]
+    ///
+    /// Variants:
+    ///  - `Cancel` will drop the message.
+    ///  - `Rewrite` will replace the message content.
+    ///  - `None` will let the message through unchanged.
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub enum ChatMessageResult {
+        Cancel,
+        Rewrite(String),
+        None,
+    }
+
+    impl Default for ChatMessageResult {
+        fn default() -> Self { Self::None }
+    }
+
+    /// This event is called before a player breaks a block.
+    /// Your event should be named `on_block_break`
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BlockBreakEvent {
+        pub player: Player,
+        pub pos: Vec3<i32>,
+    }
+
+    impl Event for BlockBreakEvent {
+        type Response = BlockChangeResult;
+
+        fn get_event_name(&self) -> String { "on_block_break".to_owned() }
+    }
+
+    /// This event is called before a player places a block.
+    /// Your event should be named `on_block_place`
+    ///
+    /// `block_kind` is the name of the placed `BlockKind` (e.g. `Rock`)
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BlockPlaceEvent {
+        pub player: Player,
+        pub pos: Vec3<i32>,
+        pub block_kind: String,
+    }
+
+    impl Event for BlockPlaceEvent {
+        type Response = BlockChangeResult;
+
+        fn get_event_name(&self) -> String { "on_block_place".to_owned() }
+    }
+
+    /// This is the return type of the `on_block_break` and `on_block_place`
+    /// events.
+    ///
+    /// Variants:
+    ///  - `Cancel` will prevent the block change.
+    ///  - `None` will let the block change happen.
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    #[repr(u8)]
+    pub enum BlockChangeResult {
+        Cancel,
+        None,
+    }
+
+    impl Default for BlockChangeResult {
+        fn default() -> Self { Self::None }
+    }
+
+    /// This event is called periodically on the server.
+    /// Your event should be named `on_tick`
+    ///
+    /// `time` is the time since the server started and `dt` the time elapsed
+    /// since the last `on_tick`, both in seconds
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct TickEvent {
+        pub time: f64,
+        pub dt: f64,
+    }
+
+    impl Event for TickEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_tick".to_owned() }
+    }
+
     // impl Default for PlayerJoinResult {
     //     fn default() -> Self {
     //         Self::None
@@ -0,0 +1,362 @@
+//! Local HTTP API to administrate the running server from scripts or web
+//! panels.
+//!
+//! Requests are JSON objects POSTed to `/` with an `action` field, e.g.
+//! `{"action": "kick", "username": "foo", "reason": "bar"}`, authenticated by
+//! the `admin_api_token` of the settings as a bearer token. Every response is a
+//! JSON object with either a `result` or an `error`.
+
+use crate::shutdown_coordinator::ShutdownCoordinator;
+use common::comp;
+use hyper::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request as HttpRequest, Response, StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use server::Server;
+use std::{convert::Infallible, io, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    runtime::Runtime,
+    sync::{mpsc, oneshot},
+};
+use tracing::{error, info};
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Request {
+    /// Lists the players which are online
+    Players,
+    Kick {
+        username: String,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// Bans a player, permanently unless a duration is given
+    Ban {
+        username: String,
+        reason: String,
+        #[serde(default)]
+        duration_secs: Option<u64>,
+        /// Replace an existing ban of the player
+        #[serde(default)]
+        overwrite: bool,
+    },
+    Unban {
+        username: String,
+    },
+    WhitelistAdd {
+        username: String,
+    },
+    WhitelistRemove {
+        username: String,
+    },
+    AdminAdd {
+        username: String,
+        role: String,
+    },
+    AdminRemove {
+        username: String,
+    },
+    /// Sends a message to the chat of every player
+    Broadcast {
+        message: String,
+    },
+    /// Shuts down the server gracefully after the given number of seconds
+    Shutdown {
+        seconds: u64,
+        #[serde(default = "default_shutdown_reason")]
+        reason: String,
+    },
+    CancelShutdown,
+    /// Runs a chat command with the permissions of an administrator, e.g.
+    /// `/time night`
+    Command {
+        command: String,
+    },
+}
+
+fn default_shutdown_reason() -> String { "The server is shutting down".to_owned() }
+
+/// Tokens shorter than this are refused, as they could be guessed
+pub const MIN_TOKEN_LEN: usize = 16;
+
+/// A request waiting to be handled by the main loop, with the channel to send
+/// its result through.
+pub type PendingRequest = (Request, oneshot::Sender<Result<Value, String>>);
+
+/// Starts serving the API on `address`, returning the requests that have to be
+/// handled with [`handle_request`]. Fails if the token is too short.
+pub fn spawn(
+    runtime: &Runtime,
+    address: SocketAddr,
+    token: String,
+) -> io::Result<mpsc::UnboundedReceiver<PendingRequest>> {
+    if token.trim().len() < MIN_TOKEN_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "The admin API token has to be at least {} characters long",
+                MIN_TOKEN_LEN
+            ),
+        ));
+    }
+    let (request_s, request_r) = mpsc::unbounded_channel();
+    let token = Arc::new(token);
+    runtime.spawn(async move {
+        let make_service = make_service_fn(move |_| {
+            let request_s = request_s.clone();
+            let token = Arc::clone(&token);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    respond(request, request_s.clone(), Arc::clone(&token))
+                }))
+            }
+        });
+        match hyper::Server::try_bind(&address) {
+            Ok(server) => {
+                info!(?address, "Admin API is ready to accept requests.");
+                if let Err(e) = server.serve(make_service).await {
+                    error!(?e, "Admin API stopped");
+                }
+            },
+            Err(e) => error!(?e, ?address, "Failed to bind the admin API"),
+        }
+    });
+    Ok(request_r)
+}
+
+fn reply(status: StatusCode, result: Result<Value, String>) -> Response<Body> {
+    let body = match result {
+        Ok(result) => json!({ "ok": true, "result": result }),
+        Err(error) => json!({ "ok": false, "error": error }),
+    };
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("Response is always valid")
+}
+
+/// Compares the tokens in constant time, so they can't be guessed from the
+/// response times.
+fn tokens_match(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+async fn respond(
+    request: HttpRequest<Body>,
+    request_s: mpsc::UnboundedSender<PendingRequest>,
+    token: Arc<String>,
+) -> Result<Response<Body>, Infallible> {
+    if request.method() != Method::POST || request.uri().path() != "/" {
+        return Ok(reply(StatusCode::NOT_FOUND, Err("Not found".to_owned())));
+    }
+    let authorized = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map_or(false, |value| {
+            tokens_match(value.as_bytes(), token.as_bytes())
+        });
+    if !authorized {
+        return Ok(reply(
+            StatusCode::UNAUTHORIZED,
+            Err("Invalid or missing token".to_owned()),
+        ));
+    }
+
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return Ok(reply(StatusCode::BAD_REQUEST, Err(e.to_string()))),
+    };
+    let request = match serde_json::from_slice::<Request>(&body) {
+        Ok(request) => request,
+        Err(e) => return Ok(reply(StatusCode::BAD_REQUEST, Err(e.to_string()))),
+    };
+
+    let (result_s, result_r) = oneshot::channel();
+    if request_s.send((request, result_s)).is_err() {
+        return Ok(reply(
+            StatusCode::SERVICE_UNAVAILABLE,
+            Err("The server is shutting down".to_owned()),
+        ));
+    }
+    Ok(match result_r.await {
+        Ok(Ok(result)) => reply(StatusCode::OK, Ok(result)),
+        Ok(Err(error)) => reply(StatusCode::BAD_REQUEST, Err(error)),
+        Err(_) => reply(
+            StatusCode::SERVICE_UNAVAILABLE,
+            Err("The server is shutting down".to_owned()),
+        ),
+    })
+}
+
+/// Executes a request on the server, from the main loop.
+pub fn handle_request(
+    request: Request,
+    server: &mut Server,
+    shutdown_coordinator: &mut ShutdownCoordinator,
+) -> Result<Value, String> {
+    info!(?request, "Admin API request");
+    match request {
+        Request::Players => Ok(json!(server.online_players())),
+        Request::Kick { username, reason } => server
+            .kick(
+                &username,
+                reason.as_deref().unwrap_or("You were kicked by the server"),
+            )
+            .map(|()| Value::Null),
+        Request::Ban {
+            username,
+            reason,
+            duration_secs,
+            overwrite,
+        } => server
+            .ban(
+                &username,
+                reason,
+                duration_secs.map(Duration::from_secs),
+                overwrite,
+            )
+            .map(Value::from),
+        Request::Unban { username } => server.unban(&username).map(Value::from),
+        Request::WhitelistAdd { username } => server.whitelist_add(&username).map(Value::from),
+        Request::WhitelistRemove { username } => {
+            server.whitelist_remove(&username).map(Value::from)
+        },
+        Request::AdminAdd { username, role } => {
+            let role = role
+                .parse::<comp::AdminRole>()
+                .map_err(|_| format!("Unknown role {:?}", role))?;
+            server.add_admin(&username, role);
+            Ok(Value::Null)
+        },
+        Request::AdminRemove { username } => {
+            server.remove_admin(&username);
+            Ok(Value::Null)
+        },
+        Request::Broadcast { message } => {
+            server.broadcast(&message);
+            Ok(Value::Null)
+        },
+        Request::Shutdown { seconds, reason } => {
+            shutdown_coordinator.initiate_shutdown(server, Duration::from_secs(seconds), reason);
+            Ok(Value::Null)
+        },
+        Request::CancelShutdown => {
+            shutdown_coordinator.abort_shutdown(server);
+            Ok(Value::Null)
+        },
+        Request::Command { command } => {
+            let mut args = shell_words::split(command.trim_start_matches('/'))
+                .map_err(|e| e.to_string())?
+                .into_iter();
+            let name = args.next().ok_or_else(|| "No command given".to_owned())?;
+            server
+                .execute_console_command(&name, args.collect())
+                .map(|output| json!(output))
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    fn request(authorization: Option<&str>, body: &str) -> HttpRequest<Body> {
+        let mut request = HttpRequest::builder().method(Method::POST).uri("/");
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        request.body(Body::from(body.to_owned())).unwrap()
+    }
+
+    #[test]
+    fn short_tokens_are_refused() {
+        let runtime = Runtime::new().unwrap();
+        let address = SocketAddr::from(([127, 0, 0, 1], 0));
+        assert!(spawn(&runtime, address, String::new()).is_err());
+        assert!(spawn(&runtime, address, " ".repeat(MIN_TOKEN_LEN)).is_err());
+        assert!(spawn(&runtime, address, "short".to_owned()).is_err());
+    }
+
+    #[test]
+    fn unauthorized_requests_are_rejected() {
+        let runtime = Runtime::new().unwrap();
+        let (request_s, mut request_r) = mpsc::unbounded_channel();
+        let token = Arc::new(TOKEN.to_owned());
+        let body = r#"{"action": "players"}"#;
+        for authorization in [
+            None,
+            Some("Bearer "),
+            Some("Bearer 0123456789abcde"),
+            Some("Bearer 0123456789abcdeF"),
+            Some("0123456789abcdef"),
+        ] {
+            let response = runtime
+                .block_on(respond(
+                    request(authorization, body),
+                    request_s.clone(),
+                    Arc::clone(&token),
+                ))
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        assert!(request_r.try_recv().is_err());
+    }
+
+    #[test]
+    fn requests_are_dispatched() {
+        let runtime = Runtime::new().unwrap();
+        let (request_s, mut request_r) = mpsc::unbounded_channel::<PendingRequest>();
+        let token = Arc::new(TOKEN.to_owned());
+        let authorization = Some("Bearer 0123456789abcdef");
+        // the main loop
+        runtime.spawn(async move {
+            while let Some((request, result_s)) = request_r.recv().await {
+                let result = match request {
+                    Request::Command { command } => Ok(json!(command)),
+                    Request::Kick { username, reason } => Err(format!("{} {:?}", username, reason)),
+                    _ => Ok(Value::Null),
+                };
+                let _ = result_s.send(result);
+            }
+        });
+        let send = |body: &str| {
+            let response = runtime
+                .block_on(respond(
+                    request(authorization, body),
+                    request_s.clone(),
+                    Arc::clone(&token),
+                ))
+                .unwrap();
+            let status = response.status();
+            let body = runtime
+                .block_on(hyper::body::to_bytes(response.into_body()))
+                .unwrap();
+            (status, serde_json::from_slice::<Value>(&body).unwrap())
+        };
+
+        assert_eq!(
+            send(r#"{"action": "command", "command": "/time night"}"#),
+            (
+                StatusCode::OK,
+                json!({ "ok": true, "result": "/time night" })
+            )
+        );
+        assert_eq!(
+            send(r#"{"action": "kick", "username": "foo"}"#),
+            (
+                StatusCode::BAD_REQUEST,
+                json!({ "ok": false, "error": "foo None" })
+            )
+        );
+        let (status, _) = send(r#"{"action": "self_destruct"}"#);
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
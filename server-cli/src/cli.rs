@@ -1,6 +1,6 @@
 use common::comp;
 use server::persistence::SqlLogMode;
-use std::sync::mpsc::Sender;
+use std::{path::PathBuf, sync::mpsc::Sender};
 use structopt::StructOpt;
 use tracing::error;
 
@@ -36,6 +36,62 @@ pub enum Shutdown {
     Cancel,
 }
 
+#[derive(Clone, Debug, StructOpt)]
+pub enum Schedule {
+    /// Lists the scheduled tasks with the time they run next
+    List,
+    /// Stops running a scheduled task until the server restarts
+    Cancel {
+        /// Name of the task
+        name: String,
+    },
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum TerrainSnapshot {
+    /// Lists all terrain snapshots, oldest first
+    List,
+    /// Shows which chunks changed between two snapshots
+    Diff {
+        /// Name of the older snapshot
+        from: String,
+        /// Name of the newer snapshot
+        to: String,
+    },
+    /// Deletes all but the newest snapshots
+    Prune {
+        /// Number of snapshots to keep
+        keep: usize,
+    },
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum TerrainPrefab {
+    /// Exports the persisted terrain changes within an area to a `.vox` file
+    /// and a structure manifest next to it
+    #[structopt(setting = clap::AppSettings::AllowNegativeNumbers)]
+    Export {
+        /// Path of the `.vox` file
+        path: PathBuf,
+        x_min: i32,
+        y_min: i32,
+        z_min: i32,
+        x_max: i32,
+        y_max: i32,
+        z_max: i32,
+    },
+    /// Imports the blocks of a `.vox` file exported by `export`, with the
+    /// minimum corner of the area at the given position
+    #[structopt(setting = clap::AppSettings::AllowNegativeNumbers)]
+    Import {
+        /// Path of the `.vox` file
+        path: PathBuf,
+        x: i32,
+        y: i32,
+        z: i32,
+    },
+}
+
 #[derive(Clone, Debug, StructOpt)]
 pub enum SharedCommand {
     /// Perform operations on the admin list
@@ -54,6 +110,11 @@ pub enum Message {
         #[structopt(subcommand)]
         command: Shutdown,
     },
+    /// List or cancel the scheduled tasks
+    Schedule {
+        #[structopt(subcommand)]
+        command: Schedule,
+    },
     /// Loads up the chunks at map center and adds a entity that mimics a
     /// player to keep them from despawning
     LoadArea {
@@ -86,6 +147,19 @@ pub struct TuiApp {
 pub enum ArgvCommand {
     #[structopt(flatten)]
     Shared(SharedCommand),
+    /// Manage the snapshots of terrain persistence
+    #[cfg(feature = "persistent_world")]
+    TerrainSnapshot {
+        #[structopt(subcommand)]
+        command: TerrainSnapshot,
+    },
+    /// Move persisted terrain changes between servers, while the server isn't
+    /// running
+    #[cfg(feature = "persistent_world")]
+    TerrainPrefab {
+        #[structopt(subcommand)]
+        command: TerrainPrefab,
+    },
 }
 
 #[derive(StructOpt)]
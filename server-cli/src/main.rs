@@ -4,13 +4,18 @@
 
 /// `server-cli` interface commands not to be confused with the commands sent
 /// from the client to the server
+mod admin_api;
 mod cli;
+mod schedule;
 mod settings;
 mod shutdown_coordinator;
 mod tui_runner;
 mod tuilog;
+#[cfg(feature = "persistent_world")]
+use crate::cli::{TerrainPrefab, TerrainSnapshot};
 use crate::{
-    cli::{Admin, ArgvApp, ArgvCommand, Message, SharedCommand, Shutdown},
+    cli::{Admin, ArgvApp, ArgvCommand, Message, Schedule, SharedCommand, Shutdown},
+    schedule::Scheduler,
     shutdown_coordinator::ShutdownCoordinator,
     tui_runner::Tui,
     tuilog::TuiLog,
@@ -32,6 +37,86 @@ lazy_static::lazy_static! {
 }
 const TPS: u64 = 30;
 
+#[cfg(feature = "persistent_world")]
+fn terrain_snapshot(
+    command: TerrainSnapshot,
+    data_dir: &std::path::Path,
+) -> Result<(), server::terrain_persistence::snapshot::SnapshotError> {
+    use server::terrain_persistence::{snapshot::SnapshotStore, terrain_path};
+
+    let store = SnapshotStore::new(&terrain_path(data_dir));
+    match command {
+        TerrainSnapshot::List => {
+            for name in store.list()? {
+                let chunks = store.manifest(&name)?.chunks.len();
+                println!("{} ({} modified chunks)", name, chunks);
+            }
+        },
+        TerrainSnapshot::Diff { from, to } => {
+            let diff = store.manifest(&from)?.diff(&store.manifest(&to)?);
+            for (label, keys) in [
+                ("Added", diff.added),
+                ("Removed", diff.removed),
+                ("Changed", diff.changed),
+            ] {
+                println!("{} chunks: {}", label, keys.len());
+                for key in keys {
+                    println!("  {} {}", key.x, key.y);
+                }
+            }
+        },
+        TerrainSnapshot::Prune { keep } => {
+            for name in store.prune(keep)? {
+                println!("Deleted {}", name);
+            }
+        },
+    }
+    Ok(())
+}
+
+#[cfg(feature = "persistent_world")]
+fn terrain_prefab(
+    command: TerrainPrefab,
+    data_dir: &std::path::Path,
+) -> Result<(), server::terrain_persistence::prefab::PrefabError> {
+    use server::terrain_persistence::{prefab::Prefab, TerrainPersistence};
+    use vek::*;
+
+    let mut terrain_persistence = TerrainPersistence::try_new(data_dir.to_owned())?;
+    match command {
+        TerrainPrefab::Export {
+            path,
+            x_min,
+            y_min,
+            z_min,
+            x_max,
+            y_max,
+            z_max,
+        } => {
+            let prefab = terrain_persistence.export(Aabb {
+                min: Vec3::new(x_min, y_min, z_min),
+                max: Vec3::new(x_max, y_max, z_max),
+            })?;
+            prefab.save(&path)?;
+            println!(
+                "Exported {} blocks to {}",
+                prefab.blocks.len(),
+                path.display()
+            );
+        },
+        TerrainPrefab::Import { path, x, y, z } => {
+            let prefab = Prefab::load(&path)?;
+            terrain_persistence.import(&prefab, Vec3::new(x, y, z));
+            println!(
+                "Imported {} blocks from {}",
+                prefab.blocks.len(),
+                path.display()
+            );
+        },
+    }
+    Ok(())
+}
+
 fn main() -> io::Result<()> {
     let app = ArgvApp::from_args();
 
@@ -137,6 +222,12 @@ fn main() -> io::Result<()> {
                 }
                 Ok(())
             },
+            #[cfg(feature = "persistent_world")]
+            ArgvCommand::TerrainSnapshot { command } => terrain_snapshot(command, &server_data_dir)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string())),
+            #[cfg(feature = "persistent_world")]
+            ArgvCommand::TerrainPrefab { command } => terrain_prefab(command, &server_data_dir)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string())),
         };
     }
 
@@ -156,6 +247,12 @@ fn main() -> io::Result<()> {
 
     let server_port = &server_settings.gameserver_address.port();
     let metrics_port = &server_settings.metrics_address.port();
+    let mut admin_api = settings
+        .admin_api_token
+        .clone()
+        .map(|token| admin_api::spawn(&runtime, settings.admin_api_address, token))
+        .transpose()?;
+
     // Create server
     let mut server = Server::new(
         server_settings,
@@ -173,6 +270,7 @@ fn main() -> io::Result<()> {
     );
 
     let mut shutdown_coordinator = ShutdownCoordinator::new(Arc::clone(&sigusr1_signal));
+    let mut scheduler = Scheduler::new(&settings.schedule);
 
     // Set up an fps clock
     let mut clock = Clock::new(Duration::from_secs_f64(1.0 / TPS as f64));
@@ -187,6 +285,8 @@ fn main() -> io::Result<()> {
             break;
         }
 
+        scheduler.tick(&mut server, &mut shutdown_coordinator);
+
         let events = server
             .tick(Input::default(), clock.dt())
             .expect("Failed to tick server");
@@ -237,6 +337,12 @@ fn main() -> io::Result<()> {
                     }) => {
                         server.remove_admin(&username);
                     },
+                    Message::Schedule {
+                        command: Schedule::List,
+                    } => scheduler.list(),
+                    Message::Schedule {
+                        command: Schedule::Cancel { name },
+                    } => scheduler.cancel(&name),
                     Message::LoadArea { view_distance } => {
                         #[cfg(feature = "worldgen")]
                         server.create_centered_persister(view_distance);
@@ -252,6 +358,15 @@ fn main() -> io::Result<()> {
             }
         }
 
+        if let Some(admin_api) = admin_api.as_mut() {
+            while let Ok((request, result_s)) = admin_api.try_recv() {
+                let result =
+                    admin_api::handle_request(request, &mut server, &mut shutdown_coordinator);
+                // The client may have closed the connection in the meantime
+                let _ = result_s.send(result);
+            }
+        }
+
         drop(guard);
         // Wait for the next tick.
         clock.tick();
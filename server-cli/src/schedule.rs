@@ -0,0 +1,365 @@
+use crate::shutdown_coordinator::ShutdownCoordinator;
+use chrono::{Datelike, Duration as ChronoDuration, Local, NaiveDate, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use server::Server;
+use std::{str::FromStr, time::Duration};
+use tracing::{error, info, warn};
+
+/// Something the server does on a schedule
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Action {
+    /// Shuts down the server gracefully, warning players during the grace
+    /// period. The server is expected to be restarted by whatever supervises
+    /// it, like for updates.
+    Restart {
+        grace_period_secs: u64,
+        message: String,
+    },
+    /// Sends a message to the chat of every player
+    Broadcast(String),
+    /// Sets the server description shown to players when they join
+    Motd(String),
+    /// Takes a terrain snapshot and backs up the character database
+    Backup,
+    /// Sets the time of day, as the `/time` command does, e.g. to `"morning"`
+    TimeOfDay(String),
+    /// Runs a chat command with the permissions of an administrator, e.g.
+    /// `"/weather_zone clear 2000"`
+    Command(String),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScheduledTask {
+    /// Name to refer to the task by in the `schedule` command
+    pub name: String,
+    /// When to run the task, as a cron expression in local time with the
+    /// fields minute, hour, day of month, month and day of week, e.g.
+    /// `"0 4 * * *"` for every day at 4:00 or `"*/30 * * * *"` for every half
+    /// hour.
+    pub cron: String,
+    pub action: Action,
+}
+
+/// The values allowed in each field of a cron expression, as bit sets
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    /// Sunday is 0, as in cron
+    weekdays: u64,
+    /// Whether the day of month and day of week fields were both restricted,
+    /// in which case matching either of them is enough
+    either_day: bool,
+}
+
+/// Parses a field like `*`, `1,15`, `9-17` or `*/10` into a bit set of the
+/// allowed values in `min..=max`.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("Invalid step {:?}", step))?,
+            ),
+            None => (part, 1),
+        };
+        let parse = |value: &str| {
+            value
+                .parse::<u32>()
+                .ok()
+                .filter(|value| (min..=max).contains(value))
+                .ok_or_else(|| format!("{:?} is not a number from {} to {}", value, min, max))
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end) = (parse(start)?, parse(end)?);
+                    if start > end {
+                        return Err(format!("Range {:?} ends before it starts", range));
+                    }
+                    (start, end)
+                },
+                // A single value with a step runs until the end, as in cron
+                None if part.contains('/') => (parse(range)?, max),
+                None => {
+                    let value = parse(range)?;
+                    (value, value)
+                },
+            },
+        };
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(format!("Expected 5 fields, found {}", fields.len()));
+        }
+        // Both 0 and 7 are sunday
+        let weekdays = parse_field(fields[4], 0, 7)?;
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays: (weekdays | (weekdays >> 7)) & 0x7f,
+            either_day: !fields[2].starts_with('*') && !fields[4].starts_with('*'),
+        })
+    }
+}
+
+impl Cron {
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        if self.either_day {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// The first minute after `time` that matches, if there is one within the
+    /// next few years.
+    pub fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut next =
+            time.date().and_hms(time.hour(), time.minute(), 0) + ChronoDuration::minutes(1);
+        let limit = time + ChronoDuration::days(366 * 5);
+        while next < limit {
+            if self.months & (1 << next.month()) == 0 {
+                let (year, month) = if next.month() == 12 {
+                    (next.year() + 1, 1)
+                } else {
+                    (next.year(), next.month() + 1)
+                };
+                next = NaiveDate::from_ymd(year, month, 1).and_hms(0, 0, 0);
+            } else if !self.matches_day(next.date()) {
+                next = next.date().succ().and_hms(0, 0, 0);
+            } else if self.hours & (1 << next.hour()) == 0 {
+                next = next.date().and_hms(next.hour(), 0, 0) + ChronoDuration::hours(1);
+            } else if self.minutes & (1 << next.minute()) == 0 {
+                next += ChronoDuration::minutes(1);
+            } else {
+                return Some(next);
+            }
+        }
+        None
+    }
+}
+
+struct Entry {
+    task: ScheduledTask,
+    cron: Cron,
+    next_run: Option<NaiveDateTime>,
+}
+
+/// Runs the tasks of the `schedule` of the settings when they are due.
+pub(crate) struct Scheduler {
+    entries: Vec<Entry>,
+}
+
+impl Scheduler {
+    pub fn new(tasks: &[ScheduledTask]) -> Self {
+        let now = Local::now().naive_local();
+        let entries = tasks
+            .iter()
+            .filter_map(|task| match task.cron.parse::<Cron>() {
+                Ok(cron) => Some(Entry {
+                    task: task.clone(),
+                    cron,
+                    next_run: cron.next_after(now),
+                }),
+                Err(e) => {
+                    error!(
+                        ?e,
+                        "Invalid cron expression {:?} of scheduled task {:?}, ignoring it",
+                        task.cron,
+                        task.name
+                    );
+                    None
+                },
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Called once per tick to run the tasks which are due.
+    pub fn tick(&mut self, server: &mut Server, shutdown_coordinator: &mut ShutdownCoordinator) {
+        let now = Local::now().naive_local();
+        for entry in self.entries.iter_mut() {
+            if entry.next_run.map_or(false, |next_run| next_run <= now) {
+                entry.next_run = entry.cron.next_after(now);
+                info!("Running scheduled task {:?}", entry.task.name);
+                run(&entry.task.action, server, shutdown_coordinator);
+            }
+        }
+    }
+
+    pub fn list(&self) {
+        if self.entries.is_empty() {
+            info!("There are no scheduled tasks");
+        }
+        for entry in self.entries.iter() {
+            match entry.next_run {
+                Some(next_run) => info!(
+                    "{:?} ({}) next runs at {}: {:?}",
+                    entry.task.name, entry.task.cron, next_run, entry.task.action
+                ),
+                None => info!(
+                    "{:?} ({}) never runs: {:?}",
+                    entry.task.name, entry.task.cron, entry.task.action
+                ),
+            }
+        }
+    }
+
+    /// Stops running the task until the server is restarted.
+    pub fn cancel(&mut self, name: &str) {
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.task.name != name);
+        if self.entries.len() == len {
+            error!("There is no scheduled task {:?}", name);
+        } else {
+            info!(
+                "Cancelled scheduled task {:?} until the server restarts (use `shutdown cancel` \
+                 to cancel a restart that is already counting down)",
+                name
+            );
+        }
+    }
+}
+
+fn run(action: &Action, server: &mut Server, shutdown_coordinator: &mut ShutdownCoordinator) {
+    let command = |server: &mut Server, name: &str, args: Vec<String>| match server
+        .execute_console_command(name, args)
+    {
+        Ok(output) => output.iter().for_each(|line| info!("{}", line)),
+        Err(e) => warn!("{}", e),
+    };
+    match action {
+        Action::Restart {
+            grace_period_secs,
+            message,
+        } => shutdown_coordinator.initiate_shutdown(
+            server,
+            Duration::from_secs(*grace_period_secs),
+            message.clone(),
+        ),
+        Action::Broadcast(message) => server.broadcast(message),
+        Action::Motd(motd) => {
+            if let Err(e) = server.set_motd(motd.clone()) {
+                error!("{}", e);
+            }
+        },
+        Action::Backup => match server.backup() {
+            Ok(backups) => backups.iter().for_each(|backup| info!("{}", backup)),
+            Err(e) => error!("{}", e),
+        },
+        Action::TimeOfDay(time) => command(server, "time", vec![time.clone()]),
+        Action::Command(line) => match shell_words::split(line.trim_start_matches('/')) {
+            Ok(mut args) if !args.is_empty() => {
+                let name = args.remove(0);
+                command(server, &name, args);
+            },
+            _ => error!("Invalid scheduled command {:?}", line),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(y, m, d).and_hms(h, min, 0)
+    }
+
+    fn next(cron: &str, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        cron.parse::<Cron>().unwrap().next_after(after)
+    }
+
+    #[test]
+    fn parse_fields() {
+        assert_eq!(parse_field("*", 0, 5), Ok(0b111111));
+        assert_eq!(parse_field("1,3", 0, 5), Ok(0b1010));
+        assert_eq!(parse_field("2-4", 0, 5), Ok(0b11100));
+        assert_eq!(parse_field("*/2", 0, 5), Ok(0b10101));
+        assert_eq!(parse_field("1-5/2,0", 0, 5), Ok(0b101011));
+        // A single value with a step runs until the end
+        assert_eq!(parse_field("3/1", 0, 5), Ok(0b111000));
+
+        assert!(parse_field("4-2", 0, 5).is_err());
+        assert!(parse_field("6", 0, 5).is_err());
+        assert!(parse_field("*/0", 0, 5).is_err());
+        assert!(parse_field("a", 0, 5).is_err());
+        assert!(parse_field("1,", 0, 5).is_err());
+    }
+
+    #[test]
+    fn parse_cron() {
+        assert!("0 4 * *".parse::<Cron>().is_err());
+        assert!("0 4 * * * *".parse::<Cron>().is_err());
+        assert!("0 17-9 * * *".parse::<Cron>().is_err());
+        assert!("60 * * * *".parse::<Cron>().is_err());
+        assert!("* * 0 * *".parse::<Cron>().is_err());
+        // Both 0 and 7 are sunday
+        assert_eq!("0 0 * * 0".parse::<Cron>(), "0 0 * * 7".parse::<Cron>());
+        assert!(!"0 0 * * 1".parse::<Cron>().unwrap().either_day);
+        assert!("0 0 1 * 1".parse::<Cron>().unwrap().either_day);
+    }
+
+    #[test]
+    fn next_runs() {
+        let now = time(2021, 6, 15, 10, 20);
+        // Always strictly after the given time
+        assert_eq!(next("* * * * *", now), Some(time(2021, 6, 15, 10, 21)));
+        assert_eq!(next("20 10 * * *", now), Some(time(2021, 6, 16, 10, 20)));
+        assert_eq!(next("*/15 * * * *", now), Some(time(2021, 6, 15, 10, 30)));
+        assert_eq!(
+            next("0,45 9-11 * * *", now),
+            Some(time(2021, 6, 15, 10, 45))
+        );
+        assert_eq!(next("0 4 * * *", now), Some(time(2021, 6, 16, 4, 0)));
+        // 2021-06-15 is a tuesday
+        assert_eq!(next("0 0 * * 1", now), Some(time(2021, 6, 21, 0, 0)));
+        // Either the day of month or the day of week
+        assert_eq!(next("0 0 1 * 3", now), Some(time(2021, 6, 16, 0, 0)));
+    }
+
+    #[test]
+    fn next_runs_roll_over() {
+        // Into the next day, month and year
+        assert_eq!(
+            next("30 * * * *", time(2021, 2, 28, 23, 45)),
+            Some(time(2021, 3, 1, 0, 30))
+        );
+        assert_eq!(
+            next("0 12 31 * *", time(2021, 4, 1, 0, 0)),
+            Some(time(2021, 5, 31, 12, 0))
+        );
+        assert_eq!(
+            next("0 0 1 1 *", time(2021, 12, 31, 23, 59)),
+            Some(time(2022, 1, 1, 0, 0))
+        );
+        assert_eq!(
+            next("0 0 29 2 *", time(2021, 3, 1, 0, 0)),
+            Some(time(2024, 2, 29, 0, 0))
+        );
+        // There is no 30th of february
+        assert_eq!(next("0 0 30 2 *", time(2021, 3, 1, 0, 0)), None);
+    }
+}
@@ -1,5 +1,10 @@
+use crate::schedule::ScheduledTask;
 use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
 use tracing::warn;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -7,6 +12,14 @@ use tracing::warn;
 pub struct Settings {
     pub update_shutdown_grace_period_secs: u32,
     pub update_shutdown_message: String,
+    /// Address the admin API listens on; keep it local, or behind a reverse
+    /// proxy with TLS, as the token is sent in plain text.
+    pub admin_api_address: SocketAddr,
+    /// Token that requests to the admin API have to be authenticated with, at
+    /// least 16 characters long. The admin API is disabled without one.
+    pub admin_api_token: Option<String>,
+    /// Tasks to run at given times, like restarts or backups
+    pub schedule: Vec<ScheduledTask>,
 }
 
 impl Default for Settings {
@@ -14,6 +27,9 @@ impl Default for Settings {
         Self {
             update_shutdown_grace_period_secs: 120,
             update_shutdown_message: "The server is restarting for an update".to_owned(),
+            admin_api_address: SocketAddr::from((Ipv4Addr::LOCALHOST, 14006)),
+            admin_api_token: None,
+            schedule: Vec::new(),
         }
     }
 }
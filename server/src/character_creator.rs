@@ -1,7 +1,10 @@
 use crate::persistence::character_updater::CharacterUpdater;
 use common::{
     character::CharacterId,
-    comp::{inventory::loadout_builder::LoadoutBuilder, Body, Inventory, Item, SkillSet, Stats},
+    comp::{
+        inventory::loadout_builder::LoadoutBuilder, Body, Inventory, Item, QuestLog, SkillSet,
+        Stats,
+    },
 };
 use specs::{Entity, WriteExpect};
 
@@ -69,7 +72,15 @@ pub fn create_character(
         entity,
         player_uuid,
         character_alias,
-        (body, stats, skill_set, inventory, waypoint, Vec::new()),
+        (
+            body,
+            stats,
+            skill_set,
+            inventory,
+            waypoint,
+            Vec::new(),
+            QuestLog::default(),
+        ),
     );
     Ok(())
 }
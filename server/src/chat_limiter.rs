@@ -0,0 +1,188 @@
+use crate::settings::ChatLimits;
+use specs::{Component, VecStorage};
+use std::collections::VecDeque;
+
+/// Most recent messages kept to detect repetitions, regardless of the window
+const MAX_RECENT_MESSAGES: usize = 32;
+
+/// Something a player did to trip the chat limits
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Offense {
+    /// Sent more messages or commands than the token bucket allows
+    TooFast,
+    /// Sent the same message too often
+    Repeated,
+}
+
+impl Offense {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Offense::TooFast => "rate_limit",
+            Offense::Repeated => "repeated",
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Offense::TooFast => "sending messages too fast",
+            Offense::Repeated => "repeating the same message",
+        }
+    }
+}
+
+/// What to do with a message, escalating with the offenses of the player
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    Allow,
+    /// Refused, as the player is still muted for the given number of seconds
+    Muted(f64),
+    /// Refused with a warning
+    Warn(Offense),
+    /// Refused, and the player is muted for `ChatLimits::mute_secs`
+    Mute(Offense),
+    /// Refused, and the player is kicked
+    Kick(Offense),
+}
+
+/// Per-player state of the chat limits: a token bucket that each message and
+/// command takes from, the recent messages and the offenses so far.
+#[derive(Clone, Debug, Default)]
+pub struct ChatLimiter {
+    /// None until the first message, when the bucket starts full
+    tokens: Option<f32>,
+    last_refill: f64,
+    recent: VecDeque<(f64, String)>,
+    offenses: u32,
+    last_offense: f64,
+    muted_until: f64,
+}
+
+impl Component for ChatLimiter {
+    type Storage = VecStorage<Self>;
+}
+
+impl ChatLimiter {
+    /// Account for a chat message (`Some`) or command (`None`) sent at `now`.
+    pub fn check(&mut self, limits: &ChatLimits, now: f64, message: Option<&str>) -> Verdict {
+        if now < self.muted_until {
+            return Verdict::Muted(self.muted_until - now);
+        }
+
+        let tokens = self.tokens.map_or(limits.burst, |tokens| {
+            (tokens + (now - self.last_refill) as f32 * limits.per_second).min(limits.burst)
+        });
+        self.last_refill = now;
+        let offense = if tokens < 1.0 {
+            self.tokens = Some(tokens);
+            Some(Offense::TooFast)
+        } else {
+            self.tokens = Some(tokens - 1.0);
+            message.and_then(|message| self.repeated(limits, now, message))
+        };
+
+        match offense {
+            Some(offense) => self.escalate(limits, now, offense),
+            None => Verdict::Allow,
+        }
+    }
+
+    fn repeated(&mut self, limits: &ChatLimits, now: f64, message: &str) -> Option<Offense> {
+        let message = message.trim().to_lowercase();
+        while self.recent.front().map_or(false, |(time, _)| {
+            now - time > limits.repeat_window_secs || self.recent.len() >= MAX_RECENT_MESSAGES
+        }) {
+            self.recent.pop_front();
+        }
+        let repeats = self
+            .recent
+            .iter()
+            .filter(|(_, recent)| *recent == message)
+            .count();
+        self.recent.push_back((now, message));
+        (repeats >= limits.max_repeats).then_some(Offense::Repeated)
+    }
+
+    fn escalate(&mut self, limits: &ChatLimits, now: f64, offense: Offense) -> Verdict {
+        if now - self.last_offense > limits.forgive_secs {
+            self.offenses = 0;
+        }
+        self.offenses += 1;
+        self.last_offense = now;
+        if self.offenses <= limits.warnings {
+            Verdict::Warn(offense)
+        } else if self.offenses <= limits.warnings + limits.mutes {
+            self.muted_until = now + limits.mute_secs;
+            Verdict::Mute(offense)
+        } else {
+            Verdict::Kick(offense)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escalates_from_warning_to_kick() {
+        let limits = ChatLimits {
+            burst: 2.0,
+            per_second: 1.0,
+            warnings: 1,
+            mutes: 1,
+            mute_secs: 10.0,
+            ..ChatLimits::default()
+        };
+        let mut limiter = ChatLimiter::default();
+
+        assert_eq!(limiter.check(&limits, 0.0, None), Verdict::Allow);
+        assert_eq!(limiter.check(&limits, 0.0, None), Verdict::Allow);
+        assert_eq!(
+            limiter.check(&limits, 0.0, None),
+            Verdict::Warn(Offense::TooFast)
+        );
+        // The bucket refills over time
+        assert_eq!(limiter.check(&limits, 1.0, None), Verdict::Allow);
+        assert_eq!(
+            limiter.check(&limits, 1.0, None),
+            Verdict::Mute(Offense::TooFast)
+        );
+        assert_eq!(limiter.check(&limits, 5.0, None), Verdict::Muted(6.0));
+        assert_eq!(limiter.check(&limits, 11.0, None), Verdict::Allow);
+        assert_eq!(limiter.check(&limits, 11.0, None), Verdict::Allow);
+        assert_eq!(
+            limiter.check(&limits, 11.0, None),
+            Verdict::Kick(Offense::TooFast)
+        );
+    }
+
+    #[test]
+    fn detects_repeated_messages() {
+        let limits = ChatLimits {
+            burst: 100.0,
+            max_repeats: 2,
+            repeat_window_secs: 30.0,
+            ..ChatLimits::default()
+        };
+        let mut limiter = ChatLimiter::default();
+
+        assert_eq!(
+            limiter.check(&limits, 0.0, Some("buy gold")),
+            Verdict::Allow
+        );
+        assert_eq!(
+            limiter.check(&limits, 1.0, Some("Buy gold ")),
+            Verdict::Allow
+        );
+        assert_eq!(limiter.check(&limits, 2.0, Some("hello")), Verdict::Allow);
+        assert_eq!(
+            limiter.check(&limits, 3.0, Some("buy gold")),
+            Verdict::Warn(Offense::Repeated)
+        );
+        // Messages outside of the window don't count
+        assert_eq!(
+            limiter.check(&limits, 40.0, Some("buy gold")),
+            Verdict::Allow
+        );
+    }
+}
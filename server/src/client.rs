@@ -1,3 +1,4 @@
+use crate::replay::{self, Recording};
 use common_net::msg::{ClientType, ServerGeneral, ServerMsg};
 use network::{Message, Participant, Stream, StreamError, StreamParams};
 use serde::{de::DeserializeOwned, Serialize};
@@ -16,6 +17,8 @@ pub struct Client {
     pub participant: Option<Participant>,
     pub last_ping: Mutex<f64>,
     pub login_msg_sent: AtomicBool,
+    /// Replay of the in-game messages sent to this client, see `/record`
+    pub(crate) recording: Mutex<Option<Recording>>,
 
     //TODO: improve network crate so that `send` is no longer `&mut self` and we can get rid of
     // this Mutex. This Mutex is just to please the compiler as we do not get into contention
@@ -25,6 +28,7 @@ pub struct Client {
     character_screen_stream: Mutex<Stream>,
     in_game_stream: Mutex<Stream>,
     terrain_stream: Mutex<Stream>,
+    plugin_stream: Mutex<Stream>,
 
     general_stream_params: StreamParams,
     ping_stream_params: StreamParams,
@@ -32,11 +36,14 @@ pub struct Client {
     character_screen_stream_params: StreamParams,
     in_game_stream_params: StreamParams,
     terrain_stream_params: StreamParams,
+    plugin_stream_params: StreamParams,
 }
 
 pub struct PreparedMsg {
     stream_id: u8,
     message: Message,
+    /// The message encoded for recordings, when there are any
+    replay: Option<Vec<u8>>,
 }
 
 impl Component for Client {
@@ -55,6 +62,7 @@ impl Client {
         character_screen_stream: Stream,
         in_game_stream: Stream,
         terrain_stream: Stream,
+        plugin_stream: Stream,
     ) -> Self {
         let general_stream_params = general_stream.params();
         let ping_stream_params = ping_stream.params();
@@ -62,23 +70,27 @@ impl Client {
         let character_screen_stream_params = character_screen_stream.params();
         let in_game_stream_params = in_game_stream.params();
         let terrain_stream_params = terrain_stream.params();
+        let plugin_stream_params = plugin_stream.params();
         Client {
             client_type,
             participant: Some(participant),
             last_ping: Mutex::new(last_ping),
             login_msg_sent: AtomicBool::new(false),
+            recording: Mutex::new(None),
             general_stream: Mutex::new(general_stream),
             ping_stream: Mutex::new(ping_stream),
             register_stream: Mutex::new(register_stream),
             character_screen_stream: Mutex::new(character_screen_stream),
             in_game_stream: Mutex::new(in_game_stream),
             terrain_stream: Mutex::new(terrain_stream),
+            plugin_stream: Mutex::new(plugin_stream),
             general_stream_params,
             ping_stream_params,
             register_stream_params,
             character_screen_stream_params,
             in_game_stream_params,
             terrain_stream_params,
+            plugin_stream_params,
         }
     }
 
@@ -114,7 +126,9 @@ impl Client {
                     | ServerGeneral::Outcomes(_)
                     | ServerGeneral::Knockback(_)
                     | ServerGeneral::UpdatePendingTrade(_, _, _)
-                    | ServerGeneral::FinishedTrade(_) => {
+                    | ServerGeneral::FinishedTrade(_)
+                    | ServerGeneral::StorageUpdate(_)
+                    | ServerGeneral::StorageClosed => {
                         self.in_game_stream.lock().unwrap().send(g)
                     },
                     //Ingame related, terrain
@@ -137,12 +151,18 @@ impl Client {
                 }
             },
             ServerMsg::Ping(m) => self.ping_stream.lock().unwrap().send(m),
+            ServerMsg::Plugin(m) => self.plugin_stream.lock().unwrap().send(m),
         }*/
     }
 
     pub(crate) fn send_fallible<M: Into<ServerMsg>>(&self, msg: M) { let _ = self.send(msg); }
 
     pub(crate) fn send_prepared(&self, msg: &PreparedMsg) -> Result<(), StreamError> {
+        if let Some(replay) = &msg.replay {
+            if let Some(recording) = self.recording.lock().unwrap().as_mut() {
+                recording.write_encoded(replay);
+            }
+        }
         match msg.stream_id {
             0 => self.register_stream.lock().unwrap().send_raw(&msg.message),
             1 => self
@@ -154,6 +174,7 @@ impl Client {
             3 => self.general_stream.lock().unwrap().send_raw(&msg.message),
             4 => self.ping_stream.lock().unwrap().send_raw(&msg.message),
             5 => self.terrain_stream.lock().unwrap().send_raw(&msg.message),
+            6 => self.plugin_stream.lock().unwrap().send_raw(&msg.message),
             _ => unreachable!("invalid stream id"),
         }
     }
@@ -186,13 +207,15 @@ impl Client {
                     | ServerGeneral::Knockback(_)
                     | ServerGeneral::SiteEconomy(_)
                     | ServerGeneral::UpdatePendingTrade(_, _, _)
-                    | ServerGeneral::FinishedTrade(_) => {
-                        PreparedMsg::new(2, &g, &self.in_game_stream_params)
+                    | ServerGeneral::FinishedTrade(_)
+                    | ServerGeneral::StorageUpdate(_)
+                    | ServerGeneral::StorageClosed => {
+                        PreparedMsg::general(2, &g, &self.in_game_stream_params)
                     },
                     //Ingame related, terrain
                     ServerGeneral::TerrainChunkUpdate { .. }
                     | ServerGeneral::TerrainBlockUpdates(_) => {
-                        PreparedMsg::new(5, &g, &self.terrain_stream_params)
+                        PreparedMsg::general(5, &g, &self.terrain_stream_params)
                     },
                     // Always possible
                     ServerGeneral::PlayerListUpdate(_)
@@ -206,11 +229,12 @@ impl Client {
                     | ServerGeneral::DeleteEntity(_)
                     | ServerGeneral::Disconnect(_)
                     | ServerGeneral::Notification(_) => {
-                        PreparedMsg::new(3, &g, &self.general_stream_params)
+                        PreparedMsg::general(3, &g, &self.general_stream_params)
                     },
                 }
             },
             ServerMsg::Ping(m) => PreparedMsg::new(4, &m, &self.ping_stream_params),
+            ServerMsg::Plugin(m) => PreparedMsg::new(6, &m, &self.plugin_stream_params),
         }
     }
 
@@ -226,6 +250,7 @@ impl Client {
             3 => self.general_stream.lock().unwrap().try_recv(),
             4 => self.ping_stream.lock().unwrap().try_recv(),
             5 => self.terrain_stream.lock().unwrap().try_recv(),
+            6 => self.plugin_stream.lock().unwrap().try_recv(),
             _ => unreachable!("invalid stream id"),
         }
     }
@@ -236,6 +261,21 @@ impl PreparedMsg {
         Self {
             stream_id: id,
             message: Message::serialize(&msg, stream_params.clone()),
+            replay: None,
+        }
+    }
+
+    /// Like [`PreparedMsg::new`], also encoding in-game messages for
+    /// recordings
+    fn general(id: u8, msg: &ServerGeneral, stream_params: &StreamParams) -> PreparedMsg {
+        match replay::encode(msg) {
+            // Both use bincode, so the message is only serialized once
+            Some(replay) => Self {
+                stream_id: id,
+                message: Message::from_serialized(replay.clone(), stream_params.clone()),
+                replay: Some(replay),
+            },
+            None => Self::new(id, msg, stream_params),
         }
     }
 }
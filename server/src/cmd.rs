@@ -4,9 +4,13 @@
 
 use crate::{
     client::Client,
+    land_claims::{ClaimError, LandClaims},
     login_provider::LoginProvider,
+    persistence::land_claims::LandClaimUpdater,
+    replay,
     settings::{
-        Ban, BanAction, BanInfo, EditableSetting, SettingError, WhitelistInfo, WhitelistRecord,
+        permissions::Membership, Ban, BanAction, BanInfo, Channel, EditableSetting, Mute,
+        MuteAction, MuteInfo, SettingError, WhitelistInfo, WhitelistRecord,
     },
     sys::terrain::NpcData,
     wiring,
@@ -36,7 +40,7 @@ use common::{
     generation::EntityInfo,
     npc::{self, get_npc_name},
     resources::{BattleMode, PlayerPhysicsSettings, Time, TimeOfDay},
-    terrain::{Block, BlockKind, SpriteKind, TerrainChunkSize},
+    terrain::{Block, BlockKind, SpriteKind, TerrainChunkSize, TerrainGrid},
     uid::Uid,
     vol::{ReadVol, RectVolSize},
     Damage, DamageKind, DamageSource, Explosion, LoadoutBuilder, RadiusEffect,
@@ -56,6 +60,8 @@ use vek::*;
 use wiring::{Circuit, Wire, WiringAction, WiringActionEffect, WiringElement};
 use world::util::Sampler;
 
+#[cfg(feature = "persistent_world")]
+use crate::terrain_persistence::journal::EditAuthor;
 use common::comp::Alignment;
 use tracing::{error, info, warn};
 
@@ -103,8 +109,9 @@ fn do_command(
     args: Vec<String>,
     cmd: &ChatCommand,
 ) -> CmdResult<()> {
-    // Make sure your role is at least high enough to execute this command.
-    if cmd.needs_role() > server.entity_admin_role(client) {
+    // Make sure your role is at least high enough to execute this command, or one
+    // of your permission groups grants it.
+    if !server.entity_permits_command(client, cmd) {
         return Err(format!(
             "You don't have permission to use '/{}'.",
             cmd.keyword()
@@ -124,10 +131,12 @@ fn do_command(
         ChatCommand::BuildAreaList => handle_build_area_list,
         ChatCommand::BuildAreaRemove => handle_build_area_remove,
         ChatCommand::Campfire => handle_spawn_campfire,
+        ChatCommand::Claim => handle_claim,
         ChatCommand::DebugColumn => handle_debug_column,
         ChatCommand::DisconnectAllPlayers => handle_disconnect_all_players,
         ChatCommand::DropAll => handle_drop_all,
         ChatCommand::Dummy => handle_spawn_training_dummy,
+        ChatCommand::EditHistory => handle_edit_history,
         ChatCommand::Explosion => handle_explosion,
         ChatCommand::Faction => handle_faction,
         ChatCommand::GiveItem => handle_give_item,
@@ -152,11 +161,16 @@ fn do_command(
         ChatCommand::MakeNpc => handle_make_npc,
         ChatCommand::MakeSprite => handle_make_sprite,
         ChatCommand::Motd => handle_motd,
+        ChatCommand::Mute => handle_mute,
         ChatCommand::Object => handle_object,
+        ChatCommand::PermissionGroup => handle_permission_group,
         ChatCommand::PermitBuild => handle_permit_build,
         ChatCommand::Players => handle_players,
+        ChatCommand::Record => handle_record,
         ChatCommand::Region => handle_region,
         ChatCommand::RemoveLights => handle_remove_lights,
+        ChatCommand::RestoreTerrain => handle_restore_terrain,
+        ChatCommand::RevertEdits => handle_revert_edits,
         ChatCommand::RevokeBuild => handle_revoke_build,
         ChatCommand::RevokeBuildAll => handle_revoke_build_all,
         ChatCommand::Safezone => handle_safezone,
@@ -172,6 +186,7 @@ fn do_command(
         ChatCommand::Time => handle_time,
         ChatCommand::Tp => handle_tp,
         ChatCommand::Unban => handle_unban,
+        ChatCommand::Unmute => handle_unmute,
         ChatCommand::Version => handle_version,
         ChatCommand::Waypoint => handle_waypoint,
         ChatCommand::Wiring => handle_spawn_wiring,
@@ -242,6 +257,18 @@ fn real_role(server: &Server, uuid: Uuid, descriptor: &str) -> CmdResult<comp::A
         .ok_or_else(|| format!("Cannot get administrator roles for {:?} uuid", descriptor))
 }
 
+/// The role a client performs moderation commands with: their permanent role,
+/// or the lowest one when only their permission groups grant the command
+/// (`do_command` has already checked that they may use it).
+///
+/// Ban, mute and whitelist records can only store a role, and the lowest one
+/// is the only choice that doesn't let group members overturn what
+/// administrators did. `verify_above_role` still keeps them from acting on
+/// players that have any role.
+fn acting_role(server: &Server, uuid: Uuid) -> comp::AdminRole {
+    real_role(server, uuid, "client").unwrap_or(comp::AdminRole::Moderator)
+}
+
 // Fallibly get uid of entity with the given descriptor (used for error
 // message).
 fn uid(server: &Server, target: EcsEntity, descriptor: &str) -> CmdResult<Uid> {
@@ -289,6 +316,9 @@ fn no_sudo(client: EcsEntity, target: EcsEntity) -> CmdResult<()> {
 /// temporary role can be different from their permanent role without someone
 /// with a higher role than their permanent role allowing it, and only permanent
 /// roles should be recorded in the settings files.
+///
+/// Clients without any role were granted the command by a permission group,
+/// which lets them act on players without a role.
 fn verify_above_role(
     server: &mut Server,
     (client, client_uuid): (EcsEntity, Uuid),
@@ -309,7 +339,11 @@ fn verify_above_role(
         .get(&player_uuid)
         .map(|record| record.role);
 
-    if client_perm > player_perm || client_perm == player_perm && client_temp > player_temp {
+    let granted = client_perm.is_none() && client_temp.is_none();
+    if client_perm > player_perm
+        || client_perm == player_perm
+            && (client_temp > player_temp || granted && player_temp.is_none())
+    {
         Ok(())
     } else {
         Err(reason.into())
@@ -548,6 +582,8 @@ fn handle_make_block(
             let pos = position(server, target, "target")?;
             let new_block = Block::new(bk, Rgb::new(r, g, b).map(|e| e.unwrap_or(255)));
             let pos = pos.0.map(|e| e.floor() as i32);
+            #[cfg(feature = "persistent_world")]
+            let old_block = server.state.get_block(pos).unwrap_or_else(Block::empty);
             server.state.set_block(pos, new_block);
             #[cfg(feature = "persistent_world")]
             if let Some(terrain_persistence) = server
@@ -556,7 +592,12 @@ fn handle_make_block(
                 .try_fetch_mut::<crate::TerrainPersistence>()
                 .as_mut()
             {
-                terrain_persistence.set_block(pos, new_block);
+                terrain_persistence.set_block(
+                    pos,
+                    old_block,
+                    new_block,
+                    EditAuthor::from_entity(server.state.ecs(), _client),
+                );
             }
             Ok(())
         } else {
@@ -664,12 +705,12 @@ fn handle_make_sprite(
         if let Ok(sk) = SpriteKind::try_from(sprite_name.as_str()) {
             let pos = position(server, target, "target")?;
             let pos = pos.0.map(|e| e.floor() as i32);
-            let new_block = server
+            let old_block = server
                 .state
                 .get_block(pos)
                 // TODO: Make more principled.
-                .unwrap_or_else(|| Block::air(SpriteKind::Empty))
-                .with_sprite(sk);
+                .unwrap_or_else(|| Block::air(SpriteKind::Empty));
+            let new_block = old_block.with_sprite(sk);
             server.state.set_block(pos, new_block);
             #[cfg(feature = "persistent_world")]
             if let Some(terrain_persistence) = server
@@ -678,7 +719,12 @@ fn handle_make_sprite(
                 .try_fetch_mut::<crate::TerrainPersistence>()
                 .as_mut()
             {
-                terrain_persistence.set_block(pos, new_block);
+                terrain_persistence.set_block(
+                    pos,
+                    old_block,
+                    new_block,
+                    EditAuthor::from_entity(server.state.ecs(), _client),
+                );
             }
             Ok(())
         } else {
@@ -1452,6 +1498,174 @@ fn handle_permit_build(
     }
 }
 
+#[cfg(not(feature = "persistent_world"))]
+fn handle_restore_terrain(
+    _server: &mut Server,
+    _client: EcsEntity,
+    _target: EcsEntity,
+    _args: Vec<String>,
+    _action: &ChatCommand,
+) -> CmdResult<()> {
+    Err("Unsupported without persistent_world enabled".into())
+}
+
+#[cfg(feature = "persistent_world")]
+fn handle_restore_terrain(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    if let (Some(snapshot), Some(xlo), Some(xhi), Some(ylo), Some(yhi), Some(zlo), Some(zhi)) =
+        parse_args!(args, String, i32, i32, i32, i32, i32, i32)
+    {
+        let area = Aabb {
+            min: Vec3::new(xlo, ylo, zlo),
+            max: Vec3::new(xhi, yhi, zhi),
+        }
+        .made_valid();
+        let changes = server
+            .state
+            .ecs()
+            .try_fetch_mut::<crate::TerrainPersistence>()
+            .ok_or_else(|| "Terrain persistence is not enabled".to_owned())?
+            .restore(&snapshot, area)
+            .map_err(|err| err.to_string())?;
+
+        let restored = changes.len();
+        let mut chunk_changes = HashMap::<_, Vec<_>>::new();
+        for (pos, block) in changes {
+            chunk_changes
+                .entry(server.state.terrain().pos_key(pos))
+                .or_default()
+                .push((pos, block));
+        }
+        for (key, changes) in chunk_changes {
+            if server.state.terrain().get_key(key).is_none() {
+                // The changes are applied when the chunk is loaded
+                if let Some(mut terrain_persistence) = server
+                    .state
+                    .ecs()
+                    .try_fetch_mut::<crate::TerrainPersistence>()
+                {
+                    terrain_persistence.unload_chunk(key);
+                }
+                continue;
+            }
+
+            // Blocks which weren't modified at the time of the snapshot are
+            // reverted to the generated terrain, which is generated in the
+            // background
+            let (modified, unmodified): (Vec<_>, Vec<_>) =
+                changes.into_iter().partition(|(_, block)| block.is_some());
+            for (pos, block) in modified {
+                if let Some(block) = block {
+                    server.state.set_block(pos, block);
+                }
+            }
+            if unmodified.is_empty() {
+                continue;
+            }
+            let regenerated_tx = match server.state.ecs().try_fetch::<crate::TerrainPersistence>() {
+                Some(terrain_persistence) => terrain_persistence.regenerated_tx(),
+                None => continue,
+            };
+            let world = Arc::clone(&server.world);
+            let index = server.index.clone();
+            server
+                .state
+                .slow_job_pool()
+                .spawn("CHUNK_GENERATOR", move || {
+                    let offset = Vec3::from(key * TerrainChunkSize::RECT_SIZE.map(|e| e as i32));
+                    if let Ok((chunk, _)) =
+                        world.generate_chunk(index.as_index_ref(), key, || false, None)
+                    {
+                        let blocks = unmodified
+                            .into_iter()
+                            .filter_map(|(pos, _)| Some((pos, *chunk.get(pos - offset).ok()?)))
+                            .collect();
+                        let _ = regenerated_tx.send(blocks);
+                    }
+                });
+        }
+
+        server.notify_client(
+            client,
+            ServerGeneral::server_msg(
+                ChatType::CommandInfo,
+                format!("Restored {} blocks to snapshot {}", restored, snapshot),
+            ),
+        );
+        Ok(())
+    } else {
+        Err(action.help_string())
+    }
+}
+
+#[cfg(not(feature = "persistent_world"))]
+fn handle_revert_edits(
+    _server: &mut Server,
+    _client: EcsEntity,
+    _target: EcsEntity,
+    _args: Vec<String>,
+    _action: &ChatCommand,
+) -> CmdResult<()> {
+    Err("Unsupported without persistent_world enabled".into())
+}
+
+#[cfg(feature = "persistent_world")]
+fn handle_revert_edits(
+    server: &mut Server,
+    client: EcsEntity,
+    target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    let mut args = args.into_iter();
+    let username = args.next().ok_or_else(|| action.help_string())?;
+    // The radius and the duration can be given in any order
+    let (mut radius, mut duration) = (None, None);
+    for arg in args {
+        if let Ok(r) = arg.parse::<f32>() {
+            if r.is_nan() || r < 0.0 {
+                return Err(format!("Invalid radius {:?}, it can't be negative", arg));
+            }
+            radius = Some(r);
+        } else if let Ok(d) = arg.parse::<HumanDuration>() {
+            let d: Duration = d.into();
+            duration = Some(d);
+        } else {
+            return Err(action.help_string());
+        }
+    }
+    if radius.is_none() && duration.is_none() {
+        return Err(action.help_string());
+    }
+
+    let player_uuid = find_username(server, &username)?;
+    let center = position(server, target, "target")?.0;
+    let since = duration.map(|duration| Utc::now().timestamp() - duration.as_secs() as i64);
+    let author = EditAuthor::from_entity(server.state.ecs(), client);
+
+    server
+        .state
+        .ecs()
+        .try_fetch::<crate::TerrainPersistence>()
+        .ok_or_else(|| "Terrain persistence is not enabled".to_owned())?
+        .journal()
+        .revert(client, player_uuid, username, author, move |edit| {
+            radius.map_or(true, |r| {
+                edit.pos.map(|e| e as f32 + 0.5).distance(center) <= r
+            }) && since.map_or(true, |since| edit.time >= since)
+        });
+    server.notify_client(
+        client,
+        ServerGeneral::server_msg(ChatType::CommandInfo, "Looking through the edit journal..."),
+    );
+    Ok(())
+}
+
 fn handle_revoke_build(
     server: &mut Server,
     client: EcsEntity,
@@ -1544,6 +1758,51 @@ fn handle_players(
     Ok(())
 }
 
+fn handle_record(
+    server: &mut Server,
+    client: EcsEntity,
+    target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    let (subcommand, player_alias) = parse_args!(args, String, String);
+    let player = match player_alias {
+        Some(alias) => {
+            let (player, player_uuid) = find_alias(server.state.ecs(), &alias)?;
+            if player != client {
+                let client_uuid = uuid(server, client, "client")?;
+                verify_above_role(
+                    server,
+                    (client, client_uuid),
+                    (player, player_uuid),
+                    "Cannot record a player with a higher or equal role",
+                )?;
+            }
+            player
+        },
+        None => target,
+    };
+
+    let message = match subcommand.as_deref() {
+        Some("start") => {
+            let dir = server.data_dir().path.join(replay::REPLAYS_DIR);
+            let path = replay::start_recording(server.state.ecs(), player, &dir)?;
+            format!("Recording to {}", path.display())
+        },
+        Some("stop") => {
+            let path = replay::stop_recording(server.state.ecs(), player)
+                .ok_or("This player isn't being recorded")?;
+            format!("Saved the recording to {}", path.display())
+        },
+        _ => return Err(action.help_string()),
+    };
+    server.notify_client(
+        client,
+        ServerGeneral::server_msg(ChatType::CommandInfo, message),
+    );
+    Ok(())
+}
+
 fn handle_build(
     server: &mut Server,
     client: EcsEntity,
@@ -1551,6 +1810,27 @@ fn handle_build(
     _args: Vec<String>,
     _action: &ChatCommand,
 ) -> CmdResult<()> {
+    // Build areas granted by permission groups and land claims are checked when
+    // building, but toggling build mode needs the component
+    let granted_build = uuid(server, target, "target").map_or(false, |uuid| {
+        !server
+            .editable_settings()
+            .permissions
+            .build_areas(uuid, Utc::now())
+            .is_empty()
+            || server
+                .state
+                .ecs()
+                .read_resource::<LandClaims>()
+                .may_build_anywhere(uuid)
+    });
+    if granted_build {
+        let mut can_build = server.state.ecs().write_storage::<comp::CanBuild>();
+        if let Ok(entry) = can_build.entry(target) {
+            entry.or_insert_with(comp::CanBuild::default);
+        }
+    }
+
     if let Some(mut can_build) = server
         .state
         .ecs()
@@ -1668,6 +1948,202 @@ fn handle_build_area_remove(
     }
 }
 
+/// How many chunks around the spawn point can't be claimed
+const CLAIM_SPAWN_DISTANCE: i32 = 8;
+
+fn handle_claim(
+    server: &mut Server,
+    client: EcsEntity,
+    target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    let limit = server.settings().max_land_claims;
+    if limit == 0 {
+        return Err("Land claims are disabled on this server".into());
+    }
+    let player = uuid(server, target, "target")?;
+    let pos = position(server, target, "target")?;
+    let chunk = TerrainGrid::chunk_key(pos.0.map(|e| e.floor() as i32));
+
+    let (subcommand, username) = parse_args!(args, String, String);
+    let result = match subcommand.as_deref() {
+        None => {
+            // Claims protect builds outside of the build areas set up by admins, so they
+            // can't overlap any but the one covering the whole world
+            let chunk_size = TerrainChunkSize::RECT_SIZE.map(|e| e as i32);
+            let chunk_aabr = Aabr {
+                min: chunk * chunk_size,
+                max: (chunk + 1) * chunk_size - 1,
+            };
+            let overlapping_area = {
+                let build_areas = server.state.ecs().read_resource::<BuildAreas>();
+                build_areas
+                    .area_names()
+                    .iter()
+                    .find(|(name, id)| {
+                        name.as_str() != "world"
+                            && build_areas.areas().get(**id).map_or(false, |aabb| {
+                                Aabr {
+                                    min: aabb.min.xy(),
+                                    max: aabb.max.xy(),
+                                }
+                                .collides_with_aabr(chunk_aabr)
+                            })
+                    })
+                    .map(|(name, _)| name.clone())
+            };
+            if let Some(name) = overlapping_area {
+                return Err(format!(
+                    "This chunk is part of the build area {}, it can't be claimed",
+                    name
+                ));
+            }
+            // Nor may towns, dungeons and the surroundings of the spawn point be claimed,
+            // which would keep everyone else from building or mining there
+            let spawn_chunk = TerrainGrid::chunk_key(
+                server
+                    .state
+                    .ecs()
+                    .read_resource::<SpawnPoint>()
+                    .0
+                    .map(|e| e.floor() as i32),
+            );
+            if (chunk - spawn_chunk).map(i32::abs).reduce_max() <= CLAIM_SPAWN_DISTANCE {
+                return Err("Chunks near the spawn point can't be claimed".into());
+            }
+            #[cfg(feature = "worldgen")]
+            if server
+                .world
+                .sim()
+                .get(chunk)
+                .map_or(true, |chunk| !chunk.sites.is_empty())
+            {
+                return Err("Chunks of towns and dungeons can't be claimed".into());
+            }
+            let land_claims = server.state.mut_resource::<LandClaims>();
+            land_claims.claim(player, chunk, limit).map(|()| {
+                format!(
+                    "Claimed the chunk {} ({} of {} claims)",
+                    chunk,
+                    land_claims.claims_of(player).len(),
+                    limit
+                )
+            })
+        },
+        Some("list") => {
+            let (claims, trusted) = {
+                let land_claims = server.state.ecs().read_resource::<LandClaims>();
+                (
+                    land_claims.claims_of(player),
+                    land_claims.trusted_by(player).collect::<Vec<_>>(),
+                )
+            };
+            let mut trusted = trusted
+                .into_iter()
+                .map(|uuid| {
+                    uuid_to_username(server, client, uuid).unwrap_or_else(|_| uuid.to_string())
+                })
+                .collect::<Vec<_>>();
+            trusted.sort();
+            let claims = claims
+                .iter()
+                .map(|chunk| {
+                    format!(
+                        "{} (from {})",
+                        chunk,
+                        *chunk * TerrainChunkSize::RECT_SIZE.map(|e| e as i32)
+                    )
+                })
+                .collect::<Vec<_>>();
+            let message = format!(
+                "Claimed chunks ({} of {}):\n{}\nTrusted players: {}",
+                claims.len(),
+                limit,
+                claims.join("\n"),
+                if trusted.is_empty() {
+                    "none".to_string()
+                } else {
+                    trusted.join(", ")
+                }
+            );
+            Ok(message)
+        },
+        Some("abandon") => server
+            .state
+            .mut_resource::<LandClaims>()
+            .abandon(player, chunk)
+            .map(|()| format!("Abandoned the chunk {}", chunk)),
+        Some("transfer") => {
+            let username = username.ok_or_else(|| action.help_string())?;
+            let new_owner = find_username(server, &username)?;
+            server
+                .state
+                .mut_resource::<LandClaims>()
+                .transfer(player, chunk, new_owner, limit)
+                .map(|()| format!("Transferred the chunk {} to {}", chunk, username))
+        },
+        Some("trust") => {
+            let username = username.ok_or_else(|| action.help_string())?;
+            let trusted = find_username(server, &username)?;
+            if trusted == player {
+                return Err("You can always build in your own claims".into());
+            }
+            Ok(
+                if server
+                    .state
+                    .mut_resource::<LandClaims>()
+                    .trust(player, trusted)
+                {
+                    format!("{} can now build in your claims", username)
+                } else {
+                    format!("{} is already trusted", username)
+                },
+            )
+        },
+        Some("untrust") => {
+            let username = username.ok_or_else(|| action.help_string())?;
+            let trusted = find_username(server, &username)?;
+            Ok(
+                if server
+                    .state
+                    .mut_resource::<LandClaims>()
+                    .untrust(player, trusted)
+                {
+                    format!("{} can no longer build in your claims", username)
+                } else {
+                    format!("{} wasn't trusted", username)
+                },
+            )
+        },
+        _ => return Err(action.help_string()),
+    };
+
+    let changes = server.state.mut_resource::<LandClaims>().take_changes();
+    server
+        .state
+        .ecs()
+        .read_resource::<LandClaimUpdater>()
+        .batch_update(changes);
+
+    let message = result.map_err(|e| match e {
+        ClaimError::AlreadyClaimed(owner) => format!(
+            "This chunk is already claimed by {}",
+            uuid_to_username(server, client, owner).unwrap_or_else(|_| "another player".into())
+        ),
+        ClaimError::NotClaimed => "This chunk isn't claimed".to_string(),
+        ClaimError::NotOwner => "This chunk is claimed by another player".to_string(),
+        ClaimError::LimitReached(limit) => {
+            format!("Players can't claim more than {} chunks", limit)
+        },
+    })?;
+    server.notify_client(
+        client,
+        ServerGeneral::server_msg(ChatType::CommandInfo, message),
+    );
+    Ok(())
+}
+
 fn handle_help(
     server: &mut Server,
     client: EcsEntity,
@@ -1682,11 +2158,10 @@ fn handle_help(
         )
     } else {
         let mut message = String::new();
-        let entity_role = server.entity_admin_role(client);
 
         // Iterate through all commands you have permission to use.
         ChatCommand::iter()
-            .filter(|cmd| cmd.needs_role() <= entity_role)
+            .filter(|cmd| server.entity_permits_command(client, cmd))
             .for_each(|cmd| {
                 message += &cmd.help_string();
                 message += "\n";
@@ -2034,6 +2509,160 @@ fn handle_lantern(
     }
 }
 
+#[cfg(not(feature = "persistent_world"))]
+fn handle_edit_history(
+    _server: &mut Server,
+    _client: EcsEntity,
+    _target: EcsEntity,
+    _args: Vec<String>,
+    _action: &ChatCommand,
+) -> CmdResult<()> {
+    Err("Unsupported without persistent_world enabled".into())
+}
+
+#[cfg(feature = "persistent_world")]
+fn handle_edit_history(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    if let (Some(x), Some(y), Some(z), radius, limit) = parse_args!(args, i32, i32, i32, i32, usize)
+    {
+        let pos = Vec3::new(x, y, z);
+        let radius = radius.unwrap_or(0).max(0);
+        server
+            .state
+            .ecs()
+            .try_fetch::<crate::TerrainPersistence>()
+            .ok_or_else(|| "Terrain persistence is not enabled".to_owned())?
+            .journal()
+            .history(
+                client,
+                Aabb {
+                    min: pos - radius,
+                    max: pos + radius,
+                },
+                limit.unwrap_or(10),
+            );
+        Ok(())
+    } else {
+        Err(action.help_string())
+    }
+}
+
+/// Answer the `/edit_history` and `/revert_edits` commands once the edit
+/// journal has been searched.
+#[cfg(feature = "persistent_world")]
+pub fn handle_edit_journal_responses(server: &mut Server) {
+    use crate::terrain_persistence::journal::JournalResponseKind;
+
+    let responses = match server.state.ecs().try_fetch::<crate::TerrainPersistence>() {
+        Some(terrain_persistence) => terrain_persistence
+            .journal()
+            .responses()
+            .collect::<Vec<_>>(),
+        None => return,
+    };
+    for response in responses {
+        let (chat_type, msg) = match response.result {
+            JournalResponseKind::History { area, result } => match result {
+                Ok(edits) => (ChatType::CommandInfo, describe_edits(area, &edits)),
+                Err(err) => (
+                    ChatType::CommandError,
+                    format!("Failed to read the edit journal: {}", err),
+                ),
+            },
+            JournalResponseKind::Revert {
+                username,
+                reverter,
+                result,
+            } => match result {
+                Ok(blocks) => {
+                    let reverted = revert_blocks(server, blocks, reverter);
+                    (
+                        ChatType::CommandInfo,
+                        format!("Reverted {} blocks edited by {}", reverted, username),
+                    )
+                },
+                Err(err) => (
+                    ChatType::CommandError,
+                    format!("Failed to read the edit journal: {}", err),
+                ),
+            },
+        };
+        server.notify_client(response.entity, ServerGeneral::server_msg(chat_type, msg));
+    }
+}
+
+#[cfg(feature = "persistent_world")]
+fn describe_edits(
+    area: Aabb<i32>,
+    edits: &[crate::terrain_persistence::journal::BlockEdit],
+) -> String {
+    let describe = |block: Block| match block.get_sprite() {
+        Some(sprite) if sprite != SpriteKind::Empty => {
+            format!("{:?} with {:?}", block.kind(), sprite)
+        },
+        _ => format!("{:?}", block.kind()),
+    };
+    edits.iter().fold(
+        format!("Edits around {}:", (area.min + area.max) / 2),
+        |acc, edit| {
+            let time = chrono::NaiveDateTime::from_timestamp(edit.time, 0);
+            let author = match &edit.author {
+                Some(author) => match author.character {
+                    Some(character) => format!("{} (character {})", author.alias, character),
+                    None => author.alias.clone(),
+                },
+                None => "Server".to_owned(),
+            };
+            format!(
+                "{}\n{} UTC {} at {}: {} -> {}",
+                acc,
+                time.format("%Y-%m-%d %H:%M:%S"),
+                author,
+                edit.pos,
+                describe(edit.old),
+                describe(edit.new)
+            )
+        },
+    )
+}
+
+/// Set the reverted blocks, skipping those changed since the journal was
+/// searched. Returns how many blocks were reverted.
+#[cfg(feature = "persistent_world")]
+fn revert_blocks(
+    server: &mut Server,
+    blocks: Vec<(Vec3<i32>, Block, Block)>,
+    reverter: Option<EditAuthor>,
+) -> usize {
+    let blocks = blocks
+        .into_iter()
+        .filter(|(pos, current, _)| {
+            server
+                .state
+                .get_block(*pos)
+                .map_or(true, |block| block == *current)
+        })
+        .collect::<Vec<_>>();
+    if let Some(mut terrain_persistence) = server
+        .state
+        .ecs()
+        .try_fetch_mut::<crate::TerrainPersistence>()
+    {
+        for (pos, current, reverted) in blocks.iter() {
+            terrain_persistence.set_block(*pos, *current, *reverted, reverter.clone());
+        }
+    }
+    for (pos, _, block) in blocks.iter() {
+        server.state.set_block(*pos, *block);
+    }
+    blocks.len()
+}
+
 fn handle_explosion(
     server: &mut Server,
     _client: EcsEntity,
@@ -2418,7 +3047,7 @@ fn handle_tell(
         } else {
             message_opt.join(" ")
         };
-        server.state.send_chat(mode.new_message(target_uid, msg));
+        server.send_chat_unless_muted(mode.new_message(target_uid, msg));
         server.notify_client(target, ServerGeneral::ChatMode(mode));
         Ok(())
     } else {
@@ -2443,7 +3072,7 @@ fn handle_faction(
         let msg = args.join(" ");
         if !msg.is_empty() {
             if let Some(uid) = server.state.ecs().read_storage().get(target) {
-                server.state.send_chat(mode.new_message(*uid, msg));
+                server.send_chat_unless_muted(mode.new_message(*uid, msg));
             }
         }
         server.notify_client(target, ServerGeneral::ChatMode(mode));
@@ -2470,7 +3099,7 @@ fn handle_group(
         let msg = args.join(" ");
         if !msg.is_empty() {
             if let Some(uid) = server.state.ecs().read_storage().get(target) {
-                server.state.send_chat(mode.new_message(*uid, msg));
+                server.send_chat_unless_muted(mode.new_message(*uid, msg));
             }
         }
         server.notify_client(target, ServerGeneral::ChatMode(mode));
@@ -2594,7 +3223,7 @@ fn handle_region(
     let msg = args.join(" ");
     if !msg.is_empty() {
         if let Some(uid) = server.state.ecs().read_storage().get(target) {
-            server.state.send_chat(mode.new_message(*uid, msg));
+            server.send_chat_unless_muted(mode.new_message(*uid, msg));
         }
     }
     server.notify_client(target, ServerGeneral::ChatMode(mode));
@@ -2615,7 +3244,7 @@ fn handle_say(
     let msg = args.join(" ");
     if !msg.is_empty() {
         if let Some(uid) = server.state.ecs().read_storage().get(target) {
-            server.state.send_chat(mode.new_message(*uid, msg));
+            server.send_chat_unless_muted(mode.new_message(*uid, msg));
         }
     }
     server.notify_client(target, ServerGeneral::ChatMode(mode));
@@ -2636,7 +3265,7 @@ fn handle_world(
     let msg = args.join(" ");
     if !msg.is_empty() {
         if let Some(uid) = server.state.ecs().read_storage().get(target) {
-            server.state.send_chat(mode.new_message(*uid, msg));
+            server.send_chat_unless_muted(mode.new_message(*uid, msg));
         }
     }
     server.notify_client(target, ServerGeneral::ChatMode(mode));
@@ -2978,7 +3607,7 @@ fn handle_whitelist(
     if let (Some(whitelist_action), Some(username)) = parse_args!(args, String, String) {
         let client_uuid = uuid(server, client, "client")?;
         let client_username = uuid_to_username(server, client, client_uuid)?;
-        let client_role = real_role(server, client_uuid, "client")?;
+        let client_role = acting_role(server, client_uuid);
 
         if whitelist_action.eq_ignore_ascii_case("add") {
             let uuid = find_username(server, &username)?;
@@ -3009,7 +3638,7 @@ fn handle_whitelist(
             })
         } else if whitelist_action.eq_ignore_ascii_case("remove") {
             let client_uuid = uuid(server, client, "client")?;
-            let client_role = real_role(server, client_uuid, "client")?;
+            let client_role = acting_role(server, client_uuid);
 
             let uuid = find_username(server, &username)?;
             let mut err_info = "not part of whitelist: ";
@@ -3038,6 +3667,112 @@ fn handle_whitelist(
     }
 }
 
+fn handle_permission_group(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    let now = Utc::now();
+
+    if let (Some(group_action), Some(username), group, parse_duration) =
+        parse_args!(args, String, String, String, HumanDuration)
+    {
+        let uuid = find_username(server, &username)?;
+
+        if group_action.eq_ignore_ascii_case("list") {
+            let message = {
+                let permissions = &server.editable_settings().permissions;
+                let mut memberships = permissions
+                    .iter()
+                    .filter_map(|(name, group)| {
+                        let membership = group.members.get(&uuid)?;
+                        Some(match membership.end_date {
+                            Some(end_date) => format!("{} (until {})", name, end_date),
+                            None => name.clone(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                memberships.sort();
+                if memberships.is_empty() {
+                    format!("{} is not in any permission group", username)
+                } else {
+                    format!(
+                        "{} is in the permission groups: {}\nIncluding inherited groups: {}",
+                        username,
+                        memberships.join(", "),
+                        permissions.groups(uuid, now).join(", ")
+                    )
+                }
+            };
+            server.notify_client(
+                client,
+                ServerGeneral::server_msg(ChatType::CommandInfo, message),
+            );
+            return Ok(());
+        }
+
+        let group = group.ok_or_else(|| action.help_string())?;
+        if !server.editable_settings().permissions.contains_key(&group) {
+            return Err(format!("Permission group {} does not exist", group));
+        }
+
+        if group_action.eq_ignore_ascii_case("add") {
+            let end_date = parse_duration
+                .map(|duration| chrono::Duration::from_std(duration.into()))
+                .transpose()
+                .map_err(|err| format!("Error converting to duration: {}", err))?
+                // On overflow (someone adding some ridiculous timespan), just make the grant
+                // permanent.
+                .and_then(|duration| now.checked_add_signed(duration));
+            let membership = Membership {
+                username_when_added: Some(username.clone()),
+                date: now,
+                end_date,
+            };
+
+            let edit = server.editable_settings_mut().permissions.edit(
+                server.data_dir().as_ref(),
+                |permissions| {
+                    // Adding an existing member again replaces the end of their membership
+                    permissions
+                        .get_mut(&group)?
+                        .members
+                        .insert(uuid, membership);
+                    Some(match end_date {
+                        Some(end_date) => {
+                            format!("Added {} to {} until {}", username, group, end_date)
+                        },
+                        None => format!("Added {} to {}", username, group),
+                    })
+                },
+            );
+            edit_setting_feedback(server, client, edit, || {
+                format!("Permission group {} does not exist", group)
+            })
+        } else if group_action.eq_ignore_ascii_case("remove") {
+            let edit = server.editable_settings_mut().permissions.edit(
+                server.data_dir().as_ref(),
+                |permissions| {
+                    permissions
+                        .get_mut(&group)?
+                        .members
+                        .remove(&uuid)
+                        .map(|_| format!("Removed {} from {}", username, group))
+                },
+            );
+            edit_setting_feedback(server, client, edit, || {
+                format!("{} is not in {}", username, group)
+            })
+        } else {
+            Err(action.help_string())
+        }
+    } else {
+        Err(action.help_string())
+    }
+}
+
 fn kick_player(
     server: &mut Server,
     (client, client_uuid): (EcsEntity, Uuid),
@@ -3111,7 +3846,7 @@ fn handle_ban(
 
         let client_uuid = uuid(server, client, "client")?;
         let client_username = uuid_to_username(server, client, client_uuid)?;
-        let client_role = real_role(server, client_uuid, "client")?;
+        let client_role = acting_role(server, client_uuid);
 
         let now = Utc::now();
         let end_date = parse_duration
@@ -3323,7 +4058,7 @@ fn handle_unban(
 
         let client_uuid = uuid(server, client, "client")?;
         let client_username = uuid_to_username(server, client, client_uuid)?;
-        let client_role = real_role(server, client_uuid, "client")?;
+        let client_role = acting_role(server, client_uuid);
 
         let now = Utc::now();
 
@@ -3356,6 +4091,131 @@ fn handle_unban(
     }
 }
 
+fn handle_mute(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    if let (Some(username), Some(channel), overwrite, parse_duration, reason_opt) =
+        parse_args!(args, String, String, bool, HumanDuration, String)
+    {
+        let channel = match channel.as_str() {
+            "all" => None,
+            channel => Some(
+                channel
+                    .parse::<Channel>()
+                    .map_err(|_| format!("Unknown chat channel: {}", channel))?,
+            ),
+        };
+        let reason = reason_opt.unwrap_or_default();
+        let overwrite = overwrite.unwrap_or(false);
+
+        let player_uuid = find_username(server, &username)?;
+
+        let client_uuid = uuid(server, client, "client")?;
+        let client_username = uuid_to_username(server, client, client_uuid)?;
+        let client_role = acting_role(server, client_uuid);
+
+        let now = Utc::now();
+        let end_date = parse_duration
+            .map(|duration| chrono::Duration::from_std(duration.into()))
+            .transpose()
+            .map_err(|err| format!("Error converting to duration: {}", err))?
+            // On overflow, just make the mute infinite.
+            .and_then(|duration| now.checked_add_signed(duration));
+
+        let mute = Mute {
+            reason: reason.clone(),
+            info: MuteInfo {
+                performed_by: client_uuid,
+                performed_by_username: client_username,
+                performed_by_role: client_role.into(),
+            },
+            channel,
+            end_date,
+        };
+
+        let edit = server
+            .editable_settings_mut()
+            .mutelist
+            .mute_action(
+                server.data_dir().as_ref(),
+                now,
+                player_uuid,
+                username.clone(),
+                MuteAction::Mute(mute),
+                overwrite,
+            )
+            .map(|result| {
+                (
+                    format!("Muted {} with reason: {}", username, reason),
+                    result,
+                )
+            });
+
+        edit_setting_feedback(server, client, edit, || {
+            format!("{} is already muted", username)
+        })?;
+        // Tell the player right away, rather than when they next try to chat
+        let ecs = server.state.ecs();
+        if let Ok(target_player) = find_uuid(ecs, player_uuid) {
+            server.notify_client(
+                target_player,
+                ServerGeneral::server_msg(
+                    ChatType::CommandError,
+                    format!("You have been muted: {}", reason),
+                ),
+            );
+        }
+        Ok(())
+    } else {
+        Err(action.help_string())
+    }
+}
+
+fn handle_unmute(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    if let Some(username) = parse_args!(args, String) {
+        let player_uuid = find_username(server, &username)?;
+
+        let client_uuid = uuid(server, client, "client")?;
+        let client_username = uuid_to_username(server, client, client_uuid)?;
+        let client_role = acting_role(server, client_uuid);
+
+        let unmute = MuteAction::Unmute(MuteInfo {
+            performed_by: client_uuid,
+            performed_by_username: client_username,
+            performed_by_role: client_role.into(),
+        });
+
+        let edit = server
+            .editable_settings_mut()
+            .mutelist
+            .mute_action(
+                server.data_dir().as_ref(),
+                Utc::now(),
+                player_uuid,
+                username.clone(),
+                unmute,
+                false,
+            )
+            .map(|result| (format!("{} was successfully unmuted", username), result));
+
+        edit_setting_feedback(server, client, edit, || {
+            format!("{} was already unmuted", username)
+        })
+    } else {
+        Err(action.help_string())
+    }
+}
+
 fn handle_server_physics(
     server: &mut Server,
     client: EcsEntity,
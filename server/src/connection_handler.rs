@@ -99,13 +99,16 @@ impl ConnectionHandler {
 
         let reliable = Promises::ORDERED | Promises::CONSISTENCY;
         let reliablec = reliable | Promises::COMPRESSED;
+        // carries the auth token
+        let secure = reliablec | Promises::ENCRYPTED;
 
         let general_stream = participant.open(3, reliablec, 500).await?;
         let ping_stream = participant.open(2, reliable, 500).await?;
-        let mut register_stream = participant.open(3, reliablec, 500).await?;
+        let mut register_stream = participant.open(3, secure, 500).await?;
         let character_screen_stream = participant.open(3, reliablec, 500).await?;
         let in_game_stream = participant.open(3, reliablec, 100_000).await?;
         let terrain_stream = participant.open(4, reliable, 20_000).await?;
+        let plugin_stream = participant.open(5, reliablec, 20_000).await?;
 
         let server_data = receiver.recv()?;
 
@@ -133,6 +136,7 @@ impl ConnectionHandler {
             character_screen_stream,
             in_game_stream,
             terrain_stream,
+            plugin_stream,
         );
 
         client_sender.send(client)?;
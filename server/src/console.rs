@@ -0,0 +1,355 @@
+//! Administration of a running server from outside of the game, e.g. by the
+//! admin API of the server CLI.
+//!
+//! NOTE: Do *not* allow any of this to be used from the game, these functions
+//! act with the permissions of the server host.
+
+use crate::{
+    login_provider::LoginProvider,
+    persistence::{self, ConnectionMode},
+    presence::Presence,
+    settings::{
+        banlist::Role, Ban, BanAction, BanInfo, EditableSetting, SettingError, WhitelistRecord,
+    },
+    Server,
+};
+use authc::Uuid;
+use chrono::Utc;
+use common::{
+    character::CharacterId,
+    cmd::ChatCommand,
+    comp::{self, ChatType},
+    event::{EventBus, ServerEvent},
+};
+use common_net::msg::{DisconnectReason, PresenceKind, ServerGeneral, ServerMsg};
+use serde::Serialize;
+use specs::{Builder, Component, HashMapStorage, Join, WorldExt};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Name recorded as the performer of bans made from the console
+const CONSOLE_USERNAME: &str = "server console";
+
+/// Collects the messages sent to an entity executing commands for the console,
+/// as it doesn't have a client to send them to.
+#[derive(Default)]
+pub struct ConsoleOutput(pub Vec<String>);
+
+impl Component for ConsoleOutput {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl ConsoleOutput {
+    pub(crate) fn push(&mut self, msg: ServerMsg) {
+        if let ServerMsg::General(ServerGeneral::ChatMsg(msg)) = msg {
+            self.0.push(msg.message);
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OnlinePlayer {
+    pub alias: String,
+    pub uuid: Uuid,
+    /// Temporary role of the player, if they have one
+    pub role: Option<String>,
+    /// `None` while the player is in the character selection
+    pub character: Option<CharacterId>,
+}
+
+fn edit_result<S: EditableSetting>(
+    edit: Option<(String, Result<(), SettingError<S>>)>,
+    failure: impl FnOnce() -> String,
+) -> Result<String, String> {
+    let (info, result) = edit.ok_or_else(failure)?;
+    match result {
+        Ok(()) => {
+            info!("{}", info);
+            Ok(info)
+        },
+        Err(SettingError::Io(err)) => {
+            warn!(
+                ?err,
+                "Failed to write settings file to disk, but succeeded in memory (success message: \
+                 {})",
+                info,
+            );
+            Ok(format!(
+                "{} (failed to write the settings file to disk: {})",
+                info, err
+            ))
+        },
+        Err(SettingError::Integrity(err)) => Err(format!(
+            "Encountered an error while validating the request: {:?}",
+            err
+        )),
+    }
+}
+
+impl Server {
+    fn console_uuid(&self, username: &str) -> Result<Uuid, String> {
+        self.state
+            .ecs()
+            .fetch::<LoginProvider>()
+            .username_to_uuid(username)
+            .map_err(|err| {
+                format!(
+                    "Unable to determine UUID for username {:?}: {}",
+                    username, err
+                )
+            })
+    }
+
+    pub fn online_players(&self) -> Vec<OnlinePlayer> {
+        let ecs = self.state.ecs();
+        (
+            &ecs.read_storage::<comp::Player>(),
+            ecs.read_storage::<comp::Admin>().maybe(),
+            ecs.read_storage::<Presence>().maybe(),
+        )
+            .join()
+            .map(|(player, admin, presence)| OnlinePlayer {
+                alias: player.alias.clone(),
+                uuid: player.uuid(),
+                role: admin.map(|admin| format!("{:?}", admin.0)),
+                character: presence.and_then(|presence| match presence.kind {
+                    PresenceKind::Character(id) => Some(id),
+                    PresenceKind::Spectator => None,
+                }),
+            })
+            .collect()
+    }
+
+    /// Disconnect the online player with the given alias
+    pub fn kick(&mut self, alias: &str, reason: &str) -> Result<(), String> {
+        let entity = {
+            let ecs = self.state.ecs();
+            (&ecs.entities(), &ecs.read_storage::<comp::Player>())
+                .join()
+                .find(|(_, player)| player.alias == alias)
+                .map(|(entity, _)| entity)
+                .ok_or_else(|| format!("Player {:?} not found!", alias))?
+        };
+        self.notify_client(
+            entity,
+            ServerGeneral::Disconnect(DisconnectReason::Kicked(reason.to_string())),
+        );
+        self.state
+            .mut_resource::<EventBus<ServerEvent>>()
+            .emit_now(ServerEvent::ClientDisconnect(
+                entity,
+                comp::DisconnectReason::Kicked,
+            ));
+        info!("Kicked {} from the console: {}", alias, reason);
+        Ok(())
+    }
+
+    /// Ban a player, kicking them if they are online. Without a duration, the
+    /// ban is permanent.
+    pub fn ban(
+        &mut self,
+        username: &str,
+        reason: String,
+        duration: Option<Duration>,
+        overwrite: bool,
+    ) -> Result<String, String> {
+        let uuid = self.console_uuid(username)?;
+        let now = Utc::now();
+        let end_date = duration
+            .map(chrono::Duration::from_std)
+            .transpose()
+            .map_err(|err| format!("Error converting to duration: {}", err))?
+            // On overflow, just make the ban infinite.
+            .and_then(|duration| now.checked_add_signed(duration));
+        let ban = Ban {
+            reason: reason.clone(),
+            info: Some(BanInfo {
+                performed_by: Uuid::nil(),
+                performed_by_username: CONSOLE_USERNAME.to_owned(),
+                performed_by_role: Role::Admin,
+            }),
+            end_date,
+        };
+
+        let edit = self
+            .editable_settings_mut()
+            .banlist
+            .ban_action(
+                self.data_dir().as_ref(),
+                now,
+                uuid,
+                username.to_owned(),
+                BanAction::Ban(ban),
+                overwrite,
+            )
+            .map(|result| {
+                (
+                    format!("Added {} to the banlist with reason: {}", username, reason),
+                    result,
+                )
+            });
+        let info = edit_result(edit, || format!("{} is already on the banlist", username))?;
+        // The player may be online with another alias
+        let alias = self
+            .online_players()
+            .into_iter()
+            .find(|player| player.uuid == uuid)
+            .map(|player| player.alias);
+        if let Some(alias) = alias {
+            self.kick(&alias, &reason)?;
+        }
+        Ok(info)
+    }
+
+    pub fn unban(&mut self, username: &str) -> Result<String, String> {
+        let uuid = self.console_uuid(username)?;
+        let unban = BanAction::Unban(BanInfo {
+            performed_by: Uuid::nil(),
+            performed_by_username: CONSOLE_USERNAME.to_owned(),
+            performed_by_role: Role::Admin,
+        });
+        let edit = self
+            .editable_settings_mut()
+            .banlist
+            .ban_action(
+                self.data_dir().as_ref(),
+                Utc::now(),
+                uuid,
+                username.to_owned(),
+                unban,
+                false,
+            )
+            .map(|result| (format!("{} was successfully unbanned", username), result));
+        edit_result(edit, || format!("{} was already unbanned", username))
+    }
+
+    pub fn whitelist_add(&mut self, username: &str) -> Result<String, String> {
+        let uuid = self.console_uuid(username)?;
+        let record = WhitelistRecord {
+            date: Utc::now(),
+            info: None,
+        };
+        let edit =
+            self.editable_settings_mut()
+                .whitelist
+                .edit(self.data_dir().as_ref(), |whitelist| {
+                    if whitelist.insert(uuid, record).is_some() {
+                        None
+                    } else {
+                        Some(format!("added to whitelist: {}", username))
+                    }
+                });
+        edit_result(edit, || format!("already in whitelist: {}!", username))
+    }
+
+    pub fn whitelist_remove(&mut self, username: &str) -> Result<String, String> {
+        let uuid = self.console_uuid(username)?;
+        let edit =
+            self.editable_settings_mut()
+                .whitelist
+                .edit(self.data_dir().as_ref(), |whitelist| {
+                    whitelist
+                        .remove(&uuid)
+                        .map(|_| format!("removed from whitelist: {}", username))
+                });
+        edit_result(edit, || format!("not part of whitelist: {}", username))
+    }
+
+    /// Send a message to the chat of every player
+    pub fn broadcast(&mut self, message: &str) {
+        info!("Broadcast from the console: {}", message);
+        self.notify_players(ServerGeneral::server_msg(ChatType::Meta, message));
+    }
+
+    pub fn set_motd(&mut self, motd: String) -> Result<String, String> {
+        let edit = self.editable_settings_mut().server_description.edit(
+            self.data_dir().as_ref(),
+            |description| {
+                let info = format!("Server description set to {:?}", motd);
+                **description = motd;
+                Some(info)
+            },
+        );
+        edit_result(edit, || unreachable!("edit always returns Some"))
+    }
+
+    /// Take a terrain persistence snapshot and write a copy of the character
+    /// database to the `backups` folder next to it, returning what was backed
+    /// up.
+    ///
+    /// NOTE: This blocks the server for as long as copying the database takes.
+    pub fn backup(&mut self) -> Result<Vec<String>, String> {
+        let mut backups = Vec::new();
+
+        #[cfg(feature = "persistent_world")]
+        if let Some(mut terrain_persistence) =
+            self.state
+                .ecs()
+                .try_fetch_mut::<crate::terrain_persistence::TerrainPersistence>()
+        {
+            match terrain_persistence.snapshot() {
+                Ok(Some(name)) => backups.push(format!("Terrain snapshot {}", name)),
+                Ok(None) => backups.push("No terrain changes since the last snapshot".to_owned()),
+                Err(err) => return Err(format!("Failed to take a terrain snapshot: {}", err)),
+            }
+        }
+
+        let database_settings = self.database_settings.read().unwrap();
+        let backup_dir = database_settings.db_dir.join("backups");
+        std::fs::create_dir_all(&backup_dir)
+            .map_err(|err| format!("Failed to create {}: {}", backup_dir.display(), err))?;
+        let backup_path = backup_dir.join(format!(
+            "db_{}.sqlite",
+            Utc::now().format("%Y-%m-%d_%H-%M-%S")
+        ));
+        let connection =
+            persistence::establish_connection(&database_settings, ConnectionMode::ReadOnly);
+        // Unlike copying the file, this includes the changes still in the
+        // write-ahead log and can't observe a half-written transaction.
+        connection
+            .execute("VACUUM INTO ?1", &[&*backup_path.to_string_lossy()])
+            .map_err(|err| format!("Failed to back up the character database: {}", err))?;
+        info!(
+            "Backed up the character database to {}",
+            backup_path.display()
+        );
+        backups.push(format!("Character database {}", backup_path.display()));
+
+        Ok(backups)
+    }
+
+    /// Execute a chat command with the permissions of an administrator,
+    /// returning the messages it produced.
+    ///
+    /// Commands which act on the player executing them fail, as the console
+    /// isn't an entity of the world.
+    pub fn execute_console_command(
+        &mut self,
+        name: &str,
+        args: Vec<String>,
+    ) -> Result<Vec<String>, String> {
+        let command = name
+            .parse::<ChatCommand>()
+            .map_err(|_| format!("Unknown command '/{}'", name))?;
+        let entity = self
+            .state
+            .ecs_mut()
+            .create_entity()
+            .with(comp::Admin(comp::AdminRole::Admin))
+            .with(ConsoleOutput::default())
+            .build();
+        info!("Executing '/{} {}' from the console", name, args.join(" "));
+        crate::cmd::ChatCommandExt::execute(&command, self, entity, args);
+        let output = self
+            .state
+            .ecs()
+            .write_storage::<ConsoleOutput>()
+            .remove(entity)
+            .map(|output| output.0)
+            .unwrap_or_default();
+        if let Err(err) = self.state.ecs_mut().delete_entity(entity) {
+            warn!(?err, "Failed to delete the console command entity");
+        }
+        Ok(output)
+    }
+}
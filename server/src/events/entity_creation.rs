@@ -29,6 +29,9 @@ pub fn handle_initialize_character(
     character_id: CharacterId,
 ) {
     server.state.initialize_character_data(entity, character_id);
+
+    #[cfg(feature = "plugins")]
+    super::plugin::handle_character_select(server, entity, character_id);
 }
 
 #[allow(clippy::type_complexity)]
@@ -5,6 +5,7 @@ use crate::{
         skills::SkillGroupKind,
         BuffKind, BuffSource, PhysicsState,
     },
+    land_claims::{Editor, LandClaims},
     rtsim::RtSim,
     sys::terrain::SAFE_ZONE_RADIUS,
     Server, SpawnPoint, StateExt,
@@ -15,7 +16,7 @@ use common::{
     comp::{
         self, aura, buff,
         chat::{KillSource, KillType},
-        inventory::item::MaterialStatManifest,
+        inventory::item::{tool::AbilityMap, MaterialStatManifest},
         object, Alignment, Auras, Body, CharacterState, Energy, Group, Health, HealthChange,
         Inventory, Player, Poise, Pos, SkillSet, Stats,
     },
@@ -29,7 +30,10 @@ use common::{
     vol::ReadVol,
     Damage, DamageKind, DamageSource, Explosion, GroupTarget, RadiusEffect,
 };
-use common_net::{msg::ServerGeneral, sync::WorldSyncExt};
+use common_net::{
+    msg::{Notification, ServerGeneral},
+    sync::WorldSyncExt,
+};
 use common_state::BlockChange;
 use comp::chat::GenericChatMsg;
 use hashbrown::HashSet;
@@ -61,6 +65,10 @@ pub fn handle_poise(server: &Server, entity: EcsEntity, change: f32, knockback_d
 }
 
 pub fn handle_health_change(server: &Server, entity: EcsEntity, change: HealthChange) {
+    #[cfg(feature = "plugins")]
+    if change.amount < 0.0 && super::plugin::handle_entity_damage(server, entity, &change) {
+        return;
+    }
     let ecs = &server.state.ecs();
     if let Some(mut health) = ecs.write_storage::<Health>().get_mut(entity) {
         health.change_by(change);
@@ -115,14 +123,17 @@ pub fn handle_knockback(server: &Server, entity: EcsEntity, impulse: Vec3<f32>)
 // loop would currently be very inefficient since it has to rescan every entity
 // on the server again.
 pub fn handle_destroy(server: &mut Server, entity: EcsEntity, last_change: HealthChange) {
-    let state = server.state_mut();
-
     // TODO: Investigate duplicate `Destroy` events (but don't remove this).
     // If the entity was already deleted, it can't be destroyed again.
-    if !state.ecs().is_alive(entity) {
+    if !server.state.ecs().is_alive(entity) {
         return;
     }
 
+    #[cfg(feature = "plugins")]
+    super::plugin::handle_entity_death(server, entity, &last_change);
+
+    let state = server.state_mut();
+
     let get_attacker_name = |cause_of_death: KillType, by: Uid| -> KillSource {
         // Get attacker entity
         if let Some(char_entity) = state.ecs().entity_from_uid(by.into()) {
@@ -283,6 +294,8 @@ pub fn handle_destroy(server: &mut Server, entity: EcsEntity, last_change: Healt
         let uids = state.ecs().read_storage::<Uid>();
         let mut outcomes = state.ecs().write_resource::<Vec<Outcome>>();
         let inventories = state.ecs().read_storage::<comp::Inventory>();
+        let mut quest_logs = state.ecs().write_storage::<comp::QuestLog>();
+        let clients = state.ecs().read_storage::<Client>();
 
         let destroyed_group = groups.get(entity);
 
@@ -366,6 +379,18 @@ pub fn handle_destroy(server: &mut Server, entity: EcsEntity, last_change: Healt
                     &mut outcomes,
                 );
             }
+
+            // Those awarded EXP are credited with the kill for their quests
+            if let (Some(quest_log), Some(client)) =
+                (quest_logs.get_mut(attacker), clients.get(attacker))
+            {
+                for quest in quest_log.record_kill(entity_body) {
+                    client.send_fallible(ServerGeneral::server_msg(
+                        comp::ChatType::Meta,
+                        quest.progress_message(),
+                    ));
+                }
+            }
         });
     })();
 
@@ -705,9 +730,15 @@ pub fn handle_explosion(server: &Server, pos: Vec3<f32>, explosion: Explosion, o
                         .cast();
                 }
 
+                // Blocks in claims the owner may not change are left alone
+                let editor = Editor::of(ecs, owner_entity);
+                let land_claims = ecs.read_resource::<LandClaims>();
+                let may_change =
+                    |pos: Vec3<i32>| land_claims.may_change(editor, TerrainGrid::chunk_key(pos));
+
                 let terrain = ecs.read_resource::<TerrainGrid>();
                 let mut block_change = ecs.write_resource::<BlockChange>();
-                for block_pos in touched_blocks {
+                for block_pos in touched_blocks.into_iter().filter(|pos| may_change(*pos)) {
                     if let Ok(block) = terrain.get(block_pos) {
                         if !matches!(block.kind(), BlockKind::Lava | BlockKind::GlowingRock) {
                             let diff2 = block_pos.map(|b| b as f32).distance_squared(pos);
@@ -768,7 +799,7 @@ pub fn handle_explosion(server: &Server, pos: Vec3<f32>, explosion: Explosion, o
                             stop
                         })
                         .for_each(|block: &Block, pos| {
-                            if block.explode_power().is_some() {
+                            if block.explode_power().is_some() && may_change(pos) {
                                 block_change.set(pos, block.into_vacant());
                             }
                         })
@@ -844,6 +875,7 @@ pub fn handle_explosion(server: &Server, pos: Vec3<f32>, explosion: Explosion, o
                                     energy: energies.get(entity),
                                     combo: combos.get(entity),
                                     inventory: inventories.get(entity),
+                                    player: players.get(entity),
                                 });
 
                         let target_info = combat::TargetInfo {
@@ -855,6 +887,7 @@ pub fn handle_explosion(server: &Server, pos: Vec3<f32>, explosion: Explosion, o
                             pos: pos_b.0,
                             ori: ori_b_maybe,
                             char_state: char_state_b_maybe,
+                            player: players.get(entity_b),
                         };
 
                         // PvP check
@@ -954,7 +987,19 @@ pub fn handle_bonk(server: &mut Server, pos: Vec3<f32>, owner: Option<Uid>, targ
     } else {
         use common::terrain::SpriteKind;
         let pos = pos.map(|e| e.floor() as i32);
-        if let Some(block) = terrain.get(pos).ok().copied().filter(|b| b.is_bonkable()) {
+        let owner_entity = owner.and_then(|uid| {
+            ecs.read_resource::<UidAllocator>()
+                .retrieve_entity_internal(uid.into())
+        });
+        let may_change = ecs
+            .read_resource::<LandClaims>()
+            .may_change(Editor::of(ecs, owner_entity), TerrainGrid::chunk_key(pos));
+        if let Some(block) = terrain
+            .get(pos)
+            .ok()
+            .copied()
+            .filter(|b| may_change && b.is_bonkable())
+        {
             if let Some(item) = comp::Item::try_reclaim_from_block(block) {
                 if block_change
                     .try_set(pos, block.with_sprite(SpriteKind::Empty))
@@ -1152,6 +1197,23 @@ pub fn handle_parry(server: &Server, entity: EcsEntity, energy_cost: f32) {
     }
 }
 
+pub fn handle_item_wear(server: &Server, entity: EcsEntity, kind: combat::WearKind, amount: u32) {
+    let ecs = &server.state.ecs();
+    if let Some(mut inventory) = ecs.write_storage::<Inventory>().get_mut(entity) {
+        let broken = inventory.wear_equipped(
+            |equip_slot| kind.wears(equip_slot),
+            amount,
+            &ecs.read_resource::<AbilityMap>(),
+            &ecs.read_resource::<MaterialStatManifest>(),
+        );
+        if let Some(client) = ecs.read_storage::<Client>().get(entity) {
+            for name in broken {
+                client.send_fallible(ServerGeneral::Notification(Notification::ItemBroken(name)));
+            }
+        }
+    }
+}
+
 pub fn handle_teleport_to(server: &Server, entity: EcsEntity, target: Uid, max_range: Option<f32>) {
     let ecs = &server.state.ecs();
     let mut positions = ecs.write_storage::<Pos>();
@@ -3,6 +3,8 @@ use common::trade::Good;
 use common_net::msg::{world_msg::EconomyInfo, ServerGeneral};
 use specs::{Entity as EcsEntity, WorldExt};
 use std::collections::HashMap;
+#[cfg(feature = "worldgen")]
+use world::sim2::RuntimeEconomy;
 
 #[cfg(not(feature = "worldgen"))]
 pub fn handle_site_info(server: &Server, entity: EcsEntity, id: u64) {
@@ -28,45 +30,44 @@ pub fn handle_site_info(server: &Server, entity: EcsEntity, id: u64) {
 #[cfg(feature = "worldgen")]
 pub fn handle_site_info(server: &Server, entity: EcsEntity, id: u64) {
     let site_id = server.index.sites.recreate_id(id);
+    let runtime_economy = server.state.ecs().read_resource::<RuntimeEconomy>();
     let info = if let Some(site_id) = site_id {
-        let site = server.index.sites.get(site_id);
+        // Sites without economic simulation keep the economy of worldgen
+        let economy = runtime_economy
+            .economy(id)
+            .unwrap_or(&server.index.sites.get(site_id).economy);
         EconomyInfo {
             id,
-            population: site.economy.pop.floor() as u32,
-            stock: site
-                .economy
+            population: economy.pop.floor() as u32,
+            stock: economy
                 .stocks
                 .iter()
                 .map(|(g, a)| (Good::from(g), *a))
                 .collect(),
-            labor_values: site
-                .economy
+            labor_values: economy
                 .labor_values
                 .iter()
                 .filter_map(|(g, a)| a.map(|a| (Good::from(g), a)))
                 .collect(),
-            values: site
-                .economy
+            values: economy
                 .values
                 .iter()
                 .filter_map(|(g, a)| a.map(|a| (Good::from(g), a)))
                 .collect(),
-            labors: site.economy.labors.iter().map(|(_, a)| (*a)).collect(),
-            last_exports: site
-                .economy
+            labors: economy.labors.iter().map(|(_, a)| (*a)).collect(),
+            last_exports: economy
                 .last_exports
                 .iter()
                 .map(|(g, a)| (Good::from(g), *a))
                 .collect(),
-            resources: site
-                .economy
+            resources: economy
                 .natural_resources
                 .chunks_per_resource
                 .iter()
                 .map(|(g, a)| {
                     (
                         Good::from(g),
-                        ((*a) as f32) * site.economy.natural_resources.average_yield_per_chunk[g],
+                        ((*a) as f32) * economy.natural_resources.average_yield_per_chunk[g],
                     )
                 })
                 .collect(),
@@ -16,7 +16,7 @@ use common::{
     },
     consts::{MAX_MOUNT_RANGE, SOUND_TRAVEL_DIST_PER_VOLUME},
     outcome::Outcome,
-    terrain::{Block, SpriteKind},
+    terrain::{Block, SpriteKind, TerrainGrid},
     uid::Uid,
     vol::ReadVol,
 };
@@ -24,12 +24,13 @@ use common_net::{msg::ServerGeneral, sync::WorldSyncExt};
 
 use crate::{
     client::Client,
+    land_claims::{Editor, LandClaims},
     presence::{Presence, RegionSubscription},
     state_ext::StateExt,
     Server,
 };
 
-use crate::pet::tame_pet;
+use crate::{events::quest::record_quest_talk, pet::tame_pet};
 use hashbrown::{HashMap, HashSet};
 use lazy_static::lazy_static;
 use serde::Deserialize;
@@ -89,9 +90,19 @@ pub fn handle_npc_interaction(server: &mut Server, interactor: EcsEntity, npc_en
     {
         if agent.target.is_none() {
             if let Some(interactor_uid) = state.ecs().uid_from_entity(interactor) {
+                // Quest givers talk about their quests instead
+                let subject = if agent.quests.is_empty() {
+                    Subject::Regular
+                } else {
+                    Subject::Work
+                };
                 agent
                     .inbox
-                    .push_back(AgentEvent::Talk(interactor_uid, Subject::Regular));
+                    .push_back(AgentEvent::Talk(interactor_uid, subject));
+
+                if let Some(npc_stats) = state.ecs().read_storage::<comp::Stats>().get(npc_entity) {
+                    record_quest_talk(state, interactor, &npc_stats.name);
+                }
             }
         }
     }
@@ -321,7 +332,11 @@ pub fn handle_mine_block(
     tool: Option<ToolKind>,
 ) {
     let state = server.state_mut();
-    if state.can_set_block(pos) {
+    let may_change = state.ecs().read_resource::<LandClaims>().may_change(
+        Editor::of(state.ecs(), Some(entity)),
+        TerrainGrid::chunk_key(pos),
+    );
+    if may_change && state.can_set_block(pos) {
         let block = state.terrain().get(pos).ok().copied();
         if let Some(block) = block.filter(|b| b.mine_tool().map_or(false, |t| Some(t) == tool)) {
             // Drop item if one is recoverable from the block
@@ -437,7 +452,11 @@ pub fn handle_sound(server: &mut Server, sound: &Sound) {
 
 pub fn handle_create_sprite(server: &mut Server, pos: Vec3<i32>, sprite: SpriteKind) {
     let state = server.state_mut();
-    if state.can_set_block(pos) {
+    let may_change = state.ecs().read_resource::<LandClaims>().may_change(
+        Editor::of(state.ecs(), Some(entity)),
+        TerrainGrid::chunk_key(pos),
+    );
+    if may_change && state.can_set_block(pos) {
         let block = state.terrain().get(pos).ok().copied();
         if block.map_or(false, |b| (*b).is_air()) {
             let new_block = state
@@ -1,14 +1,17 @@
+use hashbrown::HashSet;
 use rand::Rng;
 use specs::{join::Join, world::WorldExt, Builder, Entity as EcsEntity, WriteStorage};
 use tracing::{debug, error, warn};
 use vek::{Rgb, Vec3};
 
 use common::{
+    character::CharacterId,
     comp::{
         self,
         group::members,
         item::{self, tool::AbilityMap, MaterialStatManifest},
         slot::{self, Slot},
+        storage::{self, StorageContainer, StorageContainers, StorageView},
     },
     consts::MAX_PICKUP_RANGE,
     recipe::{self, default_recipe_book},
@@ -18,11 +21,14 @@ use common::{
     util::find_dist::{self, FindDist},
     vol::ReadVol,
 };
-use common_net::sync::WorldSyncExt;
-use common_state::State;
+use common_net::{msg::PresenceKind, sync::WorldSyncExt};
+use common_state::{BuildAreas, State, TerrainChanges};
 use comp::LightEmitter;
 
-use crate::{client::Client, Server, StateExt};
+use crate::{
+    client::Client, land_claims::LandClaims, presence::Presence, sys::msg::in_game::may_build_at,
+    EditableSettings, Server, StateExt,
+};
 use common::{
     comp::{pet::is_tameable, ChatType, Group},
     event::{EventBus, ServerEvent},
@@ -374,6 +380,18 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
 
                                 Some(comp::InventoryUpdateEvent::Used)
                             },
+                            ItemKind::Utility {
+                                kind: comp::item::Utility::StorageChest,
+                                ..
+                            } => {
+                                if !place_storage(state, entity) {
+                                    inventory.insert_or_stack_at(slot, item).expect(
+                                        "slot was just vacated of item, so it definitely fits \
+                                         there.",
+                                    );
+                                }
+                                Some(comp::InventoryUpdateEvent::Used)
+                            },
                             _ => {
                                 inventory.insert_or_stack_at(slot, item).expect(
                                     "slot was just vacated of item, so it definitely fits there.",
@@ -683,6 +701,111 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
             inventory.swap_equipped_weapons();
             drop(inventories);
         },
+        comp::InventoryManip::OpenStorage(pos) => {
+            drop(inventories);
+            if can_use_storage(state, entity, get_cylinder(state, entity), pos) {
+                state
+                    .ecs()
+                    .write_storage()
+                    .insert(entity, comp::OpenStorage(pos))
+                    .expect("We know entity exists since we got its inventory.");
+                sync_storage_viewers(state, pos);
+            } else {
+                debug!(?pos, "Can't open storage container");
+            }
+        },
+        comp::InventoryManip::StorageSwap(a, b) => {
+            swap_storage_slots(
+                state,
+                entity,
+                get_cylinder(state, entity),
+                inventory,
+                a,
+                b,
+                false,
+            );
+            drop(inventories);
+            state
+                .ecs()
+                .write_storage()
+                .insert(
+                    entity,
+                    comp::InventoryUpdate::new(comp::InventoryUpdateEvent::Swapped),
+                )
+                .expect("We know entity exists since we got its inventory.");
+        },
+        comp::InventoryManip::StorageSplitSwap(a, b) => {
+            swap_storage_slots(
+                state,
+                entity,
+                get_cylinder(state, entity),
+                inventory,
+                a,
+                b,
+                true,
+            );
+            drop(inventories);
+            state
+                .ecs()
+                .write_storage()
+                .insert(
+                    entity,
+                    comp::InventoryUpdate::new(comp::InventoryUpdateEvent::Swapped),
+                )
+                .expect("We know entity exists since we got its inventory.");
+        },
+        comp::InventoryManip::CloseStorage => {
+            drop(inventories);
+            state
+                .ecs()
+                .write_storage::<comp::OpenStorage>()
+                .remove(entity);
+        },
+        comp::InventoryManip::SetStorageAccess(access) => {
+            drop(inventories);
+            if let Some(pos) = owned_open_storage(state, entity, get_cylinder(state, entity)) {
+                if let Some(container) = state
+                    .ecs()
+                    .write_resource::<StorageContainers>()
+                    .get_mut(pos)
+                {
+                    container.access = access;
+                }
+                sync_storage_viewers(state, pos);
+            }
+        },
+        comp::InventoryManip::PickUpStorage => {
+            if let Some(pos) = owned_open_storage(state, entity, get_cylinder(state, entity)) {
+                let mut containers = state.ecs().write_resource::<StorageContainers>();
+                let block = state.terrain().get(pos).ok().copied();
+                if let Some(block) = block.filter(|_| {
+                    containers
+                        .get(pos)
+                        .map_or(false, StorageContainer::is_empty)
+                        && state.can_set_block(pos)
+                }) {
+                    containers.remove(pos);
+                    drop(containers);
+                    state.set_block(pos, block.with_sprite(SpriteKind::Empty));
+
+                    let item =
+                        comp::Item::new_from_asset_expect("common.items.utility.storage_chest");
+                    if let Err(item) = inventory.push(item) {
+                        if let Some(pos) = state.ecs().read_storage::<comp::Pos>().get(entity) {
+                            dropped_items.push((
+                                *pos,
+                                state
+                                    .read_component_copied::<comp::Ori>(entity)
+                                    .unwrap_or_default(),
+                                item,
+                            ));
+                        }
+                    }
+                    sync_storage_viewers(state, pos);
+                }
+            }
+            drop(inventories);
+        },
     }
 
     // Drop items, Debug items should simply disappear when dropped
@@ -782,6 +905,303 @@ pub fn handle_inventory(server: &mut Server, entity: EcsEntity, manip: comp::Inv
     }
 }
 
+/// Places a storage container on the ground in front of the entity, owned by
+/// its character, where it may build. Returns whether it was placed.
+fn place_storage(state: &State, entity: EcsEntity) -> bool {
+    let owner = if let Some(owner) = character_id(state.ecs(), entity) {
+        owner
+    } else {
+        return false;
+    };
+    let pos = if let Some(pos) = state.ecs().read_storage::<comp::Pos>().get(entity) {
+        pos.0
+    } else {
+        return false;
+    };
+    let ori = state
+        .read_component_copied::<comp::Ori>(entity)
+        .unwrap_or_default();
+    let target = (pos + Vec3::from(ori.look_dir().xy()) * 1.5).map(|e| e.floor() as i32);
+
+    // Placing a container is a terrain edit, which needs the same rights as
+    // placing a block
+    let may_build = {
+        let ecs = state.ecs();
+        let players = ecs.read_storage::<comp::Player>();
+        let admins = ecs.read_storage::<comp::Admin>();
+        ecs.read_storage::<comp::CanBuild>()
+            .get(entity)
+            .map_or(false, |can_build| {
+                can_build.enabled
+                    && may_build_at(
+                        target,
+                        can_build,
+                        &players.get(entity),
+                        &ecs.read_resource::<EditableSettings>(),
+                        &ecs.read_resource::<BuildAreas>(),
+                        &ecs.read_resource::<LandClaims>(),
+                        &admins.get(entity),
+                    )
+            })
+    };
+    if !may_build {
+        debug!(?target, "Not allowed to place a storage container there");
+        return false;
+    }
+
+    let mut containers = state.ecs().write_resource::<StorageContainers>();
+    match state.terrain().get(target).ok().copied() {
+        Some(block)
+            if block.is_air()
+                && block.get_sprite() == Some(SpriteKind::Empty)
+                && containers.get(target).is_none()
+                && state.can_set_block(target) =>
+        {
+            state.set_block(target, block.with_sprite(SpriteKind::StorageChest));
+            containers.insert(target, StorageContainer::new(owner));
+            true
+        },
+        _ => {
+            debug!(?target, "Can't place a storage container there");
+            false
+        },
+    }
+}
+
+/// Removes the storage containers whose sprite was destroyed by the terrain
+/// changes of this tick, dropping their contents along with the container so
+/// nothing is left orphaned in the database.
+///
+/// This includes the containers of newly loaded chunks whose sprite couldn't be
+/// restored, because their position is now filled (e.g. when the terrain isn't
+/// persisted or was restored from a snapshot).
+pub fn drop_broken_storage(state: &mut State) {
+    let broken = {
+        let containers = state.ecs().read_resource::<StorageContainers>();
+        let terrain_changes = state.ecs().read_resource::<TerrainChanges>();
+        let terrain = state.terrain();
+        let changed_blocks = terrain_changes
+            .modified_blocks
+            .iter()
+            .filter(|(pos, block)| {
+                block.get_sprite() != Some(SpriteKind::StorageChest)
+                    && containers.get(**pos).is_some()
+            })
+            .map(|(pos, _)| *pos);
+        // Their sprite is restored when their chunk is loaded, unless something
+        // else was built there in the meantime
+        let buried = terrain_changes
+            .new_chunks
+            .iter()
+            .chain(terrain_changes.modified_chunks.iter())
+            .flat_map(|key| containers.in_chunk(*key))
+            .filter(|pos| {
+                terrain.get(*pos).ok().and_then(|block| block.get_sprite())
+                    != Some(SpriteKind::StorageChest)
+            })
+            .collect::<Vec<_>>();
+        if !buried.is_empty() {
+            warn!(
+                ?buried,
+                "Dropping the contents of storage containers whose position is filled now"
+            );
+        }
+        changed_blocks.chain(buried).collect::<HashSet<_>>()
+    };
+
+    for pos in broken {
+        let container = state
+            .ecs()
+            .write_resource::<StorageContainers>()
+            .remove(pos);
+        if let Some(mut container) = container {
+            let items = container
+                .inventory
+                .drain()
+                .chain(std::iter::once(comp::Item::new_from_asset_expect(
+                    "common.items.utility.storage_chest",
+                )))
+                .collect::<Vec<_>>();
+            for item in items {
+                state
+                    .create_object(Default::default(), comp::object::Body::Pouch)
+                    .with(comp::Pos(pos.map(|e| e as f32) + Vec3::new(0.5, 0.5, 0.0)))
+                    .with(item)
+                    .build();
+            }
+        }
+        sync_storage_viewers(state, pos);
+    }
+}
+
+fn character_id(ecs: &specs::World, entity: EcsEntity) -> Option<CharacterId> {
+    ecs.read_storage::<Presence>()
+        .get(entity)
+        .and_then(|presence| match presence.kind {
+            PresenceKind::Character(id) => Some(id),
+            PresenceKind::Spectator => None,
+        })
+}
+
+/// Whether the entity may open the storage container at `pos`, considering the
+/// access the owner granted and whether they are in the same group
+fn can_access_storage(ecs: &specs::World, entity: EcsEntity, pos: Vec3<i32>) -> bool {
+    let containers = ecs.read_resource::<StorageContainers>();
+    let (container, character) = if let (Some(container), Some(character)) =
+        (containers.get(pos), character_id(ecs, entity))
+    {
+        (container, character)
+    } else {
+        return false;
+    };
+
+    let groups = ecs.read_storage::<Group>();
+    let presences = ecs.read_storage::<Presence>();
+    let same_group = groups.get(entity).map_or(false, |group| {
+        (&presences, &groups).join().any(|(presence, owner_group)| {
+            presence.kind == PresenceKind::Character(container.owner) && owner_group == group
+        })
+    });
+
+    container.can_access(character, same_group)
+}
+
+/// Whether the entity is in range of the storage container at `pos` and may
+/// open it
+fn can_use_storage(
+    state: &State,
+    entity: EcsEntity,
+    entity_cylinder: Option<find_dist::Cylinder>,
+    pos: Vec3<i32>,
+) -> bool {
+    let is_storage = state
+        .terrain()
+        .get(pos)
+        .ok()
+        .and_then(|block| block.get_sprite())
+        == Some(SpriteKind::StorageChest);
+
+    is_storage
+        && within_pickup_range(entity_cylinder, || {
+            Some(find_dist::Cube {
+                min: pos.as_(),
+                side_length: 1.0,
+            })
+        })
+        && can_access_storage(state.ecs(), entity, pos)
+}
+
+/// The position of the storage container the entity has open, if it can still
+/// use it. Otherwise the container is closed.
+fn open_storage(
+    state: &State,
+    entity: EcsEntity,
+    entity_cylinder: Option<find_dist::Cylinder>,
+) -> Option<Vec3<i32>> {
+    let pos = state
+        .ecs()
+        .read_storage::<comp::OpenStorage>()
+        .get(entity)?
+        .0;
+    if can_use_storage(state, entity, entity_cylinder, pos) {
+        Some(pos)
+    } else {
+        state
+            .ecs()
+            .write_storage::<comp::OpenStorage>()
+            .remove(entity);
+        if let Some(client) = state.ecs().read_storage::<Client>().get(entity) {
+            client.send_fallible(ServerGeneral::StorageClosed);
+        }
+        None
+    }
+}
+
+/// The position of the storage container the entity has open, if it can use it
+/// and owns it
+fn owned_open_storage(
+    state: &State,
+    entity: EcsEntity,
+    entity_cylinder: Option<find_dist::Cylinder>,
+) -> Option<Vec3<i32>> {
+    let pos = open_storage(state, entity, entity_cylinder)?;
+    let owner = state
+        .ecs()
+        .read_resource::<StorageContainers>()
+        .get(pos)
+        .map(|container| container.owner);
+    if owner.is_some() && owner == character_id(state.ecs(), entity) {
+        Some(pos)
+    } else {
+        None
+    }
+}
+
+fn swap_storage_slots(
+    state: &State,
+    entity: EcsEntity,
+    entity_cylinder: Option<find_dist::Cylinder>,
+    inventory: &mut comp::Inventory,
+    a: comp::StorageSlot,
+    b: comp::StorageSlot,
+    split: bool,
+) {
+    if let Some(pos) = open_storage(state, entity, entity_cylinder) {
+        if let Some(container) = state
+            .ecs()
+            .write_resource::<StorageContainers>()
+            .get_mut(pos)
+        {
+            storage::swap_storage_slots(
+                inventory,
+                &mut container.inventory,
+                a,
+                b,
+                split,
+                &state.ecs().read_resource::<AbilityMap>(),
+                &state.ecs().read_resource::<MaterialStatManifest>(),
+            );
+        }
+        sync_storage_viewers(state, pos);
+    }
+}
+
+/// Sends the contents of the storage container at `pos` to everyone who has it
+/// open, and closes it for those who can't access it anymore
+fn sync_storage_viewers(state: &State, pos: Vec3<i32>) {
+    let ecs = state.ecs();
+    let viewers = (&ecs.entities(), &ecs.read_storage::<comp::OpenStorage>())
+        .join()
+        .filter(|(_, open_storage)| open_storage.0 == pos)
+        .map(|(entity, _)| entity)
+        .collect::<Vec<_>>();
+
+    let clients = ecs.read_storage::<Client>();
+    for viewer in viewers {
+        let containers = ecs.read_resource::<StorageContainers>();
+        let view = containers
+            .get(pos)
+            .filter(|_| can_access_storage(ecs, viewer, pos))
+            .map(|container| StorageView {
+                pos,
+                access: container.access,
+                is_owner: character_id(ecs, viewer) == Some(container.owner),
+                inventory: container.inventory.clone(),
+            });
+        drop(containers);
+        let msg = match view {
+            Some(view) => ServerGeneral::StorageUpdate(view),
+            None => {
+                ecs.write_storage::<comp::OpenStorage>().remove(viewer);
+                ServerGeneral::StorageClosed
+            },
+        };
+        if let Some(client) = clients.get(viewer) {
+            client.send_fallible(msg);
+        }
+    }
+}
+
 fn within_pickup_range<S: FindDist<find_dist::Cylinder>>(
     entity_cylinder: Option<find_dist::Cylinder>,
     shape_fn: impl FnOnce() -> Option<S>,
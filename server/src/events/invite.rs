@@ -20,6 +20,8 @@ use common_state::State;
 use specs::{world::WorldExt, Entity};
 use std::time::{Duration, Instant};
 use tracing::{error, warn};
+#[cfg(feature = "worldgen")]
+use world::sim2::RuntimeEconomy;
 
 /// Time before invite times out
 const INVITE_TIMEOUT_DUR: Duration = Duration::from_secs(31);
@@ -192,7 +194,6 @@ pub fn handle_invite_response(
 }
 
 pub fn handle_invite_accept(server: &mut Server, entity: specs::Entity) {
-    let index = server.index.clone();
     let state = server.state_mut();
     if let Some((inviter, kind)) = get_inviter_and_kind(entity, state) {
         handle_invite_answer(state, inviter, entity, InviteAnswer::Accepted, kind);
@@ -247,20 +248,23 @@ pub fn handle_invite_accept(server: &mut Server, entity: specs::Entity) {
                             .push_back(AgentEvent::TradeAccepted(invitee_uid));
                     }
                     #[cfg(feature = "worldgen")]
-                    let pricing = agents
-                        .get(inviter)
-                        .and_then(|a| {
-                            a.behavior
-                                .trade_site
-                                .and_then(|id| index.get_site_prices(id))
-                        })
-                        .or_else(|| {
-                            agents.get(entity).and_then(|a| {
+                    let pricing = {
+                        let economy = state.ecs().read_resource::<RuntimeEconomy>();
+                        agents
+                            .get(inviter)
+                            .and_then(|a| {
                                 a.behavior
                                     .trade_site
-                                    .and_then(|id| index.get_site_prices(id))
+                                    .and_then(|id| economy.get_site_prices(id))
                             })
-                        });
+                            .or_else(|| {
+                                agents.get(entity).and_then(|a| {
+                                    a.behavior
+                                        .trade_site
+                                        .and_then(|id| economy.get_site_prices(id))
+                                })
+                            })
+                    };
                     #[cfg(not(feature = "worldgen"))]
                     let pricing = None;
 
@@ -8,8 +8,8 @@ use entity_creation::{
 use entity_manipulation::{
     handle_aura, handle_bonk, handle_buff, handle_combo_change, handle_delete, handle_destroy,
     handle_energy_change, handle_entity_attacked_hook, handle_explosion, handle_health_change,
-    handle_knockback, handle_land_on_ground, handle_parry, handle_poise, handle_respawn,
-    handle_teleport_to,
+    handle_item_wear, handle_knockback, handle_land_on_ground, handle_parry, handle_poise,
+    handle_respawn, handle_teleport_to,
 };
 use group_manip::handle_group;
 use information::handle_site_info;
@@ -17,9 +17,11 @@ use interaction::{
     handle_create_sprite, handle_lantern, handle_mine_block, handle_mount, handle_npc_interaction,
     handle_possess, handle_sound, handle_unmount,
 };
+pub use inventory_manip::drop_broken_storage;
 use inventory_manip::handle_inventory;
 use invite::{handle_invite, handle_invite_response};
 use player::{handle_client_disconnect, handle_exit_ingame};
+use quest::handle_quest_talk;
 use specs::{Builder, Entity as EcsEntity, WorldExt};
 use trade::{cancel_trade_for, handle_process_trade_action};
 
@@ -31,6 +33,8 @@ mod interaction;
 mod inventory_manip;
 mod invite;
 mod player;
+#[cfg(feature = "plugins")] mod plugin;
+mod quest;
 mod trade;
 
 pub enum Event {
@@ -54,6 +58,13 @@ impl Server {
         let mut commands = Vec::new();
         let mut chat_messages = Vec::new();
 
+        // Plugin actions are applied before the rest of the events
+        #[cfg(feature = "plugins")]
+        {
+            plugin::handle_plugin_tick(self);
+            plugin::handle_plugin_actions(self);
+        }
+
         let events = self
             .state
             .ecs()
@@ -114,6 +125,7 @@ impl Server {
                 ServerEvent::NpcInteract(interactor, target) => {
                     handle_npc_interaction(self, interactor, target)
                 },
+                ServerEvent::QuestTalk { player, giver } => handle_quest_talk(self, player, giver),
                 ServerEvent::InitiateInvite(interactor, target, kind) => {
                     handle_invite(self, interactor, target, kind)
                 },
@@ -214,6 +226,11 @@ impl Server {
                     entity,
                     energy_cost,
                 } => handle_parry(self, entity, energy_cost),
+                ServerEvent::ItemWear {
+                    entity,
+                    kind,
+                    amount,
+                } => handle_item_wear(self, entity, kind, amount),
                 ServerEvent::RequestSiteInfo { entity, id } => handle_site_info(self, entity, id),
                 ServerEvent::MineBlock { entity, pos, tool } => {
                     handle_mine_block(self, entity, pos, tool)
@@ -245,7 +262,12 @@ impl Server {
         }
 
         for msg in chat_messages {
-            self.state.send_chat(msg);
+            #[cfg(feature = "plugins")]
+            let msg = match plugin::handle_chat_message(self, msg) {
+                Some(msg) => msg,
+                None => continue,
+            };
+            self.send_chat_unless_muted(msg);
         }
 
         frontend_events
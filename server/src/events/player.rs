@@ -164,6 +164,9 @@ pub fn handle_client_disconnect(
         }
     }
 
+    #[cfg(feature = "plugins")]
+    super::plugin::handle_player_leave(server, entity);
+
     let state = server.state_mut();
 
     // Tell other clients to remove from player list
@@ -204,6 +207,7 @@ fn persist_entity(state: &mut State, entity: EcsEntity) -> EcsEntity {
         Some(inventory),
         Some(player_uid),
         Some(player_info),
+        Some(quest_log),
         mut character_updater,
         mut battlemode_buffer,
     ) = (
@@ -212,6 +216,7 @@ fn persist_entity(state: &mut State, entity: EcsEntity) -> EcsEntity {
         state.read_storage::<comp::Inventory>().get(entity),
         state.read_storage::<Uid>().get(entity),
         state.read_storage::<comp::Player>().get(entity),
+        state.read_storage::<comp::QuestLog>().get(entity),
         state.ecs().fetch_mut::<CharacterUpdater>(),
         state.ecs().fetch_mut::<BattleModeBuffer>(),
     ) {
@@ -251,7 +256,13 @@ fn persist_entity(state: &mut State, entity: EcsEntity) -> EcsEntity {
 
                 character_updater.add_pending_logout_update(
                     char_id,
-                    (skill_set.clone(), inventory.clone(), pets, waypoint),
+                    (
+                        skill_set.clone(),
+                        inventory.clone(),
+                        pets,
+                        waypoint,
+                        quest_log.clone(),
+                    ),
                 );
             },
             PresenceKind::Spectator => { /* Do nothing, spectators do not need persisting */ },
@@ -0,0 +1,446 @@
+use crate::{sys::terrain::NpcData, Server, StateExt};
+use common::{
+    assets::AssetExt,
+    character::CharacterId,
+    comp::{
+        self,
+        buff::{Buff, BuffChange, BuffData, BuffSource},
+        inventory::item::{tool::AbilityMap, MaterialStatManifest},
+        ChatType, HealthChange, Item, UnresolvedChatMsg,
+    },
+    event::{EventBus, ServerEvent},
+    generation::{EntityConfig, EntityInfo},
+    resources::Time,
+    terrain::{Block, BlockKind, SpriteKind},
+    uid::Uid,
+};
+use common_net::{msg::ServerGeneral, sync::WorldSyncExt};
+use common_state::plugin::PluginMgr;
+use core::{convert::TryFrom, str::FromStr};
+use plugin_api::{
+    event::{
+        CharacterSelectEvent, ChatMessageEvent, ChatMessageResult, EntityDamageEvent,
+        EntityDamageResult, EntityDeathEvent, Player, PlayerLeaveEvent, TickEvent,
+    },
+    Action, Event,
+};
+use specs::{Builder, Entity as EcsEntity, WorldExt};
+use tracing::{error, warn};
+use vek::*;
+
+/// Minimum time between two `on_tick` events, in seconds
+const PLUGIN_TICK_INTERVAL: f64 = 0.1;
+
+/// Most items that can't stack a plugin may give at once
+const MAX_UNSTACKABLE_GIVE_AMOUNT: u32 = 100;
+
+/// Sends an event to every loaded plugin. Errors are logged and yield no
+/// response, so a failing plugin can't cancel anything.
+pub fn execute_event<T: Event>(server: &Server, event: &T) -> Vec<T::Response> {
+    let ecs = server.state.ecs();
+    match ecs
+        .read_resource::<PluginMgr>()
+        .execute_event_in_ecs(ecs, event)
+    {
+        Ok(responses) => responses,
+        Err(e) => {
+            error!(?e, event = %event.get_event_name(), "Failed to execute plugin event");
+            Vec::new()
+        },
+    }
+}
+
+/// Sends the `on_tick` event to plugins, at most every
+/// [`PLUGIN_TICK_INTERVAL`]
+pub fn handle_plugin_tick(server: &mut Server) {
+    let time = server.state.ecs().read_resource::<Time>().0;
+    let dt = time - server.last_plugin_tick;
+    if dt < PLUGIN_TICK_INTERVAL {
+        return;
+    }
+    server.last_plugin_tick = time;
+    execute_event(server, &TickEvent { time, dt });
+}
+
+pub fn handle_character_select(server: &Server, entity: EcsEntity, character_id: CharacterId) {
+    if let Some(uid) = server.state.read_component_copied::<Uid>(entity) {
+        execute_event(server, &CharacterSelectEvent {
+            player: Player { id: uid },
+            character_id,
+        });
+    }
+}
+
+/// Must be called before the player entity is deleted
+pub fn handle_player_leave(server: &Server, entity: EcsEntity) {
+    let player = server
+        .state
+        .ecs()
+        .read_storage::<comp::Player>()
+        .get(entity)
+        .map(|player| (player.alias.clone(), player.uuid()));
+    if let Some((player_name, uuid)) = player {
+        execute_event(server, &PlayerLeaveEvent {
+            player_name,
+            player_id: *uuid.as_bytes(),
+        });
+    }
+}
+
+/// Returns `true` if a plugin cancelled the damage dealt by `change`
+pub fn handle_entity_damage(server: &Server, entity: EcsEntity, change: &HealthChange) -> bool {
+    let uid = match server.state.read_component_copied::<Uid>(entity) {
+        Some(uid) => uid,
+        None => return false,
+    };
+    execute_event(server, &EntityDamageEvent {
+        entity: uid,
+        attacker: change.by.map(|by| by.uid()),
+        source: change.cause,
+        amount: -change.amount,
+    })
+    .into_iter()
+    .any(|response| response == EntityDamageResult::Cancel)
+}
+
+pub fn handle_entity_death(server: &Server, entity: EcsEntity, last_change: &HealthChange) {
+    if let Some(uid) = server.state.read_component_copied::<Uid>(entity) {
+        execute_event(server, &EntityDeathEvent {
+            entity: uid,
+            killer: last_change.by.map(|by| by.uid()),
+            cause: last_change.cause,
+        });
+    }
+}
+
+/// Lets plugins rewrite or cancel a chat message sent by a player. Returns
+/// `None` if the message must not be sent.
+pub fn handle_chat_message(
+    server: &Server,
+    mut msg: UnresolvedChatMsg,
+) -> Option<UnresolvedChatMsg> {
+    let uid = match msg.uid() {
+        Some(uid) => uid,
+        None => return Some(msg),
+    };
+    let is_player = server
+        .state
+        .ecs()
+        .entity_from_uid(uid.0)
+        .map_or(false, |entity| {
+            server
+                .state
+                .ecs()
+                .read_storage::<comp::Player>()
+                .contains(entity)
+        });
+    if !is_player {
+        return Some(msg);
+    }
+
+    let responses = execute_event(server, &ChatMessageEvent {
+        player: Player { id: uid },
+        message: msg.message.clone(),
+    });
+    for response in responses {
+        match response {
+            ChatMessageResult::Cancel => return None,
+            ChatMessageResult::Rewrite(message) => msg.message = message,
+            ChatMessageResult::None => {},
+        }
+    }
+    Some(msg)
+}
+
+/// Applies every action queued by plugins since the last call, in the order
+/// they were emitted.
+pub fn handle_plugin_actions(server: &mut Server) {
+    let actions = server
+        .state
+        .ecs()
+        .read_resource::<EventBus<Action>>()
+        .recv_all();
+
+    for action in actions {
+        match action {
+            // Those are handled directly by the plugin runtime
+            Action::ServerClose
+            | Action::Print(_)
+            | Action::SetStorageValue { .. }
+            | Action::RemoveStorageValue(_) => {},
+            Action::PlayerSendMessage(uid, msg) => {
+                if let Some(entity) = find_entity(server, uid) {
+                    server.notify_client(entity, ServerGeneral::server_msg(ChatType::Meta, msg));
+                }
+            },
+            Action::KillEntity(uid) => {
+                if let Some(entity) = find_entity(server, uid) {
+                    server
+                        .state
+                        .ecs()
+                        .write_storage::<comp::Health>()
+                        .get_mut(entity)
+                        .map(|mut health| health.kill());
+                }
+            },
+            Action::SpawnEntity { config, pos } => handle_spawn_entity(server, &config, pos),
+            Action::GiveItem {
+                target,
+                item,
+                amount,
+            } => {
+                if let Some(entity) = find_entity(server, target) {
+                    handle_give_item(server, entity, &item, amount);
+                }
+            },
+            Action::TakeItem {
+                target,
+                item,
+                amount,
+            } => {
+                if let Some(entity) = find_entity(server, target) {
+                    handle_take_item(server, entity, &item, amount);
+                }
+            },
+            Action::TeleportEntity(uid, pos) => {
+                if let Some(entity) = find_entity(server, uid) {
+                    let ecs = server.state.ecs();
+                    if let Some(mut current_pos) = ecs.write_storage::<comp::Pos>().get_mut(entity)
+                    {
+                        current_pos.0 = pos;
+                        let _ = ecs
+                            .write_storage::<comp::ForceUpdate>()
+                            .insert(entity, comp::ForceUpdate);
+                    }
+                }
+            },
+            Action::SetBlock {
+                pos,
+                block_kind,
+                color,
+            } => match BlockKind::from_str(&block_kind) {
+                Ok(kind) => set_block(server, pos, Block::new(kind, color)),
+                Err(_) => error!(?block_kind, "Plugin tried to set an invalid block kind"),
+            },
+            Action::SetSprite { pos, sprite } => match SpriteKind::try_from(sprite.as_str()) {
+                Ok(sprite) => {
+                    let block = server
+                        .state
+                        .get_block(pos)
+                        .unwrap_or_else(|| Block::air(SpriteKind::Empty))
+                        .with_sprite(sprite);
+                    set_block(server, pos, block);
+                },
+                Err(_) => error!(?sprite, "Plugin tried to set an invalid sprite kind"),
+            },
+            Action::ApplyBuff {
+                target,
+                kind,
+                strength,
+                duration,
+            } => {
+                if let Some(entity) = find_entity(server, target) {
+                    server
+                        .state
+                        .ecs()
+                        .read_resource::<EventBus<ServerEvent>>()
+                        .emit_now(ServerEvent::Buff {
+                            entity,
+                            buff_change: BuffChange::Add(Buff::new(
+                                kind,
+                                BuffData::new(strength, duration),
+                                Vec::new(),
+                                BuffSource::Unknown,
+                            )),
+                        });
+                }
+            },
+        }
+    }
+}
+
+fn find_entity(server: &Server, uid: Uid) -> Option<EcsEntity> {
+    let entity = server.state.ecs().entity_from_uid(uid.0);
+    if entity.is_none() {
+        warn!(?uid, "Plugin action targets an entity that doesn't exist");
+    }
+    entity
+}
+
+fn set_block(server: &mut Server, pos: Vec3<i32>, block: Block) {
+    #[cfg(feature = "persistent_world")]
+    let old_block = server.state.get_block(pos).unwrap_or_else(Block::empty);
+    server.state.set_block(pos, block);
+    #[cfg(feature = "persistent_world")]
+    if let Some(terrain_persistence) = server
+        .state
+        .ecs()
+        .try_fetch_mut::<crate::TerrainPersistence>()
+        .as_mut()
+    {
+        terrain_persistence.set_block(pos, old_block, block, None);
+    }
+}
+
+fn handle_spawn_entity(server: &mut Server, config: &str, pos: Vec3<f32>) {
+    let entity_config = match EntityConfig::load_cloned(config) {
+        Ok(entity_config) => entity_config,
+        Err(e) => {
+            error!(
+                ?e,
+                ?config,
+                "Plugin tried to spawn an entity with an invalid config"
+            );
+            return;
+        },
+    };
+    let entity_info = EntityInfo::at(pos).with_entity_config(entity_config, Some(config));
+
+    match NpcData::from_entity_info(entity_info, &mut rand::thread_rng()) {
+        NpcData::Waypoint(pos) => {
+            server
+                .state
+                .ecs()
+                .read_resource::<EventBus<ServerEvent>>()
+                .emit_now(ServerEvent::CreateWaypoint(pos));
+        },
+        NpcData::Data {
+            pos,
+            stats,
+            skill_set,
+            health,
+            poise,
+            loadout,
+            agent,
+            body,
+            alignment,
+            scale,
+            loot,
+        } => {
+            server
+                .state
+                .ecs()
+                .read_resource::<EventBus<ServerEvent>>()
+                .emit_now(ServerEvent::CreateNpc {
+                    pos,
+                    stats,
+                    skill_set,
+                    health,
+                    poise,
+                    loadout,
+                    agent,
+                    body,
+                    alignment,
+                    scale,
+                    anchor: None,
+                    loot,
+                    rtsim_entity: None,
+                    projectile: None,
+                });
+        },
+    }
+}
+
+fn handle_give_item(server: &mut Server, entity: EcsEntity, item_name: &str, amount: u32) {
+    let mut item = match Item::new_from_asset(item_name) {
+        Ok(item) => item,
+        Err(e) => {
+            error!(?e, ?item_name, "Plugin tried to give an invalid item");
+            return;
+        },
+    };
+
+    // Items that can't stack are given one by one, so their amount is capped
+    let items = if item.set_amount(amount).is_ok() {
+        vec![item]
+    } else {
+        if amount > MAX_UNSTACKABLE_GIVE_AMOUNT {
+            warn!(
+                ?item_name,
+                ?amount,
+                "Plugin tried to give too many unstackable items, giving {}",
+                MAX_UNSTACKABLE_GIVE_AMOUNT
+            );
+        }
+        let ability_map = server.state.ecs().read_resource::<AbilityMap>();
+        let msm = server.state.ecs().read_resource::<MaterialStatManifest>();
+        (0..amount.min(MAX_UNSTACKABLE_GIVE_AMOUNT))
+            .map(|_| item.duplicate(&ability_map, &msm))
+            .collect()
+    };
+
+    let mut dropped_items = Vec::new();
+    if let Some(mut inventory) = server
+        .state
+        .ecs()
+        .write_storage::<comp::Inventory>()
+        .get_mut(entity)
+    {
+        for item in items {
+            if let Err(item) = inventory.push(item) {
+                dropped_items.push(item);
+            }
+        }
+    } else {
+        return;
+    }
+
+    let _ = server.state.ecs().write_storage().insert(
+        entity,
+        comp::InventoryUpdate::new(comp::InventoryUpdateEvent::Given),
+    );
+
+    // Items which didn't fit in the inventory are dropped next to the entity
+    if let Some(pos) = server.state.read_component_copied::<comp::Pos>(entity) {
+        for item in dropped_items {
+            server
+                .state
+                .create_object(Default::default(), comp::object::Body::Pouch)
+                .with(comp::Pos(pos.0 + Vec3::unit_z() * 0.5))
+                .with(item)
+                .with(comp::Vel(Vec3::zero()))
+                .build();
+        }
+    }
+}
+
+fn handle_take_item(server: &mut Server, entity: EcsEntity, item_name: &str, amount: u32) {
+    let ecs = server.state.ecs();
+    let mut inventories = ecs.write_storage::<comp::Inventory>();
+    let mut inventory = match inventories.get_mut(entity) {
+        Some(inventory) => inventory,
+        None => return,
+    };
+
+    let slots = inventory
+        .slots_with_id()
+        .filter(|(_, slot)| {
+            slot.as_ref()
+                .map_or(false, |item| item.item_definition_id() == item_name)
+        })
+        .map(|(slot, _)| slot)
+        .collect::<Vec<_>>();
+
+    let mut remaining = amount;
+    for slot in slots {
+        if remaining == 0 {
+            break;
+        }
+        if let Some(Some(item)) = inventory.slot_mut(slot) {
+            if item.amount() > remaining {
+                if item.decrease_amount(remaining).is_ok() {
+                    remaining = 0;
+                }
+            } else {
+                remaining -= item.amount();
+                inventory.remove(slot);
+            }
+        }
+    }
+    drop(inventories);
+
+    let _ = ecs.write_storage().insert(
+        entity,
+        comp::InventoryUpdate::new(comp::InventoryUpdateEvent::Used),
+    );
+}
@@ -0,0 +1,219 @@
+use crate::{client::Client, state_ext::StateExt, Server};
+use common::{
+    assets::AssetExt,
+    comp::{
+        self,
+        dialogue::{MoodContext, MoodState},
+        quest::{Quest, QuestSite},
+        ChatType, Inventory, Item, QuestLog, UnresolvedChatMsg,
+    },
+    quest::{Objective, QuestSpec, SiteKind},
+    resources::Time,
+    rtsim::{Memory, MemoryItem, RtSimEntity, RtSimEvent},
+    uid::Uid,
+};
+use common_net::msg::{world_msg, ServerGeneral};
+use common_state::State;
+use specs::{Builder, Entity as EcsEntity, WorldExt};
+use std::cmp::Ordering;
+use vek::*;
+
+/// Sites closer than this to the quest giver are the one it lives in, and
+/// aren't sent to by its quests
+const HOME_SITE_DIST: f32 = 256.0;
+
+/// Records that the player talked to an NPC, for `Talk` objectives
+pub fn record_quest_talk(state: &State, player: EcsEntity, npc_name: &str) {
+    let ecs = state.ecs();
+    if let (Some(quest_log), Some(client)) = (
+        ecs.write_storage::<QuestLog>().get_mut(player),
+        ecs.read_storage::<Client>().get(player),
+    ) {
+        for quest in quest_log.record_talk(npc_name) {
+            client.send_fallible(ServerGeneral::server_msg(
+                ChatType::Meta,
+                quest.progress_message(),
+            ));
+        }
+    }
+}
+
+/// The player asked a quest giver for work: completed quests of the giver
+/// are turned in, or else a new one is started.
+pub fn handle_quest_talk(server: &mut Server, player: EcsEntity, giver: EcsEntity) {
+    let dropped = quest_talk(server, player, giver);
+    if dropped.is_empty() {
+        return;
+    }
+    let state = server.state_mut();
+    let player_pos = match state.ecs().read_storage::<comp::Pos>().get(player) {
+        Some(pos) => pos.0,
+        None => return,
+    };
+    for item in dropped {
+        state
+            .create_object(Default::default(), comp::object::Body::Pouch)
+            .with(comp::Pos(player_pos + Vec3::unit_z()))
+            .with(item)
+            .with(comp::Vel(Vec3::zero()))
+            .build();
+    }
+}
+
+/// Returns the rewards that didn't fit in the inventory of the player
+fn quest_talk(server: &Server, player: EcsEntity, giver: EcsEntity) -> Vec<Item> {
+    let state = &server.state;
+    let ecs = state.ecs();
+    let (quests, giver_pos, giver_uid, player_uid, player_name) = match (
+        ecs.read_storage::<comp::Agent>().get(giver),
+        ecs.read_storage::<comp::Pos>().get(giver),
+        ecs.read_storage::<Uid>().get(giver),
+        ecs.read_storage::<Uid>().get(player),
+        ecs.read_storage::<comp::Stats>().get(player),
+    ) {
+        (Some(agent), Some(giver_pos), Some(giver_uid), Some(player_uid), Some(stats)) => (
+            agent.quests.clone(),
+            giver_pos.0,
+            *giver_uid,
+            *player_uid,
+            stats.name.clone(),
+        ),
+        _ => return Vec::new(),
+    };
+    let tell = |msg: String| {
+        state.send_chat(UnresolvedChatMsg::npc_tell(giver_uid, player_uid, msg));
+    };
+    let notify = |msg: String| {
+        if let Some(client) = ecs.read_storage::<Client>().get(player) {
+            client.send_fallible(ServerGeneral::server_msg(ChatType::Meta, msg));
+        }
+    };
+
+    let mut quest_logs = ecs.write_storage::<QuestLog>();
+    let quest_log = match quest_logs.get_mut(player) {
+        Some(quest_log) => quest_log,
+        None => return Vec::new(),
+    };
+
+    let active = quests
+        .iter()
+        .filter(|spec| quest_log.get(spec).map_or(false, |quest| !quest.completed))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !active.is_empty() {
+        let mut inventories = ecs.write_storage::<Inventory>();
+        if let Some(inventory) = inventories.get_mut(player) {
+            if let Some(spec_id) = active
+                .iter()
+                .find(|spec| quest_log.turn_in(spec, inventory))
+            {
+                let spec = QuestSpec::load_expect(spec_id).read();
+                let dropped = spec
+                    .rewards
+                    .iter()
+                    .filter_map(|reward| reward.to_item())
+                    .filter_map(|item| inventory.push(item).err())
+                    .collect::<Vec<_>>();
+
+                tell("npc.speech.quest_reward".to_owned());
+                notify(format!("Quest completed: {}", spec.name));
+                if !dropped.is_empty() {
+                    notify("Your inventory is full, the rest of the reward was dropped".to_owned());
+                }
+
+                // The giver is in a good mood for a while, and tells others about it
+                if ecs.read_storage::<RtSimEntity>().get(giver).is_some() {
+                    if let Some(agent) = ecs.write_storage::<comp::Agent>().get_mut(giver) {
+                        agent
+                            .rtsim_controller
+                            .events
+                            .push(RtSimEvent::SetMood(Memory {
+                                item: MemoryItem::Mood {
+                                    state: MoodState::Good(MoodContext::QuestSucceeded {
+                                        hero: player_name,
+                                        quest_desc: spec.name.clone(),
+                                    }),
+                                },
+                                time_to_forget: ecs.read_resource::<Time>().0 + 21200.0,
+                            }));
+                    }
+                }
+
+                return dropped;
+            }
+        }
+        // The quests aren't done yet, remind the player of them
+        for spec in &active {
+            if let Some(quest) = quest_log.get(spec) {
+                notify(quest.progress_message());
+            }
+        }
+        tell("npc.speech.quest_unfinished".to_owned());
+        return Vec::new();
+    }
+
+    match quests.iter().find(|spec| quest_log.can_start(spec)) {
+        Some(spec_id) => {
+            let spec = QuestSpec::load_expect(spec_id).read().clone();
+            let sites = spec
+                .objectives
+                .iter()
+                .map(|objective| match objective {
+                    Objective::Reach(kind) => nearest_site(&server.map.sites, *kind, giver_pos),
+                    _ => None,
+                })
+                .collect();
+            let quest = Quest::new(spec_id.clone(), sites);
+
+            tell(spec.description);
+            notify(format!(
+                "Quest started: {}\n{}",
+                spec.name,
+                quest.describe()
+            ));
+            quest_log.start(quest);
+        },
+        None => tell("npc.speech.villager".to_owned()),
+    }
+    Vec::new()
+}
+
+/// Finds the site of the given kind closest to the quest giver, other than
+/// the one it lives in if possible
+fn nearest_site(
+    sites: &[world_msg::SiteInfo],
+    kind: SiteKind,
+    giver_pos: Vec3<f32>,
+) -> Option<QuestSite> {
+    let mut sites_of_kind = sites
+        .iter()
+        .filter(|site| {
+            matches!(
+                (kind, &site.kind),
+                (SiteKind::Town, world_msg::SiteKind::Town)
+                    | (SiteKind::Dungeon, world_msg::SiteKind::Dungeon { .. })
+                    | (SiteKind::Castle, world_msg::SiteKind::Castle)
+                    | (SiteKind::Cave, world_msg::SiteKind::Cave)
+                    | (SiteKind::Tree, world_msg::SiteKind::Tree)
+            )
+        })
+        .map(|site| {
+            (
+                site,
+                site.wpos.as_::<f32>().distance_squared(giver_pos.xy()),
+            )
+        })
+        .collect::<Vec<_>>();
+    sites_of_kind.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let (site, _) = sites_of_kind
+        .iter()
+        .find(|(_, dist_sqr)| *dist_sqr > HOME_SITE_DIST.powi(2))
+        .or_else(|| sites_of_kind.first())?;
+    Some(QuestSite {
+        name: site
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("the nearby {:?}", kind).to_lowercase()),
+        wpos: site.wpos,
+    })
+}
@@ -4,10 +4,11 @@ use common::{
         agent::{Agent, AgentEvent},
         inventory::{
             item::{tool::AbilityMap, MaterialStatManifest},
+            trade_pricing::TradePricing,
             Inventory,
         },
     },
-    trade::{PendingTrade, ReducedInventory, TradeAction, TradeId, TradeResult, Trades},
+    trade::{Good, PendingTrade, ReducedInventory, TradeAction, TradeId, TradeResult, Trades},
 };
 use common_net::{
     msg::ServerGeneral,
@@ -17,7 +18,7 @@ use hashbrown::{hash_map::Entry, HashMap};
 use specs::{world::WorldExt, Entity as EcsEntity};
 use std::cmp::Ordering;
 use tracing::{error, trace};
-use world::IndexOwned;
+use world::sim2::RuntimeEconomy;
 
 fn notify_agent_simple(
     mut agents: specs::WriteStorage<Agent>,
@@ -31,13 +32,13 @@ fn notify_agent_simple(
 
 fn notify_agent_prices(
     mut agents: specs::WriteStorage<Agent>,
-    index: &IndexOwned,
+    economy: &RuntimeEconomy,
     entity: EcsEntity,
     event: AgentEvent,
 ) {
     if let Some((Some(site_id), agent)) = agents.get_mut(entity).map(|a| (a.behavior.trade_site, a))
     {
-        let prices = index.get_site_prices(site_id);
+        let prices = economy.get_site_prices(site_id);
         if let AgentEvent::UpdatePendingTrade(boxval) = event {
             // Box<(tid, pend, _, inventories)>) = event {
             agent
@@ -91,7 +92,13 @@ pub fn handle_process_trade_action(
             if let Entry::Occupied(entry) = trades.trades.entry(trade_id) {
                 let parties = entry.get().parties;
                 if entry.get().should_commit() {
+                    #[cfg(feature = "worldgen")]
+                    let goods = traded_goods(server.state.ecs(), entry.get());
                     let result = commit_trade(server.state.ecs(), entry.get());
+                    #[cfg(feature = "worldgen")]
+                    if let TradeResult::Completed = result {
+                        record_site_trades(server.state.ecs(), &parties, goods);
+                    }
                     entry.remove();
                     for party in parties.iter() {
                         if let Some(e) = server.state.ecs().entity_from_uid(party.0) {
@@ -109,6 +116,8 @@ pub fn handle_process_trade_action(
                     let mut inventories: [Option<ReducedInventory>; 2] = [None, None];
                     let mut prices = None;
                     let agents = server.state.ecs().read_storage::<Agent>();
+                    #[cfg(feature = "worldgen")]
+                    let economy = server.state.ecs().read_resource::<RuntimeEconomy>();
                     // sadly there is no map and collect on arrays
                     for i in 0..2 {
                         // parties.len()) {
@@ -128,7 +137,7 @@ pub fn handle_process_trade_action(
                                     agents
                                         .get(e)
                                         .and_then(|a| a.behavior.trade_site)
-                                        .and_then(|id| server.index.get_site_prices(id))
+                                        .and_then(|id| economy.get_site_prices(id))
                                 });
                             }
                         }
@@ -147,7 +156,7 @@ pub fn handle_process_trade_action(
                             #[cfg(feature = "worldgen")]
                             notify_agent_prices(
                                 server.state.ecs().write_storage::<Agent>(),
-                                &server.index,
+                                &economy,
                                 e,
                                 AgentEvent::UpdatePendingTrade(Box::new((
                                     trade_id,
@@ -190,6 +199,47 @@ pub fn cancel_trade_for(server: &mut Server, entity: EcsEntity) {
     }
 }
 
+/// The goods each party of a trade gives away, priced like the items in
+/// [`common::trade::SitePrices::balance`]
+#[cfg(feature = "worldgen")]
+fn traded_goods(ecs: &specs::World, trade: &PendingTrade) -> [Vec<(Good, f32)>; 2] {
+    let inventories = ecs.read_storage::<Inventory>();
+    let mut goods = [Vec::new(), Vec::new()];
+    for (who, goods) in goods.iter_mut().enumerate() {
+        let inventory = ecs
+            .entity_from_uid(trade.parties[who].0)
+            .and_then(|entity| inventories.get(entity));
+        if let Some(inventory) = inventory {
+            for (slot, quantity) in trade.offers[who].iter() {
+                if let Some(item) = inventory.get(*slot) {
+                    let (good, factor) = TradePricing::get_material(item.item_definition_id());
+                    goods.push((good, factor * *quantity as f32));
+                }
+            }
+        }
+    }
+    goods
+}
+
+/// Feed a completed trade back into the economy of the sites the parties trade
+/// for, so that prices react to what players buy and sell
+#[cfg(feature = "worldgen")]
+fn record_site_trades(ecs: &specs::World, parties: &[Uid; 2], goods: [Vec<(Good, f32)>; 2]) {
+    let agents = ecs.read_storage::<Agent>();
+    let mut economy = ecs.write_resource::<RuntimeEconomy>();
+    for (who, party) in parties.iter().enumerate() {
+        let site = ecs
+            .entity_from_uid(party.0)
+            .and_then(|entity| agents.get(entity))
+            .and_then(|agent| agent.behavior.trade_site);
+        if let Some(site) = site {
+            let received = goods[1 - who].iter().copied();
+            let given = goods[who].iter().map(|(good, amount)| (*good, -amount));
+            economy.record_trade(site, received.chain(given));
+        }
+    }
+}
+
 /// Commit a trade that both parties have agreed to, modifying their respective
 /// inventories
 fn commit_trade(ecs: &specs::World, trade: &PendingTrade) -> TradeResult {
@@ -0,0 +1,296 @@
+use authc::Uuid;
+use common::comp::{Admin, AdminRole, Player};
+use hashbrown::{HashMap, HashSet};
+use specs::{Entity as EcsEntity, WorldExt};
+use vek::*;
+
+/// A change to the land claims to persist
+#[derive(Clone, Debug, PartialEq)]
+pub enum LandClaimChange {
+    /// The chunk changed owner, or was abandoned if `owner` is `None`
+    Claim {
+        chunk: Vec2<i32>,
+        owner: Option<Uuid>,
+    },
+    /// `owner` started or stopped trusting `trusted`
+    Trust {
+        owner: Uuid,
+        trusted: Uuid,
+        trust: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClaimError {
+    /// The chunk is already claimed by the given player
+    AlreadyClaimed(Uuid),
+    NotClaimed,
+    NotOwner,
+    /// The player already owns the maximum number of chunks
+    LimitReached(u32),
+}
+
+/// Who changes blocks, as far as land claims are concerned
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Editor {
+    /// Admins may change blocks in every claim
+    Admin,
+    Player(Uuid),
+    /// Changes no player caused, such as the explosions of NPCs
+    Other,
+}
+
+impl Editor {
+    pub fn new(player: Option<&Player>, admin: Option<&Admin>) -> Self {
+        match (player, admin) {
+            (_, Some(Admin(AdminRole::Admin))) => Editor::Admin,
+            (Some(player), _) => Editor::Player(player.uuid()),
+            (None, _) => Editor::Other,
+        }
+    }
+
+    /// The editor of the blocks changed by an entity, if any
+    pub fn of(ecs: &specs::World, entity: Option<EcsEntity>) -> Self {
+        entity.map_or(Editor::Other, |entity| {
+            Self::new(
+                ecs.read_storage::<Player>().get(entity),
+                ecs.read_storage::<Admin>().get(entity),
+            )
+        })
+    }
+}
+
+/// Chunks claimed by players, in which only their owner and the players they
+/// trust may build.
+#[derive(Default)]
+pub struct LandClaims {
+    claims: HashMap<Vec2<i32>, Uuid>,
+    /// Players each owner trusts to build in all of their claims
+    trusted: HashMap<Uuid, HashSet<Uuid>>,
+    /// Changes made since the last call to `take_changes`
+    changes: Vec<LandClaimChange>,
+}
+
+impl LandClaims {
+    /// Adds claims and trusts loaded from the database, without recording them
+    /// as changes
+    pub fn load(
+        &mut self,
+        claims: impl IntoIterator<Item = (Vec2<i32>, Uuid)>,
+        trusts: impl IntoIterator<Item = (Uuid, Uuid)>,
+    ) {
+        self.claims.extend(claims);
+        for (owner, trusted) in trusts {
+            self.trusted.entry(owner).or_default().insert(trusted);
+        }
+    }
+
+    pub fn owner(&self, chunk: Vec2<i32>) -> Option<Uuid> { self.claims.get(&chunk).copied() }
+
+    /// The chunks claimed by a player, sorted
+    pub fn claims_of(&self, owner: Uuid) -> Vec<Vec2<i32>> {
+        let mut chunks = self
+            .claims
+            .iter()
+            .filter(|(_, o)| **o == owner)
+            .map(|(chunk, _)| *chunk)
+            .collect::<Vec<_>>();
+        chunks.sort_by_key(|chunk| (chunk.x, chunk.y));
+        chunks
+    }
+
+    pub fn trusted_by(&self, owner: Uuid) -> impl Iterator<Item = Uuid> + '_ {
+        self.trusted.get(&owner).into_iter().flatten().copied()
+    }
+
+    /// Whether a player may build in a claimed chunk, or `None` if nobody
+    /// claimed it
+    pub fn may_build(&self, player: Uuid, chunk: Vec2<i32>) -> Option<bool> {
+        self.owner(chunk).map(|owner| {
+            owner == player
+                || self
+                    .trusted
+                    .get(&owner)
+                    .map_or(false, |trusted| trusted.contains(&player))
+        })
+    }
+
+    /// Whether the blocks of a chunk may be changed, by mining, explosions or
+    /// building. Claimed chunks only allow their owner, the players they
+    /// trust and admins.
+    pub fn may_change(&self, editor: Editor, chunk: Vec2<i32>) -> bool {
+        match editor {
+            Editor::Admin => true,
+            Editor::Player(player) => self.may_build(player, chunk) != Some(false),
+            Editor::Other => self.owner(chunk).is_none(),
+        }
+    }
+
+    /// Whether there is any claimed chunk a player may build in
+    pub fn may_build_anywhere(&self, player: Uuid) -> bool {
+        self.claims.values().any(|owner| {
+            *owner == player
+                || self
+                    .trusted
+                    .get(owner)
+                    .map_or(false, |trusted| trusted.contains(&player))
+        })
+    }
+
+    pub fn claim(&mut self, player: Uuid, chunk: Vec2<i32>, limit: u32) -> Result<(), ClaimError> {
+        if let Some(owner) = self.owner(chunk) {
+            return Err(ClaimError::AlreadyClaimed(owner));
+        }
+        self.check_limit(player, limit)?;
+        self.set_owner(chunk, Some(player));
+        Ok(())
+    }
+
+    pub fn abandon(&mut self, player: Uuid, chunk: Vec2<i32>) -> Result<(), ClaimError> {
+        self.check_owner(player, chunk)?;
+        self.set_owner(chunk, None);
+        Ok(())
+    }
+
+    /// Gives a claim of `player` to `new_owner`, who must stay within the limit
+    pub fn transfer(
+        &mut self,
+        player: Uuid,
+        chunk: Vec2<i32>,
+        new_owner: Uuid,
+        limit: u32,
+    ) -> Result<(), ClaimError> {
+        self.check_owner(player, chunk)?;
+        if new_owner != player {
+            self.check_limit(new_owner, limit)?;
+            self.set_owner(chunk, Some(new_owner));
+        }
+        Ok(())
+    }
+
+    /// Returns `false` if the player was already trusted
+    pub fn trust(&mut self, owner: Uuid, trusted: Uuid) -> bool {
+        let newly = self.trusted.entry(owner).or_default().insert(trusted);
+        if newly {
+            self.changes.push(LandClaimChange::Trust {
+                owner,
+                trusted,
+                trust: true,
+            });
+        }
+        newly
+    }
+
+    /// Returns `false` if the player wasn't trusted
+    pub fn untrust(&mut self, owner: Uuid, trusted: Uuid) -> bool {
+        let was_trusted = self
+            .trusted
+            .get_mut(&owner)
+            .map_or(false, |t| t.remove(&trusted));
+        if was_trusted {
+            self.changes.push(LandClaimChange::Trust {
+                owner,
+                trusted,
+                trust: false,
+            });
+        }
+        was_trusted
+    }
+
+    pub fn take_changes(&mut self) -> Vec<LandClaimChange> { std::mem::take(&mut self.changes) }
+
+    fn check_owner(&self, player: Uuid, chunk: Vec2<i32>) -> Result<(), ClaimError> {
+        match self.owner(chunk) {
+            Some(owner) if owner == player => Ok(()),
+            Some(_) => Err(ClaimError::NotOwner),
+            None => Err(ClaimError::NotClaimed),
+        }
+    }
+
+    fn check_limit(&self, player: Uuid, limit: u32) -> Result<(), ClaimError> {
+        let owned = self.claims.values().filter(|o| **o == player).count();
+        if owned >= limit as usize {
+            Err(ClaimError::LimitReached(limit))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn set_owner(&mut self, chunk: Vec2<i32>, owner: Option<Uuid>) {
+        match owner {
+            Some(owner) => self.claims.insert(chunk, owner),
+            None => self.claims.remove(&chunk),
+        };
+        self.changes.push(LandClaimChange::Claim { chunk, owner });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn claims_limit_and_trust() {
+        let (alice, bob, carol) = (Uuid::from_u128(1), Uuid::from_u128(2), Uuid::from_u128(3));
+        let mut claims = LandClaims::default();
+        let (a, b) = (Vec2::new(0, 0), Vec2::new(1, 0));
+
+        assert_eq!(claims.may_build(bob, a), None);
+        assert_eq!(claims.claim(alice, a, 1), Ok(()));
+        assert_eq!(
+            claims.claim(bob, a, 1),
+            Err(ClaimError::AlreadyClaimed(alice))
+        );
+        assert_eq!(claims.claim(alice, b, 1), Err(ClaimError::LimitReached(1)));
+
+        assert_eq!(claims.may_build(alice, a), Some(true));
+        assert_eq!(claims.may_build(bob, a), Some(false));
+        assert!(claims.trust(alice, bob));
+        assert!(!claims.trust(alice, bob));
+        assert_eq!(claims.may_build(bob, a), Some(true));
+        assert!(claims.may_build_anywhere(bob));
+        assert!(!claims.may_build_anywhere(carol));
+        assert!(claims.untrust(alice, bob));
+        assert_eq!(claims.may_build(bob, a), Some(false));
+
+        assert!(!claims.may_change(Editor::Player(bob), a));
+        assert!(!claims.may_change(Editor::Other, a));
+        assert!(claims.may_change(Editor::Admin, a));
+        assert!(claims.may_change(Editor::Player(alice), a));
+        assert!(claims.may_change(Editor::Other, b));
+
+        assert_eq!(claims.abandon(bob, a), Err(ClaimError::NotOwner));
+        assert_eq!(claims.abandon(alice, b), Err(ClaimError::NotClaimed));
+        assert_eq!(claims.transfer(alice, a, bob, 1), Ok(()));
+        assert_eq!(claims.claims_of(bob), vec![a]);
+        assert!(claims.claims_of(alice).is_empty());
+        assert_eq!(claims.abandon(bob, a), Ok(()));
+        assert_eq!(claims.owner(a), None);
+
+        assert_eq!(claims.take_changes(), vec![
+            LandClaimChange::Claim {
+                chunk: a,
+                owner: Some(alice)
+            },
+            LandClaimChange::Trust {
+                owner: alice,
+                trusted: bob,
+                trust: true
+            },
+            LandClaimChange::Trust {
+                owner: alice,
+                trusted: bob,
+                trust: false
+            },
+            LandClaimChange::Claim {
+                chunk: a,
+                owner: Some(bob)
+            },
+            LandClaimChange::Claim {
+                chunk: a,
+                owner: None
+            },
+        ]);
+        assert!(claims.take_changes().is_empty());
+    }
+}
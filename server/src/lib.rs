@@ -14,19 +14,23 @@
 
 pub mod alias_validator;
 mod character_creator;
+pub mod chat_limiter;
 pub mod chunk_generator;
 pub mod client;
 pub mod cmd;
 pub mod connection_handler;
+pub mod console;
 mod data_dir;
 pub mod error;
 pub mod events;
 pub mod input;
+pub mod land_claims;
 pub mod login_provider;
 pub mod metrics;
 pub mod persistence;
 mod pet;
 pub mod presence;
+pub mod replay;
 pub mod rtsim;
 pub mod settings;
 pub mod state_ext;
@@ -49,10 +53,12 @@ pub use crate::{
 use crate::terrain_persistence::TerrainPersistence;
 use crate::{
     alias_validator::AliasValidator,
+    chat_limiter::ChatLimiter,
     chunk_generator::ChunkGenerator,
     client::Client,
     cmd::ChatCommandExt,
     connection_handler::ConnectionHandler,
+    console::ConsoleOutput,
     data_dir::DataDir,
     login_provider::LoginProvider,
     presence::{Presence, RegionSubscription, RepositionOnChunkLoad},
@@ -79,7 +85,8 @@ use common::{
 use common_ecs::run_now;
 use common_net::{
     msg::{
-        ClientType, DisconnectReason, ServerGeneral, ServerInfo, ServerInit, ServerMsg, WorldMapMsg,
+        ClientType, DisconnectReason, ServerGeneral, ServerInfo, ServerInit, ServerMsg,
+        WorldMapMsg, SESSION_RESUME_GRACE_PERIOD,
     },
     sync::WorldSyncExt,
 };
@@ -107,7 +114,8 @@ use tracing::{debug, error, info, trace, warn};
 use vek::*;
 
 use crate::{
-    persistence::{DatabaseSettings, SqlLogMode},
+    land_claims::LandClaims,
+    persistence::{land_claims::LandClaimUpdater, DatabaseSettings, SqlLogMode},
     sys::terrain,
 };
 use hashbrown::HashMap;
@@ -116,13 +124,15 @@ use std::sync::RwLock;
 #[cfg(feature = "plugins")]
 use {
     common::uid::UidAllocator,
-    common_state::plugin::{memory_manager::EcsWorld, PluginMgr},
+    common_state::plugin::{memory_manager::EcsWorld, storage::PluginStorage, PluginMgr},
+    persistence::plugin_storage::PluginStorageUpdater,
 };
 
 use common::comp::Anchor;
 #[cfg(feature = "worldgen")]
 use world::{
     sim::{FileOpts, WorldOpts, DEFAULT_WORLD_MAP},
+    sim2::RuntimeEconomy,
     IndexOwned, World,
 };
 
@@ -198,6 +208,9 @@ pub struct Server {
     metrics_shutdown: Arc<Notify>,
     database_settings: Arc<RwLock<DatabaseSettings>>,
     disconnect_all_clients_requested: bool,
+    /// Time at which plugins were last sent an `on_tick` event
+    #[cfg(feature = "plugins")]
+    last_plugin_tick: f64,
 }
 
 impl Server {
@@ -225,6 +238,7 @@ impl Server {
         let job_metrics = metrics::JobMetrics::new(&registry).unwrap();
         let network_request_metrics = metrics::NetworkRequestMetrics::new(&registry).unwrap();
         let player_metrics = metrics::PlayerMetrics::new(&registry).unwrap();
+        let chat_metrics = metrics::ChatMetrics::new(&registry).unwrap();
         let ecs_system_metrics = EcsSystemMetrics::new(&registry).unwrap();
         let tick_metrics = TickMetrics::new(&registry).unwrap();
         let physics_metrics = PhysicsMetrics::new(&registry).unwrap();
@@ -252,6 +266,7 @@ impl Server {
         state.ecs_mut().insert(job_metrics);
         state.ecs_mut().insert(network_request_metrics);
         state.ecs_mut().insert(player_metrics);
+        state.ecs_mut().insert(chat_metrics);
         state.ecs_mut().insert(ecs_system_metrics);
         state.ecs_mut().insert(tick_metrics);
         state.ecs_mut().insert(physics_metrics);
@@ -298,10 +313,49 @@ impl Server {
             Arc::<RwLock<DatabaseSettings>>::clone(&database_settings),
         )?);
 
+        let mut storage_containers = comp::StorageContainers::default();
+        match persistence::storage::load_storage_containers(
+            &*database_settings.read().unwrap(),
+            &state.ecs().read_resource::<comp::item::tool::AbilityMap>(),
+            &state
+                .ecs()
+                .read_resource::<comp::item::MaterialStatManifest>(),
+        ) {
+            Ok(containers) => storage_containers.load(containers),
+            Err(e) => error!(?e, "Failed to load the storage containers"),
+        }
+        state.ecs_mut().insert(storage_containers);
+
+        let mut land_claims = LandClaims::default();
+        match persistence::land_claims::load_land_claims(&*database_settings.read().unwrap()) {
+            Ok((claims, trusts)) => land_claims.load(claims, trusts),
+            Err(e) => error!(?e, "Failed to load the land claims"),
+        }
+        state.ecs_mut().insert(land_claims);
+        state.ecs_mut().insert(LandClaimUpdater::new(
+            Arc::<RwLock<DatabaseSettings>>::clone(&database_settings),
+        )?);
+
+        #[cfg(feature = "plugins")]
+        {
+            match persistence::plugin_storage::load_plugin_storage(
+                &*database_settings.read().unwrap(),
+            ) {
+                Ok(entries) => state.ecs().read_resource::<PluginStorage>().load(entries),
+                Err(e) => error!(?e, "Failed to load the plugin storage"),
+            }
+            state.ecs_mut().insert(PluginStorageUpdater::new(
+                Arc::<RwLock<DatabaseSettings>>::clone(&database_settings),
+            )?);
+        }
+
         // System schedulers to control execution of systems
         state
             .ecs_mut()
             .insert(sys::PersistenceScheduler::every(Duration::from_secs(10)));
+        state
+            .ecs_mut()
+            .insert(sys::QuestScheduler::every(Duration::from_secs(1)));
 
         // Server-only components
         state.ecs_mut().register::<RegionSubscription>();
@@ -311,8 +365,12 @@ impl Server {
         state.ecs_mut().register::<wiring::Circuit>();
         state.ecs_mut().register::<comp::Anchor>();
         state.ecs_mut().register::<comp::Pet>();
+        state.ecs_mut().register::<comp::QuestLog>();
+        state.ecs_mut().register::<comp::OpenStorage>();
         state.ecs_mut().register::<login_provider::PendingLogin>();
         state.ecs_mut().register::<RepositionOnChunkLoad>();
+        state.ecs_mut().register::<ConsoleOutput>();
+        state.ecs_mut().register::<ChatLimiter>();
 
         //Alias validator
         let banned_words_paths = &settings.banned_words_files;
@@ -440,6 +498,8 @@ impl Server {
         let world = Arc::new(world);
         state.ecs_mut().insert(Arc::clone(&world));
         state.ecs_mut().insert(index.clone());
+        #[cfg(feature = "worldgen")]
+        state.ecs_mut().insert(RuntimeEconomy::new(&index));
 
         // Set starting time for the server.
         state.ecs_mut().write_resource::<TimeOfDay>().0 = settings.start_time;
@@ -450,6 +510,9 @@ impl Server {
         state.ecs_mut().insert(DeletedEntities::default());
 
         let network = Network::new_with_registry(Pid::new(), &runtime, &registry);
+        // the client is kicked after `client_timeout` without a ping anyway
+        network
+            .set_resume_grace_period(SESSION_RESUME_GRACE_PERIOD.min(settings.client_timeout / 2));
         let metrics_shutdown = Arc::new(Notify::new());
         let metrics_shutdown_clone = Arc::clone(&metrics_shutdown);
         let addr = settings.metrics_address;
@@ -510,7 +573,13 @@ impl Server {
 
         // Initiate real-time world simulation
         #[cfg(feature = "worldgen")]
-        rtsim::init(&mut state, &world, index.as_index_ref(), spawn_point);
+        rtsim::init(
+            &mut state,
+            &world,
+            index.as_index_ref(),
+            spawn_point,
+            cfg!(feature = "persistent_world").then(|| rtsim::persistence::snapshot_path(data_dir)),
+        );
         #[cfg(not(feature = "worldgen"))]
         rtsim::init(&mut state);
 
@@ -526,6 +595,8 @@ impl Server {
             metrics_shutdown,
             database_settings,
             disconnect_all_clients_requested: false,
+            #[cfg(feature = "plugins")]
+            last_plugin_tick: 0.0,
         };
 
         debug!(?settings, "created veloren server with");
@@ -675,6 +746,9 @@ impl Server {
         // NOTE: apply_terrain_changes sends the *new* value since it is not being
         // synchronized during the tick.
         self.state.apply_terrain_changes();
+        // Containers whose sprite was destroyed by these changes would otherwise be
+        // unreachable
+        events::drop_broken_storage(&mut self.state);
 
         let before_sync = Instant::now();
 
@@ -874,6 +948,26 @@ impl Server {
         drop(character_loader);
         drop(character_updater);
 
+        #[cfg(feature = "persistent_world")]
+        {
+            // Answer the commands waiting for the edit journal to be searched
+            cmd::handle_edit_journal_responses(self);
+
+            // Set the blocks of restored terrain regenerated in the background
+            let regenerated = self
+                .state
+                .ecs()
+                .try_fetch::<TerrainPersistence>()
+                .map(|terrain_persistence| terrain_persistence.take_regenerated())
+                .unwrap_or_default();
+            for (pos, block) in regenerated {
+                self.state.set_block(pos, block);
+            }
+        }
+
+        #[cfg(feature = "plugins")]
+        self.persist_plugin_storage();
+
         {
             // Check for new chunks; cancel and regenerate all chunks if the asset has been
             // reloaded. Note that all of these assignments are no-ops, so the
@@ -961,6 +1055,10 @@ impl Server {
             .ecs()
             .try_fetch_mut::<TerrainPersistence>()
             .map(|mut t| t.maintain());
+
+        // Snapshot the rtsim state
+        let time = self.state.get_time();
+        self.state.ecs().write_resource::<RtSim>().maintain(time);
     }
 
     fn initialize_client(
@@ -990,6 +1088,14 @@ impl Server {
         // Send client all the tracked components currently attached to its entity as
         // well as synced resources (currently only `TimeOfDay`)
         debug!("Starting initial sync with client.");
+        #[cfg(feature = "plugins")]
+        let plugins = self
+            .state
+            .ecs()
+            .read_resource::<PluginMgr>()
+            .plugin_hashes();
+        #[cfg(not(feature = "plugins"))]
+        let plugins = Vec::new();
         self.state
             .ecs()
             .read_storage::<Client>()
@@ -1017,6 +1123,7 @@ impl Server {
                     .ecs()
                     .read_resource::<comp::item::tool::AbilityMap>())
                     .clone(),
+                plugins,
             })?;
         Ok(Some(entity))
     }
@@ -1102,11 +1209,12 @@ impl Server {
     where
         S: Into<ServerMsg>,
     {
-        self.state
-            .ecs()
-            .read_storage::<Client>()
-            .get(entity)
-            .map(|c| c.send(msg));
+        let ecs = self.state.ecs();
+        if let Some(client) = ecs.read_storage::<Client>().get(entity) {
+            let _ = client.send(msg);
+        } else if let Some(output) = ecs.write_storage::<ConsoleOutput>().get_mut(entity) {
+            output.push(msg.into());
+        }
     }
 
     pub fn notify_players(&mut self, msg: ServerGeneral) { self.state.notify_players(msg); }
@@ -1127,6 +1235,22 @@ impl Server {
         );
     }
 
+    /// Sends the changes plugins made to their storage to the database
+    #[cfg(feature = "plugins")]
+    fn persist_plugin_storage(&self) {
+        let changes = self
+            .state
+            .ecs()
+            .read_resource::<PluginStorage>()
+            .take_changes();
+        if !changes.is_empty() {
+            self.state
+                .ecs()
+                .read_resource::<PluginStorageUpdater>()
+                .batch_update(changes);
+        }
+    }
+
     fn process_command(&mut self, entity: EcsEntity, name: String, args: Vec<String>) {
         // Find the command object and run its handler.
         if let Ok(command) = name.parse::<ChatCommand>() {
@@ -1141,6 +1265,14 @@ impl Server {
                     uid: self.state.ecs().read_component().into(),
                     uid_allocator: &self.state.ecs().read_resource::<UidAllocator>().into(),
                     player: self.state.ecs().read_component().into(),
+                    pos: self.state.ecs().read_component().into(),
+                    inventory: self.state.ecs().read_component().into(),
+                    stats: self.state.ecs().read_component().into(),
+                    plugin_actions: &self
+                        .state
+                        .ecs()
+                        .read_resource::<EventBus<plugin_api::Action>>(),
+                    plugin_storage: &self.state.ecs().read_resource::<PluginStorage>(),
                 };
                 let uid = if let Some(uid) = ecs_world.uid.get(entity).copied() {
                     uid
@@ -1229,6 +1361,68 @@ impl Server {
             .map(|admin| admin.0)
     }
 
+    /// Whether the entity may use `cmd`, either by its admin role or by its
+    /// permission groups
+    fn entity_permits_command(&self, entity: EcsEntity, cmd: &ChatCommand) -> bool {
+        cmd.needs_role() <= self.entity_admin_role(entity)
+            || self
+                .state
+                .ecs()
+                .read_storage::<comp::Player>()
+                .get(entity)
+                .map_or(false, |player| {
+                    self.editable_settings().permissions.permits_command(
+                        player.uuid(),
+                        cmd.keyword(),
+                        chrono::Utc::now(),
+                    )
+                })
+    }
+
+    /// If `msg` was written by a player who is muted in its channel, returns
+    /// the player and a message telling them how long the mute lasts.
+    fn muted_reply(&self, msg: &comp::UnresolvedChatMsg) -> Option<(EcsEntity, String)> {
+        let channel = settings::Channel::of(&msg.chat_type)?;
+        let entity = self.state.ecs().entity_from_uid(msg.uid()?.into())?;
+        let uuid = self
+            .state
+            .ecs()
+            .read_storage::<comp::Player>()
+            .get(entity)?
+            .uuid();
+        let now = chrono::Utc::now();
+        let editable_settings = self.editable_settings();
+        let mute = editable_settings.mutelist.active_mute(uuid, channel, now)?;
+        let scope = match mute.channel {
+            Some(channel) => format!("in {} chat", channel.name()),
+            None => "in all chats".to_owned(),
+        };
+        let duration = match mute.end_date.and_then(|end| (end - now).to_std().ok()) {
+            Some(remaining) => format!(
+                "for another {}",
+                // Seconds are enough precision for players
+                humantime::format_duration(Duration::from_secs(remaining.as_secs()))
+            ),
+            None => "permanently".to_owned(),
+        };
+        Some((
+            entity,
+            format!("You are muted {} {}: {}", scope, duration, mute.reason),
+        ))
+    }
+
+    /// Sends a chat message, unless it was written by a muted player, who is
+    /// told about their mute instead.
+    pub(crate) fn send_chat_unless_muted(&self, msg: comp::UnresolvedChatMsg) {
+        match self.muted_reply(&msg) {
+            Some((entity, reply)) => self.notify_client(
+                entity,
+                ServerGeneral::server_msg(comp::ChatType::CommandError, reply),
+            ),
+            None => self.state.send_chat(msg),
+        }
+    }
+
     pub fn number_of_players(&self) -> i64 {
         self.state.ecs().read_storage::<Client>().join().count() as i64
     }
@@ -1340,6 +1534,9 @@ impl Drop for Server {
     fn drop(&mut self) {
         self.metrics_shutdown.notify_one();
 
+        #[cfg(feature = "plugins")]
+        self.persist_plugin_storage();
+
         self.state
             .notify_players(ServerGeneral::Disconnect(DisconnectReason::Shutdown));
 
@@ -1351,6 +1548,9 @@ impl Drop for Server {
                 info!("Unloading terrain persistence...");
                 terrain_persistence.unload_all()
             });
+
+        let time = self.state.get_time();
+        self.state.ecs().write_resource::<RtSim>().save(time);
     }
 }
 
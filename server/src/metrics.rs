@@ -30,6 +30,11 @@ pub struct PlayerMetrics {
     pub clients_disconnected: IntCounterVec, // timeout, network_error, gracefully
 }
 
+pub struct ChatMetrics {
+    pub messages_refused: IntCounterVec, // rate_limit, repeated, muted
+    pub enforcements: IntCounterVec,     // warning, mute, kick
+}
+
 pub struct NetworkRequestMetrics {
     pub chunks_request_dropped: IntCounter,
     pub chunks_served_from_memory: IntCounter,
@@ -180,6 +185,35 @@ impl PlayerMetrics {
     }
 }
 
+impl ChatMetrics {
+    pub fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+        let messages_refused = IntCounterVec::new(
+            Opts::new(
+                "chat_messages_refused",
+                "shows the number of chat messages and commands refused by the chat limits and \
+                 the reason",
+            ),
+            &["reason"],
+        )?;
+        let enforcements = IntCounterVec::new(
+            Opts::new(
+                "chat_limit_enforcements",
+                "shows the number of warnings, mutes and kicks of players exceeding the chat \
+                 limits",
+            ),
+            &["action"],
+        )?;
+
+        registry.register(Box::new(messages_refused.clone()))?;
+        registry.register(Box::new(enforcements.clone()))?;
+
+        Ok(Self {
+            messages_refused,
+            enforcements,
+        })
+    }
+}
+
 impl NetworkRequestMetrics {
     pub fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
         let chunks_request_dropped = IntCounter::with_opts(Opts::new(
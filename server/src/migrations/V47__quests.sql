@@ -0,0 +1,9 @@
+-- Creates the table of the quests started by characters
+CREATE TABLE "quest" (
+      "character_id" INT NOT NULL,
+      "quest" TEXT NOT NULL,
+      "completed" INT NOT NULL,
+      "progress" TEXT NOT NULL,
+      PRIMARY KEY("character_id", "quest"),
+      FOREIGN KEY("character_id") REFERENCES "character"("character_id")
+);
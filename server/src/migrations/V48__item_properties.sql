@@ -0,0 +1,3 @@
+-- Adds the properties of items that aren't part of their definition, such as
+-- the durability they lost
+ALTER TABLE "item" ADD COLUMN "properties" TEXT NOT NULL DEFAULT '{}';
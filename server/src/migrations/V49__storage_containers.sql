@@ -0,0 +1,11 @@
+-- Creates the storage containers players placed in the world, keyed by the
+-- position of their sprite
+CREATE TABLE "storage_container" (
+      "x" INTEGER NOT NULL,
+      "y" INTEGER NOT NULL,
+      "z" INTEGER NOT NULL,
+      "owner" INTEGER NOT NULL,
+      "access" TEXT NOT NULL,
+      "items" TEXT NOT NULL,
+      PRIMARY KEY("x", "y", "z")
+);
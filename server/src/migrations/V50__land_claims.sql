@@ -0,0 +1,14 @@
+-- Creates the chunks claimed by players and the players they trust to build
+-- in their claims
+CREATE TABLE "land_claim" (
+      "x" INTEGER NOT NULL,
+      "y" INTEGER NOT NULL,
+      "owner" TEXT NOT NULL,
+      PRIMARY KEY("x", "y")
+);
+
+CREATE TABLE "land_claim_trust" (
+      "owner" TEXT NOT NULL,
+      "trusted" TEXT NOT NULL,
+      PRIMARY KEY("owner", "trusted")
+);
@@ -15,6 +15,7 @@ use crate::{
             convert_body_from_database, convert_body_to_database_json,
             convert_character_from_database, convert_inventory_from_database_items,
             convert_items_to_database_items, convert_loadout_from_database_items,
+            convert_quest_log_from_database, convert_quests_to_database,
             convert_skill_groups_to_database, convert_skill_set_from_database,
             convert_skills_to_database, convert_stats_from_database,
             convert_waypoint_from_database_json, convert_waypoint_to_database_json,
@@ -64,13 +65,15 @@ pub fn load_items(connection: &Connection, root: i64) -> Result<Vec<Item>, Persi
             parent_container_item_id,
             item_definition_id,
             stack_size,
-            position
+            position,
+            properties
         ) AS (
             SELECT  item_id,
                     parent_container_item_id,
                     item_definition_id,
                     stack_size,
-                    position
+                    position,
+                    properties
             FROM item
             WHERE parent_container_item_id = ?1
             UNION ALL
@@ -78,7 +81,8 @@ pub fn load_items(connection: &Connection, root: i64) -> Result<Vec<Item>, Persi
                     item.parent_container_item_id,
                     item.item_definition_id,
                     item.stack_size,
-                    item.position
+                    item.position,
+                    item.properties
             FROM item, items_tree
             WHERE item.parent_container_item_id = items_tree.item_id
         )
@@ -94,6 +98,7 @@ pub fn load_items(connection: &Connection, root: i64) -> Result<Vec<Item>, Persi
                 item_definition_id: row.get(2)?,
                 stack_size: row.get(3)?,
                 position: row.get(4)?,
+                properties: row.get(5)?,
             })
         })?
         .filter_map(Result::ok)
@@ -251,6 +256,27 @@ pub fn load_character_data(
         })
         .collect::<Vec<(comp::Pet, comp::Body, comp::Stats)>>();
 
+    #[rustfmt::skip]
+    let mut stmt = connection.prepare_cached("
+        SELECT  quest,
+                completed,
+                progress
+        FROM    quest
+        WHERE   character_id = ?1",
+    )?;
+
+    let db_quests = stmt
+        .query_map(&[char_id], |row| {
+            Ok(Quest {
+                character_id: char_id,
+                quest: row.get(0)?,
+                completed: row.get(1)?,
+                progress: row.get(2)?,
+            })
+        })?
+        .filter_map(Result::ok)
+        .collect::<Vec<Quest>>();
+
     Ok((
         convert_body_from_database(&body_data.variant, &body_data.body_data)?,
         convert_stats_from_database(character_data.alias),
@@ -263,6 +289,7 @@ pub fn load_character_data(
         )?,
         char_waypoint,
         pets,
+        convert_quest_log_from_database(&db_quests),
     ))
 }
 
@@ -348,7 +375,7 @@ pub fn create_character(
 ) -> CharacterCreationResult {
     check_character_limit(uuid, transactionn)?;
 
-    let (body, _stats, skill_set, inventory, waypoint, _) = persisted_components;
+    let (body, _stats, skill_set, inventory, waypoint, _, _) = persisted_components;
 
     // Fetch new entity IDs for character, inventory and loadout
     let mut new_entity_ids = get_new_entity_ids(transactionn, |next_id| next_id + 3)?;
@@ -365,6 +392,7 @@ pub fn create_character(
             parent_container_item_id: WORLD_PSEUDO_CONTAINER_ID,
             item_definition_id: CHARACTER_PSEUDO_CONTAINER_DEF_ID.to_owned(),
             position: character_id.to_string(),
+            properties: String::from("{}"),
         },
         Item {
             stack_size: 1,
@@ -372,6 +400,7 @@ pub fn create_character(
             parent_container_item_id: character_id,
             item_definition_id: INVENTORY_PSEUDO_CONTAINER_DEF_ID.to_owned(),
             position: INVENTORY_PSEUDO_CONTAINER_POSITION.to_owned(),
+            properties: String::from("{}"),
         },
         Item {
             stack_size: 1,
@@ -379,6 +408,7 @@ pub fn create_character(
             parent_container_item_id: character_id,
             item_definition_id: LOADOUT_PSEUDO_CONTAINER_DEF_ID.to_owned(),
             position: LOADOUT_PSEUDO_CONTAINER_POSITION.to_owned(),
+            properties: String::from("{}"),
         },
     ];
 
@@ -388,8 +418,9 @@ pub fn create_character(
                           parent_container_item_id,
                           item_definition_id,
                           stack_size,
-                          position)
-        VALUES (?1, ?2, ?3, ?4, ?5)",
+                          position,
+                          properties)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
     )?;
 
     for pseudo_container in pseudo_containers {
@@ -399,6 +430,7 @@ pub fn create_character(
             &pseudo_container.item_definition_id,
             &pseudo_container.stack_size,
             &pseudo_container.position,
+            &pseudo_container.properties,
         ])?;
     }
     drop(stmt);
@@ -479,8 +511,9 @@ pub fn create_character(
                           parent_container_item_id,
                           item_definition_id,
                           stack_size,
-                          position)
-        VALUES (?1, ?2, ?3, ?4, ?5)",
+                          position,
+                          properties)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
     )?;
 
     for item in inserts {
@@ -490,6 +523,7 @@ pub fn create_character(
             &item.model.item_definition_id,
             &item.model.stack_size,
             &item.model.position,
+            &item.model.properties,
         ])?;
     }
     drop(stmt);
@@ -604,6 +638,17 @@ pub fn delete_character(
         delete_pets(transaction, char_id, Rc::new(pet_ids))?;
     }
 
+    // Delete quests
+    let mut stmt = transaction.prepare_cached(
+        "
+        DELETE
+        FROM    quest
+        WHERE   character_id = ?1",
+    )?;
+
+    stmt.execute(&[&char_id])?;
+    drop(stmt);
+
     // Delete character
     let mut stmt = transaction.prepare_cached(
         "
@@ -863,6 +908,48 @@ fn update_pets(
     Ok(())
 }
 
+/// Replaces the quests stored for the character with the ones of its quest log
+fn update_quests(
+    char_id: CharacterId,
+    quest_log: &comp::QuestLog,
+    transaction: &mut Transaction,
+) -> Result<(), PersistenceError> {
+    let db_quests = convert_quests_to_database(char_id, quest_log)?;
+
+    #[rustfmt::skip]
+    let mut stmt = transaction.prepare_cached("
+        DELETE
+        FROM    quest
+        WHERE   character_id = ?1"
+    )?;
+
+    let delete_count = stmt.execute(&[&char_id])?;
+    drop(stmt);
+    trace!("Deleted {} quests", delete_count);
+
+    #[rustfmt::skip]
+    let mut stmt = transaction.prepare_cached("
+        INSERT
+        INTO    quest (
+                character_id,
+                quest,
+                completed,
+                progress)
+        VALUES  (?1, ?2, ?3, ?4)"
+    )?;
+
+    for quest in db_quests {
+        stmt.execute(&[
+            &quest.character_id as &dyn ToSql,
+            &quest.quest,
+            &quest.completed,
+            &quest.progress,
+        ])?;
+    }
+
+    Ok(())
+}
+
 fn get_pet_ids(char_id: i64, transaction: &mut Transaction) -> Result<Vec<i64>, PersistenceError> {
     #[rustfmt::skip]
         let mut stmt = transaction.prepare_cached("
@@ -917,11 +1004,14 @@ pub fn update(
     inventory: comp::Inventory,
     pets: Vec<PetPersistenceData>,
     char_waypoint: Option<comp::Waypoint>,
+    quest_log: comp::QuestLog,
     transaction: &mut Transaction,
 ) -> Result<(), PersistenceError> {
     // Run pet persistence
     update_pets(char_id, pets, transaction)?;
 
+    update_quests(char_id, &quest_log, transaction)?;
+
     let pseudo_containers = get_pseudo_containers(transaction, char_id)?;
     let mut upserts = Vec::new();
     // First, get all the entity IDs for any new items, and identify which
@@ -1001,8 +1091,9 @@ pub fn update(
                           parent_container_item_id,
                           item_definition_id,
                           stack_size,
-                          position)
-            VALUES  (?1, ?2, ?3, ?4, ?5)",
+                          position,
+                          properties)
+            VALUES  (?1, ?2, ?3, ?4, ?5, ?6)",
         )?;
 
         for item in upserted_items.iter() {
@@ -1012,6 +1103,7 @@ pub fn update(
                 &item.item_definition_id,
                 &item.stack_size,
                 &item.position,
+                &item.properties,
             ])?;
         }
     }
@@ -1,13 +1,16 @@
 use crate::persistence::{
     character::EntityId,
-    models::{Character, Item, Skill, SkillGroup},
+    models::{Character, Item, Quest, Skill, SkillGroup},
 };
 
 use crate::persistence::{
     error::PersistenceError,
-    json_models::{self, CharacterPosition, GenericBody, HumanoidBody},
+    json_models::{
+        self, CharacterPosition, GenericBody, HumanoidBody, ItemProperties, QuestProgress,
+    },
 };
 use common::{
+    assets::AssetExt,
     character::CharacterId,
     comp::{
         inventory::{
@@ -24,7 +27,7 @@ use core::{convert::TryFrom, num::NonZeroU64};
 use hashbrown::HashMap;
 use lazy_static::lazy_static;
 use std::{collections::VecDeque, str::FromStr, sync::Arc};
-use tracing::trace;
+use tracing::{trace, warn};
 
 #[derive(Debug)]
 pub struct ItemModelPair {
@@ -175,6 +178,10 @@ pub fn convert_items_to_database_items(
                     } else {
                         1
                     },
+                    properties: serde_json::to_string(&ItemProperties {
+                        durability_lost: item.durability_lost(),
+                    })
+                    .expect("failed to serialize ItemProperties"),
                 },
                 // Continue to remember the atomic, in case we detect an error later and want
                 // to roll back to preserve liveness.
@@ -249,6 +256,73 @@ pub fn convert_waypoint_from_database_json(position: &str) -> Result<Waypoint, P
     Ok(Waypoint::new(character_position.waypoint, Time(0.0)))
 }
 
+pub fn convert_quests_to_database(
+    character_id: CharacterId,
+    quest_log: &QuestLog,
+) -> Result<Vec<Quest>, PersistenceError> {
+    quest_log
+        .quests
+        .iter()
+        .map(|quest| {
+            let progress = serde_json::to_string(&QuestProgress {
+                progress: quest.progress.clone(),
+                sites: quest.sites.clone(),
+            })
+            .map_err(|err| {
+                PersistenceError::ConversionError(format!(
+                    "Error encoding progress of quest {}: {:?}",
+                    quest.spec, err
+                ))
+            })?;
+            Ok(Quest {
+                character_id,
+                quest: quest.spec.clone(),
+                completed: quest.completed,
+                progress,
+            })
+        })
+        .collect()
+}
+
+/// Quests whose asset no longer exists, or whose objectives changed, are
+/// dropped since their progress can't be carried over.
+pub fn convert_quest_log_from_database(quests: &[Quest]) -> QuestLog {
+    let quests = quests
+        .iter()
+        .filter_map(|quest| {
+            let spec = match common::quest::QuestSpec::load(&quest.quest) {
+                Ok(spec) => spec,
+                Err(err) => {
+                    warn!("Dropping unknown quest {}: {}", quest.quest, err);
+                    return None;
+                },
+            };
+            let progress = match serde_json::de::from_str::<QuestProgress>(&quest.progress) {
+                Ok(progress) => progress,
+                Err(err) => {
+                    warn!(
+                        "Error de-serializing progress of quest {}: {} err: {}",
+                        quest.quest, quest.progress, err
+                    );
+                    return None;
+                },
+            };
+            let objectives = spec.read().objectives.len();
+            if progress.progress.len() != objectives || progress.sites.len() != objectives {
+                warn!("Dropping quest {} whose objectives changed", quest.quest);
+                return None;
+            }
+            Some(quest::Quest {
+                spec: quest.quest.clone(),
+                progress: progress.progress,
+                sites: progress.sites,
+                completed: quest.completed,
+            })
+        })
+        .collect();
+    QuestLog { quests }
+}
+
 /// Properly-recursive items (currently modular weapons) occupy the same
 /// inventory slot as their parent. The caller is responsible for ensuring that
 /// inventory_items and loadout_items are topologically sorted (i.e. forall i,
@@ -300,6 +374,8 @@ pub fn convert_inventory_from_database_items(
             })?;
         }
 
+        set_item_properties(&mut item, db_item)?;
+
         // Insert item into inventory
 
         // Slot position
@@ -368,7 +444,8 @@ pub fn convert_loadout_from_database_items(
     for (i, db_item) in database_items.iter().enumerate() {
         item_indices.insert(db_item.item_id, i);
 
-        let item = get_item_from_asset(db_item.item_definition_id.as_str())?;
+        let mut item = get_item_from_asset(db_item.item_definition_id.as_str())?;
+        set_item_properties(&mut item, db_item)?;
 
         // NOTE: item id is currently *unique*, so we can store the ID safely.
         let comp = item.get_item_id_for_database();
@@ -512,6 +589,25 @@ pub fn convert_skill_set_from_database(
     }
 }
 
+/// Restores the properties of an item that aren't part of its definition
+fn set_item_properties(
+    item: &mut common::comp::Item,
+    db_item: &Item,
+) -> Result<(), PersistenceError> {
+    let properties = serde_json::from_str::<ItemProperties>(&db_item.properties).map_err(|_| {
+        PersistenceError::ConversionError(format!(
+            "Failed to parse item properties: {:?}",
+            &db_item.properties
+        ))
+    })?;
+    item.set_durability_lost(
+        properties.durability_lost,
+        &ABILITY_MAP,
+        &MATERIAL_STATS_MANIFEST,
+    );
+    Ok(())
+}
+
 fn get_item_from_asset(item_definition_id: &str) -> Result<common::comp::Item, PersistenceError> {
     common::comp::Item::new_from_asset(item_definition_id).map_err(|err| {
         PersistenceError::AssetError(format!(
@@ -4,8 +4,9 @@ use common::character::CharacterId;
 use crate::persistence::{
     character_loader::{CharacterLoaderResponse, CharacterLoaderResponseKind},
     error::PersistenceError,
-    establish_connection, ConnectionMode, DatabaseSettings, EditableComponents,
-    PersistedComponents, VelorenConnection,
+    establish_connection,
+    storage::StorageChange,
+    ConnectionMode, DatabaseSettings, EditableComponents, PersistedComponents, VelorenConnection,
 };
 use crossbeam_channel::TryIter;
 use rusqlite::{DropBehavior, Transaction};
@@ -24,13 +25,16 @@ pub type CharacterUpdateData = (
     comp::Inventory,
     Vec<PetPersistenceData>,
     Option<comp::Waypoint>,
+    comp::QuestLog,
 );
 
 pub type PetPersistenceData = (comp::Pet, comp::Body, comp::Stats);
 
 #[allow(clippy::large_enum_variant)]
 pub enum CharacterUpdaterEvent {
-    BatchUpdate(Vec<(CharacterId, CharacterUpdateData)>),
+    /// Updates characters, and the storage containers whose items they may
+    /// have moved in the same transaction
+    BatchUpdate(Vec<(CharacterId, CharacterUpdateData)>, Vec<StorageChange>),
     CreateCharacter {
         entity: Entity,
         player_uuid: String,
@@ -84,7 +88,7 @@ impl CharacterUpdater {
                     establish_connection(&*settings.read().unwrap(), ConnectionMode::ReadWrite);
                 while let Ok(updates) = update_rx.recv() {
                     match updates {
-                        CharacterUpdaterEvent::BatchUpdate(updates) => {
+                        CharacterUpdaterEvent::BatchUpdate(updates, storage_changes) => {
                             if disconnect_all_clients_requested_clone.load(Ordering::Relaxed) {
                                 debug!(
                                     "Skipping persistence due to pending disconnection of all \
@@ -93,7 +97,9 @@ impl CharacterUpdater {
                                 continue;
                             }
                             conn.update_log_mode(&settings);
-                            if let Err(e) = execute_batch_update(updates, &mut conn) {
+                            if let Err(e) =
+                                execute_batch_update(updates, storage_changes, &mut conn)
+                            {
                                 error!(
                                     "Error during character batch update, disconnecting all \
                                      clients to avoid loss of data integrity. Error: {:?}",
@@ -320,7 +326,8 @@ impl CharacterUpdater {
         }
     }
 
-    /// Updates a collection of characters based on their id and components
+    /// Updates a collection of characters based on their id and components,
+    /// along with the changes to storage containers
     pub fn batch_update<'a>(
         &mut self,
         updates: impl Iterator<
@@ -330,21 +337,26 @@ impl CharacterUpdater {
                 &'a comp::Inventory,
                 Vec<PetPersistenceData>,
                 Option<&'a comp::Waypoint>,
+                &'a comp::QuestLog,
             ),
         >,
+        storage_changes: Vec<StorageChange>,
     ) {
         let updates = updates
-            .map(|(character_id, skill_set, inventory, pets, waypoint)| {
-                (
-                    character_id,
+            .map(
+                |(character_id, skill_set, inventory, pets, waypoint, quest_log)| {
                     (
-                        skill_set.clone(),
-                        inventory.clone(),
-                        pets,
-                        waypoint.cloned(),
-                    ),
-                )
-            })
+                        character_id,
+                        (
+                            skill_set.clone(),
+                            inventory.clone(),
+                            pets,
+                            waypoint.cloned(),
+                            quest_log.clone(),
+                        ),
+                    )
+                },
+            )
             .chain(self.pending_logout_updates.drain())
             .collect::<Vec<_>>();
 
@@ -352,7 +364,7 @@ impl CharacterUpdater {
             .update_tx
             .as_ref()
             .unwrap()
-            .send(CharacterUpdaterEvent::BatchUpdate(updates))
+            .send(CharacterUpdaterEvent::BatchUpdate(updates, storage_changes))
         {
             error!(?e, "Could not send stats updates");
         }
@@ -377,23 +389,26 @@ impl CharacterUpdater {
 
 fn execute_batch_update(
     updates: Vec<(CharacterId, CharacterUpdateData)>,
+    storage_changes: Vec<StorageChange>,
     connection: &mut VelorenConnection,
 ) -> Result<(), PersistenceError> {
     let mut transaction = connection.connection.transaction()?;
     transaction.set_drop_behavior(DropBehavior::Rollback);
     trace!("Transaction started for character batch update");
-    updates
-        .into_iter()
-        .try_for_each(|(character_id, (stats, inventory, pets, waypoint))| {
+    updates.into_iter().try_for_each(
+        |(character_id, (stats, inventory, pets, waypoint, quest_log))| {
             super::character::update(
                 character_id,
                 stats,
                 inventory,
                 pets,
                 waypoint,
+                quest_log,
                 &mut transaction,
             )
-        })?;
+        },
+    )?;
+    super::storage::update_containers(storage_changes, &mut transaction)?;
     transaction.commit()?;
 
     trace!("Commit for character batch update completed");
@@ -63,6 +63,38 @@ pub struct CharacterPosition {
     pub waypoint: Vec3<f32>,
 }
 
+/// Properties of an item that aren't part of its definition
+#[derive(Default, Serialize, Deserialize)]
+pub struct ItemProperties {
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub durability_lost: u32,
+}
+
+fn is_zero(value: &u32) -> bool { *value == 0 }
+
+/// An item kept in a storage container, along with its components
+#[derive(Serialize, Deserialize)]
+pub struct StoredItem {
+    pub item_definition_id: String,
+    pub amount: u32,
+    #[serde(default)]
+    pub properties: ItemProperties,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<StoredItem>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StoredSlot {
+    pub slot: comp::slot::InvSlotId,
+    pub item: StoredItem,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct QuestProgress {
+    pub progress: Vec<u32>,
+    pub sites: Vec<Option<comp::quest::QuestSite>>,
+}
+
 pub fn skill_to_db_string(skill: comp::skills::Skill) -> String {
     use comp::{
         item::tool::ToolKind,
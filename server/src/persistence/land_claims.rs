@@ -0,0 +1,188 @@
+//! Database operations for the chunks claimed by players
+
+use crate::{
+    land_claims::LandClaimChange,
+    persistence::{
+        error::PersistenceError, establish_connection, ConnectionMode, DatabaseSettings,
+        VelorenConnection,
+    },
+};
+use authc::Uuid;
+use rusqlite::{DropBehavior, ToSql, NO_PARAMS};
+use std::sync::{Arc, RwLock};
+use tracing::{error, trace, warn};
+use vek::*;
+
+/// A unidirectional messaging resource for saving the changes made to land
+/// claims in a background thread.
+pub struct LandClaimUpdater {
+    update_tx: Option<crossbeam_channel::Sender<Vec<LandClaimChange>>>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl LandClaimUpdater {
+    pub fn new(settings: Arc<RwLock<DatabaseSettings>>) -> rusqlite::Result<Self> {
+        let (update_tx, update_rx) = crossbeam_channel::unbounded::<Vec<LandClaimChange>>();
+
+        let builder = std::thread::Builder::new().name("land_claim_updater".into());
+        let handle = builder
+            .spawn(move || {
+                // Unwrap here is safe as there is no code that can panic when the write lock is
+                // taken that could cause the RwLock to become poisoned.
+                let mut conn =
+                    establish_connection(&*settings.read().unwrap(), ConnectionMode::ReadWrite);
+                while let Ok(changes) = update_rx.recv() {
+                    conn.update_log_mode(&settings);
+                    if let Err(e) = execute_batch_update(changes, &mut conn) {
+                        error!(?e, "Error during land claim batch update");
+                    }
+                }
+            })
+            .unwrap();
+
+        Ok(Self {
+            update_tx: Some(update_tx),
+            handle: Some(handle),
+        })
+    }
+
+    pub fn batch_update(&self, changes: Vec<LandClaimChange>) {
+        if changes.is_empty() {
+            return;
+        }
+        if let Err(e) = self.update_tx.as_ref().unwrap().send(changes) {
+            error!(?e, "Could not send land claim updates");
+        }
+    }
+}
+
+impl Drop for LandClaimUpdater {
+    fn drop(&mut self) {
+        drop(self.update_tx.take());
+        if let Err(e) = self.handle.take().unwrap().join() {
+            error!(?e, "Error from joining land claim update thread");
+        }
+    }
+}
+
+/// Claimed chunks with their owner, and pairs of an owner and a player they
+/// trust
+pub type LoadedLandClaims = (Vec<(Vec2<i32>, Uuid)>, Vec<(Uuid, Uuid)>);
+
+/// Loads all the land claims. Rows with an invalid UUID are skipped with a
+/// warning.
+pub fn load_land_claims(settings: &DatabaseSettings) -> Result<LoadedLandClaims, PersistenceError> {
+    let connection = establish_connection(settings, ConnectionMode::ReadOnly);
+
+    let mut stmt = connection.prepare_cached(
+        "
+        SELECT  x,
+                y,
+                owner
+        FROM    land_claim",
+    )?;
+    let claims = stmt
+        .query_map(NO_PARAMS, |row| {
+            Ok((
+                Vec2::new(row.get::<_, i32>(0)?, row.get::<_, i32>(1)?),
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter_map(|(chunk, owner)| Some((chunk, parse_uuid(&owner)?)))
+        .collect();
+
+    let mut stmt = connection.prepare_cached(
+        "
+        SELECT  owner,
+                trusted
+        FROM    land_claim_trust",
+    )?;
+    let trusts = stmt
+        .query_map(NO_PARAMS, |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter_map(|(owner, trusted)| Some((parse_uuid(&owner)?, parse_uuid(&trusted)?)))
+        .collect();
+
+    Ok((claims, trusts))
+}
+
+fn parse_uuid(uuid: &str) -> Option<Uuid> {
+    Uuid::parse_str(uuid)
+        .map_err(|e| warn!(?e, ?uuid, "Invalid UUID in land claims"))
+        .ok()
+}
+
+fn execute_batch_update(
+    changes: Vec<LandClaimChange>,
+    connection: &mut VelorenConnection,
+) -> Result<(), PersistenceError> {
+    let mut transaction = connection.connection.transaction()?;
+    transaction.set_drop_behavior(DropBehavior::Rollback);
+    trace!("Transaction started for land claim batch update");
+
+    for change in changes {
+        match change {
+            LandClaimChange::Claim {
+                chunk,
+                owner: Some(owner),
+            } => {
+                let mut stmt = transaction.prepare_cached(
+                    "
+                    REPLACE
+                    INTO    land_claim (x,
+                                        y,
+                                        owner)
+                    VALUES  (?1, ?2, ?3)",
+                )?;
+                stmt.execute(&[&chunk.x as &dyn ToSql, &chunk.y, &owner.to_string()])?;
+            },
+            LandClaimChange::Claim { chunk, owner: None } => {
+                let mut stmt = transaction.prepare_cached(
+                    "
+                    DELETE
+                    FROM    land_claim
+                    WHERE   x = ?1
+                    AND     y = ?2",
+                )?;
+                stmt.execute(&[&chunk.x, &chunk.y])?;
+            },
+            LandClaimChange::Trust {
+                owner,
+                trusted,
+                trust: true,
+            } => {
+                let mut stmt = transaction.prepare_cached(
+                    "
+                    REPLACE
+                    INTO    land_claim_trust (owner,
+                                              trusted)
+                    VALUES  (?1, ?2)",
+                )?;
+                stmt.execute(&[&owner.to_string(), &trusted.to_string()])?;
+            },
+            LandClaimChange::Trust {
+                owner,
+                trusted,
+                trust: false,
+            } => {
+                let mut stmt = transaction.prepare_cached(
+                    "
+                    DELETE
+                    FROM    land_claim_trust
+                    WHERE   owner = ?1
+                    AND     trusted = ?2",
+                )?;
+                stmt.execute(&[&owner.to_string(), &trusted.to_string()])?;
+            },
+        }
+    }
+    transaction.commit()?;
+
+    trace!("Commit for land claim batch update completed");
+    Ok(())
+}
@@ -6,7 +6,11 @@ pub mod character_updater;
 mod diesel_to_rusqlite;
 pub mod error;
 mod json_models;
+pub mod land_claims;
 mod models;
+#[cfg(feature = "plugins")]
+pub mod plugin_storage;
+pub mod storage;
 
 use crate::persistence::character_updater::PetPersistenceData;
 use common::comp;
@@ -29,6 +33,7 @@ pub type PersistedComponents = (
     comp::Inventory,
     Option<comp::Waypoint>,
     Vec<PetPersistenceData>,
+    comp::QuestLog,
 );
 
 pub type EditableComponents = (comp::Body,);
@@ -12,6 +12,7 @@ pub struct Item {
     pub item_definition_id: String,
     pub stack_size: i32,
     pub position: String,
+    pub properties: String,
 }
 
 pub struct Body {
@@ -40,3 +41,10 @@ pub struct Pet {
     pub body_variant: String,
     pub body_data: String,
 }
+
+pub struct Quest {
+    pub character_id: i64,
+    pub quest: String,
+    pub completed: bool,
+    pub progress: String,
+}
@@ -0,0 +1,119 @@
+//! Database operations for the key/value storage of plugins
+
+use crate::persistence::{
+    error::PersistenceError, establish_connection, ConnectionMode, DatabaseSettings,
+    VelorenConnection,
+};
+use common_state::plugin::storage::PluginStorageChange;
+use rusqlite::{DropBehavior, ToSql, NO_PARAMS};
+use std::sync::{Arc, RwLock};
+use tracing::{error, trace};
+
+/// A unidirectional messaging resource for saving the changes plugins made to
+/// their storage in a background thread.
+pub struct PluginStorageUpdater {
+    update_tx: Option<crossbeam_channel::Sender<Vec<PluginStorageChange>>>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl PluginStorageUpdater {
+    pub fn new(settings: Arc<RwLock<DatabaseSettings>>) -> rusqlite::Result<Self> {
+        let (update_tx, update_rx) = crossbeam_channel::unbounded::<Vec<PluginStorageChange>>();
+
+        let builder = std::thread::Builder::new().name("plugin_storage_updater".into());
+        let handle = builder
+            .spawn(move || {
+                // Unwrap here is safe as there is no code that can panic when the write lock is
+                // taken that could cause the RwLock to become poisoned.
+                let mut conn =
+                    establish_connection(&*settings.read().unwrap(), ConnectionMode::ReadWrite);
+                while let Ok(changes) = update_rx.recv() {
+                    conn.update_log_mode(&settings);
+                    if let Err(e) = execute_batch_update(changes, &mut conn) {
+                        error!(?e, "Error during plugin storage batch update");
+                    }
+                }
+            })
+            .unwrap();
+
+        Ok(Self {
+            update_tx: Some(update_tx),
+            handle: Some(handle),
+        })
+    }
+
+    /// Persists the changes, in order
+    pub fn batch_update(&self, changes: Vec<PluginStorageChange>) {
+        if let Err(e) = self.update_tx.as_ref().unwrap().send(changes) {
+            error!(?e, "Could not send plugin storage updates");
+        }
+    }
+}
+
+impl Drop for PluginStorageUpdater {
+    fn drop(&mut self) {
+        drop(self.update_tx.take());
+        if let Err(e) = self.handle.take().unwrap().join() {
+            error!(?e, "Error from joining plugin storage update thread");
+        }
+    }
+}
+
+/// Loads the whole plugin storage as `(plugin, key, value)` entries
+pub fn load_plugin_storage(
+    settings: &DatabaseSettings,
+) -> Result<Vec<(String, String, Vec<u8>)>, PersistenceError> {
+    let connection = establish_connection(settings, ConnectionMode::ReadOnly);
+    let mut stmt = connection.prepare_cached(
+        "
+        SELECT  plugin,
+                key,
+                value
+        FROM    plugin_storage",
+    )?;
+
+    let entries = stmt
+        .query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(entries)
+}
+
+fn execute_batch_update(
+    changes: Vec<PluginStorageChange>,
+    connection: &mut VelorenConnection,
+) -> Result<(), PersistenceError> {
+    let mut transaction = connection.connection.transaction()?;
+    transaction.set_drop_behavior(DropBehavior::Rollback);
+    trace!("Transaction started for plugin storage batch update");
+
+    for change in changes {
+        match change.value {
+            Some(value) => {
+                let mut stmt = transaction.prepare_cached(
+                    "
+                    REPLACE
+                    INTO    plugin_storage (plugin,
+                                            key,
+                                            value)
+                    VALUES  (?1, ?2, ?3)",
+                )?;
+                stmt.execute(&[&change.plugin as &dyn ToSql, &change.key, &value])?;
+            },
+            None => {
+                let mut stmt = transaction.prepare_cached(
+                    "
+                    DELETE
+                    FROM    plugin_storage
+                    WHERE   plugin = ?1
+                    AND     key = ?2",
+                )?;
+                stmt.execute(&[&change.plugin, &change.key])?;
+            },
+        }
+    }
+    transaction.commit()?;
+
+    trace!("Commit for plugin storage batch update completed");
+    Ok(())
+}
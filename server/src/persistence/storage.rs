@@ -0,0 +1,242 @@
+//! Database operations for the storage containers players place in the world
+
+use crate::persistence::{
+    error::PersistenceError,
+    establish_connection,
+    json_models::{ItemProperties, StoredItem, StoredSlot},
+    ConnectionMode, DatabaseSettings,
+};
+use common::comp::{
+    item::{tool::AbilityMap, MaterialStatManifest},
+    storage::{StorageAccess, StorageContainer, StorageContainers, STORAGE_SLOTS},
+    Inventory, Item,
+};
+use rusqlite::{ToSql, Transaction, NO_PARAMS};
+use tracing::warn;
+use vek::*;
+
+/// A storage container as stored in the database
+pub struct StorageRow {
+    pub pos: Vec3<i32>,
+    pub owner: i64,
+    pub access: String,
+    pub items: String,
+}
+
+/// A change to persist; `None` deletes the container at that position
+pub type StorageChange = (Vec3<i32>, Option<StorageRow>);
+
+/// Takes the containers changed since the last call, to be saved in the same
+/// transaction as the characters so items moved between them are never
+/// duplicated or lost
+pub fn take_changes(containers: &mut StorageContainers) -> Vec<StorageChange> {
+    containers
+        .take_changes()
+        .into_iter()
+        .map(|pos| {
+            (
+                pos,
+                containers
+                    .get(pos)
+                    .map(|container| convert_container_to_database(pos, container)),
+            )
+        })
+        .collect()
+}
+
+/// Loads all the storage containers. Containers that fail to load are skipped
+/// with a warning, rather than preventing the server from starting.
+pub fn load_storage_containers(
+    settings: &DatabaseSettings,
+    ability_map: &AbilityMap,
+    msm: &MaterialStatManifest,
+) -> Result<Vec<(Vec3<i32>, StorageContainer)>, PersistenceError> {
+    let connection = establish_connection(settings, ConnectionMode::ReadOnly);
+    let mut stmt = connection.prepare_cached(
+        "
+        SELECT  x,
+                y,
+                z,
+                owner,
+                access,
+                items
+        FROM    storage_container",
+    )?;
+
+    let rows = stmt
+        .query_map(NO_PARAMS, |row| {
+            Ok(StorageRow {
+                pos: Vec3::new(row.get(0)?, row.get(1)?, row.get(2)?),
+                owner: row.get(3)?,
+                access: row.get(4)?,
+                items: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(
+            |row| match convert_container_from_database(&row, ability_map, msm) {
+                Ok(container) => Some((row.pos, container)),
+                Err(e) => {
+                    warn!(?e, ?row.pos, "Failed to load storage container");
+                    None
+                },
+            },
+        )
+        .collect())
+}
+
+fn convert_container_to_database(pos: Vec3<i32>, container: &StorageContainer) -> StorageRow {
+    let items = container
+        .inventory
+        .slots_with_id()
+        .filter_map(|(slot, item)| {
+            Some(StoredSlot {
+                slot,
+                item: convert_item_to_database(item.as_ref()?),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    StorageRow {
+        pos,
+        owner: container.owner,
+        access: access_to_db_string(container.access).to_owned(),
+        items: serde_json::to_string(&items).expect("failed to serialize StoredSlot"),
+    }
+}
+
+fn convert_item_to_database(item: &Item) -> StoredItem {
+    StoredItem {
+        item_definition_id: item.item_definition_id().to_owned(),
+        amount: item.amount(),
+        properties: ItemProperties {
+            durability_lost: item.durability_lost(),
+        },
+        components: item
+            .components()
+            .iter()
+            .map(convert_item_to_database)
+            .collect(),
+    }
+}
+
+fn convert_container_from_database(
+    row: &StorageRow,
+    ability_map: &AbilityMap,
+    msm: &MaterialStatManifest,
+) -> Result<StorageContainer, PersistenceError> {
+    let access = access_from_db_string(&row.access).ok_or_else(|| {
+        PersistenceError::ConversionError(format!("Invalid storage access: {}", row.access))
+    })?;
+    let slots = serde_json::from_str::<Vec<StoredSlot>>(&row.items)
+        .map_err(|e| PersistenceError::ConversionError(e.to_string()))?;
+
+    let mut inventory = Inventory::new_container(STORAGE_SLOTS);
+    for StoredSlot { slot, item } in slots {
+        let item = convert_item_from_database(item, ability_map, msm)?;
+        if !matches!(inventory.insert_at(slot, item), Ok(None)) {
+            return Err(PersistenceError::ConversionError(format!(
+                "Error inserting item into storage container, position: {:?}",
+                slot
+            )));
+        }
+    }
+
+    Ok(StorageContainer {
+        owner: row.owner,
+        access,
+        inventory,
+    })
+}
+
+fn convert_item_from_database(
+    stored: StoredItem,
+    ability_map: &AbilityMap,
+    msm: &MaterialStatManifest,
+) -> Result<Item, PersistenceError> {
+    let mut item = Item::new_from_asset(&stored.item_definition_id).map_err(|err| {
+        PersistenceError::AssetError(format!(
+            "Error loading item asset: {} - {}",
+            stored.item_definition_id, err
+        ))
+    })?;
+    if item.is_stackable() {
+        item.set_amount(stored.amount).map_err(|_| {
+            PersistenceError::ConversionError("Error setting amount for item".to_owned())
+        })?;
+    }
+    for component in stored.components {
+        item.add_component(
+            convert_item_from_database(component, ability_map, msm)?,
+            ability_map,
+            msm,
+        );
+    }
+    item.set_durability_lost(stored.properties.durability_lost, ability_map, msm);
+    Ok(item)
+}
+
+fn access_to_db_string(access: StorageAccess) -> &'static str {
+    match access {
+        StorageAccess::Owner => "Owner",
+        StorageAccess::Group => "Group",
+        StorageAccess::Public => "Public",
+    }
+}
+
+fn access_from_db_string(access: &str) -> Option<StorageAccess> {
+    match access {
+        "Owner" => Some(StorageAccess::Owner),
+        "Group" => Some(StorageAccess::Group),
+        "Public" => Some(StorageAccess::Public),
+        _ => None,
+    }
+}
+
+/// Saves the changes to storage containers, as part of a character batch
+/// update
+pub fn update_containers(
+    changes: Vec<StorageChange>,
+    transaction: &mut Transaction,
+) -> Result<(), PersistenceError> {
+    for (pos, row) in changes {
+        match row {
+            Some(row) => {
+                let mut stmt = transaction.prepare_cached(
+                    "
+                    REPLACE
+                    INTO    storage_container (x,
+                                               y,
+                                               z,
+                                               owner,
+                                               access,
+                                               items)
+                    VALUES  (?1, ?2, ?3, ?4, ?5, ?6)",
+                )?;
+                stmt.execute(&[
+                    &pos.x as &dyn ToSql,
+                    &pos.y,
+                    &pos.z,
+                    &row.owner,
+                    &row.access,
+                    &row.items,
+                ])?;
+            },
+            None => {
+                let mut stmt = transaction.prepare_cached(
+                    "
+                    DELETE
+                    FROM    storage_container
+                    WHERE   x = ?1
+                    AND     y = ?2
+                    AND     z = ?3",
+                )?;
+                stmt.execute(&[&pos.x, &pos.y, &pos.z])?;
+            },
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+//! Recording of the messages sent to a client, for replays
+use crate::{client::Client, sys::subscription::initialize_region_subscription};
+use chrono::Utc;
+use common::{
+    calendar::Calendar,
+    comp::{Admin, Player, Stats},
+    resources::TimeOfDay,
+    uid::Uid,
+};
+use common_net::{
+    msg::{CharacterInfo, PlayerInfo, PlayerListUpdate, ServerGeneral},
+    replay::{self, ReplayError, ReplayHeader, ReplayWriter},
+};
+use hashbrown::HashMap;
+use specs::{Entity as EcsEntity, Join, World, WorldExt};
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+use tracing::{error, info};
+
+/// Directory of the data dir where replays are written
+pub const REPLAYS_DIR: &str = "replays";
+
+/// Number of recordings in progress, so messages are only encoded for them
+/// when needed
+static RECORDINGS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn any_recording() -> bool { RECORDINGS.load(Ordering::Relaxed) > 0 }
+
+/// Encodes a message for the recordings, if there are any.
+pub fn encode(msg: &ServerGeneral) -> Option<Vec<u8>> {
+    if any_recording() {
+        replay::encode(msg)
+            .map_err(|err| error!(?err, "Failed to encode a message for recording"))
+            .ok()
+    } else {
+        None
+    }
+}
+
+/// A replay being written, stored in the [`crate::client::Client`] it records
+pub struct Recording {
+    writer: ReplayWriter<BufWriter<File>>,
+    path: PathBuf,
+    start: Instant,
+    failed: bool,
+}
+
+impl Recording {
+    /// Starts recording into a new file in `dir`.
+    pub fn start(dir: &Path, player_alias: &str) -> Result<Self, ReplayError> {
+        std::fs::create_dir_all(dir)?;
+        let now = Utc::now();
+        let path = dir.join(format!(
+            "{}_{}.replay",
+            player_alias,
+            now.format("%Y-%m-%d_%H-%M-%S")
+        ));
+        let writer = ReplayWriter::new(BufWriter::new(File::create(&path)?), &ReplayHeader {
+            server_version: common::util::GIT_HASH.to_string(),
+            player_alias: player_alias.to_owned(),
+            start_time: now.timestamp(),
+        })?;
+        RECORDINGS.fetch_add(1, Ordering::Relaxed);
+        info!("Started recording {}", path.display());
+        Ok(Self {
+            writer,
+            path,
+            start: Instant::now(),
+            failed: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    pub fn write(&mut self, msg: &ServerGeneral) {
+        if let Ok(msg) = replay::encode(msg) {
+            self.write_encoded(&msg);
+        }
+    }
+
+    /// Appends a message encoded with [`encode`].
+    pub fn write_encoded(&mut self, msg: &[u8]) {
+        // Only log the first failure, e.g. when the disk is full
+        if !self.failed {
+            let time = self.start.elapsed().as_secs_f64();
+            if let Err(err) = self.writer.write_encoded(time, msg) {
+                error!(%err, "Failed to write to recording {}", self.path.display());
+                self.failed = true;
+            }
+        }
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        RECORDINGS.fetch_sub(1, Ordering::Relaxed);
+        if let Err(err) = self.writer.flush() {
+            error!(%err, "Failed to write to recording {}", self.path.display());
+        }
+        info!("Stopped recording {}", self.path.display());
+    }
+}
+
+/// Starts recording the messages sent to the client of `entity` into a new
+/// file in `dir`, returning its path.
+///
+/// The replay begins with what the client already knows of the world, apart
+/// from the terrain which is only recorded as it changes or gets loaded.
+pub fn start_recording(world: &World, entity: EcsEntity, dir: &Path) -> Result<PathBuf, String> {
+    let alias = world
+        .read_storage::<Player>()
+        .get(entity)
+        .map(|player| player.alias.clone())
+        .ok_or("Only players can be recorded")?;
+    let path = {
+        let clients = world.read_storage::<Client>();
+        let client = clients.get(entity).ok_or("Only players can be recorded")?;
+        let mut slot = client.recording.lock().unwrap();
+        if let Some(recording) = &*slot {
+            return Err(format!(
+                "{} is already being recorded to {}",
+                alias,
+                recording.path().display()
+            ));
+        }
+        let mut recording = Recording::start(dir, &alias)
+            .map_err(|err| format!("Failed to start recording: {}", err))?;
+        recording.write(&ServerGeneral::TimeOfDay(
+            *world.read_resource::<TimeOfDay>(),
+            world.read_resource::<Calendar>().clone(),
+        ));
+        let player_list = (
+            &world.read_storage::<Uid>(),
+            &world.read_storage::<Player>(),
+            world.read_storage::<Stats>().maybe(),
+            world.read_storage::<Admin>().maybe(),
+        )
+            .join()
+            .map(|(uid, player, stats, admin)| {
+                (*uid, PlayerInfo {
+                    is_online: true,
+                    is_moderator: admin.is_some(),
+                    player_alias: player.alias.clone(),
+                    character: stats.map(|stats| CharacterInfo {
+                        name: stats.name.clone(),
+                    }),
+                })
+            })
+            .collect::<HashMap<_, _>>();
+        recording.write(&ServerGeneral::PlayerListUpdate(PlayerListUpdate::Init(
+            player_list,
+        )));
+        let path = recording.path().to_owned();
+        *slot = Some(recording);
+        path
+    };
+
+    // Sends the entities in view again, through the recording. The client
+    // already knowing them is harmless.
+    initialize_region_subscription(world, entity);
+    if let Some(uid) = world.read_storage::<Uid>().get(entity) {
+        if let Some(client) = world.read_storage::<Client>().get(entity) {
+            if let Some(recording) = client.recording.lock().unwrap().as_mut() {
+                recording.write(&ServerGeneral::SetPlayerEntity(*uid));
+            }
+        }
+    }
+    Ok(path)
+}
+
+/// Stops recording the client of `entity`, returning the path of the replay.
+pub fn stop_recording(world: &World, entity: EcsEntity) -> Option<PathBuf> {
+    let clients = world.read_storage::<Client>();
+    let recording = clients.get(entity)?.recording.lock().unwrap().take()?;
+    Some(recording.path().to_owned())
+}
@@ -8,6 +8,7 @@ use common::{
     trade, LoadoutBuilder,
 };
 use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
 use tracing::warn;
 use world::{
@@ -26,7 +27,7 @@ pub struct Entity {
     pub brain: Brain,
 }
 
-#[derive(Clone, Copy, strum::EnumIter)]
+#[derive(Clone, Copy, strum::EnumIter, Serialize, Deserialize)]
 pub enum RtSimEntityKind {
     Random,
     Cultist,
@@ -607,7 +608,7 @@ impl Entity {
 }
 
 #[derive(Clone, Debug)]
-enum Travel {
+pub(super) enum Travel {
     // The initial state all entities start in, and a fallback for when a state has stopped making
     // sense. Non humanoids will always revert to this state after reaching their goal since the
     // current site they are in doesn't change their behavior.
@@ -655,11 +656,11 @@ impl Default for Travel {
 
 #[derive(Default)]
 pub struct Brain {
-    begin: Option<Id<Site>>,
-    tgt: Option<Id<Site>>,
-    route: Travel,
-    last_visited: Option<Id<Site>>,
-    memories: Vec<Memory>,
+    pub(super) begin: Option<Id<Site>>,
+    pub(super) tgt: Option<Id<Site>>,
+    pub(super) route: Travel,
+    pub(super) last_visited: Option<Id<Site>>,
+    pub(super) memories: Vec<Memory>,
 }
 
 impl Brain {
@@ -3,6 +3,7 @@
 mod chunks;
 mod entity;
 mod load_chunks;
+pub mod persistence;
 mod tick;
 mod unload_chunks;
 
@@ -18,6 +19,7 @@ use common_state::State;
 use rand::prelude::*;
 use slab::Slab;
 use specs::{DispatcherBuilder, WorldExt};
+use std::path::PathBuf;
 use vek::*;
 
 pub use self::entity::{Brain, Entity, RtSimEntityKind};
@@ -26,6 +28,12 @@ pub struct RtSim {
     tick: u64,
     chunks: Chunks,
     entities: Slab<Entity>,
+    /// Seed of the world, to recognize snapshots of other worlds
+    world_seed: u32,
+    /// File the state is saved to, if it is persisted
+    snapshot_path: Option<PathBuf>,
+    /// `Time` of the last snapshot
+    last_snapshot: f64,
 }
 
 impl RtSim {
@@ -34,6 +42,9 @@ impl RtSim {
             tick: 0,
             chunks: Chunks::new(world_chunk_size),
             entities: Slab::new(),
+            world_seed: 0,
+            snapshot_path: None,
+            last_snapshot: 0.0,
         }
     }
 
@@ -109,16 +120,36 @@ pub fn init(
     #[cfg(feature = "worldgen")] world: &world::World,
     #[cfg(feature = "worldgen")] index: world::IndexRef,
     #[cfg(feature = "worldgen")] spawn_point: crate::SpawnPoint,
+    #[cfg(feature = "worldgen")] snapshot_path: Option<PathBuf>,
 ) {
     #[cfg(feature = "worldgen")]
     let mut rtsim = RtSim::new(world.sim().get_size());
     #[cfg(not(feature = "worldgen"))]
     let mut rtsim = RtSim::new(Vec2::new(40, 40));
 
+    #[cfg(feature = "worldgen")]
+    let restored = {
+        let time = state.get_time();
+        rtsim.world_seed = world.sim().seed;
+        rtsim.last_snapshot = time;
+        let snapshot = snapshot_path
+            .as_deref()
+            .and_then(|path| persistence::load(path, world, time));
+        rtsim.snapshot_path = snapshot_path;
+        snapshot.map_or(false, |snapshot| {
+            rtsim.tick = snapshot.tick;
+            for entity in snapshot.entities {
+                rtsim.entities.insert(entity);
+            }
+            tracing::info!("Restored {} rtsim entities", rtsim.entities.len());
+            true
+        })
+    };
+
     // TODO: Determine number of rtsim entities based on things like initial site
     // populations rather than world size
     #[cfg(feature = "worldgen")]
-    {
+    if !restored {
         for _ in 0..world.sim().get_size().product() / 400 {
             let pos = rtsim
                 .chunks
@@ -0,0 +1,457 @@
+//! Snapshots of the rtsim state, so the simulated world carries on where it
+//! was left when the server restarts.
+//!
+//! Times are saved relative to the [`Time`] of the snapshot, because the
+//! [`Time`] of a new server starts at zero again.
+//!
+//! [`Time`]: common::resources::Time
+use super::{entity::Travel, *};
+use atomicwrites::{AtomicFile, OverwriteBehavior};
+use common::{rtsim::MemoryItem, store::Id};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    any::{type_name, Any},
+    fs::File,
+    io::{self, Read as _, Write as _},
+    path::{Path, PathBuf},
+};
+use tracing::{debug, error, info};
+use world::{
+    civ::{Civs, Site},
+    World,
+};
+
+/// Seconds between two snapshots
+const SNAPSHOT_INTERVAL: f64 = 300.0;
+
+/// Returns the file the rtsim state is saved to.
+///
+/// If the `VELOREN_RTSIM` environment variable is set, this will be used as
+/// the snapshot file instead.
+pub fn snapshot_path(data_dir: &Path) -> PathBuf {
+    std::env::var("VELOREN_RTSIM")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| data_dir.join("rtsim.dat"))
+}
+
+/// The rtsim state, as restored from a snapshot
+pub struct Snapshot {
+    pub tick: u64,
+    pub entities: Vec<Entity>,
+}
+
+impl RtSim {
+    /// Save the state if the last snapshot is older than
+    /// [`SNAPSHOT_INTERVAL`].
+    pub fn maintain(&mut self, time: f64) {
+        if self.snapshot_path.is_some() && time - self.last_snapshot >= SNAPSHOT_INTERVAL {
+            self.save(time);
+        }
+    }
+
+    /// Save the state to the snapshot file, if the state is persisted.
+    pub fn save(&mut self, time: f64) {
+        self.last_snapshot = time;
+        let path = match &self.snapshot_path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let raw = version::Current::new(self, time);
+        let bytes = match bincode::serialize(&raw) {
+            Err(err) => {
+                error!("Failed to serialize rtsim state: {:?}", err);
+                return;
+            },
+            Ok(bytes) => bytes,
+        };
+
+        let atomic_file = AtomicFile::new(path, OverwriteBehavior::AllowOverwrite);
+        if let Err(err) = atomic_file.write(|file| file.write_all(&bytes)) {
+            error!("Failed to write rtsim state to file: {:?}", err);
+        } else {
+            debug!("Saved {} rtsim entities", raw.entities.len());
+        }
+    }
+}
+
+/// Load the snapshot at `path`, if there is one and it belongs to `world`.
+///
+/// A snapshot that can't be read is moved away, so it isn't overwritten by the
+/// next snapshot.
+pub fn load(path: &Path, world: &World, time: f64) -> Option<Snapshot> {
+    let bytes = match File::open(path) {
+        Ok(f) => match std::io::BufReader::new(f)
+            .bytes()
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                error!("Failed to read rtsim state from file: {:?}", err);
+                return None;
+            },
+        },
+        Err(_) => return None,
+    };
+
+    match version::try_load(std::io::Cursor::new(bytes)) {
+        Some(raw)
+            if raw.world_seed() != world.sim().seed
+                || raw.world_size() != world.sim().get_size() =>
+        {
+            info!("The rtsim snapshot belongs to another world, starting a new simulation instead");
+            None
+        },
+        Some(raw) => {
+            let snapshot = raw.into_snapshot(world.civs(), time);
+            if snapshot.is_none() {
+                error!("The rtsim snapshot refers to sites which don't exist in this world");
+            }
+            snapshot
+        },
+        None => {
+            // Find an untaken name for a backup
+            let mut backup_path = path.to_owned();
+            backup_path.set_extension("dat_backup_0");
+            let mut i = 1;
+            while backup_path.exists() {
+                backup_path.set_extension(format!("dat_backup_{}", i));
+                i += 1;
+            }
+
+            error!(
+                "Failed to load rtsim state, moving possibly corrupt (or too new) data to {:?} \
+                 for you to repair.",
+                backup_path
+            );
+            if let Err(err) = std::fs::rename(path, backup_path) {
+                error!("Failed to rename invalid rtsim state file: {:?}", err);
+            }
+            None
+        },
+    }
+}
+
+/// Conversion of a raw format into the rtsim state of the current world.
+trait RawSnapshot {
+    fn world_seed(&self) -> u32;
+
+    fn world_size(&self) -> Vec2<u32>;
+
+    /// Returns `None` if a site or track doesn't exist in `civs`
+    fn into_snapshot(self: Box<Self>, civs: &Civs, time: f64) -> Option<Snapshot>;
+}
+
+/// # Adding a new snapshot format version
+///
+/// This follows the same steps as adding a new chunk format version to
+/// [`crate::terrain_persistence`], with [`RawSnapshot`] instead of
+/// `From<{YourRawFormat}> for Chunk`.
+mod version {
+    use super::*;
+
+    /// The newest supported raw format type. This should be changed every time
+    /// a new raw format is added.
+    pub type Current = V1;
+
+    type LoadSnapshotFn<R> = fn(R) -> Result<Box<dyn RawSnapshot>, (&'static str, bincode::Error)>;
+    fn loaders<'a, R: io::Read + Clone>() -> &'a [LoadSnapshotFn<R>] { &[load_raw::<V1, _>] }
+
+    // Convert back to current
+
+    impl V1 {
+        pub fn new(rtsim: &RtSim, time: f64) -> Self {
+            let site = |id: Option<Id<Site>>| id.map(|id| id.id());
+            Self {
+                version: version_magic(1),
+                world_seed: rtsim.world_seed,
+                world_size: rtsim.chunks.size(),
+                tick: rtsim.tick,
+                entities: rtsim
+                    .entities
+                    .iter()
+                    .map(|(_, entity)| EntityV1 {
+                        pos: entity.pos,
+                        seed: entity.seed,
+                        last_time_ticked: entity.last_time_ticked - time,
+                        travel_to: entity.controller.travel_to.clone(),
+                        speed_factor: entity.controller.speed_factor,
+                        kind: entity.kind,
+                        begin: site(entity.brain.begin),
+                        tgt: site(entity.brain.tgt),
+                        route: match &entity.brain.route {
+                            Travel::Lost => TravelV1::Lost,
+                            Travel::InSite { site_id } => TravelV1::InSite {
+                                site_id: site_id.id(),
+                            },
+                            Travel::Direct { target_id } => TravelV1::Direct {
+                                target_id: target_id.id(),
+                            },
+                            Travel::CustomPath {
+                                target_id,
+                                path,
+                                progress,
+                            } => TravelV1::CustomPath {
+                                target_id: target_id.id(),
+                                path: path.clone(),
+                                progress: *progress,
+                            },
+                            Travel::Path {
+                                target_id,
+                                track_id,
+                                progress,
+                                reversed,
+                            } => TravelV1::Path {
+                                target_id: target_id.id(),
+                                track_id: track_id.id(),
+                                progress: *progress,
+                                reversed: *reversed,
+                            },
+                            Travel::DirectRaid {
+                                target_id,
+                                home_id,
+                                raid_complete,
+                                time_to_move,
+                            } => TravelV1::DirectRaid {
+                                target_id: target_id.id(),
+                                home_id: home_id.id(),
+                                raid_complete: *raid_complete,
+                                time_to_move: time_to_move.map(|t| t - time),
+                            },
+                            Travel::Idle => TravelV1::Idle,
+                        },
+                        last_visited: site(entity.brain.last_visited),
+                        // Moods are rolled again
+                        memories: entity
+                            .brain
+                            .memories
+                            .iter()
+                            .filter_map(|memory| {
+                                Some(MemoryV1 {
+                                    item: match &memory.item {
+                                        MemoryItem::CharacterInteraction { name } => {
+                                            MemoryItemV1::CharacterInteraction {
+                                                name: name.clone(),
+                                            }
+                                        },
+                                        MemoryItem::CharacterFight { name } => {
+                                            MemoryItemV1::CharacterFight { name: name.clone() }
+                                        },
+                                        MemoryItem::Mood { .. } => return None,
+                                    },
+                                    time_to_forget: memory.time_to_forget - time,
+                                })
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            }
+        }
+    }
+
+    /// Version 1 of the raw snapshot format.
+    #[derive(Serialize, Deserialize)]
+    pub struct V1 {
+        #[serde(deserialize_with = "version::<_, 1>")]
+        pub version: u64,
+        pub world_seed: u32,
+        pub world_size: Vec2<u32>,
+        pub tick: u64,
+        pub entities: Vec<EntityV1>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct EntityV1 {
+        pub pos: Vec3<f32>,
+        pub seed: u32,
+        pub last_time_ticked: f64,
+        pub travel_to: Option<(Vec3<f32>, String)>,
+        pub speed_factor: f32,
+        pub kind: RtSimEntityKind,
+        pub begin: Option<u64>,
+        pub tgt: Option<u64>,
+        pub route: TravelV1,
+        pub last_visited: Option<u64>,
+        pub memories: Vec<MemoryV1>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum TravelV1 {
+        Lost,
+        InSite {
+            site_id: u64,
+        },
+        Direct {
+            target_id: u64,
+        },
+        CustomPath {
+            target_id: u64,
+            path: Vec<Vec2<i32>>,
+            progress: usize,
+        },
+        Path {
+            target_id: u64,
+            track_id: u64,
+            progress: usize,
+            reversed: bool,
+        },
+        DirectRaid {
+            target_id: u64,
+            home_id: u64,
+            raid_complete: bool,
+            time_to_move: Option<f64>,
+        },
+        Idle,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct MemoryV1 {
+        pub item: MemoryItemV1,
+        pub time_to_forget: f64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum MemoryItemV1 {
+        CharacterInteraction { name: String },
+        CharacterFight { name: String },
+    }
+
+    impl RawSnapshot for V1 {
+        fn world_seed(&self) -> u32 { self.world_seed }
+
+        fn world_size(&self) -> Vec2<u32> { self.world_size }
+
+        fn into_snapshot(self: Box<Self>, civs: &Civs, time: f64) -> Option<Snapshot> {
+            let this = *self;
+            let site = |id: u64| civs.sites.recreate_id(id);
+            let opt_site = |id: Option<u64>| match id {
+                Some(id) => site(id).map(Some),
+                None => Some(None),
+            };
+            let entities = this
+                .entities
+                .into_iter()
+                .map(|entity| {
+                    let route = match entity.route {
+                        TravelV1::Lost => Travel::Lost,
+                        TravelV1::InSite { site_id } => Travel::InSite {
+                            site_id: site(site_id)?,
+                        },
+                        TravelV1::Direct { target_id } => Travel::Direct {
+                            target_id: site(target_id)?,
+                        },
+                        TravelV1::CustomPath {
+                            target_id,
+                            path,
+                            progress,
+                        } => Travel::CustomPath {
+                            target_id: site(target_id)?,
+                            path,
+                            progress,
+                        },
+                        TravelV1::Path {
+                            target_id,
+                            track_id,
+                            progress,
+                            reversed,
+                        } => Travel::Path {
+                            target_id: site(target_id)?,
+                            track_id: civs.tracks.recreate_id(track_id)?,
+                            progress,
+                            reversed,
+                        },
+                        TravelV1::DirectRaid {
+                            target_id,
+                            home_id,
+                            raid_complete,
+                            time_to_move,
+                        } => Travel::DirectRaid {
+                            target_id: site(target_id)?,
+                            home_id: site(home_id)?,
+                            raid_complete,
+                            time_to_move: time_to_move.map(|t| t + time),
+                        },
+                        TravelV1::Idle => Travel::Idle,
+                    };
+                    Some(Entity {
+                        is_loaded: false,
+                        pos: entity.pos,
+                        seed: entity.seed,
+                        last_time_ticked: entity.last_time_ticked + time,
+                        controller: RtSimController {
+                            travel_to: entity.travel_to,
+                            speed_factor: entity.speed_factor,
+                            events: Vec::new(),
+                        },
+                        kind: entity.kind,
+                        brain: Brain {
+                            begin: opt_site(entity.begin)?,
+                            tgt: opt_site(entity.tgt)?,
+                            route,
+                            last_visited: opt_site(entity.last_visited)?,
+                            memories: entity
+                                .memories
+                                .into_iter()
+                                .map(|memory| Memory {
+                                    item: match memory.item {
+                                        MemoryItemV1::CharacterInteraction { name } => {
+                                            MemoryItem::CharacterInteraction { name }
+                                        },
+                                        MemoryItemV1::CharacterFight { name } => {
+                                            MemoryItem::CharacterFight { name }
+                                        },
+                                    },
+                                    time_to_forget: memory.time_to_forget + time,
+                                })
+                                .collect(),
+                        },
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(Snapshot {
+                tick: this.tick,
+                entities,
+            })
+        }
+    }
+
+    // Utility things
+
+    fn version_magic(n: u16) -> u64 { (n as u64) | (0x7254_5349_4D00 << 16) }
+
+    fn version<'de, D: serde::Deserializer<'de>, const V: u16>(de: D) -> Result<u64, D::Error> {
+        u64::deserialize(de).and_then(|x| {
+            if x == version_magic(V) {
+                Ok(x)
+            } else {
+                Err(serde::de::Error::invalid_value(
+                    serde::de::Unexpected::Unsigned(x),
+                    &"incorrect magic/version bytes",
+                ))
+            }
+        })
+    }
+
+    fn load_raw<Raw: Any + RawSnapshot + DeserializeOwned, R: io::Read + Clone>(
+        reader: R,
+    ) -> Result<Box<dyn RawSnapshot>, (&'static str, bincode::Error)> {
+        bincode::deserialize_from::<_, Raw>(reader)
+            .map(|raw| Box::new(raw) as Box<dyn RawSnapshot>)
+            .map_err(|e| (type_name::<Raw>(), e))
+    }
+
+    pub fn try_load<R: io::Read + Clone>(reader: R) -> Option<Box<dyn RawSnapshot>> {
+        loaders()
+            .iter()
+            .find_map(|load_raw| match load_raw(reader.clone()) {
+                Ok(raw) => Some(raw),
+                Err((raw_name, e)) => {
+                    debug!(
+                        "Attempt to load rtsim state with raw format `{}` failed: {:?}",
+                        raw_name, e
+                    );
+                    None
+                },
+            })
+    }
+}
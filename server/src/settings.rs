@@ -1,6 +1,8 @@
 pub mod admin;
 pub mod banlist;
 mod editable;
+pub mod mutelist;
+pub mod permissions;
 pub mod server_description;
 pub mod whitelist;
 
@@ -10,6 +12,8 @@ pub use admin::{AdminRecord, Admins};
 pub use banlist::{
     Ban, BanAction, BanEntry, BanError, BanErrorKind, BanInfo, BanKind, BanRecord, Banlist,
 };
+pub use mutelist::{Channel, Mute, MuteAction, MuteInfo, Mutelist};
+pub use permissions::{Capability, Permissions};
 pub use server_description::ServerDescription;
 pub use whitelist::{Whitelist, WhitelistInfo, WhitelistRecord};
 
@@ -34,8 +38,10 @@ const CONFIG_DIR: &str = "server_config";
 const SETTINGS_FILENAME: &str = "settings.ron";
 const WHITELIST_FILENAME: &str = "whitelist.ron";
 const BANLIST_FILENAME: &str = "banlist.ron";
+const MUTELIST_FILENAME: &str = "mutelist.ron";
 const SERVER_DESCRIPTION_FILENAME: &str = "description.ron";
 const ADMINS_FILENAME: &str = "admins.ron";
+const PERMISSIONS_FILENAME: &str = "permissions.ron";
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct X509FilePair {
@@ -88,6 +94,42 @@ impl CalendarMode {
     }
 }
 
+/// Limits on how fast players may chat and use commands, and how the server
+/// responds to players exceeding them. Admins are exempt.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatLimits {
+    /// Messages and commands a player may send in quick succession
+    pub burst: f32,
+    /// Messages and commands per second a player may send in the long run
+    pub per_second: f32,
+    /// Times a player may send the same message within `repeat_window_secs`
+    pub max_repeats: usize,
+    pub repeat_window_secs: f64,
+    /// Offenses answered with a warning, before the player is muted
+    pub warnings: u32,
+    /// Offenses answered with a temporary mute, before the player is kicked
+    pub mutes: u32,
+    pub mute_secs: f64,
+    /// Offenses are forgiven after this long without a new one
+    pub forgive_secs: f64,
+}
+
+impl Default for ChatLimits {
+    fn default() -> Self {
+        Self {
+            burst: 8.0,
+            per_second: 1.0,
+            max_repeats: 3,
+            repeat_window_secs: 30.0,
+            warnings: 2,
+            mutes: 2,
+            mute_secs: 60.0,
+            forgive_secs: 600.0,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Settings {
@@ -111,6 +153,14 @@ pub struct Settings {
     pub safe_spawn: bool,
     pub max_player_for_kill_broadcast: Option<usize>,
     pub calendar_mode: CalendarMode,
+    /// Days the economy of the sites simulates per real-time hour, 0 pauses
+    /// it. Prices change each time three months have been simulated.
+    pub economy_speed: f32,
+    /// Set to None to let players chat as fast as they like
+    pub chat_limits: Option<ChatLimits>,
+    /// Chunks each player may claim to keep others from building there. 0,
+    /// the default, disables land claims
+    pub max_land_claims: u32,
 
     /// Experimental feature. No guaranteed forwards-compatibility, may be
     /// removed at *any time* with no migration.
@@ -135,6 +185,9 @@ impl Default for Settings {
             banned_words_files: Vec::new(),
             max_player_group_size: 6,
             calendar_mode: CalendarMode::Auto,
+            economy_speed: 360.0,
+            chat_limits: Some(ChatLimits::default()),
+            max_land_claims: 0,
             client_timeout: Duration::from_secs(40),
             spawn_town: None,
             safe_spawn: true,
@@ -252,8 +305,10 @@ const MIGRATION_UPGRADE_GUARANTEE: &str = "Any valid file of an old verison shou
 pub struct EditableSettings {
     pub whitelist: Whitelist,
     pub banlist: Banlist,
+    pub mutelist: Mutelist,
     pub server_description: ServerDescription,
     pub admins: Admins,
+    pub permissions: Permissions,
 }
 
 impl EditableSettings {
@@ -261,8 +316,10 @@ impl EditableSettings {
         Self {
             whitelist: Whitelist::load(data_dir),
             banlist: Banlist::load(data_dir),
+            mutelist: Mutelist::load(data_dir),
             server_description: ServerDescription::load(data_dir),
             admins: Admins::load(data_dir),
+            permissions: Permissions::load(data_dir),
         }
     }
 
@@ -0,0 +1,498 @@
+//! Versioned mutelist settings files.
+//!
+//! Mutes keep players from chatting, either everywhere or in one chat
+//! channel, and are recorded with the same history as bans.
+
+use super::MUTELIST_FILENAME as FILENAME;
+use crate::settings::editable::{EditableSetting, Version};
+use authc::Uuid;
+use core::convert::TryFrom;
+use serde::{Deserialize, Serialize};
+
+/// NOTE: Always replace this with the latest mutelist version. Then update the
+/// MutelistRaw, the TryFrom<MutelistRaw> for Mutelist, the previously most
+/// recent module, and add a new module for the latest version!  Please respect
+/// the migration upgrade guarantee found in the parent module with any upgrade.
+pub use self::v1::*;
+
+/// Versioned settings files, one per version.
+#[derive(Deserialize, Serialize)]
+pub enum MutelistRaw {
+    V1(v1::Mutelist),
+}
+
+impl From<Mutelist> for MutelistRaw {
+    fn from(value: Mutelist) -> Self {
+        // Replace variant with that of current latest version.
+        Self::V1(value)
+    }
+}
+
+impl TryFrom<MutelistRaw> for (Version, Mutelist) {
+    type Error = <Mutelist as EditableSetting>::Error;
+
+    fn try_from(value: MutelistRaw) -> Result<Self, <Mutelist as EditableSetting>::Error> {
+        use MutelistRaw::*;
+        Ok(match value {
+            // Latest version (move to old section using the pattern of other old version when it
+            // is no longer latest).
+            V1(mut value) => (value.validate()?, value),
+        })
+    }
+}
+
+type Final = Mutelist;
+
+impl EditableSetting for Mutelist {
+    type Error = MuteError;
+    type Legacy = legacy::Mutelist;
+    type Setting = MutelistRaw;
+
+    const FILENAME: &'static str = FILENAME;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MuteKind {
+    Mute,
+    Unmute,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MuteErrorKind {
+    /// An end date went past a start date.
+    InvalidDateRange {
+        start_date: chrono::DateTime<chrono::Utc>,
+        end_date: chrono::DateTime<chrono::Utc>,
+    },
+    /// Cannot unmute an already-unmuted user.
+    AlreadyUnmuted,
+    /// Permission denied to perform requested action.
+    PermissionDenied(MuteKind),
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct MuteError {
+    kind: MuteErrorKind,
+    /// Uuid of affected user
+    uuid: Uuid,
+    /// Username of affected user (as of mute/unmute time).
+    username: String,
+}
+
+mod legacy {
+    use super::{v1 as next, Final};
+    use serde::{Deserialize, Serialize};
+
+    /// Mutelists were versioned from the start, but hosts editing the file by
+    /// hand may leave out the version, so such files are read in the format of
+    /// the first version.
+    #[derive(Deserialize, Serialize)]
+    #[serde(transparent)]
+    pub struct Mutelist(pub(super) next::Mutelist);
+
+    impl From<Mutelist> for Final {
+        /// Note that legacy files are always valid, which is why we implement
+        /// From rather than TryFrom.
+        fn from(value: Mutelist) -> Self { value.0 }
+    }
+}
+
+mod v1 {
+    use super::{Final, MuteError, MuteErrorKind, MuteKind};
+    use crate::settings::editable::{EditableSetting, Error, Version};
+    use authc::Uuid;
+    use chrono::{prelude::*, Utc};
+    use common::comp::{AdminRole, ChatType};
+    use core::{mem, ops::Deref};
+    use hashbrown::{hash_map, HashMap};
+    use serde::{Deserialize, Serialize};
+    use tracing::warn;
+    /* use super::v2 as next; */
+
+    /// Important: even if the role we are storing here appears to be identical
+    /// to one used in another versioned store (like banlist::Role), we *must*
+    /// have our own versioned copy!  This ensures that if there's an update
+    /// to the role somewhere else, the conversion function between them
+    /// will break, letting people make an intelligent decision.
+    ///
+    /// In particular, *never remove variants from this enum* (or any other enum
+    /// in a versioned settings file) without bumping the version and
+    /// writing a migration that understands how to properly deal with
+    /// existing instances of the old variant.  Otherwise, we will lose
+    /// compatibility with old settings files, since we won't be able to
+    /// deserialize them!
+    #[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+    pub enum Role {
+        Moderator = 0,
+        Admin = 1,
+    }
+
+    impl From<AdminRole> for Role {
+        fn from(value: AdminRole) -> Self {
+            match value {
+                AdminRole::Moderator => Self::Moderator,
+                AdminRole::Admin => Self::Admin,
+            }
+        }
+    }
+
+    /// The chat channels players can be muted in, our own versioned copy of
+    /// the player channels of `ChatType`.
+    #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+    pub enum Channel {
+        Tell,
+        Say,
+        Group,
+        Faction,
+        Region,
+        World,
+    }
+
+    impl Channel {
+        /// The channel of a message written by a player, None for messages of
+        /// the server or NPCs.
+        pub fn of<G>(chat_type: &ChatType<G>) -> Option<Self> {
+            match chat_type {
+                ChatType::Tell(..) => Some(Self::Tell),
+                ChatType::Say(_) => Some(Self::Say),
+                ChatType::Group(..) => Some(Self::Group),
+                ChatType::Faction(..) => Some(Self::Faction),
+                ChatType::Region(_) => Some(Self::Region),
+                ChatType::World(_) => Some(Self::World),
+                _ => None,
+            }
+        }
+
+        pub fn name(&self) -> &'static str {
+            match self {
+                Self::Tell => "tell",
+                Self::Say => "say",
+                Self::Group => "group",
+                Self::Faction => "faction",
+                Self::Region => "region",
+                Self::World => "world",
+            }
+        }
+    }
+
+    impl core::str::FromStr for Channel {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            [
+                Self::Tell,
+                Self::Say,
+                Self::Group,
+                Self::Faction,
+                Self::Region,
+                Self::World,
+            ]
+            .iter()
+            .copied()
+            .find(|channel| channel.name() == s)
+            .ok_or(())
+        }
+    }
+
+    #[derive(Clone, Deserialize, Serialize)]
+    pub struct MuteInfo {
+        pub performed_by: Uuid,
+        /// NOTE: May not be up to date, if we allow username changes.
+        pub performed_by_username: String,
+        /// NOTE: Role of the muting user at the time of the mute.
+        pub performed_by_role: Role,
+    }
+
+    #[derive(Clone, Deserialize, Serialize)]
+    pub struct Mute {
+        pub reason: String,
+        pub info: MuteInfo,
+        /// The channel the player is muted in, None if they are muted in all of
+        /// them.
+        pub channel: Option<Channel>,
+        /// NOTE: Should always be higher than start_date, if both are
+        /// present!
+        pub end_date: Option<DateTime<Utc>>,
+    }
+
+    impl Mute {
+        /// Returns true if the mute is expired, false otherwise.
+        pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+            self.end_date.map_or(false, |end_date| end_date <= now)
+        }
+
+        pub fn applies_to(&self, channel: Channel) -> bool {
+            self.channel.map_or(true, |muted| muted == channel)
+        }
+    }
+
+    type Unmute = MuteInfo;
+
+    #[derive(Clone, Deserialize, Serialize)]
+    pub enum MuteAction {
+        Unmute(Unmute),
+        Mute(Mute),
+    }
+
+    impl MuteAction {
+        pub fn mute(&self) -> Option<&Mute> {
+            match self {
+                MuteAction::Unmute(_) => None,
+                MuteAction::Mute(mute) => Some(mute),
+            }
+        }
+    }
+
+    #[derive(Clone, Deserialize, Serialize)]
+    pub struct MuteRecord {
+        /// Username of the user upon whom the action was performed, when it was
+        /// performed.
+        pub username_when_performed: String,
+        pub action: MuteAction,
+        pub date: DateTime<Utc>,
+    }
+
+    impl MuteRecord {
+        /// Returns true if this record represents an expired mute, false
+        /// otherwise.
+        fn is_expired(&self, now: DateTime<Utc>) -> bool {
+            match &self.action {
+                MuteAction::Mute(mute) => mute.is_expired(now),
+                MuteAction::Unmute(_) => true,
+            }
+        }
+
+        /// The history vector in a MuteEntry is stored forwards (from oldest
+        /// entry to newest), so `prev_record` is the previous entry in
+        /// this vector when iterating forwards (by array index).
+        ///
+        /// The rules are the same as for bans: mutes can't be shortened or
+        /// lifted by users with a lower role than the one who muted.
+        fn validate(&self, prev_record: Option<&MuteRecord>) -> Result<(), MuteErrorKind> {
+            if let Some(prev_record) = prev_record {
+                if prev_record.date > self.date {
+                    warn!(
+                        "Mute list history is inconsistent, or a just-added mute was behind a \
+                         historical entry in the mute record; please investigate the contents of \
+                         the file (might indicate a system clock change?)."
+                    );
+                }
+            }
+            let mute = match (&self.action, prev_record.map(|record| &record.action)) {
+                (MuteAction::Mute(mute), None)
+                | (MuteAction::Mute(mute), Some(MuteAction::Unmute(_))) => mute,
+                (MuteAction::Mute(new_mute), Some(MuteAction::Mute(old_mute))) => {
+                    match (new_mute.end_date, old_mute.end_date) {
+                        _ if new_mute.info.performed_by_role >= old_mute.info.performed_by_role => {
+                            new_mute
+                        },
+                        // Permanent mute retracted to temp mute.
+                        (Some(_), None) => {
+                            return Err(MuteErrorKind::PermissionDenied(MuteKind::Mute));
+                        },
+                        // Temp mute retracted to shorter temp mute.
+                        (Some(new_date), Some(old_date)) if new_date < old_date => {
+                            return Err(MuteErrorKind::PermissionDenied(MuteKind::Mute));
+                        },
+                        _ => new_mute,
+                    }
+                },
+                (MuteAction::Unmute(_), None)
+                | (MuteAction::Unmute(_), Some(MuteAction::Unmute(_))) => {
+                    return Err(MuteErrorKind::AlreadyUnmuted);
+                },
+                (MuteAction::Unmute(unmute), Some(MuteAction::Mute(mute))) => {
+                    if unmute.performed_by_role >= mute.info.performed_by_role {
+                        return Ok(());
+                    } else {
+                        return Err(MuteErrorKind::PermissionDenied(MuteKind::Unmute));
+                    }
+                },
+            };
+
+            // End date of a mute must be at least as big as the start date.
+            if let Some(end_date) = mute.end_date {
+                if self.date > end_date {
+                    return Err(MuteErrorKind::InvalidDateRange {
+                        start_date: self.date,
+                        end_date,
+                    });
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, Deserialize, Serialize)]
+    pub struct MuteEntry {
+        /// The latest mute record for this user.
+        pub current: MuteRecord,
+        /// Historical mute records for this user, stored in order from oldest
+        /// to newest.
+        pub history: Vec<MuteRecord>,
+        /// A *hint* about whether the system thinks this entry is expired,
+        /// for people going through the file by hand.  This is based off the
+        /// contents of `current`.
+        pub expired: bool,
+    }
+
+    impl Deref for MuteEntry {
+        type Target = MuteRecord;
+
+        fn deref(&self) -> &Self::Target { &self.current }
+    }
+
+    impl MuteEntry {
+        /// Both validates, and updates the hint bit if it's inconsistent with
+        /// reality.
+        fn validate(
+            &mut self,
+            now: DateTime<Utc>,
+            uuid: Uuid,
+        ) -> Result<Version, <Final as EditableSetting>::Error> {
+            let make_error = |current_entry: &MuteRecord| {
+                let username = current_entry.username_when_performed.clone();
+                move |kind| MuteError {
+                    kind,
+                    uuid,
+                    username,
+                }
+            };
+            let mut prev_entry = None;
+            for current_entry in &self.history {
+                current_entry
+                    .validate(prev_entry)
+                    .map_err(make_error(current_entry))?;
+                prev_entry = Some(current_entry);
+            }
+            self.current
+                .validate(prev_entry)
+                .map_err(make_error(&self.current))?;
+
+            let is_expired = self.current.is_expired(now);
+            if self.expired != is_expired {
+                self.expired = is_expired;
+                Ok(Version::Old)
+            } else {
+                Ok(Version::Latest)
+            }
+        }
+    }
+
+    #[derive(Clone, Deserialize, Serialize, Default)]
+    #[serde(transparent)]
+    pub struct Mutelist(pub(super) HashMap<Uuid, MuteEntry>);
+
+    impl Deref for Mutelist {
+        type Target = HashMap<Uuid, MuteEntry>;
+
+        fn deref(&self) -> &Self::Target { &self.0 }
+    }
+
+    impl Mutelist {
+        /// The mute keeping the user with UUID `uuid` from chatting in
+        /// `channel`, if any.
+        pub fn active_mute(
+            &self,
+            uuid: Uuid,
+            channel: Channel,
+            now: DateTime<Utc>,
+        ) -> Option<&Mute> {
+            self.0
+                .get(&uuid)
+                .and_then(|entry| entry.current.action.mute())
+                .filter(|mute| !mute.is_expired(now) && mute.applies_to(channel))
+        }
+
+        /// Attempt to perform the mute action `action` for the user with UUID
+        /// `uuid` and username `username`, starting from time `now`, with a
+        /// settings file maintained at path root `data_dir`.
+        ///
+        /// This works like [`crate::settings::Banlist::ban_action`]: None is
+        /// returned for actions which wouldn't change whether the user is
+        /// muted (unless `overwrite` is set), invalid actions change nothing,
+        /// and IO errors keep the change in memory.
+        #[must_use]
+        pub fn mute_action(
+            &mut self,
+            data_dir: &std::path::Path,
+            now: DateTime<Utc>,
+            uuid: Uuid,
+            username_when_performed: String,
+            action: MuteAction,
+            overwrite: bool,
+        ) -> Option<Result<(), Error<Final>>> {
+            let mute_record = MuteRecord {
+                username_when_performed,
+                action,
+                date: now,
+            };
+
+            // Perform an atomic edit.
+            Some(
+                self.edit(data_dir.as_ref(), |mutelist| {
+                    match mutelist.0.entry(uuid) {
+                        hash_map::Entry::Vacant(v) => {
+                            // If this is an unmute, it will have no effect, so return early.
+                            if matches!(mute_record.action, MuteAction::Unmute(_)) {
+                                return None;
+                            }
+                            v.insert(MuteEntry {
+                                current: mute_record,
+                                history: Vec::new(),
+                                expired: false,
+                            });
+                            Some(())
+                        },
+                        hash_map::Entry::Occupied(mut o) => {
+                            let entry = o.get_mut();
+                            // If overwrite is off, check that this entry (if successful) would
+                            // actually change the mute status.
+                            if !overwrite
+                                && entry.current.is_expired(now) == mute_record.is_expired(now)
+                            {
+                                return None;
+                            }
+                            entry
+                                .history
+                                .push(mem::replace(&mut entry.current, mute_record));
+                            Some(())
+                        },
+                    }
+                })?
+                .1,
+            )
+        }
+
+        /// Perform any needed validation on this mutelist that can't be done
+        /// using parsing.
+        ///
+        /// The returned version being "Old" indicates the loaded setting has
+        /// been modified during validation (this is why validate takes
+        /// `&mut self`).
+        pub(super) fn validate(&mut self) -> Result<Version, <Final as EditableSetting>::Error> {
+            let mut version = Version::Latest;
+            let now = Utc::now();
+            for (&uuid, value) in self.0.iter_mut() {
+                if matches!(value.validate(now, uuid)?, Version::Old) {
+                    // Update detected.
+                    version = Version::Old;
+                }
+            }
+            Ok(version)
+        }
+    }
+
+    // NOTE: Whenever there is a version upgrade, copy this note as well as the
+    // commented-out code below to the next version, then uncomment the code
+    // for this version.
+    /* impl TryFrom<Mutelist> for Final {
+        type Error = <Final as EditableSetting>::Error;
+
+        fn try_from(mut value: Mutelist) -> Result<Final, Self::Error> {
+            value.validate()?;
+            Ok(next::Mutelist::migrate(value).try_into().expect(MIGRATION_UPGRADE_GUARANTEE))
+        }
+    } */
+}
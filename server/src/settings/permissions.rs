@@ -0,0 +1,373 @@
+//! Versioned permission groups settings files.
+//!
+//! Permission groups grant commands and capabilities to players without
+//! making them moderators or administrators, e.g. to let builders build
+//! everywhere or event hosts spawn NPCs. Groups can inherit from other
+//! groups, and players can be added to a group until a given date.
+//!
+//! Groups sit next to the moderator and administrator roles rather than
+//! replacing them: roles still grant every command up to their level, and a
+//! group never lets its members act on players that have a role. Members using
+//! a moderation command granted by a group act as the lowest role, moderator,
+//! since ban, mute and whitelist records can only store a role.
+
+use super::PERMISSIONS_FILENAME as FILENAME;
+use crate::settings::editable::{EditableSetting, Version};
+use core::convert::{Infallible, TryFrom};
+use serde::{Deserialize, Serialize};
+
+/// NOTE: Always replace this with the latest permissions version. Then update
+/// the PermissionsRaw, the TryFrom<PermissionsRaw> for Permissions, the
+/// previously most recent module, and add a new module for the latest version!
+/// Please respect the migration upgrade guarantee found in the parent module
+/// with any upgrade.
+pub use self::v1::*;
+
+/// Versioned settings files, one per version.
+#[derive(Deserialize, Serialize)]
+pub enum PermissionsRaw {
+    V1(v1::Permissions),
+}
+
+impl From<Permissions> for PermissionsRaw {
+    fn from(value: Permissions) -> Self {
+        // Replace variant with that of current latest version.
+        Self::V1(value)
+    }
+}
+
+impl TryFrom<PermissionsRaw> for (Version, Permissions) {
+    type Error = <Permissions as EditableSetting>::Error;
+
+    fn try_from(value: PermissionsRaw) -> Result<Self, <Permissions as EditableSetting>::Error> {
+        use PermissionsRaw::*;
+        Ok(match value {
+            // Latest version (move to old section using the pattern of other old version when it
+            // is no longer latest).
+            V1(mut value) => (value.validate()?, value),
+        })
+    }
+}
+
+type Final = Permissions;
+
+impl EditableSetting for Permissions {
+    type Error = Infallible;
+    type Legacy = legacy::Permissions;
+    type Setting = PermissionsRaw;
+
+    const FILENAME: &'static str = FILENAME;
+}
+
+mod legacy {
+    use super::{v1 as next, Final};
+    use serde::{Deserialize, Serialize};
+
+    /// Permission groups were versioned from the start, but hosts editing the
+    /// file by hand may leave out the version, so such files are read in the
+    /// format of the first version.
+    #[derive(Deserialize, Serialize)]
+    #[serde(transparent)]
+    pub struct Permissions(pub(super) next::Permissions);
+
+    impl From<Permissions> for Final {
+        /// Note that legacy files are always valid, which is why we implement
+        /// From rather than TryFrom.
+        fn from(value: Permissions) -> Self { value.0 }
+    }
+}
+
+mod v1 {
+    use super::Final;
+    use crate::settings::editable::{EditableSetting, Version};
+    use authc::Uuid;
+    use chrono::{prelude::*, Utc};
+    use core::ops::{Deref, DerefMut};
+    use hashbrown::{HashMap, HashSet};
+    use serde::{Deserialize, Serialize};
+    use tracing::warn;
+    /* use super::v2 as next; */
+
+    /// Something members of a group may do, besides running commands.
+    ///
+    /// In particular, *never remove variants from this enum* (or any other enum
+    /// in a versioned settings file) without bumping the version and
+    /// writing a migration that understands how to properly deal with
+    /// existing instances of the old variant.  Otherwise, we will lose
+    /// compatibility with old settings files, since we won't be able to
+    /// deserialize them!
+    #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+    pub enum Capability {
+        /// Build in the build area with the given name, once build mode is
+        /// toggled on with `/build`. The "world" area spans the whole world.
+        Build(String),
+        /// Join the server as a spectator instead of with a character.
+        ///
+        /// NOTE: The server doesn't implement spectator mode yet, for now this
+        /// only decides whose requests to spectate are accepted (and then
+        /// ignored with a warning).
+        Spectate,
+    }
+
+    #[derive(Clone, Deserialize, Serialize)]
+    pub struct Membership {
+        /// NOTE: Should only be None for members added by editing the file.
+        pub username_when_added: Option<String>,
+        /// Date that the player was added to the group.
+        pub date: DateTime<Utc>,
+        /// Date that the membership ends, for temporary grants.
+        pub end_date: Option<DateTime<Utc>>,
+    }
+
+    impl Membership {
+        pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+            self.end_date.map_or(true, |end_date| end_date > now)
+        }
+    }
+
+    #[derive(Clone, Default, Deserialize, Serialize)]
+    #[serde(default)]
+    pub struct Group {
+        /// Names of groups whose commands and capabilities members of this
+        /// group have as well.
+        pub inherits: Vec<String>,
+        /// Keywords of the commands members may use, without the leading '/'.
+        pub commands: HashSet<String>,
+        pub capabilities: HashSet<Capability>,
+        pub members: HashMap<Uuid, Membership>,
+    }
+
+    #[derive(Clone, Deserialize, Serialize)]
+    #[serde(transparent)]
+    pub struct Permissions(pub(super) HashMap<String, Group>);
+
+    impl Default for Permissions {
+        /// Groups without members, for hosts to fill in or adapt to their
+        /// community.
+        fn default() -> Self {
+            fn group(inherits: &[&str], commands: &[&str], capabilities: Vec<Capability>) -> Group {
+                Group {
+                    inherits: inherits.iter().map(|name| name.to_string()).collect(),
+                    commands: commands.iter().map(|cmd| cmd.to_string()).collect(),
+                    capabilities: capabilities.into_iter().collect(),
+                    members: HashMap::new(),
+                }
+            }
+
+            Permissions(
+                vec![
+                    (
+                        "helper",
+                        group(&[], &["edit_history", "goto"], vec![Capability::Spectate]),
+                    ),
+                    (
+                        "builder",
+                        group(&[], &["make_block", "make_sprite"], vec![
+                            Capability::Build("world".to_owned()),
+                        ]),
+                    ),
+                    (
+                        "event_host",
+                        group(
+                            &["helper"],
+                            &["campfire", "dummy", "make_npc", "safezone", "spawn", "time"],
+                            vec![],
+                        ),
+                    ),
+                ]
+                .into_iter()
+                .map(|(name, group)| (name.to_owned(), group))
+                .collect(),
+            )
+        }
+    }
+
+    impl Deref for Permissions {
+        type Target = HashMap<String, Group>;
+
+        fn deref(&self) -> &Self::Target { &self.0 }
+    }
+
+    impl DerefMut for Permissions {
+        fn deref_mut(&mut self) -> &mut Self::Target { &mut self.0 }
+    }
+
+    impl Permissions {
+        /// Names of the groups `player` is an active member of, followed by
+        /// the groups they inherit from.
+        pub fn groups(&self, player: Uuid, now: DateTime<Utc>) -> Vec<&str> {
+            let mut groups = self
+                .0
+                .iter()
+                .filter(|(_, group)| {
+                    group
+                        .members
+                        .get(&player)
+                        .map_or(false, |membership| membership.is_active(now))
+                })
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>();
+            groups.sort_unstable();
+            // Inheritance may be cyclic, so every group is only visited once
+            let mut i = 0;
+            while i < groups.len() {
+                let inherits = self
+                    .0
+                    .get(groups[i])
+                    .map_or(&[][..], |group| &group.inherits[..]);
+                for parent in inherits {
+                    if self.0.contains_key(parent) && !groups.contains(&parent.as_str()) {
+                        groups.push(parent.as_str());
+                    }
+                }
+                i += 1;
+            }
+            groups
+        }
+
+        /// Whether the groups of `player` grant the command with the given
+        /// keyword.
+        pub fn permits_command(&self, player: Uuid, keyword: &str, now: DateTime<Utc>) -> bool {
+            self.groups(player, now)
+                .into_iter()
+                .any(|name| self.0[name].commands.contains(keyword))
+        }
+
+        pub fn has_capability(
+            &self,
+            player: Uuid,
+            capability: &Capability,
+            now: DateTime<Utc>,
+        ) -> bool {
+            self.groups(player, now)
+                .into_iter()
+                .any(|name| self.0[name].capabilities.contains(capability))
+        }
+
+        /// Names of the build areas the groups of `player` allow building in
+        pub fn build_areas(&self, player: Uuid, now: DateTime<Utc>) -> Vec<&str> {
+            self.groups(player, now)
+                .into_iter()
+                .flat_map(|name| self.0[name].capabilities.iter())
+                .filter_map(|capability| match capability {
+                    Capability::Build(area) => Some(area.as_str()),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        /// Perform any needed validation on these permissions that can't be
+        /// done using parsing.
+        ///
+        /// The returned version being "Old" indicates the loaded setting has
+        /// been modified during validation (this is why validate takes
+        /// `&mut self`).
+        pub(super) fn validate(&mut self) -> Result<Version, <Final as EditableSetting>::Error> {
+            let now = Utc::now();
+            let mut version = Version::Latest;
+            for group in self.0.values_mut() {
+                // Expired temporary grants are only kept until the file is next written
+                let members = group.members.len();
+                group
+                    .members
+                    .retain(|_, membership| membership.is_active(now));
+                if group.members.len() != members {
+                    version = Version::Old;
+                }
+            }
+            for (name, group) in self.0.iter() {
+                for parent in group.inherits.iter() {
+                    if !self.0.contains_key(parent) {
+                        warn!(
+                            "Permission group {:?} inherits from the unknown group {:?}",
+                            name, parent
+                        );
+                    }
+                }
+            }
+            Ok(version)
+        }
+    }
+
+    // NOTE: Whenever there is a version upgrade, copy this note as well as the
+    // commented-out code below to the next version, then uncomment the code
+    // for this version.
+    /* impl TryFrom<Permissions> for Final {
+        type Error = <Final as EditableSetting>::Error;
+
+        fn try_from(mut value: Permissions) -> Result<Final, Self::Error> {
+            value.validate()?;
+            Ok(next::Permissions::migrate(value).try_into().expect(MIGRATION_UPGRADE_GUARANTEE))
+        }
+    } */
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use authc::Uuid;
+    use chrono::{Duration, Utc};
+
+    fn membership(end_date: Option<chrono::DateTime<Utc>>) -> Membership {
+        Membership {
+            username_when_added: None,
+            date: Utc::now(),
+            end_date,
+        }
+    }
+
+    #[test]
+    fn groups_are_inherited() {
+        let player = Uuid::from_u128(1);
+        let now = Utc::now();
+        let mut permissions = Permissions::default();
+        permissions
+            .get_mut("event_host")
+            .unwrap()
+            .members
+            .insert(player, membership(None));
+        // Cyclic inheritance must not hang
+        permissions
+            .get_mut("helper")
+            .unwrap()
+            .inherits
+            .push("event_host".to_owned());
+
+        assert_eq!(permissions.groups(player, now), vec![
+            "event_host",
+            "helper"
+        ]);
+        assert!(permissions.permits_command(player, "make_npc", now));
+        assert!(permissions.permits_command(player, "edit_history", now));
+        assert!(!permissions.permits_command(player, "make_block", now));
+        assert!(permissions.has_capability(player, &Capability::Spectate, now));
+        assert!(permissions.groups(Uuid::from_u128(2), now).is_empty());
+    }
+
+    #[test]
+    fn temporary_grants_expire() {
+        let player = Uuid::from_u128(1);
+        let now = Utc::now();
+        let mut permissions = Permissions::default();
+        permissions
+            .get_mut("builder")
+            .unwrap()
+            .members
+            .insert(player, membership(Some(now + Duration::hours(1))));
+
+        assert_eq!(permissions.build_areas(player, now), vec!["world"]);
+        assert!(
+            permissions
+                .build_areas(player, now + Duration::hours(2))
+                .is_empty()
+        );
+
+        permissions
+            .get_mut("builder")
+            .unwrap()
+            .members
+            .insert(player, membership(Some(now - Duration::hours(1))));
+        assert!(matches!(permissions.validate(), Ok(Version::Old)));
+        assert!(permissions["builder"].members.is_empty());
+    }
+}
@@ -487,7 +487,7 @@ impl StateExt for State {
     }
 
     fn update_character_data(&mut self, entity: EcsEntity, components: PersistedComponents) {
-        let (body, stats, skill_set, inventory, waypoint, pets) = components;
+        let (body, stats, skill_set, inventory, waypoint, pets, quest_log) = components;
 
         if let Some(player_uid) = self.read_component_copied::<Uid>(entity) {
             // Notify clients of a player list update
@@ -529,6 +529,7 @@ impl StateExt for State {
                 entity,
                 comp::InventoryUpdate::new(comp::InventoryUpdateEvent::default()),
             );
+            self.write_component_ignore_entity_dead(entity, quest_log);
 
             if let Some(waypoint) = waypoint {
                 self.write_component_ignore_entity_dead(entity, RepositionOnChunkLoad);
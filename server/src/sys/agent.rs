@@ -1,5 +1,6 @@
 use crate::rtsim::{Entity as RtSimData, RtSim};
 use common::{
+    assets::AssetExt,
     combat,
     comp::{
         self,
@@ -18,11 +19,10 @@ use common::{
             ConsumableKind, Item, ItemDesc, ItemKind,
         },
         projectile::ProjectileConstructor,
-        skills::{AxeSkill, BowSkill, HammerSkill, SceptreSkill, Skill, StaffSkill, SwordSkill},
-        AbilityInput, ActiveAbilities, Agent, Alignment, BehaviorCapability, BehaviorState, Body,
-        CharacterAbility, CharacterState, Combo, ControlAction, ControlEvent, Controller, Energy,
-        Health, HealthChange, InputKind, Inventory, InventoryAction, LightEmitter, MountState, Ori,
-        PhysicsState, Pos, Scale, SkillSet, Stats, UnresolvedChatMsg, UtteranceKind, Vel,
+        Agent, Alignment, BehaviorCapability, BehaviorState, Body, CharacterState, Combo,
+        ControlAction, ControlEvent, Controller, Energy, Health, HealthChange, InputKind,
+        Inventory, InventoryAction, LightEmitter, MountState, Ori, PhysicsState, Pos, Scale,
+        SkillSet, Stats, UnresolvedChatMsg, UtteranceKind, Vel,
     },
     consts::GRAVITY,
     effect::{BuffEffect, Effect},
@@ -30,7 +30,8 @@ use common::{
     path::TraversalConfig,
     resources::{DeltaTime, Time, TimeOfDay},
     rtsim::{Memory, MemoryItem, RtSimEntity, RtSimEvent},
-    states::{basic_beam, utils::StageSection},
+    states::basic_beam,
+    tactic::TacticSpec,
     terrain::{Block, TerrainGrid},
     time::DayPeriod,
     trade::{TradeAction, TradePhase, TradeResult},
@@ -48,8 +49,9 @@ use specs::{
     Entities, Entity as EcsEntity, Join, ParJoin, Read, ReadExpect, ReadStorage, SystemData, World,
     Write, WriteExpect, WriteStorage,
 };
-use std::{f32::consts::PI, sync::Arc, time::Duration};
+use std::sync::Arc;
 use vek::*;
+mod tactic;
 
 struct AgentData<'a> {
     entity: &'a EcsEntity,
@@ -73,7 +75,6 @@ struct AgentData<'a> {
     is_gliding: bool,
     health: Option<&'a Health>,
     char_state: &'a CharacterState,
-    active_abilities: &'a ActiveAbilities,
     cached_spatial_grid: &'a common::CachedSpatialGrid,
 }
 
@@ -89,45 +90,6 @@ struct AttackData {
     angle: f32,
 }
 
-impl AttackData {
-    fn in_min_range(&self) -> bool { self.dist_sqrd < self.min_attack_dist.powi(2) }
-}
-
-#[derive(Eq, PartialEq)]
-pub enum Tactic {
-    Melee,
-    Axe,
-    Hammer,
-    Sword,
-    Bow,
-    Staff,
-    Sceptre,
-    StoneGolem,
-    CircleCharge { radius: u32, circle_time: u32 },
-    QuadLowRanged,
-    TailSlap,
-    QuadLowQuick,
-    QuadLowBasic,
-    QuadLowBeam,
-    QuadMedJump,
-    QuadMedBasic,
-    Theropod,
-    Turret,
-    FixedTurret,
-    RotatingTurret,
-    RadialTurret,
-    Mindflayer,
-    BirdLargeBreathe,
-    BirdLargeFire,
-    BirdLargeBasic,
-    Minotaur,
-    ClayGolem,
-    TidalWarrior,
-    Yeti,
-    Tornado,
-    Harvester,
-}
-
 #[derive(SystemData)]
 pub struct ReadData<'a> {
     entities: Entities<'a>,
@@ -160,7 +122,6 @@ pub struct ReadData<'a> {
     rtsim_entities: ReadStorage<'a, RtSimEntity>,
     buffs: ReadStorage<'a, Buffs>,
     combos: ReadStorage<'a, Combo>,
-    active_abilities: ReadStorage<'a, ActiveAbilities>,
 }
 
 const DAMAGE_MEMORY_DURATION: f64 = 0.25;
@@ -213,11 +174,7 @@ impl<'a> System<'a> for Sys {
             ),
             read_data.bodies.maybe(),
             &read_data.inventories,
-            (
-                &read_data.char_states,
-                &read_data.skill_set,
-                &read_data.active_abilities,
-            ),
+            (&read_data.char_states, &read_data.skill_set),
             &read_data.physics_states,
             &read_data.uids,
             &mut agents,
@@ -245,7 +202,7 @@ impl<'a> System<'a> for Sys {
                     (pos, vel, ori),
                     body,
                     inventory,
-                    (char_state, skill_set, active_abilities),
+                    (char_state, skill_set),
                     physics_state,
                     uid,
                     agent,
@@ -357,7 +314,6 @@ impl<'a> System<'a> for Sys {
                         is_gliding,
                         health: read_data.healths.get(entity),
                         char_state,
-                        active_abilities,
                         cached_spatial_grid: &read_data.cached_spatial_grid,
                     };
                     ///////////////////////////////////////////////////////////
@@ -1217,7 +1173,16 @@ impl<'a> AgentData<'a> {
                                         self.chat_npc(msg, event_emitter);
                                     }
                                 },
-                                Subject::Work => {},
+                                Subject::Work => {
+                                    if agent.quests.is_empty() {
+                                        self.chat_npc("npc.speech.villager", event_emitter);
+                                    } else {
+                                        event_emitter.emit(ServerEvent::QuestTalk {
+                                            player: target,
+                                            giver: *self.entity,
+                                        });
+                                    }
+                                },
                             }
                         }
                     }
@@ -1725,73 +1690,98 @@ impl<'a> AgentData<'a> {
         tgt_data: &TargetData,
         read_data: &ReadData,
     ) {
+        let asset_tactic = |specifier: &str| TacticSpec::load_expect(specifier);
         let tool_tactic = |tool_kind| match tool_kind {
-            ToolKind::Bow => Tactic::Bow,
-            ToolKind::Staff => Tactic::Staff,
-            ToolKind::Sceptre => Tactic::Sceptre,
-            ToolKind::Hammer => Tactic::Hammer,
-            ToolKind::Sword | ToolKind::Spear => Tactic::Sword,
-            ToolKind::Axe => Tactic::Axe,
-            _ => Tactic::Melee,
+            ToolKind::Bow => asset_tactic("common.tactic.bow"),
+            ToolKind::Staff => asset_tactic("common.tactic.staff"),
+            ToolKind::Sceptre => asset_tactic("common.tactic.sceptre"),
+            ToolKind::Hammer => asset_tactic("common.tactic.hammer"),
+            ToolKind::Sword | ToolKind::Spear => asset_tactic("common.tactic.sword"),
+            ToolKind::Axe => asset_tactic("common.tactic.axe"),
+            _ => asset_tactic("common.tactic.melee"),
         };
 
-        let tactic = self
-            .inventory
-            .equipped(EquipSlot::ActiveMainhand)
-            .as_ref()
-            .map(|item| {
-                if let Some(ability_spec) = item.ability_spec() {
-                    match ability_spec {
-                        AbilitySpec::Custom(spec) => match spec.as_str() {
-                            "Axe Simple" | "Sword Simple" => Tactic::Sword,
-                            "Staff Simple" => Tactic::Staff,
-                            "Bow Simple" => Tactic::Bow,
-                            "Stone Golem" => Tactic::StoneGolem,
-                            "Quad Med Quick" => Tactic::CircleCharge {
-                                radius: 3,
-                                circle_time: 2,
-                            },
-                            "Quad Med Jump" => Tactic::QuadMedJump,
-                            "Quad Med Charge" => Tactic::CircleCharge {
-                                radius: 6,
-                                circle_time: 1,
-                            },
-                            "Quad Med Basic" => Tactic::QuadMedBasic,
-                            "Asp" | "Maneater" => Tactic::QuadLowRanged,
-                            "Quad Low Breathe" | "Quad Low Beam" | "Basilisk" => {
-                                Tactic::QuadLowBeam
-                            },
-                            "Quad Low Tail" | "Husk Brute" => Tactic::TailSlap,
-                            "Quad Low Quick" => Tactic::QuadLowQuick,
-                            "Quad Low Basic" => Tactic::QuadLowBasic,
-                            "Theropod Basic" | "Theropod Bird" => Tactic::Theropod,
-                            "Theropod Charge" => Tactic::CircleCharge {
-                                radius: 6,
-                                circle_time: 1,
-                            },
-                            "Turret" => Tactic::Turret,
-                            "Haniwa Sentry" => Tactic::RotatingTurret,
-                            "Bird Large Breathe" => Tactic::BirdLargeBreathe,
-                            "Bird Large Fire" => Tactic::BirdLargeFire,
-                            "Bird Large Basic" => Tactic::BirdLargeBasic,
-                            "Mindflayer" => Tactic::Mindflayer,
-                            "Minotaur" => Tactic::Minotaur,
-                            "Clay Golem" => Tactic::ClayGolem,
-                            "Tidal Warrior" => Tactic::TidalWarrior,
-                            "Tidal Totem" => Tactic::RadialTurret,
-                            "Yeti" => Tactic::Yeti,
-                            "Harvester" => Tactic::Harvester,
-                            _ => Tactic::Melee,
-                        },
-                        AbilitySpec::Tool(tool_kind) => tool_tactic(*tool_kind),
-                    }
-                } else if let ItemKind::Tool(tool) = &item.kind() {
-                    tool_tactic(tool.kind)
-                } else {
-                    Tactic::Melee
-                }
+        // The tactic set by the entity config takes precedence over the one of the
+        // weapon, falling back to the latter if it fails to load
+        let tactic = agent
+            .tactic
+            .as_deref()
+            .and_then(|specifier| TacticSpec::load(specifier).ok())
+            .or_else(|| {
+                self.inventory
+                    .equipped(EquipSlot::ActiveMainhand)
+                    .as_ref()
+                    .map(|item| {
+                        if let Some(ability_spec) = item.ability_spec() {
+                            match ability_spec {
+                                AbilitySpec::Custom(spec) => match spec.as_str() {
+                                    "Axe Simple" | "Sword Simple" => {
+                                        asset_tactic("common.tactic.sword")
+                                    },
+                                    "Staff Simple" => asset_tactic("common.tactic.staff"),
+                                    "Bow Simple" => asset_tactic("common.tactic.bow"),
+                                    "Stone Golem" => asset_tactic("common.tactic.stone_golem"),
+                                    "Quad Med Quick" => {
+                                        asset_tactic("common.tactic.quad_med_quick")
+                                    },
+                                    "Quad Med Jump" => asset_tactic("common.tactic.quad_med_jump"),
+                                    "Quad Med Charge" | "Theropod Charge" => {
+                                        asset_tactic("common.tactic.quad_med_charge")
+                                    },
+                                    "Quad Med Basic" => {
+                                        asset_tactic("common.tactic.quad_med_basic")
+                                    },
+                                    "Asp" | "Maneater" => {
+                                        asset_tactic("common.tactic.quad_low_ranged")
+                                    },
+                                    "Quad Low Breathe" | "Quad Low Beam" | "Basilisk" => {
+                                        asset_tactic("common.tactic.quad_low_beam")
+                                    },
+                                    "Quad Low Tail" | "Husk Brute" => {
+                                        asset_tactic("common.tactic.tail_slap")
+                                    },
+                                    "Quad Low Quick" => {
+                                        asset_tactic("common.tactic.quad_low_quick")
+                                    },
+                                    "Quad Low Basic" => {
+                                        asset_tactic("common.tactic.quad_low_basic")
+                                    },
+                                    "Theropod Basic" | "Theropod Bird" => {
+                                        asset_tactic("common.tactic.theropod")
+                                    },
+                                    "Turret" => asset_tactic("common.tactic.turret"),
+                                    "Haniwa Sentry" => {
+                                        asset_tactic("common.tactic.rotating_turret")
+                                    },
+                                    "Tornado" => asset_tactic("common.tactic.tornado"),
+                                    "Bird Large Breathe" => {
+                                        asset_tactic("common.tactic.bird_large_breathe")
+                                    },
+                                    "Bird Large Fire" => {
+                                        asset_tactic("common.tactic.bird_large_fire")
+                                    },
+                                    "Bird Large Basic" => {
+                                        asset_tactic("common.tactic.bird_large_basic")
+                                    },
+                                    "Mindflayer" => asset_tactic("common.tactic.mindflayer"),
+                                    "Minotaur" => asset_tactic("common.tactic.minotaur"),
+                                    "Clay Golem" => asset_tactic("common.tactic.clay_golem"),
+                                    "Tidal Warrior" => asset_tactic("common.tactic.tidal_warrior"),
+                                    "Tidal Totem" => asset_tactic("common.tactic.radial_turret"),
+                                    "Yeti" => asset_tactic("common.tactic.yeti"),
+                                    "Harvester" => asset_tactic("common.tactic.harvester"),
+                                    _ => asset_tactic("common.tactic.melee"),
+                                },
+                                AbilitySpec::Tool(tool_kind) => tool_tactic(*tool_kind),
+                            }
+                        } else if let ItemKind::Tool(tool) = &item.kind() {
+                            tool_tactic(tool.kind)
+                        } else {
+                            asset_tactic("common.tactic.melee")
+                        }
+                    })
             })
-            .unwrap_or(Tactic::Melee);
+            .unwrap_or_else(|| asset_tactic("common.tactic.melee"));
 
         // Wield the weapon as running towards the target
         controller.actions.push(ControlAction::Wield);
@@ -1809,18 +1799,11 @@ impl<'a> AgentData<'a> {
         let eye_offset = self.body.map_or(0.0, |b| b.eye_height());
 
         let tgt_eye_height = tgt_data.body.map_or(0.0, |b| b.eye_height());
-        let tgt_eye_offset = tgt_eye_height +
-                   // Special case for jumping attacks to jump at the body
-                   // of the target and not the ground around the target
-                   // For the ranged it is to shoot at the feet and not
-                   // the head to get splash damage
-                   if tactic == Tactic::QuadMedJump {
-                       1.0
-                   } else if matches!(tactic, Tactic::QuadLowRanged) {
-                       -1.0
-                   } else {
-                       0.0
-                   };
+        let (aim_offset, leap_aim) = {
+            let spec = tactic.read();
+            (spec.aim_offset, spec.leap_aim)
+        };
+        let tgt_eye_offset = tgt_eye_height + aim_offset;
 
         // FIXME:
         // 1) Retrieve actual projectile speed!
@@ -1895,12 +1878,8 @@ impl<'a> AgentData<'a> {
                     ),
                 )
             },
-            CharacterState::LeapMelee(_) if matches!(tactic, Tactic::Hammer | Tactic::Axe) => {
-                let direction_weight = match tactic {
-                    Tactic::Hammer => 0.1,
-                    Tactic::Axe => 0.3,
-                    _ => unreachable!("Direction weight called on incorrect tactic."),
-                };
+            CharacterState::LeapMelee(_) if leap_aim.is_some() => {
+                let direction_weight = leap_aim.unwrap_or(1.0);
 
                 let tgt_pos = tgt_data.pos.0;
                 let self_pos = self.pos.0;
@@ -1948,2182 +1927,14 @@ impl<'a> AgentData<'a> {
             angle,
         };
 
-        // Match on tactic. Each tactic has different controls
-        // depending on the distance from the agent to the target
-        match tactic {
-            Tactic::Melee => {
-                self.handle_melee_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::Axe => {
-                self.handle_axe_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::Hammer => {
-                self.handle_hammer_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::Sword => {
-                self.handle_sword_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::Bow => {
-                self.handle_bow_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::Staff => {
-                self.handle_staff_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::Sceptre => {
-                self.handle_sceptre_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::StoneGolem => {
-                self.handle_stone_golem_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::CircleCharge {
-                radius,
-                circle_time,
-            } => self.handle_circle_charge_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-                radius,
-                circle_time,
-            ),
-            Tactic::QuadLowRanged => self.handle_quadlow_ranged_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            Tactic::TailSlap => {
-                self.handle_tail_slap_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::QuadLowQuick => self.handle_quadlow_quick_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            Tactic::QuadLowBasic => self.handle_quadlow_basic_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            Tactic::QuadMedJump => self.handle_quadmed_jump_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            Tactic::QuadMedBasic => self.handle_quadmed_basic_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            Tactic::QuadLowBeam => self.handle_quadlow_beam_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            Tactic::Theropod => {
-                self.handle_theropod_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::Turret => {
-                self.handle_turret_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::FixedTurret => self.handle_fixed_turret_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            Tactic::RotatingTurret => self.handle_rotating_turret_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            Tactic::Tornado => self.handle_tornado_attack(controller),
-            Tactic::Mindflayer => {
-                self.handle_mindflayer_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::BirdLargeFire => self.handle_birdlarge_fire_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            // Mostly identical to BirdLargeFire but tweaked for flamethrower instead of shockwave
-            Tactic::BirdLargeBreathe => self.handle_birdlarge_breathe_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            Tactic::BirdLargeBasic => self.handle_birdlarge_basic_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            Tactic::Minotaur => {
-                self.handle_minotaur_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::ClayGolem => {
-                self.handle_clay_golem_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::TidalWarrior => self.handle_tidal_warrior_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            Tactic::RadialTurret => self.handle_radial_turret_attack(
-                agent,
-                controller,
-                &attack_data,
-                tgt_data,
-                read_data,
-            ),
-            Tactic::Yeti => {
-                self.handle_yeti_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-            Tactic::Harvester => {
-                self.handle_harvester_attack(agent, controller, &attack_data, tgt_data, read_data)
-            },
-        }
-    }
-
-    fn handle_melee_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if attack_data.in_min_range() && attack_data.angle < 45.0 {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-            controller.inputs.move_dir = Vec2::zero();
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, true, None);
-
-            if self.body.map(|b| b.is_humanoid()).unwrap_or(false)
-                && attack_data.dist_sqrd < 16.0f32.powi(2)
-                && thread_rng().gen::<f32>() < 0.02
-            {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Roll));
-            }
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, true, None);
-        }
-    }
-
-    fn handle_axe_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        let has_leap = || self.skill_set.has_skill(Skill::Axe(AxeSkill::UnlockLeap));
-
-        let has_energy = |need| self.energy.current() > need;
-
-        let use_leap = |controller: &mut Controller| {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(0)));
-        };
-        if attack_data.in_min_range() && attack_data.angle < 45.0 {
-            controller.inputs.move_dir = Vec2::zero();
-            if agent.action_state.timer > 5.0 {
-                controller
-                    .actions
-                    .push(ControlAction::CancelInput(InputKind::Secondary));
-                agent.action_state.timer = 0.0;
-            } else if agent.action_state.timer > 2.5 && has_energy(10.0) {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-                agent.action_state.timer += read_data.dt.0;
-            } else if has_leap() && has_energy(45.0) && thread_rng().gen_bool(0.5) {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(0)));
-                agent.action_state.timer += read_data.dt.0;
-            } else {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-                agent.action_state.timer += read_data.dt.0;
-            }
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-            if attack_data.dist_sqrd < 32.0f32.powi(2)
-                && has_leap()
-                && has_energy(50.0)
-                && can_see_tgt(
-                    &read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                )
-            {
-                use_leap(controller);
-            }
-            if self.body.map(|b| b.is_humanoid()).unwrap_or(false)
-                && attack_data.dist_sqrd < 16.0f32.powi(2)
-                && thread_rng().gen::<f32>() < 0.02
-            {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Roll));
-            }
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_hammer_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        let has_leap = || {
-            self.skill_set
-                .has_skill(Skill::Hammer(HammerSkill::UnlockLeap))
-        };
-
-        let has_energy = |need| self.energy.current() > need;
-
-        let use_leap = |controller: &mut Controller| {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(0)));
-        };
-
-        if attack_data.in_min_range() && attack_data.angle < 45.0 {
-            controller.inputs.move_dir = Vec2::zero();
-            if agent.action_state.timer > 4.0 {
-                controller
-                    .actions
-                    .push(ControlAction::CancelInput(InputKind::Secondary));
-                agent.action_state.timer = 0.0;
-            } else if agent.action_state.timer > 3.0 {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-                agent.action_state.timer += read_data.dt.0;
-            } else if has_leap() && has_energy(50.0) && thread_rng().gen_bool(0.9) {
-                use_leap(controller);
-                agent.action_state.timer += read_data.dt.0;
-            } else {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-                agent.action_state.timer += read_data.dt.0;
-            }
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-            if attack_data.dist_sqrd < 32.0f32.powi(2)
-                && has_leap()
-                && has_energy(50.0)
-                && can_see_tgt(
-                    &read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                )
-            {
-                use_leap(controller);
-            }
-            if self.body.map(|b| b.is_humanoid()).unwrap_or(false)
-                && attack_data.dist_sqrd < 16.0f32.powi(2)
-                && thread_rng().gen::<f32>() < 0.02
-            {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Roll));
-            }
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_sword_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if attack_data.in_min_range() && attack_data.angle < 45.0 {
-            controller.inputs.move_dir = Vec2::zero();
-            if self
-                .skill_set
-                .has_skill(Skill::Sword(SwordSkill::UnlockSpin))
-                && agent.action_state.timer < 2.0
-                && self.energy.current() > 60.0
-            {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(0)));
-                agent.action_state.timer += read_data.dt.0;
-            } else if agent.action_state.timer > 2.0 {
-                agent.action_state.timer = 0.0;
-            } else {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-                agent.action_state.timer += read_data.dt.0;
-            }
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            if self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None)
-                && can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                )
-            {
-                if agent.action_state.timer > 4.0 && attack_data.angle < 45.0 {
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Secondary));
-                    agent.action_state.timer = 0.0;
-                } else {
-                    agent.action_state.timer += read_data.dt.0;
-                }
-            }
-            if self.body.map(|b| b.is_humanoid()).unwrap_or(false)
-                && attack_data.dist_sqrd < 16.0f32.powi(2)
-                && thread_rng().gen::<f32>() < 0.02
-            {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Roll));
-            }
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_bow_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        const MIN_CHARGE_FRAC: f32 = 0.5;
-        const OPTIMAL_TARGET_VELOCITY: f32 = 5.0;
-        const DESIRED_ENERGY_LEVEL: f32 = 50.0;
-        // Logic to use abilities
-        if let CharacterState::ChargedRanged(c) = self.char_state {
-            if !matches!(c.stage_section, StageSection::Recover) {
-                // Don't even bother with this logic if in recover
-                let target_speed_sqd = agent
-                    .target
-                    .as_ref()
-                    .map(|t| t.target)
-                    .and_then(|e| read_data.velocities.get(e))
-                    .map_or(0.0, |v| v.0.magnitude_squared());
-                if c.charge_frac() < MIN_CHARGE_FRAC
-                    || (target_speed_sqd > OPTIMAL_TARGET_VELOCITY.powi(2) && c.charge_frac() < 1.0)
-                {
-                    // If haven't charged to desired level, or target is moving too fast and haven't
-                    // fully charged, keep charging
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Primary));
-                }
-                // Else don't send primary input to release the shot
-            }
-        } else if matches!(self.char_state, CharacterState::RepeaterRanged(c) if self.energy.current() > 5.0 && !matches!(c.stage_section, StageSection::Recover))
-        {
-            // If in repeater ranged, have enough energy, and aren't in recovery, try to
-            // keep firing
-            if attack_data.dist_sqrd > attack_data.min_attack_dist.powi(2)
-                && can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                )
-            {
-                // Only keep firing if not in melee range or if can see target
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-            }
-        } else if attack_data.dist_sqrd < (2.0 * attack_data.min_attack_dist).powi(2) {
-            if self
-                .skill_set
-                .has_skill(Skill::Bow(BowSkill::UnlockShotgun))
-                && self.energy.current() > 45.0
-                && thread_rng().gen_bool(0.5)
-            {
-                // Use shotgun if target close and have sufficient energy
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(0)));
-            } else if self.body.map(|b| b.is_humanoid()).unwrap_or(false)
-                && self.energy.current() > CharacterAbility::default_roll().get_energy_cost()
-                && !matches!(self.char_state, CharacterState::BasicRanged(c) if !matches!(c.stage_section, StageSection::Recover))
-            {
-                // Else roll away if can roll and have enough energy, and not using shotgun
-                // (other 2 attacks have interrupt handled above) unless in recover
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Roll));
-            } else {
-                self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-                if attack_data.angle < 15.0 {
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Primary));
-                }
-            }
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2)
-            && can_see_tgt(
-                &*read_data.terrain,
-                self.pos,
-                tgt_data.pos,
-                attack_data.dist_sqrd,
-            )
-        {
-            // If not really far, and can see target, attempt to shoot bow
-            if self.energy.current() < DESIRED_ENERGY_LEVEL {
-                // If low on energy, use primary to attempt to regen energy
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-            } else {
-                // Else we have enough energy, use repeater
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-            }
-        }
-        // Logic to move. Intentionally kept separate from ability logic so duplicated
-        // work is less necessary.
-        if attack_data.dist_sqrd < (2.0 * attack_data.min_attack_dist).powi(2) {
-            // Attempt to move away from target if too close
-            if let Some((bearing, speed)) = agent.chaser.chase(
-                &*read_data.terrain,
-                self.pos.0,
-                self.vel.0,
-                tgt_data.pos.0,
-                TraversalConfig {
-                    min_tgt_dist: 1.25,
-                    ..self.traversal_config
-                },
-            ) {
-                controller.inputs.move_dir =
-                    -bearing.xy().try_normalized().unwrap_or_else(Vec2::zero) * speed;
-            }
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            // Else attempt to circle target if neither too close nor too far
-            if let Some((bearing, speed)) = agent.chaser.chase(
-                &*read_data.terrain,
-                self.pos.0,
-                self.vel.0,
-                tgt_data.pos.0,
-                TraversalConfig {
-                    min_tgt_dist: 1.25,
-                    ..self.traversal_config
-                },
-            ) {
-                if can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                ) && attack_data.angle < 45.0
-                {
-                    controller.inputs.move_dir = bearing
-                        .xy()
-                        .rotated_z(thread_rng().gen_range(0.5..1.57))
-                        .try_normalized()
-                        .unwrap_or_else(Vec2::zero)
-                        * speed;
-                } else {
-                    // Unless cannot see target, then move towards them
-                    controller.inputs.move_dir =
-                        bearing.xy().try_normalized().unwrap_or_else(Vec2::zero) * speed;
-                    self.jump_if(controller, bearing.z > 1.5);
-                    controller.inputs.move_z = bearing.z;
-                }
-            }
-            // Sometimes try to roll
-            if self.body.map(|b| b.is_humanoid()).unwrap_or(false)
-                && attack_data.dist_sqrd < 16.0f32.powi(2)
-                && thread_rng().gen::<f32>() < 0.01
-            {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Roll));
-            }
-        } else {
-            // If too far, move towards target
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_staff_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        let extract_ability = |input: AbilityInput| {
-            self.active_abilities
-                .activate_ability(input, Some(self.inventory), self.skill_set, self.body)
-                .unwrap_or_default()
-                .0
-        };
-        let (flamethrower, shockwave) = (
-            extract_ability(AbilityInput::Secondary),
-            extract_ability(AbilityInput::Auxiliary(0)),
+        self.run_tactic(
+            agent,
+            controller,
+            &attack_data,
+            tgt_data,
+            read_data,
+            &tactic.read().root,
         );
-        let flamethrower_range = match flamethrower {
-            CharacterAbility::BasicBeam { range, .. } => range,
-            _ => 20.0_f32,
-        };
-        let shockwave_cost = shockwave.get_energy_cost();
-        if self.body.map_or(false, |b| b.is_humanoid())
-            && attack_data.in_min_range()
-            && self.energy.current() > CharacterAbility::default_roll().get_energy_cost()
-            && !matches!(self.char_state, CharacterState::Shockwave(_))
-        {
-            // if a humanoid, have enough stamina, not in shockwave, and in melee range,
-            // emergency roll
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Roll));
-        } else if matches!(self.char_state, CharacterState::Shockwave(_)) {
-            agent.action_state.condition = false;
-        } else if agent.action_state.condition
-            && matches!(self.char_state, CharacterState::Wielding(_))
-        {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(0)));
-        } else if !matches!(self.char_state, CharacterState::Shockwave(c) if !matches!(c.stage_section, StageSection::Recover))
-        {
-            // only try to use another ability unless in shockwave or recover
-            let target_approaching_speed = -agent
-                .target
-                .as_ref()
-                .map(|t| t.target)
-                .and_then(|e| read_data.velocities.get(e))
-                .map_or(0.0, |v| v.0.dot(self.ori.look_vec()));
-            if self
-                .skill_set
-                .has_skill(Skill::Staff(StaffSkill::UnlockShockwave))
-                && target_approaching_speed > 12.0
-                && self.energy.current() > shockwave_cost
-            {
-                // if enemy is closing distance quickly, use shockwave to knock back
-                if matches!(self.char_state, CharacterState::Wielding(_)) {
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Ability(0)));
-                } else {
-                    agent.action_state.condition = true;
-                }
-            } else if self.energy.current()
-                > shockwave_cost + CharacterAbility::default_roll().get_energy_cost()
-                && attack_data.dist_sqrd < flamethrower_range.powi(2)
-            {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-            } else {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-            }
-        }
-        // Logic to move. Intentionally kept separate from ability logic so duplicated
-        // work is less necessary.
-        if attack_data.dist_sqrd < (2.0 * attack_data.min_attack_dist).powi(2) {
-            // Attempt to move away from target if too close
-            if let Some((bearing, speed)) = agent.chaser.chase(
-                &*read_data.terrain,
-                self.pos.0,
-                self.vel.0,
-                tgt_data.pos.0,
-                TraversalConfig {
-                    min_tgt_dist: 1.25,
-                    ..self.traversal_config
-                },
-            ) {
-                controller.inputs.move_dir =
-                    -bearing.xy().try_normalized().unwrap_or_else(Vec2::zero) * speed;
-            }
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            // Else attempt to circle target if neither too close nor too far
-            if let Some((bearing, speed)) = agent.chaser.chase(
-                &*read_data.terrain,
-                self.pos.0,
-                self.vel.0,
-                tgt_data.pos.0,
-                TraversalConfig {
-                    min_tgt_dist: 1.25,
-                    ..self.traversal_config
-                },
-            ) {
-                if can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                ) && attack_data.angle < 45.0
-                {
-                    controller.inputs.move_dir = bearing
-                        .xy()
-                        .rotated_z(thread_rng().gen_range(-1.57..-0.5))
-                        .try_normalized()
-                        .unwrap_or_else(Vec2::zero)
-                        * speed;
-                } else {
-                    // Unless cannot see target, then move towards them
-                    controller.inputs.move_dir =
-                        bearing.xy().try_normalized().unwrap_or_else(Vec2::zero) * speed;
-                    self.jump_if(controller, bearing.z > 1.5);
-                    controller.inputs.move_z = bearing.z;
-                }
-            }
-            // Sometimes try to roll
-            if self.body.map_or(false, |b| b.is_humanoid())
-                && attack_data.dist_sqrd < 16.0f32.powi(2)
-                && !matches!(self.char_state, CharacterState::Shockwave(_))
-                && thread_rng().gen::<f32>() < 0.02
-            {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Roll));
-            }
-        } else {
-            // If too far, move towards target
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_sceptre_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        const DESIRED_ENERGY_LEVEL: f32 = 50.0;
-        const DESIRED_COMBO_LEVEL: u32 = 8;
-        // Logic to use abilities
-        if attack_data.dist_sqrd > attack_data.min_attack_dist.powi(2)
-            && can_see_tgt(
-                &*read_data.terrain,
-                self.pos,
-                tgt_data.pos,
-                attack_data.dist_sqrd,
-            )
-        {
-            // If far enough away, and can see target, check which skill is appropriate to
-            // use
-            if self.energy.current() > DESIRED_ENERGY_LEVEL
-                && read_data
-                    .combos
-                    .get(*self.entity)
-                    .map_or(false, |c| c.counter() >= DESIRED_COMBO_LEVEL)
-                && !read_data.buffs.get(*self.entity).iter().any(|buff| {
-                    buff.iter_kind(BuffKind::Regeneration)
-                        .peekable()
-                        .peek()
-                        .is_some()
-                })
-            {
-                // If have enough energy and combo to use healing aura, do so
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-            } else if self
-                .skill_set
-                .has_skill(Skill::Sceptre(SceptreSkill::UnlockAura))
-                && self.energy.current() > DESIRED_ENERGY_LEVEL
-                && !read_data.buffs.get(*self.entity).iter().any(|buff| {
-                    buff.iter_kind(BuffKind::ProtectingWard)
-                        .peekable()
-                        .peek()
-                        .is_some()
-                })
-            {
-                // Use ward if target is far enough away, self is not buffed, and have
-                // sufficient energy
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(0)));
-            } else {
-                // If low on energy, use primary to attempt to regen energy
-                // Or if at desired energy level but not able/willing to ward, just attack
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-            }
-        } else if attack_data.dist_sqrd < (2.0 * attack_data.min_attack_dist).powi(2) {
-            if self.body.map_or(false, |b| b.is_humanoid())
-                && self.energy.current() > CharacterAbility::default_roll().get_energy_cost()
-                && !matches!(self.char_state, CharacterState::BasicAura(c) if !matches!(c.stage_section, StageSection::Recover))
-            {
-                // Else roll away if can roll and have enough energy, and not using aura or in
-                // recover
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Roll));
-            } else if attack_data.angle < 15.0 {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-            }
-        }
-        // Logic to move. Intentionally kept separate from ability logic where possible
-        // so duplicated work is less necessary.
-        if attack_data.dist_sqrd < (2.0 * attack_data.min_attack_dist).powi(2) {
-            // Attempt to move away from target if too close
-            if let Some((bearing, speed)) = agent.chaser.chase(
-                &*read_data.terrain,
-                self.pos.0,
-                self.vel.0,
-                tgt_data.pos.0,
-                TraversalConfig {
-                    min_tgt_dist: 1.25,
-                    ..self.traversal_config
-                },
-            ) {
-                controller.inputs.move_dir =
-                    -bearing.xy().try_normalized().unwrap_or_else(Vec2::zero) * speed;
-            }
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            // Else attempt to circle target if neither too close nor too far
-            if let Some((bearing, speed)) = agent.chaser.chase(
-                &*read_data.terrain,
-                self.pos.0,
-                self.vel.0,
-                tgt_data.pos.0,
-                TraversalConfig {
-                    min_tgt_dist: 1.25,
-                    ..self.traversal_config
-                },
-            ) {
-                if can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                ) && attack_data.angle < 45.0
-                {
-                    controller.inputs.move_dir = bearing
-                        .xy()
-                        .rotated_z(thread_rng().gen_range(0.5..1.57))
-                        .try_normalized()
-                        .unwrap_or_else(Vec2::zero)
-                        * speed;
-                } else {
-                    // Unless cannot see target, then move towards them
-                    controller.inputs.move_dir =
-                        bearing.xy().try_normalized().unwrap_or_else(Vec2::zero) * speed;
-                    self.jump_if(controller, bearing.z > 1.5);
-                    controller.inputs.move_z = bearing.z;
-                }
-            }
-            // Sometimes try to roll
-            if self.body.map(|b| b.is_humanoid()).unwrap_or(false)
-                && !matches!(self.char_state, CharacterState::BasicAura(_))
-                && attack_data.dist_sqrd < 16.0f32.powi(2)
-                && thread_rng().gen::<f32>() < 0.01
-            {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Roll));
-            }
-        } else {
-            // If too far, move towards target
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_stone_golem_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if attack_data.in_min_range() && attack_data.angle < 90.0 {
-            controller.inputs.move_dir = Vec2::zero();
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-            //controller.inputs.primary.set_state(true);
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            if self.vel.0.is_approx_zero() {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(0)));
-            }
-            if self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None)
-                && can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                )
-                && attack_data.angle < 90.0
-            {
-                if agent.action_state.timer > 5.0 {
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Secondary));
-                    agent.action_state.timer = 0.0;
-                } else {
-                    agent.action_state.timer += read_data.dt.0;
-                }
-            }
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    #[allow(clippy::too_many_arguments)]
-    fn handle_circle_charge_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-        radius: u32,
-        circle_time: u32,
-    ) {
-        if agent.action_state.counter >= circle_time as f32 {
-            // if circle charge is in progress and time hasn't expired, continue charging
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Secondary));
-        }
-        if attack_data.in_min_range() {
-            if agent.action_state.counter > 0.0 {
-                // set timer and rotation counter to zero if in minimum range
-                agent.action_state.counter = 0.0;
-                agent.action_state.int_counter = 0;
-            } else {
-                // melee attack
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-                controller.inputs.move_dir = Vec2::zero();
-            }
-        } else if attack_data.dist_sqrd < (radius as f32 + attack_data.min_attack_dist).powi(2) {
-            // if in range to charge, circle, then charge
-            if agent.action_state.int_counter == 0 {
-                // if you haven't chosen a direction to go in, choose now
-                agent.action_state.int_counter = 1 + thread_rng().gen_bool(0.5) as u8;
-            }
-            if agent.action_state.counter < circle_time as f32 {
-                // circle if circle timer not ready
-                let move_dir = match agent.action_state.int_counter {
-                    1 =>
-                    // circle left if counter is 1
-                    {
-                        (tgt_data.pos.0 - self.pos.0)
-                            .xy()
-                            .rotated_z(0.47 * PI)
-                            .try_normalized()
-                            .unwrap_or_else(Vec2::unit_y)
-                    },
-                    2 =>
-                    // circle right if counter is 2
-                    {
-                        (tgt_data.pos.0 - self.pos.0)
-                            .xy()
-                            .rotated_z(-0.47 * PI)
-                            .try_normalized()
-                            .unwrap_or_else(Vec2::unit_y)
-                    },
-                    _ =>
-                    // if some illegal value slipped in, get zero vector
-                    {
-                        vek::Vec2::zero()
-                    },
-                };
-                let obstacle = read_data
-                    .terrain
-                    .ray(
-                        self.pos.0 + Vec3::unit_z(),
-                        self.pos.0 + move_dir.with_z(0.0) * 2.0 + Vec3::unit_z(),
-                    )
-                    .until(Block::is_solid)
-                    .cast()
-                    .1
-                    .map_or(true, |b| b.is_some());
-                if obstacle {
-                    // if obstacle detected, stop circling
-                    agent.action_state.counter = circle_time as f32;
-                }
-                controller.inputs.move_dir = move_dir;
-                // use counter as timer since timer may be modified in other parts of the code
-                agent.action_state.counter += read_data.dt.0;
-            }
-            // activating charge once circle timer expires is handled above
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            // if too far away from target, move towards them
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_quadlow_ranged_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if attack_data.dist_sqrd < (3.0 * attack_data.min_attack_dist).powi(2)
-            && attack_data.angle < 90.0
-        {
-            controller.inputs.move_dir = (tgt_data.pos.0 - self.pos.0)
-                .xy()
-                .try_normalized()
-                .unwrap_or_else(Vec2::unit_y);
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            if let Some((bearing, speed)) = agent.chaser.chase(
-                &*read_data.terrain,
-                self.pos.0,
-                self.vel.0,
-                tgt_data.pos.0,
-                TraversalConfig {
-                    min_tgt_dist: 1.25,
-                    ..self.traversal_config
-                },
-            ) {
-                if attack_data.angle < 15.0
-                    && can_see_tgt(
-                        &*read_data.terrain,
-                        self.pos,
-                        tgt_data.pos,
-                        attack_data.dist_sqrd,
-                    )
-                {
-                    if agent.action_state.timer > 5.0 {
-                        agent.action_state.timer = 0.0;
-                    } else if agent.action_state.timer > 2.5 {
-                        controller.inputs.move_dir = (tgt_data.pos.0 - self.pos.0)
-                            .xy()
-                            .rotated_z(1.75 * PI)
-                            .try_normalized()
-                            .unwrap_or_else(Vec2::zero)
-                            * speed;
-                        agent.action_state.timer += read_data.dt.0;
-                    } else {
-                        controller.inputs.move_dir = (tgt_data.pos.0 - self.pos.0)
-                            .xy()
-                            .rotated_z(0.25 * PI)
-                            .try_normalized()
-                            .unwrap_or_else(Vec2::zero)
-                            * speed;
-                        agent.action_state.timer += read_data.dt.0;
-                    }
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Secondary));
-                    self.jump_if(controller, bearing.z > 1.5);
-                    controller.inputs.move_z = bearing.z;
-                } else {
-                    controller.inputs.move_dir =
-                        bearing.xy().try_normalized().unwrap_or_else(Vec2::zero) * speed;
-                    self.jump_if(controller, bearing.z > 1.5);
-                    controller.inputs.move_z = bearing.z;
-                }
-            } else {
-                agent.target = None;
-            }
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_tail_slap_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if attack_data.angle < 90.0
-            && attack_data.dist_sqrd < (1.5 * attack_data.min_attack_dist).powi(2)
-        {
-            if agent.action_state.timer > 4.0 {
-                controller
-                    .actions
-                    .push(ControlAction::CancelInput(InputKind::Primary));
-                agent.action_state.timer = 0.0;
-            } else if agent.action_state.timer > 1.0 {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-                agent.action_state.timer += read_data.dt.0;
-            } else {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-                agent.action_state.timer += read_data.dt.0;
-            }
-            controller.inputs.move_dir = (tgt_data.pos.0 - self.pos.0)
-                .xy()
-                .try_normalized()
-                .unwrap_or_else(Vec2::unit_y)
-                * 0.1;
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_quadlow_quick_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if attack_data.angle < 90.0
-            && attack_data.dist_sqrd < (1.5 * attack_data.min_attack_dist).powi(2)
-        {
-            controller.inputs.move_dir = Vec2::zero();
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Secondary));
-        } else if attack_data.dist_sqrd < (3.0 * attack_data.min_attack_dist).powi(2)
-            && attack_data.dist_sqrd > (2.0 * attack_data.min_attack_dist).powi(2)
-            && attack_data.angle < 90.0
-        {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-            controller.inputs.move_dir = (tgt_data.pos.0 - self.pos.0)
-                .xy()
-                .rotated_z(-0.47 * PI)
-                .try_normalized()
-                .unwrap_or_else(Vec2::unit_y);
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_quadlow_basic_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if attack_data.angle < 70.0
-            && attack_data.dist_sqrd < (1.3 * attack_data.min_attack_dist).powi(2)
-        {
-            controller.inputs.move_dir = Vec2::zero();
-            if agent.action_state.timer > 5.0 {
-                agent.action_state.timer = 0.0;
-            } else if agent.action_state.timer > 2.0 {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-                agent.action_state.timer += read_data.dt.0;
-            } else {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-                agent.action_state.timer += read_data.dt.0;
-            }
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_quadmed_jump_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if attack_data.angle < 90.0
-            && attack_data.dist_sqrd < (1.5 * attack_data.min_attack_dist).powi(2)
-        {
-            controller.inputs.move_dir = Vec2::zero();
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Secondary));
-        } else if attack_data.angle < 15.0
-            && attack_data.dist_sqrd < (5.0 * attack_data.min_attack_dist).powi(2)
-        {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(0)));
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            if self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None)
-                && attack_data.angle < 15.0
-                && can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                )
-            {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-            }
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_quadmed_basic_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if attack_data.angle < 90.0 && attack_data.in_min_range() {
-            controller.inputs.move_dir = Vec2::zero();
-            if agent.action_state.timer < 2.0 {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-                agent.action_state.timer += read_data.dt.0;
-            } else if agent.action_state.timer < 3.0 {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-                agent.action_state.timer += read_data.dt.0;
-            } else {
-                agent.action_state.timer = 0.0;
-            }
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_quadlow_beam_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if attack_data.angle < 90.0
-            && attack_data.dist_sqrd < (2.5 * attack_data.min_attack_dist).powi(2)
-        {
-            controller.inputs.move_dir = Vec2::zero();
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Secondary));
-        } else if attack_data.dist_sqrd < (7.0 * attack_data.min_attack_dist).powi(2)
-            && attack_data.angle < 15.0
-        {
-            if agent.action_state.timer < 2.0 {
-                controller.inputs.move_dir = (tgt_data.pos.0 - self.pos.0)
-                    .xy()
-                    .rotated_z(0.47 * PI)
-                    .try_normalized()
-                    .unwrap_or_else(Vec2::unit_y);
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-                agent.action_state.timer += read_data.dt.0;
-            } else if agent.action_state.timer < 4.0 && attack_data.angle < 15.0 {
-                controller.inputs.move_dir = (tgt_data.pos.0 - self.pos.0)
-                    .xy()
-                    .rotated_z(-0.47 * PI)
-                    .try_normalized()
-                    .unwrap_or_else(Vec2::unit_y);
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-                agent.action_state.timer += read_data.dt.0;
-            } else if agent.action_state.timer < 6.0 && attack_data.angle < 15.0 {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(0)));
-                agent.action_state.timer += read_data.dt.0;
-            } else {
-                agent.action_state.timer = 0.0;
-            }
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_theropod_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if attack_data.angle < 90.0 && attack_data.in_min_range() {
-            controller.inputs.move_dir = Vec2::zero();
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_turret_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if can_see_tgt(
-            &*read_data.terrain,
-            self.pos,
-            tgt_data.pos,
-            attack_data.dist_sqrd,
-        ) && attack_data.angle < 15.0
-        {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-        } else {
-            agent.target = None;
-        }
-    }
-
-    fn handle_fixed_turret_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        controller.inputs.look_dir = self.ori.look_dir();
-        if can_see_tgt(
-            &*read_data.terrain,
-            self.pos,
-            tgt_data.pos,
-            attack_data.dist_sqrd,
-        ) && attack_data.angle < 15.0
-        {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-        } else {
-            agent.target = None;
-        }
-    }
-
-    fn handle_rotating_turret_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        controller.inputs.look_dir = Dir::new(
-            Quaternion::from_xyzw(self.ori.look_dir().x, self.ori.look_dir().y, 0.0, 0.0)
-                .rotated_z(6.0 * read_data.dt.0 as f32)
-                .into_vec3()
-                .try_normalized()
-                .unwrap_or_default(),
-        );
-        if can_see_tgt(
-            &*read_data.terrain,
-            self.pos,
-            tgt_data.pos,
-            attack_data.dist_sqrd,
-        ) {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-        } else {
-            agent.target = None;
-        }
-    }
-
-    fn handle_radial_turret_attack(
-        &self,
-        _agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if can_see_tgt(
-            &*read_data.terrain,
-            self.pos,
-            tgt_data.pos,
-            attack_data.dist_sqrd,
-        ) {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-        }
-    }
-
-    fn handle_tornado_attack(&self, controller: &mut Controller) {
-        controller
-            .actions
-            .push(ControlAction::basic_input(InputKind::Primary));
-    }
-
-    fn handle_mindflayer_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        const MINDFLAYER_ATTACK_DIST: f32 = 16.0;
-        const MINION_SUMMON_THRESHOLD: f32 = 0.20;
-        let health_fraction = self.health.map_or(0.5, |h| h.fraction());
-        // Sets counter at start of combat, using `condition` to keep track of whether
-        // it was already intitialized
-        if !agent.action_state.condition {
-            agent.action_state.counter = 1.0 - MINION_SUMMON_THRESHOLD;
-            agent.action_state.condition = true;
-        }
-
-        if agent.action_state.counter > health_fraction {
-            // Summon minions at particular thresholds of health
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(2)));
-
-            if matches!(self.char_state, CharacterState::BasicSummon(c) if matches!(c.stage_section, StageSection::Recover))
-            {
-                agent.action_state.counter -= MINION_SUMMON_THRESHOLD;
-            }
-        } else if attack_data.dist_sqrd < MINDFLAYER_ATTACK_DIST.powi(2) {
-            if can_see_tgt(
-                &*read_data.terrain,
-                self.pos,
-                tgt_data.pos,
-                attack_data.dist_sqrd,
-            ) {
-                // If close to target, use either primary or secondary ability
-                if matches!(self.char_state, CharacterState::BasicBeam(c) if c.timer < Duration::from_secs(10) && !matches!(c.stage_section, StageSection::Recover))
-                {
-                    // If already using primary, keep using primary until 10 consecutive seconds
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Primary));
-                } else if matches!(self.char_state, CharacterState::SpinMelee(c) if c.consecutive_spins < 50 && !matches!(c.stage_section, StageSection::Recover))
-                {
-                    // If already using secondary, keep using secondary until 10 consecutive
-                    // seconds
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Secondary));
-                } else if thread_rng().gen_bool(health_fraction.into()) {
-                    // Else if at high health, use primary
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Primary));
-                } else {
-                    // Else use secondary
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Secondary));
-                }
-            } else {
-                self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-            }
-        } else if attack_data.dist_sqrd < MAX_PATH_DIST.powi(2) {
-            // If too far from target, throw a random number of necrotic spheres at them and
-            // then blink to them.
-            let num_fireballs = &mut agent.action_state.int_counter;
-            if *num_fireballs == 0 {
-                controller.actions.push(ControlAction::StartInput {
-                    input: InputKind::Ability(0),
-                    target_entity: agent
-                        .target
-                        .as_ref()
-                        .and_then(|t| read_data.uids.get(t.target))
-                        .copied(),
-                    select_pos: None,
-                });
-                if matches!(self.char_state, CharacterState::Blink(_)) {
-                    *num_fireballs = rand::random::<u8>() % 4;
-                }
-            } else if matches!(self.char_state, CharacterState::Wielding(_)) {
-                *num_fireballs -= 1;
-                controller.actions.push(ControlAction::StartInput {
-                    input: InputKind::Ability(1),
-                    target_entity: agent
-                        .target
-                        .as_ref()
-                        .and_then(|t| read_data.uids.get(t.target))
-                        .copied(),
-                    select_pos: None,
-                });
-            }
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-        } else {
-            self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-        }
-    }
-
-    fn handle_birdlarge_fire_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        if attack_data.dist_sqrd > 30.0_f32.powi(2) {
-            let small_chance = thread_rng().gen_bool(0.05);
-
-            if small_chance
-                && can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                )
-                && attack_data.angle < 15.0
-            {
-                // Fireball
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-            }
-            // If some target
-            if let Some((bearing, speed)) = agent.chaser.chase(
-                &*read_data.terrain,
-                self.pos.0,
-                self.vel.0,
-                tgt_data.pos.0,
-                TraversalConfig {
-                    min_tgt_dist: 1.25,
-                    ..self.traversal_config
-                },
-            ) {
-                // Walk to target
-                controller.inputs.move_dir =
-                    bearing.xy().try_normalized().unwrap_or_else(Vec2::zero) * speed;
-                // If less than 20 blocks higher than target
-                if (self.pos.0.z - tgt_data.pos.0.z) < 20.0 {
-                    // Fly upward
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Fly));
-                    controller.inputs.move_z = 1.0;
-                } else {
-                    // Jump
-                    self.jump_if(controller, bearing.z > 1.5);
-                    controller.inputs.move_z = bearing.z;
-                }
-            }
-        }
-        // If higher than 2 blocks
-        else if !read_data
-            .terrain
-            .ray(self.pos.0, self.pos.0 - (Vec3::unit_z() * 2.0))
-            .until(Block::is_solid)
-            .cast()
-            .1
-            .map_or(true, |b| b.is_some())
-        {
-            // Do not increment the timer during this movement
-            // The next stage shouldn't trigger until the entity
-            // is on the ground
-            // Fly to target
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Fly));
-            let move_dir = tgt_data.pos.0 - self.pos.0;
-            controller.inputs.move_dir =
-                move_dir.xy().try_normalized().unwrap_or_else(Vec2::zero) * 2.0;
-            controller.inputs.move_z = move_dir.z - 0.5;
-            // If further than 4 blocks and random chance
-            if thread_rng().gen_bool(0.05)
-                && attack_data.dist_sqrd > (4.0 * attack_data.min_attack_dist).powi(2)
-                && attack_data.angle < 15.0
-            {
-                // Fireball
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-            }
-        }
-        // If further than 4 blocks and random chance
-        else if thread_rng().gen_bool(0.05)
-            && attack_data.dist_sqrd > (4.0 * attack_data.min_attack_dist).powi(2)
-            && attack_data.angle < 15.0
-        {
-            // Fireball
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-        }
-        // If random chance and less than 20 blocks higher than target and further than 4
-        // blocks
-        else if thread_rng().gen_bool(0.5)
-            && (self.pos.0.z - tgt_data.pos.0.z) < 15.0
-            && attack_data.dist_sqrd > (4.0 * attack_data.min_attack_dist).powi(2)
-        {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Fly));
-            controller.inputs.move_z = 1.0;
-        }
-        // If further than 2.5 blocks and random chance
-        else if attack_data.dist_sqrd > (2.5 * attack_data.min_attack_dist).powi(2) {
-            // Walk to target
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-        }
-        // If energy higher than 600 and random chance
-        else if self.energy.current() > 60.0 && thread_rng().gen_bool(0.4) {
-            // Shockwave
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(0)));
-        } else if attack_data.angle < 90.0 {
-            // Triple strike
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Secondary));
-        } else {
-            // Target is behind us. Turn around and chase target
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-        }
-    }
-
-    fn handle_birdlarge_breathe_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        // Set fly to false
-        controller
-            .actions
-            .push(ControlAction::CancelInput(InputKind::Fly));
-        if attack_data.dist_sqrd > 30.0_f32.powi(2) {
-            if thread_rng().gen_bool(0.05)
-                && can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                )
-                && attack_data.angle < 15.0
-            {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-            }
-            if let Some((bearing, speed)) = agent.chaser.chase(
-                &*read_data.terrain,
-                self.pos.0,
-                self.vel.0,
-                tgt_data.pos.0,
-                TraversalConfig {
-                    min_tgt_dist: 1.25,
-                    ..self.traversal_config
-                },
-            ) {
-                controller.inputs.move_dir =
-                    bearing.xy().try_normalized().unwrap_or_else(Vec2::zero) * speed;
-                if (self.pos.0.z - tgt_data.pos.0.z) < 20.0 {
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Fly));
-                    controller.inputs.move_z = 1.0;
-                } else {
-                    self.jump_if(controller, bearing.z > 1.5);
-                    controller.inputs.move_z = bearing.z;
-                }
-            }
-        } else if !read_data
-            .terrain
-            .ray(self.pos.0, self.pos.0 - (Vec3::unit_z() * 2.0))
-            .until(Block::is_solid)
-            .cast()
-            .1
-            .map_or(true, |b| b.is_some())
-        {
-            // Do not increment the timer during this movement
-            // The next stage shouldn't trigger until the entity
-            // is on the ground
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Fly));
-            let move_dir = tgt_data.pos.0 - self.pos.0;
-            controller.inputs.move_dir =
-                move_dir.xy().try_normalized().unwrap_or_else(Vec2::zero) * 2.0;
-            controller.inputs.move_z = move_dir.z - 0.5;
-            if thread_rng().gen_bool(0.05)
-                && attack_data.dist_sqrd > (4.0 * attack_data.min_attack_dist).powi(2)
-                && attack_data.angle < 15.0
-            {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-            }
-        } else if thread_rng().gen_bool(0.05)
-            && attack_data.dist_sqrd > (4.0 * attack_data.min_attack_dist).powi(2)
-            && attack_data.angle < 15.0
-        {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-        } else if thread_rng().gen_bool(0.5)
-            && (self.pos.0.z - tgt_data.pos.0.z) < 15.0
-            && attack_data.dist_sqrd > (4.0 * attack_data.min_attack_dist).powi(2)
-        {
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Fly));
-            controller.inputs.move_z = 1.0;
-        } else if attack_data.dist_sqrd > (3.0 * attack_data.min_attack_dist).powi(2) {
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-        } else if self.energy.current() > 60.0
-            && agent.action_state.timer < 3.0
-            && attack_data.angle < 15.0
-        {
-            // Fire breath attack
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(0)));
-            // Move towards the target slowly
-            self.path_toward_target(
-                agent,
-                controller,
-                tgt_data,
-                read_data,
-                true,
-                false,
-                Some(0.5),
-            );
-            agent.action_state.timer += read_data.dt.0;
-        } else if agent.action_state.timer < 6.0
-            && attack_data.angle < 90.0
-            && attack_data.in_min_range()
-        {
-            // Triplestrike
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Secondary));
-            agent.action_state.timer += read_data.dt.0;
-        } else {
-            // Reset timer
-            agent.action_state.timer = 0.0;
-            // Target is behind us or the timer needs to be reset. Chase target
-            self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-        }
-    }
-
-    fn handle_birdlarge_basic_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        const BIRD_ATTACK_RANGE: f32 = 4.0;
-        const BIRD_CHARGE_DISTANCE: f32 = 15.0;
-        let bird_attack_distance = self.body.map_or(0.0, |b| b.max_radius()) + BIRD_ATTACK_RANGE;
-        // Increase action timer
-        agent.action_state.timer += read_data.dt.0;
-        // If higher than 2 blocks
-        if !read_data
-            .terrain
-            .ray(self.pos.0, self.pos.0 - (Vec3::unit_z() * 2.0))
-            .until(Block::is_solid)
-            .cast()
-            .1
-            .map_or(true, |b| b.is_some())
-        {
-            // Fly to target and land
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Fly));
-            let move_dir = tgt_data.pos.0 - self.pos.0;
-            controller.inputs.move_dir =
-                move_dir.xy().try_normalized().unwrap_or_else(Vec2::zero) * 2.0;
-            controller.inputs.move_z = move_dir.z - 0.5;
-        } else if agent.action_state.timer > 8.0 {
-            // If action timer higher than 8, make bird summon tornadoes
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Secondary));
-            if matches!(self.char_state, CharacterState::BasicSummon(c) if matches!(c.stage_section, StageSection::Recover))
-            {
-                // Reset timer
-                agent.action_state.timer = 0.0;
-            }
-        } else if matches!(self.char_state, CharacterState::DashMelee(c) if !matches!(c.stage_section, StageSection::Recover))
-        {
-            // If already in dash, keep dashing if not in recover
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(0)));
-        } else if matches!(self.char_state, CharacterState::ComboMelee(c) if matches!(c.stage_section, StageSection::Recover))
-        {
-            // If already in combo keep comboing if not in recover
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-        } else if attack_data.dist_sqrd > BIRD_CHARGE_DISTANCE.powi(2) {
-            // Charges at target if they are far enough away
-            if attack_data.angle < 60.0 {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(0)));
-            }
-        } else if attack_data.dist_sqrd < bird_attack_distance.powi(2) {
-            // Combo melee target
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-            agent.action_state.condition = true;
-        }
-        // Make bird move towards target
-        self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-    }
-
-    fn handle_minotaur_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        const MINOTAUR_FRENZY_THRESHOLD: f32 = 0.5;
-        const MINOTAUR_ATTACK_RANGE: f32 = 5.0;
-        const MINOTAUR_CHARGE_DISTANCE: f32 = 15.0;
-        let minotaur_attack_distance =
-            self.body.map_or(0.0, |b| b.max_radius()) + MINOTAUR_ATTACK_RANGE;
-        let health_fraction = self.health.map_or(1.0, |h| h.fraction());
-        // Sets action counter at start of combat
-        if agent.action_state.counter < MINOTAUR_FRENZY_THRESHOLD
-            && health_fraction > MINOTAUR_FRENZY_THRESHOLD
-        {
-            agent.action_state.counter = MINOTAUR_FRENZY_THRESHOLD;
-        }
-        if health_fraction < agent.action_state.counter {
-            // Makes minotaur buff itself with frenzy
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(1)));
-            if matches!(self.char_state, CharacterState::SelfBuff(c) if matches!(c.stage_section, StageSection::Recover))
-            {
-                agent.action_state.counter = 0.0;
-            }
-        } else if matches!(self.char_state, CharacterState::DashMelee(c) if !matches!(c.stage_section, StageSection::Recover))
-        {
-            // If already charging, keep charging if not in recover
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(0)));
-        } else if matches!(self.char_state, CharacterState::ChargedMelee(c) if matches!(c.stage_section, StageSection::Charge) && c.timer < c.static_data.charge_duration)
-        {
-            // If already charging a melee attack, keep charging it if charging
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Primary));
-        } else if attack_data.dist_sqrd > MINOTAUR_CHARGE_DISTANCE.powi(2) {
-            // Charges at target if they are far enough away
-            if attack_data.angle < 60.0 {
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(0)));
-            }
-        } else if attack_data.dist_sqrd < minotaur_attack_distance.powi(2) {
-            if agent.action_state.condition && !self.char_state.is_attack() {
-                // Cripple target if not just used cripple
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-                agent.action_state.condition = false;
-            } else if !self.char_state.is_attack() {
-                // Cleave target if not just used cleave
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-                agent.action_state.condition = true;
-            }
-        }
-        // Make minotaur move towards target
-        self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-    }
-
-    fn handle_clay_golem_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        const GOLEM_MELEE_RANGE: f32 = 4.0;
-        const GOLEM_LASER_RANGE: f32 = 30.0;
-        const GOLEM_LONG_RANGE: f32 = 50.0;
-        const GOLEM_TARGET_SPEED: f32 = 8.0;
-        let golem_melee_range = self.body.map_or(0.0, |b| b.max_radius()) + GOLEM_MELEE_RANGE;
-        // Fraction of health, used for activation of shockwave
-        // If golem don't have health for some reason, assume it's full
-        let health_fraction = self.health.map_or(1.0, |h| h.fraction());
-        // Magnitude squared of cross product of target velocity with golem orientation
-        let target_speed_cross_sqd = agent
-            .target
-            .as_ref()
-            .map(|t| t.target)
-            .and_then(|e| read_data.velocities.get(e))
-            .map_or(0.0, |v| v.0.cross(self.ori.look_vec()).magnitude_squared());
-        if attack_data.dist_sqrd < golem_melee_range.powi(2) {
-            if agent.action_state.counter < 7.5 {
-                // If target is close, whack them
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-                agent.action_state.counter += read_data.dt.0;
-            } else {
-                // If whacked for too long, nuke them
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(1)));
-                if matches!(self.char_state, CharacterState::BasicRanged(c) if matches!(c.stage_section, StageSection::Recover))
-                {
-                    agent.action_state.counter = 0.0;
-                }
-            }
-        } else if attack_data.dist_sqrd < GOLEM_LASER_RANGE.powi(2) {
-            if matches!(self.char_state, CharacterState::BasicBeam(c) if c.timer < Duration::from_secs(5))
-                || target_speed_cross_sqd < GOLEM_TARGET_SPEED.powi(2)
-                    && can_see_tgt(
-                        &*read_data.terrain,
-                        self.pos,
-                        tgt_data.pos,
-                        attack_data.dist_sqrd,
-                    )
-                    && attack_data.angle < 45.0
-            {
-                // If target in range threshold and haven't been lasering for more than 5
-                // seconds already or if target is moving slow-ish, laser them
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-            } else if health_fraction < 0.7 {
-                // Else target moving too fast for laser, shockwave time.
-                // But only if damaged enough
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(0)));
-            }
-        } else if attack_data.dist_sqrd < GOLEM_LONG_RANGE.powi(2) {
-            if target_speed_cross_sqd < GOLEM_TARGET_SPEED.powi(2)
-                && can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                )
-            {
-                // If target is far-ish and moving slow-ish, rocket them
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(1)));
-            } else if health_fraction < 0.7 {
-                // Else target moving too fast for laser, shockwave time.
-                // But only if damaged enough
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(0)));
-            }
-        }
-        // Make clay golem move towards target
-        self.path_toward_target(agent, controller, tgt_data, read_data, true, false, None);
-    }
-
-    fn handle_tidal_warrior_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        const SCUTTLE_RANGE: f32 = 40.0;
-        const BUBBLE_RANGE: f32 = 20.0;
-        const MINION_SUMMON_THRESHOLD: f32 = 0.20;
-        let health_fraction = self.health.map_or(0.5, |h| h.fraction());
-        // Sets counter at start of combat, using `condition` to keep track of whether
-        // it was already intitialized
-        if !agent.action_state.condition {
-            agent.action_state.counter = 1.0 - MINION_SUMMON_THRESHOLD;
-            agent.action_state.condition = true;
-        }
-
-        if agent.action_state.counter > health_fraction {
-            // Summon minions at particular thresholds of health
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(1)));
-
-            if matches!(self.char_state, CharacterState::BasicSummon(c) if matches!(c.stage_section, StageSection::Recover))
-            {
-                agent.action_state.counter -= MINION_SUMMON_THRESHOLD;
-            }
-        } else if attack_data.dist_sqrd < SCUTTLE_RANGE.powi(2) {
-            if matches!(self.char_state, CharacterState::DashMelee(c) if !matches!(c.stage_section, StageSection::Recover))
-            {
-                // Keep scuttling if already in dash melee and not in recover
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-            } else if attack_data.dist_sqrd < BUBBLE_RANGE.powi(2) {
-                if matches!(self.char_state, CharacterState::BasicBeam(c) if !matches!(c.stage_section, StageSection::Recover) && c.timer < Duration::from_secs(10))
-                {
-                    // Keep shooting bubbles at them if already in basic beam and not in recover and
-                    // have not been bubbling too long
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Ability(0)));
-                } else if attack_data.in_min_range() && attack_data.angle < 60.0 {
-                    // Pincer them if they're in range and angle
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Primary));
-                } else if attack_data.angle < 30.0
-                    && can_see_tgt(
-                        &*read_data.terrain,
-                        self.pos,
-                        tgt_data.pos,
-                        attack_data.dist_sqrd,
-                    )
-                {
-                    // Start bubbling them if not close enough to do something else and in angle and
-                    // can see target
-                    controller
-                        .actions
-                        .push(ControlAction::basic_input(InputKind::Ability(0)));
-                }
-            } else if attack_data.angle < 90.0
-                && can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                )
-            {
-                // Start scuttling if not close enough to do something else and in angle and can
-                // see target
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-            }
-        }
-        // Always attempt to path towards target
-        self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-    }
-
-    fn handle_yeti_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        const ICE_SPIKES_RANGE: f32 = 15.0;
-        const ICE_BREATH_RANGE: f32 = 10.0;
-        const ICE_BREATH_TIMER: f32 = 10.0;
-        const SNOWBALL_MAX_RANGE: f32 = 50.0;
-
-        agent.action_state.counter += read_data.dt.0;
-
-        if attack_data.dist_sqrd < ICE_BREATH_RANGE.powi(2) {
-            if matches!(self.char_state, CharacterState::BasicBeam(c) if c.timer < Duration::from_secs(2))
-            {
-                // Keep using ice breath for 2 second
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(0)));
-            } else if agent.action_state.counter > ICE_BREATH_TIMER {
-                // Use ice breath if timer has gone for long enough
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Ability(0)));
-
-                if matches!(self.char_state, CharacterState::BasicBeam(_)) {
-                    // Resets action counter when using beam
-                    agent.action_state.counter = 0.0;
-                }
-            } else if attack_data.in_min_range() {
-                // Basic attack if on top of them
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-            } else {
-                // Use ice spikes if too far for other abilities
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-            }
-        } else if attack_data.dist_sqrd < ICE_SPIKES_RANGE.powi(2) && attack_data.angle < 60.0 {
-            // Use ice spikes if in range
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Secondary));
-        } else if attack_data.dist_sqrd < SNOWBALL_MAX_RANGE.powi(2) && attack_data.angle < 60.0 {
-            // Otherwise, chuck all the snowballs
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(1)));
-        }
-
-        // Always attempt to path towards target
-        self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
-    }
-
-    fn handle_harvester_attack(
-        &self,
-        agent: &mut Agent,
-        controller: &mut Controller,
-        attack_data: &AttackData,
-        tgt_data: &TargetData,
-        read_data: &ReadData,
-    ) {
-        const VINE_CREATION_THRESHOLD: f32 = 0.50;
-        const FIRE_BREATH_RANGE: f32 = 20.0;
-        const MAX_PUMPKIN_RANGE: f32 = 50.0;
-        let health_fraction = self.health.map_or(0.5, |h| h.fraction());
-
-        if health_fraction < VINE_CREATION_THRESHOLD && !agent.action_state.condition {
-            // Summon vines when reach threshold of health
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(0)));
-
-            if matches!(self.char_state, CharacterState::SpriteSummon(c) if matches!(c.stage_section, StageSection::Recover))
-            {
-                agent.action_state.condition = true;
-            }
-        } else if attack_data.dist_sqrd < FIRE_BREATH_RANGE.powi(2) {
-            if matches!(self.char_state, CharacterState::BasicBeam(c) if c.timer < Duration::from_secs(5))
-                && can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                )
-            {
-                // Keep breathing fire if close enough, can see target, and have not been
-                // breathing for more than 5 seconds
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-            } else if attack_data.in_min_range() && attack_data.angle < 60.0 {
-                // Scythe them if they're in range and angle
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Primary));
-            } else if attack_data.angle < 30.0
-                && can_see_tgt(
-                    &*read_data.terrain,
-                    self.pos,
-                    tgt_data.pos,
-                    attack_data.dist_sqrd,
-                )
-            {
-                // Start breathing fire at them if close enough, in angle, and can see target
-                controller
-                    .actions
-                    .push(ControlAction::basic_input(InputKind::Secondary));
-            }
-        } else if attack_data.dist_sqrd < MAX_PUMPKIN_RANGE.powi(2)
-            && can_see_tgt(
-                &*read_data.terrain,
-                self.pos,
-                tgt_data.pos,
-                attack_data.dist_sqrd,
-            )
-        {
-            // Throw a pumpkin at them if close enough and can see them
-            controller
-                .actions
-                .push(ControlAction::basic_input(InputKind::Ability(1)));
-        }
-        // Always attempt to path towards target
-        self.path_toward_target(agent, controller, tgt_data, read_data, false, false, None);
     }
 
     fn follow(
@@ -0,0 +1,340 @@
+//! Runs the combat tactics defined in assets, see [`common::tactic`]
+use super::{can_see_tgt, AgentData, AttackData, ReadData, TargetData, MAX_PATH_DIST};
+use common::{
+    comp::{Agent, CharacterState, ControlAction, Controller, InputKind},
+    states::utils::StageSection,
+    tactic::{Condition, Node, Tactician},
+    terrain::Block,
+    util::Dir,
+    vol::ReadVol,
+};
+use rand::{thread_rng, Rng};
+use vek::*;
+
+impl<'a> AgentData<'a> {
+    /// Runs a node of a tactic, returning whether it succeeded.
+    pub(super) fn run_tactic(
+        &self,
+        agent: &mut Agent,
+        controller: &mut Controller,
+        attack_data: &AttackData,
+        tgt_data: &TargetData,
+        read_data: &ReadData,
+        node: &Node,
+    ) -> bool {
+        node.run(&mut TacticRun {
+            data: self,
+            agent,
+            controller,
+            attack_data,
+            tgt_data,
+            read_data,
+        })
+    }
+}
+
+/// An agent running its tactic against its target for a tick
+struct TacticRun<'a, 'r> {
+    data: &'a AgentData<'a>,
+    agent: &'a mut Agent,
+    controller: &'a mut Controller,
+    attack_data: &'a AttackData,
+    tgt_data: &'a TargetData<'a>,
+    read_data: &'a ReadData<'r>,
+}
+
+impl<'a, 'r> Tactician for TacticRun<'a, 'r> {
+    fn act(&mut self, node: &Node) -> bool {
+        match node {
+            Node::Select(_) | Node::When(_, _) | Node::Sequence(_) | Node::All(_) => node.run(self),
+            Node::Use(input) => {
+                self.controller
+                    .actions
+                    .push(ControlAction::basic_input(*input));
+                true
+            },
+            Node::UseOnTarget(input) => {
+                self.controller.actions.push(ControlAction::StartInput {
+                    input: *input,
+                    target_entity: self
+                        .agent
+                        .target
+                        .as_ref()
+                        .and_then(|t| self.read_data.uids.get(t.target))
+                        .copied(),
+                    select_pos: None,
+                });
+                true
+            },
+            Node::Cancel(input) => {
+                self.controller
+                    .actions
+                    .push(ControlAction::CancelInput(*input));
+                true
+            },
+            Node::Stand => {
+                self.controller.inputs.move_dir = Vec2::zero();
+                true
+            },
+            Node::Chase | Node::ChaseSeparated | Node::Approach(_) => self.data.path_toward_target(
+                self.agent,
+                self.controller,
+                self.tgt_data,
+                self.read_data,
+                self.attack_data.dist_sqrd < MAX_PATH_DIST.powi(2),
+                matches!(node, Node::ChaseSeparated),
+                match node {
+                    Node::Approach(speed) => Some(*speed),
+                    _ => None,
+                },
+            ),
+            Node::Strafe { angle, speed } => {
+                self.controller.inputs.move_dir = (self.tgt_data.pos.0 - self.data.pos.0)
+                    .xy()
+                    .rotated_z(angle.to_radians())
+                    .try_normalized()
+                    .unwrap_or_else(Vec2::unit_y)
+                    * *speed;
+                true
+            },
+            Node::Circle { angle, speed } => {
+                if self.agent.action_state.int_counter == 0 {
+                    self.agent.action_state.int_counter = 1 + thread_rng().gen_bool(0.5) as u8;
+                }
+                let angle = if self.agent.action_state.int_counter == 1 {
+                    *angle
+                } else {
+                    -*angle
+                };
+                self.controller.inputs.move_dir = (self.tgt_data.pos.0 - self.data.pos.0)
+                    .xy()
+                    .rotated_z(angle.to_radians())
+                    .try_normalized()
+                    .unwrap_or_else(Vec2::unit_y)
+                    * *speed;
+                true
+            },
+            Node::Flee => {
+                self.data.flee(
+                    self.agent,
+                    self.controller,
+                    &self.read_data.terrain,
+                    self.tgt_data.pos,
+                );
+                true
+            },
+            Node::Fly => {
+                self.controller
+                    .actions
+                    .push(ControlAction::basic_input(InputKind::Fly));
+                self.controller.inputs.move_z = 1.0;
+                true
+            },
+            Node::FlyToward => {
+                self.controller
+                    .actions
+                    .push(ControlAction::basic_input(InputKind::Fly));
+                let move_dir = self.tgt_data.pos.0 - self.data.pos.0;
+                self.controller.inputs.move_dir =
+                    move_dir.xy().try_normalized().unwrap_or_else(Vec2::zero) * 2.0;
+                self.controller.inputs.move_z = move_dir.z - 0.5;
+                true
+            },
+            Node::LookForward => {
+                self.controller.inputs.look_dir = self.data.ori.look_dir();
+                true
+            },
+            Node::Spin(speed) => {
+                self.controller.inputs.look_dir = Dir::new(
+                    Quaternion::from_xyzw(
+                        self.data.ori.look_dir().x,
+                        self.data.ori.look_dir().y,
+                        0.0,
+                        0.0,
+                    )
+                    .rotated_z(speed * self.read_data.dt.0 as f32)
+                    .into_vec3()
+                    .try_normalized()
+                    .unwrap_or_default(),
+                );
+                true
+            },
+            Node::Forget => {
+                self.agent.target = None;
+                true
+            },
+            Node::TickTimer => {
+                self.agent.action_state.timer += self.read_data.dt.0;
+                true
+            },
+            Node::ResetTimer => {
+                self.agent.action_state.timer = 0.0;
+                true
+            },
+            Node::TickCounter => {
+                self.agent.action_state.counter += self.read_data.dt.0;
+                true
+            },
+            Node::SetCounter(value) => {
+                self.agent.action_state.counter = *value;
+                true
+            },
+            Node::AddCounter(value) => {
+                self.agent.action_state.counter += *value;
+                true
+            },
+            Node::SetFlag(value) => {
+                self.agent.action_state.condition = *value;
+                true
+            },
+            Node::SetCount(count) => {
+                self.agent.action_state.int_counter = *count;
+                true
+            },
+            Node::RandomCount(bound) => {
+                self.agent.action_state.int_counter = thread_rng().gen_range(0..(*bound).max(1));
+                true
+            },
+            Node::DecCount => match self.agent.action_state.int_counter.checked_sub(1) {
+                Some(count) => {
+                    self.agent.action_state.int_counter = count;
+                    true
+                },
+                None => false,
+            },
+            Node::StartTimer(name) => {
+                self.agent.timer.start_named(self.read_data.time.0, name);
+                true
+            },
+        }
+    }
+
+    fn check(&mut self, condition: &Condition) -> bool {
+        let target_vel = || {
+            self.agent
+                .target
+                .as_ref()
+                .and_then(|target| self.read_data.velocities.get(target.target))
+                .map_or(Vec3::zero(), |vel| vel.0)
+        };
+        match condition {
+            Condition::InRange(multiple) => {
+                self.attack_data.dist_sqrd < (multiple * self.attack_data.min_attack_dist).powi(2)
+            },
+            Condition::Within(dist) => self.attack_data.dist_sqrd < dist.powi(2),
+            Condition::Reach(dist) => {
+                self.attack_data.dist_sqrd
+                    < (self.data.body.map_or(0.0, |b| b.max_radius()) + dist).powi(2)
+            },
+            Condition::NearRange(dist) => {
+                self.attack_data.dist_sqrd < (dist + self.attack_data.min_attack_dist).powi(2)
+            },
+            Condition::Angle(angle) => self.attack_data.angle < *angle,
+            Condition::CanSee => can_see_tgt(
+                &*self.read_data.terrain,
+                self.data.pos,
+                self.tgt_data.pos,
+                self.attack_data.dist_sqrd,
+            ),
+            Condition::TargetSlowerThan(speed) => {
+                target_vel()
+                    .cross(self.data.ori.look_vec())
+                    .magnitude_squared()
+                    < speed.powi(2)
+            },
+            Condition::TargetFasterThan(speed) => target_vel().magnitude_squared() > speed.powi(2),
+            Condition::TargetApproaching(speed) => {
+                -target_vel().dot(self.data.ori.look_vec()) > *speed
+            },
+            Condition::Above(height) => self.data.pos.0.z - self.tgt_data.pos.0.z > *height,
+            Condition::Airborne(height) => self
+                .read_data
+                .terrain
+                .ray(self.data.pos.0, self.data.pos.0 - Vec3::unit_z() * *height)
+                .until(Block::is_solid)
+                .cast()
+                .1
+                .map_or(false, |block| block.is_none()),
+            Condition::Blocked(dist) => {
+                self.data.vel.0.xy().try_normalized().map_or(false, |dir| {
+                    self.read_data
+                        .terrain
+                        .ray(
+                            self.data.pos.0 + Vec3::unit_z(),
+                            self.data.pos.0 + dir.with_z(0.0) * *dist + Vec3::unit_z(),
+                        )
+                        .until(Block::is_solid)
+                        .cast()
+                        .1
+                        .map_or(true, |block| block.is_some())
+                })
+            },
+            Condition::Energy(energy) => self.data.energy.current() > *energy,
+            Condition::Health(fraction) => {
+                self.data.health.map_or(1.0, |h| h.fraction()) < *fraction
+            },
+            Condition::HealthBelowCounter => {
+                self.data.health.map_or(1.0, |h| h.fraction()) < self.agent.action_state.counter
+            },
+            Condition::HasSkill(skill) => self.data.skill_set.has_skill(*skill),
+            Condition::HasBuff(kind) => self
+                .read_data
+                .buffs
+                .get(*self.data.entity)
+                .map_or(false, |buffs| buffs.iter_kind(*kind).next().is_some()),
+            Condition::Combo(combo) => self
+                .read_data
+                .combos
+                .get(*self.data.entity)
+                .map_or(false, |c| c.counter() >= *combo),
+            Condition::Humanoid => self.data.body.map_or(false, |b| b.is_humanoid()),
+            Condition::Still => self.data.vel.0.is_approx_zero(),
+            Condition::Chance(probability) => thread_rng().gen_bool(*probability),
+            Condition::HealthChance => thread_rng().gen_bool(
+                self.data
+                    .health
+                    .map_or(0.5, |h| h.fraction())
+                    .max(0.0)
+                    .min(1.0)
+                    .into(),
+            ),
+            Condition::Timer(secs) => self.agent.action_state.timer > *secs,
+            Condition::Counter(value) => self.agent.action_state.counter > *value,
+            Condition::Flag => self.agent.action_state.condition,
+            Condition::Count => self.agent.action_state.int_counter > 0,
+            Condition::Elapsed(name, secs) => self
+                .agent
+                .timer
+                .time_of_last_named(name)
+                .map_or(true, |start| self.read_data.time.0 - start >= *secs),
+            Condition::Attacking => self.data.char_state.is_attack(),
+            Condition::Charging(fraction) => matches!(
+                self.data.char_state,
+                CharacterState::ChargedRanged(c)
+                    if c.stage_section != StageSection::Recover && c.charge_frac() < *fraction
+            ),
+            Condition::Spinning(spins) => matches!(
+                self.data.char_state,
+                CharacterState::SpinMelee(c)
+                    if c.stage_section != StageSection::Recover && c.consecutive_spins < *spins
+            ),
+            Condition::State {
+                name,
+                stage,
+                not_stage,
+                max_secs,
+            } => {
+                <&'static str>::from(self.data.char_state) == name.as_str()
+                    && self.data.char_state.attack_stage().map_or(
+                        stage.is_none() && not_stage.is_none() && max_secs.is_none(),
+                        |(section, timer)| {
+                            stage.map_or(true, |stage| section == stage)
+                                && not_stage.map_or(true, |stage| section != stage)
+                                && max_secs.map_or(true, |secs| timer.as_secs_f32() < secs)
+                        },
+                    )
+            },
+            Condition::Not(_) | Condition::Any(_) | Condition::All(_) => condition.holds(self),
+        }
+    }
+}
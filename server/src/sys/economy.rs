@@ -0,0 +1,25 @@
+use crate::Settings;
+use common::resources::DeltaTime;
+use common_ecs::{Job, Origin, Phase, System};
+use specs::{Read, ReadExpect, WriteExpect};
+use world::sim2::RuntimeEconomy;
+
+/// This system keeps simulating the economy of the sites at the speed
+/// configured in the server settings
+#[derive(Default)]
+pub struct Sys;
+impl<'a> System<'a> for Sys {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        ReadExpect<'a, Settings>,
+        WriteExpect<'a, RuntimeEconomy>,
+    );
+
+    const NAME: &'static str = "economy";
+    const ORIGIN: Origin = Origin::Server;
+    const PHASE: Phase = Phase::Create;
+
+    fn run(_job: &mut Job<Self>, (dt, settings, mut economy): Self::SystemData) {
+        economy.simulate(dt.0 * settings.economy_speed / 3600.0);
+    }
+}
@@ -1,4 +1,5 @@
 pub mod agent;
+#[cfg(feature = "worldgen")] pub mod economy;
 pub mod entity_sync;
 pub mod invite_timeout;
 pub mod metrics;
@@ -6,6 +7,7 @@ pub mod msg;
 pub mod object;
 pub mod persistence;
 pub mod pets;
+pub mod quest;
 pub mod sentinel;
 pub mod subscription;
 pub mod terrain;
@@ -22,6 +24,7 @@ use std::{
 };
 
 pub type PersistenceScheduler = SysScheduler<persistence::Sys>;
+pub type QuestScheduler = SysScheduler<quest::Sys>;
 
 pub fn add_server_systems(dispatch_builder: &mut DispatcherBuilder) {
     dispatch::<melee::Sys>(dispatch_builder, &[&projectile::Sys::sys_name()]);
@@ -29,10 +32,13 @@ pub fn add_server_systems(dispatch_builder: &mut DispatcherBuilder) {
     dispatch::<agent::Sys>(dispatch_builder, &[]);
     dispatch::<terrain::Sys>(dispatch_builder, &[&msg::terrain::Sys::sys_name()]);
     dispatch::<waypoint::Sys>(dispatch_builder, &[]);
+    dispatch::<quest::Sys>(dispatch_builder, &[]);
     dispatch::<invite_timeout::Sys>(dispatch_builder, &[]);
     dispatch::<persistence::Sys>(dispatch_builder, &[]);
     dispatch::<object::Sys>(dispatch_builder, &[]);
     dispatch::<wiring::Sys>(dispatch_builder, &[]);
+    #[cfg(feature = "worldgen")]
+    dispatch::<economy::Sys>(dispatch_builder, &[]);
 }
 
 pub fn run_sync_systems(ecs: &mut specs::World) {
@@ -4,8 +4,10 @@ use crate::{
     client::Client,
     persistence::{character_loader::CharacterLoader, character_updater::CharacterUpdater},
     presence::Presence,
+    settings::Capability,
     EditableSettings,
 };
+use chrono::Utc;
 use common::{
     comp::{ChatType, Player, UnresolvedChatMsg},
     event::{EventBus, ServerEvent},
@@ -35,8 +37,19 @@ impl Sys {
         match msg {
             // Request spectator state
             ClientGeneral::Spectate => {
-                if players.contains(entity) {
-                    warn!("Spectator mode not yet implemented on server");
+                if let Some(player) = players.get(entity) {
+                    // Only admins and players whose permission groups allow it may spectate
+                    let may_spectate = editable_settings.admins.contains_key(&player.uuid())
+                        || editable_settings.permissions.has_capability(
+                            player.uuid(),
+                            &Capability::Spectate,
+                            Utc::now(),
+                        );
+                    if may_spectate {
+                        warn!("Spectator mode not yet implemented on server");
+                    } else {
+                        debug!("dropped Spectate msg from player without permission to spectate")
+                    }
                 } else {
                     debug!("dropped Spectate msg from unregistered client")
                 }
@@ -1,30 +1,131 @@
-use crate::client::Client;
+use crate::{
+    chat_limiter::{ChatLimiter, Verdict},
+    client::Client,
+    metrics::ChatMetrics,
+    settings::{ChatLimits, Settings},
+};
 use common::{
-    comp::{ChatMode, Player},
+    comp::{Admin, ChatMode, ChatType, Player},
     event::{EventBus, ServerEvent},
     resources::Time,
     uid::Uid,
 };
 use common_ecs::{Job, Origin, Phase, System};
 use common_net::msg::{
-    validate_chat_msg, ChatMsgValidationError, ClientGeneral, MAX_BYTES_CHAT_MSG,
+    validate_chat_msg, ChatMsgValidationError, ClientGeneral, DisconnectReason, ServerGeneral,
+    MAX_BYTES_CHAT_MSG,
 };
-use specs::{Entities, Join, Read, ReadStorage};
-use tracing::{debug, error, warn};
+use specs::{Entities, Join, Read, ReadExpect, ReadStorage, WriteStorage};
+use tracing::{debug, error, info, warn};
 
 impl Sys {
+    /// Applies the chat limits to a chat message (`Some`) or command (`None`)
+    /// of a player, returning whether it may be processed.
+    #[allow(clippy::too_many_arguments)]
+    fn within_chat_limits(
+        server_emitter: &mut common::event::Emitter<'_, ServerEvent>,
+        entity: specs::Entity,
+        client: &Client,
+        player: &Player,
+        limiter: &mut ChatLimiter,
+        limits: &ChatLimits,
+        time: f64,
+        chat_metrics: &ChatMetrics,
+        message: Option<&str>,
+    ) -> bool {
+        let tell = |message: String| {
+            client.send_fallible(ServerGeneral::server_msg(ChatType::CommandError, message))
+        };
+        let offense = match limiter.check(limits, time, message) {
+            Verdict::Allow => return true,
+            Verdict::Muted(remaining) => {
+                chat_metrics
+                    .messages_refused
+                    .with_label_values(&["muted"])
+                    .inc();
+                tell(format!(
+                    "You are muted for spamming for another {:.0} seconds.",
+                    remaining.ceil()
+                ));
+                return false;
+            },
+            Verdict::Warn(offense) => {
+                chat_metrics
+                    .enforcements
+                    .with_label_values(&["warning"])
+                    .inc();
+                info!(?offense, "Warned {} for spamming", player.alias);
+                tell(format!(
+                    "Stop {}, or you will be muted.",
+                    offense.describe()
+                ));
+                offense
+            },
+            Verdict::Mute(offense) => {
+                chat_metrics.enforcements.with_label_values(&["mute"]).inc();
+                warn!(?offense, "Muted {} for spamming", player.alias);
+                tell(format!(
+                    "You are muted for {:.0} seconds for {}. Keep going and you will be kicked.",
+                    limits.mute_secs,
+                    offense.describe()
+                ));
+                offense
+            },
+            Verdict::Kick(offense) => {
+                chat_metrics.enforcements.with_label_values(&["kick"]).inc();
+                warn!(?offense, "Kicked {} for spamming", player.alias);
+                client.send_fallible(ServerGeneral::Disconnect(DisconnectReason::Kicked(
+                    format!("Kicked for {}", offense.describe()),
+                )));
+                server_emitter.emit(ServerEvent::ClientDisconnect(
+                    entity,
+                    common::comp::DisconnectReason::Kicked,
+                ));
+                offense
+            },
+        };
+        chat_metrics
+            .messages_refused
+            .with_label_values(&[offense.label()])
+            .inc();
+        false
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn handle_general_msg(
         server_emitter: &mut common::event::Emitter<'_, ServerEvent>,
         entity: specs::Entity,
-        _client: &Client,
+        client: &Client,
         player: Option<&Player>,
+        mut limiter: Option<&mut ChatLimiter>,
+        limits: &Option<ChatLimits>,
+        time: f64,
+        chat_metrics: &ChatMetrics,
         uids: &ReadStorage<'_, Uid>,
         chat_modes: &ReadStorage<'_, ChatMode>,
         msg: ClientGeneral,
     ) -> Result<(), crate::error::Error> {
+        let mut within_chat_limits =
+            |player: &Player, message: Option<&str>| match (limiter.as_deref_mut(), limits) {
+                (Some(limiter), Some(limits)) => Self::within_chat_limits(
+                    server_emitter,
+                    entity,
+                    client,
+                    player,
+                    limiter,
+                    limits,
+                    time,
+                    chat_metrics,
+                    message,
+                ),
+                _ => true,
+            };
         match msg {
             ClientGeneral::ChatMsg(message) => {
-                if player.is_some() {
+                if let Some(player) = player {
+                    if !within_chat_limits(player, Some(&message)) {
+                        return Ok(());
+                    }
                     match validate_chat_msg(&message) {
                         Ok(()) => {
                             if let Some(from) = uids.get(entity) {
@@ -46,8 +147,10 @@ impl Sys {
                 }
             },
             ClientGeneral::Command(name, args) => {
-                if player.is_some() {
-                    server_emitter.emit(ServerEvent::Command(entity, name, args));
+                if let Some(player) = player {
+                    if within_chat_limits(player, None) {
+                        server_emitter.emit(ServerEvent::Command(entity, name, args));
+                    }
                 }
             },
             ClientGeneral::Terminate => {
@@ -76,6 +179,10 @@ impl<'a> System<'a> for Sys {
         ReadStorage<'a, ChatMode>,
         ReadStorage<'a, Player>,
         ReadStorage<'a, Client>,
+        ReadStorage<'a, Admin>,
+        WriteStorage<'a, ChatLimiter>,
+        Read<'a, Settings>,
+        ReadExpect<'a, ChatMetrics>,
     );
 
     const NAME: &'static str = "msg::general";
@@ -84,17 +191,38 @@ impl<'a> System<'a> for Sys {
 
     fn run(
         _job: &mut Job<Self>,
-        (entities, server_event_bus, time, uids, chat_modes, players, clients): Self::SystemData,
+        (
+            entities,
+            server_event_bus,
+            time,
+            uids,
+            chat_modes,
+            players,
+            clients,
+            admins,
+            mut chat_limiters,
+            settings,
+            chat_metrics,
+        ): Self::SystemData,
     ) {
         let mut server_emitter = server_event_bus.emitter();
 
         for (entity, client, player) in (&entities, &clients, (&players).maybe()).join() {
+            // Admins are trusted not to spam
+            let mut limiter = (player.is_some() && !admins.contains(entity))
+                .then(|| chat_limiters.entry(entity).ok())
+                .flatten()
+                .map(|entry| entry.or_insert_with(ChatLimiter::default));
             let res = super::try_recv_all(client, 3, |client, msg| {
                 Self::handle_general_msg(
                     &mut server_emitter,
                     entity,
                     client,
                     player,
+                    limiter.as_deref_mut(),
+                    &settings.chat_limits,
+                    time.0,
+                    &chat_metrics,
                     &uids,
                     &chat_modes,
                     msg,
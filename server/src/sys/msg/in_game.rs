@@ -1,11 +1,18 @@
+use crate::{
+    client::Client,
+    land_claims::{Editor, LandClaims},
+    presence::Presence,
+    EditableSettings, Settings,
+};
 #[cfg(feature = "persistent_world")]
-use crate::TerrainPersistence;
-use crate::{client::Client, presence::Presence, Settings};
+use crate::{terrain_persistence::journal::EditAuthor, TerrainPersistence};
+use chrono::Utc;
 use common::{
     comp::{
         Admin, CanBuild, ControlEvent, Controller, ForceUpdate, Health, Ori, Player, Pos, SkillSet,
         Vel,
     },
+    depot::Id,
     event::{EventBus, ServerEvent},
     resources::PlayerPhysicsSettings,
     terrain::TerrainGrid,
@@ -18,11 +25,78 @@ use specs::{Entities, Join, Read, ReadExpect, ReadStorage, Write, WriteStorage};
 use tracing::{debug, trace, warn};
 use vek::*;
 
+#[cfg(feature = "plugins")]
+use {
+    common::{
+        comp::{Inventory, Stats},
+        uid::{Uid, UidAllocator},
+    },
+    common_state::plugin::{memory_manager::EcsWorld, storage::PluginStorage, PluginMgr},
+    plugin_api::{
+        event::{BlockBreakEvent, BlockChangeResult, BlockPlaceEvent, Player as PluginPlayer},
+        Event,
+    },
+    specs::{shred::ResourceId, SystemData, World},
+    tracing::error,
+};
+
 #[cfg(feature = "persistent_world")]
 pub type TerrainPersistenceData<'a> = Option<Write<'a, TerrainPersistence>>;
 #[cfg(not(feature = "persistent_world"))]
 pub type TerrainPersistenceData<'a> = ();
 
+/// What plugins need to be able to cancel block changes
+#[cfg(feature = "plugins")]
+#[derive(SystemData)]
+pub struct PluginData<'a> {
+    plugin_mgr: Read<'a, PluginMgr>,
+    entities: Entities<'a>,
+    uids: ReadStorage<'a, Uid>,
+    healths: ReadStorage<'a, Health>,
+    players: ReadStorage<'a, Player>,
+    inventories: ReadStorage<'a, Inventory>,
+    stats: ReadStorage<'a, Stats>,
+    uid_allocator: Read<'a, UidAllocator>,
+    plugin_actions: Read<'a, EventBus<plugin_api::Action>>,
+    plugin_storage: Read<'a, PluginStorage>,
+}
+#[cfg(not(feature = "plugins"))]
+pub type PluginData<'a> = ();
+
+#[cfg(feature = "plugins")]
+impl<'a> PluginData<'a> {
+    /// Returns `true` if a plugin cancelled the block change
+    fn is_block_change_cancelled<T: Event<Response = BlockChangeResult>>(
+        &self,
+        positions: &WriteStorage<'_, Pos>,
+        event: &T,
+    ) -> bool {
+        let ecs_world = EcsWorld {
+            entities: &self.entities,
+            health: (&self.healths).into(),
+            uid: (&self.uids).into(),
+            player: (&self.players).into(),
+            pos: positions.into(),
+            inventory: (&self.inventories).into(),
+            stats: (&self.stats).into(),
+            uid_allocator: &self.uid_allocator,
+            plugin_actions: &self.plugin_actions,
+            plugin_storage: &self.plugin_storage,
+        };
+        match self.plugin_mgr.execute_event(&ecs_world, event) {
+            Ok(responses) => responses.contains(&BlockChangeResult::Cancel),
+            Err(e) => {
+                error!(?e, "Failed to execute plugin block change event");
+                false
+            },
+        }
+    }
+
+    fn player(&self, entity: specs::Entity) -> Option<PluginPlayer> {
+        self.uids.get(entity).map(|uid| PluginPlayer { id: *uid })
+    }
+}
+
 impl Sys {
     #[allow(clippy::too_many_arguments)]
     fn handle_client_in_game_msg(
@@ -41,9 +115,12 @@ impl Sys {
         orientations: &mut WriteStorage<'_, Ori>,
         controllers: &mut WriteStorage<'_, Controller>,
         settings: &Read<'_, Settings>,
+        editable_settings: &ReadExpect<'_, EditableSettings>,
         build_areas: &Read<'_, BuildAreas>,
+        land_claims: &Read<'_, LandClaims>,
         player_physics_settings: &mut Write<'_, PlayerPhysicsSettings>,
         _terrain_persistence: &mut TerrainPersistenceData<'_>,
+        _plugin_data: &PluginData<'_>,
         maybe_player: &Option<&Player>,
         maybe_admin: &Option<&Admin>,
         msg: ClientGeneral,
@@ -196,24 +273,40 @@ impl Sys {
             },
             ClientGeneral::BreakBlock(pos) => {
                 if let Some(comp_can_build) = can_build.get(entity) {
-                    if comp_can_build.enabled {
-                        for area in comp_can_build.build_areas.iter() {
-                            if let Some(old_block) = build_areas
-                                .areas()
-                                .get(*area)
-                                // TODO: Make this an exclusive check on the upper bound of the AABB
-                                // Vek defaults to inclusive which is not optimal
-                                .filter(|aabb| aabb.contains_point(pos))
-                                .and_then(|_| terrain.get(pos).ok())
-                            {
-                                let new_block = old_block.into_vacant();
-                                let _was_set = block_changes.try_set(pos, new_block).is_some();
-                                #[cfg(feature = "persistent_world")]
-                                if _was_set {
-                                    if let Some(terrain_persistence) = _terrain_persistence.as_mut()
-                                    {
-                                        terrain_persistence.set_block(pos, new_block);
-                                    }
+                    if comp_can_build.enabled
+                        && may_build_at(
+                            pos,
+                            comp_can_build,
+                            maybe_player,
+                            editable_settings,
+                            build_areas,
+                            land_claims,
+                            maybe_admin,
+                        )
+                    {
+                        if let Ok(old_block) = terrain.get(pos) {
+                            #[cfg(feature = "plugins")]
+                            if let Some(player) = _plugin_data.player(entity) {
+                                if _plugin_data.is_block_change_cancelled(
+                                    positions,
+                                    &BlockBreakEvent { player, pos },
+                                ) {
+                                    return Ok(());
+                                }
+                            }
+                            let new_block = old_block.into_vacant();
+                            let _was_set = block_changes.try_set(pos, new_block).is_some();
+                            #[cfg(feature = "persistent_world")]
+                            if _was_set {
+                                if let Some(terrain_persistence) = _terrain_persistence.as_mut() {
+                                    terrain_persistence.set_block(
+                                        pos,
+                                        *old_block,
+                                        new_block,
+                                        maybe_player.map(|player| {
+                                            EditAuthor::new(player, Some(&**presence))
+                                        }),
+                                    );
                                 }
                             }
                         }
@@ -222,24 +315,40 @@ impl Sys {
             },
             ClientGeneral::PlaceBlock(pos, new_block) => {
                 if let Some(comp_can_build) = can_build.get(entity) {
-                    if comp_can_build.enabled {
-                        for area in comp_can_build.build_areas.iter() {
-                            if build_areas
-                                .areas()
-                                .get(*area)
-                                // TODO: Make this an exclusive check on the upper bound of the AABB
-                                // Vek defaults to inclusive which is not optimal
-                                .filter(|aabb| aabb.contains_point(pos))
-                                .is_some()
-                            {
-                                let _was_set = block_changes.try_set(pos, new_block).is_some();
-                                #[cfg(feature = "persistent_world")]
-                                if _was_set {
-                                    if let Some(terrain_persistence) = _terrain_persistence.as_mut()
-                                    {
-                                        terrain_persistence.set_block(pos, new_block);
-                                    }
-                                }
+                    if comp_can_build.enabled
+                        && may_build_at(
+                            pos,
+                            comp_can_build,
+                            maybe_player,
+                            editable_settings,
+                            build_areas,
+                            land_claims,
+                            maybe_admin,
+                        )
+                    {
+                        #[cfg(feature = "plugins")]
+                        if let Some(player) = _plugin_data.player(entity) {
+                            if _plugin_data.is_block_change_cancelled(positions, &BlockPlaceEvent {
+                                player,
+                                pos,
+                                block_kind: new_block.kind().to_string(),
+                            }) {
+                                return Ok(());
+                            }
+                        }
+                        #[cfg(feature = "persistent_world")]
+                        let old_block = terrain.get(pos).ok().copied();
+                        let _was_set = block_changes.try_set(pos, new_block).is_some();
+                        #[cfg(feature = "persistent_world")]
+                        if let (true, Some(old_block)) = (_was_set, old_block) {
+                            if let Some(terrain_persistence) = _terrain_persistence.as_mut() {
+                                terrain_persistence.set_block(
+                                    pos,
+                                    old_block,
+                                    new_block,
+                                    maybe_player
+                                        .map(|player| EditAuthor::new(player, Some(&**presence))),
+                                );
                             }
                         }
                     }
@@ -296,6 +405,58 @@ impl Sys {
     }
 }
 
+/// Build areas the player may build in, granted by `/permit_build` or by their
+/// permission groups
+fn permitted_build_areas(
+    can_build: &CanBuild,
+    player: &Option<&Player>,
+    editable_settings: &EditableSettings,
+    build_areas: &BuildAreas,
+) -> Vec<Id<Aabb<i32>>> {
+    let mut areas = can_build.build_areas.iter().copied().collect::<Vec<_>>();
+    if let Some(player) = player {
+        let granted = editable_settings
+            .permissions
+            .build_areas(player.uuid(), Utc::now());
+        for name in granted {
+            match build_areas.area_names().get(name) {
+                Some(area) if !areas.contains(area) => areas.push(*area),
+                Some(_) => {},
+                None => debug!(?name, "Permission group grants building in an unknown area"),
+            }
+        }
+    }
+    areas
+}
+
+/// Whether the player may build at `pos`. Claimed land only lets its owner,
+/// the players they trust and admins build there, whatever their build areas;
+/// elsewhere the player needs a build area containing `pos`.
+pub(crate) fn may_build_at(
+    pos: Vec3<i32>,
+    can_build: &CanBuild,
+    player: &Option<&Player>,
+    editable_settings: &EditableSettings,
+    build_areas: &BuildAreas,
+    land_claims: &LandClaims,
+    admin: &Option<&Admin>,
+) -> bool {
+    let chunk = TerrainGrid::chunk_key(pos);
+    match land_claims.owner(chunk) {
+        Some(_) => land_claims.may_change(Editor::new(*player, *admin), chunk),
+        None => permitted_build_areas(can_build, player, editable_settings, build_areas)
+            .iter()
+            .any(|area| {
+                build_areas
+                    .areas()
+                    .get(*area)
+                    // TODO: Make this an exclusive check on the upper bound of the AABB
+                    // Vek defaults to inclusive which is not optimal
+                    .map_or(false, |aabb| aabb.contains_point(pos))
+            }),
+    }
+}
+
 /// This system will handle new messages from clients
 #[derive(Default)]
 pub struct Sys;
@@ -317,9 +478,12 @@ impl<'a> System<'a> for Sys {
         WriteStorage<'a, Client>,
         WriteStorage<'a, Controller>,
         Read<'a, Settings>,
+        ReadExpect<'a, EditableSettings>,
         Read<'a, BuildAreas>,
+        Read<'a, LandClaims>,
         Write<'a, PlayerPhysicsSettings>,
         TerrainPersistenceData<'a>,
+        PluginData<'a>,
         ReadStorage<'a, Player>,
         ReadStorage<'a, Admin>,
     );
@@ -346,9 +510,12 @@ impl<'a> System<'a> for Sys {
             mut clients,
             mut controllers,
             settings,
+            editable_settings,
             build_areas,
+            land_claims,
             mut player_physics_settings,
             mut terrain_persistence,
+            plugin_data,
             players,
             admins,
         ): Self::SystemData,
@@ -381,9 +548,12 @@ impl<'a> System<'a> for Sys {
                     &mut orientations,
                     &mut controllers,
                     &settings,
+                    &editable_settings,
                     &build_areas,
+                    &land_claims,
                     &mut player_physics_settings,
                     &mut terrain_persistence,
+                    &plugin_data,
                     &player,
                     &maybe_admin,
                     msg,
@@ -2,6 +2,7 @@ pub mod character_screen;
 pub mod general;
 pub mod in_game;
 pub mod ping;
+#[cfg(feature = "plugins")] pub mod plugin;
 pub mod register;
 pub mod terrain;
 
@@ -17,6 +18,8 @@ pub fn add_server_systems(dispatch_builder: &mut DispatcherBuilder) {
     dispatch::<general::Sys>(dispatch_builder, &[]);
     dispatch::<in_game::Sys>(dispatch_builder, &[]);
     dispatch::<ping::Sys>(dispatch_builder, &[&general::Sys::sys_name()]);
+    #[cfg(feature = "plugins")]
+    dispatch::<plugin::Sys>(dispatch_builder, &[]);
     dispatch::<register::Sys>(dispatch_builder, &[]);
     dispatch::<terrain::Sys>(dispatch_builder, &[]);
     dispatch::<pets::Sys>(dispatch_builder, &[]);
@@ -0,0 +1,66 @@
+use crate::client::Client;
+use common::event::{EventBus, ServerEvent};
+use common_ecs::{Job, Origin, Phase, System};
+use common_net::msg::PluginMsg;
+use common_state::plugin::PluginMgr;
+use specs::{Entities, Join, Read, ReadStorage};
+use tracing::{debug, trace};
+
+impl Sys {
+    fn handle_plugin_msg(
+        client: &Client,
+        plugin_mgr: &PluginMgr,
+        msg: PluginMsg,
+    ) -> Result<(), crate::error::Error> {
+        match msg {
+            PluginMsg::RequestPlugins(hashes) => {
+                trace!(?hashes, "Client requested plugins");
+                let archives = hashes
+                    .iter()
+                    .filter_map(|hash| plugin_mgr.find_archive(hash))
+                    .map(|archive| archive.to_vec())
+                    .collect();
+                client.send(PluginMsg::Plugins(archives))?;
+            },
+            PluginMsg::Plugins(_) => {},
+        }
+        Ok(())
+    }
+}
+
+/// This system will send the requested plugin archives to clients
+#[derive(Default)]
+pub struct Sys;
+impl<'a> System<'a> for Sys {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, EventBus<ServerEvent>>,
+        ReadStorage<'a, Client>,
+        Read<'a, PluginMgr>,
+    );
+
+    const NAME: &'static str = "msg::plugin";
+    const ORIGIN: Origin = Origin::Server;
+    const PHASE: Phase = Phase::Create;
+
+    fn run(
+        _job: &mut Job<Self>,
+        (entities, server_event_bus, clients, plugin_mgr): Self::SystemData,
+    ) {
+        let mut server_emitter = server_event_bus.emitter();
+
+        for (entity, client) in (&entities, &clients).join() {
+            let res = super::try_recv_all(client, 6, |client, msg| {
+                Self::handle_plugin_msg(client, &plugin_mgr, msg)
+            });
+
+            if let Err(e) = res {
+                debug!(?entity, ?e, "network error with client, disconnecting");
+                server_emitter.emit(ServerEvent::ClientDisconnect(
+                    entity,
+                    common::comp::DisconnectReason::NetworkError,
+                ));
+            }
+        }
+    }
+}
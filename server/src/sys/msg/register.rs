@@ -5,7 +5,7 @@ use crate::{
     EditableSettings, Settings,
 };
 use common::{
-    comp::{Admin, Player, Stats},
+    comp::{Admin, Inventory, Player, Pos, Stats},
     event::{EventBus, ServerEvent},
     uid::{Uid, UidAllocator},
 };
@@ -23,13 +23,26 @@ use specs::{
 use tracing::trace;
 
 #[cfg(feature = "plugins")]
-use {common_state::plugin::memory_manager::EcsWorld, common_state::plugin::PluginMgr};
+use {
+    common_state::plugin::memory_manager::EcsWorld, common_state::plugin::storage::PluginStorage,
+    common_state::plugin::PluginMgr,
+};
 
 #[cfg(feature = "plugins")]
 type ReadPlugin<'a> = Read<'a, PluginMgr>;
 #[cfg(not(feature = "plugins"))]
 type ReadPlugin<'a> = Option<Read<'a, ()>>;
 
+#[cfg(feature = "plugins")]
+type ReadPluginActions<'a> = Read<'a, EventBus<plugin_api::Action>>;
+#[cfg(not(feature = "plugins"))]
+type ReadPluginActions<'a> = Option<Read<'a, ()>>;
+
+#[cfg(feature = "plugins")]
+type ReadPluginStorage<'a> = Read<'a, PluginStorage>;
+#[cfg(not(feature = "plugins"))]
+type ReadPluginStorage<'a> = Option<Read<'a, ()>>;
+
 #[derive(SystemData)]
 pub struct ReadData<'a> {
     entities: Entities<'a>,
@@ -43,6 +56,10 @@ pub struct ReadData<'a> {
     _healths: ReadStorage<'a, Health>, // used by plugin feature
     _plugin_mgr: ReadPlugin<'a>,       // used by plugin feature
     _uid_allocator: Read<'a, UidAllocator>, // used by plugin feature
+    _positions: ReadStorage<'a, Pos>,  // used by plugin feature
+    _inventories: ReadStorage<'a, Inventory>, // used by plugin feature
+    _plugin_actions: ReadPluginActions<'a>, // used by plugin feature
+    _plugin_storage: ReadPluginStorage<'a>, // used by plugin feature
 }
 
 /// This system will handle new messages from clients
@@ -116,7 +133,12 @@ impl<'a> System<'a> for Sys {
                     health: (&read_data._healths).into(),
                     uid: (&read_data.uids).into(),
                     player: (&players).into(),
+                    pos: (&read_data._positions).into(),
+                    inventory: (&read_data._inventories).into(),
+                    stats: (&read_data.stats).into(),
                     uid_allocator: &read_data._uid_allocator,
+                    plugin_actions: &read_data._plugin_actions,
+                    plugin_storage: &read_data._plugin_storage,
                 };
 
                 let (username, uuid) = match login_provider.login(
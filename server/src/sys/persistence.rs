@@ -1,8 +1,12 @@
-use crate::{persistence::character_updater, presence::Presence, sys::SysScheduler};
+use crate::{
+    persistence::{character_updater, storage},
+    presence::Presence,
+    sys::SysScheduler,
+};
 use common::{
     comp::{
         pet::{is_tameable, Pet},
-        Alignment, Body, Inventory, SkillSet, Stats, Waypoint,
+        Alignment, Body, Inventory, QuestLog, SkillSet, Stats, StorageContainers, Waypoint,
     },
     uid::Uid,
 };
@@ -25,7 +29,9 @@ impl<'a> System<'a> for Sys {
         ReadStorage<'a, Waypoint>,
         ReadStorage<'a, Pet>,
         ReadStorage<'a, Stats>,
+        ReadStorage<'a, QuestLog>,
         WriteExpect<'a, character_updater::CharacterUpdater>,
+        Write<'a, StorageContainers>,
         Write<'a, SysScheduler<Self>>,
     );
 
@@ -45,7 +51,9 @@ impl<'a> System<'a> for Sys {
             player_waypoints,
             pets,
             stats,
+            quest_logs,
             mut updater,
+            mut storage_containers,
             mut scheduler,
         ): Self::SystemData,
     ) {
@@ -57,32 +65,37 @@ impl<'a> System<'a> for Sys {
                     &player_inventories,
                     &uids,
                     player_waypoints.maybe(),
+                    &quest_logs,
                 )
                     .join()
                     .filter_map(
-                        |(presence, skill_set, inventory, player_uid, waypoint)| match presence.kind
-                        {
-                            PresenceKind::Character(id) => {
-                                let pets = (&alignments, &bodies, &stats, &pets)
-                                    .join()
-                                    .filter_map(|(alignment, body, stats, pet)| match alignment {
-                                        // Don't try to persist non-tameable pets (likely spawned
-                                        // using /spawn) since there isn't any code to handle
-                                        // persisting them
-                                        Alignment::Owned(ref pet_owner)
-                                            if pet_owner == player_uid && is_tameable(body) =>
+                        |(presence, skill_set, inventory, player_uid, waypoint, quest_log)| {
+                            match presence.kind {
+                                PresenceKind::Character(id) => {
+                                    let pets = (&alignments, &bodies, &stats, &pets)
+                                        .join()
+                                        .filter_map(|(alignment, body, stats, pet)| match alignment
                                         {
-                                            Some(((*pet).clone(), *body, stats.clone()))
-                                        },
-                                        _ => None,
-                                    })
-                                    .collect();
+                                            // Don't try to persist non-tameable pets (likely
+                                            // spawned
+                                            // using /spawn) since there isn't any code to handle
+                                            // persisting them
+                                            Alignment::Owned(ref pet_owner)
+                                                if pet_owner == player_uid && is_tameable(body) =>
+                                            {
+                                                Some(((*pet).clone(), *body, stats.clone()))
+                                            },
+                                            _ => None,
+                                        })
+                                        .collect();
 
-                                Some((id, skill_set, inventory, pets, waypoint))
-                            },
-                            PresenceKind::Spectator => None,
+                                    Some((id, skill_set, inventory, pets, waypoint, quest_log))
+                                },
+                                PresenceKind::Spectator => None,
+                            }
                         },
                     ),
+                storage::take_changes(&mut storage_containers),
             );
         }
     }
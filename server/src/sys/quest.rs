@@ -0,0 +1,53 @@
+use crate::{client::Client, sys::SysScheduler};
+use common::comp::{ChatType, Inventory, Pos, QuestLog};
+use common_ecs::{Job, Origin, Phase, System};
+use common_net::msg::ServerGeneral;
+use specs::{Join, ReadStorage, Write, WriteStorage};
+
+/// This system updates the progress of players toward the `Reach` and
+/// `Collect` objectives of their quests
+#[derive(Default)]
+pub struct Sys;
+impl<'a> System<'a> for Sys {
+    type SystemData = (
+        ReadStorage<'a, Pos>,
+        ReadStorage<'a, Inventory>,
+        ReadStorage<'a, Client>,
+        WriteStorage<'a, QuestLog>,
+        Write<'a, SysScheduler<Self>>,
+    );
+
+    const NAME: &'static str = "quest";
+    const ORIGIN: Origin = Origin::Server;
+    const PHASE: Phase = Phase::Create;
+
+    fn run(
+        _job: &mut Job<Self>,
+        (positions, inventories, clients, mut quest_logs, mut scheduler): Self::SystemData,
+    ) {
+        if !scheduler.should_run() {
+            return;
+        }
+        for (pos, inventory, client, quest_log) in
+            (&positions, &inventories, &clients, &mut quest_logs).join()
+        {
+            if quest_log.active().next().is_none() {
+                continue;
+            }
+            let mut messages = quest_log
+                .record_pos(pos.0)
+                .into_iter()
+                .map(|quest| quest.progress_message())
+                .collect::<Vec<_>>();
+            messages.extend(
+                quest_log
+                    .record_inventory(inventory)
+                    .into_iter()
+                    .map(|quest| quest.progress_message()),
+            );
+            for msg in messages {
+                client.send_fallible(ServerGeneral::server_msg(ChatType::Meta, msg));
+            }
+        }
+    }
+}
@@ -16,13 +16,16 @@ use crate::{
 };
 use common::{
     calendar::Calendar,
-    comp::{self, agent, bird_medium, BehaviorCapability, ForceUpdate, Pos, Waypoint},
+    comp::{
+        self, agent, bird_medium, BehaviorCapability, ForceUpdate, Pos, StorageContainers, Waypoint,
+    },
     event::{EventBus, ServerEvent},
     generation::EntityInfo,
     lottery::LootSpec,
     resources::{Time, TimeOfDay},
     slowjob::SlowJobPool,
-    terrain::TerrainGrid,
+    terrain::{SpriteKind, TerrainGrid},
+    vol::{ReadVol, WriteVol},
     LoadoutBuilder, SkillSetBuilder,
 };
 use common_ecs::{Job, Origin, Phase, System};
@@ -130,6 +133,7 @@ impl<'a> System<'a> for Sys {
         WriteStorage<'a, ForceUpdate>,
         WriteStorage<'a, Waypoint>,
         ReadExpect<'a, Time>,
+        Read<'a, StorageContainers>,
     );
 
     const NAME: &'static str = "terrain";
@@ -163,6 +167,7 @@ impl<'a> System<'a> for Sys {
             mut force_update,
             mut waypoints,
             time,
+            storage_containers,
         ): Self::SystemData,
     ) {
         let mut server_emitter = server_event_bus.emitter();
@@ -210,6 +215,16 @@ impl<'a> System<'a> for Sys {
                 terrain_persistence.apply_changes(key, &mut chunk);
             }
 
+            // Restore the sprites of the storage containers in this chunk, so they stay
+            // reachable when the terrain itself isn't persisted. Containers whose
+            // position is filled now are dropped by `drop_broken_storage`.
+            for pos in storage_containers.in_chunk(key) {
+                let offs = TerrainGrid::chunk_offs(pos);
+                if let Some(block) = chunk.get(offs).ok().copied().filter(|b| !b.is_filled()) {
+                    let _ = chunk.set(offs, block.with_sprite(SpriteKind::StorageChest));
+                }
+            }
+
             // Arcify the chunk
             let chunk = Arc::new(chunk);
 
@@ -416,6 +431,8 @@ impl NpcData {
             loot,
             // tools and skills
             skillset_asset,
+            tactic_asset,
+            quests,
             main_tool,
             second_tool,
             loadout_asset,
@@ -499,6 +516,8 @@ impl NpcData {
                 )
                 .with_patrol_origin(pos)
                 .with_no_flee_if(matches!(agent_mark, Some(agent::Mark::Guard)))
+                .with_tactic(tactic_asset)
+                .with_quests(quests)
         });
 
         let agent = if matches!(alignment, comp::Alignment::Enemy)
@@ -1,22 +1,109 @@
-use atomicwrites::{AtomicFile, OverwriteBehavior};
 use common::{
     terrain::{Block, TerrainChunk},
     vol::{RectRasterableVol, WriteVol},
 };
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
+use region::{region_key, Region};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     any::{type_name, Any},
-    fs::File,
+    fs::{File, OpenOptions},
     io::{self, Read as _, Write as _},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 use tracing::{debug, error, info, warn};
 use vek::*;
 
+pub mod journal;
+pub mod prefab;
+pub mod region;
+pub mod snapshot;
+
+/// How often a snapshot of the modified terrain is taken
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How many chunks of a snapshot are written per tick
+const SNAPSHOT_CHUNKS_PER_TICK: usize = 4;
+
 pub struct TerrainPersistence {
     path: PathBuf,
     chunks: HashMap<Vec2<i32>, Chunk>,
+    regions: HashMap<Vec2<i32>, Region>,
+    /// Loaded chunks modified since they were written to their region
+    dirty: HashSet<Vec2<i32>>,
+    /// Chunks modified since the last snapshot
+    unsnapshotted: HashSet<Vec2<i32>>,
+    last_snapshot: Option<snapshot::Manifest>,
+    last_snapshot_time: Instant,
+    pending_snapshot: Option<snapshot::PendingSnapshot>,
+    /// Restored blocks which were regenerated in the background
+    regenerated: (
+        crossbeam_channel::Sender<Vec<(Vec3<i32>, Block)>>,
+        crossbeam_channel::Receiver<Vec<(Vec3<i32>, Block)>>,
+    ),
+    journal: journal::EditJournal,
+    _lock: TerrainLock,
+}
+
+/// Lock file of a terrain persistence directory, so the server CLI doesn't
+/// change its files while a server is running on it.
+///
+/// The file is left behind when the process crashes, the next server started
+/// takes it over.
+pub struct TerrainLock {
+    path: PathBuf,
+}
+
+impl TerrainLock {
+    const FILENAME: &'static str = "terrain.lock";
+
+    /// Fails if the directory is locked already
+    pub fn acquire(terrain_path: &Path) -> io::Result<Self> {
+        let path = terrain_path.join(Self::FILENAME);
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::AlreadyExists => io::Error::new(
+                    err.kind(),
+                    format!(
+                        "{} exists, is the server running? Delete it if it isn't",
+                        path.display()
+                    ),
+                ),
+                _ => err,
+            })?;
+        Self::write_pid(file)?;
+        Ok(Self { path })
+    }
+
+    /// Lock the directory even if it's locked already, e.g. after a crash
+    fn take_over(terrain_path: &Path) -> io::Result<Self> {
+        let path = terrain_path.join(Self::FILENAME);
+        Self::write_pid(File::create(&path)?)?;
+        Ok(Self { path })
+    }
+
+    fn write_pid(mut file: File) -> io::Result<()> { writeln!(file, "{}", std::process::id()) }
+}
+
+impl Drop for TerrainLock {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            warn!("Failed to remove the terrain lock file: {:?}", err);
+        }
+    }
+}
+
+/// The terrain persistence directory within the given data directory.
+///
+/// If the `VELOREN_TERRAIN` environment variable is set, this will be used
+/// as the persistence directory instead.
+pub fn terrain_path(data_dir: &Path) -> PathBuf {
+    std::env::var("VELOREN_TERRAIN")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| data_dir.join("terrain"))
 }
 
 impl TerrainPersistence {
@@ -24,21 +111,48 @@ impl TerrainPersistence {
     ///
     /// If the `VELOREN_TERRAIN` environment variable is set, this will be used
     /// as the persistence directory instead.
-    pub fn new(mut data_dir: PathBuf) -> Self {
-        let path = std::env::var("VELOREN_TERRAIN")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| {
-                data_dir.push("terrain");
-                data_dir
-            });
+    pub fn new(data_dir: PathBuf) -> Self {
+        let path = terrain_path(&data_dir);
 
         std::fs::create_dir_all(&path).expect("Failed to create terrain persistence directory");
 
         info!("Using {:?} as the terrain persistence path", path);
 
+        let lock = TerrainLock::acquire(&path)
+            .or_else(|err| {
+                warn!(
+                    "Taking over the terrain persistence lock, the previous server might have \
+                     crashed: {}",
+                    err
+                );
+                TerrainLock::take_over(&path)
+            })
+            .expect("Failed to lock the terrain persistence directory");
+        Self::open(path, lock)
+    }
+
+    /// Like [`Self::new`], but fails if a server is using the directory, for
+    /// tools which change persisted terrain while the server isn't running.
+    pub fn try_new(data_dir: PathBuf) -> io::Result<Self> {
+        let path = terrain_path(&data_dir);
+        std::fs::create_dir_all(&path)?;
+        let lock = TerrainLock::acquire(&path)?;
+        Ok(Self::open(path, lock))
+    }
+
+    fn open(path: PathBuf, lock: TerrainLock) -> Self {
         Self {
+            journal: journal::EditJournal::new(&path),
             path,
             chunks: HashMap::default(),
+            regions: HashMap::default(),
+            dirty: HashSet::default(),
+            unsnapshotted: HashSet::default(),
+            last_snapshot: None,
+            last_snapshot_time: Instant::now(),
+            pending_snapshot: None,
+            regenerated: crossbeam_channel::unbounded(),
+            _lock: lock,
         }
     }
 
@@ -70,108 +184,280 @@ impl TerrainPersistence {
     /// Maintain terrain persistence (writing changes changes back to
     /// filesystem, etc.)
     pub fn maintain(&mut self) {
-        // Currently, this does nothing because filesystem writeback occurs on
-        // chunk unload However, this is not a particularly reliable
-        // mechanism (it doesn't survive power loss, say). Later, a more
-        // reliable strategy should be implemented here.
+        self.journal.flush();
+        // Regions are written once per tick, however many of their chunks
+        // were unloaded
+        self.save_regions();
+
+        // Filesystem writeback occurs on chunk unload, and periodically for all
+        // modified chunks when a snapshot is taken. Snapshots are taken a few
+        // chunks per tick, so they don't stall the server.
+        if self.pending_snapshot.is_none() && self.last_snapshot_time.elapsed() >= SNAPSHOT_INTERVAL
+        {
+            self.last_snapshot_time = Instant::now();
+            match self.start_snapshot() {
+                Ok(pending) => self.pending_snapshot = Some(pending),
+                Err(err) => error!("Failed to take a terrain snapshot: {}", err),
+            }
+        }
+        if let Some(mut pending) = self.pending_snapshot.take() {
+            match self.continue_snapshot(&mut pending, SNAPSHOT_CHUNKS_PER_TICK) {
+                Ok(false) => self.pending_snapshot = Some(pending),
+                Ok(true) => {
+                    if let Err(err) = self.finish_snapshot(pending) {
+                        error!("Failed to take a terrain snapshot: {}", err);
+                    }
+                },
+                Err(err) => error!("Failed to take a terrain snapshot: {}", err),
+            }
+        }
     }
 
-    fn path_for(&self, key: Vec2<i32>) -> PathBuf {
+    /// Path of a chunk file of the formats before regions were introduced
+    fn legacy_path_for(&self, key: Vec2<i32>) -> PathBuf {
         let mut path = self.path.clone();
         path.push(format!("chunk_{}_{}.dat", key.x, key.y));
         path
     }
 
+    fn region_path(path: &Path, region_key: Vec2<i32>) -> PathBuf {
+        path.join(format!("region_{}_{}.dat", region_key.x, region_key.y))
+    }
+
+    fn region<'a>(
+        regions: &'a mut HashMap<Vec2<i32>, Region>,
+        path: &Path,
+        region_key: Vec2<i32>,
+    ) -> &'a mut Region {
+        regions
+            .entry(region_key)
+            .or_insert_with(|| Region::load(&Self::region_path(path, region_key)))
+    }
+
     fn load_chunk(&mut self, key: Vec2<i32>) -> &mut Chunk {
-        let path = self.path_for(key);
-        self.chunks.entry(key).or_insert_with(|| {
-            File::open(&path)
-                .ok()
-                .map(|f| {
-                    let bytes = match std::io::BufReader::new(f)
-                        .bytes()
-                        .collect::<Result<Vec<_>, _>>()
-                    {
-                        Ok(bytes) => bytes,
-                        Err(err) => {
-                            error!(
-                                "Failed to read data for chunk {:?} from file: {:?}",
-                                key, err
-                            );
-                            return Chunk::default();
-                        },
-                    };
-                    match Chunk::deserialize_from(std::io::Cursor::new(bytes)) {
-                        Some(chunk) => chunk,
-                        None => {
-                            // Find an untaken name for a backup
-                            let mut backup_path = path.clone();
-                            backup_path.set_extension("dat_backup_0");
-                            let mut i = 1;
-                            while backup_path.exists() {
-                                backup_path.set_extension(format!("dat_backup_{}", i));
-                                i += 1;
-                            }
-
-                            error!(
-                                "Failed to load chunk {:?}, moving possibly corrupt (or too new) \
-                                 data to {:?} for you to repair.",
-                                key, backup_path
-                            );
-                            if let Err(err) = std::fs::rename(path, backup_path) {
-                                error!("Failed to rename invalid chunk file: {:?}", err);
-                            }
-                            Chunk::default()
-                        },
-                    }
-                })
-                .unwrap_or_default()
-        })
+        if !self.chunks.contains_key(&key) {
+            let chunk = self.read_chunk(key).unwrap_or_default();
+            Self::region(&mut self.regions, &self.path, region_key(key)).loaded_chunks += 1;
+            return self.chunks.entry(key).or_insert(chunk);
+        }
+        self.chunks.get_mut(&key).unwrap()
     }
 
-    pub fn unload_chunk(&mut self, key: Vec2<i32>) {
-        if let Some(chunk) = self.chunks.remove(&key) {
-            // No need to write if no blocks have ever been written
-            if chunk.blocks.is_empty() {
-                return;
-            }
+    /// Read the stored changes of a chunk from its region, migrating chunk
+    /// files of older formats.
+    fn read_chunk(&mut self, key: Vec2<i32>) -> Option<Chunk> {
+        let region_path = Self::region_path(&self.path, region_key(key));
+        match Self::region(&mut self.regions, &self.path, region_key(key)).chunk(key) {
+            Ok(Some(chunk)) => Some(chunk),
+            Ok(None) => self.migrate_legacy_chunk(key),
+            Err(()) => {
+                // Find an untaken name for a backup
+                let mut backup_path = region_path.clone();
+                backup_path.set_extension("dat_backup_0");
+                let mut i = 1;
+                while backup_path.exists() {
+                    backup_path.set_extension(format!("dat_backup_{}", i));
+                    i += 1;
+                }
 
-            let bytes = match bincode::serialize::<version::Current>(&chunk.prepare_raw()) {
-                Err(err) => {
-                    error!("Failed to serialize chunk data: {:?}", err);
-                    return;
-                },
-                Ok(bytes) => bytes,
-            };
+                error!(
+                    "Failed to load chunk {:?}, copying its possibly corrupt (or too new) region \
+                     to {:?} for you to repair.",
+                    key, backup_path
+                );
+                if let Err(err) = std::fs::copy(region_path, backup_path) {
+                    error!("Failed to copy invalid region file: {:?}", err);
+                }
+                None
+            },
+        }
+    }
+
+    /// Move the chunk file of a chunk stored in an older format into its
+    /// region.
+    fn migrate_legacy_chunk(&mut self, key: Vec2<i32>) -> Option<Chunk> {
+        let path = self.legacy_path_for(key);
+        let file = File::open(&path).ok()?;
+        let bytes = match std::io::BufReader::new(file)
+            .bytes()
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                error!(
+                    "Failed to read data for chunk {:?} from file: {:?}",
+                    key, err
+                );
+                return None;
+            },
+        };
+        match Chunk::deserialize_from(std::io::Cursor::new(bytes)) {
+            Some(chunk) => {
+                let region = Self::region(&mut self.regions, &self.path, region_key(key));
+                region.set_chunk(key, &chunk);
+                region.migrated.push(path);
+                debug!("Migrated chunk {:?} into its region", key);
+                Some(chunk)
+            },
+            None => {
+                // Find an untaken name for a backup
+                let mut backup_path = path.clone();
+                backup_path.set_extension("dat_backup_0");
+                let mut i = 1;
+                while backup_path.exists() {
+                    backup_path.set_extension(format!("dat_backup_{}", i));
+                    i += 1;
+                }
+
+                error!(
+                    "Failed to load chunk {:?}, moving possibly corrupt (or too new) data to {:?} \
+                     for you to repair.",
+                    key, backup_path
+                );
+                if let Err(err) = std::fs::rename(path, backup_path) {
+                    error!("Failed to rename invalid chunk file: {:?}", err);
+                }
+                None
+            },
+        }
+    }
+
+    /// The stored entry of a chunk in the [`version::Current`] format, after
+    /// writing its changes.
+    fn stored_entry(&mut self, key: Vec2<i32>) -> io::Result<Option<Vec<u8>>> {
+        self.write_chunk(key);
+        let region = Self::region(&mut self.regions, &self.path, region_key(key));
+        match region.entry(key) {
+            Some(entry) => entry.map(Some),
+            None => Ok(self
+                .migrate_legacy_chunk(key)
+                .and_then(|chunk| bincode::serialize(&chunk.prepare_raw()).ok())),
+        }
+    }
+
+    /// Keys of all regions, and of all chunks stored in the formats before
+    /// regions were introduced
+    fn persisted_files(&self) -> (Vec<Vec2<i32>>, Vec<Vec2<i32>>) {
+        let names = std::fs::read_dir(&self.path)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let coords = |name: &str, prefix: &str| {
+            let mut coords = name
+                .strip_prefix(prefix)?
+                .strip_suffix(".dat")?
+                .split('_')
+                .map(|e| e.parse().ok());
+            Some(Vec2::new(coords.next()??, coords.next()??))
+        };
+
+        let regions = names
+            .iter()
+            .filter_map(|name| coords(name, "region_"))
+            .collect();
+        let legacy_chunks = names
+            .iter()
+            .filter_map(|name| coords(name, "chunk_"))
+            .collect();
+        (regions, legacy_chunks)
+    }
+
+    /// Store the changes of a loaded chunk in its region, which is written by
+    /// [`TerrainPersistence::maintain`] or once it's dropped
+    fn write_chunk(&mut self, key: Vec2<i32>) {
+        if let (true, Some(chunk)) = (self.dirty.remove(&key), self.chunks.get(&key)) {
+            Self::region(&mut self.regions, &self.path, region_key(key)).set_chunk(key, chunk);
+        }
+    }
+
+    /// Write the regions modified since they were last written
+    fn save_regions(&mut self) {
+        for (region_key, region) in self.regions.iter_mut() {
+            region.save(&Self::region_path(&self.path, *region_key));
+        }
+    }
+
+    /// Write and drop the regions without loaded chunks
+    fn evict_regions(&mut self) {
+        let path = &self.path;
+        self.regions.retain(|region_key, region| {
+            let keep = region.loaded_chunks > 0;
+            if !keep {
+                region.save(&Self::region_path(path, *region_key));
+            }
+            keep
+        });
+    }
 
-            let atomic_file =
-                AtomicFile::new(self.path_for(key), OverwriteBehavior::AllowOverwrite);
-            if let Err(err) = atomic_file.write(|file| file.write_all(&bytes)) {
-                error!("Failed to write chunk data to file: {:?}", err);
+    pub fn unload_chunk(&mut self, key: Vec2<i32>) {
+        self.write_chunk(key);
+        if self.chunks.remove(&key).is_some() {
+            let region_key = region_key(key);
+            if let Some(region) = self.regions.get_mut(&region_key) {
+                region.loaded_chunks = region.loaded_chunks.saturating_sub(1);
+                if region.loaded_chunks == 0 {
+                    region.save(&Self::region_path(&self.path, region_key));
+                    self.regions.remove(&region_key);
+                }
             }
         }
     }
 
     pub fn unload_all(&mut self) {
-        for key in self.chunks.keys().copied().collect::<Vec<_>>() {
-            self.unload_chunk(key);
+        // Write every region only once
+        for key in self.dirty.drain().collect::<Vec<_>>() {
+            if let Some(chunk) = self.chunks.get(&key) {
+                Self::region(&mut self.regions, &self.path, region_key(key)).set_chunk(key, chunk);
+            }
         }
+        self.save_regions();
+        self.chunks.clear();
+        self.regions.clear();
     }
 
-    pub fn set_block(&mut self, pos: Vec3<i32>, block: Block) {
+    /// Persist a block edit and record it in the edit journal. `old` is the
+    /// block which was replaced.
+    pub fn set_block(
+        &mut self,
+        pos: Vec3<i32>,
+        old: Block,
+        block: Block,
+        author: Option<journal::EditAuthor>,
+    ) {
+        self.persist_block(pos, block);
+        self.journal.record(author, pos, old, block);
+    }
+
+    fn persist_block(&mut self, pos: Vec3<i32>, block: Block) {
         let key = pos
             .xy()
             .map2(TerrainChunk::RECT_SIZE, |e, sz| e.div_euclid(sz as i32));
         self.load_chunk(key)
             .blocks
             .insert(pos - key * TerrainChunk::RECT_SIZE.map(|e| e as i32), block);
+        self.dirty.insert(key);
+        self.unsnapshotted.insert(key);
     }
+
+    pub fn journal(&self) -> &journal::EditJournal { &self.journal }
 }
 
 impl Drop for TerrainPersistence {
     fn drop(&mut self) { self.unload_all(); }
 }
 
+/// An empty directory for the tests of terrain persistence
+#[cfg(test)]
+fn test_dir(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("veloren-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct Chunk {
     blocks: HashMap<Vec3<i32>, Block>,
@@ -182,7 +468,7 @@ impl Chunk {
         version::try_load(reader)
     }
 
-    fn prepare_raw(self) -> version::Current { self.into() }
+    fn prepare_raw(&self) -> version::Current { self.into() }
 
     fn blocks(&self) -> impl Iterator<Item = (Vec3<i32>, Block)> + '_ {
         self.blocks.iter().map(|(k, b)| (*k, *b))
@@ -218,6 +504,10 @@ impl Chunk {
 ///
 /// 5. Remove the `Serialize` implementation from the previous raw format type:
 /// we don't need it any longer!
+///
+/// Since [`version::V4`], chunks are stored in region files, whose entries use
+/// the [`version::Current`] chunk format. Chunk files of the older formats are
+/// moved into their region when they are loaded.
 mod version {
     use super::*;
 
@@ -234,19 +524,39 @@ mod version {
 
     // Convert back to current
 
-    impl From<Chunk> for Current {
-        fn from(chunk: Chunk) -> Self {
+    impl From<&Chunk> for Current {
+        fn from(chunk: &Chunk) -> Self {
             Self {
                 version: version_magic(3),
                 blocks: chunk
                     .blocks
-                    .into_iter()
+                    .iter()
                     .map(|(pos, b)| (pos.x as u8, pos.y as u8, pos.z as i16, b.to_u32()))
                     .collect(),
             }
         }
     }
 
+    /// Version 4 is the header of a region file, see [`super::region`]. It is
+    /// followed by the compressed chunk entries.
+    #[derive(Serialize, Deserialize)]
+    pub struct V4 {
+        #[serde(deserialize_with = "version::<_, 4>")]
+        pub version: u64,
+        /// Offset and length of the entry of each chunk of the region, relative
+        /// to the end of the header. Chunks without changes have no entry.
+        pub index: Vec<(u32, u32)>,
+    }
+
+    impl V4 {
+        pub fn new(index: Vec<(u32, u32)>) -> Self {
+            Self {
+                version: version_magic(4),
+                index,
+            }
+        }
+    }
+
     /// Version 3 of the raw chunk format.
     #[derive(Serialize, Deserialize)]
     pub struct V3 {
@@ -342,3 +652,51 @@ mod version {
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::terrain::BlockKind;
+
+    #[test]
+    fn regions_are_written_once_per_maintain() {
+        let path = test_dir("regions");
+        let mut terrain =
+            TerrainPersistence::open(path.clone(), TerrainLock::acquire(&path).unwrap());
+        let region_path = TerrainPersistence::region_path(&path, Vec2::zero());
+        let block = Block::new(BlockKind::Rock, Rgb::new(1, 2, 3));
+
+        // A chunk file of an older format is only removed once its region is written
+        let legacy_key = Vec2::new(2, 0);
+        let legacy_chunk = Chunk {
+            blocks: std::iter::once((Vec3::new(1, 1, 1), block)).collect(),
+        };
+        let legacy_path = terrain.legacy_path_for(legacy_key);
+        std::fs::write(
+            &legacy_path,
+            bincode::serialize(&legacy_chunk.prepare_raw()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(terrain.load_chunk(legacy_key).blocks, legacy_chunk.blocks);
+        assert!(legacy_path.exists());
+
+        terrain.persist_block(Vec3::new(1, 1, 1), block);
+        terrain.persist_block(Vec3::new(40, 1, 1), block);
+        terrain.unload_chunk(Vec2::new(0, 0));
+        // Other chunks of the region are still loaded
+        assert!(!region_path.exists());
+        terrain.maintain();
+        assert!(region_path.exists());
+        assert!(!legacy_path.exists());
+
+        terrain.unload_chunk(Vec2::new(1, 0));
+        terrain.unload_chunk(legacy_key);
+        assert!(terrain.regions.is_empty());
+        let region = Region::load(&region_path);
+        for key in [Vec2::new(0, 0), Vec2::new(1, 0), legacy_key] {
+            assert!(region.chunk(key).unwrap().is_some());
+        }
+        drop(terrain);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
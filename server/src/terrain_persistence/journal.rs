@@ -0,0 +1,658 @@
+//! Append-only journal of the block edits recorded by terrain persistence,
+//! used by moderators to find out who changed the terrain and to revert it.
+//!
+//! The journal file starts with a version magic, followed by bincode encoded
+//! entries. A journal written by an unsupported version is moved aside and a
+//! new one is started.
+//!
+//! The journal is written and searched by a background thread, so that
+//! looking through it doesn't stall the server. Once the journal reaches
+//! [`MAX_JOURNAL_SIZE`] it is archived and a new one is started, keeping only
+//! the [`ARCHIVED_JOURNALS`] newest archives.
+use crate::presence::Presence;
+use authc::Uuid;
+use common::{character::CharacterId, comp::Player, terrain::Block};
+use common_net::msg::PresenceKind;
+use crossbeam_channel::TryIter;
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use specs::WorldExt;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, BufWriter, Write as _},
+    path::{Path, PathBuf},
+};
+use tracing::{error, warn};
+use vek::*;
+
+/// The size in bytes from which the journal is archived
+pub const MAX_JOURNAL_SIZE: u64 = 64 * 1024 * 1024;
+/// How many archived journals are kept, the oldest one is deleted when a new
+/// one is archived
+pub const ARCHIVED_JOURNALS: usize = 3;
+
+/// The player who made an edit
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EditAuthor {
+    pub player: Uuid,
+    pub alias: String,
+    pub character: Option<CharacterId>,
+}
+
+impl EditAuthor {
+    pub fn new(player: &Player, presence: Option<&Presence>) -> Self {
+        Self {
+            player: player.uuid(),
+            alias: player.alias.clone(),
+            character: presence.and_then(|presence| match presence.kind {
+                PresenceKind::Character(id) => Some(id),
+                PresenceKind::Spectator => None,
+            }),
+        }
+    }
+
+    pub fn from_entity(ecs: &specs::World, entity: specs::Entity) -> Option<Self> {
+        let presences = ecs.read_storage::<Presence>();
+        ecs.read_storage::<Player>()
+            .get(entity)
+            .map(|player| Self::new(player, presences.get(entity)))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockEdit {
+    /// Unix timestamp, in seconds
+    pub time: i64,
+    /// `None` for edits which weren't made by a player, e.g. by plugins
+    pub author: Option<EditAuthor>,
+    pub pos: Vec3<i32>,
+    pub old: Block,
+    pub new: Block,
+}
+
+type RevertFilter = Box<dyn Fn(&BlockEdit) -> bool + Send>;
+
+enum JournalRequest {
+    Record(BlockEdit),
+    Flush,
+    History {
+        entity: specs::Entity,
+        area: Aabb<i32>,
+        limit: usize,
+    },
+    Revert {
+        entity: specs::Entity,
+        player: Uuid,
+        username: String,
+        reverter: Option<EditAuthor>,
+        filter: RevertFilter,
+    },
+}
+
+/// The result of a search through the journal
+pub enum JournalResponseKind {
+    /// The newest edits within `area`, newest first
+    History {
+        area: Aabb<i32>,
+        result: io::Result<Vec<BlockEdit>>,
+    },
+    /// The blocks to set to revert the edits of the player named `username`,
+    /// with their current and their reverted block
+    Revert {
+        username: String,
+        reverter: Option<EditAuthor>,
+        result: io::Result<Vec<(Vec3<i32>, Block, Block)>>,
+    },
+}
+
+/// A search through the journal requested by `entity`
+pub struct JournalResponse {
+    pub entity: specs::Entity,
+    pub result: JournalResponseKind,
+}
+
+/// A bi-directional messaging resource for recording block edits and
+/// searching through them in a background thread.
+///
+/// Searches are answered after all the edits recorded before them, the
+/// responses are polled on each server tick with [`EditJournal::responses`].
+pub struct EditJournal {
+    request_tx: Option<crossbeam_channel::Sender<JournalRequest>>,
+    response_rx: crossbeam_channel::Receiver<JournalResponse>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl EditJournal {
+    pub fn new(terrain_path: &Path) -> Self {
+        let (request_tx, request_rx) = crossbeam_channel::unbounded::<JournalRequest>();
+        let (response_tx, response_rx) = crossbeam_channel::unbounded::<JournalResponse>();
+
+        let mut file = JournalFile::new(terrain_path);
+        let builder = std::thread::Builder::new().name("edit_journal".into());
+        let handle = builder
+            .spawn(move || {
+                for request in request_rx {
+                    let response = match request {
+                        JournalRequest::Record(edit) => {
+                            file.record(&edit);
+                            continue;
+                        },
+                        JournalRequest::Flush => {
+                            file.flush();
+                            continue;
+                        },
+                        JournalRequest::History {
+                            entity,
+                            area,
+                            limit,
+                        } => JournalResponse {
+                            entity,
+                            result: JournalResponseKind::History {
+                                area,
+                                result: file.history(area, limit),
+                            },
+                        },
+                        JournalRequest::Revert {
+                            entity,
+                            player,
+                            username,
+                            reverter,
+                            filter,
+                        } => JournalResponse {
+                            entity,
+                            result: JournalResponseKind::Revert {
+                                username,
+                                reverter,
+                                result: file.edits_to_revert(player, filter),
+                            },
+                        },
+                    };
+                    if let Err(e) = response_tx.send(response) {
+                        error!(?e, "Could not send edit journal response");
+                    }
+                }
+                file.flush();
+            })
+            .unwrap();
+
+        Self {
+            request_tx: Some(request_tx),
+            response_rx,
+            handle: Some(handle),
+        }
+    }
+
+    fn request(&self, request: JournalRequest) {
+        if let Err(e) = self.request_tx.as_ref().unwrap().send(request) {
+            error!(?e, "Could not send edit journal request");
+        }
+    }
+
+    pub fn record(&self, author: Option<EditAuthor>, pos: Vec3<i32>, old: Block, new: Block) {
+        self.request(JournalRequest::Record(BlockEdit {
+            time: chrono::Utc::now().timestamp(),
+            author,
+            pos,
+            old,
+            new,
+        }));
+    }
+
+    /// Write recorded edits to the filesystem
+    pub fn flush(&self) { self.request(JournalRequest::Flush); }
+
+    /// Look for the newest edits within `area`, for `entity`
+    pub fn history(&self, entity: specs::Entity, area: Aabb<i32>, limit: usize) {
+        self.request(JournalRequest::History {
+            entity,
+            area,
+            limit,
+        });
+    }
+
+    /// Look for the blocks to set to revert the edits of `player` (named
+    /// `username`) matching `filter`, for `entity`.
+    ///
+    /// Blocks are reverted to how they were before the first matching edit.
+    /// Blocks edited by someone else afterwards are left alone.
+    pub fn revert(
+        &self,
+        entity: specs::Entity,
+        player: Uuid,
+        username: String,
+        reverter: Option<EditAuthor>,
+        filter: impl Fn(&BlockEdit) -> bool + Send + 'static,
+    ) {
+        self.request(JournalRequest::Revert {
+            entity,
+            player,
+            username,
+            reverter,
+            filter: Box::new(filter),
+        });
+    }
+
+    /// The searches answered since the last call
+    pub fn responses(&self) -> TryIter<JournalResponse> { self.response_rx.try_iter() }
+}
+
+impl Drop for EditJournal {
+    fn drop(&mut self) {
+        drop(self.request_tx.take());
+        if let Err(e) = self.handle.take().unwrap().join() {
+            error!(?e, "Error from joining edit journal thread");
+        }
+    }
+}
+
+/// The journal files, only accessed by the journal thread
+struct JournalFile {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    /// The size of the journal, including what is buffered by `writer`
+    len: u64,
+}
+
+impl JournalFile {
+    fn new(terrain_path: &Path) -> Self {
+        Self {
+            path: terrain_path.join("journal.log"),
+            writer: None,
+            len: 0,
+        }
+    }
+
+    /// The path of an archived journal, 1 being the newest one
+    fn archive_path(&self, n: usize) -> PathBuf {
+        self.path.with_file_name(format!("journal.{}.log", n))
+    }
+
+    fn writer(&mut self) -> io::Result<&mut BufWriter<File>> {
+        if self.writer.is_none() {
+            let readable =
+                File::open(&self.path).and_then(|mut file| version::read_header(&mut file));
+            if readable.is_err() {
+                self.start_new()?;
+            }
+            let file = OpenOptions::new().append(true).open(&self.path)?;
+            self.len = file.metadata()?.len();
+            self.writer = Some(BufWriter::new(file));
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+
+    /// Start a new journal, keeping an unreadable one for you to repair.
+    fn start_new(&mut self) -> io::Result<()> {
+        if self.path.exists() {
+            let mut backup_path = self.path.clone();
+            backup_path.set_extension("log_backup_0");
+            let mut i = 1;
+            while backup_path.exists() {
+                backup_path.set_extension(format!("log_backup_{}", i));
+                i += 1;
+            }
+            error!(
+                "Failed to read the block edit journal, moving possibly corrupt (or too new) data \
+                 to {:?} for you to repair.",
+                backup_path
+            );
+            std::fs::rename(&self.path, backup_path)?;
+        }
+        let mut file = File::create(&self.path)?;
+        version::write_header(&mut file)
+    }
+
+    /// Archive the journal, so that the next edit starts a new one
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        let oldest = self.archive_path(ARCHIVED_JOURNALS);
+        if oldest.exists() {
+            std::fs::remove_file(oldest)?;
+        }
+        for n in (1..ARCHIVED_JOURNALS).rev() {
+            let archive = self.archive_path(n);
+            if archive.exists() {
+                std::fs::rename(archive, self.archive_path(n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.archive_path(1))
+    }
+
+    fn record(&mut self, edit: &BlockEdit) {
+        let result = bincode::serialize(&version::Current::from(edit))
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+            .and_then(|bytes| {
+                self.writer()?.write_all(&bytes)?;
+                self.len += bytes.len() as u64;
+                if self.len >= MAX_JOURNAL_SIZE {
+                    self.rotate()?;
+                }
+                Ok(())
+            });
+        if let Err(err) = result {
+            error!(?err, "Failed to record block edit");
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(Err(err)) = self.writer.as_mut().map(|writer| writer.flush()) {
+            error!(?err, "Failed to write the block edit journal");
+        }
+    }
+
+    /// Visit all recorded edits, including the archived ones, oldest first
+    fn for_each(&mut self, mut f: impl FnMut(BlockEdit)) -> io::Result<()> {
+        self.flush();
+        let paths = (1..=ARCHIVED_JOURNALS)
+            .rev()
+            .map(|n| self.archive_path(n))
+            .chain(std::iter::once(self.path.clone()));
+        for path in paths {
+            if !path.exists() {
+                continue;
+            }
+            let mut reader = BufReader::new(File::open(&path)?);
+            version::read_header(&mut reader)?;
+            loop {
+                match bincode::deserialize_from::<_, version::Current>(&mut reader) {
+                    Ok(edit) => f(edit.into()),
+                    Err(err) => {
+                        let end_of_journal = matches!(
+                            &*err,
+                            bincode::ErrorKind::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof
+                        );
+                        if !end_of_journal {
+                            warn!(
+                                ?err,
+                                ?path,
+                                "Stopped reading the block edit journal at a corrupt entry"
+                            );
+                        }
+                        break;
+                    },
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn history(&mut self, area: Aabb<i32>, limit: usize) -> io::Result<Vec<BlockEdit>> {
+        let mut edits = Vec::new();
+        self.for_each(|edit| {
+            if area.contains_point(edit.pos) {
+                edits.push(edit);
+            }
+        })?;
+        edits.reverse();
+        edits.truncate(limit);
+        Ok(edits)
+    }
+
+    fn edits_to_revert(
+        &mut self,
+        player: Uuid,
+        filter: RevertFilter,
+    ) -> io::Result<Vec<(Vec3<i32>, Block, Block)>> {
+        struct Reverted {
+            before: Block,
+            current: Block,
+            by_player: bool,
+        }
+
+        let mut reverted = HashMap::<Vec3<i32>, Reverted>::new();
+        self.for_each(|edit| {
+            let by_player = edit
+                .author
+                .as_ref()
+                .map_or(false, |author| author.player == player);
+            if by_player && filter(&edit) {
+                let entry = reverted.entry(edit.pos).or_insert(Reverted {
+                    before: edit.old,
+                    current: edit.new,
+                    by_player,
+                });
+                entry.current = edit.new;
+                entry.by_player = true;
+            } else if let Some(entry) = reverted.get_mut(&edit.pos) {
+                entry.current = edit.new;
+                entry.by_player = by_player;
+            }
+        })?;
+
+        let mut blocks = reverted
+            .into_iter()
+            .filter(|(_, reverted)| reverted.by_player && reverted.before != reverted.current)
+            .map(|(pos, reverted)| (pos, reverted.current, reverted.before))
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|(pos, _, _)| (pos.x, pos.y, pos.z));
+        Ok(blocks)
+    }
+}
+
+/// Journal entries use the same versioning as chunks, see the
+/// [`super::version`] module. As the journal is only ever appended to, a new
+/// version means starting a new journal.
+mod version {
+    use super::*;
+
+    pub type Current = V1;
+
+    impl From<&BlockEdit> for Current {
+        fn from(edit: &BlockEdit) -> Self {
+            Self {
+                time: edit.time,
+                author: edit
+                    .author
+                    .as_ref()
+                    .map(|author| (author.player, author.alias.clone(), author.character)),
+                pos: edit.pos.into_tuple(),
+                old: edit.old.to_u32(),
+                new: edit.new.to_u32(),
+            }
+        }
+    }
+
+    /// Version 1 of the raw journal entry format.
+    #[derive(Serialize, Deserialize)]
+    pub struct V1 {
+        pub time: i64,
+        pub author: Option<(Uuid, String, Option<CharacterId>)>,
+        pub pos: (i32, i32, i32),
+        pub old: u32,
+        pub new: u32,
+    }
+
+    impl From<V1> for BlockEdit {
+        fn from(v1: V1) -> Self {
+            Self {
+                time: v1.time,
+                author: v1.author.map(|(player, alias, character)| EditAuthor {
+                    player,
+                    alias,
+                    character,
+                }),
+                pos: Vec3::from(v1.pos),
+                old: Block::from_u32(v1.old).unwrap_or_else(Block::empty),
+                new: Block::from_u32(v1.new).unwrap_or_else(Block::empty),
+            }
+        }
+    }
+
+    fn version_magic(n: u16) -> u64 { (n as u64) | (0x70E2_4A1D_B10C << 16) }
+
+    pub fn write_header(writer: &mut impl io::Write) -> io::Result<()> {
+        writer.write_all(&version_magic(1).to_le_bytes())
+    }
+
+    pub fn read_header(reader: &mut impl io::Read) -> io::Result<()> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if u64::from_le_bytes(magic) == version_magic(1) {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "incorrect magic/version bytes",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::test_dir, *};
+    use common::terrain::BlockKind;
+
+    fn block(kind: BlockKind) -> Block { Block::new(kind, Rgb::new(1, 2, 3)) }
+
+    fn edit(time: i64, player: u128, pos: (i32, i32, i32), old: Block, new: Block) -> BlockEdit {
+        BlockEdit {
+            time,
+            author: Some(EditAuthor {
+                player: Uuid::from_u128(player),
+                alias: format!("player{}", player),
+                character: None,
+            }),
+            pos: Vec3::from(pos),
+            old,
+            new,
+        }
+    }
+
+    fn revert(
+        file: &mut JournalFile,
+        filter: impl Fn(&BlockEdit) -> bool + Send + 'static,
+    ) -> Vec<(Vec3<i32>, Block, Block)> {
+        file.edits_to_revert(Uuid::from_u128(1), Box::new(filter))
+            .unwrap()
+    }
+
+    #[test]
+    fn revert_interleaved_edits() {
+        let path = test_dir("journal-interleaved");
+        let mut file = JournalFile::new(&path);
+        let (air, rock, wood, dirt) = (
+            Block::empty(),
+            block(BlockKind::Rock),
+            block(BlockKind::Wood),
+            block(BlockKind::Earth),
+        );
+        for recorded in [
+            // Changed by someone else afterwards, which is left alone
+            edit(1, 1, (0, 0, 0), air, rock),
+            edit(2, 2, (0, 0, 0), rock, wood),
+            // Changed by the player after someone else
+            edit(1, 2, (1, 0, 0), air, dirt),
+            edit(2, 1, (1, 0, 0), dirt, rock),
+            // Changed back by the player
+            edit(1, 1, (2, 0, 0), air, rock),
+            edit(2, 1, (2, 0, 0), rock, air),
+            // Changed by the player around someone else, reverted to before
+            // the first edit of the player
+            edit(1, 1, (3, 0, 0), air, rock),
+            edit(2, 2, (3, 0, 0), rock, wood),
+            edit(3, 1, (3, 0, 0), wood, dirt),
+            // Only changed by someone else
+            edit(1, 2, (4, 0, 0), air, rock),
+        ]
+        .iter()
+        {
+            file.record(recorded);
+        }
+
+        assert_eq!(revert(&mut file, |_| true), vec![
+            (Vec3::new(1, 0, 0), rock, dirt),
+            (Vec3::new(3, 0, 0), dirt, air),
+        ]);
+        assert!(
+            file.edits_to_revert(Uuid::from_u128(3), Box::new(|_| true))
+                .unwrap()
+                .is_empty()
+        );
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn revert_filtered_edits() {
+        let path = test_dir("journal-filtered");
+        let mut file = JournalFile::new(&path);
+        let (air, rock, wood) = (
+            Block::empty(),
+            block(BlockKind::Rock),
+            block(BlockKind::Wood),
+        );
+        for recorded in [
+            edit(10, 1, (0, 0, 0), air, rock),
+            edit(20, 1, (0, 0, 0), rock, wood),
+            edit(10, 1, (50, 0, 0), air, rock),
+            edit(30, 1, (1, 1, 0), air, wood),
+        ]
+        .iter()
+        {
+            file.record(recorded);
+        }
+
+        // Only edits since the given time, reverted to before the first of them
+        assert_eq!(revert(&mut file, |edit| edit.time >= 15), vec![
+            (Vec3::new(0, 0, 0), wood, rock),
+            (Vec3::new(1, 1, 0), wood, air),
+        ]);
+        // Only edits within a radius
+        assert_eq!(
+            revert(&mut file, |edit| edit.pos.map(|e| e as f32).magnitude()
+                <= 10.0),
+            vec![
+                (Vec3::new(0, 0, 0), wood, air),
+                (Vec3::new(1, 1, 0), wood, air),
+            ]
+        );
+        assert!(revert(&mut file, |edit| edit.time > 30).is_empty());
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn revert_across_archives() {
+        let path = test_dir("journal-archives");
+        let mut file = JournalFile::new(&path);
+        let (air, rock, wood) = (
+            Block::empty(),
+            block(BlockKind::Rock),
+            block(BlockKind::Wood),
+        );
+        file.record(&edit(1, 1, (0, 0, 0), air, rock));
+        file.rotate().unwrap();
+        file.record(&edit(2, 2, (1, 0, 0), air, rock));
+        file.rotate().unwrap();
+        file.record(&edit(3, 1, (1, 0, 0), rock, wood));
+        file.record(&edit(3, 1, (2, 0, 0), air, wood));
+        file.rotate().unwrap();
+        file.record(&edit(4, 1, (0, 0, 0), rock, wood));
+
+        assert_eq!(revert(&mut file, |_| true), vec![
+            (Vec3::new(0, 0, 0), wood, air),
+            (Vec3::new(1, 0, 0), wood, rock),
+            (Vec3::new(2, 0, 0), wood, air),
+        ]);
+        // Newest first
+        let history = file
+            .history(Aabb::new_empty(Vec3::zero()), 10)
+            .unwrap()
+            .iter()
+            .map(|edit| edit.time)
+            .collect::<Vec<_>>();
+        assert_eq!(history, vec![4, 1]);
+
+        // Only the newest archives are kept, the oldest edit is forgotten
+        file.rotate().unwrap();
+        assert!(file.archive_path(ARCHIVED_JOURNALS).exists());
+        assert!(!file.archive_path(ARCHIVED_JOURNALS + 1).exists());
+        assert_eq!(revert(&mut file, |_| true), vec![
+            (Vec3::new(0, 0, 0), wood, rock),
+            (Vec3::new(1, 0, 0), wood, rock),
+            (Vec3::new(2, 0, 0), wood, air),
+        ]);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,439 @@
+//! Export of the persisted changes within an area as a prefab, and import of
+//! prefabs at an offset, so builds can be moved between servers and worlds.
+//!
+//! A prefab is a `.vox` model next to a `.ron` structure manifest, in the
+//! format of the manifests in `assets/world/manifests`. Its custom indices
+//! map every palette index of the model to the exact block, so a prefab can
+//! also be placed by world generation like any other structure.
+use super::TerrainPersistence;
+use common::{
+    terrain::{Block, BlockKind, SpriteKind, StructureBlock, TerrainChunk},
+    vol::RectRasterableVol,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, io, path::Path};
+use tracing::warn;
+use vek::*;
+
+/// Models of `.vox` files can't be larger than this along any axis
+const MAX_SIZE: u32 = 256;
+/// Palette index 0 is empty, so 255 different blocks remain
+const MAX_PALETTE_LEN: usize = 255;
+
+#[derive(Debug)]
+pub enum PrefabError {
+    Io(io::Error),
+    /// The area is larger than a `.vox` model can be
+    TooLarge(Vec3<u32>),
+    /// The area contains more different blocks than fit into a palette
+    TooManyBlocks,
+    Invalid(String),
+}
+
+impl From<io::Error> for PrefabError {
+    fn from(err: io::Error) -> Self { Self::Io(err) }
+}
+
+impl fmt::Display for PrefabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {}", err),
+            Self::TooLarge(size) => write!(
+                f,
+                "The area is {} blocks large, prefabs can be at most {} blocks along every axis",
+                size, MAX_SIZE
+            ),
+            Self::TooManyBlocks => write!(
+                f,
+                "The area contains more than {} different blocks",
+                MAX_PALETTE_LEN
+            ),
+            Self::Invalid(err) => write!(f, "Invalid prefab: {}", err),
+        }
+    }
+}
+
+/// An entry of a structure manifest
+#[derive(Serialize, Deserialize)]
+struct StructureSpec {
+    specifier: String,
+    center: [i32; 3],
+    #[serde(default)]
+    custom_indices: BTreeMap<u8, StructureBlock>,
+}
+
+/// Persisted changes, relative to the minimum corner of their area
+pub struct Prefab {
+    pub size: Vec3<u32>,
+    pub blocks: Vec<(Vec3<i32>, Block)>,
+}
+
+impl Prefab {
+    fn structure_block(block: Block) -> StructureBlock {
+        match (block.get_color(), block.get_sprite()) {
+            (Some(color), _) => StructureBlock::Filled(block.kind(), color),
+            (_, Some(sprite)) if sprite != SpriteKind::Empty => StructureBlock::Sprite(sprite),
+            _ if block.kind() == BlockKind::Water => StructureBlock::Water,
+            _ => StructureBlock::Hollow,
+        }
+    }
+
+    fn block(structure_block: StructureBlock) -> Option<Block> {
+        match structure_block {
+            StructureBlock::Filled(kind, color) => Some(Block::new(kind, color)),
+            StructureBlock::Normal(color) => Some(Block::new(BlockKind::Misc, color)),
+            StructureBlock::Sprite(sprite) => Some(Block::air(sprite)),
+            StructureBlock::Water => Some(Block::water(SpriteKind::Empty)),
+            StructureBlock::Hollow => Some(Block::empty()),
+            _ => None,
+        }
+    }
+
+    /// Write the prefab to the `.vox` file at `path` and its manifest next to
+    /// it.
+    pub fn save(&self, path: &Path) -> Result<(), PrefabError> {
+        if self.size.reduce_max() > MAX_SIZE {
+            return Err(PrefabError::TooLarge(self.size));
+        }
+
+        let mut palette = Vec::<StructureBlock>::new();
+        let mut voxels = Vec::with_capacity(self.blocks.len());
+        for (pos, block) in self.blocks.iter() {
+            let structure_block = Self::structure_block(*block);
+            let index = match palette.iter().position(|sb| *sb == structure_block) {
+                Some(index) => index,
+                None if palette.len() < MAX_PALETTE_LEN => {
+                    palette.push(structure_block);
+                    palette.len() - 1
+                },
+                None => return Err(PrefabError::TooManyBlocks),
+            };
+            // Palette index 0 is empty
+            voxels.push((pos.map(|e| e as u8), index as u8 + 1));
+        }
+
+        let name = path
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or("prefab");
+        let spec = StructureSpec {
+            specifier: format!("world.structure.{}", name),
+            center: [self.size.x as i32 / 2, self.size.y as i32 / 2, 0],
+            custom_indices: palette
+                .iter()
+                .enumerate()
+                .map(|(i, sb)| (i as u8 + 1, *sb))
+                .collect(),
+        };
+        let manifest = ron::ser::to_string_pretty(&vec![spec], ron::ser::PrettyConfig::default())
+            .map_err(|err| PrefabError::Invalid(err.to_string()))?;
+
+        std::fs::write(path, vox_bytes(self.size, &voxels, &palette))?;
+        std::fs::write(path.with_extension("ron"), manifest)?;
+        Ok(())
+    }
+
+    /// Read the prefab from the `.vox` file at `path`. Without a manifest next
+    /// to it, the colors of the model are imported as blocks, like world
+    /// generation would.
+    pub fn load(path: &Path) -> Result<Self, PrefabError> {
+        let data = dot_vox::load_bytes(&std::fs::read(path)?)
+            .map_err(|err| PrefabError::Invalid(err.to_owned()))?;
+        let model = data
+            .models
+            .get(0)
+            .ok_or_else(|| PrefabError::Invalid("the file contains no model".to_owned()))?;
+
+        let manifest_path = path.with_extension("ron");
+        let custom_indices = if manifest_path.exists() {
+            ron::de::from_bytes::<Vec<StructureSpec>>(&std::fs::read(manifest_path)?)
+                .map_err(|err| PrefabError::Invalid(err.to_string()))?
+                .into_iter()
+                .next()
+                .map(|spec| spec.custom_indices)
+                .unwrap_or_default()
+        } else {
+            BTreeMap::new()
+        };
+
+        let mut unsupported = 0;
+        let blocks = model
+            .voxels
+            .iter()
+            .filter_map(|voxel| {
+                // `dot_vox` subtracts 1 from the palette index of voxels
+                let index = voxel.i.saturating_add(1);
+                let structure_block = custom_indices.get(&index).copied().unwrap_or_else(|| {
+                    let color = data.palette.get(voxel.i as usize).copied().unwrap_or(0);
+                    let [r, g, b, _] = color.to_le_bytes();
+                    StructureBlock::Filled(BlockKind::Misc, Rgb::new(r, g, b))
+                });
+                let block = Self::block(structure_block);
+                unsupported += block.is_none() as usize;
+                Some((Vec3::new(voxel.x, voxel.y, voxel.z).map(i32::from), block?))
+            })
+            .collect();
+        if unsupported > 0 {
+            warn!(
+                "Skipped {} blocks of the prefab which can only be placed by world generation",
+                unsupported
+            );
+        }
+
+        Ok(Self {
+            size: Vec3::new(model.size.x, model.size.y, model.size.z),
+            blocks,
+        })
+    }
+}
+
+/// Encode a model in the MagicaVoxel `.vox` format
+fn vox_bytes(size: Vec3<u32>, voxels: &[(Vec3<u8>, u8)], palette: &[StructureBlock]) -> Vec<u8> {
+    fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(children.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(content);
+        bytes.extend_from_slice(children);
+        bytes
+    }
+
+    let size = [size.x, size.y, size.z]
+        .iter()
+        .flat_map(|e| e.to_le_bytes())
+        .collect::<Vec<_>>();
+    let mut xyzi = (voxels.len() as u32).to_le_bytes().to_vec();
+    for (pos, index) in voxels {
+        xyzi.extend_from_slice(&[pos.x, pos.y, pos.z, *index]);
+    }
+    // The colors are only used to display the model in editors
+    let rgba = (0..256)
+        .flat_map(|i| {
+            let color = match palette.get(i) {
+                Some(StructureBlock::Filled(_, color)) => Rgba::from_opaque(*color),
+                Some(StructureBlock::Sprite(_)) => Rgba::new(200, 200, 200, 255),
+                Some(StructureBlock::Water) => Rgba::new(40, 80, 200, 128),
+                Some(_) => Rgba::new(255, 255, 255, 32),
+                None => Rgba::zero(),
+            };
+            [color.r, color.g, color.b, color.a]
+        })
+        .collect::<Vec<_>>();
+
+    let children = [
+        chunk(b"SIZE", &size, &[]),
+        chunk(b"XYZI", &xyzi, &[]),
+        chunk(b"RGBA", &rgba, &[]),
+    ]
+    .concat();
+    let mut bytes = b"VOX ".to_vec();
+    bytes.extend_from_slice(&150u32.to_le_bytes());
+    bytes.extend(chunk(b"MAIN", &[], &children));
+    bytes
+}
+
+impl TerrainPersistence {
+    /// The persisted changes within `area`
+    pub fn export(&mut self, area: Aabb<i32>) -> Result<Prefab, PrefabError> {
+        let area = area.made_valid();
+        // Checked before any chunks are read, as the area could be huge
+        let size = area.max.map2(area.min, |max, min| {
+            (max as i64 - min as i64 + 1).min(u32::MAX as i64) as u32
+        });
+        if size.reduce_max() > MAX_SIZE {
+            return Err(PrefabError::TooLarge(size));
+        }
+        let chunk_size = TerrainChunk::RECT_SIZE.map(|e| e as i32);
+        let min_key = area.min.xy().map2(chunk_size, |e, sz| e.div_euclid(sz));
+        let max_key = area.max.xy().map2(chunk_size, |e, sz| e.div_euclid(sz));
+
+        let mut blocks = Vec::new();
+        for x in min_key.x..=max_key.x {
+            for y in min_key.y..=max_key.y {
+                let key = Vec2::new(x, y);
+                let offset = Vec3::from(key * chunk_size);
+                let chunk = match self.chunks.get(&key) {
+                    Some(chunk) => chunk.blocks().collect::<Vec<_>>(),
+                    None => self
+                        .read_chunk(key)
+                        .map(|chunk| chunk.blocks().collect())
+                        .unwrap_or_default(),
+                };
+                blocks.extend(
+                    chunk
+                        .into_iter()
+                        .map(|(rpos, block)| (offset + rpos, block))
+                        .filter(|(wpos, _)| area.contains_point(*wpos))
+                        .map(|(wpos, block)| (wpos - area.min, block)),
+                );
+            }
+        }
+        self.evict_regions();
+        blocks.sort_by_key(|(pos, _)| (pos.z, pos.y, pos.x));
+
+        Ok(Prefab { size, blocks })
+    }
+
+    /// Persist the blocks of a prefab, with its minimum corner at `offset`.
+    /// This must only be used while the server isn't running, see
+    /// [`TerrainPersistence::try_new`].
+    ///
+    /// The blocks aren't recorded in the edit journal, as the blocks they
+    /// replace aren't known without generating the world.
+    pub fn import(&mut self, prefab: &Prefab, offset: Vec3<i32>) {
+        for (pos, block) in prefab.blocks.iter() {
+            self.persist_block(offset + *pos, *block);
+        }
+        // Nothing is loaded while the server isn't running
+        self.unload_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::test_dir, *};
+    use crate::terrain_persistence::TerrainLock;
+
+    fn terrain(path: &Path) -> TerrainPersistence {
+        TerrainPersistence::open(path.to_owned(), TerrainLock::acquire(path).unwrap())
+    }
+
+    fn sorted(mut blocks: Vec<(Vec3<i32>, Block)>) -> Vec<(Vec3<i32>, Block)> {
+        blocks.sort_by_key(|(pos, _)| (pos.x, pos.y, pos.z));
+        blocks
+    }
+
+    #[test]
+    fn export_import_roundtrip() {
+        let path = test_dir("prefab-roundtrip");
+        let blocks = vec![
+            (
+                Vec3::new(-3, 5, 10),
+                Block::new(BlockKind::Rock, Rgb::new(1, 2, 3)),
+            ),
+            (
+                Vec3::new(40, 5, 10),
+                Block::new(BlockKind::Wood, Rgb::new(4, 5, 6)),
+            ),
+            (Vec3::new(0, 0, 12), Block::air(SpriteKind::Lantern)),
+            (Vec3::new(1, 0, 12), Block::water(SpriteKind::Empty)),
+            (Vec3::new(2, 0, 12), Block::empty()),
+        ];
+        let mut terrain = terrain(&path);
+        terrain.import(
+            &Prefab {
+                size: Vec3::new(64, 64, 64),
+                blocks: blocks.clone(),
+            },
+            Vec3::zero(),
+        );
+        // Outside of the exported area
+        terrain.persist_block(
+            Vec3::new(41, 5, 10),
+            Block::new(BlockKind::Misc, Rgb::zero()),
+        );
+
+        let prefab = terrain
+            .export(Aabb {
+                min: Vec3::new(40, 5, 12),
+                max: Vec3::new(-3, 0, 10),
+            })
+            .unwrap();
+        assert_eq!(prefab.size, Vec3::new(44, 6, 3));
+        let vox_path = path.join("prefab.vox");
+        prefab.save(&vox_path).unwrap();
+
+        // Every block is mapped back from its palette index through the manifest
+        let loaded = Prefab::load(&vox_path).unwrap();
+        assert_eq!(loaded.size, prefab.size);
+        assert_eq!(sorted(loaded.blocks.clone()), sorted(prefab.blocks));
+
+        let offset = Vec3::new(-3, 0, 10);
+        let other_path = test_dir("prefab-roundtrip-import");
+        let mut other = terrain(&other_path);
+        other.import(&loaded, offset + Vec3::new(100, 0, 0));
+        let moved = other
+            .export(Aabb {
+                min: Vec3::new(97, 0, 10),
+                max: Vec3::new(140, 5, 12),
+            })
+            .unwrap();
+        assert_eq!(
+            sorted(
+                moved
+                    .blocks
+                    .into_iter()
+                    .map(|(pos, block)| (pos + offset, block))
+                    .collect()
+            ),
+            sorted(blocks)
+        );
+
+        // Without the manifest, the colors of the model are imported
+        std::fs::remove_file(vox_path.with_extension("ron")).unwrap();
+        let colors = Prefab::load(&vox_path).unwrap();
+        assert!(colors.blocks.contains(&(
+            Vec3::new(0, 5, 0),
+            Block::new(BlockKind::Misc, Rgb::new(1, 2, 3))
+        )));
+        drop((terrain, other));
+        std::fs::remove_dir_all(path).unwrap();
+        std::fs::remove_dir_all(other_path).unwrap();
+    }
+
+    #[test]
+    fn oversized_prefabs_are_rejected() {
+        let path = test_dir("prefab-errors");
+        let mut terrain = terrain(&path);
+        assert!(matches!(
+            terrain.export(Aabb {
+                min: Vec3::new(0, 0, 0),
+                max: Vec3::new(255, 10, 256),
+            }),
+            Err(PrefabError::TooLarge(size)) if size == Vec3::new(256, 11, 257)
+        ));
+        // A model is at most 256 blocks along every axis
+        assert!(
+            terrain
+                .export(Aabb {
+                    min: Vec3::new(i32::MIN, 0, 0),
+                    max: Vec3::new(i32::MAX, 0, 0),
+                })
+                .is_err()
+        );
+
+        let too_large = Prefab {
+            size: Vec3::new(1, 257, 1),
+            blocks: Vec::new(),
+        };
+        assert!(matches!(
+            too_large.save(&path.join("large.vox")),
+            Err(PrefabError::TooLarge(_))
+        ));
+
+        // Only 255 different blocks fit into the palette
+        let blocks = (0..256)
+            .map(|i| {
+                (
+                    Vec3::new(i % 16, i / 16, 0),
+                    Block::new(BlockKind::Rock, Rgb::new(i as u8, 0, 0)),
+                )
+            })
+            .collect::<Vec<_>>();
+        let palette = Prefab {
+            size: Vec3::new(16, 16, 1),
+            blocks: blocks[..255].to_vec(),
+        };
+        assert!(palette.save(&path.join("palette.vox")).is_ok());
+        let too_many = Prefab {
+            size: Vec3::new(16, 16, 1),
+            blocks,
+        };
+        assert!(matches!(
+            too_many.save(&path.join("many.vox")),
+            Err(PrefabError::TooManyBlocks)
+        ));
+        drop(terrain);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,271 @@
+//! Regions group the persisted chunks of a [`REGION_SIZE`] x [`REGION_SIZE`]
+//! area into a single file, so busy servers don't end up with a file per
+//! modified chunk.
+//!
+//! A region file is a [`version::V4`] header, which indexes the entries of the
+//! chunks by their position within the region, followed by the entries. Every
+//! entry is a deflate compressed chunk in the [`version::Current`] format.
+use super::{version, Chunk};
+use atomicwrites::{AtomicFile, OverwriteBehavior};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use std::{
+    collections::BTreeMap,
+    io::{self, Read as _, Write as _},
+    path::{Path, PathBuf},
+};
+use tracing::error;
+use vek::*;
+
+/// Width and height of a region, in chunks
+pub const REGION_SIZE: i32 = 32;
+
+pub fn region_key(chunk_key: Vec2<i32>) -> Vec2<i32> {
+    chunk_key.map(|e| e.div_euclid(REGION_SIZE))
+}
+
+fn entry_index(chunk_key: Vec2<i32>) -> usize {
+    let local = chunk_key.map(|e| e.rem_euclid(REGION_SIZE));
+    (local.y * REGION_SIZE + local.x) as usize
+}
+
+#[derive(Default)]
+pub struct Region {
+    /// Compressed chunk entries, by their index within the region
+    entries: BTreeMap<usize, Vec<u8>>,
+    /// Whether the entries changed since the region was saved
+    modified: bool,
+    /// Number of chunks of this region loaded by terrain persistence
+    pub(super) loaded_chunks: usize,
+    /// Chunk files of older formats moved into the region, which are removed
+    /// once it's written
+    pub(super) migrated: Vec<PathBuf>,
+}
+
+impl Region {
+    /// Load the region file at `path`, if it exists.
+    pub fn load(path: &Path) -> Self {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(err) => {
+                error!("Failed to read region file {:?}: {:?}", path, err);
+                return Self::default();
+            },
+        };
+        Self::from_bytes(&bytes).unwrap_or_else(|| {
+            // Find an untaken name for a backup
+            let mut backup_path = path.to_path_buf();
+            backup_path.set_extension("dat_backup_0");
+            let mut i = 1;
+            while backup_path.exists() {
+                backup_path.set_extension(format!("dat_backup_{}", i));
+                i += 1;
+            }
+
+            error!(
+                "Failed to load region {:?}, moving possibly corrupt (or too new) data to {:?} \
+                 for you to repair.",
+                path, backup_path
+            );
+            if let Err(err) = std::fs::rename(path, backup_path) {
+                error!("Failed to rename invalid region file: {:?}", err);
+            }
+            Self::default()
+        })
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let header = bincode::deserialize::<version::V4>(bytes).ok()?;
+        if header.index.len() != (REGION_SIZE * REGION_SIZE) as usize {
+            return None;
+        }
+        let data = bytes.get(bincode::serialized_size(&header).ok()? as usize..)?;
+        let entries = header
+            .index
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, len))| *len > 0)
+            .map(|(i, (offset, len))| {
+                let entry = data.get(*offset as usize..*offset as usize + *len as usize)?;
+                Some((i, entry.to_vec()))
+            })
+            .collect::<Option<_>>()?;
+        Some(Self {
+            entries,
+            modified: false,
+            loaded_chunks: 0,
+            migrated: Vec::new(),
+        })
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, bincode::Error> {
+        let mut index = vec![(0, 0); (REGION_SIZE * REGION_SIZE) as usize];
+        let mut data = Vec::new();
+        for (i, entry) in self.entries.iter() {
+            index[*i] = (data.len() as u32, entry.len() as u32);
+            data.extend_from_slice(entry);
+        }
+        let mut bytes = bincode::serialize(&version::V4::new(index))?;
+        bytes.extend(data);
+        Ok(bytes)
+    }
+
+    /// Write the region to `path` if it was modified, removing the file
+    /// once the region is empty.
+    pub fn save(&mut self, path: &Path) {
+        if self.modified && !self.write(path) {
+            // The chunk files are migrated again when the region is next loaded
+            self.migrated.clear();
+            return;
+        }
+        for path in self.migrated.drain(..) {
+            if let Err(err) = std::fs::remove_file(path) {
+                error!("Failed to remove migrated chunk file: {:?}", err);
+            }
+        }
+    }
+
+    /// Returns whether the region was written
+    fn write(&mut self, path: &Path) -> bool {
+        self.modified = false;
+
+        if self.entries.is_empty() {
+            if let Err(err) = std::fs::remove_file(path) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    error!("Failed to remove empty region file: {:?}", err);
+                    return false;
+                }
+            }
+            return true;
+        }
+
+        let bytes = match self.to_bytes() {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                error!("Failed to serialize region data: {:?}", err);
+                return false;
+            },
+        };
+        let atomic_file = AtomicFile::new(path, OverwriteBehavior::AllowOverwrite);
+        if let Err(err) = atomic_file.write(|file| file.write_all(&bytes)) {
+            error!("Failed to write region data to file: {:?}", err);
+            return false;
+        }
+        true
+    }
+
+    /// Keys of all chunks stored in the region with the given key
+    pub fn chunk_keys(&self, region_key: Vec2<i32>) -> impl Iterator<Item = Vec2<i32>> + '_ {
+        self.entries.keys().map(move |i| {
+            region_key * REGION_SIZE + Vec2::new(*i as i32 % REGION_SIZE, *i as i32 / REGION_SIZE)
+        })
+    }
+
+    /// The uncompressed entry of a chunk, in the [`version::Current`] format
+    pub fn entry(&self, chunk_key: Vec2<i32>) -> Option<io::Result<Vec<u8>>> {
+        self.entries.get(&entry_index(chunk_key)).map(|entry| {
+            let mut bytes = Vec::with_capacity(entry.len() * 4);
+            DeflateDecoder::new(&**entry)
+                .read_to_end(&mut bytes)
+                .map(|_| bytes)
+        })
+    }
+
+    /// `Err` if the entry of the chunk is corrupt
+    pub fn chunk(&self, chunk_key: Vec2<i32>) -> Result<Option<Chunk>, ()> {
+        match self.entry(chunk_key) {
+            Some(Ok(bytes)) => Chunk::deserialize_from(io::Cursor::new(bytes))
+                .map(Some)
+                .ok_or(()),
+            Some(Err(_)) => Err(()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_chunk(&mut self, chunk_key: Vec2<i32>, chunk: &Chunk) {
+        let index = entry_index(chunk_key);
+        // No need to store chunks without changes
+        if chunk.blocks.is_empty() {
+            self.modified |= self.entries.remove(&index).is_some();
+            return;
+        }
+
+        let bytes = match bincode::serialize::<version::Current>(&chunk.prepare_raw()) {
+            Err(err) => {
+                error!("Failed to serialize chunk data: {:?}", err);
+                return;
+            },
+            Ok(bytes) => bytes,
+        };
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        match encoder.write_all(&bytes).and_then(|_| encoder.finish()) {
+            Ok(entry) => {
+                self.entries.insert(index, entry);
+                self.modified = true;
+            },
+            Err(err) => error!("Failed to compress chunk data: {:?}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::terrain::{Block, BlockKind};
+    use hashbrown::HashMap;
+
+    fn chunk(blocks: &[(Vec3<i32>, BlockKind)]) -> Chunk {
+        Chunk {
+            blocks: blocks
+                .iter()
+                .map(|(pos, kind)| (*pos, Block::new(*kind, Rgb::new(1, 2, 3))))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn region_roundtrip() {
+        let mut region = Region::default();
+        let a = chunk(&[(Vec3::new(0, 0, 10), BlockKind::Rock)]);
+        let b = chunk(&[
+            (Vec3::new(31, 31, -5), BlockKind::Wood),
+            (Vec3::new(1, 2, 300), BlockKind::Misc),
+        ]);
+        region.set_chunk(Vec2::new(-1, -1), &a);
+        region.set_chunk(Vec2::new(64, 33), &b);
+
+        let loaded = Region::from_bytes(&region.to_bytes().unwrap()).unwrap();
+        let mut keys = loaded.chunk_keys(Vec2::new(2, 1)).collect::<Vec<_>>();
+        keys.sort_by_key(|key| (key.x, key.y));
+        // (-1, -1) is in another region, but ends up at the same index
+        assert_eq!(keys, vec![Vec2::new(64, 33), Vec2::new(95, 63)]);
+        assert_eq!(
+            loaded.chunk(Vec2::new(-1, -1)).unwrap().unwrap().blocks,
+            a.blocks
+        );
+        assert_eq!(
+            loaded.chunk(Vec2::new(64, 33)).unwrap().unwrap().blocks,
+            b.blocks
+        );
+        assert!(loaded.chunk(Vec2::new(0, 0)).unwrap().is_none());
+    }
+
+    #[test]
+    fn empty_chunks_are_removed() {
+        let mut region = Region::default();
+        region.set_chunk(Vec2::new(3, 4), &chunk(&[(Vec3::zero(), BlockKind::Rock)]));
+        region.modified = false;
+        region.set_chunk(Vec2::new(3, 4), &Chunk::default());
+        assert!(region.modified);
+        assert!(region.chunk(Vec2::new(3, 4)).unwrap().is_none());
+    }
+
+    #[test]
+    fn corrupt_region_is_rejected() {
+        let mut region = Region::default();
+        region.set_chunk(Vec2::new(3, 4), &chunk(&[(Vec3::zero(), BlockKind::Rock)]));
+        let bytes = region.to_bytes().unwrap();
+        assert!(Region::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+        assert!(Region::from_bytes(&bytes[1..]).is_none());
+    }
+}
@@ -0,0 +1,660 @@
+//! Snapshots of the modified chunks of terrain persistence.
+//!
+//! A snapshot is a manifest, named after the time it was taken, which maps
+//! every modified chunk to the hash of its changes, serialized in the newest
+//! chunk format. The changes themselves are stored once per content in the
+//! `objects` directory, so
+//! snapshots only take space for the chunks that changed since the previous
+//! one.
+use super::{region::region_key, Chunk, TerrainLock, TerrainPersistence};
+use atomicwrites::{AtomicFile, OverwriteBehavior};
+use common::{
+    terrain::{Block, TerrainChunk},
+    vol::RectRasterableVol,
+};
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, warn};
+use vek::*;
+
+pub type ChunkHash = [u8; 32];
+
+const MANIFEST_EXTENSION: &str = "snapshot";
+/// Largest area that can be restored at once, in chunks
+pub const MAX_RESTORE_CHUNKS: u64 = 256;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    /// The snapshot doesn't exist or can't be read
+    InvalidSnapshot(String),
+    /// The area to restore spans more than [`MAX_RESTORE_CHUNKS`] chunks
+    AreaTooLarge(u64),
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self { Self::Io(err) }
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {}", err),
+            Self::InvalidSnapshot(name) => write!(f, "Snapshot {} could not be read", name),
+            Self::AreaTooLarge(chunks) => write!(
+                f,
+                "The area spans {} chunks, at most {} can be restored at once",
+                chunks, MAX_RESTORE_CHUNKS
+            ),
+        }
+    }
+}
+
+/// The chunks of a snapshot
+#[derive(Clone, Default, PartialEq)]
+pub struct Manifest {
+    pub chunks: HashMap<Vec2<i32>, ChunkHash>,
+}
+
+/// Changes between two snapshots
+#[derive(Default)]
+pub struct ManifestDiff {
+    pub added: Vec<Vec2<i32>>,
+    pub removed: Vec<Vec2<i32>>,
+    pub changed: Vec<Vec2<i32>>,
+}
+
+impl Manifest {
+    pub fn diff(&self, newer: &Manifest) -> ManifestDiff {
+        let mut diff = ManifestDiff::default();
+        for (key, hash) in newer.chunks.iter() {
+            match self.chunks.get(key) {
+                None => diff.added.push(*key),
+                Some(old_hash) if old_hash != hash => diff.changed.push(*key),
+                Some(_) => {},
+            }
+        }
+        diff.removed = self
+            .chunks
+            .keys()
+            .filter(|key| !newer.chunks.contains_key(*key))
+            .copied()
+            .collect();
+        for keys in [&mut diff.added, &mut diff.removed, &mut diff.changed] {
+            keys.sort_by_key(|key| (key.x, key.y));
+        }
+        diff
+    }
+}
+
+/// The snapshot directory of a terrain persistence directory
+pub struct SnapshotStore {
+    terrain_path: PathBuf,
+    path: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(terrain_path: &Path) -> Self {
+        Self {
+            terrain_path: terrain_path.to_owned(),
+            path: terrain_path.join("snapshots"),
+        }
+    }
+
+    fn objects_path(&self) -> PathBuf { self.path.join("objects") }
+
+    fn object_path(&self, hash: &ChunkHash) -> PathBuf {
+        let name = hash
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        self.objects_path().join(format!("{}.dat", name))
+    }
+
+    fn manifest_path(&self, name: &str) -> PathBuf {
+        self.path.join(format!("{}.{}", name, MANIFEST_EXTENSION))
+    }
+
+    /// Names of all snapshots, oldest first
+    pub fn list(&self) -> Result<Vec<String>, SnapshotError> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut names = fs::read_dir(&self.path)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                (path.extension()? == MANIFEST_EXTENSION)
+                    .then(|| path.file_stem()?.to_str().map(str::to_owned))
+                    .flatten()
+            })
+            .collect::<Vec<_>>();
+        // Names are timestamps, so they sort chronologically
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn latest(&self) -> Result<Option<(String, Manifest)>, SnapshotError> {
+        match self.list()?.pop() {
+            Some(name) => {
+                let manifest = self.manifest(&name)?;
+                Ok(Some((name, manifest)))
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn manifest(&self, name: &str) -> Result<Manifest, SnapshotError> {
+        let invalid = || SnapshotError::InvalidSnapshot(name.to_owned());
+        // Don't allow names to escape the snapshot directory
+        if name.contains(|c: char| std::path::is_separator(c) || c == '.') {
+            return Err(invalid());
+        }
+        let bytes = fs::read(self.manifest_path(name)).map_err(|_| invalid())?;
+        version::try_load(&bytes).ok_or_else(invalid)
+    }
+
+    /// Load the chunk of a snapshot, an empty chunk if it wasn't modified at
+    /// that time
+    pub fn chunk(&self, manifest: &Manifest, key: Vec2<i32>) -> Result<Chunk, SnapshotError> {
+        match manifest.chunks.get(&key) {
+            Some(hash) => {
+                let bytes = fs::read(self.object_path(hash))?;
+                Chunk::deserialize_from(io::Cursor::new(bytes)).ok_or_else(|| {
+                    SnapshotError::InvalidSnapshot(format!("object of chunk {:?}", key))
+                })
+            },
+            None => Ok(Chunk::default()),
+        }
+    }
+
+    /// Store the changes of a chunk, unless a chunk with the same content is
+    /// stored already
+    pub fn store_object(&self, bytes: &[u8]) -> Result<ChunkHash, SnapshotError> {
+        let mut hash = ChunkHash::default();
+        hash.copy_from_slice(&Sha256::digest(bytes));
+        let path = self.object_path(&hash);
+        if !path.exists() {
+            fs::create_dir_all(self.objects_path())?;
+            AtomicFile::new(path, OverwriteBehavior::AllowOverwrite)
+                .write(|file| file.write_all(bytes))
+                .map_err(|err| match err {
+                    atomicwrites::Error::Internal(err) | atomicwrites::Error::User(err) => err,
+                })?;
+        }
+        Ok(hash)
+    }
+
+    /// Save a new snapshot, named after the current time
+    pub fn save(&self, manifest: &Manifest) -> Result<String, SnapshotError> {
+        fs::create_dir_all(&self.path)?;
+        let timestamp = chrono::Utc::now().format("%Y-%m-%dT%H-%M-%SZ").to_string();
+        // Snapshots taken in the same second
+        let mut name = timestamp.clone();
+        let mut i = 1;
+        while self.manifest_path(&name).exists() {
+            name = format!("{}-{}", timestamp, i);
+            i += 1;
+        }
+
+        let bytes = bincode::serialize(&version::Current::from(manifest))
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        AtomicFile::new(
+            self.manifest_path(&name),
+            OverwriteBehavior::DisallowOverwrite,
+        )
+        .write(|file| file.write_all(&bytes))
+        .map_err(|err| match err {
+            atomicwrites::Error::Internal(err) | atomicwrites::Error::User(err) => err,
+        })?;
+        Ok(name)
+    }
+
+    /// Delete all but the newest `keep` snapshots, and the chunk files only
+    /// they referenced. Returns the deleted snapshots.
+    ///
+    /// Fails while a server is running on the terrain, as it might be about to
+    /// reference a chunk file which isn't referenced yet.
+    pub fn prune(&self, keep: usize) -> Result<Vec<String>, SnapshotError> {
+        let _lock = TerrainLock::acquire(&self.terrain_path)?;
+        let mut names = self.list()?;
+        let pruned = names
+            .drain(..names.len().saturating_sub(keep))
+            .collect::<Vec<_>>();
+        for name in pruned.iter() {
+            fs::remove_file(self.manifest_path(name))?;
+        }
+
+        let referenced = names
+            .iter()
+            .map(|name| self.manifest(name))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flat_map(|manifest| {
+                manifest
+                    .chunks
+                    .into_iter()
+                    .map(|(_, hash)| self.object_path(&hash))
+            })
+            .collect::<hashbrown::HashSet<_>>();
+        if self.objects_path().exists() {
+            for entry in fs::read_dir(self.objects_path())? {
+                let path = entry?.path();
+                if !referenced.contains(&path) {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+/// A snapshot taken over several ticks, a few chunks at a time
+pub struct PendingSnapshot {
+    last_snapshot: Manifest,
+    manifest: Manifest,
+    /// Regions whose chunks still have to be added to `chunks`, when checking
+    /// all persisted chunks
+    regions: Vec<Vec2<i32>>,
+    /// Chunks still to be written to the snapshot
+    chunks: Vec<Vec2<i32>>,
+}
+
+impl TerrainPersistence {
+    /// Write all modified chunks and save a snapshot of them, unless nothing
+    /// changed since the last snapshot. Returns the name of the new snapshot.
+    ///
+    /// This finishes a snapshot being taken by [`TerrainPersistence::maintain`]
+    /// at once.
+    pub fn snapshot(&mut self) -> Result<Option<String>, SnapshotError> {
+        let mut pending = match self.pending_snapshot.take() {
+            Some(pending) => pending,
+            None => self.start_snapshot()?,
+        };
+        pending.chunks.extend(self.unsnapshotted.drain());
+        self.continue_snapshot(&mut pending, usize::MAX)?;
+        self.finish_snapshot(pending)
+    }
+
+    /// Start a snapshot of the chunks modified since the last one, to be
+    /// written with [`TerrainPersistence::continue_snapshot`].
+    pub(super) fn start_snapshot(&mut self) -> Result<PendingSnapshot, SnapshotError> {
+        let mut regions = Vec::new();
+        let last_snapshot = match self.last_snapshot.take() {
+            Some(manifest) => manifest,
+            None => {
+                // Chunks might have been modified after the last snapshot of a
+                // previous run, so check all of them once
+                let latest = SnapshotStore::new(&self.path)
+                    .latest()?
+                    .map(|(_, manifest)| manifest)
+                    .unwrap_or_default();
+                let (persisted_regions, legacy_chunks) = self.persisted_files();
+                regions = persisted_regions;
+                self.unsnapshotted.extend(legacy_chunks);
+                self.unsnapshotted.extend(latest.chunks.keys().copied());
+                latest
+            },
+        };
+        // Write the chunks of a region one after the other
+        let mut chunks = self.unsnapshotted.drain().collect::<Vec<_>>();
+        chunks.sort_by_key(|key| {
+            let region_key = region_key(*key);
+            (region_key.x, region_key.y)
+        });
+        Ok(PendingSnapshot {
+            manifest: last_snapshot.clone(),
+            last_snapshot,
+            regions,
+            chunks,
+        })
+    }
+
+    /// Write up to `max_chunks` chunks of a pending snapshot, listing the
+    /// chunks of a region counts as one. Returns whether all of them are
+    /// written.
+    ///
+    /// After an error the snapshot has to be started again, which checks all
+    /// persisted chunks.
+    pub(super) fn continue_snapshot(
+        &mut self,
+        pending: &mut PendingSnapshot,
+        max_chunks: usize,
+    ) -> Result<bool, SnapshotError> {
+        let store = SnapshotStore::new(&self.path);
+        let mut result = Ok(());
+        for _ in 0..max_chunks {
+            if let Some(region_key) = pending.regions.pop() {
+                pending.chunks.extend(
+                    Self::region(&mut self.regions, &self.path, region_key).chunk_keys(region_key),
+                );
+                continue;
+            }
+            let key = match pending.chunks.pop() {
+                Some(key) => key,
+                None => break,
+            };
+            result = self
+                .stored_entry(key)
+                .map_err(Into::into)
+                .and_then(|entry| {
+                    match entry {
+                        Some(bytes) => {
+                            pending
+                                .manifest
+                                .chunks
+                                .insert(key, store.store_object(&bytes)?);
+                        },
+                        None => {
+                            pending.manifest.chunks.remove(&key);
+                        },
+                    }
+                    Ok(())
+                });
+            if result.is_err() {
+                break;
+            }
+        }
+        let done = pending.regions.is_empty() && pending.chunks.is_empty();
+        // Regions stay loaded while their chunks are written, rather than being
+        // read again on each tick
+        if done || result.is_err() {
+            self.evict_regions();
+        }
+        result.map(|()| done)
+    }
+
+    /// Save a pending snapshot whose chunks are all written, unless nothing
+    /// changed since the last snapshot. Returns the name of the new snapshot.
+    pub(super) fn finish_snapshot(
+        &mut self,
+        pending: PendingSnapshot,
+    ) -> Result<Option<String>, SnapshotError> {
+        let PendingSnapshot {
+            last_snapshot,
+            manifest,
+            ..
+        } = pending;
+        if manifest == last_snapshot {
+            self.last_snapshot = Some(manifest);
+            debug!("No terrain changes since the last snapshot");
+            return Ok(None);
+        }
+        let name = SnapshotStore::new(&self.path).save(&manifest)?;
+        self.last_snapshot = Some(manifest);
+        info!("Saved terrain snapshot {}", name);
+        Ok(Some(name))
+    }
+
+    /// Restore the blocks within `area` to how they were in the snapshot
+    /// `name`.
+    ///
+    /// Returns the blocks which changed: `Some` for blocks modified in the
+    /// snapshot, `None` for blocks which weren't modified at that time and need
+    /// to be regenerated.
+    pub fn restore(
+        &mut self,
+        name: &str,
+        area: Aabb<i32>,
+    ) -> Result<Vec<(Vec3<i32>, Option<Block>)>, SnapshotError> {
+        let area = area.made_valid();
+        let chunk_size = TerrainChunk::RECT_SIZE.map(|e| e as i32);
+        let min_key = area.min.xy().map2(chunk_size, |e, sz| e.div_euclid(sz));
+        let max_key = area.max.xy().map2(chunk_size, |e, sz| e.div_euclid(sz));
+        let chunks = (max_key - min_key).map(|e| e as u64 + 1).product();
+        if chunks > MAX_RESTORE_CHUNKS {
+            return Err(SnapshotError::AreaTooLarge(chunks));
+        }
+        let store = SnapshotStore::new(&self.path);
+        let manifest = store.manifest(name)?;
+
+        let mut changes = Vec::new();
+        for x in min_key.x..=max_key.x {
+            for y in min_key.y..=max_key.y {
+                let key = Vec2::new(x, y);
+                let offset = Vec3::from(key * chunk_size);
+                let contains = |rpos: &Vec3<i32>| area.contains_point(offset + *rpos);
+                let snapshot = store.chunk(&manifest, key)?;
+                let chunk = self.load_chunk(key);
+
+                let removed = chunk
+                    .blocks
+                    .keys()
+                    .filter(|rpos| contains(rpos) && !snapshot.blocks.contains_key(*rpos))
+                    .copied()
+                    .collect::<Vec<_>>();
+                for rpos in removed {
+                    chunk.blocks.remove(&rpos);
+                    changes.push((offset + rpos, None));
+                }
+                for (rpos, block) in snapshot.blocks() {
+                    if contains(&rpos) && chunk.blocks.insert(rpos, block) != Some(block) {
+                        changes.push((offset + rpos, Some(block)));
+                    }
+                }
+                self.dirty.insert(key);
+                self.unsnapshotted.insert(key);
+            }
+        }
+        if changes.is_empty() {
+            warn!("Restoring snapshot {} didn't change any block", name);
+        }
+        Ok(changes)
+    }
+
+    /// Where to send the blocks regenerated for the `None` changes of
+    /// [`TerrainPersistence::restore`]
+    pub fn regenerated_tx(&self) -> crossbeam_channel::Sender<Vec<(Vec3<i32>, Block)>> {
+        self.regenerated.0.clone()
+    }
+
+    /// The regenerated blocks to set, without those modified since they were
+    /// restored or whose chunk was unloaded
+    pub fn take_regenerated(&self) -> Vec<(Vec3<i32>, Block)> {
+        let chunk_size = TerrainChunk::RECT_SIZE.map(|e| e as i32);
+        self.regenerated
+            .1
+            .try_iter()
+            .flatten()
+            .filter(|(pos, _)| {
+                let key = pos.xy().map2(chunk_size, |e, sz| e.div_euclid(sz));
+                self.chunks.get(&key).map_or(false, |chunk| {
+                    !chunk
+                        .blocks
+                        .contains_key(&(*pos - Vec3::from(key * chunk_size)))
+                })
+            })
+            .collect()
+    }
+}
+
+/// Manifests use the same versioning as chunks, see the [`super::version`]
+/// module for how to add a new version.
+mod version {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use std::any::{type_name, Any};
+
+    pub type Current = V1;
+
+    type LoadManifestFn = fn(&[u8]) -> Result<Manifest, (&'static str, bincode::Error)>;
+    fn loaders() -> &'static [LoadManifestFn] { &[load_raw::<V1>] }
+
+    impl From<&Manifest> for Current {
+        fn from(manifest: &Manifest) -> Self {
+            let mut chunks = manifest
+                .chunks
+                .iter()
+                .map(|(key, hash)| (key.x, key.y, *hash))
+                .collect::<Vec<_>>();
+            chunks.sort_unstable();
+            Self {
+                version: version_magic(1),
+                chunks,
+            }
+        }
+    }
+
+    /// Version 1 of the raw manifest format.
+    #[derive(Serialize, Deserialize)]
+    pub struct V1 {
+        #[serde(deserialize_with = "version::<_, 1>")]
+        pub version: u64,
+        pub chunks: Vec<(i32, i32, ChunkHash)>,
+    }
+
+    impl From<V1> for Manifest {
+        fn from(v1: V1) -> Self {
+            Self {
+                chunks: v1
+                    .chunks
+                    .into_iter()
+                    .map(|(x, y, hash)| (Vec2::new(x, y), hash))
+                    .collect(),
+            }
+        }
+    }
+
+    fn version_magic(n: u16) -> u64 { (n as u64) | (0x5E1A_9D0C_ADE5 << 16) }
+
+    fn version<'de, D: serde::Deserializer<'de>, const V: u16>(de: D) -> Result<u64, D::Error> {
+        u64::deserialize(de).and_then(|x| {
+            if x == version_magic(V) {
+                Ok(x)
+            } else {
+                Err(serde::de::Error::invalid_value(
+                    serde::de::Unexpected::Unsigned(x),
+                    &"incorrect magic/version bytes",
+                ))
+            }
+        })
+    }
+
+    fn load_raw<RawManifest: Any + Into<Manifest> + DeserializeOwned>(
+        bytes: &[u8],
+    ) -> Result<Manifest, (&'static str, bincode::Error)> {
+        bincode::deserialize::<RawManifest>(bytes)
+            .map(Into::into)
+            .map_err(|e| (type_name::<RawManifest>(), e))
+    }
+
+    pub fn try_load(bytes: &[u8]) -> Option<Manifest> {
+        loaders().iter().find_map(|load_raw| match load_raw(bytes) {
+            Ok(manifest) => Some(manifest),
+            Err((raw_name, e)) => {
+                debug!(
+                    "Attempt to load manifest with raw format `{}` failed: {:?}",
+                    raw_name, e
+                );
+                None
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain_persistence::test_dir;
+    use common::terrain::BlockKind;
+
+    fn block(kind: BlockKind) -> Block { Block::new(kind, Rgb::new(1, 2, 3)) }
+
+    fn manifest(chunks: &[((i32, i32), ChunkHash)]) -> Manifest {
+        Manifest {
+            chunks: chunks
+                .iter()
+                .map(|((x, y), hash)| (Vec2::new(*x, *y), *hash))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn manifest_diff() {
+        let old = manifest(&[((0, 0), [1; 32]), ((1, 0), [2; 32]), ((2, 0), [3; 32])]);
+        let new = manifest(&[((0, 0), [1; 32]), ((1, 0), [4; 32]), ((3, 0), [5; 32])]);
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, vec![Vec2::new(3, 0)]);
+        assert_eq!(diff.removed, vec![Vec2::new(2, 0)]);
+        assert_eq!(diff.changed, vec![Vec2::new(1, 0)]);
+
+        let diff = old.diff(&old);
+        assert!(diff.added.is_empty() && diff.removed.is_empty() && diff.changed.is_empty());
+    }
+
+    #[test]
+    fn prune_keeps_referenced_objects() {
+        let path = test_dir("snapshot-prune");
+        let store = SnapshotStore::new(&path);
+        let a = store.store_object(b"a").unwrap();
+        let b = store.store_object(b"b").unwrap();
+        let c = store.store_object(b"c").unwrap();
+        let first = store.save(&manifest(&[((0, 0), a), ((1, 0), b)])).unwrap();
+        let second = store.save(&manifest(&[((0, 0), a), ((1, 0), c)])).unwrap();
+
+        assert_eq!(store.prune(1).unwrap(), vec![first]);
+        assert_eq!(store.list().unwrap(), vec![second.clone()]);
+        assert!(store.object_path(&a).exists());
+        assert!(!store.object_path(&b).exists());
+        assert!(store.object_path(&c).exists());
+
+        // A running server might be about to reference the objects
+        let lock = TerrainLock::acquire(&path).unwrap();
+        assert!(store.prune(0).is_err());
+        drop(lock);
+        assert_eq!(store.prune(0).unwrap(), vec![second]);
+        assert!(!store.object_path(&a).exists());
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn restore_within_area() {
+        let path = test_dir("snapshot-restore");
+        let mut terrain =
+            TerrainPersistence::open(path.clone(), TerrainLock::acquire(&path).unwrap());
+        terrain.persist_block(Vec3::new(1, 1, 1), block(BlockKind::Rock));
+        terrain.persist_block(Vec3::new(40, 1, 1), block(BlockKind::Wood));
+        let name = terrain.snapshot().unwrap().unwrap();
+        assert_eq!(terrain.snapshot().unwrap(), None);
+
+        terrain.persist_block(Vec3::new(1, 1, 1), block(BlockKind::Misc));
+        terrain.persist_block(Vec3::new(2, 1, 1), block(BlockKind::Misc));
+        terrain.persist_block(Vec3::new(40, 1, 1), block(BlockKind::Misc));
+        let mut changes = terrain
+            .restore(&name, Aabb {
+                min: Vec3::new(0, 0, 0),
+                max: Vec3::new(31, 31, 10),
+            })
+            .unwrap();
+        changes.sort_by_key(|(pos, _)| (pos.x, pos.y, pos.z));
+        // Blocks which weren't modified in the snapshot have to be regenerated
+        assert_eq!(changes, vec![
+            (Vec3::new(1, 1, 1), Some(block(BlockKind::Rock))),
+            (Vec3::new(2, 1, 1), None),
+        ]);
+        assert_eq!(
+            terrain.load_chunk(Vec2::new(1, 0)).blocks[&Vec3::new(8, 1, 1)],
+            block(BlockKind::Misc)
+        );
+
+        let too_large = Aabb {
+            min: Vec3::new(0, 0, 0),
+            max: Vec3::new(32 * 17, 32 * 16 - 1, 0),
+        };
+        assert!(matches!(
+            terrain.restore(&name, too_large),
+            Err(SnapshotError::AreaTooLarge(_))
+        ));
+        assert!(matches!(
+            terrain.restore("missing", Aabb::new_empty(Vec3::zero())),
+            Err(SnapshotError::InvalidSnapshot(_))
+        ));
+        drop(terrain);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -137,6 +137,16 @@ impl<'a> Crafting<'a> {
             common: widget::CommonBuilder::default(),
         }
     }
+
+    fn recipe_name(&self, recipe: &Recipe) -> String {
+        if recipe.repair {
+            self.localized_strings
+                .get("hud.crafting.repair")
+                .replace("{item}", recipe.output.0.name())
+        } else {
+            recipe.output.0.name().to_owned()
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, PartialEq)]
@@ -556,7 +566,8 @@ impl<'a> Widget for Crafting<'a> {
             .press_image(self.imgs.selection_press)
             .image_color(color::rgba(1.0, 0.82, 0.27, 1.0));
 
-            let text = Text::new(recipe.output.0.name())
+            let output_name = self.recipe_name(recipe);
+            let text = Text::new(&output_name)
                 .color(if is_craftable {
                     TEXT_COLOR
                 } else {
@@ -659,7 +670,7 @@ impl<'a> Widget for Crafting<'a> {
             .and_then(|rn| self.client.recipe_book().get(rn.as_str()).map(|r| (rn, r)))
         {
             // Title
-            Text::new(recipe.output.0.name())
+            Text::new(&self.recipe_name(recipe))
                 .mid_top_with_margin_on(state.ids.align_ing, -22.0)
                 .font_id(self.fonts.cyri.conrod_id)
                 .font_size(self.fonts.cyri.scale(14))
@@ -21,6 +21,7 @@ mod settings_window;
 mod skillbar;
 mod slots;
 mod social;
+mod storage;
 mod trade;
 pub mod util;
 
@@ -50,6 +51,7 @@ use serde::{Deserialize, Serialize};
 use settings_window::{SettingsTab, SettingsWindow};
 use skillbar::Skillbar;
 use social::Social;
+use storage::Storage;
 use trade::Trade;
 
 use crate::{
@@ -280,6 +282,7 @@ widget_ids! {
         prompt_dialog,
         bag,
         trade,
+        storage,
         social,
         quest,
         diary,
@@ -513,6 +516,17 @@ pub enum Event {
     SortInventory,
     ChangeHotbarState(Box<HotbarState>),
     TradeAction(TradeAction),
+    StorageSwap {
+        slot_a: comp::StorageSlot,
+        slot_b: comp::StorageSlot,
+    },
+    StorageSplitSwap {
+        slot_a: comp::StorageSlot,
+        slot_b: comp::StorageSlot,
+    },
+    SetStorageAccess(comp::StorageAccess),
+    PickUpStorage,
+    CloseStorage,
     Ability(usize, bool),
     Logout,
     Quit,
@@ -639,6 +653,7 @@ pub struct Show {
     bag: bool,
     bag_inv: bool,
     trade: bool,
+    storage: bool,
     social: bool,
     diary: bool,
     group: bool,
@@ -687,6 +702,15 @@ impl Show {
         }
     }
 
+    fn storage(&mut self, open: bool) {
+        if !self.esc_menu {
+            self.bag = open;
+            self.storage = open;
+            self.map = false;
+            self.want_grab = !open;
+        }
+    }
+
     fn map(&mut self, open: bool) {
         if !self.esc_menu {
             self.map = open;
@@ -1024,6 +1048,7 @@ impl Hud {
                 bag: false,
                 bag_inv: false,
                 trade: false,
+                storage: false,
                 esc_menu: false,
                 open_windows: Windows::None,
                 map: false,
@@ -1135,6 +1160,10 @@ impl Hud {
                 self.show.toggle_trade();
             }
 
+            if client.storage().is_some() != self.show.storage {
+                self.show.storage(client.storage().is_some());
+            }
+
             //self.input = client.read_storage::<comp::ControllerInputs>();
             if let Some(health) = healths.get(me) {
                 // Hurt Frame
@@ -2795,6 +2824,34 @@ impl Hud {
                 None => {},
             }
         }
+        // Storage window
+        if let (true, Some(storage)) = (self.show.storage, client.storage()) {
+            match Storage::new(
+                client,
+                storage,
+                &self.imgs,
+                &self.item_imgs,
+                &self.fonts,
+                &self.rot_imgs,
+                item_tooltip_manager,
+                &mut self.slot_manager,
+                i18n,
+                &msm,
+                self.pulse,
+            )
+            .set(self.ids.storage, ui_widgets)
+            {
+                Some(storage::Event::Close) => {
+                    self.show.storage(false);
+                    events.push(Event::CloseStorage);
+                },
+                Some(storage::Event::SetAccess(access)) => {
+                    events.push(Event::SetStorageAccess(access))
+                },
+                Some(storage::Event::PickUp) => events.push(Event::PickUpStorage),
+                None => {},
+            }
+        }
 
         // Buffs
         let ecs = client.state().ecs();
@@ -3233,6 +3290,17 @@ impl Hud {
                 Equip(e) => Some(Slot::Equip(e)),
                 Hotbar(_) => None,
                 Trade(_) => None,
+                Storage(_) => None,
+            };
+            // Moves involving the open storage container are handled separately
+            // from the other slots, as the container isn't part of any entity
+            let storage_open = client.storage().is_some();
+            let to_storage_slot = |slot_kind| match slot_kind {
+                Inventory(InventorySlot {
+                    slot, ours: true, ..
+                }) => Some(comp::StorageSlot::Inventory(slot)),
+                Storage(s) => Some(comp::StorageSlot::Storage(s.slot)),
+                _ => None,
             };
             match event {
                 slot::Event::Dragged(a, b) => {
@@ -3243,6 +3311,10 @@ impl Hud {
                             slot_b: b,
                             bypass_dialog: false,
                         });
+                    } else if let (true, Some(slot_a), Some(slot_b)) =
+                        (storage_open, to_storage_slot(a), to_storage_slot(b))
+                    {
+                        events.push(Event::StorageSwap { slot_a, slot_b });
                     } else if let (
                         Inventory(InventorySlot {
                             slot, ours: true, ..
@@ -3315,6 +3387,10 @@ impl Hud {
                             slot_b: b,
                             bypass_dialog: false,
                         });
+                    } else if let (true, Some(slot_a), Some(slot_b)) =
+                        (storage_open, to_storage_slot(a), to_storage_slot(b))
+                    {
+                        events.push(Event::StorageSplitSwap { slot_a, slot_b });
                     } else if let (Inventory(i), Hotbar(h)) = (a, b) {
                         self.hotbar.add_inventory_link(h, i.slot);
                         events.push(Event::ChangeHotbarState(Box::new(self.hotbar.to_owned())));
@@ -3682,6 +3758,9 @@ impl Hud {
                     self.ui.focus_widget(None);
                 } else if self.show.trade {
                     self.events.push(Event::TradeAction(TradeAction::Decline));
+                } else if self.show.storage {
+                    self.show.storage(false);
+                    self.events.push(Event::CloseStorage);
                 } else {
                     // Close windows on esc
                     self.show.toggle_windows(global_state);
@@ -4088,6 +4167,7 @@ pub fn get_sprite_desc(sprite: SpriteKind, localized_strings: &Localization) ->
         SpriteKind::SpinningWheel => "hud.crafting.spinning_wheel",
         SpriteKind::TanningRack => "hud.crafting.tanning_rack",
         SpriteKind::DismantlingBench => "hud.crafting.salvaging_station",
+        SpriteKind::StorageChest => "hud.storage.storage_chest",
         sprite => return Some(Cow::Owned(format!("{:?}", sprite))),
     };
     Some(Cow::Borrowed(localized_strings.get(i18n_key)))
@@ -130,6 +130,15 @@ impl<'a> Widget for Popup<'a> {
                         s.infos.push_back(text.to_string());
                     });
                 },
+                Notification::ItemBroken(name) => {
+                    state.update(|s| {
+                        if s.infos.is_empty() {
+                            s.last_info_update = Instant::now();
+                        }
+                        let text = self.i18n.get("hud.item_broken").replace("{item}", name);
+                        s.infos.push_back(text);
+                    });
+                },
             }
         }
 
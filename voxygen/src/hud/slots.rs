@@ -20,6 +20,7 @@ pub enum SlotKind {
     Equip(EquipSlot),
     Hotbar(HotbarSlot),
     Trade(TradeSlot),
+    Storage(StorageSlot),
     /* Spellbook(SpellbookSlot), TODO */
 }
 
@@ -107,6 +108,32 @@ impl SlotKey<Inventory, ItemImgs> for TradeSlot {
     }
 }
 
+/// A slot of the storage container the player has open, whose contents come
+/// from the inventory of the container rather than of an entity
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StorageSlot {
+    pub slot: InvSlotId,
+}
+
+impl SlotKey<Inventory, ItemImgs> for StorageSlot {
+    type ImageKey = ItemKey;
+
+    fn image_key(&self, source: &Inventory) -> Option<(Self::ImageKey, Option<Color>)> {
+        source.get(self.slot).map(|i| (i.into(), None))
+    }
+
+    fn amount(&self, source: &Inventory) -> Option<u32> {
+        source
+            .get(self.slot)
+            .map(|item| item.amount())
+            .filter(|amount| *amount > 1)
+    }
+
+    fn image_ids(key: &Self::ImageKey, source: &ItemImgs) -> Vec<image::Id> {
+        source.img_ids_or_not_found_img(key.clone())
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub enum HotbarImage {
     Item(ItemKey),
@@ -202,5 +229,8 @@ impl From<HotbarSlot> for SlotKind {
 impl From<TradeSlot> for SlotKind {
     fn from(trade: TradeSlot) -> Self { Self::Trade(trade) }
 }
+impl From<StorageSlot> for SlotKind {
+    fn from(storage: StorageSlot) -> Self { Self::Storage(storage) }
+}
 
 impl SumSlot for SlotKind {}
@@ -0,0 +1,373 @@
+use conrod_core::{
+    color,
+    position::Relative,
+    widget::{self, Button, Image, Rectangle, State as ConrodState, Text},
+    widget_ids, Color, Colorable, Labelable, Positionable, Sizeable, UiCell, Widget, WidgetCommon,
+};
+use vek::*;
+
+use client::Client;
+use common::comp::{
+    inventory::item::{ItemDesc, MaterialStatManifest, Quality},
+    storage::STORAGE_SLOTS,
+    StorageAccess, StorageView,
+};
+use i18n::Localization;
+
+use crate::ui::{
+    fonts::Fonts,
+    slot::{ContentSize, SlotMaker},
+    ImageFrame, ItemTooltip, ItemTooltipManager, ItemTooltipable,
+};
+
+use super::{
+    img_ids::{Imgs, ImgsRot},
+    item_imgs::ItemImgs,
+    slots::{SlotManager, StorageSlot},
+    TEXT_COLOR, TEXT_GRAY_COLOR, UI_HIGHLIGHT_0, UI_MAIN,
+};
+
+/// Number of slots per row of the storage grid
+const COLUMNS: usize = 9;
+
+pub enum Event {
+    Close,
+    SetAccess(StorageAccess),
+    PickUp,
+}
+
+widget_ids! {
+    pub struct Ids {
+        storage_close,
+        bg,
+        bg_frame,
+        storage_title_bg,
+        storage_title,
+        inv_alignment,
+        inv_slots[],
+        access_text,
+        access_buttons[],
+        pick_up_button,
+        pick_up_hint,
+    }
+}
+
+pub struct State {
+    ids: Ids,
+}
+
+#[derive(WidgetCommon)]
+pub struct Storage<'a> {
+    client: &'a Client,
+    storage: &'a StorageView,
+    imgs: &'a Imgs,
+    item_imgs: &'a ItemImgs,
+    fonts: &'a Fonts,
+    rot_imgs: &'a ImgsRot,
+    item_tooltip_manager: &'a mut ItemTooltipManager,
+    #[conrod(common_builder)]
+    common: widget::CommonBuilder,
+    slot_manager: &'a mut SlotManager,
+    localized_strings: &'a Localization,
+    msm: &'a MaterialStatManifest,
+    pulse: f32,
+}
+
+impl<'a> Storage<'a> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        client: &'a Client,
+        storage: &'a StorageView,
+        imgs: &'a Imgs,
+        item_imgs: &'a ItemImgs,
+        fonts: &'a Fonts,
+        rot_imgs: &'a ImgsRot,
+        item_tooltip_manager: &'a mut ItemTooltipManager,
+        slot_manager: &'a mut SlotManager,
+        localized_strings: &'a Localization,
+        msm: &'a MaterialStatManifest,
+        pulse: f32,
+    ) -> Self {
+        Self {
+            client,
+            storage,
+            imgs,
+            item_imgs,
+            fonts,
+            rot_imgs,
+            item_tooltip_manager,
+            common: widget::CommonBuilder::default(),
+            slot_manager,
+            localized_strings,
+            msm,
+            pulse,
+        }
+    }
+}
+
+impl<'a> Storage<'a> {
+    fn background(&mut self, state: &mut ConrodState<'_, State>, ui: &mut UiCell<'_>) {
+        Image::new(self.imgs.inv_middle_bg_bag)
+            .w_h(424.0, 300.0)
+            .color(Some(UI_MAIN))
+            .mid_bottom_with_margin_on(ui.window, 295.0)
+            .set(state.ids.bg, ui);
+        Image::new(self.imgs.inv_middle_frame)
+            .w_h(424.0, 300.0)
+            .middle_of(state.ids.bg)
+            .color(Some(UI_HIGHLIGHT_0))
+            .set(state.ids.bg_frame, ui);
+    }
+
+    fn title(&mut self, state: &mut ConrodState<'_, State>, ui: &mut UiCell<'_>) {
+        Text::new(self.localized_strings.get("hud.storage.storage_chest"))
+            .mid_top_with_margin_on(state.ids.bg_frame, 9.0)
+            .font_id(self.fonts.cyri.conrod_id)
+            .font_size(self.fonts.cyri.scale(20))
+            .color(Color::Rgba(0.0, 0.0, 0.0, 1.0))
+            .set(state.ids.storage_title_bg, ui);
+        Text::new(self.localized_strings.get("hud.storage.storage_chest"))
+            .top_left_with_margins_on(state.ids.storage_title_bg, 2.0, 2.0)
+            .font_id(self.fonts.cyri.conrod_id)
+            .font_size(self.fonts.cyri.scale(20))
+            .color(TEXT_COLOR)
+            .set(state.ids.storage_title, ui);
+    }
+
+    fn slots(&mut self, state: &mut ConrodState<'_, State>, ui: &mut UiCell<'_>) {
+        // Tooltips
+        let item_tooltip = ItemTooltip::new(
+            {
+                // Edge images [t, b, r, l]
+                // Corner images [tr, tl, br, bl]
+                let edge = &self.rot_imgs.tt_side;
+                let corner = &self.rot_imgs.tt_corner;
+                ImageFrame::new(
+                    [edge.cw180, edge.none, edge.cw270, edge.cw90],
+                    [corner.none, corner.cw270, corner.cw90, corner.cw180],
+                    Color::Rgba(0.08, 0.07, 0.04, 1.0),
+                    5.0,
+                )
+            },
+            self.client,
+            self.imgs,
+            self.item_imgs,
+            self.pulse,
+            self.msm,
+            self.localized_strings,
+        )
+        .title_font_size(self.fonts.cyri.scale(20))
+        .parent(ui.window)
+        .desc_font_size(self.fonts.cyri.scale(12))
+        .font_id(self.fonts.cyri.conrod_id)
+        .desc_text_color(TEXT_COLOR);
+
+        // Alignment for Grid
+        let rows = (STORAGE_SLOTS + COLUMNS - 1) / COLUMNS;
+        Rectangle::fill_with(
+            [COLUMNS as f64 * 40.0, rows as f64 * 40.0],
+            color::TRANSPARENT,
+        )
+        .mid_top_with_margin_on(state.ids.bg, 60.0)
+        .set(state.ids.inv_alignment, ui);
+
+        let inventory = &self.storage.inventory;
+        let mut slot_maker = SlotMaker {
+            empty_slot: self.imgs.inv_slot,
+            filled_slot: self.imgs.inv_slot,
+            selected_slot: self.imgs.inv_slot_sel,
+            background_color: Some(UI_MAIN),
+            content_size: ContentSize {
+                width_height_ratio: 1.0,
+                max_fraction: 0.75,
+            },
+            selected_content_scale: 1.067,
+            amount_font: self.fonts.cyri.conrod_id,
+            amount_margins: Vec2::new(-4.0, 0.0),
+            amount_font_size: self.fonts.cyri.scale(12),
+            amount_text_color: TEXT_COLOR,
+            content_source: inventory,
+            image_source: self.item_imgs,
+            slot_manager: Some(self.slot_manager),
+            pulse: self.pulse,
+        };
+
+        let slots = inventory.slots_with_id().collect::<Vec<_>>();
+        if state.ids.inv_slots.len() < slots.len() {
+            state.update(|s| {
+                s.ids
+                    .inv_slots
+                    .resize(slots.len(), &mut ui.widget_id_generator());
+            });
+        }
+
+        for (i, (slot, item)) in slots.into_iter().enumerate() {
+            let x = i % COLUMNS;
+            let y = i / COLUMNS;
+
+            let slot_widget = slot_maker
+                .fabricate(StorageSlot { slot }, [40.0; 2])
+                .top_left_with_margins_on(
+                    state.ids.inv_alignment,
+                    y as f64 * 40.0,
+                    x as f64 * 40.0,
+                );
+            let slot_id = state.ids.inv_slots[i];
+            if let Some(item) = item {
+                let quality_col_img = match item.quality() {
+                    Quality::Low => self.imgs.inv_slot_grey,
+                    Quality::Common => self.imgs.inv_slot_common,
+                    Quality::Moderate => self.imgs.inv_slot_green,
+                    Quality::High => self.imgs.inv_slot_blue,
+                    Quality::Epic => self.imgs.inv_slot_purple,
+                    Quality::Legendary => self.imgs.inv_slot_gold,
+                    Quality::Artifact => self.imgs.inv_slot_orange,
+                    _ => self.imgs.inv_slot_red,
+                };
+
+                slot_widget
+                    .filled_slot(quality_col_img)
+                    .with_item_tooltip(
+                        self.item_tooltip_manager,
+                        core::iter::once(item as &dyn ItemDesc),
+                        &None,
+                        &item_tooltip,
+                    )
+                    .set(slot_id, ui);
+            } else {
+                slot_widget.set(slot_id, ui);
+            }
+        }
+    }
+
+    /// Lets the owner choose who else may open the container, and pick it up
+    /// once it is empty
+    fn owner_buttons(
+        &mut self,
+        state: &mut ConrodState<'_, State>,
+        ui: &mut UiCell<'_>,
+    ) -> Option<Event> {
+        let mut event = None;
+        const ACCESS: [(StorageAccess, &str); 3] = [
+            (StorageAccess::Owner, "hud.storage.access.owner"),
+            (StorageAccess::Group, "hud.storage.access.group"),
+            (StorageAccess::Public, "hud.storage.access.public"),
+        ];
+
+        Text::new(self.localized_strings.get("hud.storage.access"))
+            .down_from(state.ids.inv_alignment, 20.0)
+            .align_left_of(state.ids.inv_alignment)
+            .font_id(self.fonts.cyri.conrod_id)
+            .font_size(self.fonts.cyri.scale(16))
+            .color(TEXT_COLOR)
+            .set(state.ids.access_text, ui);
+
+        if state.ids.access_buttons.len() < ACCESS.len() {
+            state.update(|s| {
+                s.ids
+                    .access_buttons
+                    .resize(ACCESS.len(), &mut ui.widget_id_generator());
+            });
+        }
+        for (i, (access, key)) in ACCESS.iter().enumerate() {
+            let button = Button::image(self.imgs.button)
+                .w_h(31.0 * 3.5, 12.0 * 2.0)
+                .hover_image(self.imgs.button_hover)
+                .press_image(self.imgs.button_press)
+                .label(self.localized_strings.get(key))
+                .label_font_size(self.fonts.cyri.scale(14))
+                .label_color(if *access == self.storage.access {
+                    TEXT_COLOR
+                } else {
+                    TEXT_GRAY_COLOR
+                })
+                .label_font_id(self.fonts.cyri.conrod_id)
+                .label_y(Relative::Scalar(2.0));
+            let button = if i == 0 {
+                button.right_from(state.ids.access_text, 10.0)
+            } else {
+                button.right_from(state.ids.access_buttons[i - 1], 5.0)
+            };
+            if button.set(state.ids.access_buttons[i], ui).was_clicked()
+                && *access != self.storage.access
+            {
+                event = Some(Event::SetAccess(*access));
+            }
+        }
+
+        if self.storage.inventory.populated_slots() == 0 {
+            if Button::image(self.imgs.button)
+                .w_h(31.0 * 5.0, 12.0 * 2.0)
+                .hover_image(self.imgs.button_hover)
+                .press_image(self.imgs.button_press)
+                .mid_bottom_with_margin_on(state.ids.bg, 30.0)
+                .label(self.localized_strings.get("hud.storage.pick_up"))
+                .label_font_size(self.fonts.cyri.scale(14))
+                .label_color(TEXT_COLOR)
+                .label_font_id(self.fonts.cyri.conrod_id)
+                .label_y(Relative::Scalar(2.0))
+                .set(state.ids.pick_up_button, ui)
+                .was_clicked()
+            {
+                event = Some(Event::PickUp);
+            }
+        } else {
+            Text::new(self.localized_strings.get("hud.storage.pick_up_hint"))
+                .mid_bottom_with_margin_on(state.ids.bg, 34.0)
+                .font_id(self.fonts.cyri.conrod_id)
+                .font_size(self.fonts.cyri.scale(14))
+                .color(TEXT_GRAY_COLOR)
+                .set(state.ids.pick_up_hint, ui);
+        }
+
+        event
+    }
+
+    fn close_button(
+        &mut self,
+        state: &mut ConrodState<'_, State>,
+        ui: &mut UiCell<'_>,
+    ) -> Option<Event> {
+        if Button::image(self.imgs.close_btn)
+            .w_h(24.0, 25.0)
+            .hover_image(self.imgs.close_btn_hover)
+            .press_image(self.imgs.close_btn_press)
+            .top_right_with_margins_on(state.ids.bg, 0.0, 0.0)
+            .set(state.ids.storage_close, ui)
+            .was_clicked()
+        {
+            Some(Event::Close)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> Widget for Storage<'a> {
+    type Event = Option<Event>;
+    type State = State;
+    type Style = ();
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        State {
+            ids: Ids::new(id_gen),
+        }
+    }
+
+    fn style(&self) -> Self::Style {}
+
+    fn update(mut self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        common_base::prof_span!("Storage::update");
+        let widget::UpdateArgs { state, ui, .. } = args;
+
+        self.background(state, ui);
+        self.title(state, ui);
+        self.slots(state, ui);
+
+        let mut event = None;
+        if self.storage.is_owner {
+            event = self.owner_buttons(state, ui);
+        }
+        self.close_button(state, ui).or(event)
+    }
+}
@@ -9,6 +9,7 @@ use vek::*;
 pub enum Interaction {
     Collect,
     Craft(CraftingTab),
+    Storage,
 }
 
 #[derive(Default)]
@@ -139,6 +140,9 @@ impl BlocksOfInterest {
                         fires.push(pos);
                         interactables.push((pos, Interaction::Craft(CraftingTab::Dismantle)))
                     },
+                    Some(SpriteKind::StorageChest) => {
+                        interactables.push((pos, Interaction::Storage))
+                    },
                     _ => {},
                 },
             }
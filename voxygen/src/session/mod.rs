@@ -717,6 +717,9 @@ impl PlayState for SessionState {
                                                             block.get_sprite().map(|s| (pos, s)),
                                                         )
                                                     },
+                                                    Some(Interaction::Storage) => {
+                                                        client.open_storage(pos);
+                                                    },
                                                     _ => {},
                                                 }
                                             },
@@ -1367,6 +1370,23 @@ impl PlayState for SessionState {
                     HudEvent::TradeAction(action) => {
                         self.client.borrow_mut().perform_trade_action(action);
                     },
+                    HudEvent::StorageSwap { slot_a, slot_b } => {
+                        self.client.borrow_mut().storage_swap_slots(slot_a, slot_b);
+                    },
+                    HudEvent::StorageSplitSwap { slot_a, slot_b } => {
+                        self.client
+                            .borrow_mut()
+                            .storage_split_swap_slots(slot_a, slot_b);
+                    },
+                    HudEvent::SetStorageAccess(access) => {
+                        self.client.borrow_mut().set_storage_access(access);
+                    },
+                    HudEvent::PickUpStorage => {
+                        self.client.borrow_mut().pick_up_storage();
+                    },
+                    HudEvent::CloseStorage => {
+                        self.client.borrow_mut().close_storage();
+                    },
                     HudEvent::Ability(i, state) => {
                         self.client.borrow_mut().handle_input(
                             InputKind::Ability(i),
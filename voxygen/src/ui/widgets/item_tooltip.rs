@@ -320,6 +320,7 @@ widget_ids! {
         stats[],
         diff_main_stat,
         diffs[],
+        durability,
         item_frame,
         item_render,
         image_frame,
@@ -1118,6 +1119,50 @@ impl<'a> Widget for ItemTooltip<'a> {
             _ => (),
         }
 
+        let stats_id = if util::stats_count(item) > 0 {
+            state.ids.stats[state.ids.stats.len() - 1]
+        } else {
+            state.ids.item_frame
+        };
+
+        // Durability
+        if let (Some(durability), Some(max_durability)) = (item.durability(), item.max_durability())
+        {
+            let (text, color) = if durability == 0 {
+                (
+                    format!(
+                        "{} : {}",
+                        i18n.get("common.stats.durability"),
+                        i18n.get("common.stats.broken")
+                    ),
+                    conrod_core::color::RED,
+                )
+            } else {
+                (
+                    format!(
+                        "{} : {}/{}",
+                        i18n.get("common.stats.durability"),
+                        durability,
+                        max_durability
+                    ),
+                    text_color,
+                )
+            };
+            widget::Text::new(&text)
+                .x_align_to(state.ids.item_frame, conrod_core::position::Align::Start)
+                .graphics_for(id)
+                .parent(id)
+                .with_style(self.style.desc)
+                .color(color)
+                .down_from(stats_id, V_PAD)
+                .set(state.ids.durability, ui);
+        }
+        let stats_id = if item.max_durability().is_some() {
+            state.ids.durability
+        } else {
+            stats_id
+        };
+
         // Description
         if !desc.is_empty() {
             widget::Text::new(&format!("\"{}\"", &desc))
@@ -1126,14 +1171,7 @@ impl<'a> Widget for ItemTooltip<'a> {
                 .parent(id)
                 .with_style(self.style.desc)
                 .color(conrod_core::color::GREY)
-                .down_from(
-                    if util::stats_count(item) > 0 {
-                        state.ids.stats[state.ids.stats.len() - 1]
-                    } else {
-                        state.ids.item_frame
-                    },
-                    V_PAD,
-                )
+                .down_from(stats_id, V_PAD)
                 .w(text_w)
                 .set(state.ids.desc, ui);
         }
@@ -1151,10 +1189,8 @@ impl<'a> Widget for ItemTooltip<'a> {
                 .down_from(
                     if !desc.is_empty() {
                         state.ids.desc
-                    } else if util::stats_count(item) > 0 {
-                        state.ids.stats[state.ids.stats.len() - 1]
                     } else {
-                        state.ids.item_frame
+                        stats_id
                     },
                     V_PAD,
                 )
@@ -1223,6 +1259,17 @@ impl<'a> Widget for ItemTooltip<'a> {
             0.0
         };
 
+        // Durability
+        let durability_h = if item.max_durability().is_some() {
+            widget::Text::new("placeholder")
+                .with_style(self.style.desc)
+                .get_h(ui)
+                .unwrap_or(0.0)
+                + V_PAD
+        } else {
+            0.0
+        };
+
         // Description
         let desc_h: f64 = if !desc.is_empty() {
             widget::Text::new(&format!("\"{}\"", &desc))
@@ -1256,7 +1303,7 @@ impl<'a> Widget for ItemTooltip<'a> {
             0.0
         };
 
-        let height = title_h + frame_h + stat_h + desc_h + price_h + V_PAD + 5.0; // extra padding to fit frame top padding
+        let height = title_h + frame_h + stat_h + durability_h + desc_h + price_h + V_PAD + 5.0; // extra padding to fit frame top padding
         Dimension::Absolute(height)
     }
 }
@@ -15,10 +15,16 @@ use common::{
     trade::{
         Good,
         Good::{Coin, Transportation},
+        SiteId, SitePrices,
     },
 };
 use lazy_static::lazy_static;
-use std::{cmp::Ordering::Less, convert::TryInto};
+use std::{
+    borrow::BorrowMut,
+    cmp::Ordering::Less,
+    collections::BTreeMap,
+    convert::{TryFrom, TryInto},
+};
 use tracing::{debug, info};
 
 const MONTH: f32 = 30.0;
@@ -262,28 +268,125 @@ fn check_money(index: &mut Index) {
     );
 }
 
-pub fn tick(index: &mut Index, _world: &mut WorldSim, dt: f32, mut vc: vergleich::Context) {
-    let site_ids = index.sites.ids().collect::<Vec<_>>();
-    for site in site_ids {
-        tick_site_economy(index, site, dt, vc.context(&site.id().to_string()));
+pub fn tick(index: &mut Index, _world: &mut WorldSim, dt: f32, vc: vergleich::Context) {
+    let mut economies = index
+        .sites
+        .iter_mut()
+        .filter(|(_, site)| site.do_economic_simulation())
+        .map(|(id, site)| (id, &mut site.economy))
+        .collect::<BTreeMap<_, _>>();
+    tick_economies(&mut economies, &mut index.trade, &mut index.time, dt, vc);
+    //check_money(index);
+}
+
+/// Tick the economies of all sites which do economic simulation, in the order
+/// of their ids
+fn tick_economies<E: BorrowMut<Economy>>(
+    economies: &mut BTreeMap<Id<Site>, E>,
+    trade_info: &mut TradeInformation,
+    time: &mut f32,
+    dt: f32,
+    mut vc: vergleich::Context,
+) {
+    for (&site, economy) in economies.iter_mut() {
+        tick_site_economy(
+            site,
+            economy.borrow_mut(),
+            trade_info,
+            *time,
+            dt,
+            vc.context(&site.id().to_string()),
+        );
     }
     if INTER_SITE_TRADE {
-        for (&site, orders) in index.trade.orders.iter_mut() {
-            let siteinfo = index.sites.get_mut(site);
-            if siteinfo.do_economic_simulation() {
-                // let name: String = siteinfo.name().into();
+        for (&site, orders) in trade_info.orders.iter_mut() {
+            if let Some(economy) = economies.get_mut(&site) {
                 trade_at_site(
                     site,
                     orders,
-                    &mut siteinfo.economy,
-                    &mut index.trade.deliveries,
+                    economy.borrow_mut(),
+                    &mut trade_info.deliveries,
                 );
             }
         }
     }
-    //check_money(index);
 
-    index.time += dt;
+    *time += dt;
+}
+
+/// The economy of the sites while the server runs, continuing the economy
+/// simulated during worldgen. The [`Index`] keeps the economy as it was at the
+/// end of worldgen, as it is shared with chunk generation.
+///
+/// The runtime economy isn't persisted, so every restart of the server starts
+/// over from the economy of worldgen, forgetting the trades with players.
+pub struct RuntimeEconomy {
+    time: f32,
+    /// Days which weren't simulated yet, as the economy ticks in steps of
+    /// `TICK_PERIOD`
+    pending_days: f32,
+    economies: BTreeMap<Id<Site>, Economy>,
+    trade_info: TradeInformation,
+}
+
+impl RuntimeEconomy {
+    pub fn new(index: &Index) -> Self {
+        Self {
+            time: index.time,
+            pending_days: 0.0,
+            economies: index
+                .sites
+                .iter()
+                .filter(|(_, site)| site.do_economic_simulation())
+                .map(|(id, site)| (id, site.economy.clone()))
+                .collect(),
+            trade_info: index.trade.clone(),
+        }
+    }
+
+    /// Simulate `days` more days, ticking once a full `TICK_PERIOD` has passed.
+    pub fn simulate(&mut self, days: f32) {
+        self.pending_days += days;
+        while self.pending_days >= TICK_PERIOD {
+            self.pending_days -= TICK_PERIOD;
+            tick_economies(
+                &mut self.economies,
+                &mut self.trade_info,
+                &mut self.time,
+                TICK_PERIOD,
+                vergleich::Context {},
+            );
+        }
+    }
+
+    pub fn economy(&self, site_id: SiteId) -> Option<&Economy> {
+        self.economies.get(&self.recreate_id(site_id)?)
+    }
+
+    pub fn get_site_prices(&self, site_id: SiteId) -> Option<SitePrices> {
+        self.economy(site_id).map(Economy::get_site_prices)
+    }
+
+    /// Add the goods a site received from a trade with a player to its stocks
+    /// and remove the ones it gave away (negative amounts).
+    pub fn record_trade(&mut self, site_id: SiteId, goods: impl IntoIterator<Item = (Good, f32)>) {
+        let economy = match self
+            .recreate_id(site_id)
+            .and_then(|id| self.economies.get_mut(&id))
+        {
+            Some(economy) => economy,
+            None => return,
+        };
+        for (good, amount) in goods {
+            if let Ok(good) = GoodIndex::try_from(good) {
+                economy.stocks[good] = (economy.stocks[good] + amount).max(0.0);
+            }
+        }
+    }
+
+    fn recreate_id(&self, site_id: SiteId) -> Option<Id<Site>> {
+        self.economies.keys().find(|id| id.id() == site_id).copied()
+    }
 }
 
 lazy_static! {
@@ -297,7 +400,7 @@ lazy_static! {
 // returns wares spent (-) and procured (+)
 // potential_trade: positive = buy, (negative = sell, unused)
 fn plan_trade_for_site(
-    site: &mut Site,
+    economy: &mut Economy,
     site_id: &Id<Site>,
     transportation_capacity: f32,
     external_orders: &mut DHashMap<Id<Site>, Vec<TradeOrder>>,
@@ -305,7 +408,7 @@ fn plan_trade_for_site(
 ) -> GoodMap<f32> {
     // TODO: Do we have some latency of information here (using last years
     // capacity?)
-    //let total_transport_capacity = site.economy.stocks[Transportation];
+    //let total_transport_capacity = economy.stocks[Transportation];
     // TODO: We don't count the capacity per site, but globally (so there might be
     // some imbalance in dispatch vs collection across sites (e.g. more dispatch
     // than collection at one while more collection than dispatch at another))
@@ -317,29 +420,25 @@ fn plan_trade_for_site(
     let mut result = GoodMap::default();
     const MIN_SELL_PRICE: f32 = 1.0;
     // value+amount per good
-    let mut missing_goods: Vec<(GoodIndex, (f32, f32))> = site
-        .economy
+    let mut missing_goods: Vec<(GoodIndex, (f32, f32))> = economy
         .surplus
         .iter()
         .filter(|(g, a)| (**a < 0.0 && *g != *TRANSPORTATION_INDEX))
         .map(|(g, a)| {
             (
                 g,
-                (
-                    site.economy.values[g].unwrap_or(Economy::MINIMUM_PRICE),
-                    -*a,
-                ),
+                (economy.values[g].unwrap_or(Economy::MINIMUM_PRICE), -*a),
             )
         })
         .collect();
     missing_goods.sort_by(|a, b| b.1.0.partial_cmp(&a.1.0).unwrap_or(Less));
     let mut extra_goods: GoodMap<f32> = GoodMap::from_iter(
-        site.economy
+        economy
             .surplus
             .iter()
             .chain(core::iter::once((
                 *COIN_INDEX,
-                &site.economy.stocks[*COIN_INDEX],
+                &economy.stocks[*COIN_INDEX],
             )))
             .filter(|(g, a)| (**a > 0.0 && *g != *TRANSPORTATION_INDEX))
             .map(|(g, a)| (g, *a)),
@@ -347,8 +446,7 @@ fn plan_trade_for_site(
     );
     // ratio+price per good and site
     type GoodRatioPrice = Vec<(GoodIndex, (f32, f32))>;
-    let good_payment: DHashMap<Id<Site>, GoodRatioPrice> = site
-        .economy
+    let good_payment: DHashMap<Id<Site>, GoodRatioPrice> = economy
         .neighbors
         .iter()
         .map(|n| {
@@ -361,7 +459,7 @@ fn plan_trade_for_site(
                         g,
                         (
                             last_val
-                                / site.economy.values[g]
+                                / economy.values[g]
                                     .unwrap_or(-1.0)
                                     .max(Economy::MINIMUM_PRICE),
                             last_val,
@@ -379,8 +477,7 @@ fn plan_trade_for_site(
         .iter()
         .map(|(g, _)| {
             (*g, {
-                let mut neighbor_prices: Vec<(Id<Site>, (f32, f32))> = site
-                    .economy
+                let mut neighbor_prices: Vec<(Id<Site>, (f32, f32))> = economy
                     .neighbors
                     .iter()
                     .filter(|n| n.last_supplies[*g] > 0.0)
@@ -393,8 +490,7 @@ fn plan_trade_for_site(
         .collect();
     // TODO: we need to introduce priority (according to available transportation
     // capacity)
-    let mut neighbor_orders: DHashMap<Id<Site>, GoodMap<f32>> = site
-        .economy
+    let mut neighbor_orders: DHashMap<Id<Site>, GoodMap<f32>> = economy
         .neighbors
         .iter()
         .map(|n| (n.id, GoodMap::default()))
@@ -404,7 +500,7 @@ fn plan_trade_for_site(
         debug!(
             "Site {} #neighbors {} Transport capacity {}",
             site_id.id(),
-            site.economy.neighbors.len(),
+            economy.neighbors.len(),
             transportation_capacity,
         );
         debug!("missing {:#?} extra {:#?}", missing_goods, extra_goods,);
@@ -496,7 +592,7 @@ fn plan_trade_for_site(
     //     info!("orders {:#?}", neighbor_orders,);
     // }
     // TODO: Use planned orders and calculate value, stock etc. accordingly
-    for n in &site.economy.neighbors {
+    for n in &economy.neighbors {
         if let Some(orders) = neighbor_orders.get(&n.id) {
             for (g, a) in orders.iter() {
                 result[g] += *a;
@@ -685,13 +781,13 @@ fn trade_at_site(
 
 /// 3rd step of trading
 fn collect_deliveries(
-    site: &mut Site,
+    economy: &mut Economy,
     deliveries: &mut Vec<TradeDelivery>,
     ctx: &mut vergleich::Context,
 ) {
     // collect all the goods we shipped
     let mut last_exports = GoodMap::from_iter(
-        site.economy
+        economy
             .active_exports
             .iter()
             .filter(|(_g, a)| **a > 0.0)
@@ -705,12 +801,7 @@ fn collect_deliveries(
             last_exports[i.0] -= ictx.value(&format!("{:?}", i.0), *i.1);
         }
         // remember price
-        if let Some(n) = site
-            .economy
-            .neighbors
-            .iter_mut()
-            .find(|n| n.id == d.supplier)
-        {
+        if let Some(n) = economy.neighbors.iter_mut().find(|n| n.id == d.supplier) {
             // remember (and consume) last values
             std::mem::swap(&mut n.last_values, &mut d.prices);
             std::mem::swap(&mut n.last_supplies, &mut d.supply);
@@ -720,7 +811,7 @@ fn collect_deliveries(
                     // likely rounding error, ignore
                     debug!("Unexpected delivery for {:?} {}", g, *a);
                 } else {
-                    site.economy.stocks[g] += *a;
+                    economy.stocks[g] += *a;
                 }
             }
         }
@@ -729,8 +820,8 @@ fn collect_deliveries(
         info!("non empty deliveries {:?}", deliveries);
         deliveries.clear();
     }
-    std::mem::swap(&mut last_exports, &mut site.economy.last_exports);
-    //site.economy.active_exports.clear();
+    std::mem::swap(&mut last_exports, &mut economy.last_exports);
+    //economy.active_exports.clear();
 }
 
 /// Simulate a site's economy. This simulation is roughly equivalent to the
@@ -751,26 +842,23 @@ fn collect_deliveries(
 /// through a mechanism such as trade, an entire arm of the economy may
 /// materialise to take advantage of this.
 pub fn tick_site_economy(
-    index: &mut Index,
     site_id: Id<Site>,
+    economy: &mut Economy,
+    trade_info: &mut TradeInformation,
+    time: f32,
     dt: f32,
     mut vc: vergleich::Context,
 ) {
-    let site = &mut index.sites[site_id];
-    if !site.do_economic_simulation() {
-        return;
-    }
-
     // collect goods from trading
     if INTER_SITE_TRADE {
-        let deliveries = index.trade.deliveries.get_mut(&site_id);
+        let deliveries = trade_info.deliveries.get_mut(&site_id);
         if let Some(deliveries) = deliveries {
-            collect_deliveries(site, deliveries, &mut vc);
+            collect_deliveries(economy, deliveries, &mut vc);
         }
     }
 
-    let orders = site.economy.get_orders();
-    let productivity = site.economy.get_productivity();
+    let orders = economy.get_orders();
+    let productivity = economy.get_productivity();
 
     for i in productivity.iter() {
         vc.context("productivity")
@@ -780,10 +868,10 @@ pub fn tick_site_economy(
     let mut demand = GoodMap::from_default(0.0);
     for (labor, orders) in &orders {
         let workers = if let Some(labor) = labor {
-            site.economy.labors[*labor]
+            economy.labors[*labor]
         } else {
             1.0
-        } * site.economy.pop;
+        } * economy.pop;
         for (good, amount) in orders {
             demand[*good] += *amount * workers;
         }
@@ -798,17 +886,16 @@ pub fn tick_site_economy(
         .find(|(_, v)| v.0 == *TRANSPORTATION_INDEX)
         .map(|(l, _)| l);
 
-    let mut supply = site.economy.stocks; //GoodMap::from_default(0.0);
+    let mut supply = economy.stocks; //GoodMap::from_default(0.0);
     for (labor, goodvec) in productivity.iter() {
         //for (output_good, _) in goodvec.iter() {
         //info!("{} supply{:?}+={}", site_id.id(), Good::from(goodvec.0),
-        // site.economy.yields[labor] * site.economy.labors[labor] * site.economy.pop);
-        supply[goodvec.0] +=
-            site.economy.yields[labor] * site.economy.labors[labor] * site.economy.pop;
+        // economy.yields[labor] * economy.labors[labor] * economy.pop);
+        supply[goodvec.0] += economy.yields[labor] * economy.labors[labor] * economy.pop;
         vc.context(&std::format!("{:?}-{:?}", Good::from(goodvec.0), labor))
-            .value("yields", site.economy.yields[labor]);
+            .value("yields", economy.yields[labor]);
         vc.context(&std::format!("{:?}-{:?}", Good::from(goodvec.0), labor))
-            .value("labors", site.economy.labors[labor]);
+            .value("labors", economy.labors[labor]);
         //}
     }
 
@@ -817,29 +904,29 @@ pub fn tick_site_economy(
             .value(&std::format!("{:?}", Good::from(i.0)), *i.1);
     }
 
-    let stocks = &site.economy.stocks;
+    let stocks = &economy.stocks;
     for i in stocks.iter() {
         vc.context("stocks")
             .value(&std::format!("{:?}", Good::from(i.0)), *i.1);
     }
-    site.economy.surplus = demand.map(|g, demand| supply[g] + stocks[g] - demand);
-    site.economy.marginal_surplus = demand.map(|g, demand| supply[g] - demand);
+    economy.surplus = demand.map(|g, demand| supply[g] + stocks[g] - demand);
+    economy.marginal_surplus = demand.map(|g, demand| supply[g] - demand);
 
     // plan trading with other sites
-    let external_orders = &mut index.trade.orders;
+    let external_orders = &mut trade_info.orders;
     let mut potential_trade = GoodMap::from_default(0.0);
     // use last year's generated transportation for merchants (could we do better?
     // this is in line with the other professions)
-    let transportation_capacity = site.economy.stocks[*TRANSPORTATION_INDEX];
+    let transportation_capacity = economy.stocks[*TRANSPORTATION_INDEX];
     let trade = if INTER_SITE_TRADE {
         let trade = plan_trade_for_site(
-            site,
+            economy,
             &site_id,
             transportation_capacity,
             external_orders,
             &mut potential_trade,
         );
-        site.economy.active_exports = GoodMap::from_iter(trade.iter().map(|(g, a)| (g, -*a)), 0.0); // TODO: check for availability?
+        economy.active_exports = GoodMap::from_iter(trade.iter().map(|(g, a)| (g, -*a)), 0.0); // TODO: check for availability?
 
         // add the wares to sell to demand and the goods to buy to supply
         for (g, a) in trade.iter() {
@@ -862,10 +949,10 @@ pub fn tick_site_economy(
     // Note that values are used for workforce allocation and are not the same thing
     // as price
     // fall back to old (less wrong than other goods) coin logic
-    let old_coin_surplus = site.economy.stocks[*COIN_INDEX] - demand[*COIN_INDEX];
-    let values = &mut site.economy.values;
+    let old_coin_surplus = economy.stocks[*COIN_INDEX] - demand[*COIN_INDEX];
+    let values = &mut economy.values;
 
-    site.economy.surplus.iter().for_each(|(good, surplus)| {
+    economy.surplus.iter().for_each(|(good, surplus)| {
         let old_surplus = if good == *COIN_INDEX {
             old_coin_surplus
         } else {
@@ -905,13 +992,13 @@ pub fn tick_site_economy(
                     all_trade_goods
                         .iter()
                         .chain(std::iter::once(&goodvec.0))
-                        .map(|&output_good| site.economy.values[output_good].unwrap_or(0.0))
+                        .map(|&output_good| economy.values[output_good].unwrap_or(0.0))
                         .max_by(|a, b| a.abs().partial_cmp(&b.abs()).unwrap_or(Less))
                 } else {
-                    site.economy.values[goodvec.0]
+                    economy.values[goodvec.0]
                 }
                 .unwrap_or(0.0)
-                    * site.economy.productivity[labor],
+                    * economy.productivity[labor],
             )
         }),
         0.0,
@@ -922,23 +1009,23 @@ pub fn tick_site_economy(
     let mut labor_context = vc.context("labor");
     productivity.iter().for_each(|(labor, _)| {
         let smooth = 0.8;
-        site.economy.labors[labor] = labor_context.value(
+        economy.labors[labor] = labor_context.value(
             &format!("{:?}", labor),
-            smooth * site.economy.labors[labor]
+            smooth * economy.labors[labor]
                 + (1.0 - smooth)
                     * (labor_ratios[labor].max(labor_ratio_sum / 1000.0) / labor_ratio_sum),
         );
-        assert!(site.economy.labors[labor] >= 0.0);
+        assert!(economy.labors[labor] >= 0.0);
     });
 
     // Production
-    let stocks_before = site.economy.stocks;
+    let stocks_before = economy.stocks;
     // TODO: Should we recalculate demand after labor reassignment?
 
     let direct_use = direct_use_goods();
     // Handle the stocks you can't pile (decay)
     for g in direct_use {
-        site.economy.stocks[*g] = 0.0;
+        economy.stocks[*g] = 0.0;
     }
 
     let mut total_labor_values = GoodMap::<f32>::default();
@@ -946,10 +1033,10 @@ pub fn tick_site_economy(
     let mut total_outputs = GoodMap::<f32>::default();
     for (labor, orders) in orders.iter() {
         let workers = if let Some(labor) = labor {
-            site.economy.labors[*labor]
+            economy.labors[*labor]
         } else {
             1.0
-        } * site.economy.pop;
+        } * economy.pop;
         assert!(workers >= 0.0);
         let is_merchant = merchant_labor == *labor;
 
@@ -981,11 +1068,11 @@ pub fn tick_site_economy(
             let used = quantity * labor_productivity;
 
             // Material cost of each factor of production
-            total_materials_cost += used * site.economy.labor_values[*good].unwrap_or(0.0);
+            total_materials_cost += used * economy.labor_values[*good].unwrap_or(0.0);
 
             // Deplete stocks accordingly
             if !direct_use.contains(good) {
-                site.economy.stocks[*good] = (site.economy.stocks[*good] - used).max(0.0);
+                economy.stocks[*good] = (economy.stocks[*good] - used).max(0.0);
             }
         }
         let mut produced_goods: GoodMap<f32> = GoodMap::from_default(0.0);
@@ -995,12 +1082,12 @@ pub fn tick_site_economy(
                 if !direct_use.contains(&g) {
                     if *a < 0.0 {
                         // take these goods to the road
-                        if site.economy.stocks[g] + *a < 0.0 {
+                        if economy.stocks[g] + *a < 0.0 {
                             // we have a problem: Probably due to a shift in productivity we have
                             // less goods available than planned,
                             // so we would need to reduce the amount shipped
-                            debug!("NEG STOCK {:?} {} {}", g, site.economy.stocks[g], *a);
-                            let reduced_amount = site.economy.stocks[g];
+                            debug!("NEG STOCK {:?} {} {}", g, economy.stocks[g], *a);
+                            let reduced_amount = economy.stocks[g];
                             let planned_amount: f32 = external_orders
                                 .iter()
                                 .map(|i| {
@@ -1017,14 +1104,14 @@ pub fn tick_site_economy(
                                     l.amount[g] *= scale;
                                 }
                             }
-                            site.economy.stocks[g] = 0.0;
+                            economy.stocks[g] = 0.0;
                         }
-                        //                    assert!(site.economy.stocks[g] + *a >= 0.0);
+                        //                    assert!(economy.stocks[g] + *a >= 0.0);
                         else {
-                            site.economy.stocks[g] += *a;
+                            economy.stocks[g] += *a;
                         }
                     }
-                    total_materials_cost += (-*a) * site.economy.labor_values[g].unwrap_or(0.0);
+                    total_materials_cost += (-*a) * economy.labor_values[g].unwrap_or(0.0);
                 } else {
                     // count on receiving these
                     produced_goods[g] += *a;
@@ -1033,7 +1120,7 @@ pub fn tick_site_economy(
             debug!(
                 "merchant {} {}: {:?} {} {:?}",
                 site_id.id(),
-                site.economy.pop,
+                economy.pop,
                 produced_goods,
                 total_materials_cost,
                 trade
@@ -1043,16 +1130,16 @@ pub fn tick_site_economy(
         // Industries produce things
         if let Some(labor) = labor {
             let work_products = &productivity[*labor];
-            //let workers = site.economy.labors[*labor] * site.economy.pop;
+            //let workers = economy.labors[*labor] * economy.pop;
             //let final_rate = rate;
             //let yield_per_worker = labor_productivity;
-            site.economy.yields[*labor] = labor_productivity * work_products.1;
-            site.economy.productivity[*labor] = labor_productivity;
+            economy.yields[*labor] = labor_productivity * work_products.1;
+            economy.productivity[*labor] = labor_productivity;
             //let total_product_rate: f32 = work_products.iter().map(|(_, r)| *r).sum();
             let (stock, rate) = work_products;
             let total_output = labor_productivity * *rate * workers;
             assert!(total_output >= 0.0);
-            site.economy.stocks[*stock] += total_output;
+            economy.stocks[*stock] += total_output;
             produced_goods[*stock] += total_output;
 
             let produced_amount: f32 = produced_goods.iter().map(|(_, a)| *a).sum();
@@ -1061,7 +1148,7 @@ pub fn tick_site_economy(
                 // Materials cost per unit
                 // TODO: How to handle this reasonably for multiple producers (collect upper and
                 // lower term separately)
-                site.economy.material_costs[stock] =
+                economy.material_costs[stock] =
                     total_materials_cost / amount.max(0.001) * cost_weight;
                 // Labor costs
                 let wages = 1.0;
@@ -1075,7 +1162,7 @@ pub fn tick_site_economy(
     }
 
     // Update labour values per unit
-    site.economy.labor_values = total_labor_values.map(|stock, tlv| {
+    economy.labor_values = total_labor_values.map(|stock, tlv| {
         let total_output = total_outputs[stock];
         if total_output > 0.01 {
             Some(tlv / total_output)
@@ -1085,46 +1172,43 @@ pub fn tick_site_economy(
     });
 
     // Decay stocks (the ones which totally decay are handled later)
-    site.economy
+    economy
         .stocks
         .iter_mut()
         .map(|(c, v)| (v, 1.0 - decay_rate(c)))
         .for_each(|(v, factor)| *v *= factor);
 
     // Decay stocks
-    site.economy.replenish(index.time);
+    economy.replenish(time);
 
     // Births/deaths
     const NATURAL_BIRTH_RATE: f32 = 0.05;
     const DEATH_RATE: f32 = 0.005;
-    let birth_rate = if site.economy.surplus[*FOOD_INDEX] > 0.0 {
+    let birth_rate = if economy.surplus[*FOOD_INDEX] > 0.0 {
         NATURAL_BIRTH_RATE
     } else {
         0.0
     };
-    site.economy.pop += vc.value(
-        "pop",
-        dt / YEAR * site.economy.pop * (birth_rate - DEATH_RATE),
-    );
+    economy.pop += vc.value("pop", dt / YEAR * economy.pop * (birth_rate - DEATH_RATE));
 
     // calculate the new unclaimed stock
-    //let next_orders = site.economy.get_orders();
+    //let next_orders = economy.get_orders();
     // orders are static
     let mut next_demand = GoodMap::from_default(0.0);
     for (labor, orders) in orders.iter() {
         let workers = if let Some(labor) = labor {
-            site.economy.labors[*labor]
+            economy.labors[*labor]
         } else {
             1.0
-        } * site.economy.pop;
+        } * economy.pop;
         for (good, amount) in orders {
             next_demand[*good] += *amount * workers;
             assert!(next_demand[*good] >= 0.0);
         }
     }
     let mut us = vc.context("unconsumed");
-    site.economy.unconsumed_stock = GoodMap::from_iter(
-        site.economy.stocks.iter().map(|(g, a)| {
+    economy.unconsumed_stock = GoodMap::from_iter(
+        economy.stocks.iter().map(|(g, a)| {
             (
                 g,
                 us.value(&format!("{:?}", Good::from(g)), *a - next_demand[g]),
@@ -1136,8 +1220,13 @@ pub fn tick_site_economy(
 
 #[cfg(test)]
 mod tests {
-    use crate::{sim, site::economy::GoodMap, util::seed_expan};
-    use common::trade::Good;
+    use super::{RuntimeEconomy, TICK_PERIOD};
+    use crate::{
+        sim,
+        site::economy::{GoodIndex, GoodMap},
+        util::seed_expan,
+    };
+    use common::{store::Id, terrain::BiomeKind, trade::Good};
     use rand::SeedableRng;
     use rand_chacha::ChaChaRng;
     use serde::{Deserialize, Serialize};
@@ -1297,4 +1386,75 @@ mod tests {
         }
         crate::sim2::simulate(&mut index, &mut sim);
     }
+
+    fn close(a: f32, b: f32) -> bool { (a - b).abs() <= 1e-4 * a.abs().max(1.0) }
+
+    /// An index with a single settlement, which has some natural resources
+    fn runtime_economy() -> (crate::index::Index, RuntimeEconomy, Id<crate::site::Site>) {
+        let mut rng = ChaChaRng::from_seed(seed_expan::rng_state(1));
+        let mut index = crate::index::Index::new(1);
+        let mut settlement = crate::site::Site::settlement(crate::site::Settlement::generate(
+            Vec2::zero(),
+            None,
+            &mut rng,
+        ));
+        for good in [
+            Good::Terrain(BiomeKind::Grassland),
+            Good::Terrain(BiomeKind::Forest),
+        ] {
+            let good: GoodIndex = good.try_into().unwrap_or_default();
+            settlement.economy.natural_resources.chunks_per_resource[good] = 50.0;
+            settlement.economy.natural_resources.average_yield_per_chunk[good] = 1.0;
+        }
+        let site = index.sites.insert(settlement);
+        let economy = RuntimeEconomy::new(&index);
+        (index, economy, site)
+    }
+
+    #[test]
+    fn runtime_trades_change_stocks() {
+        let (index, mut economy, site) = runtime_economy();
+        let wood: GoodIndex = Good::Wood.try_into().unwrap_or_default();
+        let coin: GoodIndex = Good::Coin.try_into().unwrap_or_default();
+        let stock = economy.economy(site.id()).unwrap().stocks[wood];
+
+        economy.record_trade(site.id(), vec![
+            (Good::Wood, 10.0),
+            (Good::Coin, -1.0e9),
+            // Not traded between sites
+            (Good::Terrain(BiomeKind::Void), 5.0),
+        ]);
+        let traded = economy.economy(site.id()).unwrap();
+        assert!(close(traded.stocks[wood], stock + 10.0));
+        // Sites can't give away more than they have
+        assert!(close(traded.stocks[coin], 0.0));
+        // Unknown sites are ignored
+        economy.record_trade(site.id() + 1, vec![(Good::Wood, 10.0)]);
+        assert!(economy.economy(site.id() + 1).is_none());
+
+        // The economy starts over from worldgen when the server restarts
+        let restarted = RuntimeEconomy::new(&index);
+        assert!(close(
+            restarted.economy(site.id()).unwrap().stocks[wood],
+            stock
+        ));
+    }
+
+    #[test]
+    fn runtime_economy_ticks_every_period() {
+        let (_, mut economy, site) = runtime_economy();
+        let time = economy.time;
+
+        // Nothing happens until a full period has passed
+        economy.simulate(TICK_PERIOD * 0.5);
+        assert!(close(economy.time, time));
+        economy.simulate(TICK_PERIOD * 0.6);
+        assert!(close(economy.time, time + TICK_PERIOD));
+        assert!(close(economy.pending_days, TICK_PERIOD * 0.1));
+
+        // Several ticks at once when the server falls behind
+        economy.simulate(TICK_PERIOD * 2.0);
+        assert!(close(economy.time, time + TICK_PERIOD * 3.0));
+        assert!(economy.get_site_prices(site.id()).is_some());
+    }
 }
@@ -274,14 +274,14 @@ impl<V: Copy + Default> LaborMap<V> {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct AreaResources {
     pub resource_sum: GoodMap<f32>,
     pub resource_chunks: GoodMap<f32>,
     pub chunks: u32,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct NaturalResources {
     // resources per distance, we should increase labor cost for far resources
     pub per_area: Vec<AreaResources>,
@@ -349,13 +349,13 @@ impl Default for Labor {
     fn default() -> Self { *DUMMY_LABOR }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct TradeOrder {
     pub customer: Id<Site>,
     pub amount: GoodMap<f32>, // positive for orders, negative for exchange
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct TradeDelivery {
     pub supplier: Id<Site>,
     pub amount: GoodMap<f32>, // positive for orders, negative for exchange
@@ -363,13 +363,13 @@ pub struct TradeDelivery {
     pub supply: GoodMap<f32>, // maximum amount available, at the time of interaction
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct TradeInformation {
     pub orders: DHashMap<Id<Site>, Vec<TradeOrder>>, // per provider
     pub deliveries: DHashMap<Id<Site>, Vec<TradeDelivery>>, // per receiver
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct NeighborInformation {
     pub id: Id<Site>,
     pub travel_distance: usize,
@@ -379,7 +379,7 @@ pub struct NeighborInformation {
     pub last_supplies: GoodMap<f32>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Economy {
     // Population
     pub pop: f32,